@@ -1,7 +1,8 @@
-use std::{path::PathBuf, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use log::error;
 use opcua_core::config::{Config, ConfigError};
+use opcua_crypto::CertificateStoreBackend;
 
 use super::{Client, ClientConfig, ClientEndpoint, ClientUserToken, ANONYMOUS_USER_TOKEN_ID};
 
@@ -9,6 +10,7 @@ use super::{Client, ClientConfig, ClientEndpoint, ClientUserToken, ANONYMOUS_USE
 /// Client builder.
 pub struct ClientBuilder {
     config: ClientConfig,
+    certificate_store_backend: Option<Arc<dyn CertificateStoreBackend>>,
 }
 
 impl ClientBuilder {
@@ -21,6 +23,7 @@ impl ClientBuilder {
     pub fn from_config(path: impl Into<PathBuf>) -> Result<ClientBuilder, ConfigError> {
         Ok(ClientBuilder {
             config: ClientConfig::load(&path.into())?,
+            certificate_store_backend: None,
         })
     }
 
@@ -35,7 +38,10 @@ impl ClientBuilder {
             }
             Err(e)
         } else {
-            Ok(Client::new(self.config))
+            Ok(match self.certificate_store_backend {
+                Some(backend) => Client::new_with_certificate_store_backend(self.config, backend),
+                None => Client::new(self.config),
+            })
         }
     }
 
@@ -94,8 +100,8 @@ impl ClientBuilder {
 
     /// Sets whether the client should automatically trust servers. If this is not set then
     /// the client will reject the server upon first connect and the server's certificate
-    /// must be manually moved from pki's `/rejected` folder to the `/trusted` folder. If it is
-    /// set, then the server cert will automatically be stored in the `/trusted` folder.
+    /// must be manually moved from pki's `/rejected` folder to the `/trusted/certs` folder. If it is
+    /// set, then the server cert will automatically be stored in the `/trusted/certs` folder.
     pub fn trust_server_certs(mut self, trust_server_certs: bool) -> Self {
         self.config.trust_server_certs = trust_server_certs;
         self
@@ -110,13 +116,29 @@ impl ClientBuilder {
         self
     }
 
-    /// Sets the pki directory where client's own key pair is stored and where `/trusted` and
+    /// Sets whether the client should accept server certificates issued by a CA that has
+    /// no CRL, or only an out of date CRL, in the trusted or issuer lists. By default such
+    /// certificates are rejected, since they cannot be checked for revocation.
+    pub fn allow_missing_crls(mut self, allow_missing_crls: bool) -> Self {
+        self.config.allow_missing_crls = allow_missing_crls;
+        self
+    }
+
+    /// Sets the pki directory where client's own key pair is stored and where `/trusted/certs` and
     /// `/rejected` server certificates are stored.
     pub fn pki_dir(mut self, pki_dir: impl Into<PathBuf>) -> Self {
         self.config.pki_dir = pki_dir.into();
         self
     }
 
+    /// Sets a custom backend for storing the client's own key pair and the trusted and rejected
+    /// server certificates. If this is set, `pki_dir`, `certificate_path` and `private_key_path`
+    /// are ignored.
+    pub fn certificate_store_backend(mut self, backend: Arc<dyn CertificateStoreBackend>) -> Self {
+        self.certificate_store_backend = Some(backend);
+        self
+    }
+
     /// Sets the preferred locales of the client. These are passed to the server during session
     /// creation to ensure localized strings are in the preferred language.
     pub fn preferred_locales(mut self, preferred_locales: Vec<String>) -> Self {
@@ -306,4 +328,14 @@ impl ClientBuilder {
         self.config.session_timeout = session_timeout;
         self
     }
+
+    /// Encrypt passwords with RSA security policies in the `RsaEncryptedSecret` format
+    /// defined in OPC UA 1.05, instead of the legacy format.
+    ///
+    /// Defaults to `false`, since servers implementing earlier versions of the standard
+    /// only support the legacy format.
+    pub fn rsa_encrypted_secret(mut self, rsa_encrypted_secret: bool) -> Self {
+        self.config.rsa_encrypted_secret = rsa_encrypted_secret;
+        self
+    }
 }
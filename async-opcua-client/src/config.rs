@@ -238,6 +238,10 @@ pub struct ClientConfig {
     /// Verify server certificates. For testing/samples only unless you're sure what you're
     /// doing.
     pub(crate) verify_server_certs: bool,
+    /// Accept server certificates issued by a CA without a CRL, or with an out of
+    /// date CRL, whose revocation status is unknown.
+    #[serde(default)]
+    pub(crate) allow_missing_crls: bool,
     /// PKI folder, either absolute or relative to executable
     pub(crate) pki_dir: PathBuf,
     /// Preferred locales
@@ -298,6 +302,11 @@ pub struct ClientConfig {
     /// Requested session timeout in milliseconds
     #[serde(default = "defaults::session_timeout")]
     pub(crate) session_timeout: u32,
+    /// Encrypt passwords with RSA security policies in the `RsaEncryptedSecret` format
+    /// instead of the legacy format. Passwords are always encrypted in the
+    /// `EccEncryptedSecret` format with ECC security policies.
+    #[serde(default)]
+    pub(crate) rsa_encrypted_secret: bool,
 }
 
 impl Config for ClientConfig {
@@ -573,6 +582,7 @@ impl ClientConfig {
             private_key_path: None,
             trust_server_certs: false,
             verify_server_certs: defaults::verify_server_certs(),
+            allow_missing_crls: false,
             pki_dir,
             preferred_locales: Vec::new(),
             default_endpoint: String::new(),
@@ -592,6 +602,7 @@ impl ClientConfig {
             recreate_subscriptions: defaults::recreate_subscriptions(),
             session_name: "Rust OPC UA Client".into(),
             session_timeout: defaults::session_timeout(),
+            rsa_encrypted_secret: false,
         }
     }
 }
@@ -0,0 +1,452 @@
+//! This module contains a client for the pull certificate management model of a
+//! Global Discovery Server (GDS), defined in OPC UA Part 12.
+//!
+//! [`GdsClient`] wraps a [`Session`] connected to the GDS and calls the methods of its
+//! `Directory` object, with typed arguments and results. The methods are resolved by
+//! browse name when the client is created, so it works with any GDS that implements
+//! `DirectoryType` and `CertificateDirectoryType`.
+//!
+//! A typical enrolment looks like this:
+//!
+//!  1. Register the application with [`GdsClient::register_application`], which returns
+//!     the application ID assigned by the GDS.
+//!  2. Send a signing request for the certificate in the client's certificate store with
+//!     [`GdsClient::request_certificate`].
+//!  3. Call [`GdsClient::finish_certificate_request`] until it returns `true`, at which point
+//!     the issued certificate has been written to the certificate store.
+//!  4. Write the trust list of the application into the certificate store with
+//!     [`GdsClient::update_trust_list`].
+//!
+//! The issued certificate is used by secure channels opened after it has been written to
+//! the certificate store, see [`Client::set_client_certificate`](crate::Client::set_client_certificate).
+
+use opcua_core::sync::RwLock;
+use opcua_crypto::{CertificateListKind, CertificateStore, KeyType, PrivateKey, X509};
+use opcua_types::{
+    gds::{ApplicationRecordDataType, GDS_NAMESPACE_URI},
+    BinaryDecodable, BrowsePath, ByteString, CallMethodRequest, ExtensionObject, NodeId, ObjectId,
+    QualifiedName, StatusCode, TrustListDataType, TryFromVariant, Variant,
+};
+
+use crate::Session;
+
+/// The number of bytes requested from the trust list file in each `Read` call.
+const TRUST_LIST_READ_CHUNK_SIZE: i32 = 65536;
+
+/// The `Read` mode of the `Open` method on `FileType`.
+const FILE_MODE_READ: u8 = 1;
+
+/// A certificate issued by the GDS, returned by `FinishRequest`.
+#[derive(Debug, Clone)]
+pub struct IssuedCertificate {
+    /// The DER encoded certificate.
+    pub certificate: ByteString,
+    /// The private key, if the GDS generated the key pair. This is a PEM or PFX file,
+    /// depending on the format requested.
+    pub private_key: ByteString,
+    /// The DER encoded certificates of the issuers of the certificate.
+    pub issuer_certificates: Vec<ByteString>,
+}
+
+/// The node IDs of the methods on the GDS `Directory` object. Methods the GDS does not
+/// expose are `None`.
+#[derive(Debug, Default)]
+struct DirectoryMethods {
+    register_application: Option<NodeId>,
+    start_signing_request: Option<NodeId>,
+    finish_request: Option<NodeId>,
+    get_trust_list: Option<NodeId>,
+    get_certificate_status: Option<NodeId>,
+}
+
+/// Client for the `Directory` object of a Global Discovery Server.
+pub struct GdsClient<'a> {
+    session: &'a Session,
+    directory_id: NodeId,
+    methods: DirectoryMethods,
+}
+
+impl<'a> GdsClient<'a> {
+    /// Create a new GDS client on `session`, which must be connected to a GDS.
+    /// This looks up the `Directory` object and its methods on the server.
+    ///
+    /// # Errors
+    ///
+    /// `BadNotSupported` if the server does not have the GDS namespace or a `Directory`
+    /// object, or the status code of a failed request.
+    pub async fn new(session: &'a Session) -> Result<Self, StatusCode> {
+        let namespace = session
+            .get_namespace_index(GDS_NAMESPACE_URI)
+            .await
+            .map_err(|_| StatusCode::BadNotSupported)?;
+        let directory = QualifiedName::new(namespace, "Directory");
+        let method_names = [
+            "RegisterApplication",
+            "StartSigningRequest",
+            "FinishRequest",
+            "GetTrustList",
+            "GetCertificateStatus",
+        ];
+
+        let mut paths = vec![BrowsePath {
+            starting_node: ObjectId::ObjectsFolder.into(),
+            relative_path: [directory.clone()][..].into(),
+        }];
+        paths.extend(method_names.iter().map(|name| BrowsePath {
+            starting_node: ObjectId::ObjectsFolder.into(),
+            relative_path: [directory.clone(), QualifiedName::new(namespace, *name)][..].into(),
+        }));
+        let mut targets = Self::resolve_paths(session, &paths).await?.into_iter();
+
+        let Some(directory_id) = targets.next().flatten() else {
+            return Err(StatusCode::BadNotSupported);
+        };
+        let mut methods = DirectoryMethods::default();
+        for (target, method_id) in [
+            &mut methods.register_application,
+            &mut methods.start_signing_request,
+            &mut methods.finish_request,
+            &mut methods.get_trust_list,
+            &mut methods.get_certificate_status,
+        ]
+        .into_iter()
+        .zip(targets)
+        {
+            *target = method_id;
+        }
+
+        Ok(Self {
+            session,
+            directory_id,
+            methods,
+        })
+    }
+
+    /// Translate `paths`, returning the first target of each path, if any.
+    async fn resolve_paths(
+        session: &Session,
+        paths: &[BrowsePath],
+    ) -> Result<Vec<Option<NodeId>>, StatusCode> {
+        let results = session.translate_browse_paths_to_node_ids(paths).await?;
+        Ok(results
+            .into_iter()
+            .map(|r| {
+                r.targets
+                    .and_then(|t| t.into_iter().next())
+                    .filter(|t| t.target_id.server_index == 0)
+                    .map(|t| t.target_id.node_id)
+            })
+            .collect())
+    }
+
+    /// Call `method_id` on `object_id`, returning the output arguments.
+    async fn call(
+        &self,
+        object_id: &NodeId,
+        method_id: Option<&NodeId>,
+        input_arguments: Vec<Variant>,
+    ) -> Result<Vec<Variant>, StatusCode> {
+        let Some(method_id) = method_id else {
+            return Err(StatusCode::BadMethodInvalid);
+        };
+        let result = self
+            .session
+            .call_one(CallMethodRequest {
+                object_id: object_id.clone(),
+                method_id: method_id.clone(),
+                input_arguments: Some(input_arguments),
+            })
+            .await?;
+        if result.status_code.is_bad() {
+            return Err(result.status_code);
+        }
+        Ok(result.output_arguments.unwrap_or_default())
+    }
+
+    /// Call a method on the `Directory` object.
+    async fn call_directory(
+        &self,
+        method_id: Option<&NodeId>,
+        input_arguments: Vec<Variant>,
+    ) -> Result<Vec<Variant>, StatusCode> {
+        self.call(&self.directory_id, method_id, input_arguments)
+            .await
+    }
+
+    /// Register an application with the GDS, returning the application ID assigned to it.
+    /// The `application_id` of `application` should be null.
+    pub async fn register_application(
+        &self,
+        application: ApplicationRecordDataType,
+    ) -> Result<NodeId, StatusCode> {
+        let outputs = self
+            .call_directory(
+                self.methods.register_application.as_ref(),
+                vec![ExtensionObject::from_message(application).into()],
+            )
+            .await?;
+        let [application_id] = outputs_as(outputs)?;
+        Ok(application_id)
+    }
+
+    /// Ask the GDS to sign the DER encoded PKCS#10 `certificate_request`, returning
+    /// the ID of the request. A null `certificate_group_id` or `certificate_type_id`
+    /// selects the default application group or the default certificate type of the group.
+    pub async fn start_signing_request(
+        &self,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+        certificate_request: ByteString,
+    ) -> Result<NodeId, StatusCode> {
+        let outputs = self
+            .call_directory(
+                self.methods.start_signing_request.as_ref(),
+                vec![
+                    application_id.clone().into(),
+                    certificate_group_id.clone().into(),
+                    certificate_type_id.clone().into(),
+                    certificate_request.into(),
+                ],
+            )
+            .await?;
+        let [request_id] = outputs_as(outputs)?;
+        Ok(request_id)
+    }
+
+    /// Get the certificate issued for the request `request_id`. Returns `None` if the
+    /// request has not been approved yet.
+    pub async fn finish_request(
+        &self,
+        application_id: &NodeId,
+        request_id: &NodeId,
+    ) -> Result<Option<IssuedCertificate>, StatusCode> {
+        let result = self
+            .call_directory(
+                self.methods.finish_request.as_ref(),
+                vec![application_id.clone().into(), request_id.clone().into()],
+            )
+            .await;
+        let outputs = match result {
+            Ok(outputs) => outputs,
+            Err(StatusCode::BadNothingToDo) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let [certificate, private_key, issuer_certificates] = outputs_as::<3, Variant>(outputs)?;
+        Ok(Some(IssuedCertificate {
+            certificate: ByteString::try_from_variant(certificate)?,
+            private_key: <Option<ByteString>>::try_from_variant(private_key)?.unwrap_or_default(),
+            issuer_certificates: <Option<Vec<ByteString>>>::try_from_variant(issuer_certificates)?
+                .unwrap_or_default(),
+        }))
+    }
+
+    /// Get the node ID of the trust list of the certificate group `certificate_group_id`
+    /// of an application. This is a `TrustListType` file object, which can be read with
+    /// [`GdsClient::read_trust_list`].
+    pub async fn get_trust_list(
+        &self,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+    ) -> Result<NodeId, StatusCode> {
+        let outputs = self
+            .call_directory(
+                self.methods.get_trust_list.as_ref(),
+                vec![
+                    application_id.clone().into(),
+                    certificate_group_id.clone().into(),
+                ],
+            )
+            .await?;
+        let [trust_list_id] = outputs_as(outputs)?;
+        Ok(trust_list_id)
+    }
+
+    /// Check whether the GDS requires the certificate of an application to be updated.
+    pub async fn get_certificate_status(
+        &self,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+    ) -> Result<bool, StatusCode> {
+        let outputs = self
+            .call_directory(
+                self.methods.get_certificate_status.as_ref(),
+                vec![
+                    application_id.clone().into(),
+                    certificate_group_id.clone().into(),
+                    certificate_type_id.clone().into(),
+                ],
+            )
+            .await?;
+        let [update_required] = outputs_as(outputs)?;
+        Ok(update_required)
+    }
+
+    /// Read the content of the trust list file object `trust_list_id`.
+    pub async fn read_trust_list(
+        &self,
+        trust_list_id: &NodeId,
+    ) -> Result<TrustListDataType, StatusCode> {
+        let paths: Vec<_> = ["Open", "Read", "Close"]
+            .into_iter()
+            .map(|name| BrowsePath {
+                starting_node: trust_list_id.clone(),
+                relative_path: [QualifiedName::new(0, name)][..].into(),
+            })
+            .collect();
+        let [open, read, close] = Self::resolve_paths(self.session, &paths)
+            .await?
+            .try_into()
+            .map_err(|_| StatusCode::BadUnexpectedError)?;
+
+        let outputs = self
+            .call(trust_list_id, open.as_ref(), vec![FILE_MODE_READ.into()])
+            .await?;
+        let [handle] = outputs_as::<1, u32>(outputs)?;
+
+        let mut data = Vec::new();
+        let result = loop {
+            let outputs = self
+                .call(
+                    trust_list_id,
+                    read.as_ref(),
+                    vec![handle.into(), TRUST_LIST_READ_CHUNK_SIZE.into()],
+                )
+                .await;
+            match outputs.and_then(outputs_as::<1, ByteString>) {
+                Ok([chunk]) if chunk.is_null_or_empty() => break Ok(()),
+                Ok([chunk]) => data.extend_from_slice(chunk.as_ref()),
+                Err(e) => break Err(e),
+            }
+        };
+        let closed = self
+            .call(trust_list_id, close.as_ref(), vec![handle.into()])
+            .await;
+        result?;
+        closed?;
+
+        let ctx = self.session.context();
+        let ctx = ctx.read();
+        TrustListDataType::decode(&mut data.as_slice(), &ctx.context()).map_err(|e| e.status())
+    }
+
+    /// Create a signing request for the certificate and private key in `store`, and send
+    /// it to the GDS. If `certificate_type_id` requires a key type, the certificate for that
+    /// key type is used. Returns the ID of the request, which is passed to
+    /// [`GdsClient::finish_certificate_request`].
+    pub async fn request_certificate(
+        &self,
+        store: &RwLock<CertificateStore>,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+        certificate_type_id: &NodeId,
+    ) -> Result<NodeId, StatusCode> {
+        let certificate_request = {
+            let store = store.read();
+            let (cert, pkey) = match KeyType::from_certificate_type(certificate_type_id)? {
+                Some(key_type) => (
+                    store.backend().read_own_cert_for_key_type(key_type),
+                    store.backend().read_own_pkey_for_key_type(key_type),
+                ),
+                None => (store.read_own_cert(), store.read_own_pkey()),
+            };
+            let (Ok(cert), Ok(pkey)) = (cert, pkey) else {
+                return Err(StatusCode::BadConfigurationError);
+            };
+            cert.create_signing_request(&pkey, None)
+                .map_err(|_| StatusCode::BadInternalError)?
+        };
+        self.start_signing_request(
+            application_id,
+            certificate_group_id,
+            certificate_type_id,
+            certificate_request.into(),
+        )
+        .await
+    }
+
+    /// Finish the signing request `request_id`, writing the issued certificate and its
+    /// issuers to `store`. Returns `false` if the request has not been approved yet.
+    ///
+    /// # Errors
+    ///
+    /// `BadSecurityChecksFailed` if the issued certificate does not belong to the private
+    /// key in `store`, `BadNotSupported` if the GDS returns a private key in a format
+    /// other than PEM.
+    pub async fn finish_certificate_request(
+        &self,
+        store: &RwLock<CertificateStore>,
+        application_id: &NodeId,
+        request_id: &NodeId,
+    ) -> Result<bool, StatusCode> {
+        let Some(issued) = self.finish_request(application_id, request_id).await? else {
+            return Ok(false);
+        };
+        let cert = X509::from_byte_string(&issued.certificate)?;
+        let issuers = issued
+            .issuer_certificates
+            .iter()
+            .map(X509::from_byte_string)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Hold the write lock so that no channel reads a mismatched certificate and key.
+        let store = store.write();
+        let pkey = if issued.private_key.is_null_or_empty() {
+            let key_type = cert.public_key()?.key_type();
+            store
+                .backend()
+                .read_own_pkey_for_key_type(key_type)
+                .map_err(|_| StatusCode::BadConfigurationError)?
+        } else {
+            PrivateKey::from_pem(issued.private_key.as_ref())
+                .map_err(|_| StatusCode::BadNotSupported)?
+        };
+        if !cert.matches_private_key(&pkey) {
+            return Err(StatusCode::BadSecurityChecksFailed);
+        }
+
+        let backend = store.backend();
+        backend
+            .store_own_cert_and_pkey(&cert, &pkey, true)
+            .and_then(|_| {
+                issuers
+                    .iter()
+                    .try_for_each(|c| backend.store_certificate(CertificateListKind::Issuer, c))
+            })
+            .map_err(|_| StatusCode::BadInternalError)?;
+        Ok(true)
+    }
+
+    /// Read the trust list of the certificate group `certificate_group_id` of an
+    /// application from the GDS, and replace the trusted and issuer lists in `store`
+    /// with it.
+    pub async fn update_trust_list(
+        &self,
+        store: &RwLock<CertificateStore>,
+        application_id: &NodeId,
+        certificate_group_id: &NodeId,
+    ) -> Result<(), StatusCode> {
+        let trust_list_id = self
+            .get_trust_list(application_id, certificate_group_id)
+            .await?;
+        let trust_list = self.read_trust_list(&trust_list_id).await?;
+        store.read().update_trust_list(trust_list)
+    }
+}
+
+/// Convert the output arguments of a method to `N` values of type `T`.
+fn outputs_as<const N: usize, T: TryFromVariant>(
+    outputs: Vec<Variant>,
+) -> Result<[T; N], StatusCode> {
+    let outputs: [Variant; N] = outputs
+        .try_into()
+        .map_err(|_| StatusCode::BadUnexpectedError)?;
+    let mut values = Vec::with_capacity(N);
+    for output in outputs {
+        values.push(T::try_from_variant(output).map_err(|_| StatusCode::BadTypeMismatch)?);
+    }
+    values
+        .try_into()
+        .map_err(|_| StatusCode::BadUnexpectedError)
+}
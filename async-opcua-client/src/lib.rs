@@ -117,14 +117,19 @@ pub mod browser;
 mod builder;
 mod config;
 pub mod custom_types;
+pub mod gds;
 mod retry;
 mod session;
 mod transport;
 
 use std::path::PathBuf;
 
+use opcua_types::ByteString;
+
 pub use builder::ClientBuilder;
 pub use config::{ClientConfig, ClientEndpoint, ClientUserToken, ANONYMOUS_USER_TOKEN_ID};
+#[cfg(feature = "https")]
+pub use opcua_core::comms::https::HttpsEncoding;
 pub use retry::{ExponentialBackoff, SessionRetryPolicy};
 pub use session::{
     Client, DataChangeCallback, DefaultRetryPolicy, EventCallback, HistoryReadAction,
@@ -132,7 +137,11 @@ pub use session::{
     SessionActivity, SessionBuilder, SessionConnectMode, SessionEventLoop, SessionPollResult,
     Subscription, SubscriptionActivity, SubscriptionCallbacks, UARequest,
 };
-pub use transport::AsyncSecureChannel;
+#[cfg(feature = "https")]
+pub use transport::https::HttpsConnector;
+#[cfg(feature = "websocket")]
+pub use transport::websocket::WebSocketConnector;
+pub use transport::{tcp::ReverseConnector, AsyncSecureChannel};
 
 pub mod services {
     //! This module contains request builders for most OPC-UA services.
@@ -145,8 +154,9 @@ pub mod services {
         ActivateSession, AddNodes, AddReferences, Browse, BrowseNext, Call, Cancel, CloseSession,
         CreateMonitoredItems, CreateSession, CreateSubscription, DeleteMonitoredItems, DeleteNodes,
         DeleteReferences, DeleteSubscriptions, HistoryRead, HistoryUpdate, ModifyMonitoredItems,
-        ModifySubscription, Read, RegisterNodes, SetMonitoringMode, SetPublishingMode,
-        SetTriggering, TransferSubscriptions, TranslateBrowsePaths, UnregisterNodes, Write,
+        ModifySubscription, QueryFirst, QueryNext, Read, RegisterNodes, SetMonitoringMode,
+        SetPublishingMode, SetTriggering, TransferSubscriptions, TranslateBrowsePaths,
+        UnregisterNodes, Write,
     };
 }
 
@@ -159,4 +169,7 @@ pub enum IdentityToken {
     UserName(String, String),
     /// X5090 cert - a path to the cert.der, and private.pem
     X509(PathBuf, PathBuf),
+    /// Token issued by an authorization service, for example the UTF-8 encoded
+    /// JSON Web Token returned by an OAuth2 server.
+    IssuedToken(ByteString),
 }
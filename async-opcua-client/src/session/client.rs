@@ -6,21 +6,20 @@ use tokio::{pin, select};
 
 use crate::{
     transport::{
-        tcp::{TcpConnector, TransportConfiguration},
-        TransportPollResult,
+        connector_for_endpoint, is_supported_url, tcp::TransportConfiguration, TransportPollResult,
     },
     AsyncSecureChannel, ClientConfig, ClientEndpoint, IdentityToken,
 };
 use opcua_core::{
     comms::url::{
-        hostname_from_url, is_opc_ua_binary_url, is_valid_opc_ua_url, server_url_from_endpoint_url,
+        hostname_from_url, is_valid_opc_ua_url, server_url_from_endpoint_url,
         url_matches_except_host, url_with_replaced_hostname,
     },
     config::Config,
     sync::RwLock,
-    ResponseMessage,
+    trace_write_lock, ResponseMessage,
 };
-use opcua_crypto::{CertificateStore, SecurityPolicy};
+use opcua_crypto::{CertificateStore, CertificateStoreBackend, PrivateKey, SecurityPolicy, X509};
 use opcua_types::{
     ApplicationDescription, ContextOwned, DecodingOptions, EndpointDescription,
     FindServersOnNetworkRequest, FindServersOnNetworkResponse, FindServersRequest,
@@ -57,7 +56,7 @@ impl Client {
             None
         };
 
-        let (mut certificate_store, client_certificate, client_pkey) =
+        let (certificate_store, client_certificate, client_pkey) =
             CertificateStore::new_with_x509_data(
                 &config.pki_dir,
                 false,
@@ -65,6 +64,43 @@ impl Client {
                 config.private_key_path.as_deref(),
                 application_description,
             );
+        Self::new_with_certificate_store(config, certificate_store, client_certificate, client_pkey)
+    }
+
+    /// Create a new client from config, storing certificates and keys in `backend`
+    /// instead of the configured pki directory.
+    ///
+    /// Note that this does not make any connection to the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Client configuration object.
+    /// * `backend` - Storage for the client's own key pair and the server certificates.
+    pub fn new_with_certificate_store_backend(
+        config: ClientConfig,
+        backend: Arc<dyn CertificateStoreBackend>,
+    ) -> Self {
+        let application_description = if config.create_sample_keypair {
+            Some(config.application_description())
+        } else {
+            None
+        };
+
+        let (certificate_store, client_certificate, client_pkey) =
+            CertificateStore::new_with_backend_and_x509_data(
+                backend,
+                false,
+                application_description,
+            );
+        Self::new_with_certificate_store(config, certificate_store, client_certificate, client_pkey)
+    }
+
+    fn new_with_certificate_store(
+        config: ClientConfig,
+        mut certificate_store: CertificateStore,
+        client_certificate: Option<X509>,
+        client_pkey: Option<PrivateKey>,
+    ) -> Self {
         if client_certificate.is_none() || client_pkey.is_none() {
             error!("Client is missing its application instance certificate and/or its private key. Encrypted endpoints will not function correctly.")
         }
@@ -75,6 +111,9 @@ impl Client {
         // Clients may choose to auto trust servers to save some messing around with rejected certs
         certificate_store.set_trust_unknown_certs(config.trust_server_certs);
 
+        // Clients may choose to accept servers whose CA has not published a CRL
+        certificate_store.set_allow_missing_crls(config.allow_missing_crls);
+
         // The session retry policy dictates how many times to retry if connection to the server goes down
         // and on what interval
 
@@ -221,6 +260,8 @@ impl Client {
         session_info: SessionInfo,
         channel_lifetime: u32,
     ) -> AsyncSecureChannel {
+        let connector =
+            connector_for_endpoint(&session_info.endpoint, self.certificate_store.clone());
         AsyncSecureChannel::new(
             self.certificate_store.clone(),
             session_info,
@@ -234,7 +275,7 @@ impl Client {
                 max_message_size: self.config.decoding_options.max_message_size,
                 max_chunk_count: self.config.decoding_options.max_chunk_count,
             },
-            Box::new(TcpConnector),
+            connector,
             channel_lifetime,
             // We should only ever need the default decoding context for temporary connections.
             Arc::new(RwLock::new(ContextOwned::new_default(
@@ -363,7 +404,7 @@ impl Client {
         profile_uris: &[&str],
     ) -> Result<Vec<EndpointDescription>, StatusCode> {
         let server_url = server_url.into();
-        if !is_opc_ua_binary_url(&server_url) {
+        if !is_supported_url(&server_url) {
             return Err(StatusCode::BadTcpEndpointUrlInvalid);
         }
         let preferred_locales = Vec::new();
@@ -755,4 +796,26 @@ impl Client {
     pub fn certificate_store(&self) -> &Arc<RwLock<CertificateStore>> {
         &self.certificate_store
     }
+
+    /// Replace the client's application instance certificate and private key at runtime,
+    /// for example when the certificate is renewed. The new certificate is written to the
+    /// certificate store and used by every secure channel opened after this, including when
+    /// an existing session reconnects. Open secure channels keep the certificate they were
+    /// opened with.
+    ///
+    /// Returns `BadSecurityChecksFailed` if `pkey` is not the private key of `cert`.
+    pub fn set_client_certificate(&self, cert: X509, pkey: PrivateKey) -> Result<(), StatusCode> {
+        if !cert.matches_private_key(&pkey) {
+            return Err(StatusCode::BadSecurityChecksFailed);
+        }
+        // Hold the write lock so that no channel reads a mismatched certificate and key.
+        let store = trace_write_lock!(self.certificate_store);
+        store
+            .backend()
+            .store_own_cert_and_pkey(&cert, &pkey, true)
+            .map_err(|e| {
+                error!("Failed to store new client certificate: {e}");
+                StatusCode::BadInternalError
+            })
+    }
 }
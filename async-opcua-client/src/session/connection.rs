@@ -1,14 +1,14 @@
 use std::{str::FromStr, sync::Arc};
 
 use log::error;
-use opcua_core::{comms::url::is_opc_ua_binary_url, config::Config, sync::RwLock};
+use opcua_core::{config::Config, sync::RwLock};
 use opcua_crypto::{CertificateStore, SecurityPolicy};
 use opcua_types::{
     EndpointDescription, MessageSecurityMode, NodeId, StatusCode, TypeLoader, UserTokenType,
 };
 
 use crate::{
-    transport::{tcp::TcpConnector, Connector},
+    transport::{connector_for_endpoint, is_supported_url, tcp::ReverseConnector, Connector},
     ClientConfig, IdentityToken,
 };
 
@@ -17,7 +17,9 @@ use super::{Client, Session, SessionEventLoop, SessionInfo};
 struct SessionBuilderInner {
     session_id: Option<NodeId>,
     user_identity_token: IdentityToken,
-    connector: Box<dyn Connector>,
+    /// Custom connector. If this is not set, the connector is
+    /// picked based on the endpoint URL.
+    connector: Option<Box<dyn Connector>>,
     type_loaders: Vec<Arc<dyn TypeLoader>>,
 }
 
@@ -42,7 +44,7 @@ impl<'a> SessionBuilder<'a, (), ()> {
             inner: SessionBuilderInner {
                 session_id: None,
                 user_identity_token: IdentityToken::Anonymous,
-                connector: Box::new(TcpConnector),
+                connector: None,
                 type_loaders: Vec::new(),
             },
         }
@@ -89,6 +91,14 @@ impl<T, R> SessionBuilder<'_, T, R> {
         self
     }
 
+    /// Use reverse connect, waiting for the server to connect to the client
+    /// instead of connecting to the server. The endpoint URL of the session
+    /// must match the endpoint URL sent by the server.
+    pub fn reverse_connect(mut self, connector: ReverseConnector) -> Self {
+        self.inner.connector = Some(Box::new(connector));
+        self
+    }
+
     fn endpoint_supports_token(&self, endpoint: &EndpointDescription) -> bool {
         match &self.inner.user_identity_token {
             IdentityToken::Anonymous => {
@@ -106,6 +116,10 @@ impl<T, R> SessionBuilder<'_, T, R> {
                 .user_identity_tokens
                 .as_ref()
                 .is_some_and(|e| e.iter().any(|p| p.token_type == UserTokenType::Certificate)),
+            IdentityToken::IssuedToken(_) => endpoint
+                .user_identity_tokens
+                .as_ref()
+                .is_some_and(|e| e.iter().any(|p| p.token_type == UserTokenType::IssuedToken)),
         }
     }
 }
@@ -218,13 +232,20 @@ impl<'a> SessionBuilder<'a, (), Vec<EndpointDescription>> {
         secure: bool,
     ) -> Result<SessionBuilder<'a, EndpointDescription, Vec<EndpointDescription>>, String> {
         let endpoint = if secure {
+            // Reverse the iterator, so that the first endpoint is picked if several
+            // have the same security level.
             self.endpoints
                 .iter()
-                .filter(|e| self.endpoint_supports_token(e))
+                .filter(|e| {
+                    is_supported_url(e.endpoint_url.as_ref()) && self.endpoint_supports_token(e)
+                })
+                .rev()
                 .max_by(|a, b| a.security_level.cmp(&b.security_level))
         } else {
             self.endpoints.iter().find(|e| {
-                e.security_mode == MessageSecurityMode::None && self.endpoint_supports_token(e)
+                e.security_mode == MessageSecurityMode::None
+                    && is_supported_url(e.endpoint_url.as_ref())
+                    && self.endpoint_supports_token(e)
             })
         };
         let Some(endpoint) = endpoint else {
@@ -247,7 +268,7 @@ impl<'a, R> SessionBuilder<'a, (), R> {
         endpoint: impl Into<EndpointDescription>,
     ) -> Result<SessionBuilder<'a, EndpointDescription, R>, String> {
         let endpoint = endpoint.into();
-        if !is_opc_ua_binary_url(endpoint.endpoint_url.as_ref()) {
+        if !is_supported_url(endpoint.endpoint_url.as_ref()) {
             return Err(format!(
                 "Endpoint url {} is not a valid / supported url",
                 endpoint.endpoint_url
@@ -269,6 +290,10 @@ impl<R> SessionBuilder<'_, EndpointDescription, R> {
         self,
         certificate_store: Arc<RwLock<CertificateStore>>,
     ) -> (Arc<Session>, SessionEventLoop) {
+        let connector = self
+            .inner
+            .connector
+            .unwrap_or_else(|| connector_for_endpoint(&self.endpoint, certificate_store.clone()));
         Session::new(
             certificate_store,
             SessionInfo {
@@ -282,7 +307,7 @@ impl<R> SessionBuilder<'_, EndpointDescription, R> {
             self.config.decoding_options.as_comms_decoding_options(),
             self.config,
             self.inner.session_id,
-            self.inner.connector,
+            connector,
             self.inner.type_loaders,
         )
     }
@@ -42,7 +42,7 @@ use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use arc_swap::ArcSwap;
+use arc_swap::{ArcSwap, ArcSwapOption};
 pub use client::Client;
 pub use connect::SessionConnectMode;
 pub use connection::SessionBuilder;
@@ -58,6 +58,7 @@ pub use services::attributes::{
 };
 pub use services::method::Call;
 pub use services::node_management::{AddNodes, AddReferences, DeleteNodes, DeleteReferences};
+pub use services::query::{QueryFirst, QueryNext};
 pub use services::session::{ActivateSession, Cancel, CloseSession, CreateSession};
 use services::subscriptions::state::SubscriptionState;
 use services::subscriptions::PublishLimits;
@@ -109,8 +110,8 @@ pub(crate) use session_trace;
 
 use opcua_core::ResponseMessage;
 use opcua_types::{
-    ApplicationDescription, ContextOwned, DecodingOptions, EndpointDescription, Error, IntegerId,
-    NamespaceMap, NodeId, ReadValueId, RequestHeader, ResponseHeader, StatusCode,
+    ApplicationDescription, ByteString, ContextOwned, DecodingOptions, EndpointDescription, Error,
+    IntegerId, NamespaceMap, NodeId, ReadValueId, RequestHeader, ResponseHeader, StatusCode,
     TimestampsToReturn, TypeLoader, UAString, VariableId, Variant,
 };
 
@@ -191,6 +192,10 @@ pub struct Session {
     pub(super) trigger_publish_tx: tokio::sync::watch::Sender<Instant>,
     decoding_options: DecodingOptions,
     pub(super) encoding_context: Arc<RwLock<ContextOwned>>,
+    pub(super) rsa_encrypted_secret: bool,
+    /// Ephemeral key last sent by the server, used to encrypt user identity token
+    /// secrets with ECC security policies.
+    pub(super) server_ephemeral_key: ArcSwapOption<ByteString>,
 }
 
 impl Session {
@@ -266,6 +271,8 @@ impl Session {
             trigger_publish_tx,
             decoding_options,
             encoding_context,
+            rsa_encrypted_secret: config.rsa_encrypted_secret,
+            server_ephemeral_key: ArcSwapOption::empty(),
         });
 
         (
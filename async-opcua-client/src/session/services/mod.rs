@@ -1,6 +1,7 @@
 pub mod attributes;
 pub mod method;
 pub mod node_management;
+pub mod query;
 pub mod session;
 pub mod subscriptions;
 pub mod view;
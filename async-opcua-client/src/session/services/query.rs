@@ -0,0 +1,401 @@
+use std::{collections::VecDeque, time::Duration};
+
+use futures::Stream;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    session::{
+        process_service_result, process_unexpected_response,
+        request_builder::{builder_base, builder_debug, builder_error, RequestHeaderBuilder},
+    },
+    Session, UARequest,
+};
+use opcua_core::ResponseMessage;
+use opcua_types::{
+    ByteString, ContentFilter, IntegerId, NodeId, NodeTypeDescription, QueryDataSet,
+    QueryFirstRequest, QueryFirstResponse, QueryNextRequest, QueryNextResponse, StatusCode,
+    ViewDescription,
+};
+
+#[derive(Debug, Clone)]
+/// Query the address space of the server by sending a [`QueryFirstRequest`]. The server
+/// returns a set of nodes matching the given node types and content filter, with the
+/// requested data for each node.
+///
+/// See OPC UA Part 4 - Services 5.9.3 for complete description of the service and error responses.
+pub struct QueryFirst {
+    view: ViewDescription,
+    node_types: Vec<NodeTypeDescription>,
+    filter: ContentFilter,
+    max_data_sets_to_return: u32,
+    max_references_to_return: u32,
+
+    header: RequestHeaderBuilder,
+}
+
+builder_base!(QueryFirst);
+
+impl QueryFirst {
+    /// Construct a new call to the `QueryFirst` service.
+    pub fn new(session: &Session) -> Self {
+        Self {
+            view: ViewDescription::default(),
+            node_types: Vec::new(),
+            filter: ContentFilter::default(),
+            max_data_sets_to_return: 0,
+            max_references_to_return: 0,
+
+            header: RequestHeaderBuilder::new_from_session(session),
+        }
+    }
+
+    /// Construct a new call to the `QueryFirst` service, setting header parameters manually.
+    pub fn new_manual(
+        session_id: u32,
+        timeout: Duration,
+        auth_token: NodeId,
+        request_handle: IntegerId,
+    ) -> Self {
+        Self {
+            view: ViewDescription::default(),
+            node_types: Vec::new(),
+            filter: ContentFilter::default(),
+            max_data_sets_to_return: 0,
+            max_references_to_return: 0,
+
+            header: RequestHeaderBuilder::new(session_id, timeout, auth_token, request_handle),
+        }
+    }
+
+    /// Set the view to query.
+    pub fn view(mut self, view: ViewDescription) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// Set node types to query, overwriting any that were set previously.
+    pub fn node_types(mut self, node_types: Vec<NodeTypeDescription>) -> Self {
+        self.node_types = node_types;
+        self
+    }
+
+    /// Add a node type to query.
+    pub fn node_type(mut self, node_type: impl Into<NodeTypeDescription>) -> Self {
+        self.node_types.push(node_type.into());
+        self
+    }
+
+    /// Set the content filter used to select nodes. The default is an empty filter,
+    /// meaning all nodes of the given types are returned.
+    pub fn filter(mut self, filter: ContentFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Set max data sets to return. The default is zero, meaning server-defined.
+    pub fn max_data_sets_to_return(mut self, max_data_sets_to_return: u32) -> Self {
+        self.max_data_sets_to_return = max_data_sets_to_return;
+        self
+    }
+
+    /// Set max references to return for each data description that targets
+    /// multiple nodes. The default is zero, meaning server-defined.
+    pub fn max_references_to_return(mut self, max_references_to_return: u32) -> Self {
+        self.max_references_to_return = max_references_to_return;
+        self
+    }
+}
+
+impl UARequest for QueryFirst {
+    type Out = QueryFirstResponse;
+
+    async fn send<'a>(self, channel: &'a crate::AsyncSecureChannel) -> Result<Self::Out, StatusCode>
+    where
+        Self: 'a,
+    {
+        if self.node_types.is_empty() {
+            builder_error!(self, "query_first was not supplied with any node types");
+            return Err(StatusCode::BadNothingToDo);
+        }
+        let request = QueryFirstRequest {
+            request_header: self.header.header,
+            view: self.view,
+            node_types: Some(self.node_types),
+            filter: self.filter,
+            max_data_sets_to_return: self.max_data_sets_to_return,
+            max_references_to_return: self.max_references_to_return,
+        };
+        let response = channel.send(request, self.header.timeout).await?;
+        if let ResponseMessage::QueryFirst(response) = response {
+            builder_debug!(self, "query_first, success");
+            process_service_result(&response.response_header)?;
+            Ok(*response)
+        } else {
+            builder_error!(self, "query_first failed");
+            Err(process_unexpected_response(response))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Continue a query by sending a continuation point in a [`QueryNextRequest`] to the server.
+/// This may have to be called repeatedly to process the initial query.
+///
+/// See OPC UA Part 4 - Services 5.9.4 for complete description of the service and error responses.
+pub struct QueryNext {
+    continuation_point: ByteString,
+    release_continuation_point: bool,
+
+    header: RequestHeaderBuilder,
+}
+
+builder_base!(QueryNext);
+
+impl QueryNext {
+    /// Construct a new call to the `QueryNext` service.
+    pub fn new(session: &Session) -> Self {
+        Self {
+            continuation_point: ByteString::null(),
+            release_continuation_point: false,
+
+            header: RequestHeaderBuilder::new_from_session(session),
+        }
+    }
+
+    /// Construct a new call to the `QueryNext` service, setting header parameters manually.
+    pub fn new_manual(
+        session_id: u32,
+        timeout: Duration,
+        auth_token: NodeId,
+        request_handle: IntegerId,
+    ) -> Self {
+        Self {
+            continuation_point: ByteString::null(),
+            release_continuation_point: false,
+
+            header: RequestHeaderBuilder::new(session_id, timeout, auth_token, request_handle),
+        }
+    }
+
+    /// Set release continuation point. Default is false, if this is true,
+    /// the continuation point will be released and no results will be returned.
+    pub fn release_continuation_point(mut self, release_continuation_point: bool) -> Self {
+        self.release_continuation_point = release_continuation_point;
+        self
+    }
+
+    /// Set the continuation point returned from a previous `QueryFirst` or `QueryNext`.
+    pub fn continuation_point(mut self, continuation_point: ByteString) -> Self {
+        self.continuation_point = continuation_point;
+        self
+    }
+}
+
+impl UARequest for QueryNext {
+    type Out = QueryNextResponse;
+
+    async fn send<'a>(self, channel: &'a crate::AsyncSecureChannel) -> Result<Self::Out, StatusCode>
+    where
+        Self: 'a,
+    {
+        if self.continuation_point.is_null_or_empty() {
+            builder_error!(
+                self,
+                "query_next was not supplied with a continuation point"
+            );
+            return Err(StatusCode::BadNothingToDo);
+        }
+        let request = QueryNextRequest {
+            request_header: self.header.header,
+            release_continuation_point: self.release_continuation_point,
+            continuation_point: self.continuation_point,
+        };
+        let response = channel.send(request, self.header.timeout).await?;
+        if let ResponseMessage::QueryNext(response) = response {
+            builder_debug!(self, "query_next, success");
+            process_service_result(&response.response_header)?;
+            Ok(*response)
+        } else {
+            builder_error!(self, "query_next failed");
+            Err(process_unexpected_response(response))
+        }
+    }
+}
+
+enum QueryStep {
+    First(Box<QueryFirst>),
+    Next(ByteString),
+    Done,
+}
+
+impl QueryStep {
+    fn from_continuation_point(continuation_point: ByteString) -> Self {
+        if continuation_point.is_null_or_empty() {
+            Self::Done
+        } else {
+            Self::Next(continuation_point)
+        }
+    }
+}
+
+struct QueryExecution<'a> {
+    session: &'a Session,
+    token: CancellationToken,
+    step: QueryStep,
+    pending_out: VecDeque<QueryDataSet>,
+}
+
+impl QueryExecution<'_> {
+    async fn cleanup(&mut self) {
+        if let QueryStep::Next(continuation_point) =
+            std::mem::replace(&mut self.step, QueryStep::Done)
+        {
+            if let Err(e) = QueryNext::new(self.session)
+                .continuation_point(continuation_point)
+                .release_continuation_point(true)
+                .send(&self.session.channel)
+                .await
+            {
+                log::warn!("Failed to release query continuation point: {e}");
+            }
+        }
+    }
+}
+
+impl Session {
+    /// Query the address space of the server by sending a [`QueryFirstRequest`]. The server
+    /// returns a set of nodes matching the given node types and content filter, with the
+    /// requested data for each node.
+    ///
+    /// See OPC UA Part 4 - Services 5.9.3 for complete description of the service and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `node_types` - A list of [`NodeTypeDescription`] describing the types of node to return,
+    ///   and the data to return for each.
+    /// * `filter` - A [`ContentFilter`] used to select nodes.
+    /// * `max_data_sets_to_return` - Maximum number of data sets to return, or zero for no limit.
+    /// * `max_references_to_return` - Maximum number of references to return for each data
+    ///   description that targets multiple nodes, or zero for no limit.
+    /// * `view` - Optional view to query.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(QueryFirstResponse)` - The query response. This contains the returned data sets,
+    ///   any parsing and filter errors, and may contain a continuation
+    ///   point, for use with `query_next()`.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn query_first(
+        &self,
+        node_types: &[NodeTypeDescription],
+        filter: ContentFilter,
+        max_data_sets_to_return: u32,
+        max_references_to_return: u32,
+        view: Option<ViewDescription>,
+    ) -> Result<QueryFirstResponse, StatusCode> {
+        QueryFirst::new(self)
+            .node_types(node_types.to_vec())
+            .filter(filter)
+            .max_data_sets_to_return(max_data_sets_to_return)
+            .max_references_to_return(max_references_to_return)
+            .view(view.unwrap_or_default())
+            .send(&self.channel)
+            .await
+    }
+
+    /// Continue a query by sending a continuation point in a [`QueryNextRequest`] to the server.
+    /// This function may have to be called repeatedly to process the initial query.
+    ///
+    /// See OPC UA Part 4 - Services 5.9.4 for complete description of the service and error responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `release_continuation_point` - Flag indicating if the continuation point should be released by the server
+    /// * `continuation_point` - The continuation point returned by `query_first()` or a previous `query_next()`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(QueryNextResponse)` - The next set of data sets. This may contain a revised continuation
+    ///   point, if there are still more results.
+    /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
+    ///
+    pub async fn query_next(
+        &self,
+        release_continuation_point: bool,
+        continuation_point: ByteString,
+    ) -> Result<QueryNextResponse, StatusCode> {
+        QueryNext::new(self)
+            .continuation_point(continuation_point)
+            .release_continuation_point(release_continuation_point)
+            .send(&self.channel)
+            .await
+    }
+
+    /// Run a query to completion, returning a stream of every [`QueryDataSet`] returned
+    /// by the server. Continuation points are followed automatically using `QueryNext`.
+    ///
+    /// To stop the query you can simply stop polling the stream. If you instead cancel `token`
+    /// and keep polling the stream until it terminates, any outstanding continuation point is
+    /// released on the server. The same happens if a request fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The [`QueryFirst`] request starting the query.
+    /// * `token` - Cancellation token used to stop the query.
+    ///
+    /// # Returns
+    ///
+    /// A stream of [`QueryDataSet`]. If a request fails the stream yields an error and terminates.
+    ///
+    pub fn query(
+        &self,
+        request: QueryFirst,
+        token: CancellationToken,
+    ) -> impl Stream<Item = Result<QueryDataSet, StatusCode>> + '_ {
+        let initial = QueryExecution {
+            session: self,
+            token,
+            step: QueryStep::First(Box::new(request)),
+            pending_out: VecDeque::new(),
+        };
+        futures::stream::try_unfold(initial, |mut s| async move {
+            loop {
+                // If we're cancelled, release the continuation point and stop immediately.
+                if s.token.is_cancelled() {
+                    s.cleanup().await;
+                    return Ok(None);
+                }
+
+                if let Some(data_set) = s.pending_out.pop_front() {
+                    return Ok(Some((data_set, s)));
+                }
+
+                let (data_sets, continuation_point) =
+                    match std::mem::replace(&mut s.step, QueryStep::Done) {
+                        QueryStep::First(request) => request
+                            .send(&s.session.channel)
+                            .await
+                            .map(|r| (r.query_data_sets, r.continuation_point))?,
+                        QueryStep::Next(continuation_point) => {
+                            let res = QueryNext::new(s.session)
+                                .continuation_point(continuation_point.clone())
+                                .send(&s.session.channel)
+                                .await;
+                            match res {
+                                Ok(r) => (r.query_data_sets, r.revised_continuation_point),
+                                Err(e) => {
+                                    s.step = QueryStep::Next(continuation_point);
+                                    s.cleanup().await;
+                                    return Err(e);
+                                }
+                            }
+                        }
+                        QueryStep::Done => return Ok(None),
+                    };
+                s.pending_out.extend(data_sets.into_iter().flatten());
+                s.step = QueryStep::from_continuation_point(continuation_point);
+            }
+        })
+    }
+}
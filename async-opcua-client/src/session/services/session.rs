@@ -7,22 +7,27 @@ use opcua_core::{
     trace_read_lock, trace_write_lock, ResponseMessage,
 };
 use opcua_crypto::{
-    self, certificate_store::CertificateStore, user_identity::make_user_name_identity_token, PKey,
-    SecurityPolicy,
+    self,
+    certificate_store::CertificateStore,
+    user_identity::{
+        ecdh_key_from_response_header, ecdh_key_request_header, make_issued_identity_token,
+        make_user_name_identity_token_with_options, EccSecretKeys, SecretOptions,
+    },
+    PrivateKey, SecurityPolicy, X509,
 };
 use opcua_types::{
     ActivateSessionRequest, ActivateSessionResponse, AnonymousIdentityToken,
     ApplicationDescription, ByteString, CancelRequest, CancelResponse, CloseSessionRequest,
     CloseSessionResponse, CreateSessionRequest, CreateSessionResponse, EndpointDescription,
-    ExtensionObject, IntegerId, NodeId, SignatureData, SignedSoftwareCertificate, StatusCode,
-    UAString, UserTokenType, X509IdentityToken,
+    ExtensionObject, IntegerId, NodeId, ResponseHeader, SignatureData, SignedSoftwareCertificate,
+    StatusCode, UAString, UserTokenType, X509IdentityToken,
 };
-use rsa::RsaPrivateKey;
 
 use crate::{
     session::{
         process_service_result, process_unexpected_response,
         request_builder::{builder_base, builder_error, RequestHeaderBuilder},
+        session_warn,
     },
     AsyncSecureChannel, IdentityToken, Session, UARequest,
 };
@@ -68,7 +73,9 @@ impl<'a> CreateSession<'a> {
             client_certificate: {
                 let cert_store = trace_read_lock!(session.certificate_store);
                 cert_store
-                    .read_own_cert()
+                    .read_own_cert_for_policy(SecurityPolicy::from_uri(
+                        session.session_info.endpoint.security_policy_uri.as_ref(),
+                    ))
                     .ok()
                     .map(|m| m.as_byte_string())
                     .unwrap_or_default()
@@ -79,6 +86,7 @@ impl<'a> CreateSession<'a> {
             max_response_message_size: 0,
             header: RequestHeaderBuilder::new_from_session(session),
         }
+        .maybe_request_ephemeral_key(session.ecdh_security_policy())
     }
 
     /// Create a new `CreateSession` request with the given data.
@@ -138,7 +146,9 @@ impl<'a> CreateSession<'a> {
     pub fn client_cert_from_store(mut self, certificate_store: &RwLock<CertificateStore>) -> Self {
         let cert_store = trace_read_lock!(certificate_store);
         self.client_certificate = cert_store
-            .read_own_cert()
+            .read_own_cert_for_policy(SecurityPolicy::from_uri(
+                self.endpoint.security_policy_uri.as_ref(),
+            ))
             .ok()
             .map(|m| m.as_byte_string())
             .unwrap_or_default();
@@ -156,6 +166,21 @@ impl<'a> CreateSession<'a> {
         self.max_response_message_size = max_response_message_size;
         self
     }
+
+    /// Ask the server for an ephemeral key, used to encrypt user identity token secrets
+    /// with the given ECC security policy. The key is returned in the additional header of
+    /// the response, see [`ecdh_key_from_response_header`].
+    pub fn request_ephemeral_key(mut self, security_policy: SecurityPolicy) -> Self {
+        self.header.header.additional_header = ecdh_key_request_header(security_policy);
+        self
+    }
+
+    fn maybe_request_ephemeral_key(self, security_policy: Option<SecurityPolicy>) -> Self {
+        match security_policy {
+            Some(security_policy) => self.request_ephemeral_key(security_policy),
+            None => self,
+        }
+    }
 }
 
 impl UARequest for CreateSession<'_> {
@@ -228,7 +253,9 @@ impl UARequest for CreateSession<'_> {
 /// is handled automatically as part of connect/reconnect logic.
 pub struct ActivateSession {
     identity_token: IdentityToken,
-    private_key: Option<PKey<RsaPrivateKey>>,
+    private_key: Option<PrivateKey>,
+    rsa_encrypted_secret: bool,
+    server_ephemeral_key: Option<ByteString>,
     locale_ids: Vec<UAString>,
     client_software_certificates: Vec<SignedSoftwareCertificate>,
     endpoint: EndpointDescription,
@@ -247,8 +274,17 @@ impl ActivateSession {
             identity_token: session.session_info.user_identity_token.clone(),
             private_key: {
                 let cert_store = trace_read_lock!(session.certificate_store);
-                cert_store.read_own_pkey().ok()
+                cert_store
+                    .read_own_pkey_for_policy(SecurityPolicy::from_uri(
+                        session.session_info.endpoint.security_policy_uri.as_ref(),
+                    ))
+                    .ok()
             },
+            rsa_encrypted_secret: session.rsa_encrypted_secret,
+            server_ephemeral_key: session
+                .server_ephemeral_key
+                .load_full()
+                .map(|key| (*key).clone()),
             locale_ids: session
                 .session_info
                 .preferred_locales
@@ -259,6 +295,7 @@ impl ActivateSession {
             endpoint: session.session_info.endpoint.clone(),
             header: RequestHeaderBuilder::new_from_session(session),
         }
+        .maybe_request_ephemeral_key(session.ecdh_security_policy())
     }
 
     /// Create a new `ActivateSession` request.
@@ -272,6 +309,8 @@ impl ActivateSession {
         Self {
             identity_token: IdentityToken::Anonymous,
             private_key: None,
+            rsa_encrypted_secret: false,
+            server_ephemeral_key: None,
             locale_ids: Vec::new(),
             client_software_certificates: Vec::new(),
             endpoint,
@@ -286,7 +325,7 @@ impl ActivateSession {
     }
 
     /// Set the client private key.
-    pub fn private_key(mut self, private_key: PKey<RsaPrivateKey>) -> Self {
+    pub fn private_key(mut self, private_key: PrivateKey) -> Self {
         self.private_key = Some(private_key);
         self
     }
@@ -318,6 +357,51 @@ impl ActivateSession {
         self
     }
 
+    /// Encrypt passwords with RSA security policies in the `RsaEncryptedSecret` format,
+    /// instead of the legacy format.
+    pub fn rsa_encrypted_secret(mut self, rsa_encrypted_secret: bool) -> Self {
+        self.rsa_encrypted_secret = rsa_encrypted_secret;
+        self
+    }
+
+    /// Set the ephemeral key last sent by the server, which is required to encrypt
+    /// passwords with ECC security policies.
+    pub fn server_ephemeral_key(mut self, server_ephemeral_key: ByteString) -> Self {
+        self.server_ephemeral_key = Some(server_ephemeral_key);
+        self
+    }
+
+    /// Ask the server for a new ephemeral key, used to encrypt user identity token secrets
+    /// with the given ECC security policy the next time the session is activated.
+    pub fn request_ephemeral_key(mut self, security_policy: SecurityPolicy) -> Self {
+        self.header.header.additional_header = ecdh_key_request_header(security_policy);
+        self
+    }
+
+    fn maybe_request_ephemeral_key(self, security_policy: Option<SecurityPolicy>) -> Self {
+        match security_policy {
+            Some(security_policy) => self.request_ephemeral_key(security_policy),
+            None => self,
+        }
+    }
+
+    /// Get the options used to encrypt the secret of a user identity token. `own_cert` is the
+    /// certificate of the secure channel.
+    fn secret_options<'a>(&'a self, own_cert: &'a Option<X509>) -> SecretOptions<'a> {
+        let ecc = match (own_cert, &self.private_key, &self.server_ephemeral_key) {
+            (Some(certificate), Some(private_key), Some(receiver_key)) => Some(EccSecretKeys {
+                certificate,
+                private_key,
+                receiver_key: receiver_key.as_ref(),
+            }),
+            _ => None,
+        };
+        SecretOptions {
+            rsa_encrypted_secret: self.rsa_encrypted_secret,
+            ecc,
+        }
+    }
+
     fn user_identity_token(
         &self,
         secure_channel: &SecureChannel,
@@ -326,6 +410,7 @@ impl ActivateSession {
             IdentityToken::Anonymous => UserTokenType::Anonymous,
             IdentityToken::UserName(_, _) => UserTokenType::UserName,
             IdentityToken::X509(_, _) => UserTokenType::Certificate,
+            IdentityToken::IssuedToken(_) => UserTokenType::IssuedToken,
         };
         let Some(policy) = self.endpoint.find_policy(user_token_type) else {
             builder_error!(
@@ -359,13 +444,33 @@ impl ActivateSession {
                 let channel_sec_policy = secure_channel.security_policy();
                 let nonce = secure_channel.remote_nonce();
                 let cert = secure_channel.remote_cert();
-                let identity_token = make_user_name_identity_token(
+                let own_cert = secure_channel.cert();
+                let identity_token = make_user_name_identity_token_with_options(
                     channel_sec_policy,
                     policy,
                     nonce,
                     &cert,
                     user,
                     pass,
+                    &self.secret_options(&own_cert),
+                )?;
+                Ok((
+                    ExtensionObject::from_message(identity_token),
+                    SignatureData::null(),
+                ))
+            }
+            IdentityToken::IssuedToken(token_data) => {
+                let channel_sec_policy = secure_channel.security_policy();
+                let nonce = secure_channel.remote_nonce();
+                let cert = secure_channel.remote_cert();
+                let own_cert = secure_channel.cert();
+                let identity_token = make_issued_identity_token(
+                    channel_sec_policy,
+                    policy,
+                    nonce,
+                    &cert,
+                    token_data.as_ref(),
+                    &self.secret_options(&own_cert),
                 )?;
                 Ok((
                     ExtensionObject::from_message(identity_token),
@@ -633,6 +738,8 @@ impl Session {
     ///
     pub(crate) async fn create_session(&self) -> Result<NodeId, StatusCode> {
         let response = CreateSession::new(self).send(&self.channel).await?;
+        self.server_ephemeral_key
+            .store(self.ephemeral_key_from_response(&response.response_header));
 
         let session_id = {
             self.session_id.store(Arc::new(response.session_id.clone()));
@@ -654,10 +761,51 @@ impl Session {
     /// * `Err(StatusCode)` - Request failed, [Status code](StatusCode) is the reason for failure.
     ///
     pub(crate) async fn activate_session(&self) -> Result<(), StatusCode> {
-        ActivateSession::new(self).send(&self.channel).await?;
+        let response = ActivateSession::new(self).send(&self.channel).await?;
+        // Each ephemeral key is used once, the server sends a new one for the next activation.
+        if let Some(key) = self.ephemeral_key_from_response(&response.response_header) {
+            self.server_ephemeral_key.store(Some(key));
+        }
         Ok(())
     }
 
+    /// Get the ECC security policy used to encrypt the secret of the user identity token,
+    /// if any. The server must send an ephemeral key for this security policy before the
+    /// session can be activated.
+    pub(crate) fn ecdh_security_policy(&self) -> Option<SecurityPolicy> {
+        let token_type = match self.session_info.user_identity_token {
+            IdentityToken::UserName(..) => UserTokenType::UserName,
+            IdentityToken::IssuedToken(..) => UserTokenType::IssuedToken,
+            _ => return None,
+        };
+        let endpoint = &self.session_info.endpoint;
+        let policy = endpoint.find_policy(token_type)?;
+        // An empty security policy means that the policy of the secure channel is used.
+        let security_policy = if policy.security_policy_uri.is_empty() {
+            SecurityPolicy::from_uri(endpoint.security_policy_uri.as_ref())
+        } else {
+            SecurityPolicy::from_uri(policy.security_policy_uri.as_ref())
+        };
+        security_policy.is_ecc().then_some(security_policy)
+    }
+
+    /// Get the ephemeral key sent by the server in a CreateSession or ActivateSession response.
+    fn ephemeral_key_from_response(&self, header: &ResponseHeader) -> Option<Arc<ByteString>> {
+        let security_policy = self.ecdh_security_policy()?;
+        let server_cert = trace_read_lock!(self.channel.secure_channel).remote_cert()?;
+        match ecdh_key_from_response_header(
+            &header.additional_header,
+            security_policy,
+            &server_cert,
+        ) {
+            Ok(key) => key.map(Arc::new),
+            Err(e) => {
+                session_warn!(self, "Ignoring invalid ephemeral key from the server: {e}");
+                None
+            }
+        }
+    }
+
     /// Close the session by sending a [`CloseSessionRequest`] to the server.
     ///
     /// This is not accessible by users, they must instead call `disconnect` to properly close the session.
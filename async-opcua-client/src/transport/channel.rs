@@ -244,8 +244,12 @@ impl AsyncSecureChannel {
             let (cert, key) = {
                 let certificate_store = trace_write_lock!(self.certificate_store);
                 (
-                    certificate_store.read_own_cert().ok(),
-                    certificate_store.read_own_pkey().ok(),
+                    certificate_store
+                        .read_own_cert_for_policy(security_policy)
+                        .ok(),
+                    certificate_store
+                        .read_own_pkey_for_policy(security_policy)
+                        .ok(),
                 )
             };
 
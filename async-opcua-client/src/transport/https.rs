@@ -0,0 +1,379 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use log::{debug, error};
+use opcua_core::{
+    comms::{
+        buffer::SendBuffer,
+        chunker::Chunker,
+        https::{self, HttpsEncoding},
+        message_chunk::{MessageChunk, MessageIsFinalType},
+        secure_channel::{Role, SecureChannel},
+        tcp_codec::{Message, TcpCodec},
+        tcp_types::AcknowledgeMessage,
+        tls,
+        url::hostname_port_from_url,
+    },
+    sync::{Mutex, RwLock},
+    trace_read_lock, Message as _, RequestMessage, ResponseMessage,
+};
+use opcua_crypto::{CertificateStore, SecurityPolicy};
+use opcua_types::{
+    ByteString, ChannelSecurityToken, ContextOwned, DateTime, MessageSecurityMode,
+    OpenSecureChannelRequest, OpenSecureChannelResponse, ResponseHeader, ServiceFault, StatusCode,
+};
+use tokio::{
+    io::{BufReader, DuplexStream},
+    net::TcpStream,
+};
+use tokio_rustls::{client::TlsStream, rustls::pki_types::ServerName, TlsConnector};
+use tokio_util::codec::FramedRead;
+
+use super::{
+    connect::Connector,
+    tcp::{connect_socket, handshake, TcpTransport, TransportConfiguration},
+    OutgoingMessage,
+};
+
+/// Size of the in-memory pipe between the transport and the HTTPS bridge.
+const PIPE_BUFFER_SIZE: usize = 65536;
+
+/// Secure channel ID reported to the client. There is no secure channel over HTTPS,
+/// so this is never sent to the server.
+const BRIDGE_SECURE_CHANNEL_ID: u32 = 1;
+
+type HttpsStream = BufReader<TlsStream<TcpStream>>;
+
+/// Connector for `https`, OPC-UA over HTTPS.
+///
+/// HTTPS has no secure channel, each service request is sent as an HTTP `POST`. To let the
+/// rest of the client treat it like any other transport, the connector answers the hello and
+/// secure channel messages locally, and forwards all other requests to the server.
+/// Requests are sent on a pool of connections, so long-running requests like `Publish`
+/// do not block other requests.
+///
+/// Messages are only protected by TLS, so this can only be used with endpoints with
+/// security policy `None`. The TLS certificate of the server is validated using the
+/// client certificate store, the same way as application instance certificates.
+pub struct HttpsConnector {
+    certificate_store: Arc<RwLock<CertificateStore>>,
+    encoding: HttpsEncoding,
+}
+
+impl HttpsConnector {
+    /// Create a new HTTPS connector sending messages with `encoding`, validating TLS
+    /// certificates against `certificate_store`.
+    pub fn new(certificate_store: Arc<RwLock<CertificateStore>>, encoding: HttpsEncoding) -> Self {
+        Self {
+            certificate_store,
+            encoding,
+        }
+    }
+}
+
+#[async_trait]
+impl Connector for HttpsConnector {
+    async fn connect(
+        &self,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
+        endpoint_url: &str,
+    ) -> Result<TcpTransport, StatusCode> {
+        let (security_policy, context) = {
+            let channel = trace_read_lock!(channel);
+            (channel.security_policy(), channel.context_arc())
+        };
+        if security_policy != SecurityPolicy::None {
+            error!("HTTPS endpoint {endpoint_url} cannot be used with security policy {security_policy}");
+            return Err(StatusCode::BadSecurityPolicyRejected);
+        }
+
+        let (host, _) = hostname_port_from_url(endpoint_url, 443)?;
+        let server_name = ServerName::try_from(host).map_err(|e| {
+            error!("Invalid TLS server name in {endpoint_url}: {e}");
+            StatusCode::BadTcpEndpointUrlInvalid
+        })?;
+        let (host, path) = https::request_target(endpoint_url)?;
+        let client = Arc::new(HttpsClient {
+            endpoint_url: endpoint_url.to_owned(),
+            host,
+            path,
+            server_name,
+            tls: TlsConnector::from(tls::client_config(self.certificate_store.clone())?),
+            encoding: self.encoding,
+            max_message_size: config.max_message_size,
+            context: context.clone(),
+            idle: Mutex::new(Vec::new()),
+        });
+        // Open the first connection immediately, so that the caller gets
+        // connection errors.
+        let stream = client.open().await?;
+        client.idle.lock().push(stream);
+
+        let mut bridge_channel =
+            SecureChannel::new(self.certificate_store.clone(), Role::Server, context);
+        bridge_channel.set_security_policy(SecurityPolicy::None);
+        bridge_channel.set_security_mode(MessageSecurityMode::None);
+        bridge_channel.set_secure_channel_id(BRIDGE_SECURE_CHANNEL_ID);
+
+        let (local, remote) = tokio::io::duplex(PIPE_BUFFER_SIZE);
+        tokio::spawn(run_bridge(client, bridge_channel, remote, config.clone()));
+
+        let handshake = handshake(Box::new(local), &channel, &config, endpoint_url).await?;
+        Ok(TcpTransport::new(channel, outgoing_recv, config, handshake))
+    }
+}
+
+/// Sends service requests to an HTTPS endpoint.
+struct HttpsClient {
+    endpoint_url: String,
+    host: String,
+    path: String,
+    server_name: ServerName<'static>,
+    tls: TlsConnector,
+    encoding: HttpsEncoding,
+    max_message_size: usize,
+    context: Arc<RwLock<ContextOwned>>,
+    /// Open connections that are not currently used by a request.
+    idle: Mutex<Vec<HttpsStream>>,
+}
+
+impl HttpsClient {
+    async fn open(&self) -> Result<HttpsStream, StatusCode> {
+        let socket = connect_socket(&self.endpoint_url).await?;
+        let stream = self
+            .tls
+            .connect(self.server_name.clone(), socket)
+            .await
+            .map_err(|e| {
+                error!("TLS handshake with {} failed: {e}", self.endpoint_url);
+                StatusCode::BadSecureChannelClosed
+            })?;
+        Ok(BufReader::new(stream))
+    }
+
+    async fn exchange(
+        &self,
+        stream: &mut HttpsStream,
+        body: &[u8],
+    ) -> Result<https::HttpResponse, StatusCode> {
+        https::write_request(
+            stream,
+            &self.host,
+            &self.path,
+            self.encoding.content_type(),
+            body,
+        )
+        .await
+        .map_err(|e| {
+            debug!("Failed to write HTTPS request: {e}");
+            StatusCode::BadConnectionClosed
+        })?;
+        https::read_response(stream, self.max_message_size).await
+    }
+
+    /// Send `request` to the server and wait for the response. Fails if the server
+    /// cannot be reached. Invalid responses are returned as service faults.
+    async fn send(&self, request: RequestMessage) -> Result<ResponseMessage, StatusCode> {
+        let request_handle = request.request_handle();
+        let body = {
+            let ctx = trace_read_lock!(self.context);
+            https::encode_message(request, self.encoding, &ctx.context())
+        };
+        let body = match body {
+            Ok(b) => b,
+            Err(e) => return Ok(ServiceFault::new(request_handle, e).into()),
+        };
+
+        let idle = self.idle.lock().pop();
+        let response = match idle {
+            // The server may have closed an idle connection, if so, retry on a new one.
+            Some(mut stream) => match self.exchange(&mut stream, &body).await {
+                Ok(r) => Ok((stream, r)),
+                Err(StatusCode::BadConnectionClosed | StatusCode::BadCommunicationError) => {
+                    let mut stream = self.open().await?;
+                    self.exchange(&mut stream, &body).await.map(|r| (stream, r))
+                }
+                Err(e) => Err(e),
+            },
+            None => {
+                let mut stream = self.open().await?;
+                self.exchange(&mut stream, &body).await.map(|r| (stream, r))
+            }
+        };
+        let (stream, response) = match response {
+            Ok(r) => r,
+            Err(e @ (StatusCode::BadConnectionClosed | StatusCode::BadCommunicationError)) => {
+                return Err(e)
+            }
+            Err(e) => return Ok(ServiceFault::new(request_handle, e).into()),
+        };
+
+        if response.status != 200 {
+            error!(
+                "HTTPS request to {} failed with status {}",
+                self.endpoint_url, response.status
+            );
+            let status = if response.status == 413 {
+                StatusCode::BadRequestTooLarge
+            } else {
+                StatusCode::BadCommunicationError
+            };
+            return Ok(ServiceFault::new(request_handle, status).into());
+        }
+        self.idle.lock().push(stream);
+
+        let encoding = response
+            .content_type
+            .as_deref()
+            .and_then(HttpsEncoding::from_content_type)
+            .unwrap_or(self.encoding);
+        let ctx = trace_read_lock!(self.context);
+        Ok(
+            https::decode_message(&response.body, encoding, &ctx.context())
+                .unwrap_or_else(|e| ServiceFault::new(request_handle, e).into()),
+        )
+    }
+}
+
+fn open_secure_channel_response(
+    channel: &mut SecureChannel,
+    request: &OpenSecureChannelRequest,
+) -> OpenSecureChannelResponse {
+    let token_id = channel.token_id() + 1;
+    channel.set_token_id(token_id);
+    OpenSecureChannelResponse {
+        response_header: ResponseHeader::new_good(&request.request_header),
+        server_protocol_version: 0,
+        security_token: ChannelSecurityToken {
+            channel_id: channel.secure_channel_id(),
+            token_id,
+            created_at: DateTime::now(),
+            revised_lifetime: request.requested_lifetime,
+        },
+        server_nonce: ByteString::null(),
+    }
+}
+
+/// Act as the server end of an OPC-UA binary connection on `stream`, forwarding
+/// service requests to the HTTPS server. Closing the HTTPS client closes `stream`.
+async fn run_bridge(
+    client: Arc<HttpsClient>,
+    mut channel: SecureChannel,
+    stream: DuplexStream,
+    config: TransportConfiguration,
+) {
+    let (read, mut write) = tokio::io::split(stream);
+    let mut read = FramedRead::new(read, TcpCodec::new(channel.decoding_options()));
+    let mut send_buffer = SendBuffer::new(
+        config.recv_buffer_size,
+        config.max_message_size,
+        config.max_chunk_count,
+    );
+    let (response_send, mut response_recv) = tokio::sync::mpsc::unbounded_channel();
+    let mut pending_chunks: Vec<MessageChunk> = Vec::new();
+
+    loop {
+        while send_buffer.should_encode_chunks() || send_buffer.can_read() {
+            if send_buffer.should_encode_chunks() {
+                if let Err(e) = send_buffer.encode_next_chunk(&channel) {
+                    error!("Failed to encode HTTPS response: {e}");
+                    return;
+                }
+            }
+            if send_buffer.can_read() && send_buffer.read_into_async(&mut write).await.is_err() {
+                return;
+            }
+        }
+
+        let (request_id, response) = tokio::select! {
+            incoming = read.next() => {
+                let chunk = match incoming {
+                    Some(Ok(Message::Hello(hello))) => {
+                        let ack = AcknowledgeMessage::new(
+                            0,
+                            hello.send_buffer_size,
+                            hello.receive_buffer_size,
+                            hello.max_message_size,
+                            hello.max_chunk_count,
+                        );
+                        send_buffer.revise(
+                            ack.send_buffer_size as usize,
+                            ack.max_message_size as usize,
+                            ack.max_chunk_count as usize,
+                        );
+                        send_buffer.write_ack(ack);
+                        continue;
+                    }
+                    Some(Ok(Message::Chunk(chunk))) => chunk,
+                    Some(Ok(message)) => {
+                        error!("Unexpected message from client: {message:?}");
+                        return;
+                    }
+                    Some(Err(e)) => {
+                        debug!("Failed to read message from client: {e}");
+                        return;
+                    }
+                    None => return,
+                };
+
+                let request = channel
+                    .verify_and_remove_security(&chunk.data)
+                    .and_then(|chunk| {
+                        let chunk_info = chunk.chunk_info(&channel)?;
+                        pending_chunks.push(chunk);
+                        if chunk_info.message_header.is_final == MessageIsFinalType::Intermediate {
+                            return Ok(None);
+                        }
+                        let request_id = chunk_info.sequence_header.request_id;
+                        let request = Chunker::decode::<RequestMessage>(&pending_chunks, &channel, None);
+                        pending_chunks.clear();
+                        request.map(|r| Some((request_id, r)))
+                    });
+                let (request_id, request) = match request {
+                    Ok(Some(r)) => r,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        error!("Invalid message from client: {e}");
+                        return;
+                    }
+                };
+
+                match request {
+                    RequestMessage::OpenSecureChannel(request) => (
+                        request_id,
+                        open_secure_channel_response(&mut channel, &request).into(),
+                    ),
+                    RequestMessage::CloseSecureChannel(_) => return,
+                    request => {
+                        let client = client.clone();
+                        let response_send = response_send.clone();
+                        tokio::spawn(async move {
+                            let response = client.send(request).await;
+                            let _ = response_send.send((request_id, response));
+                        });
+                        continue;
+                    }
+                }
+            }
+            Some((request_id, response)) = response_recv.recv() => match response {
+                Ok(response) => (request_id, response),
+                Err(e) => {
+                    error!("Lost connection to HTTPS server {}: {e}", client.endpoint_url);
+                    return;
+                }
+            }
+        };
+
+        let request_handle = response.request_handle();
+        if let Err(e) = send_buffer.write(request_id, response, &channel) {
+            error!("Failed to encode response to client: {e}");
+            let fault = ResponseMessage::from(ServiceFault::new(request_handle, e.status()));
+            if send_buffer.write(request_id, fault, &channel).is_err() {
+                return;
+            }
+        }
+    }
+}
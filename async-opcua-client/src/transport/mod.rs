@@ -1,10 +1,54 @@
 mod channel;
 mod connect;
 mod core;
+#[cfg(feature = "https")]
+pub mod https;
 mod state;
 pub mod tcp;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 pub use channel::{AsyncSecureChannel, SecureChannelEventLoop};
 pub use connect::Connector;
 pub(crate) use core::OutgoingMessage;
 pub use core::TransportPollResult;
+
+use std::sync::Arc;
+
+use opcua_core::{
+    comms::url::{is_opc_ua_binary_url, is_opc_ua_https_url, is_opc_ua_websocket_url},
+    sync::RwLock,
+};
+use opcua_crypto::CertificateStore;
+use opcua_types::EndpointDescription;
+
+/// Check if the client supports connecting to `url`. WebSocket URLs
+/// require the `websocket` feature, HTTPS URLs require the `https` feature.
+pub(crate) fn is_supported_url(url: &str) -> bool {
+    is_opc_ua_binary_url(url)
+        || cfg!(feature = "websocket") && is_opc_ua_websocket_url(url)
+        || cfg!(feature = "https") && is_opc_ua_https_url(url)
+}
+
+/// Create a connector for the transport given by the scheme of the URL of `endpoint`.
+/// For HTTPS, the message encoding is given by the transport profile of the endpoint.
+#[allow(unused_variables)]
+pub(crate) fn connector_for_endpoint(
+    endpoint: &EndpointDescription,
+    certificate_store: Arc<RwLock<CertificateStore>>,
+) -> Box<dyn Connector> {
+    let url = endpoint.endpoint_url.as_ref();
+    #[cfg(feature = "websocket")]
+    if is_opc_ua_websocket_url(url) {
+        return Box::new(websocket::WebSocketConnector::new(certificate_store));
+    }
+    #[cfg(feature = "https")]
+    if is_opc_ua_https_url(url) {
+        use opcua_core::comms::https::HttpsEncoding;
+        let encoding =
+            HttpsEncoding::from_transport_profile_uri(endpoint.transport_profile_uri.as_ref())
+                .unwrap_or(HttpsEncoding::Binary);
+        return Box::new(https::HttpsConnector::new(certificate_store, encoding));
+    }
+    Box::new(tcp::TcpConnector)
+}
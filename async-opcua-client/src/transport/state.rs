@@ -14,8 +14,8 @@ use opcua_core::{
 };
 use opcua_crypto::SecurityPolicy;
 use opcua_types::{
-    DateTime, DiagnosticBits, IntegerId, MessageSecurityMode, NodeId, OpenSecureChannelRequest,
-    RequestHeader, SecurityTokenRequestType, StatusCode,
+    ByteString, DateTime, DiagnosticBits, IntegerId, MessageSecurityMode, NodeId,
+    OpenSecureChannelRequest, RequestHeader, SecurityTokenRequestType, StatusCode,
 };
 
 pub(crate) type RequestSend = tokio::sync::mpsc::Sender<OutgoingMessage>;
@@ -113,8 +113,12 @@ impl SecureChannelState {
 
         let (security_mode, security_policy, client_nonce) = {
             let mut secure_channel = trace_write_lock!(self.secure_channel);
-            let client_nonce = secure_channel.security_policy().random_nonce();
-            secure_channel.set_local_nonce(client_nonce.as_ref());
+            let client_nonce = if secure_channel.security_policy() == SecurityPolicy::None {
+                ByteString::null()
+            } else {
+                secure_channel.create_random_nonce();
+                secure_channel.local_nonce_as_byte_string()
+            };
             (
                 secure_channel.security_mode(),
                 secure_channel.security_policy(),
@@ -179,7 +183,7 @@ impl SecureChannelState {
                         || secure_channel.security_mode() == MessageSecurityMode::SignAndEncrypt)
                 {
                     secure_channel.set_remote_nonce_from_byte_string(&response.server_nonce)?;
-                    secure_channel.derive_keys();
+                    secure_channel.derive_keys()?;
                 }
             }
             Ok(())
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use super::connect::{Connector, Transport};
 use super::core::{OutgoingMessage, TransportPollResult, TransportState};
@@ -11,16 +11,17 @@ use opcua_core::{
     comms::{
         buffer::SendBuffer,
         secure_channel::SecureChannel,
+        stream::BoxedStream,
         tcp_codec::{Message, TcpCodec},
         tcp_types::HelloMessage,
-        url::hostname_port_from_url,
+        url::{hostname_port_from_url, url_matches_except_host},
     },
     trace_read_lock,
 };
 use opcua_types::StatusCode;
 use parking_lot::RwLock;
 use tokio::io::{AsyncWriteExt, ReadHalf, WriteHalf};
-use tokio::net::TcpStream;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 use tokio_util::codec::FramedRead;
 
 #[derive(Debug, Clone, Copy)]
@@ -32,8 +33,8 @@ enum TransportCloseState {
 
 pub struct TcpTransport {
     state: TransportState,
-    read: FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-    write: WriteHalf<TcpStream>,
+    read: FramedRead<ReadHalf<BoxedStream>, TcpCodec>,
+    write: WriteHalf<BoxedStream>,
     send_buffer: SendBuffer,
     should_close: bool,
     closed: TransportCloseState,
@@ -48,114 +49,128 @@ pub struct TransportConfiguration {
     pub max_chunk_count: usize,
 }
 
+/// Connector for `opc.tcp` that connects to the server.
 pub struct TcpConnector;
 
-impl TcpConnector {
-    async fn connect_inner(
-        secure_channel: &RwLock<SecureChannel>,
-        config: &TransportConfiguration,
-        endpoint_url: &str,
-    ) -> Result<
-        (
-            FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-            WriteHalf<TcpStream>,
-            AcknowledgeMessage,
-        ),
-        StatusCode,
-    > {
-        let (host, port) = hostname_port_from_url(
-            endpoint_url,
-            opcua_core::constants::DEFAULT_OPC_UA_SERVER_PORT,
-        )?;
-
-        let addr = {
-            let addr = format!("{}:{}", host, port);
-            match tokio::net::lookup_host(addr).await {
-                Ok(mut addrs) => {
-                    if let Some(addr) = addrs.next() {
-                        addr
-                    } else {
-                        error!(
-                            "Invalid address {}, does not resolve to any socket",
-                            endpoint_url
-                        );
-                        return Err(StatusCode::BadTcpEndpointUrlInvalid);
-                    }
-                }
-                Err(e) => {
-                    error!("Invalid address {}, cannot be parsed {:?}", endpoint_url, e);
+pub(super) type HandshakeResult = Result<
+    (
+        FramedRead<ReadHalf<BoxedStream>, TcpCodec>,
+        WriteHalf<BoxedStream>,
+        AcknowledgeMessage,
+    ),
+    StatusCode,
+>;
+
+/// Open a TCP connection to the host and port given by `endpoint_url`.
+pub(super) async fn connect_socket(endpoint_url: &str) -> Result<TcpStream, StatusCode> {
+    let (host, port) = hostname_port_from_url(
+        endpoint_url,
+        opcua_core::constants::DEFAULT_OPC_UA_SERVER_PORT,
+    )?;
+
+    let addr = {
+        let addr = format!("{}:{}", host, port);
+        match tokio::net::lookup_host(addr).await {
+            Ok(mut addrs) => {
+                if let Some(addr) = addrs.next() {
+                    addr
+                } else {
+                    error!(
+                        "Invalid address {}, does not resolve to any socket",
+                        endpoint_url
+                    );
                     return Err(StatusCode::BadTcpEndpointUrlInvalid);
                 }
             }
-        };
+            Err(e) => {
+                error!("Invalid address {}, cannot be parsed {:?}", endpoint_url, e);
+                return Err(StatusCode::BadTcpEndpointUrlInvalid);
+            }
+        }
+    };
 
-        debug!("Connecting to {} with url {}", addr, endpoint_url);
+    debug!("Connecting to {} with url {}", addr, endpoint_url);
 
-        let socket = TcpStream::connect(&addr).await.map_err(|err| {
-            error!("Could not connect to host {}, {:?}", addr, err);
-            StatusCode::BadCommunicationError
-        })?;
+    TcpStream::connect(&addr).await.map_err(|err| {
+        error!("Could not connect to host {}, {:?}", addr, err);
+        StatusCode::BadCommunicationError
+    })
+}
 
-        let (reader, mut writer) = tokio::io::split(socket);
+/// Split `stream` into a framed reader and a writer, then perform the hello
+/// handshake on it.
+pub(super) async fn handshake(
+    stream: BoxedStream,
+    secure_channel: &RwLock<SecureChannel>,
+    config: &TransportConfiguration,
+    endpoint_url: &str,
+) -> HandshakeResult {
+    let (reader, writer) = tokio::io::split(stream);
+    let framed_read = {
+        let secure_channel = trace_read_lock!(secure_channel);
+        FramedRead::new(reader, TcpCodec::new(secure_channel.decoding_options()))
+    };
 
-        let hello = HelloMessage::new(
-            endpoint_url,
-            config.send_buffer_size,
-            config.recv_buffer_size,
-            config.max_message_size,
-            config.max_chunk_count,
-        );
-        log::trace!("Send hello message: {hello:?}");
-        let mut framed_read = {
-            let secure_channel = trace_read_lock!(secure_channel);
-            FramedRead::new(reader, TcpCodec::new(secure_channel.decoding_options()))
-        };
+    hello_handshake(framed_read, writer, config, endpoint_url).await
+}
 
-        writer
-            .write_all(&opcua_types::SimpleBinaryEncodable::encode_to_vec(&hello))
-            .await
-            .map_err(|err| {
-                error!("Cannot send hello to server, err = {}", err);
-                StatusCode::BadCommunicationError
-            })?;
-        let ack = match framed_read.next().await {
-            Some(Ok(Message::Acknowledge(ack))) => {
-                if ack.send_buffer_size > hello.receive_buffer_size {
-                    log::warn!("Acknowledged send buffer size is greater than receive buffer size in hello message!")
-                }
-                if ack.receive_buffer_size > hello.send_buffer_size {
-                    log::warn!("Acknowledged receive buffer size is greater than send buffer size in hello message!")
-                }
-                log::trace!("Received acknowledgement: {:?}", ack);
-                ack
+/// Send a hello message to the server and wait for an acknowledge.
+async fn hello_handshake(
+    mut framed_read: FramedRead<ReadHalf<BoxedStream>, TcpCodec>,
+    mut writer: WriteHalf<BoxedStream>,
+    config: &TransportConfiguration,
+    endpoint_url: &str,
+) -> HandshakeResult {
+    let hello = HelloMessage::new(
+        endpoint_url,
+        config.send_buffer_size,
+        config.recv_buffer_size,
+        config.max_message_size,
+        config.max_chunk_count,
+    );
+    log::trace!("Send hello message: {hello:?}");
+
+    writer
+        .write_all(&opcua_types::SimpleBinaryEncodable::encode_to_vec(&hello))
+        .await
+        .map_err(|err| {
+            error!("Cannot send hello to server, err = {}", err);
+            StatusCode::BadCommunicationError
+        })?;
+    let ack = match framed_read.next().await {
+        Some(Ok(Message::Acknowledge(ack))) => {
+            if ack.send_buffer_size > hello.receive_buffer_size {
+                log::warn!("Acknowledged send buffer size is greater than receive buffer size in hello message!")
             }
-            other => {
-                error!(
-                    "Unexpected error while waiting for server ACK. Expected ACK, got {:?}",
-                    other
-                );
-                return Err(StatusCode::BadConnectionClosed);
+            if ack.receive_buffer_size > hello.send_buffer_size {
+                log::warn!("Acknowledged receive buffer size is greater than send buffer size in hello message!")
             }
-        };
+            log::trace!("Received acknowledgement: {:?}", ack);
+            ack
+        }
+        other => {
+            error!(
+                "Unexpected error while waiting for server ACK. Expected ACK, got {:?}",
+                other
+            );
+            return Err(StatusCode::BadConnectionClosed);
+        }
+    };
 
-        Ok((framed_read, writer, ack))
-    }
+    Ok((framed_read, writer, ack))
 }
 
-#[async_trait]
-impl Connector for TcpConnector {
-    async fn connect(
-        &self,
+impl TcpTransport {
+    pub(super) fn new(
         channel: Arc<RwLock<SecureChannel>>,
         outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
         config: TransportConfiguration,
-        endpoint_url: &str,
-    ) -> Result<TcpTransport, StatusCode> {
-        let (framed_read, writer, ack) =
-            match Self::connect_inner(&channel, &config, endpoint_url).await {
-                Ok(k) => k,
-                Err(status) => return Err(status),
-            };
+        (framed_read, writer, ack): (
+            FramedRead<ReadHalf<BoxedStream>, TcpCodec>,
+            WriteHalf<BoxedStream>,
+            AcknowledgeMessage,
+        ),
+    ) -> Self {
         let mut buffer = SendBuffer::new(
             config.send_buffer_size,
             config.max_message_size,
@@ -167,7 +182,7 @@ impl Connector for TcpConnector {
             ack.max_chunk_count as usize,
         );
 
-        Ok(TcpTransport {
+        TcpTransport {
             state: TransportState::new(
                 channel,
                 outgoing_recv,
@@ -179,7 +194,134 @@ impl Connector for TcpConnector {
             send_buffer: buffer,
             should_close: false,
             closed: TransportCloseState::Open,
-        })
+        }
+    }
+}
+
+#[async_trait]
+impl Connector for TcpConnector {
+    async fn connect(
+        &self,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
+        endpoint_url: &str,
+    ) -> Result<TcpTransport, StatusCode> {
+        let socket = connect_socket(endpoint_url).await?;
+        let handshake = handshake(Box::new(socket), &channel, &config, endpoint_url).await?;
+        Ok(TcpTransport::new(channel, outgoing_recv, config, handshake))
+    }
+}
+
+/// Connector for `opc.tcp` reverse connect. Instead of connecting to the server,
+/// this waits for the server to connect to a listening socket and send a
+/// `ReverseHello` message, then proceeds with the normal connection handshake.
+///
+/// Each time the session connects, or reconnects, a new connection is accepted
+/// on the listener. Connections from servers that do not match the expected
+/// server URI or endpoint URL are closed.
+pub struct ReverseConnector {
+    listener: TcpListener,
+    server_uri: Option<String>,
+}
+
+impl ReverseConnector {
+    /// Create a new reverse connector accepting connections on `listener`.
+    pub fn new(listener: TcpListener) -> Self {
+        Self {
+            listener,
+            server_uri: None,
+        }
+    }
+
+    /// Create a new reverse connector listening on `addr`.
+    pub async fn bind(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        Ok(Self::new(TcpListener::bind(addr).await?))
+    }
+
+    /// Only accept connections from the server with this application URI.
+    pub fn server_uri(mut self, server_uri: impl Into<String>) -> Self {
+        self.server_uri = Some(server_uri.into());
+        self
+    }
+
+    /// Get the local address the connector is listening on.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Wait for a valid reverse hello message on an accepted connection.
+    async fn accept_reverse_hello(
+        &self,
+        secure_channel: &RwLock<SecureChannel>,
+        endpoint_url: &str,
+    ) -> Result<
+        (
+            FramedRead<ReadHalf<BoxedStream>, TcpCodec>,
+            WriteHalf<BoxedStream>,
+        ),
+        StatusCode,
+    > {
+        let (socket, addr) = self.listener.accept().await.map_err(|e| {
+            error!("Failed to accept reverse connection: {e}");
+            StatusCode::BadCommunicationError
+        })?;
+        debug!("Accepted reverse connection from {addr}");
+
+        let (reader, writer) = tokio::io::split(Box::new(socket) as BoxedStream);
+        let mut framed_read = {
+            let secure_channel = trace_read_lock!(secure_channel);
+            FramedRead::new(reader, TcpCodec::new(secure_channel.decoding_options()))
+        };
+
+        let reverse_hello = match framed_read.next().await {
+            Some(Ok(Message::ReverseHello(reverse_hello))) => reverse_hello,
+            other => {
+                error!("Expected reverse hello from {addr}, got {:?}", other);
+                return Err(StatusCode::BadConnectionRejected);
+            }
+        };
+        log::trace!("Received reverse hello: {reverse_hello:?}");
+
+        if !reverse_hello.is_valid() {
+            error!("Reverse hello from {addr} is invalid");
+            return Err(StatusCode::BadConnectionRejected);
+        }
+        if self
+            .server_uri
+            .as_ref()
+            .is_some_and(|uri| uri != reverse_hello.server_uri.as_ref())
+        {
+            error!(
+                "Reverse hello from {addr} has unexpected server uri {}",
+                reverse_hello.server_uri
+            );
+            return Err(StatusCode::BadConnectionRejected);
+        }
+        if !url_matches_except_host(reverse_hello.endpoint_url.as_ref(), endpoint_url) {
+            error!(
+                "Reverse hello from {addr} has endpoint url {}, expected {endpoint_url}",
+                reverse_hello.endpoint_url
+            );
+            return Err(StatusCode::BadTcpEndpointUrlInvalid);
+        }
+
+        Ok((framed_read, writer))
+    }
+}
+
+#[async_trait]
+impl Connector for ReverseConnector {
+    async fn connect(
+        &self,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
+        endpoint_url: &str,
+    ) -> Result<TcpTransport, StatusCode> {
+        let (framed_read, writer) = self.accept_reverse_hello(&channel, endpoint_url).await?;
+        let handshake = hello_handshake(framed_read, writer, &config, endpoint_url).await?;
+        Ok(TcpTransport::new(channel, outgoing_recv, config, handshake))
     }
 }
 
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::error;
+use opcua_core::{
+    comms::{
+        secure_channel::SecureChannel,
+        tls,
+        url::{hostname_port_from_url, OPC_WSS_SCHEME},
+        websocket,
+    },
+    sync::RwLock,
+};
+use opcua_crypto::CertificateStore;
+use opcua_types::StatusCode;
+use tokio_rustls::{rustls::pki_types::ServerName, TlsConnector};
+
+use super::{
+    connect::Connector,
+    tcp::{connect_socket, handshake, TcpTransport, TransportConfiguration},
+    OutgoingMessage,
+};
+
+/// Connector for `opc.ws` and `opc.wss`, OPC-UA over WebSockets.
+///
+/// For `opc.wss`, the TLS certificate of the server is validated using the
+/// client certificate store, the same way as application instance certificates.
+pub struct WebSocketConnector {
+    certificate_store: Arc<RwLock<CertificateStore>>,
+}
+
+impl WebSocketConnector {
+    /// Create a new WebSocket connector, validating TLS certificates against
+    /// `certificate_store`.
+    pub fn new(certificate_store: Arc<RwLock<CertificateStore>>) -> Self {
+        Self { certificate_store }
+    }
+}
+
+#[async_trait]
+impl Connector for WebSocketConnector {
+    async fn connect(
+        &self,
+        channel: Arc<RwLock<SecureChannel>>,
+        outgoing_recv: tokio::sync::mpsc::Receiver<OutgoingMessage>,
+        config: TransportConfiguration,
+        endpoint_url: &str,
+    ) -> Result<TcpTransport, StatusCode> {
+        let socket = connect_socket(endpoint_url).await?;
+
+        let stream = if endpoint_url.starts_with(&format!("{OPC_WSS_SCHEME}://")) {
+            let (host, _) = hostname_port_from_url(
+                endpoint_url,
+                opcua_core::constants::DEFAULT_OPC_UA_SERVER_PORT,
+            )?;
+            let server_name = ServerName::try_from(host).map_err(|e| {
+                error!("Invalid TLS server name in {endpoint_url}: {e}");
+                StatusCode::BadTcpEndpointUrlInvalid
+            })?;
+            let tls_config = tls::client_config(self.certificate_store.clone())?;
+            let socket = TlsConnector::from(tls_config)
+                .connect(server_name, socket)
+                .await
+                .map_err(|e| {
+                    error!("TLS handshake with {endpoint_url} failed: {e}");
+                    StatusCode::BadSecureChannelClosed
+                })?;
+            websocket::connect(endpoint_url, socket).await?
+        } else {
+            websocket::connect(endpoint_url, socket).await?
+        };
+
+        let handshake = handshake(stream, &channel, &config, endpoint_url).await?;
+        Ok(TcpTransport::new(channel, outgoing_recv, config, handshake))
+    }
+}
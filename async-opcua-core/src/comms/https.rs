@@ -0,0 +1,508 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Contains the OPC-UA over HTTPS transport mapping.
+//!
+//! Each service request is sent as the body of an HTTP `POST`, and the service response
+//! is returned as the body of the HTTP response. Messages are either encoded using
+//! OPC-UA binary, as the encoding ID followed by the message, like in a secure channel message,
+//! or using reversible OPC-UA JSON, as an extension object.
+//!
+//! This only contains a minimal HTTP/1.1 implementation, enough to exchange messages with
+//! a `Content-Length`. TLS is handled by the caller.
+
+use std::io::{Cursor, Read, Write};
+
+use log::{debug, error};
+use opcua_types::{
+    json::{JsonDecodable, JsonEncodable, JsonStreamReader, JsonStreamWriter, JsonWriter},
+    profiles, BinaryDecodable, BinaryEncodable, Context, ExtensionObject, NodeId, StatusCode,
+};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::Message;
+
+/// Content type of OPC-UA binary encoded messages.
+pub const CONTENT_TYPE_BINARY: &str = "application/opcua+uabinary";
+/// Content type of OPC-UA JSON encoded messages.
+pub const CONTENT_TYPE_JSON: &str = "application/opcua+uajson";
+
+/// Maximum size of the request line and headers of an HTTP message.
+const MAX_HEADER_SIZE: usize = 16384;
+/// Maximum number of headers in an HTTP message.
+const MAX_HEADERS: usize = 32;
+/// Maximum size of an HTTP message body when no limit is configured.
+pub const DEFAULT_MAX_BODY_SIZE: usize = opcua_types::constants::MAX_MESSAGE_SIZE;
+
+/// Encoding used for messages sent over HTTPS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpsEncoding {
+    /// OPC-UA binary, `application/opcua+uabinary`.
+    Binary,
+    /// Reversible OPC-UA JSON, `application/opcua+uajson`.
+    Json,
+}
+
+impl HttpsEncoding {
+    /// Get the HTTP content type for this encoding.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Binary => CONTENT_TYPE_BINARY,
+            Self::Json => CONTENT_TYPE_JSON,
+        }
+    }
+
+    /// Get the encoding for the HTTP content type `content_type`, ignoring any parameters.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim();
+        if media_type.eq_ignore_ascii_case(CONTENT_TYPE_BINARY) {
+            Some(Self::Binary)
+        } else if media_type.eq_ignore_ascii_case(CONTENT_TYPE_JSON) {
+            Some(Self::Json)
+        } else {
+            None
+        }
+    }
+
+    /// Get the transport profile URI of HTTPS with this encoding.
+    pub fn transport_profile_uri(&self) -> &'static str {
+        match self {
+            Self::Binary => profiles::TRANSPORT_PROFILE_URI_HTTPS_BINARY,
+            Self::Json => profiles::TRANSPORT_PROFILE_URI_HTTPS_JSON,
+        }
+    }
+
+    /// Get the encoding for the transport profile URI `uri`, if it is an HTTPS profile.
+    pub fn from_transport_profile_uri(uri: &str) -> Option<Self> {
+        match uri {
+            profiles::TRANSPORT_PROFILE_URI_HTTPS_BINARY => Some(Self::Binary),
+            profiles::TRANSPORT_PROFILE_URI_HTTPS_JSON => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Encode `message` as the body of an HTTP message.
+pub fn encode_message<T>(
+    message: T,
+    encoding: HttpsEncoding,
+    ctx: &Context<'_>,
+) -> Result<Vec<u8>, StatusCode>
+where
+    T: Message + Into<ExtensionObject>,
+{
+    let res = match encoding {
+        HttpsEncoding::Binary => {
+            let node_id = message.type_id();
+            let mut body = Vec::with_capacity(node_id.byte_len(ctx) + message.byte_len(ctx));
+            BinaryEncodable::encode(&node_id, &mut body, ctx)
+                .and_then(|_| message.encode(&mut body, ctx))
+                .map(|_| body)
+        }
+        HttpsEncoding::Json => {
+            let message: ExtensionObject = message.into();
+            let mut body = Vec::new();
+            let res = {
+                let mut stream = JsonStreamWriter::new(&mut body as &mut dyn Write);
+                JsonEncodable::encode(&message, &mut stream, ctx).and_then(|_| {
+                    stream
+                        .finish_document()
+                        .map(|_| ())
+                        .map_err(opcua_types::Error::encoding)
+                })
+            };
+            res.map(|_| body)
+        }
+    };
+    res.map_err(|e| {
+        error!("Failed to encode HTTPS message: {e}");
+        StatusCode::BadEncodingError
+    })
+}
+
+/// Decode a message from the body of an HTTP message.
+pub fn decode_message<T>(
+    body: &[u8],
+    encoding: HttpsEncoding,
+    ctx: &Context<'_>,
+) -> Result<T, StatusCode>
+where
+    T: Message + TryFrom<ExtensionObject, Error = opcua_types::Error>,
+{
+    let mut stream = Cursor::new(body);
+    let res = match encoding {
+        HttpsEncoding::Binary => {
+            <NodeId as BinaryDecodable>::decode(&mut stream, ctx).and_then(|node_id| {
+                let object_id = node_id.as_object_id().map_err(|_| {
+                    opcua_types::Error::decoding(format!("{node_id} is not a message encoding ID"))
+                })?;
+                T::decode_by_object_id(&mut stream, object_id, ctx)
+            })
+        }
+        HttpsEncoding::Json => {
+            let mut stream = JsonStreamReader::new(&mut stream as &mut dyn Read);
+            <ExtensionObject as JsonDecodable>::decode(&mut stream, ctx).and_then(T::try_from)
+        }
+    };
+    res.map_err(|e| {
+        debug!("Failed to decode HTTPS message: {e}");
+        StatusCode::BadDecodingError
+    })
+}
+
+/// Get the value of the `Host` header and the request path for the `https` URL `url`.
+pub fn request_target(url: &str) -> Result<(String, String), StatusCode> {
+    let parsed = url::Url::parse(url).map_err(|e| {
+        error!("Invalid HTTPS URL {url}: {e}");
+        StatusCode::BadTcpEndpointUrlInvalid
+    })?;
+    let Some(host) = parsed.host_str() else {
+        error!("HTTPS URL {url} has no host");
+        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+    };
+    let host = match parsed.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_owned(),
+    };
+    let path = match parsed.query() {
+        Some(query) => format!("{}?{query}", parsed.path()),
+        None => parsed.path().to_owned(),
+    };
+    Ok((host, path))
+}
+
+/// An HTTP request.
+#[derive(Debug)]
+pub struct HttpRequest {
+    /// Request method.
+    pub method: String,
+    /// Request path.
+    pub path: String,
+    /// Value of the `Content-Type` header, if present.
+    pub content_type: Option<String>,
+    /// Request body.
+    pub body: Vec<u8>,
+}
+
+/// An HTTP response.
+#[derive(Debug)]
+pub struct HttpResponse {
+    /// Response status code.
+    pub status: u16,
+    /// Value of the `Content-Type` header, if present.
+    pub content_type: Option<String>,
+    /// Response body.
+    pub body: Vec<u8>,
+}
+
+/// Read the request line or status line and the headers of an HTTP message.
+/// Returns `None` if the stream is closed before the message starts.
+async fn read_head(
+    stream: &mut (impl AsyncBufRead + Unpin),
+) -> Result<Option<Vec<u8>>, StatusCode> {
+    let mut head = Vec::new();
+    loop {
+        let len = head.len();
+        let read = (&mut *stream)
+            .take((MAX_HEADER_SIZE - len) as u64)
+            .read_until(b'\n', &mut head)
+            .await
+            .map_err(|e| {
+                debug!("Failed to read HTTP message: {e}");
+                StatusCode::BadCommunicationError
+            })?;
+        if read == 0 {
+            if len == 0 {
+                return Ok(None);
+            }
+            debug!("HTTP message headers are incomplete or too large");
+            return Err(StatusCode::BadCommunicationError);
+        }
+        if head == b"\r\n" {
+            // Skip empty lines before the message, as allowed by RFC 9112.
+            head.clear();
+        } else if head.ends_with(b"\r\n\r\n") {
+            return Ok(Some(head));
+        }
+    }
+}
+
+/// Get the content type and length from a list of parsed headers.
+fn content_headers(
+    headers: &[httparse::Header<'_>],
+    max_body_size: usize,
+) -> Result<(Option<String>, usize), StatusCode> {
+    let mut content_type = None;
+    let mut content_length = 0;
+    for header in headers {
+        let value = std::str::from_utf8(header.value).map_err(|_| {
+            debug!("Invalid value of HTTP header {}", header.name);
+            StatusCode::BadCommunicationError
+        })?;
+        if header.name.eq_ignore_ascii_case("Content-Type") {
+            content_type = Some(value.trim().to_owned());
+        } else if header.name.eq_ignore_ascii_case("Content-Length") {
+            content_length = value.trim().parse().map_err(|_| {
+                debug!("Invalid HTTP content length {value}");
+                StatusCode::BadCommunicationError
+            })?;
+        } else if header.name.eq_ignore_ascii_case("Transfer-Encoding") {
+            debug!("HTTP transfer encoding {value} is not supported");
+            return Err(StatusCode::BadCommunicationError);
+        }
+    }
+    let max_body_size = if max_body_size > 0 {
+        max_body_size
+    } else {
+        DEFAULT_MAX_BODY_SIZE
+    };
+    if content_length > max_body_size {
+        debug!("HTTP message body of {content_length} bytes exceeds the limit of {max_body_size}");
+        return Err(StatusCode::BadRequestTooLarge);
+    }
+    Ok((content_type, content_length))
+}
+
+async fn read_body(
+    stream: &mut (impl AsyncBufRead + Unpin),
+    content_length: usize,
+) -> Result<Vec<u8>, StatusCode> {
+    let mut body = vec![0u8; content_length];
+    stream.read_exact(&mut body).await.map_err(|e| {
+        debug!("Failed to read HTTP message body: {e}");
+        StatusCode::BadCommunicationError
+    })?;
+    Ok(body)
+}
+
+/// Read an HTTP request from `stream`. Returns `None` if the stream was closed
+/// before a new request started. Fails with `BadRequestTooLarge` if the body is
+/// larger than `max_body_size`, or [`DEFAULT_MAX_BODY_SIZE`] if it is zero.
+pub async fn read_request(
+    stream: &mut (impl AsyncBufRead + Unpin),
+    max_body_size: usize,
+) -> Result<Option<HttpRequest>, StatusCode> {
+    let Some(head) = read_head(stream).await? else {
+        return Ok(None);
+    };
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut request = httparse::Request::new(&mut headers);
+    if !matches!(request.parse(&head), Ok(httparse::Status::Complete(_))) {
+        debug!("Invalid HTTP request");
+        return Err(StatusCode::BadCommunicationError);
+    }
+    let (content_type, content_length) = content_headers(request.headers, max_body_size)?;
+    let method = request.method.unwrap_or_default().to_owned();
+    let path = request.path.unwrap_or_default().to_owned();
+    let body = read_body(stream, content_length).await?;
+    Ok(Some(HttpRequest {
+        method,
+        path,
+        content_type,
+        body,
+    }))
+}
+
+/// Read an HTTP response from `stream`. Fails with `BadResponseTooLarge` if the body
+/// is larger than `max_body_size`, or [`DEFAULT_MAX_BODY_SIZE`] if it is zero.
+pub async fn read_response(
+    stream: &mut (impl AsyncBufRead + Unpin),
+    max_body_size: usize,
+) -> Result<HttpResponse, StatusCode> {
+    let Some(head) = read_head(stream).await? else {
+        debug!("Connection closed while waiting for HTTP response");
+        return Err(StatusCode::BadConnectionClosed);
+    };
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut response = httparse::Response::new(&mut headers);
+    if !matches!(response.parse(&head), Ok(httparse::Status::Complete(_))) {
+        debug!("Invalid HTTP response");
+        return Err(StatusCode::BadCommunicationError);
+    }
+    let (content_type, content_length) =
+        content_headers(response.headers, max_body_size).map_err(|e| {
+            if e == StatusCode::BadRequestTooLarge {
+                StatusCode::BadResponseTooLarge
+            } else {
+                e
+            }
+        })?;
+    let status = response.code.unwrap_or_default();
+    let body = read_body(stream, content_length).await?;
+    Ok(HttpResponse {
+        status,
+        content_type,
+        body,
+    })
+}
+
+/// Write an HTTP `POST` request to `stream`.
+pub async fn write_request(
+    stream: &mut (impl AsyncWrite + Unpin),
+    host: &str,
+    path: &str,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let head = format!(
+        "POST {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: {content_type}\r\nAccept: {content_type}\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    );
+    write_message(stream, head, body).await
+}
+
+/// Write an HTTP response to `stream`.
+pub async fn write_response(
+    stream: &mut (impl AsyncWrite + Unpin),
+    status: u16,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {status} {}\r\n", reason_phrase(status));
+    if let Some(content_type) = content_type {
+        head.push_str(&format!("Content-Type: {content_type}\r\n"));
+    }
+    head.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+    write_message(stream, head, body).await
+}
+
+async fn write_message(
+    stream: &mut (impl AsyncWrite + Unpin),
+    head: String,
+    body: &[u8],
+) -> std::io::Result<()> {
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        415 => "Unsupported Media Type",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_types::{
+        ContextOwned, DateTime, NodeId, ReadRequest, ReadValueId, RequestHeader, TimestampsToReturn,
+    };
+    use tokio::io::BufReader;
+
+    use crate::RequestMessage;
+
+    use super::*;
+
+    fn test_request() -> RequestMessage {
+        ReadRequest {
+            // JSON only encodes timestamps to millisecond precision.
+            request_header: RequestHeader {
+                timestamp: DateTime::ymd_hms(2024, 1, 1, 12, 0, 0),
+                ..RequestHeader::dummy()
+            },
+            max_age: 0.0,
+            timestamps_to_return: TimestampsToReturn::Both,
+            nodes_to_read: Some(vec![ReadValueId::from(NodeId::new(1, "foo"))]),
+        }
+        .into()
+    }
+
+    #[test]
+    fn content_type() {
+        assert_eq!(
+            HttpsEncoding::from_content_type("application/opcua+uabinary"),
+            Some(HttpsEncoding::Binary)
+        );
+        assert_eq!(
+            HttpsEncoding::from_content_type("Application/OPCUA+UAJSON; charset=utf-8"),
+            Some(HttpsEncoding::Json)
+        );
+        assert_eq!(HttpsEncoding::from_content_type("application/json"), None);
+    }
+
+    #[test]
+    fn encode_decode() {
+        let ctx_owned = ContextOwned::default();
+        let ctx = ctx_owned.context();
+        let request = test_request();
+        for encoding in [HttpsEncoding::Binary, HttpsEncoding::Json] {
+            let body = encode_message(request.clone(), encoding, &ctx).unwrap();
+            let decoded: RequestMessage = decode_message(&body, encoding, &ctx).unwrap();
+            assert_eq!(decoded, request);
+        }
+        let body = encode_message(request, HttpsEncoding::Json, &ctx).unwrap();
+        assert!(decode_message::<RequestMessage>(&body, HttpsEncoding::Binary, &ctx).is_err());
+    }
+
+    #[test]
+    fn target() {
+        assert_eq!(
+            request_target("https://localhost:4843/UA/Server").unwrap(),
+            ("localhost:4843".to_owned(), "/UA/Server".to_owned())
+        );
+        assert_eq!(
+            request_target("https://localhost").unwrap(),
+            ("localhost".to_owned(), "/".to_owned())
+        );
+        assert!(request_target("localhost").is_err());
+    }
+
+    #[tokio::test]
+    async fn request_response() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (mut client, mut server) = (BufReader::new(client), BufReader::new(server));
+
+        write_request(&mut client, "localhost", "/", CONTENT_TYPE_BINARY, b"hello")
+            .await
+            .unwrap();
+        let request = read_request(&mut server, 0).await.unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/");
+        assert_eq!(request.content_type.as_deref(), Some(CONTENT_TYPE_BINARY));
+        assert_eq!(request.body, b"hello");
+
+        write_response(&mut server, 200, Some(CONTENT_TYPE_JSON), b"{}")
+            .await
+            .unwrap();
+        let response = read_response(&mut client, 0).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type.as_deref(), Some(CONTENT_TYPE_JSON));
+        assert_eq!(response.body, b"{}");
+
+        // Bodies over the limit are rejected.
+        write_request(&mut client, "localhost", "/", CONTENT_TYPE_BINARY, b"hello")
+            .await
+            .unwrap();
+        assert_eq!(
+            read_request(&mut server, 4).await.unwrap_err(),
+            StatusCode::BadRequestTooLarge
+        );
+
+        // Without a configured limit, the body is still capped.
+        let head = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            DEFAULT_MAX_BODY_SIZE + 1
+        );
+        client.write_all(head.as_bytes()).await.unwrap();
+        client.flush().await.unwrap();
+        assert_eq!(
+            read_request(&mut server, 0).await.unwrap_err(),
+            StatusCode::BadRequestTooLarge
+        );
+
+        // Closing the stream ends the request stream.
+        drop(client);
+        let mut rest = Vec::new();
+        server.read_to_end(&mut rest).await.unwrap();
+        assert!(read_request(&mut server, 0).await.unwrap().is_none());
+    }
+}
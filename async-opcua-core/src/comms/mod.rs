@@ -7,10 +7,17 @@
 
 pub mod buffer;
 pub mod chunker;
+#[cfg(feature = "https")]
+pub mod https;
 pub mod message_chunk;
 pub mod message_chunk_info;
 pub mod secure_channel;
 pub mod security_header;
+pub mod stream;
 pub mod tcp_codec;
 pub mod tcp_types;
+#[cfg(feature = "tls")]
+pub mod tls;
 pub mod url;
+#[cfg(feature = "websocket")]
+pub mod websocket;
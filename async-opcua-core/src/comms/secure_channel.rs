@@ -18,15 +18,16 @@ use log::{error, trace};
 
 use opcua_crypto::{
     aeskey::AesKey,
+    ecc::EphemeralKey,
     pkey::{KeySize, PrivateKey, PublicKey},
     random,
     x509::X509,
     CertificateStore, SecurityPolicy,
 };
 use opcua_types::{
-    status_code::StatusCode, write_bytes, write_u32, write_u8, ByteString, ChannelSecurityToken,
-    ContextOwned, DateTime, DecodingOptions, Error, MessageSecurityMode, NamespaceMap,
-    SimpleBinaryDecodable,
+    read_u32, status_code::StatusCode, write_bytes, write_u32, write_u8, ByteString,
+    ChannelSecurityToken, ContextOwned, DateTime, DecodingOptions, Error, MessageSecurityMode,
+    NamespaceMap, SimpleBinaryDecodable,
 };
 use parking_lot::RwLock;
 
@@ -79,6 +80,11 @@ pub struct SecureChannel {
     remote_nonce: Vec<u8>,
     /// Our nonce generated while handling open secure channel
     local_nonce: Vec<u8>,
+    /// Our ephemeral key when using an ECC security policy, the local nonce is its public key.
+    local_ephemeral_key: Option<EphemeralKey>,
+    /// Sequence number of the last chunk received, which is part of the nonce of the next
+    /// chunk with security policies using authenticated encryption.
+    last_received_sequence_number: u32,
     /// Client (i.e. other end's set of keys) Symmetric Signing Key, Encrypt Key, IV
     ///
     /// This is a map of channel token ids and their respective keys. We need to keep
@@ -92,6 +98,8 @@ pub struct SecureChannel {
     local_keys: Option<(Vec<u8>, AesKey, Vec<u8>)>,
     /// Decoding options
     encoding_context: Arc<RwLock<ContextOwned>>,
+    /// The store holding our certificates, one per key type.
+    certificate_store: Option<Arc<RwLock<CertificateStore>>>,
 }
 
 impl SecureChannel {
@@ -107,6 +115,8 @@ impl SecureChannel {
             token_created_at: DateTime::now(),
             token_lifetime: 0,
             local_nonce: Vec::new(),
+            local_ephemeral_key: None,
+            last_received_sequence_number: 0,
             remote_nonce: Vec::new(),
             cert: None,
             private_key: None,
@@ -114,9 +124,16 @@ impl SecureChannel {
             local_keys: None,
             encoding_context: Default::default(),
             remote_keys: HashMap::new(),
+            certificate_store: None,
         }
     }
 
+    /// For testing purposes only
+    #[cfg(test)]
+    pub fn set_role(&mut self, role: Role) {
+        self.role = role;
+    }
+
     /// Create a new secure channel with the given certificate store
     /// and role.
     pub fn new(
@@ -151,6 +168,8 @@ impl SecureChannel {
             token_created_at: DateTime::now(),
             token_lifetime: 0,
             local_nonce: Vec::new(),
+            local_ephemeral_key: None,
+            last_received_sequence_number: 0,
             remote_nonce: Vec::new(),
             cert,
             private_key,
@@ -158,9 +177,34 @@ impl SecureChannel {
             local_keys: None,
             encoding_context,
             remote_keys: HashMap::new(),
+            certificate_store: Some(certificate_store),
         }
     }
 
+    /// Use the application certificate and private key for the key type required by
+    /// `security_policy`, so that e.g. RSA and ECC security policies can be used with the
+    /// same certificate store.
+    fn read_own_cert_for_policy(&mut self, security_policy: SecurityPolicy) {
+        let Some(certificate_store) = &self.certificate_store else {
+            return;
+        };
+        let certificate_store = certificate_store.read();
+        self.cert = match certificate_store.read_own_cert_for_policy(security_policy) {
+            Err(e) => {
+                error!("Failed to read own certificate for {security_policy}: {e}");
+                None
+            }
+            Ok(r) => Some(r),
+        };
+        self.private_key = match certificate_store.read_own_pkey_for_policy(security_policy) {
+            Err(e) => {
+                error!("Failed to read own private key for {security_policy}: {e}");
+                None
+            }
+            Ok(r) => Some(r),
+        };
+    }
+
     /// Return `true` if this channel is for a client.
     pub fn is_client_role(&self) -> bool {
         self.role == Role::Client
@@ -191,6 +235,11 @@ impl SecureChannel {
         self.private_key = private_key;
     }
 
+    /// Get the application private key.
+    pub fn private_key(&self) -> Option<PrivateKey> {
+        self.private_key.clone()
+    }
+
     /// Get the application security mode.
     pub fn security_mode(&self) -> MessageSecurityMode {
         self.security_mode
@@ -338,11 +387,19 @@ impl SecureChannel {
         }
     }
 
-    /// Creates a nonce for the connection. The nonce should be the same size as the symmetric key
+    /// Creates a nonce for the connection. The nonce should be the same size as the symmetric key.
+    ///
+    /// For ECC security policies the nonce is the public key of a new ephemeral key.
     pub fn create_random_nonce(&mut self) {
-        self.local_nonce
-            .resize(self.security_policy.secure_channel_nonce_length(), 0);
-        random::bytes(&mut self.local_nonce);
+        if let Some(key) = EphemeralKey::new(self.security_policy) {
+            self.local_nonce = key.nonce();
+            self.local_ephemeral_key = Some(key);
+        } else {
+            self.local_nonce
+                .resize(self.security_policy.secure_channel_nonce_length(), 0);
+            random::bytes(&mut self.local_nonce);
+            self.local_ephemeral_key = None;
+        }
     }
 
     /// Sets the remote certificate
@@ -428,15 +485,46 @@ impl SecureChannel {
     /// The Client keys are used to secure Messages sent by the Client. The Server keys
     /// are used to secure Messages sent by the Server.
     ///
-    pub fn derive_keys(&mut self) {
-        self.insert_remote_keys(
-            self.security_policy
-                .make_secure_channel_keys(&self.local_nonce, &self.remote_nonce),
-        );
-        self.local_keys = Some(
-            self.security_policy
-                .make_secure_channel_keys(&self.remote_nonce, &self.local_nonce),
-        );
+    /// ECC security policies instead derive the keys from a secret shared using the
+    /// ephemeral keys exchanged as nonces, see
+    /// [`SecurityPolicy::make_ecc_secure_channel_keys`].
+    pub fn derive_keys(&mut self) -> Result<(), StatusCode> {
+        if self.security_policy.is_ecc() {
+            let Some(key) = self.local_ephemeral_key.as_ref() else {
+                error!("Cannot derive keys without a local ephemeral key");
+                return Err(StatusCode::BadNonceInvalid);
+            };
+            let shared_secret = key.shared_secret(&self.remote_nonce).map_err(|e| {
+                error!("Failed to compute shared secret: {e}");
+                e.status()
+            })?;
+            let (client_nonce, server_nonce) = if self.is_client_role() {
+                (&self.local_nonce, &self.remote_nonce)
+            } else {
+                (&self.remote_nonce, &self.local_nonce)
+            };
+            let (client_keys, server_keys) = self.security_policy.make_ecc_secure_channel_keys(
+                &shared_secret,
+                client_nonce,
+                server_nonce,
+            );
+            let (local_keys, remote_keys) = if self.is_client_role() {
+                (client_keys, server_keys)
+            } else {
+                (server_keys, client_keys)
+            };
+            self.insert_remote_keys(remote_keys);
+            self.local_keys = Some(local_keys);
+        } else {
+            self.insert_remote_keys(
+                self.security_policy
+                    .make_secure_channel_keys(&self.local_nonce, &self.remote_nonce),
+            );
+            self.local_keys = Some(
+                self.security_policy
+                    .make_secure_channel_keys(&self.remote_nonce, &self.local_nonce),
+            );
+        }
         trace!("Remote nonce = {:?}", self.remote_nonce);
         trace!("Local nonce = {:?}", self.local_nonce);
         trace!(
@@ -444,6 +532,7 @@ impl SecureChannel {
             self.get_remote_keys(self.token_id)
         );
         trace!("Derived local keys = {:?}", self.local_keys);
+        Ok(())
     }
 
     /// Get the deadline as an [`Instant`] for token renewal, used
@@ -467,7 +556,7 @@ impl SecureChannel {
             SecurityHeader::Asymmetric(security_header) => {
                 if !security_header.sender_certificate.is_null() {
                     let x509 = X509::from_byte_string(&security_header.sender_certificate).unwrap();
-                    x509.public_key().unwrap().size()
+                    x509.public_key().unwrap().signature_size()
                 } else {
                     trace!("No certificate / public key was supplied in the asymmetric security header");
                     0
@@ -506,6 +595,8 @@ impl SecureChannel {
         }
 
         match security_header {
+            // OpenSecureChannel messages are never encrypted with ECC security policies.
+            SecurityHeader::Asymmetric(_) if self.security_policy.is_ecc() => (0, 0),
             SecurityHeader::Asymmetric(security_header) => {
                 if security_header.sender_certificate.is_null() {
                     error!("Sender has not supplied a certificate so it is doubtful that this will work");
@@ -521,6 +612,8 @@ impl SecureChannel {
                     )
                 }
             }
+            // Authenticated encryption is done with a stream cipher, so there is no padding.
+            SecurityHeader::Symmetric(_) if self.security_policy.is_aead() => (0, 0),
             SecurityHeader::Symmetric(_) => {
                 // Plain text block size comes from policy
                 (
@@ -772,6 +865,10 @@ impl SecureChannel {
                 }
                 _ => {}
             }
+            // Renewals keep the certificate the channel was opened with.
+            if self.security_policy != security_policy {
+                self.read_own_cert_for_policy(security_policy);
+            }
             self.security_policy = security_policy;
 
             // Asymmetric decrypt and verify
@@ -857,7 +954,12 @@ impl SecureChannel {
             src.to_vec()
         };
 
-        Ok(MessageChunk { data })
+        let chunk = MessageChunk { data };
+        if self.security_policy.is_aead() {
+            self.last_received_sequence_number =
+                chunk.chunk_info(self)?.sequence_header.sequence_number;
+        }
+        Ok(chunk)
     }
 
     /// Use the security policy to asymmetric encrypt and sign the specified chunk of data.
@@ -872,13 +974,21 @@ impl SecureChannel {
         let header_size = encrypted_range.start;
 
         let signing_key = self.private_key.as_ref().unwrap();
-        let signing_key_size = signing_key.size();
+        let signing_key_size = signing_key.signature_size();
 
         let signed_range = 0..(encrypted_range.end - signing_key_size);
         let signature_range = signed_range.end..encrypted_range.end;
 
         trace!("Header size = {}, Encrypted range = {:?}, Signed range = {:?}, Signature range = {:?}, signature size = {}", header_size, encrypted_range, signed_range, signature_range, signing_key_size);
 
+        if security_policy.is_ecc() {
+            // ECC security policies only sign the OpenSecureChannel messages
+            let (l, r) = src.split_at_mut(signed_range.end);
+            security_policy.asymmetric_sign(signing_key, l, &mut r[0..signing_key_size])?;
+            dst[..signature_range.end].copy_from_slice(&src[..signature_range.end]);
+            return Ok(signature_range.end);
+        }
+
         let encryption_key = self.remote_cert.as_ref().unwrap().public_key()?;
 
         // Encryption will change the size of the chunk. Since we sign before encrypting, we need to
@@ -1035,6 +1145,25 @@ impl SecureChannel {
                 StatusCode::BadNoValidCertificates,
                 "Supplied thumbprint does not match application certificate's thumbprint",
             ))
+        } else if security_policy.is_ecc() {
+            // ECC security policies only sign the OpenSecureChannel messages, there is
+            // no encryption or padding.
+            let signature_size = verification_key.signature_size();
+            if encrypted_range.end < encrypted_range.start + signature_size {
+                return Err(Error::new(
+                    StatusCode::BadSecurityChecksFailed,
+                    "Message is too short to contain a signature",
+                ));
+            }
+            let signature_start = encrypted_range.end - signature_size;
+            dst[..encrypted_range.end].copy_from_slice(&src[..encrypted_range.end]);
+            security_policy.asymmetric_verify_signature(
+                verification_key,
+                &dst[..signature_start],
+                &dst[signature_start..encrypted_range.end],
+                their_key,
+            )?;
+            Ok(signature_start)
         } else {
             // Copy message, security header
             dst[..encrypted_range.start].copy_from_slice(&src[..encrypted_range.start]);
@@ -1214,6 +1343,9 @@ impl SecureChannel {
         encrypted_range: Range<usize>,
         dst: &mut [u8],
     ) -> Result<usize, StatusCode> {
+        if self.security_policy.is_aead() && self.security_mode != MessageSecurityMode::None {
+            return Ok(self.symmetric_aead_encrypt(src, encrypted_range, dst)?);
+        }
         let encrypted_size = match self.security_mode {
             MessageSecurityMode::None => {
                 trace!("encrypt_and_sign is doing nothing because security mode == None");
@@ -1295,6 +1427,9 @@ impl SecureChannel {
         token_id: u32,
         dst: &mut [u8],
     ) -> Result<usize, Error> {
+        if self.security_policy.is_aead() && self.security_mode != MessageSecurityMode::None {
+            return self.symmetric_aead_decrypt(src, encrypted_range, token_id, dst);
+        }
         match self.security_mode {
             MessageSecurityMode::None => {
                 // Just copy everything from src to dst
@@ -1401,6 +1536,97 @@ impl SecureChannel {
         }
     }
 
+    /// The nonce of a chunk secured with authenticated encryption is the initialization vector,
+    /// with the first four bytes XORed with the token ID and the next four bytes XORed with the
+    /// sequence number of the previous chunk.
+    fn aead_nonce(iv: &[u8], token_id: u32, last_sequence_number: u32) -> Vec<u8> {
+        let mut nonce = iv.to_vec();
+        let mask = token_id
+            .to_le_bytes()
+            .into_iter()
+            .chain(last_sequence_number.to_le_bytes());
+        for (n, m) in nonce.iter_mut().zip(mask) {
+            *n ^= m;
+        }
+        nonce
+    }
+
+    /// Secure a chunk with authenticated encryption. The tag of the encryption takes the
+    /// place of the signature, and covers everything before it. With `SignAndEncrypt` the
+    /// headers preceding `encrypted_range` are authenticated but not encrypted, with `Sign`
+    /// nothing is encrypted.
+    fn symmetric_aead_encrypt(
+        &self,
+        src: &[u8],
+        encrypted_range: Range<usize>,
+        dst: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.expect_supported_security_policy();
+        let (key, iv) = self.encryption_keys();
+        // The sequence header is at the start of the encrypted range, chunks are sent in
+        // order so the previous chunk has the sequence number before this one.
+        let mut stream = Cursor::new(&src[encrypted_range.start..]);
+        let sequence_number = read_u32(&mut stream)?;
+        let nonce = Self::aead_nonce(iv, self.token_id, sequence_number.wrapping_sub(1));
+
+        let tag_start = src.len() - key.tag_size();
+        let authenticated_end = match self.security_mode {
+            MessageSecurityMode::Sign => tag_start,
+            _ => encrypted_range.start,
+        };
+        dst[..authenticated_end].copy_from_slice(&src[..authenticated_end]);
+        let size = key.encrypt_aead(
+            &src[authenticated_end..tag_start],
+            &nonce,
+            &src[..authenticated_end],
+            &mut dst[authenticated_end..],
+        )?;
+        Ok(authenticated_end + size)
+    }
+
+    /// Verify and decrypt a chunk secured with [`SecureChannel::symmetric_aead_encrypt`].
+    ///
+    /// Returns the size of the decrypted data, without the tag.
+    fn symmetric_aead_decrypt(
+        &self,
+        src: &[u8],
+        encrypted_range: Range<usize>,
+        token_id: u32,
+        dst: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.expect_supported_security_policy();
+        let (key, iv) = self.decryption_keys(token_id).ok_or_else(|| {
+            Error::new(
+                StatusCode::BadSecureChannelClosed,
+                "Missing decryption keys",
+            )
+        })?;
+        let nonce = Self::aead_nonce(iv, token_id, self.last_received_sequence_number);
+
+        let Some(tag_start) = src
+            .len()
+            .checked_sub(key.tag_size())
+            .filter(|end| *end >= encrypted_range.start)
+        else {
+            return Err(Error::new(
+                StatusCode::BadSecurityChecksFailed,
+                "Message is too short to contain a tag",
+            ));
+        };
+        let authenticated_end = match self.security_mode {
+            MessageSecurityMode::Sign => tag_start,
+            _ => encrypted_range.start,
+        };
+        dst[..authenticated_end].copy_from_slice(&src[..authenticated_end]);
+        let size = key.decrypt_aead(
+            &src[authenticated_end..],
+            &nonce,
+            &src[..authenticated_end],
+            &mut dst[authenticated_end..],
+        )?;
+        Ok(authenticated_end + size)
+    }
+
     // Panic code which requires a policy
     fn expect_supported_security_policy(&self) {
         match self.security_policy {
@@ -1408,7 +1634,12 @@ impl SecureChannel {
             | SecurityPolicy::Basic256
             | SecurityPolicy::Basic256Sha256
             | SecurityPolicy::Aes128Sha256RsaOaep
-            | SecurityPolicy::Aes256Sha256RsaPss => {}
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccCurve25519
+            | SecurityPolicy::EccBrainpoolP256r1
+            | SecurityPolicy::EccBrainpoolP384r1 => {}
             _ => {
                 panic!("Unsupported security policy");
             }
@@ -0,0 +1,16 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Contains a type-erased byte stream, so that the same transport implementation
+//! can be used for plain TCP, TLS, and WebSocket connections.
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Trait for byte streams that OPC-UA messages can be sent over.
+pub trait AsyncStream: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+
+impl<T> AsyncStream for T where T: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+
+/// Boxed byte stream.
+pub type BoxedStream = Box<dyn AsyncStream>;
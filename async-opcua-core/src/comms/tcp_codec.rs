@@ -11,6 +11,7 @@
 //! * MSG - Message chunk
 //! * OPN - Open Secure Channel message
 //! * CLO - Close Secure Channel message
+//! * RHE - Reverse Hello message
 use std::io;
 
 use bytes::{BufMut, BytesMut};
@@ -26,7 +27,7 @@ use super::{
     message_chunk::MessageChunk,
     tcp_types::{
         AcknowledgeMessage, ErrorMessage, HelloMessage, MessageHeader, MessageType,
-        MESSAGE_HEADER_LEN,
+        ReverseHelloMessage, MESSAGE_HEADER_LEN,
     },
 };
 
@@ -42,6 +43,9 @@ pub enum Message {
     Error(ErrorMessage),
     /// Part of a general OPC-UA message.
     Chunk(MessageChunk),
+    /// Reverse hello message, sent by a server that opens
+    /// a connection to a client.
+    ReverseHello(ReverseHelloMessage),
 }
 
 /// Implements a tokio codec that as close as possible, allows incoming data to be transformed into
@@ -98,6 +102,7 @@ impl Encoder<Message> for TcpCodec {
             Message::Acknowledge(msg) => self.write(msg, buf),
             Message::Error(msg) => self.write(msg, buf),
             Message::Chunk(msg) => self.write(msg, buf),
+            Message::ReverseHello(msg) => self.write(msg, buf),
         }
     }
 }
@@ -145,6 +150,10 @@ impl TcpCodec {
                 &mut buf,
                 decoding_options,
             )?)),
+            MessageType::ReverseHello => Ok(Message::ReverseHello(ReverseHelloMessage::decode(
+                &mut buf,
+                decoding_options,
+            )?)),
             MessageType::Invalid => {
                 error!("Message type for chunk is invalid.");
                 Err(StatusCode::BadCommunicationError)
@@ -27,6 +27,8 @@ pub(crate) const HELLO_MESSAGE: &[u8] = b"HEL";
 pub(crate) const ACKNOWLEDGE_MESSAGE: &[u8] = b"ACK";
 /// Message header type for error messages.
 pub(crate) const ERROR_MESSAGE: &[u8] = b"ERR";
+/// Message header type for reverse hello messages.
+pub(crate) const REVERSE_HELLO_MESSAGE: &[u8] = b"RHE";
 
 /// ChunkIsFinal type for the final chunk in a message.
 pub(crate) const CHUNK_FINAL: u8 = b'F';
@@ -54,6 +56,8 @@ pub enum MessageType {
     Chunk,
     /// Fatal error, followed by shutting down the channel.
     Error,
+    /// REVERSE HELLO message, sent by a server that initiates the connection.
+    ReverseHello,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -75,6 +79,7 @@ impl SimpleBinaryEncodable for MessageHeader {
             MessageType::Hello => stream.write_all(HELLO_MESSAGE),
             MessageType::Acknowledge => stream.write_all(ACKNOWLEDGE_MESSAGE),
             MessageType::Error => stream.write_all(ERROR_MESSAGE),
+            MessageType::ReverseHello => stream.write_all(REVERSE_HELLO_MESSAGE),
             MessageType::Chunk => {
                 panic!("Don't write chunks to stream with this call, use Chunk and Chunker");
             }
@@ -167,6 +172,7 @@ impl MessageHeader {
                 HELLO_MESSAGE => MessageType::Hello,
                 ACKNOWLEDGE_MESSAGE => MessageType::Acknowledge,
                 ERROR_MESSAGE => MessageType::Error,
+                REVERSE_HELLO_MESSAGE => MessageType::ReverseHello,
                 CHUNK_MESSAGE | OPEN_SECURE_CHANNEL_MESSAGE | CLOSE_SECURE_CHANNEL_MESSAGE => {
                     MessageType::Chunk
                 }
@@ -315,6 +321,72 @@ impl HelloMessage {
     }
 }
 
+/// Implementation of the RHE message in OPC UA. This is sent by the server
+/// when it opens a connection to a client, the client then continues with
+/// a normal HEL message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReverseHelloMessage {
+    message_header: MessageHeader,
+    /// Application URI of the server that opened the connection.
+    pub server_uri: UAString,
+    /// Endpoint URL the client should use in the HEL message.
+    pub endpoint_url: UAString,
+}
+
+impl SimpleBinaryEncodable for ReverseHelloMessage {
+    fn byte_len(&self) -> usize {
+        self.message_header.byte_len() + self.server_uri.byte_len() + self.endpoint_url.byte_len()
+    }
+
+    fn encode<S: Write + ?Sized>(&self, stream: &mut S) -> EncodingResult<()> {
+        self.message_header.encode(stream)?;
+        self.server_uri.encode(stream)?;
+        self.endpoint_url.encode(stream)
+    }
+}
+
+impl SimpleBinaryDecodable for ReverseHelloMessage {
+    fn decode<S: Read + ?Sized>(
+        stream: &mut S,
+        decoding_options: &DecodingOptions,
+    ) -> EncodingResult<Self> {
+        let message_header = MessageHeader::decode(stream, decoding_options)?;
+        let server_uri = UAString::decode(stream, decoding_options)?;
+        let endpoint_url = UAString::decode(stream, decoding_options)?;
+        Ok(ReverseHelloMessage {
+            message_header,
+            server_uri,
+            endpoint_url,
+        })
+    }
+}
+
+impl ReverseHelloMessage {
+    const MAX_URI_LEN: usize = 4096;
+
+    /// Creates a RHE message
+    pub fn new(server_uri: &str, endpoint_url: &str) -> ReverseHelloMessage {
+        let mut msg = ReverseHelloMessage {
+            message_header: MessageHeader::new(MessageType::ReverseHello),
+            server_uri: UAString::from(server_uri),
+            endpoint_url: UAString::from(endpoint_url),
+        };
+        msg.message_header.message_size = msg.byte_len() as u32;
+        msg
+    }
+
+    /// Check if the server URI and endpoint URL are present and within the
+    /// maximum length.
+    pub fn is_valid(&self) -> bool {
+        let valid = |s: &UAString| {
+            s.value()
+                .as_ref()
+                .is_some_and(|s| !s.is_empty() && s.len() <= Self::MAX_URI_LEN)
+        };
+        valid(&self.server_uri) && valid(&self.endpoint_url)
+    }
+}
+
 /// Implementation of the ACK message in OPC UA
 #[derive(Debug, Clone, PartialEq)]
 pub struct AcknowledgeMessage {
@@ -450,10 +522,12 @@ impl ErrorMessage {
 mod tests {
     use std::io::Cursor;
 
-    use crate::comms::tcp_types::{AcknowledgeMessage, HelloMessage, MessageHeader, MessageType};
+    use crate::comms::tcp_types::{
+        AcknowledgeMessage, HelloMessage, MessageHeader, MessageType, ReverseHelloMessage,
+    };
     use opcua_types::{
         ApplicationDescription, ByteString, DecodingOptions, EndpointDescription,
-        MessageSecurityMode, SimpleBinaryDecodable, UAString,
+        MessageSecurityMode, SimpleBinaryDecodable, SimpleBinaryEncodable, UAString,
     };
 
     fn hello_data() -> Vec<u8> {
@@ -507,6 +581,28 @@ mod tests {
         assert_eq!(ack.max_chunk_count, 65535);
     }
 
+    #[test]
+    fn reverse_hello() {
+        let rhe = ReverseHelloMessage::new("urn:server", "opc.tcp://localhost:4855/");
+        let data = rhe.encode_to_vec();
+        assert_eq!(&data[0..4], b"RHEF");
+        assert_eq!(data.len(), rhe.message_header.message_size as usize);
+        assert_eq!(
+            MessageHeader::message_type(&data[0..4]),
+            MessageType::ReverseHello
+        );
+        let decoded =
+            ReverseHelloMessage::decode(&mut Cursor::new(data), &DecodingOptions::test()).unwrap();
+        assert_eq!(decoded, rhe);
+        assert!(decoded.is_valid());
+
+        let rhe = ReverseHelloMessage::new("", "opc.tcp://localhost:4855/");
+        assert!(!rhe.is_valid());
+        let long_url = (0..4097).map(|_| 'A').collect::<String>();
+        let rhe = ReverseHelloMessage::new("urn:server", &long_url);
+        assert!(!rhe.is_valid());
+    }
+
     #[test]
     fn endpoint_url() {
         // Ensure hello with None endpoint is invalid
@@ -0,0 +1,141 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Contains utilities for building TLS configurations from OPC-UA certificates,
+//! used by the secure WebSocket transport.
+
+use std::sync::Arc;
+
+use log::{error, warn};
+use opcua_crypto::{CertificateStore, PrivateKey, SecurityPolicy, X509};
+use opcua_types::StatusCode;
+use tokio_rustls::rustls::{
+    self,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring, CryptoProvider, WebPkiSupportedAlgorithms},
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, ServerConfig, SignatureScheme,
+};
+
+use crate::{sync::RwLock, trace_read_lock};
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// Create a TLS server configuration using the given certificate and private key.
+pub fn server_config(cert: &X509, pkey: &PrivateKey) -> Result<Arc<ServerConfig>, StatusCode> {
+    let cert = cert.to_der().map_err(|e| {
+        error!("Failed to encode TLS certificate: {e}");
+        StatusCode::BadCertificateInvalid
+    })?;
+    let key = pkey.to_der().map_err(|e| {
+        error!("Failed to encode TLS private key: {e}");
+        StatusCode::BadCertificateInvalid
+    })?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.as_bytes().to_vec()));
+
+    let config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .and_then(|b| {
+            b.with_no_client_auth()
+                .with_single_cert(vec![CertificateDer::from(cert)], key)
+        })
+        .map_err(|e| {
+            error!("Failed to create TLS server configuration: {e}");
+            StatusCode::BadConfigurationError
+        })?;
+    Ok(Arc::new(config))
+}
+
+/// Create a TLS client configuration. The server certificate is validated
+/// against the trusted certificates in `certificate_store`, the same way
+/// application instance certificates are.
+pub fn client_config(
+    certificate_store: Arc<RwLock<CertificateStore>>,
+) -> Result<Arc<ClientConfig>, StatusCode> {
+    let provider = provider();
+    let verifier = CertificateStoreVerifier {
+        certificate_store,
+        algorithms: provider.signature_verification_algorithms,
+    };
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| {
+            error!("Failed to create TLS client configuration: {e}");
+            StatusCode::BadConfigurationError
+        })?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// Server certificate verifier that checks certificates using the
+/// OPC-UA certificate store.
+struct CertificateStoreVerifier {
+    certificate_store: Arc<RwLock<CertificateStore>>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl std::fmt::Debug for CertificateStoreVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateStoreVerifier").finish()
+    }
+}
+
+impl ServerCertVerifier for CertificateStoreVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let cert = X509::from_der(end_entity).map_err(|_| {
+            rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+        })?;
+        let hostname = server_name.to_str();
+        let store = trace_read_lock!(self.certificate_store);
+        // TLS certificates have no associated security policy, so require
+        // the same key length as the default secure policy.
+        store
+            .validate_or_reject_application_instance_cert(
+                &cert,
+                SecurityPolicy::Basic256Sha256,
+                Some(&hostname),
+                None,
+            )
+            .map_err(|e| {
+                warn!("Rejected TLS certificate for {hostname}: {e}");
+                rustls::Error::InvalidCertificate(
+                    rustls::CertificateError::ApplicationVerificationFailure,
+                )
+            })?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
@@ -11,12 +11,18 @@ use opcua_types::status_code::StatusCode;
 
 /// Scheme for OPC-UA TCP.
 pub const OPC_TCP_SCHEME: &str = "opc.tcp";
+/// Scheme for OPC-UA over WebSockets.
+pub const OPC_WS_SCHEME: &str = "opc.ws";
+/// Scheme for OPC-UA over secure WebSockets.
+pub const OPC_WSS_SCHEME: &str = "opc.wss";
+/// Scheme for OPC-UA over HTTPS.
+pub const OPC_HTTPS_SCHEME: &str = "https";
 
 /// Creates a `Url` from the input string, supplying a default port if necessary.
 fn opc_url_from_str(s: &str) -> Result<Url, url::ParseError> {
     Url::parse(s)
         .map(|mut url| {
-            if url.port().is_none() {
+            if url.port_or_known_default().is_none() {
                 // If no port is supplied, then treat it as the default port 4840
                 let _ = url.set_port(Some(crate::constants::DEFAULT_OPC_UA_SERVER_PORT));
             }
@@ -69,9 +75,9 @@ pub fn server_url_from_endpoint_url(
     })
 }
 
-/// Check if this is a valid OPC-UA TCP URL.
+/// Check if this is a valid OPC-UA URL, using either TCP, WebSockets or HTTPS.
 pub fn is_valid_opc_ua_url(url: &str) -> bool {
-    is_opc_ua_binary_url(url)
+    is_opc_ua_binary_url(url) || is_opc_ua_websocket_url(url) || is_opc_ua_https_url(url)
 }
 
 /// Check if this is an OPC-UA TCP URL.
@@ -83,6 +89,24 @@ pub fn is_opc_ua_binary_url(url: &str) -> bool {
     }
 }
 
+/// Check if this is an OPC-UA WebSocket URL, i.e. `opc.ws` or `opc.wss`.
+pub fn is_opc_ua_websocket_url(url: &str) -> bool {
+    if let Ok(url) = opc_url_from_str(url) {
+        url.scheme() == OPC_WS_SCHEME || url.scheme() == OPC_WSS_SCHEME
+    } else {
+        false
+    }
+}
+
+/// Check if this is an OPC-UA HTTPS URL.
+pub fn is_opc_ua_https_url(url: &str) -> bool {
+    if let Ok(url) = opc_url_from_str(url) {
+        url.scheme() == OPC_HTTPS_SCHEME
+    } else {
+        false
+    }
+}
+
 /// Error returned when getting host name from URL.
 pub enum HostnameFromUrlError {
     /// URL failed to parse.
@@ -113,11 +137,19 @@ pub fn hostname_port_from_url(url: &str, default_port: u16) -> Result<(String, u
     // Validate and split out the endpoint we have
     let url = Url::parse(url).map_err(|_| StatusCode::BadTcpEndpointUrlInvalid)?;
 
-    if url.scheme() != OPC_TCP_SCHEME || !url.has_host() {
+    if ![
+        OPC_TCP_SCHEME,
+        OPC_WS_SCHEME,
+        OPC_WSS_SCHEME,
+        OPC_HTTPS_SCHEME,
+    ]
+    .contains(&url.scheme())
+        || !url.has_host()
+    {
         Err(StatusCode::BadTcpEndpointUrlInvalid)
     } else {
         let host = url.host_str().unwrap();
-        let port = url.port().unwrap_or(default_port);
+        let port = url.port_or_known_default().unwrap_or(default_port);
         Ok((host.to_string(), port))
     }
 }
@@ -133,6 +165,32 @@ mod tests {
             "opc.tcp://[FEDC:BA98:7654:3210:FEDC:BA98:7654:3210]:80/xyz"
         ));
         assert!(!is_opc_ua_binary_url("http://foo/xyz"));
+        assert!(!is_opc_ua_binary_url("opc.ws://foo/xyz"));
+        assert!(is_opc_ua_websocket_url("opc.ws://foo/xyz"));
+        assert!(is_opc_ua_websocket_url("opc.wss://foo:443/xyz"));
+        assert!(!is_opc_ua_websocket_url("opc.tcp://foo/xyz"));
+        assert!(is_valid_opc_ua_url("opc.wss://foo/xyz"));
+        assert!(!is_valid_opc_ua_url("ws://foo/xyz"));
+        assert!(is_opc_ua_https_url("https://foo/xyz"));
+        assert!(!is_opc_ua_https_url("http://foo/xyz"));
+        assert!(is_valid_opc_ua_url("https://foo:4843/xyz"));
+    }
+
+    #[test]
+    fn hostname_port_test() {
+        assert_eq!(
+            hostname_port_from_url("opc.tcp://foo:123/xyz", 4840).unwrap(),
+            ("foo".to_owned(), 123)
+        );
+        assert_eq!(
+            hostname_port_from_url("opc.wss://foo/xyz", 4840).unwrap(),
+            ("foo".to_owned(), 4840)
+        );
+        assert_eq!(
+            hostname_port_from_url("https://foo/xyz", 4840).unwrap(),
+            ("foo".to_owned(), 443)
+        );
+        assert!(hostname_port_from_url("http://foo:123/xyz", 4840).is_err());
     }
 
     #[test]
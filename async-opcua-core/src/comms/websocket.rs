@@ -0,0 +1,256 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Contains the OPC-UA over WebSockets transport mapping.
+//!
+//! Each OPC-UA message chunk is sent in a single binary WebSocket frame, using the
+//! `opcua+uacp` subprotocol. The WebSocket is exposed as a plain byte stream, so
+//! the rest of the stack can treat it the same way as a TCP connection.
+
+use futures::{SinkExt, StreamExt};
+use log::{debug, error};
+use opcua_types::StatusCode;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::{HeaderValue, StatusCode as HttpStatusCode},
+        Message,
+    },
+    WebSocketStream,
+};
+
+use super::{
+    stream::BoxedStream,
+    tcp_types::MESSAGE_HEADER_LEN,
+    url::{OPC_WSS_SCHEME, OPC_WS_SCHEME},
+};
+
+/// WebSocket subprotocol for OPC-UA binary messages.
+pub const OPCUA_WEBSOCKET_PROTOCOL: &str = "opcua+uacp";
+
+const PROTOCOL_HEADER: &str = "Sec-WebSocket-Protocol";
+
+/// Size of the in-memory pipe between the WebSocket and the byte stream.
+const PIPE_BUFFER_SIZE: usize = 65536;
+
+/// Convert an `opc.ws` or `opc.wss` URL into the equivalent `ws` or `wss` URL.
+pub fn websocket_url(url: &str) -> Result<String, StatusCode> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        error!("Invalid WebSocket URL {url}");
+        return Err(StatusCode::BadTcpEndpointUrlInvalid);
+    };
+    match scheme {
+        OPC_WS_SCHEME => Ok(format!("ws://{rest}")),
+        OPC_WSS_SCHEME => Ok(format!("wss://{rest}")),
+        _ => {
+            error!("URL {url} does not have a WebSocket scheme");
+            Err(StatusCode::BadTcpEndpointUrlInvalid)
+        }
+    }
+}
+
+fn has_opcua_protocol(value: Option<&HeaderValue>) -> bool {
+    value
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|p| p.trim() == OPCUA_WEBSOCKET_PROTOCOL))
+}
+
+// The error type is given by tungstenite.
+#[allow(clippy::result_large_err)]
+fn negotiate_protocol(
+    request: &Request,
+    mut response: Response,
+) -> Result<Response, ErrorResponse> {
+    if !has_opcua_protocol(request.headers().get(PROTOCOL_HEADER)) {
+        let mut error = ErrorResponse::new(Some(format!(
+            "Missing {OPCUA_WEBSOCKET_PROTOCOL} subprotocol"
+        )));
+        *error.status_mut() = HttpStatusCode::BAD_REQUEST;
+        return Err(error);
+    }
+    response.headers_mut().insert(
+        PROTOCOL_HEADER,
+        HeaderValue::from_static(OPCUA_WEBSOCKET_PROTOCOL),
+    );
+    Ok(response)
+}
+
+/// Perform the server side of the WebSocket handshake on `stream`, requiring
+/// the OPC-UA subprotocol.
+pub async fn accept<S>(stream: S) -> Result<BoxedStream, StatusCode>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let ws = tokio_tungstenite::accept_hdr_async(stream, negotiate_protocol)
+        .await
+        .map_err(|e| {
+            debug!("WebSocket handshake failed: {e}");
+            StatusCode::BadCommunicationError
+        })?;
+    Ok(into_byte_stream(ws))
+}
+
+/// Perform the client side of the WebSocket handshake on `stream`, connecting
+/// to the `opc.ws` or `opc.wss` endpoint `url`.
+pub async fn connect<S>(url: &str, stream: S) -> Result<BoxedStream, StatusCode>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let mut request = websocket_url(url)?.into_client_request().map_err(|e| {
+        error!("Invalid WebSocket URL {url}: {e}");
+        StatusCode::BadTcpEndpointUrlInvalid
+    })?;
+    request.headers_mut().insert(
+        PROTOCOL_HEADER,
+        HeaderValue::from_static(OPCUA_WEBSOCKET_PROTOCOL),
+    );
+    let (ws, response) = tokio_tungstenite::client_async(request, stream)
+        .await
+        .map_err(|e| {
+            error!("WebSocket handshake with {url} failed: {e}");
+            StatusCode::BadCommunicationError
+        })?;
+    if !has_opcua_protocol(response.headers().get(PROTOCOL_HEADER)) {
+        error!("Server at {url} did not accept the {OPCUA_WEBSOCKET_PROTOCOL} subprotocol");
+        return Err(StatusCode::BadCommunicationError);
+    }
+    Ok(into_byte_stream(ws))
+}
+
+/// Spawn a task moving message chunks between the WebSocket and a byte stream,
+/// and return the byte stream.
+fn into_byte_stream<S>(ws: WebSocketStream<S>) -> BoxedStream
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (local, remote) = tokio::io::duplex(PIPE_BUFFER_SIZE);
+    tokio::spawn(run_pipe(ws, remote));
+    Box::new(local)
+}
+
+async fn run_pipe<S>(ws: WebSocketStream<S>, pipe: DuplexStream)
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut ws_write, mut ws_read) = ws.split();
+    let (mut pipe_read, mut pipe_write) = tokio::io::split(pipe);
+
+    let incoming = async {
+        while let Some(message) = ws_read.next().await {
+            match message {
+                Ok(Message::Binary(data)) => {
+                    if pipe_write.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(Message::Close(_)) => break,
+                // Pings are answered by tungstenite, anything else is ignored.
+                Ok(_) => (),
+                Err(e) => {
+                    debug!("Error reading from WebSocket: {e}");
+                    break;
+                }
+            }
+        }
+    };
+
+    let outgoing = async {
+        loop {
+            match read_chunk(&mut pipe_read).await {
+                Ok(Some(chunk)) => {
+                    if let Err(e) = ws_write.send(Message::Binary(chunk.into())).await {
+                        debug!("Error writing to WebSocket: {e}");
+                        break;
+                    }
+                }
+                Ok(None) => {
+                    let _ = ws_write.close().await;
+                    break;
+                }
+                Err(e) => {
+                    error!("Invalid message chunk written to WebSocket: {e}");
+                    break;
+                }
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = incoming => (),
+        _ = outgoing => (),
+    }
+}
+
+/// Read a single message chunk from `read`, using the size in its header.
+/// Returns `None` if the stream is closed.
+async fn read_chunk(read: &mut (impl AsyncRead + Unpin)) -> std::io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; MESSAGE_HEADER_LEN];
+    match read.read_exact(&mut header).await {
+        Ok(_) => (),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if size < MESSAGE_HEADER_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Message size {size} is smaller than the header"),
+        ));
+    }
+    let mut chunk = vec![0u8; size];
+    chunk[..MESSAGE_HEADER_LEN].copy_from_slice(&header);
+    read.read_exact(&mut chunk[MESSAGE_HEADER_LEN..]).await?;
+    Ok(Some(chunk))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::comms::tcp_types::HelloMessage;
+    use opcua_types::SimpleBinaryEncodable;
+
+    use super::{accept, connect, websocket_url};
+
+    #[test]
+    fn url_conversion() {
+        assert_eq!(
+            websocket_url("opc.ws://localhost:4840/UA").unwrap(),
+            "ws://localhost:4840/UA"
+        );
+        assert_eq!(
+            websocket_url("opc.wss://localhost:443").unwrap(),
+            "wss://localhost:443"
+        );
+        assert!(websocket_url("opc.tcp://localhost:4840").is_err());
+        assert!(websocket_url("localhost").is_err());
+    }
+
+    #[tokio::test]
+    async fn send_chunks() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client, server) =
+            tokio::join!(connect("opc.ws://localhost:4840", client), accept(server));
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+
+        let hello = HelloMessage::new("opc.ws://localhost:4840", 8192, 8192, 65536, 1);
+        let bytes = hello.encode_to_vec();
+        // Write the message in two parts, it should still arrive as a single chunk.
+        client.write_all(&bytes[..5]).await.unwrap();
+        client.write_all(&bytes[5..]).await.unwrap();
+
+        let mut received = vec![0u8; bytes.len()];
+        server.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, bytes);
+
+        // Closing the client closes the server stream.
+        drop(client);
+        let mut rest = Vec::new();
+        server.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    }
+}
@@ -70,6 +70,33 @@ macro_rules! request_enum {
                 }
             }
         }
+
+        impl From<RequestMessage> for ExtensionObject {
+            fn from(value: RequestMessage) -> Self {
+                match value {
+                    $( RequestMessage::$name(value) => ExtensionObject::from_message(*value), )*
+                }
+            }
+        }
+
+        impl TryFrom<ExtensionObject> for RequestMessage {
+            type Error = Error;
+
+            fn try_from(value: ExtensionObject) -> std::result::Result<Self, Self::Error> {
+                $(
+                    if value.inner_is::<$value>() {
+                        return value
+                            .into_inner_as::<$value>()
+                            .map(Self::$name)
+                            .ok_or_else(|| Error::decoding("failed to cast extension object"));
+                    }
+                )*
+                Err(Error::decoding(format!(
+                    "extension object of type {:?} is not a request message",
+                    value.type_name()
+                )))
+            }
+        }
     };
 }
 
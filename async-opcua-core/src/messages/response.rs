@@ -70,6 +70,33 @@ macro_rules! response_enum {
                 }
             }
         }
+
+        impl From<ResponseMessage> for ExtensionObject {
+            fn from(value: ResponseMessage) -> Self {
+                match value {
+                    $( ResponseMessage::$name(value) => ExtensionObject::from_message(*value), )*
+                }
+            }
+        }
+
+        impl TryFrom<ExtensionObject> for ResponseMessage {
+            type Error = Error;
+
+            fn try_from(value: ExtensionObject) -> std::result::Result<Self, Self::Error> {
+                $(
+                    if value.inner_is::<$value>() {
+                        return value
+                            .into_inner_as::<$value>()
+                            .map(Self::$name)
+                            .ok_or_else(|| Error::decoding("failed to cast extension object"));
+                    }
+                )*
+                Err(Error::decoding(format!(
+                    "extension object of type {:?} is not a response message",
+                    value.type_name()
+                )))
+            }
+        }
     };
 }
 
@@ -3,7 +3,7 @@ use std::fmt::Debug;
 use std::io::Cursor;
 
 use opcua_crypto::{
-    pkey::PrivateKey,
+    pkey::{KeyType, PrivateKey},
     security_policy::SecurityPolicy,
     x509::{X509Data, X509},
 };
@@ -14,7 +14,10 @@ use opcua_types::{
     UAString,
 };
 
-use crate::{comms::secure_channel::SecureChannel, RequestMessage};
+use crate::{
+    comms::secure_channel::{Role, SecureChannel},
+    RequestMessage,
+};
 
 pub(crate) fn serialize_test_and_return<T>(value: T) -> T
 where
@@ -64,7 +67,7 @@ fn make_secure_channel(
     secure_channel.set_security_policy(security_policy);
     secure_channel.set_local_nonce(&local_nonce);
     secure_channel.set_remote_nonce(&remote_nonce);
-    secure_channel.derive_keys();
+    secure_channel.derive_keys().unwrap();
     secure_channel
 }
 
@@ -93,6 +96,33 @@ fn make_secure_channels(
     (secure_channel1, secure_channel2)
 }
 
+/// Makes a client and server secure channel for an ECC security policy, which
+/// derive their keys from the exchanged ephemeral keys.
+fn make_ecc_secure_channels(
+    security_mode: MessageSecurityMode,
+    security_policy: SecurityPolicy,
+) -> (SecureChannel, SecureChannel) {
+    let make_secure_channel = |role| {
+        let mut secure_channel = SecureChannel::new_no_certificate_store();
+        secure_channel.set_role(role);
+        secure_channel.set_security_mode(security_mode);
+        secure_channel.set_security_policy(security_policy);
+        secure_channel.create_random_nonce();
+        secure_channel
+    };
+    let mut client = make_secure_channel(Role::Client);
+    let mut server = make_secure_channel(Role::Server);
+    client
+        .set_remote_nonce_from_byte_string(&server.local_nonce_as_byte_string())
+        .unwrap();
+    server
+        .set_remote_nonce_from_byte_string(&client.local_nonce_as_byte_string())
+        .unwrap();
+    client.derive_keys().unwrap();
+    server.derive_keys().unwrap();
+    (client, server)
+}
+
 fn make_open_secure_channel_response() -> OpenSecureChannelResponse {
     OpenSecureChannelResponse {
         response_header: ResponseHeader {
@@ -132,10 +162,11 @@ fn make_sample_message() -> RequestMessage {
     .into()
 }
 
-fn make_test_cert(key_size: u32) -> (X509, PrivateKey) {
+fn make_test_cert(key_type: KeyType, key_size: u32) -> (X509, PrivateKey) {
     const APPLICATION_URI: &str = "urn:testapplication";
     const APPLICATION_HOSTNAME: &str = "testhost";
     let args = X509Data {
+        key_type,
         key_size,
         common_name: "x".to_string(),
         organization: "x.org".to_string(),
@@ -157,11 +188,15 @@ fn make_test_cert(key_size: u32) -> (X509, PrivateKey) {
 }
 
 fn make_test_cert_2048() -> (X509, PrivateKey) {
-    make_test_cert(2048)
+    make_test_cert(KeyType::Rsa, 2048)
 }
 
 fn make_test_cert_4096() -> (X509, PrivateKey) {
-    make_test_cert(4096)
+    make_test_cert(KeyType::Rsa, 4096)
+}
+
+fn make_test_cert_nist_p256() -> (X509, PrivateKey) {
+    make_test_cert(KeyType::NistP256, 0)
 }
 
 struct Test;
@@ -101,6 +101,37 @@ fn test_asymmetric_encrypt_decrypt(
     }
 }
 
+fn test_ecc_symmetric_encrypt_decrypt(
+    message: impl Message + PartialEq + Debug,
+    security_mode: MessageSecurityMode,
+    security_policy: SecurityPolicy,
+) {
+    let (mut client, mut server) = make_ecc_secure_channels(security_mode, security_policy);
+
+    // Messages must be readable in both directions, since client and server
+    // derive different keys.
+    let send = |sender: &SecureChannel, receiver: &mut SecureChannel| {
+        let mut chunks = Chunker::encode(1, 1, 0, 0, sender, &message).unwrap();
+        assert_eq!(chunks.len(), 1);
+        let chunk = &mut chunks[0];
+
+        let mut encrypted_data = vec![0u8; chunk.data.len() + 4096];
+        let encrypted_size = sender
+            .apply_security(chunk, &mut encrypted_data[..])
+            .unwrap();
+
+        let chunk2 = receiver
+            .verify_and_remove_security(&encrypted_data[..encrypted_size])
+            .unwrap();
+        assert_eq!(&chunk.data, &chunk2.data);
+
+        let message2 = Chunker::decode(&[chunk2], receiver, None).unwrap();
+        assert_eq!(message, message2);
+    };
+    send(&client, &mut server);
+    send(&server, &mut client);
+}
+
 //
 
 #[test]
@@ -217,3 +248,165 @@ fn symmetric_sign_and_encrypt_message_chunk_basic256sha256() {
         SecurityPolicy::Basic256Sha256,
     );
 }
+
+/// OpenSecureChannel messages with ECC are signed but never encrypted
+#[test]
+fn asymmetric_sign_message_chunk_ecc_nist_p256() {
+    use crate::ResponseMessage;
+
+    let _ = Test::setup();
+    let (our_cert, our_key) = make_test_cert_nist_p256();
+    let (their_cert, their_key) = make_test_cert_nist_p256();
+
+    let mut secure_channel = SecureChannel::new_no_certificate_store();
+    secure_channel.set_security_mode(MessageSecurityMode::SignAndEncrypt);
+    secure_channel.set_security_policy(SecurityPolicy::EccNistP256);
+    secure_channel.set_cert(Some(our_cert));
+    secure_channel.set_remote_cert(Some(their_cert));
+    secure_channel.set_private_key(Some(our_key));
+
+    let message: ResponseMessage = make_open_secure_channel_response().into();
+    let mut chunks = Chunker::encode(1, 1, 0, 0, &secure_channel, &message).unwrap();
+    assert_eq!(chunks.len(), 1);
+    let chunk = &mut chunks[0];
+
+    let mut signed_data = vec![0u8; chunk.data.len() + 4096];
+    let signed_size = secure_channel
+        .apply_security(chunk, &mut signed_data[..])
+        .unwrap();
+    // The body is sent in the clear, followed by the raw ECDSA signature
+    assert_eq!(signed_size, chunk.data.len() + 64);
+
+    // Flip the certificates around and verify on the other side
+    let tmp = secure_channel.cert();
+    let remote_cert = secure_channel.remote_cert();
+    secure_channel.set_cert(remote_cert);
+    secure_channel.set_remote_cert(tmp);
+    secure_channel.set_private_key(Some(their_key));
+
+    let chunk2 = secure_channel
+        .verify_and_remove_security(&signed_data[..signed_size])
+        .unwrap();
+    assert_eq!(&chunk.data, &chunk2.data);
+
+    // Tampering with the body must fail verification
+    let last = chunk.data.len() - 1;
+    signed_data[last] = !signed_data[last];
+    assert!(secure_channel
+        .verify_and_remove_security(&signed_data[..signed_size])
+        .is_err());
+}
+
+#[test]
+fn symmetric_sign_message_chunk_ecc_nist_p256() {
+    let _ = Test::setup();
+    test_ecc_symmetric_encrypt_decrypt(
+        make_sample_message(),
+        MessageSecurityMode::Sign,
+        SecurityPolicy::EccNistP256,
+    );
+}
+
+#[test]
+fn symmetric_sign_and_encrypt_message_chunk_ecc_nist_p256() {
+    let _ = Test::setup();
+    test_ecc_symmetric_encrypt_decrypt(
+        make_sample_message(),
+        MessageSecurityMode::SignAndEncrypt,
+        SecurityPolicy::EccNistP256,
+    );
+}
+
+#[test]
+fn symmetric_sign_and_encrypt_message_chunk_ecc_nist_p384() {
+    let _ = Test::setup();
+    test_ecc_symmetric_encrypt_decrypt(
+        make_sample_message(),
+        MessageSecurityMode::SignAndEncrypt,
+        SecurityPolicy::EccNistP384,
+    );
+}
+
+#[test]
+fn symmetric_sign_and_encrypt_message_chunk_ecc_brainpool_p256r1() {
+    let _ = Test::setup();
+    test_ecc_symmetric_encrypt_decrypt(
+        make_sample_message(),
+        MessageSecurityMode::SignAndEncrypt,
+        SecurityPolicy::EccBrainpoolP256r1,
+    );
+}
+
+#[test]
+fn symmetric_sign_and_encrypt_message_chunk_ecc_brainpool_p384r1() {
+    let _ = Test::setup();
+    test_ecc_symmetric_encrypt_decrypt(
+        make_sample_message(),
+        MessageSecurityMode::SignAndEncrypt,
+        SecurityPolicy::EccBrainpoolP384r1,
+    );
+}
+
+#[test]
+fn symmetric_sign_message_chunk_ecc_curve25519() {
+    let _ = Test::setup();
+    test_ecc_symmetric_encrypt_decrypt(
+        make_sample_message(),
+        MessageSecurityMode::Sign,
+        SecurityPolicy::EccCurve25519,
+    );
+}
+
+#[test]
+fn symmetric_sign_and_encrypt_message_chunk_ecc_curve25519() {
+    let _ = Test::setup();
+    test_ecc_symmetric_encrypt_decrypt(
+        make_sample_message(),
+        MessageSecurityMode::SignAndEncrypt,
+        SecurityPolicy::EccCurve25519,
+    );
+}
+
+/// The nonce of chunks secured with authenticated encryption depends on the sequence number
+/// of the previous chunk, so chunks must be received in order and cannot be replayed.
+#[test]
+fn symmetric_aead_message_chunk_sequence() {
+    let _ = Test::setup();
+    let message = make_sample_message();
+    let (client, mut server) = make_ecc_secure_channels(
+        MessageSecurityMode::SignAndEncrypt,
+        SecurityPolicy::EccCurve25519,
+    );
+
+    let secure = |sequence_number| {
+        let chunks = Chunker::encode(sequence_number, 1, 0, 0, &client, &message).unwrap();
+        let mut encrypted_data = vec![0u8; chunks[0].data.len() + 4096];
+        let encrypted_size = client
+            .apply_security(&chunks[0], &mut encrypted_data[..])
+            .unwrap();
+        encrypted_data.truncate(encrypted_size);
+        // There is no padding, only the tag is added
+        assert_eq!(encrypted_size, chunks[0].data.len() + 16);
+        (chunks, encrypted_data)
+    };
+    let (chunks1, encrypted1) = secure(1);
+    let (chunks2, encrypted2) = secure(2);
+    let (_, encrypted3) = secure(3);
+
+    // Skipping a chunk fails
+    assert!(server.verify_and_remove_security(&encrypted2).is_err());
+
+    let chunk = server.verify_and_remove_security(&encrypted1).unwrap();
+    assert_eq!(chunk.data, chunks1[0].data);
+    let chunk = server.verify_and_remove_security(&encrypted2).unwrap();
+    assert_eq!(chunk.data, chunks2[0].data);
+
+    // Replaying a chunk fails
+    assert!(server.verify_and_remove_security(&encrypted2).is_err());
+
+    // Tampering with the unencrypted header fails
+    let mut tampered = encrypted3.clone();
+    tampered[12] = !tampered[12];
+    assert!(server.verify_and_remove_security(&tampered).is_err());
+    assert!(server.verify_and_remove_security(&encrypted3).is_ok());
+}
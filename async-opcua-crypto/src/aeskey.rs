@@ -3,6 +3,9 @@
 // Copyright (C) 2017-2024 Adam Lock
 
 //! Symmetric encryption / decryption wrapper.
+//!
+//! The curve25519 security policy uses the ChaCha20-Poly1305 authenticated
+//! encryption instead of AES, where the Poly1305 tag takes the place of the signature.
 
 use std::result::Result;
 
@@ -12,6 +15,7 @@ use aes::cipher::{
     KeyIvInit,
 };
 use cbc;
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, KeyInit};
 
 use opcua_types::status_code::StatusCode;
 use opcua_types::Error;
@@ -26,6 +30,9 @@ type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
 const AES_BLOCK_SIZE: usize = 16;
 const AES128_KEY_SIZE: usize = 16;
 const AES256_KEY_SIZE: usize = 32;
+const CHACHA20_POLY1305_KEY_SIZE: usize = 32;
+const CHACHA20_POLY1305_NONCE_SIZE: usize = 12;
+const POLY1305_TAG_SIZE: usize = 16;
 
 type AesArray128 = GenericArray<u8, <aes::Aes128 as aes::cipher::BlockSizeUser>::BlockSize>;
 type AesArray256 = GenericArray<u8, <aes::Aes256 as aes::cipher::KeySizeUser>::KeySize>;
@@ -121,6 +128,89 @@ impl AesKey {
         Ok(src.len())
     }
 
+    fn chacha20_poly1305(&self, iv: &[u8]) -> Result<ChaCha20Poly1305, Error> {
+        if !self.security_policy.is_aead() {
+            Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                "Security policy does not use authenticated encryption",
+            ))
+        } else if self.value.len() != CHACHA20_POLY1305_KEY_SIZE {
+            Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                format!("Key is not an expected size, len = {}", self.value.len()),
+            ))
+        } else if iv.len() != CHACHA20_POLY1305_NONCE_SIZE {
+            Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                format!("IV is not an expected size, len = {}", iv.len()),
+            ))
+        } else {
+            Ok(ChaCha20Poly1305::new(self.value.as_slice().into()))
+        }
+    }
+
+    /// Encrypt `src` into `dst` with authenticated encryption, also authenticating the
+    /// additional data `aad`. The tag is written to `dst` after the encrypted data, and
+    /// the returned size includes it.
+    pub fn encrypt_aead(&self, src: &[u8], iv: &[u8], aad: &[u8], dst: &mut [u8]) -> EncryptResult {
+        let cipher = self.chacha20_poly1305(iv)?;
+        let size = src.len() + POLY1305_TAG_SIZE;
+        if dst.len() < size {
+            return Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                format!("Dst buffer is too small {} vs {}", dst.len(), size),
+            ));
+        }
+        let (data, tag) = dst[..size].split_at_mut(src.len());
+        data.copy_from_slice(src);
+        let computed = cipher
+            .encrypt_in_place_detached(iv.into(), aad, data)
+            .map_err(|e| Error::new(StatusCode::BadUnexpectedError, e.to_string()))?;
+        tag.copy_from_slice(&computed);
+        Ok(size)
+    }
+
+    /// Decrypt `src`, the encrypted data followed by its tag, into `dst` with authenticated
+    /// encryption, also authenticating the additional data `aad`. Returns the size of the
+    /// decrypted data.
+    pub fn decrypt_aead(&self, src: &[u8], iv: &[u8], aad: &[u8], dst: &mut [u8]) -> EncryptResult {
+        let cipher = self.chacha20_poly1305(iv)?;
+        let Some(size) = src.len().checked_sub(POLY1305_TAG_SIZE) else {
+            return Err(Error::new(
+                StatusCode::BadSecurityChecksFailed,
+                "Encrypted data is shorter than the tag",
+            ));
+        };
+        if dst.len() < size {
+            return Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                format!("Dst buffer is too small {} vs {}", dst.len(), size),
+            ));
+        }
+        let (encrypted, tag) = src.split_at(size);
+        let data = &mut dst[..size];
+        data.copy_from_slice(encrypted);
+        cipher
+            .decrypt_in_place_detached(iv.into(), aad, data, tag.into())
+            .map_err(|_| {
+                Error::new(
+                    StatusCode::BadSecurityChecksFailed,
+                    "Authenticated decryption failed",
+                )
+            })?;
+        Ok(size)
+    }
+
+    /// Get the size of the authentication tag appended by [`AesKey::encrypt_aead`], or zero if
+    /// the security policy of this key does not use authenticated encryption.
+    pub fn tag_size(&self) -> usize {
+        if self.security_policy.is_aead() {
+            POLY1305_TAG_SIZE
+        } else {
+            0
+        }
+    }
+
     /// Get the block size of the associated security policy for this key.
     pub fn block_size(&self) -> usize {
         match self.security_policy {
@@ -128,7 +218,13 @@ impl AesKey {
             | SecurityPolicy::Aes128Sha256RsaOaep
             | SecurityPolicy::Basic256
             | SecurityPolicy::Basic256Sha256
-            | SecurityPolicy::Aes256Sha256RsaPss => AES_BLOCK_SIZE,
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccBrainpoolP256r1
+            | SecurityPolicy::EccBrainpoolP384r1 => AES_BLOCK_SIZE,
+            // ChaCha20 is a stream cipher.
+            SecurityPolicy::EccCurve25519 => 1,
             _ => 0,
         }
     }
@@ -140,7 +236,12 @@ impl AesKey {
             | SecurityPolicy::Aes128Sha256RsaOaep
             | SecurityPolicy::Basic256
             | SecurityPolicy::Basic256Sha256
-            | SecurityPolicy::Aes256Sha256RsaPss => AES_BLOCK_SIZE,
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccBrainpoolP256r1
+            | SecurityPolicy::EccBrainpoolP384r1 => AES_BLOCK_SIZE,
+            SecurityPolicy::EccCurve25519 => CHACHA20_POLY1305_NONCE_SIZE,
             _ => 0,
         }
     }
@@ -148,25 +249,38 @@ impl AesKey {
     /// Get the AES key length.
     pub fn key_length(&self) -> usize {
         match self.security_policy {
-            SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Aes128Sha256RsaOaep => AES128_KEY_SIZE,
+            SecurityPolicy::Basic128Rsa15
+            | SecurityPolicy::Aes128Sha256RsaOaep
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccBrainpoolP256r1 => AES128_KEY_SIZE,
 
             SecurityPolicy::Basic256
             | SecurityPolicy::Basic256Sha256
-            | SecurityPolicy::Aes256Sha256RsaPss => AES256_KEY_SIZE,
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccBrainpoolP384r1 => AES256_KEY_SIZE,
+
+            SecurityPolicy::EccCurve25519 => CHACHA20_POLY1305_KEY_SIZE,
             _ => 0,
         }
     }
 
-    /// Encrypt data in `src` into `dst`.
+    /// Encrypt data in `src` into `dst`. With authenticated encryption the tag is appended to
+    /// the encrypted data, see [`AesKey::encrypt_aead`].
     pub fn encrypt(&self, src: &[u8], iv: &[u8], dst: &mut [u8]) -> EncryptResult {
         match self.security_policy {
-            SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Aes128Sha256RsaOaep => {
-                self.encrypt_aes128_cbc(src, iv, dst)
-            }
+            SecurityPolicy::Basic128Rsa15
+            | SecurityPolicy::Aes128Sha256RsaOaep
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccBrainpoolP256r1 => self.encrypt_aes128_cbc(src, iv, dst),
 
             SecurityPolicy::Basic256
             | SecurityPolicy::Basic256Sha256
-            | SecurityPolicy::Aes256Sha256RsaPss => self.encrypt_aes256_cbc(src, iv, dst),
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccBrainpoolP384r1 => self.encrypt_aes256_cbc(src, iv, dst),
+
+            SecurityPolicy::EccCurve25519 => self.encrypt_aead(src, iv, &[], dst),
 
             _ => Err(Error::new(
                 StatusCode::BadUnexpectedError,
@@ -178,13 +292,18 @@ impl AesKey {
     /// Decrypts data using AES. The initialization vector is the nonce generated for the secure channel
     pub fn decrypt(&self, src: &[u8], iv: &[u8], dst: &mut [u8]) -> EncryptResult {
         match self.security_policy {
-            SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Aes128Sha256RsaOaep => {
-                self.decrypt_aes128_cbc(src, iv, dst)
-            }
+            SecurityPolicy::Basic128Rsa15
+            | SecurityPolicy::Aes128Sha256RsaOaep
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccBrainpoolP256r1 => self.decrypt_aes128_cbc(src, iv, dst),
 
             SecurityPolicy::Basic256
             | SecurityPolicy::Basic256Sha256
-            | SecurityPolicy::Aes256Sha256RsaPss => self.decrypt_aes256_cbc(src, iv, dst),
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccBrainpoolP384r1 => self.decrypt_aes256_cbc(src, iv, dst),
+
+            SecurityPolicy::EccCurve25519 => self.decrypt_aead(src, iv, &[], dst),
 
             _ => Err(Error::new(
                 StatusCode::BadUnexpectedError,
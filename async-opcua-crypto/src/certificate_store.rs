@@ -2,44 +2,80 @@
 // SPDX-License-Identifier: MPL-2.0
 // Copyright (C) 2017-2024 Adam Lock
 
-//! The certificate store holds and retrieves private keys and certificates from disk. It is responsible
+//! The certificate store holds and retrieves private keys and certificates through a
+//! [`CertificateStoreBackend`], which stores them on disk by default. It is responsible
 //! for checking certificates supplied by the remote end to see if they are valid and trusted or not.
 
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
 
-use log::{debug, error, info, trace, warn};
+use log::{debug, error, info, warn};
 
-use opcua_types::status_code::StatusCode;
+use opcua_types::{status_code::StatusCode, ByteString, TrustListDataType, TrustListMasks};
 
 use super::{
+    certificate_store_backend::{
+        write_cert_and_pkey, CertificateListKind, CertificateStoreBackend,
+        FileSystemCertificateStoreBackend,
+    },
+    crl::X509Crl,
     pkey::PrivateKey,
     security_policy::SecurityPolicy,
     x509::{X509Data, X509},
 };
 
-use rsa;
-use x509_cert;
+/// The maximum number of issuers in a certificate chain. This stops loops between
+/// issuer certificates.
+const MAX_CHAIN_LENGTH: usize = 10;
+
+/// The lists that make up a trust list, with the mask used to select each of them and
+/// whether the list holds CRLs.
+const TRUST_LISTS: [(TrustListMasks, CertificateListKind, bool); 4] = [
+    (
+        TrustListMasks::TrustedCertificates,
+        CertificateListKind::Trusted,
+        false,
+    ),
+    (
+        TrustListMasks::TrustedCrls,
+        CertificateListKind::Trusted,
+        true,
+    ),
+    (
+        TrustListMasks::IssuerCertificates,
+        CertificateListKind::Issuer,
+        false,
+    ),
+    (
+        TrustListMasks::IssuerCrls,
+        CertificateListKind::Issuer,
+        true,
+    ),
+];
+
+/// The parsed content of one of the lists in a trust list.
+enum TrustListContent {
+    Certificates(Vec<X509>),
+    Crls(Vec<X509Crl>),
+}
 
-/// Default path to the applications own certificate
-const OWN_CERTIFICATE_PATH: &str = "own/cert.der";
-/// Default path to the applications own private key
-const OWN_PRIVATE_KEY_PATH: &str = "private/private.pem";
-/// The directory holding trusted certificates
-const TRUSTED_CERTS_DIR: &str = "trusted";
-/// The directory holding rejected certificates
-const REJECTED_CERTS_DIR: &str = "rejected";
+/// A certificate chain built from the trusted and issuer certificates in the store.
+struct CertificateChain {
+    /// The certificate followed by its issuers.
+    certs: Vec<X509>,
+    /// The chain ends with a self-signed certificate.
+    complete: bool,
+    /// One of the issuers in the chain is a trusted certificate.
+    trusted: bool,
+}
 
 /// The certificate store manages the storage of a server/client's own certificate & private key
 /// and the trust / rejection of certificates from the other end.
 pub struct CertificateStore {
-    /// Path to the applications own certificate
-    own_certificate_path: PathBuf,
-    /// Path to the applications own private key
-    own_private_key_path: PathBuf,
-    /// Path to the certificate store on disk
-    pub(crate) pki_path: PathBuf,
+    /// Where certificates and keys are stored
+    backend: Arc<dyn CertificateStoreBackend>,
     /// Timestamps of the cert are normally checked on the cert to ensure it cannot be used before
     /// or after its limits, but this check can be disabled.
     check_time: bool,
@@ -47,10 +83,24 @@ pub struct CertificateStore {
     /// uri and the not before / after values). Certificates are always checked to see if they are
     /// trusted and have a valid key length.
     skip_verify_certs: bool,
-    /// Ordinarily an unknown cert will be dropped into the rejected folder, but it can be dropped
-    /// into the trusted folder if this flag is set. Certs in the trusted folder must still pass
+    /// Ordinarily an unknown cert will be dropped into the rejected list, but it can be dropped
+    /// into the trusted list if this flag is set. Certs in the trusted list must still pass
     /// validity checks.
     trust_unknown_certs: bool,
+    /// Ordinarily a cert is rejected if one of its issuers has no CRL, or only CRLs that are
+    /// out of date, since it cannot be checked for revocation. This flag accepts such certs.
+    allow_missing_crls: bool,
+}
+
+impl std::fmt::Debug for CertificateStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateStore")
+            .field("check_time", &self.check_time)
+            .field("skip_verify_certs", &self.skip_verify_certs)
+            .field("trust_unknown_certs", &self.trust_unknown_certs)
+            .field("allow_missing_crls", &self.allow_missing_crls)
+            .finish_non_exhaustive()
+    }
 }
 
 impl CertificateStore {
@@ -58,13 +108,19 @@ impl CertificateStore {
     /// It is a bad idea to have more than one running instance pointing to the same path
     /// location on disk.
     pub fn new(pki_path: &Path) -> CertificateStore {
+        CertificateStore::new_with_backend(Arc::new(FileSystemCertificateStoreBackend::new(
+            pki_path,
+        )))
+    }
+
+    /// Sets up the certificate store to use the given backend for storage.
+    pub fn new_with_backend(backend: Arc<dyn CertificateStoreBackend>) -> CertificateStore {
         CertificateStore {
-            own_certificate_path: PathBuf::from(OWN_CERTIFICATE_PATH),
-            own_private_key_path: PathBuf::from(OWN_PRIVATE_KEY_PATH),
-            pki_path: pki_path.to_path_buf(),
+            backend,
             check_time: true,
             skip_verify_certs: false,
             trust_unknown_certs: false,
+            allow_missing_crls: false,
         }
     }
 
@@ -80,13 +136,27 @@ impl CertificateStore {
     where
         X: Into<X509Data>,
     {
-        let mut certificate_store = CertificateStore::new(pki_path);
+        let mut backend = FileSystemCertificateStoreBackend::new(pki_path);
         if let (Some(cert_path), Some(pkey_path)) = (cert_path, pkey_path) {
-            certificate_store.own_certificate_path = cert_path.to_path_buf();
-            certificate_store.own_private_key_path = pkey_path.to_path_buf();
+            backend.set_own_paths(cert_path, pkey_path);
         }
-        let (cert, pkey) = if certificate_store.ensure_pki_path().is_err() {
-            error!("Folder for storing certificates cannot be examined so server has no application instance certificate or private key.");
+        CertificateStore::new_with_backend_and_x509_data(Arc::new(backend), overwrite, x509_data)
+    }
+
+    /// Create a new certificate store using the given backend for storage. If the backend
+    /// does not hold an application certificate and private key, they are created from
+    /// `x509_data` when it is set.
+    pub fn new_with_backend_and_x509_data<X>(
+        backend: Arc<dyn CertificateStoreBackend>,
+        overwrite: bool,
+        x509_data: Option<X>,
+    ) -> (CertificateStore, Option<X509>, Option<PrivateKey>)
+    where
+        X: Into<X509Data>,
+    {
+        let certificate_store = CertificateStore::new_with_backend(backend);
+        let (cert, pkey) = if let Err(e) = certificate_store.backend.initialize() {
+            error!("Certificate store cannot be initialized so there is no application instance certificate or private key: {e}");
             (None, None)
         } else {
             let cert = certificate_store.read_own_cert();
@@ -119,6 +189,11 @@ impl CertificateStore {
         (certificate_store, cert, pkey)
     }
 
+    /// Get the backend used to store certificates and keys.
+    pub fn backend(&self) -> &Arc<dyn CertificateStoreBackend> {
+        &self.backend
+    }
+
     /// Set `skip_verify_certs` to not verify incoming certificates.
     pub fn set_skip_verify_certs(&mut self, skip_verify_certs: bool) {
         self.skip_verify_certs = skip_verify_certs;
@@ -135,6 +210,13 @@ impl CertificateStore {
         self.check_time = check_time;
     }
 
+    /// Set `allow_missing_crls` to accept certificates issued by a CA without a CRL,
+    /// or with an out of date CRL, instead of rejecting them because their revocation
+    /// status is unknown.
+    pub fn set_allow_missing_crls(&mut self, allow_missing_crls: bool) {
+        self.allow_missing_crls = allow_missing_crls;
+    }
+
     /// Reads a private key from a path on disk.
     pub fn read_pkey(path: &Path) -> Result<PrivateKey, String> {
         if let Ok(pkey) = PrivateKey::read_pem_file(path) {
@@ -146,22 +228,37 @@ impl CertificateStore {
 
     /// Reads the store's own certificate
     pub fn read_own_cert(&self) -> Result<X509, String> {
-        CertificateStore::read_cert(&self.own_certificate_path()).map_err(|e| {
-            format!(
-                "Cannot read cert from path {:?}: {e}",
-                self.own_certificate_path()
-            )
-        })
+        self.backend.read_own_cert()
     }
 
-    /// Read own private key from file.
+    /// Read own private key.
     pub fn read_own_pkey(&self) -> Result<PrivateKey, String> {
-        CertificateStore::read_pkey(&self.own_private_key_path()).map_err(|e| {
-            format!(
-                "Cannot read pkey from path {:?}: {e}",
-                self.own_private_key_path()
-            )
-        })
+        self.backend.read_own_pkey()
+    }
+
+    /// Reads the store's own certificate for use with `security_policy`. This is the
+    /// certificate for the key type required by the policy, or the default certificate
+    /// if the policy does not use certificates.
+    pub fn read_own_cert_for_policy(
+        &self,
+        security_policy: SecurityPolicy,
+    ) -> Result<X509, String> {
+        match security_policy.key_type() {
+            Some(key_type) => self.backend.read_own_cert_for_key_type(key_type),
+            None => self.read_own_cert(),
+        }
+    }
+
+    /// Read own private key for use with `security_policy`, see
+    /// [`read_own_cert_for_policy`](Self::read_own_cert_for_policy).
+    pub fn read_own_pkey_for_policy(
+        &self,
+        security_policy: SecurityPolicy,
+    ) -> Result<PrivateKey, String> {
+        match security_policy.key_type() {
+            Some(key_type) => self.backend.read_own_pkey_for_key_type(key_type),
+            None => self.read_own_pkey(),
+        }
     }
 
     /// Create a certificate and key pair to the specified locations
@@ -172,39 +269,26 @@ impl CertificateStore {
         pkey_path: &Path,
     ) -> Result<(X509, PrivateKey), String> {
         let (cert, pkey) = X509::cert_and_pkey(args)?;
-
-        // Write the public cert
-        let _ = CertificateStore::store_cert(&cert, cert_path, overwrite)?;
-
-        // Write the private key
-        use rsa::pkcs8;
-        use x509_cert::der::pem::PemLabel;
-        let doc = pkey.to_der().unwrap();
-        let pem = doc
-            .to_pem(rsa::pkcs8::PrivateKeyInfo::PEM_LABEL, pkcs8::LineEnding::CR)
-            .unwrap();
-        let _ = CertificateStore::write_to_file(pem.as_bytes(), pkey_path, overwrite)?;
+        write_cert_and_pkey(&cert, &pkey, overwrite, cert_path, pkey_path)?;
         Ok((cert, pkey))
     }
 
     /// This function will use the supplied arguments to create an Application Instance Certificate
     /// consisting of a X509v3 certificate and public/private key pair. The cert (including pubkey)
-    /// and private key will be written to disk under the pki path.
+    /// and private key will be written to the backend.
     pub fn create_and_store_application_instance_cert(
         &self,
         args: &X509Data,
         overwrite: bool,
     ) -> Result<(X509, PrivateKey), String> {
-        CertificateStore::create_certificate_and_key(
-            args,
-            overwrite,
-            &self.own_certificate_path(),
-            &self.own_private_key_path(),
-        )
+        let (cert, pkey) = X509::cert_and_pkey(args)?;
+        self.backend
+            .store_own_cert_and_pkey(&cert, &pkey, overwrite)?;
+        Ok((cert, pkey))
     }
 
     /// Validates the cert as trusted and valid. If the cert is unknown, it will be written to
-    /// the rejected folder so that the administrator can manually move it to the trusted folder.
+    /// the rejected list so that the administrator can manually move it to the trusted list.
     ///
     /// # Errors
     ///
@@ -221,52 +305,12 @@ impl CertificateStore {
         self.validate_application_instance_cert(cert, security_policy, hostname, application_uri)
     }
 
-    /// Ensures that the cert provided is the same as the one specified by a path. This is a
-    /// security check to stop someone from renaming a cert on disk to match another cert and
-    /// somehow bypassing or subverting a check. The disk cert must exactly match the memory cert
-    /// or the test is assumed to fail.
-    fn ensure_cert_and_file_are_the_same(cert: &X509, cert_path: &Path) -> bool {
-        if !cert_path.exists() {
-            trace!("Cannot find cert on disk");
-            false
-        } else {
-            match CertificateStore::read_cert(cert_path) {
-                Ok(file_der) => {
-                    // Compare the buffers
-                    trace!("Comparing cert on disk to memory");
-                    let der;
-                    {
-                        let r = cert.to_der();
-                        match r {
-                            Err(_) => return false,
-                            Ok(val) => der = val,
-                        }
-                    }
-
-                    let target_der;
-                    {
-                        let r = file_der.to_der();
-                        match r {
-                            Err(_) => return false,
-                            Ok(val) => target_der = val,
-                        }
-                    }
-
-                    der == target_der
-                }
-                Err(err) => {
-                    trace!("Cannot read cert from disk {:?} - {}", cert_path, err);
-                    // No cert2 to compare to
-                    false
-                }
-            }
-        }
-    }
-
     /// Validates the certificate according to the strictness set in the CertificateStore itself.
-    /// Validation might include checking the issue time, expiration time, revocation, trust chain
-    /// etc. In the first instance this function will only check if the cert is recognized
-    /// and is already contained in the trusted or rejected folder.
+    ///
+    /// The cert is trusted if it is in the trusted list, or if it is issued by a CA in the
+    /// trusted list, possibly through intermediate CAs in the issuer list. The cert and its
+    /// issuers are checked against the CRLs of the issuers, and validation might also include
+    /// checking the issue time, expiration time, hostname and application uri.
     ///
     /// # Errors
     ///
@@ -281,110 +325,199 @@ impl CertificateStore {
         application_uri: Option<&str>,
     ) -> Result<(), StatusCode> {
         let cert_file_name = CertificateStore::cert_file_name(cert);
-        debug!("Validating cert with name on disk {}", cert_file_name);
+        debug!("Validating cert {}", cert_file_name);
+
+        let trusted_certs = self.backend.certificates(CertificateListKind::Trusted);
+        let issuer_certs = self.backend.certificates(CertificateListKind::Issuer);
+        let chain = CertificateStore::build_chain(cert, &trusted_certs, &issuer_certs);
+        let trusted_by_ca = chain.trusted && chain.complete;
+
+        // Look for the cert in the rejected list. If it's rejected there is no purpose going
+        // any further. Certs issued by a trusted CA are accepted anyway, since they may have been
+        // rejected before the CA was trusted.
+        if !trusted_by_ca
+            && self
+                .backend
+                .contains_certificate(CertificateListKind::Rejected, cert)
+        {
+            warn!(
+                "Certificate {} is untrusted because it resides in the rejected list",
+                cert_file_name
+            );
+            return Err(StatusCode::BadSecurityChecksFailed);
+        }
 
-        // Look for the cert in the rejected folder. If it's rejected there is no purpose going
-        // any further
+        // Check if cert is in the trusted list. The backend makes sure the stored cert
+        // matches the one supplied
+        if self
+            .backend
+            .contains_certificate(CertificateListKind::Trusted, cert)
         {
-            let mut cert_path = self.rejected_certs_dir();
-            if !cert_path.exists() {
-                error!(
-                    "Path for rejected certificates {} does not exist",
-                    cert_path.display()
-                );
-                return Err(StatusCode::BadUnexpectedError);
-            }
-            cert_path.push(&cert_file_name);
-            if cert_path.exists() {
-                warn!(
-                    "Certificate {} is untrusted because it resides in the rejected directory",
-                    cert_file_name
-                );
-                return Err(StatusCode::BadSecurityChecksFailed);
+            debug!("Certificate {} is in the trusted list", cert_file_name);
+        } else if trusted_by_ca {
+            debug!(
+                "Certificate {} is trusted because it is issued by a trusted CA",
+                cert_file_name
+            );
+        } else if self.trust_unknown_certs {
+            // Put the unknown cert into the trusted list
+            warn!(
+                "Certificate {} is unknown but policy will store it into the trusted list",
+                cert_file_name
+            );
+            if let Err(e) = self
+                .backend
+                .store_certificate(CertificateListKind::Trusted, cert)
+            {
+                warn!("Failed to store trusted certificate: {e}");
             }
+            // Note that we drop through and still check the cert for validity
+        } else if !chain.complete {
+            warn!(
+                "Certificate {} is not issued by a known CA so it will be stored in the rejected list",
+                cert_file_name
+            );
+            let _ = self.store_rejected_cert(cert);
+            return Err(StatusCode::BadCertificateChainIncomplete);
+        } else {
+            warn!(
+                "Certificate {} is unknown and untrusted so it will be stored in the rejected list",
+                cert_file_name
+            );
+            let _ = self.store_rejected_cert(cert);
+            return Err(StatusCode::BadCertificateUntrusted);
         }
 
-        // Check the trusted folder. These checks are more strict to ensure the cert is genuinely
-        // trusted
-        {
-            // Check the trusted folder
-            let mut cert_path = self.trusted_certs_dir();
-            if !cert_path.exists() {
-                error!(
-                    "Path for rejected certificates {} does not exist",
-                    cert_path.display()
-                );
-                return Err(StatusCode::BadUnexpectedError);
+        // Check that the certificate is the right length for the security policy
+        match cert.key_length() {
+            Err(_) => {
+                error!("Cannot read key length from certificate {}", cert_file_name);
+                return Err(StatusCode::BadSecurityChecksFailed);
             }
-            cert_path.push(&cert_file_name);
-
-            // Check if cert is in the trusted folder
-            if !cert_path.exists() {
-                // ... trust checks based on ca could be added here to add cert straight to trust folder
-                if self.trust_unknown_certs {
-                    // Put the unknown cert into the trusted folder
-                    warn!("Certificate {} is unknown but policy will store it into the trusted directory", cert_file_name);
-                    let _ = self.store_trusted_cert(cert);
-                // Note that we drop through and still check the cert for validity
-                } else {
-                    warn!("Certificate {} is unknown and untrusted so it will be stored in rejected directory", cert_file_name);
-                    let _ = self.store_rejected_cert(cert);
-                    return Err(StatusCode::BadCertificateUntrusted);
+            Ok(key_length) => {
+                if !security_policy.is_valid_keylength(key_length) {
+                    warn!(
+                        "Certificate {} has an invalid key length {} for the policy {}",
+                        cert_file_name, key_length, security_policy
+                    );
+                    return Err(StatusCode::BadSecurityChecksFailed);
                 }
             }
+        }
 
-            // Read the cert from the trusted folder to make sure it matches the one supplied
-            if !CertificateStore::ensure_cert_and_file_are_the_same(cert, &cert_path) {
-                error!("Certificate in memory does not match the one on disk {} so cert will automatically be treated as untrusted", cert_path.display());
-                return Err(StatusCode::BadUnexpectedError);
-            }
+        // Check that neither the cert nor its issuers are revoked
+        self.check_revocation(&chain)?;
 
-            // Check that the certificate is the right length for the security policy
-            match cert.key_length() {
-                Err(_) => {
-                    error!("Cannot read key length from certificate {}", cert_file_name);
-                    return Err(StatusCode::BadSecurityChecksFailed);
-                }
-                Ok(key_length) => {
-                    if !security_policy.is_valid_keylength(key_length) {
-                        warn!(
-                            "Certificate {} has an invalid key length {} for the policy {}",
-                            cert_file_name, key_length, security_policy
-                        );
-                        return Err(StatusCode::BadSecurityChecksFailed);
+        if self.skip_verify_certs {
+            debug!(
+                "Skipping additional verifications for certificate {}",
+                cert_file_name
+            );
+            return Ok(());
+        }
+
+        // Now inspect the cert not before / after values to ensure its validity
+        if self.check_time {
+            use chrono::Utc;
+            let now = Utc::now();
+            cert.is_time_valid(&now)?;
+            for issuer in chain.certs.iter().skip(1) {
+                issuer.is_time_valid(&now).map_err(|e| {
+                    if e == StatusCode::BadCertificateTimeInvalid {
+                        StatusCode::BadCertificateIssuerTimeInvalid
+                    } else {
+                        e
                     }
-                }
+                })?;
             }
+        }
 
-            if self.skip_verify_certs {
-                debug!(
-                    "Skipping additional verifications for certificate {}",
-                    cert_file_name
-                );
-                return Ok(());
-            }
+        // Compare the hostname of the cert against the cert supplied
+        if let Some(hostname) = hostname {
+            cert.is_hostname_valid(hostname)?;
+        }
 
-            // Now inspect the cert not before / after values to ensure its validity
-            if self.check_time {
-                use chrono::Utc;
-                let now = Utc::now();
-                cert.is_time_valid(&now)?;
-            }
+        // Compare the application / product uri to the supplied application description
+        if let Some(application_uri) = application_uri {
+            cert.is_application_uri_valid(application_uri)?;
+        }
 
-            // Compare the hostname of the cert against the cert supplied
-            if let Some(hostname) = hostname {
-                cert.is_hostname_valid(hostname)?;
-            }
+        Ok(())
+    }
 
-            // Compare the application / product uri to the supplied application description
-            if let Some(application_uri) = application_uri {
-                cert.is_application_uri_valid(application_uri)?;
+    /// Builds the chain of issuers of `cert`, preferring trusted certificates over
+    /// issuer certificates. The chain is complete if it ends with a self-signed certificate.
+    fn build_chain(cert: &X509, trusted: &[X509], issuers: &[X509]) -> CertificateChain {
+        let mut chain = CertificateChain {
+            certs: vec![cert.clone()],
+            complete: false,
+            trusted: false,
+        };
+        while chain.certs.len() <= MAX_CHAIN_LENGTH {
+            let current = chain.certs.last().unwrap();
+            if current.is_self_signed() {
+                chain.complete = true;
+                break;
             }
+            let is_issuer = |c: &&X509| c.is_ca() && current.is_issued_by(c);
+            let next = if let Some(issuer) = trusted.iter().find(is_issuer) {
+                chain.trusted = true;
+                issuer.clone()
+            } else if let Some(issuer) = issuers.iter().find(is_issuer) {
+                issuer.clone()
+            } else {
+                debug!("No issuer found for {}", current.subject_name());
+                break;
+            };
+            chain.certs.push(next);
+        }
+        chain
+    }
 
-            // Other tests that we might do with trust lists
-            // ... issuer
-            // ... trust (self-signed, ca etc.)
-            // ... revocation
+    /// Checks the CRLs of each issuer in the chain to see if any certificate in the chain
+    /// has been revoked. The revocation status of a certificate is unknown if its issuer
+    /// has no CRL, or if `check_time` is set and all its CRLs are out of date, which is an
+    /// error unless `allow_missing_crls` is set.
+    fn check_revocation(&self, chain: &CertificateChain) -> Result<(), StatusCode> {
+        let crls: Vec<_> = [CertificateListKind::Trusted, CertificateListKind::Issuer]
+            .into_iter()
+            .flat_map(|kind| self.backend.crls(kind))
+            .collect();
+        let now = chrono::Utc::now();
+        for (idx, pair) in chain.certs.windows(2).enumerate() {
+            let (cert, issuer) = (&pair[0], &pair[1]);
+            let issuer_crls: Vec<_> = crls.iter().filter(|crl| crl.is_issued_by(issuer)).collect();
+            let revoked = issuer_crls.iter().any(|crl| crl.is_revoked(cert));
+            if !revoked
+                && !issuer_crls
+                    .iter()
+                    .any(|crl| !self.check_time || !crl.is_out_of_date(&now))
+            {
+                if issuer_crls.is_empty() {
+                    warn!("Issuer {} has no CRL", issuer.subject_name());
+                } else {
+                    warn!("The CRL of issuer {} is out of date", issuer.subject_name());
+                }
+                if !self.allow_missing_crls {
+                    return Err(if idx == 0 {
+                        StatusCode::BadCertificateRevocationUnknown
+                    } else {
+                        StatusCode::BadCertificateIssuerRevocationUnknown
+                    });
+                }
+            }
+            if revoked {
+                warn!(
+                    "Certificate {} has been revoked by {}",
+                    cert.subject_name(),
+                    issuer.subject_name()
+                );
+                return Err(if idx == 0 {
+                    StatusCode::BadCertificateRevoked
+                } else {
+                    StatusCode::BadCertificateIssuerRevoked
+                });
+            }
         }
         Ok(())
     }
@@ -407,112 +540,15 @@ impl CertificateStore {
         }
     }
 
-    /// Creates the PKI directory structure
-    ///
-    /// # Errors
-    ///
-    /// A string description of any failure
-    ///
-    pub fn ensure_pki_path(&self) -> Result<(), String> {
-        let mut path = self.pki_path.clone();
-        let subdirs = [TRUSTED_CERTS_DIR, REJECTED_CERTS_DIR];
-        for subdir in &subdirs {
-            path.push(subdir);
-            CertificateStore::ensure_dir(&path)?;
-            path.pop();
-        }
-        Ok(())
-    }
-
-    /// Ensure the directory exists, creating it if necessary
-    ///
-    /// # Errors
-    ///
-    /// A string description of any failure
-    ///
-    fn ensure_dir(path: &Path) -> Result<(), String> {
-        if path.exists() {
-            if !path.is_dir() {
-                Err(format!("{} is not a directory ", path.display()))
-            } else {
-                Ok(())
-            }
-        } else {
-            std::fs::create_dir_all(path)
-                .map_err(|_| format!("Cannot make directories for {}", path.display()))
-        }
-    }
-
-    /// Get path to application instance certificate
-    pub fn own_certificate_path(&self) -> PathBuf {
-        let mut path = PathBuf::from(&self.pki_path);
-        path.push(&self.own_certificate_path);
-        path
-    }
-
-    /// Get path to application instance private key
-    pub fn own_private_key_path(&self) -> PathBuf {
-        let mut path = PathBuf::from(&self.pki_path);
-        path.push(&self.own_private_key_path);
-        path
-    }
-
-    /// Get the path to the rejected certs dir
-    pub fn rejected_certs_dir(&self) -> PathBuf {
-        let mut path = PathBuf::from(&self.pki_path);
-        path.push(REJECTED_CERTS_DIR);
-        path
-    }
-
-    /// Get the path to the trusted certs dir
-    pub fn trusted_certs_dir(&self) -> PathBuf {
-        let mut path = PathBuf::from(&self.pki_path);
-        path.push(TRUSTED_CERTS_DIR);
-        path
-    }
-
-    /// Write a cert to the rejected directory. If the write succeeds, the function
-    /// returns a path to the written file.
-    ///
-    /// # Errors
-    ///
-    /// A string description of any failure
-    ///
-    pub fn store_rejected_cert(&self, cert: &X509) -> Result<PathBuf, String> {
-        // Store the cert in the rejected folder where untrusted certs go
-        let cert_file_name = CertificateStore::cert_file_name(cert);
-        let mut cert_path = self.rejected_certs_dir();
-        cert_path.push(&cert_file_name);
-        let _ = CertificateStore::store_cert(cert, &cert_path, true)?;
-        Ok(cert_path)
-    }
-
-    /// Writes a cert to the trusted directory. If the write succeeds, the function
-    /// returns a path to the written file.
-    ///
-    /// # Errors
-    ///
-    /// A string description of any failure
-    ///
-    fn store_trusted_cert(&self, cert: &X509) -> Result<PathBuf, String> {
-        // Store the cert in the trusted folder where trusted certs go
-        let cert_file_name = CertificateStore::cert_file_name(cert);
-        let mut cert_path = self.trusted_certs_dir();
-        cert_path.push(&cert_file_name);
-        let _ = CertificateStore::store_cert(cert, &cert_path, true)?;
-        Ok(cert_path)
-    }
-
-    /// Writes a cert to the specified directory
+    /// Write a cert to the rejected list.
     ///
     /// # Errors
     ///
     /// A string description of any failure
     ///
-    fn store_cert(cert: &X509, path: &Path, overwrite: bool) -> Result<usize, String> {
-        let der = cert.to_der().unwrap();
-        info!("Writing X509 cert to {}", path.display());
-        CertificateStore::write_to_file(&der, path, overwrite)
+    pub fn store_rejected_cert(&self, cert: &X509) -> Result<(), String> {
+        self.backend
+            .store_certificate(CertificateListKind::Rejected, cert)
     }
 
     /// Reads an X509 certificate in .def or .pem format from disk
@@ -552,25 +588,127 @@ impl CertificateStore {
         }
     }
 
-    /// Writes bytes to file and returns the size written, or an error reason for failure.
+    /// Read the lists selected by `masks`, a combination of [`TrustListMasks`], into a
+    /// `TrustListDataType`.
+    pub fn read_trust_list(&self, masks: u32) -> TrustListDataType {
+        let mut lists: [Option<Vec<ByteString>>; 4] = Default::default();
+        let mut specified_lists = 0;
+        for (list, (mask, kind, is_crl)) in lists.iter_mut().zip(TRUST_LISTS) {
+            if masks & mask as u32 == 0 {
+                continue;
+            }
+            specified_lists |= mask as u32;
+            *list = Some(if is_crl {
+                self.backend
+                    .crls(kind)
+                    .iter()
+                    .filter_map(|crl| crl.to_der().ok())
+                    .map(ByteString::from)
+                    .collect()
+            } else {
+                self.backend
+                    .certificates(kind)
+                    .iter()
+                    .map(|cert| cert.as_byte_string())
+                    .collect()
+            });
+        }
+        let [trusted_certificates, trusted_crls, issuer_certificates, issuer_crls] = lists;
+        TrustListDataType {
+            specified_lists,
+            trusted_certificates,
+            trusted_crls,
+            issuer_certificates,
+            issuer_crls,
+        }
+    }
+
+    /// Replace the lists specified in `trust_list` with its content. Every entry is
+    /// parsed before the store is modified, so an invalid entry leaves the store unchanged.
     ///
     /// # Errors
     ///
-    /// A string description of any failure
+    /// `BadCertificateInvalid` if an entry cannot be parsed, `BadInternalError` if the
+    /// backend fails to update a list.
     ///
-    fn write_to_file(bytes: &[u8], file_path: &Path, overwrite: bool) -> Result<usize, String> {
-        if !overwrite && file_path.exists() {
-            Err(format!("File {} already exists and will not be overwritten. Enable overwrite to disable this safeguard.", file_path.display()))
-        } else {
-            if let Some(parent) = file_path.parent() {
-                CertificateStore::ensure_dir(parent)?;
-            }
-            match File::create(file_path) {
-                Ok(mut file) => file
-                    .write(bytes)
-                    .map_err(|_| format!("Could not write bytes to file {}", file_path.display())),
-                Err(_) => Err(format!("Could not create file {}", file_path.display())),
+    pub fn update_trust_list(&self, trust_list: TrustListDataType) -> Result<(), StatusCode> {
+        let TrustListDataType {
+            specified_lists,
+            trusted_certificates,
+            trusted_crls,
+            issuer_certificates,
+            issuer_crls,
+        } = trust_list;
+        let lists = [
+            trusted_certificates,
+            trusted_crls,
+            issuer_certificates,
+            issuer_crls,
+        ];
+
+        let mut updates = Vec::new();
+        for (list, (mask, kind, is_crl)) in lists.into_iter().zip(TRUST_LISTS) {
+            if specified_lists & mask as u32 == 0 {
+                continue;
             }
+            let list = list.unwrap_or_default();
+            let content = if is_crl {
+                TrustListContent::Crls(
+                    list.iter()
+                        .map(|v| X509Crl::from_der(v.as_ref()))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| StatusCode::BadCertificateInvalid)?,
+                )
+            } else {
+                TrustListContent::Certificates(
+                    list.iter()
+                        .map(X509::from_byte_string)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| StatusCode::BadCertificateInvalid)?,
+                )
+            };
+            updates.push((kind, content));
         }
+
+        let backend = &self.backend;
+        let result = updates
+            .into_iter()
+            .try_for_each(|(kind, content)| match content {
+                TrustListContent::Certificates(certs) => {
+                    for cert in backend.certificates(kind) {
+                        backend.remove_certificate(kind, &cert)?;
+                    }
+                    certs
+                        .iter()
+                        .try_for_each(|cert| backend.store_certificate(kind, cert))
+                }
+                TrustListContent::Crls(crls) => {
+                    for crl in backend.crls(kind) {
+                        backend.remove_crl(kind, &crl)?;
+                    }
+                    crls.iter().try_for_each(|crl| backend.store_crl(kind, crl))
+                }
+            });
+        result.map_err(|e| {
+            error!("Failed to update trust list: {e}");
+            StatusCode::BadInternalError
+        })
+    }
+
+    /// Reads an X509 CRL in .der, .crl or .pem format from disk
+    ///
+    /// # Errors
+    ///
+    /// A string description of any failure
+    ///
+    pub fn read_crl(path: &Path) -> Result<X509Crl, String> {
+        let data = std::fs::read(path)
+            .map_err(|_| format!("Could not read CRL file {}", path.display()))?;
+        let crl = match path.extension() {
+            Some(v) if v == "der" || v == "crl" => X509Crl::from_der(&data),
+            Some(v) if v == "pem" => X509Crl::from_pem(&data),
+            _ => return Err("Only .der, .crl and .pem CRLs are supported".to_string()),
+        };
+        crl.map_err(|_| format!("Could not read CRL from file {}", path.display()))
     }
 }
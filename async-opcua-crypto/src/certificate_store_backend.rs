@@ -0,0 +1,756 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Storage backends for the certificate store. The backend decides where the application's own
+//! certificate and private key, the trust lists and rejected certificates are kept, while the
+//! [`CertificateStore`](super::CertificateStore) decides whether certificates are trusted.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use log::{info, trace, warn};
+
+use super::{
+    certificate_store::CertificateStore,
+    crl::X509Crl,
+    pkey::{KeyType, PrivateKey},
+    x509::X509,
+};
+
+/// Default path to the applications own certificate
+const OWN_CERTIFICATE_PATH: &str = "own/cert.der";
+/// Default path to the applications own private key
+const OWN_PRIVATE_KEY_PATH: &str = "private/private.pem";
+/// The directory holding trusted application and CA certificates
+const TRUSTED_CERTS_DIR: &str = "trusted/certs";
+/// The directory holding CRLs issued by trusted CAs
+const TRUSTED_CRL_DIR: &str = "trusted/crl";
+/// The directory holding CA certificates which are not trusted, but are used to build chains
+const ISSUER_CERTS_DIR: &str = "issuers/certs";
+/// The directory holding CRLs issued by the issuer CAs
+const ISSUER_CRL_DIR: &str = "issuers/crl";
+/// The directory holding rejected certificates
+const REJECTED_CERTS_DIR: &str = "rejected";
+/// Older versions stored trusted certificates directly in this directory
+const LEGACY_TRUSTED_CERTS_DIR: &str = "trusted";
+
+/// The lists of certificates kept by a certificate store backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CertificateListKind {
+    /// Trusted application and CA certificates, and the CRLs of the trusted CAs.
+    Trusted,
+    /// CA certificates which are used to build certificate chains without being trusted
+    /// themselves, and their CRLs.
+    Issuer,
+    /// Certificates that were rejected, so that an administrator can decide to trust them
+    /// later. This list never holds CRLs.
+    Rejected,
+}
+
+/// Storage for the application's own certificate and private key, and for the
+/// certificates and CRLs used to validate the certificates of the other end.
+///
+/// The store is shared between connections, so implementations must use interior
+/// mutability. Implement this trait to keep certificates in an external secret store,
+/// for example by wrapping one of the provided backends and reading the private key
+/// from elsewhere.
+pub trait CertificateStoreBackend: Send + Sync {
+    /// Prepare the backend for use, e.g. by creating directories. This is called once
+    /// when the certificate store is created.
+    fn initialize(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Read the application's own certificate.
+    fn read_own_cert(&self) -> Result<X509, String>;
+
+    /// Read the application's own private key.
+    fn read_own_pkey(&self) -> Result<PrivateKey, String>;
+
+    /// Read the application's own certificate for keys of type `key_type`. An application
+    /// has one certificate per key type, so that e.g. RSA and ECC security policies can be
+    /// used at the same time. The default implementation only knows the certificate returned
+    /// by [`read_own_cert`](Self::read_own_cert).
+    fn read_own_cert_for_key_type(&self, key_type: KeyType) -> Result<X509, String> {
+        let cert = self.read_own_cert()?;
+        match cert.public_key() {
+            Ok(public_key) if public_key.key_type() == key_type => Ok(cert),
+            _ => Err(format!(
+                "No application instance certificate for {key_type:?} keys"
+            )),
+        }
+    }
+
+    /// Read the application's own private key for keys of type `key_type`. The default
+    /// implementation only knows the private key returned by [`read_own_pkey`](Self::read_own_pkey).
+    fn read_own_pkey_for_key_type(&self, key_type: KeyType) -> Result<PrivateKey, String> {
+        let pkey = self.read_own_pkey()?;
+        if pkey.key_type() == key_type {
+            Ok(pkey)
+        } else {
+            Err(format!(
+                "No application instance private key for {key_type:?} keys"
+            ))
+        }
+    }
+
+    /// Store the application's own certificate and private key, replacing the certificate
+    /// for the key type of `pkey`. Fails if there already is a certificate for that key type
+    /// and `overwrite` is `false`.
+    fn store_own_cert_and_pkey(
+        &self,
+        cert: &X509,
+        pkey: &PrivateKey,
+        overwrite: bool,
+    ) -> Result<(), String>;
+
+    /// Get all certificates in the list `kind`. Certificates that cannot be read are skipped.
+    fn certificates(&self, kind: CertificateListKind) -> Vec<X509>;
+
+    /// Test if `cert` is in the list `kind`.
+    fn contains_certificate(&self, kind: CertificateListKind, cert: &X509) -> bool {
+        let thumbprint = cert.thumbprint();
+        self.certificates(kind)
+            .iter()
+            .any(|c| c.thumbprint() == thumbprint)
+    }
+
+    /// Add `cert` to the list `kind`, replacing it if it is already present.
+    fn store_certificate(&self, kind: CertificateListKind, cert: &X509) -> Result<(), String>;
+
+    /// Remove `cert` from the list `kind`. Returns `true` if the certificate was present.
+    fn remove_certificate(&self, kind: CertificateListKind, cert: &X509) -> Result<bool, String>;
+
+    /// Get all CRLs in the list `kind`. CRLs that cannot be read are skipped.
+    fn crls(&self, kind: CertificateListKind) -> Vec<X509Crl>;
+
+    /// Add `crl` to the list `kind`, replacing it if it is already present.
+    fn store_crl(&self, kind: CertificateListKind, crl: &X509Crl) -> Result<(), String>;
+
+    /// Remove `crl` from the list `kind`. Returns `true` if the CRL was present.
+    fn remove_crl(&self, kind: CertificateListKind, crl: &X509Crl) -> Result<bool, String>;
+}
+
+/// Certificate store backend keeping certificates in a PKI directory on disk, laid out as
+///
+/// ```text
+/// own/cert.der
+/// private/private.pem
+/// trusted/certs
+/// trusted/crl
+/// issuers/certs
+/// issuers/crl
+/// rejected
+/// ```
+///
+/// The own certificate and private key paths hold the application certificate of any key
+/// type. Certificates for other key types are stored next to them, with the key type
+/// appended to the file name, e.g. `own/cert_nistP256.der` and `private/private_nistP256.pem`.
+///
+/// It is a bad idea to have more than one running instance pointing to the same path
+/// location on disk.
+#[derive(Debug, Clone)]
+pub struct FileSystemCertificateStoreBackend {
+    /// Path to the applications own certificate
+    own_certificate_path: PathBuf,
+    /// Path to the applications own private key
+    own_private_key_path: PathBuf,
+    /// Path to the certificate store on disk
+    pki_path: PathBuf,
+}
+
+impl FileSystemCertificateStoreBackend {
+    /// Create a backend using the PKI directory at `pki_path`.
+    pub fn new(pki_path: &Path) -> Self {
+        Self {
+            own_certificate_path: PathBuf::from(OWN_CERTIFICATE_PATH),
+            own_private_key_path: PathBuf::from(OWN_PRIVATE_KEY_PATH),
+            pki_path: pki_path.to_path_buf(),
+        }
+    }
+
+    /// Set the paths to the application's own certificate and private key. Relative
+    /// paths are relative to the PKI directory.
+    pub fn set_own_paths(&mut self, cert_path: &Path, pkey_path: &Path) {
+        self.own_certificate_path = cert_path.to_path_buf();
+        self.own_private_key_path = pkey_path.to_path_buf();
+    }
+
+    /// Get the path to the PKI directory
+    pub fn pki_path(&self) -> &Path {
+        &self.pki_path
+    }
+
+    /// Creates the PKI directory structure
+    ///
+    /// # Errors
+    ///
+    /// A string description of any failure
+    ///
+    pub fn ensure_pki_path(&self) -> Result<(), String> {
+        let subdirs = [
+            TRUSTED_CERTS_DIR,
+            TRUSTED_CRL_DIR,
+            ISSUER_CERTS_DIR,
+            ISSUER_CRL_DIR,
+            REJECTED_CERTS_DIR,
+        ];
+        for subdir in &subdirs {
+            ensure_dir(&self.pki_path.join(subdir))?;
+        }
+
+        // Move certs trusted by older versions to the trusted certs directory
+        let legacy_dir = self.pki_path.join(LEGACY_TRUSTED_CERTS_DIR);
+        for path in files_in_dir(&legacy_dir, &["der", "pem"]) {
+            let target = self.trusted_certs_dir().join(path.file_name().unwrap());
+            info!(
+                "Moving trusted certificate {} to {}",
+                path.display(),
+                target.display()
+            );
+            std::fs::rename(&path, &target)
+                .map_err(|e| format!("Cannot move {}: {e}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Get path to application instance certificate
+    pub fn own_certificate_path(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.pki_path);
+        path.push(&self.own_certificate_path);
+        path
+    }
+
+    /// Get path to application instance private key
+    pub fn own_private_key_path(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.pki_path);
+        path.push(&self.own_private_key_path);
+        path
+    }
+
+    /// Get the paths to the application instance certificate and private key for keys of
+    /// type `key_type`. These are the configured paths if they are unused or hold a key of
+    /// the same type, otherwise the key type is appended to the file names.
+    pub fn own_paths_for_key_type(&self, key_type: KeyType) -> (PathBuf, PathBuf) {
+        let cert_path = self.own_certificate_path();
+        let pkey_path = self.own_private_key_path();
+        let default_key_type = if cert_path.exists() {
+            CertificateStore::read_cert(&cert_path)
+                .ok()
+                .and_then(|cert| cert.public_key().ok())
+                .map(|public_key| public_key.key_type())
+        } else {
+            None
+        };
+        if default_key_type.is_none_or(|t| t == key_type) {
+            (cert_path, pkey_path)
+        } else {
+            let suffix = key_type_file_suffix(key_type);
+            (
+                append_to_file_stem(&cert_path, suffix),
+                append_to_file_stem(&pkey_path, suffix),
+            )
+        }
+    }
+
+    /// Get the path to the rejected certs dir
+    pub fn rejected_certs_dir(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.pki_path);
+        path.push(REJECTED_CERTS_DIR);
+        path
+    }
+
+    /// Get the path to the trusted certs dir
+    pub fn trusted_certs_dir(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.pki_path);
+        path.push(TRUSTED_CERTS_DIR);
+        path
+    }
+
+    /// Get the path to the dir of CRLs issued by trusted CAs
+    pub fn trusted_crl_dir(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.pki_path);
+        path.push(TRUSTED_CRL_DIR);
+        path
+    }
+
+    /// Get the path to the issuer certs dir, which holds CA certificates that are used
+    /// to build certificate chains without being trusted themselves
+    pub fn issuer_certs_dir(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.pki_path);
+        path.push(ISSUER_CERTS_DIR);
+        path
+    }
+
+    /// Get the path to the dir of CRLs issued by the issuer CAs
+    pub fn issuer_crl_dir(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.pki_path);
+        path.push(ISSUER_CRL_DIR);
+        path
+    }
+
+    /// Get the directory holding the certificates in the list `kind`
+    pub fn certs_dir(&self, kind: CertificateListKind) -> PathBuf {
+        match kind {
+            CertificateListKind::Trusted => self.trusted_certs_dir(),
+            CertificateListKind::Issuer => self.issuer_certs_dir(),
+            CertificateListKind::Rejected => self.rejected_certs_dir(),
+        }
+    }
+
+    /// Get the directory holding the CRLs in the list `kind`, if the list can hold CRLs
+    pub fn crl_dir(&self, kind: CertificateListKind) -> Option<PathBuf> {
+        match kind {
+            CertificateListKind::Trusted => Some(self.trusted_crl_dir()),
+            CertificateListKind::Issuer => Some(self.issuer_crl_dir()),
+            CertificateListKind::Rejected => None,
+        }
+    }
+
+    /// Ensures that the cert provided is the same as the one specified by a path. This is a
+    /// security check to stop someone from renaming a cert on disk to match another cert and
+    /// somehow bypassing or subverting a check. The disk cert must exactly match the memory cert
+    /// or the test is assumed to fail.
+    fn ensure_cert_and_file_are_the_same(cert: &X509, cert_path: &Path) -> bool {
+        if !cert_path.exists() {
+            trace!("Cannot find cert on disk");
+            return false;
+        }
+        match CertificateStore::read_cert(cert_path) {
+            Ok(file_cert) => {
+                trace!("Comparing cert on disk to memory");
+                match (cert.to_der(), file_cert.to_der()) {
+                    (Ok(der), Ok(target_der)) => der == target_der,
+                    _ => false,
+                }
+            }
+            Err(err) => {
+                trace!("Cannot read cert from disk {:?} - {}", cert_path, err);
+                false
+            }
+        }
+    }
+}
+
+impl CertificateStoreBackend for FileSystemCertificateStoreBackend {
+    fn initialize(&self) -> Result<(), String> {
+        self.ensure_pki_path()
+    }
+
+    fn read_own_cert(&self) -> Result<X509, String> {
+        CertificateStore::read_cert(&self.own_certificate_path()).map_err(|e| {
+            format!(
+                "Cannot read cert from path {:?}: {e}",
+                self.own_certificate_path()
+            )
+        })
+    }
+
+    fn read_own_pkey(&self) -> Result<PrivateKey, String> {
+        CertificateStore::read_pkey(&self.own_private_key_path()).map_err(|e| {
+            format!(
+                "Cannot read pkey from path {:?}: {e}",
+                self.own_private_key_path()
+            )
+        })
+    }
+
+    fn read_own_cert_for_key_type(&self, key_type: KeyType) -> Result<X509, String> {
+        let (cert_path, _) = self.own_paths_for_key_type(key_type);
+        let cert = CertificateStore::read_cert(&cert_path)
+            .map_err(|e| format!("Cannot read cert from path {cert_path:?}: {e}"))?;
+        match cert.public_key() {
+            Ok(public_key) if public_key.key_type() == key_type => Ok(cert),
+            _ => Err(format!(
+                "Cert at path {cert_path:?} is not for {key_type:?} keys"
+            )),
+        }
+    }
+
+    fn read_own_pkey_for_key_type(&self, key_type: KeyType) -> Result<PrivateKey, String> {
+        let (_, pkey_path) = self.own_paths_for_key_type(key_type);
+        let pkey = CertificateStore::read_pkey(&pkey_path)
+            .map_err(|e| format!("Cannot read pkey from path {pkey_path:?}: {e}"))?;
+        if pkey.key_type() == key_type {
+            Ok(pkey)
+        } else {
+            Err(format!(
+                "Pkey at path {pkey_path:?} is not a {key_type:?} key"
+            ))
+        }
+    }
+
+    fn store_own_cert_and_pkey(
+        &self,
+        cert: &X509,
+        pkey: &PrivateKey,
+        overwrite: bool,
+    ) -> Result<(), String> {
+        let (cert_path, pkey_path) = self.own_paths_for_key_type(pkey.key_type());
+        write_cert_and_pkey(cert, pkey, overwrite, &cert_path, &pkey_path)
+    }
+
+    fn certificates(&self, kind: CertificateListKind) -> Vec<X509> {
+        files_in_dir(&self.certs_dir(kind), &["der", "pem"])
+            .into_iter()
+            .filter_map(|path| {
+                CertificateStore::read_cert(&path)
+                    .map_err(|e| warn!("{e}"))
+                    .ok()
+            })
+            .collect()
+    }
+
+    fn contains_certificate(&self, kind: CertificateListKind, cert: &X509) -> bool {
+        let cert_path = self
+            .certs_dir(kind)
+            .join(CertificateStore::cert_file_name(cert));
+        if !cert_path.exists() {
+            return false;
+        }
+        // Read the cert from disk to make sure it matches the one supplied
+        if !Self::ensure_cert_and_file_are_the_same(cert, &cert_path) {
+            warn!(
+                "Certificate in memory does not match the one on disk {}",
+                cert_path.display()
+            );
+            return false;
+        }
+        true
+    }
+
+    fn store_certificate(&self, kind: CertificateListKind, cert: &X509) -> Result<(), String> {
+        let cert_path = self
+            .certs_dir(kind)
+            .join(CertificateStore::cert_file_name(cert));
+        write_cert(cert, &cert_path, true)
+    }
+
+    fn remove_certificate(&self, kind: CertificateListKind, cert: &X509) -> Result<bool, String> {
+        let thumbprint = cert.thumbprint();
+        let mut removed = false;
+        for path in files_in_dir(&self.certs_dir(kind), &["der", "pem"]) {
+            let Ok(file_cert) = CertificateStore::read_cert(&path) else {
+                continue;
+            };
+            if file_cert.thumbprint() == thumbprint {
+                info!("Removing X509 cert {}", path.display());
+                std::fs::remove_file(&path)
+                    .map_err(|e| format!("Cannot remove {}: {e}", path.display()))?;
+                removed = true;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn crls(&self, kind: CertificateListKind) -> Vec<X509Crl> {
+        let Some(dir) = self.crl_dir(kind) else {
+            return Vec::new();
+        };
+        files_in_dir(&dir, &["der", "crl", "pem"])
+            .into_iter()
+            .filter_map(|path| {
+                CertificateStore::read_crl(&path)
+                    .map_err(|e| warn!("{e}"))
+                    .ok()
+            })
+            .collect()
+    }
+
+    fn store_crl(&self, kind: CertificateListKind, crl: &X509Crl) -> Result<(), String> {
+        let Some(dir) = self.crl_dir(kind) else {
+            return Err(format!("Cannot store CRLs in the {kind:?} list"));
+        };
+        let path = dir.join(format!("{}.crl", crl.thumbprint().as_hex_string()));
+        let der = crl.to_der().map_err(|e| e.to_string())?;
+        info!("Writing X509 CRL to {}", path.display());
+        write_to_file(&der, &path, true).map(|_| ())
+    }
+
+    fn remove_crl(&self, kind: CertificateListKind, crl: &X509Crl) -> Result<bool, String> {
+        let Some(dir) = self.crl_dir(kind) else {
+            return Ok(false);
+        };
+        let thumbprint = crl.thumbprint();
+        let mut removed = false;
+        for path in files_in_dir(&dir, &["der", "crl", "pem"]) {
+            let Ok(file_crl) = CertificateStore::read_crl(&path) else {
+                continue;
+            };
+            if file_crl.thumbprint() == thumbprint {
+                info!("Removing X509 CRL {}", path.display());
+                std::fs::remove_file(&path)
+                    .map_err(|e| format!("Cannot remove {}: {e}", path.display()))?;
+                removed = true;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[derive(Default)]
+struct InMemoryState {
+    /// The own certificates and private keys, one per key type. The first one is returned
+    /// by `read_own_cert` and `read_own_pkey`.
+    own: Vec<(X509, PrivateKey)>,
+    certs: HashMap<CertificateListKind, Vec<X509>>,
+    crls: HashMap<CertificateListKind, Vec<X509Crl>>,
+}
+
+/// Certificate store backend keeping everything in memory, for applications that cannot
+/// write to disk. Nothing is persisted, so certificates trusted or rejected at runtime
+/// are lost when the application stops.
+#[derive(Default)]
+pub struct InMemoryCertificateStoreBackend {
+    state: RwLock<InMemoryState>,
+}
+
+impl InMemoryCertificateStoreBackend {
+    /// Create a new, empty in-memory backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new in-memory backend with the application's own certificate and private key.
+    pub fn new_with_own_cert_and_pkey(cert: X509, pkey: PrivateKey) -> Self {
+        Self {
+            state: RwLock::new(InMemoryState {
+                own: vec![(cert, pkey)],
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+impl CertificateStoreBackend for InMemoryCertificateStoreBackend {
+    fn read_own_cert(&self) -> Result<X509, String> {
+        let state = self.state.read().unwrap();
+        state
+            .own
+            .first()
+            .map(|(cert, _)| cert.clone())
+            .ok_or_else(|| "No application instance certificate".to_string())
+    }
+
+    fn read_own_pkey(&self) -> Result<PrivateKey, String> {
+        let state = self.state.read().unwrap();
+        state
+            .own
+            .first()
+            .map(|(_, pkey)| pkey.clone())
+            .ok_or_else(|| "No application instance private key".to_string())
+    }
+
+    fn read_own_cert_for_key_type(&self, key_type: KeyType) -> Result<X509, String> {
+        let state = self.state.read().unwrap();
+        state
+            .own
+            .iter()
+            .find(|(_, pkey)| pkey.key_type() == key_type)
+            .map(|(cert, _)| cert.clone())
+            .ok_or_else(|| format!("No application instance certificate for {key_type:?} keys"))
+    }
+
+    fn read_own_pkey_for_key_type(&self, key_type: KeyType) -> Result<PrivateKey, String> {
+        let state = self.state.read().unwrap();
+        state
+            .own
+            .iter()
+            .find(|(_, pkey)| pkey.key_type() == key_type)
+            .map(|(_, pkey)| pkey.clone())
+            .ok_or_else(|| format!("No application instance private key for {key_type:?} keys"))
+    }
+
+    fn store_own_cert_and_pkey(
+        &self,
+        cert: &X509,
+        pkey: &PrivateKey,
+        overwrite: bool,
+    ) -> Result<(), String> {
+        let mut state = self.state.write().unwrap();
+        let key_type = pkey.key_type();
+        match state.own.iter_mut().find(|(_, p)| p.key_type() == key_type) {
+            Some(_) if !overwrite => {
+                return Err("Application instance certificate already exists and will not be overwritten. Enable overwrite to disable this safeguard.".to_string());
+            }
+            Some(own) => *own = (cert.clone(), pkey.clone()),
+            None => state.own.push((cert.clone(), pkey.clone())),
+        }
+        Ok(())
+    }
+
+    fn certificates(&self, kind: CertificateListKind) -> Vec<X509> {
+        let state = self.state.read().unwrap();
+        state.certs.get(&kind).cloned().unwrap_or_default()
+    }
+
+    fn store_certificate(&self, kind: CertificateListKind, cert: &X509) -> Result<(), String> {
+        let mut state = self.state.write().unwrap();
+        let certs = state.certs.entry(kind).or_default();
+        let thumbprint = cert.thumbprint();
+        certs.retain(|c| c.thumbprint() != thumbprint);
+        certs.push(cert.clone());
+        Ok(())
+    }
+
+    fn remove_certificate(&self, kind: CertificateListKind, cert: &X509) -> Result<bool, String> {
+        let mut state = self.state.write().unwrap();
+        let Some(certs) = state.certs.get_mut(&kind) else {
+            return Ok(false);
+        };
+        let len = certs.len();
+        let thumbprint = cert.thumbprint();
+        certs.retain(|c| c.thumbprint() != thumbprint);
+        Ok(certs.len() != len)
+    }
+
+    fn crls(&self, kind: CertificateListKind) -> Vec<X509Crl> {
+        let state = self.state.read().unwrap();
+        state.crls.get(&kind).cloned().unwrap_or_default()
+    }
+
+    fn store_crl(&self, kind: CertificateListKind, crl: &X509Crl) -> Result<(), String> {
+        if kind == CertificateListKind::Rejected {
+            return Err(format!("Cannot store CRLs in the {kind:?} list"));
+        }
+        let mut state = self.state.write().unwrap();
+        let crls = state.crls.entry(kind).or_default();
+        let thumbprint = crl.thumbprint();
+        crls.retain(|c| c.thumbprint() != thumbprint);
+        crls.push(crl.clone());
+        Ok(())
+    }
+
+    fn remove_crl(&self, kind: CertificateListKind, crl: &X509Crl) -> Result<bool, String> {
+        let mut state = self.state.write().unwrap();
+        let Some(crls) = state.crls.get_mut(&kind) else {
+            return Ok(false);
+        };
+        let len = crls.len();
+        let thumbprint = crl.thumbprint();
+        crls.retain(|c| c.thumbprint() != thumbprint);
+        Ok(crls.len() != len)
+    }
+}
+
+/// Writes a certificate and private key to the specified locations
+pub(crate) fn write_cert_and_pkey(
+    cert: &X509,
+    pkey: &PrivateKey,
+    overwrite: bool,
+    cert_path: &Path,
+    pkey_path: &Path,
+) -> Result<(), String> {
+    // Write the public cert
+    write_cert(cert, cert_path, overwrite)?;
+
+    // Write the private key
+    use rsa::pkcs8;
+    use x509_cert::der::pem::PemLabel;
+    let doc = pkey.to_der().unwrap();
+    let pem = doc
+        .to_pem(rsa::pkcs8::PrivateKeyInfo::PEM_LABEL, pkcs8::LineEnding::CR)
+        .unwrap();
+    write_to_file(pem.as_bytes(), pkey_path, overwrite)?;
+    Ok(())
+}
+
+/// Writes a cert to the specified path
+///
+/// # Errors
+///
+/// A string description of any failure
+///
+fn write_cert(cert: &X509, path: &Path, overwrite: bool) -> Result<(), String> {
+    let der = cert.to_der().unwrap();
+    info!("Writing X509 cert to {}", path.display());
+    write_to_file(&der, path, overwrite).map(|_| ())
+}
+
+/// The suffix appended to the file names of an own certificate and private key, when they
+/// are not stored at the configured paths.
+fn key_type_file_suffix(key_type: KeyType) -> &'static str {
+    match key_type {
+        KeyType::Rsa => "rsa",
+        KeyType::NistP256 => "nistP256",
+        KeyType::NistP384 => "nistP384",
+        KeyType::Curve25519 => "curve25519",
+        KeyType::BrainpoolP256r1 => "brainpoolP256r1",
+        KeyType::BrainpoolP384r1 => "brainpoolP384r1",
+    }
+}
+
+/// Appends `_<suffix>` to the file stem of `path`, keeping the extension.
+fn append_to_file_stem(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}_{suffix}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{suffix}"),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Ensure the directory exists, creating it if necessary
+///
+/// # Errors
+///
+/// A string description of any failure
+///
+fn ensure_dir(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        if !path.is_dir() {
+            Err(format!("{} is not a directory ", path.display()))
+        } else {
+            Ok(())
+        }
+    } else {
+        std::fs::create_dir_all(path)
+            .map_err(|_| format!("Cannot make directories for {}", path.display()))
+    }
+}
+
+/// Lists the files in a directory with one of the given extensions
+fn files_in_dir(dir: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| extensions.contains(&e))
+        })
+        .collect()
+}
+
+/// Writes bytes to file and returns the size written, or an error reason for failure.
+///
+/// # Errors
+///
+/// A string description of any failure
+///
+fn write_to_file(bytes: &[u8], file_path: &Path, overwrite: bool) -> Result<usize, String> {
+    if !overwrite && file_path.exists() {
+        Err(format!("File {} already exists and will not be overwritten. Enable overwrite to disable this safeguard.", file_path.display()))
+    } else {
+        if let Some(parent) = file_path.parent() {
+            ensure_dir(parent)?;
+        }
+        match File::create(file_path) {
+            Ok(mut file) => file
+                .write(bytes)
+                .map_err(|_| format!("Could not write bytes to file {}", file_path.display())),
+            Err(_) => Err(format!("Could not create file {}", file_path.display())),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Wrapper for X509 certificate revocation lists.
+
+use std::fmt::{self, Debug, Formatter};
+
+use chrono::{DateTime, Utc};
+use x509_cert::{self as x509, crl::CertificateList};
+
+use super::{
+    thumbprint::Thumbprint,
+    x509::{X509Error, X509},
+};
+
+#[derive(Clone)]
+/// Wrapper around an X509 certificate revocation list (CRL), listing the certificates
+/// revoked by a certificate authority.
+pub struct X509Crl {
+    value: CertificateList,
+}
+
+impl Debug for X509Crl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[crl]")
+    }
+}
+
+impl X509Crl {
+    /// Load a CRL from a pem file.
+    pub fn from_pem(data: &[u8]) -> Result<Self, X509Error> {
+        use x509::der::{Decode, PemReader, Reader};
+
+        let mut reader = PemReader::new(data)?;
+        let val = CertificateList::decode(&mut reader)?;
+        let val = reader.finish(val)?;
+        Ok(X509Crl { value: val })
+    }
+
+    /// Load a CRL from a der file.
+    pub fn from_der(data: &[u8]) -> Result<Self, X509Error> {
+        use x509::der::Decode;
+
+        let val = CertificateList::from_der(data)?;
+        Ok(X509Crl { value: val })
+    }
+
+    /// Serialize the CRL to der.
+    pub fn to_der(&self) -> Result<Vec<u8>, X509Error> {
+        use x509::der::Encode;
+
+        Ok(self.value.to_der()?)
+    }
+
+    /// Return the SHA1 thumbprint of the CRL, which can be used to identify it.
+    pub fn thumbprint(&self) -> Thumbprint {
+        use sha1::Digest;
+        use x509::der::Encode;
+
+        let der = self.value.to_der().unwrap();
+
+        let mut hasher = sha1::Sha1::new();
+        hasher.update(&der);
+        let digest = hasher.finalize();
+        Thumbprint::new(&digest)
+    }
+
+    /// Tests if this CRL was issued by `issuer`, i.e. the issuer name of the CRL is the
+    /// subject of `issuer` and the signature is made by its key.
+    pub fn is_issued_by(&self, issuer: &X509) -> bool {
+        use x509::der::Encode;
+
+        if &self.value.tbs_cert_list.issuer != issuer.subject() {
+            return false;
+        }
+        let (Ok(public_key), Ok(tbs), Some(signature)) = (
+            issuer.public_key(),
+            self.value.tbs_cert_list.to_der(),
+            self.value.signature.as_bytes(),
+        ) else {
+            return false;
+        };
+        public_key.verify_signed_data(&self.value.signature_algorithm.oid, &tbs, signature)
+    }
+
+    /// Return the time by which the issuer publishes the next CRL, after which this CRL
+    /// is out of date. Returns `None` if the CRL does not say when the next one is issued.
+    pub fn next_update(&self) -> Option<DateTime<Utc>> {
+        let dur = self
+            .value
+            .tbs_cert_list
+            .next_update
+            .as_ref()?
+            .to_unix_duration();
+        DateTime::from_timestamp_micros(dur.as_micros() as i64)
+    }
+
+    /// Tests if this CRL is out of date at `now`, i.e. the next CRL should have been
+    /// issued by now.
+    pub fn is_out_of_date(&self, now: &DateTime<Utc>) -> bool {
+        self.next_update().is_some_and(|next| next < *now)
+    }
+
+    /// Tests if `cert` is in the list of revoked certificates. This only compares serial
+    /// numbers, so the caller must make sure the CRL was issued by the issuer of `cert`.
+    pub fn is_revoked(&self, cert: &X509) -> bool {
+        self.value
+            .tbs_cert_list
+            .revoked_certificates
+            .as_ref()
+            .is_some_and(|revoked| {
+                revoked
+                    .iter()
+                    .any(|r| &r.serial_number == cert.serial_number())
+            })
+    }
+}
@@ -0,0 +1,170 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Elliptic curve Diffie-Hellman key agreement used by the ECC security policies.
+//!
+//! With an ECC security policy the nonces exchanged in OpenSecureChannel are ephemeral
+//! public keys. Each side combines its own ephemeral private key with the nonce of
+//! the other side to get a shared secret, which the symmetric keys are derived from.
+//! The curve25519 policy uses X25519, where the nonce is the raw public key.
+
+use std::fmt::{self, Debug, Formatter};
+
+use opcua_types::{status_code::StatusCode, Error};
+
+use super::SecurityPolicy;
+
+#[derive(Clone)]
+enum EphemeralKeyValue {
+    NistP256(p256::SecretKey),
+    NistP384(p384::SecretKey),
+    Curve25519(x25519_dalek::StaticSecret),
+    BrainpoolP256r1(bp256::r1::SecretKey),
+    BrainpoolP384r1(bp384::r1::SecretKey),
+}
+
+/// An ephemeral key pair, created for each OpenSecureChannel request or response
+/// on a channel using an ECC security policy.
+#[derive(Clone)]
+pub struct EphemeralKey {
+    value: EphemeralKeyValue,
+}
+
+impl Debug for EphemeralKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Never write out the key
+        write!(f, "[ephemeral key]")
+    }
+}
+
+/// Encode a public key as a nonce, which is the uncompressed curve point
+/// without the leading `0x04` tag.
+fn point_to_nonce(point: &[u8]) -> Vec<u8> {
+    point[1..].to_vec()
+}
+
+fn nonce_to_point(nonce: &[u8]) -> Vec<u8> {
+    let mut point = Vec::with_capacity(nonce.len() + 1);
+    point.push(0x04);
+    point.extend_from_slice(nonce);
+    point
+}
+
+impl EphemeralKey {
+    /// Generate a new ephemeral key for the given security policy. Returns `None`
+    /// if the security policy does not use elliptic curve cryptography.
+    pub fn new(security_policy: SecurityPolicy) -> Option<EphemeralKey> {
+        use bp256::elliptic_curve::Generate;
+
+        let mut rng = rand::thread_rng();
+        let value = match security_policy {
+            SecurityPolicy::EccNistP256 => {
+                EphemeralKeyValue::NistP256(p256::SecretKey::random(&mut rng))
+            }
+            SecurityPolicy::EccNistP384 => {
+                EphemeralKeyValue::NistP384(p384::SecretKey::random(&mut rng))
+            }
+            SecurityPolicy::EccCurve25519 => {
+                EphemeralKeyValue::Curve25519(x25519_dalek::StaticSecret::random_from_rng(rng))
+            }
+            SecurityPolicy::EccBrainpoolP256r1 => {
+                EphemeralKeyValue::BrainpoolP256r1(bp256::r1::SecretKey::generate())
+            }
+            SecurityPolicy::EccBrainpoolP384r1 => {
+                EphemeralKeyValue::BrainpoolP384r1(bp384::r1::SecretKey::generate())
+            }
+            _ => return None,
+        };
+        Some(EphemeralKey { value })
+    }
+
+    /// Get the public key of this ephemeral key encoded as a nonce.
+    pub fn nonce(&self) -> Vec<u8> {
+        use bp256::elliptic_curve::sec1::ToSec1Point;
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        match &self.value {
+            EphemeralKeyValue::NistP256(key) => {
+                point_to_nonce(key.public_key().to_encoded_point(false).as_bytes())
+            }
+            EphemeralKeyValue::NistP384(key) => {
+                point_to_nonce(key.public_key().to_encoded_point(false).as_bytes())
+            }
+            EphemeralKeyValue::Curve25519(key) => {
+                x25519_dalek::PublicKey::from(key).as_bytes().to_vec()
+            }
+            EphemeralKeyValue::BrainpoolP256r1(key) => {
+                point_to_nonce(key.public_key().to_sec1_point(false).as_bytes())
+            }
+            EphemeralKeyValue::BrainpoolP384r1(key) => {
+                point_to_nonce(key.public_key().to_sec1_point(false).as_bytes())
+            }
+        }
+    }
+
+    /// Compute the shared secret from this key and the nonce received from the other side
+    /// of the secure channel.
+    pub fn shared_secret(&self, remote_nonce: &[u8]) -> Result<Vec<u8>, Error> {
+        use bp256::elliptic_curve::point::AffineCoordinates;
+
+        let point = nonce_to_point(remote_nonce);
+        let secret = match &self.value {
+            EphemeralKeyValue::Curve25519(key) => {
+                let remote = <[u8; 32]>::try_from(remote_nonce)
+                    .map_err(|_| Error::new(StatusCode::BadNonceInvalid, "Invalid nonce length"))?;
+                let secret = key.diffie_hellman(&x25519_dalek::PublicKey::from(remote));
+                return Self::contributory_secret(secret.was_contributory(), secret.as_bytes());
+            }
+            EphemeralKeyValue::NistP256(key) => {
+                p256::PublicKey::from_sec1_bytes(&point).map(|remote| {
+                    p256::ecdh::diffie_hellman(key.to_nonzero_scalar(), remote.as_affine())
+                        .raw_secret_bytes()
+                        .to_vec()
+                })
+            }
+            EphemeralKeyValue::NistP384(key) => {
+                p384::PublicKey::from_sec1_bytes(&point).map(|remote| {
+                    p384::ecdh::diffie_hellman(key.to_nonzero_scalar(), remote.as_affine())
+                        .raw_secret_bytes()
+                        .to_vec()
+                })
+            }
+            // The brainpool crates have no ECDH module, the shared secret is the
+            // x coordinate of the product of the remote point and our scalar.
+            EphemeralKeyValue::BrainpoolP256r1(key) => {
+                crate::pkey::BrainpoolP256r1PublicKey::from_sec1_bytes(&point)
+                    .map(|remote| {
+                        (remote.to_projective() * *key.to_nonzero_scalar())
+                            .to_affine()
+                            .x()
+                            .to_vec()
+                    })
+                    .map_err(|_| p256::elliptic_curve::Error)
+            }
+            EphemeralKeyValue::BrainpoolP384r1(key) => {
+                crate::pkey::BrainpoolP384r1PublicKey::from_sec1_bytes(&point)
+                    .map(|remote| {
+                        (remote.to_projective() * *key.to_nonzero_scalar())
+                            .to_affine()
+                            .x()
+                            .to_vec()
+                    })
+                    .map_err(|_| p256::elliptic_curve::Error)
+            }
+        };
+        secret.map_err(|e| Error::new(StatusCode::BadNonceInvalid, e))
+    }
+
+    /// Reject a shared secret computed from a public key of small order, which does
+    /// not depend on our own key.
+    fn contributory_secret(contributory: bool, secret: &[u8]) -> Result<Vec<u8>, Error> {
+        if contributory {
+            Ok(secret.to_vec())
+        } else {
+            Err(Error::new(
+                StatusCode::BadNonceInvalid,
+                "Nonce is a public key of small order",
+            ))
+        }
+    }
+}
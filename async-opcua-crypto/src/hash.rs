@@ -9,13 +9,14 @@ use std::result::Result;
 use hmac::{digest, Hmac, Mac};
 use log::error;
 use sha1::Sha1;
-use sha2::Sha256;
+use sha2::{Sha256, Sha384};
 
 use opcua_types::status_code::StatusCode;
 
-use super::{SHA1_SIZE, SHA256_SIZE};
+use super::{SHA1_SIZE, SHA256_SIZE, SHA384_SIZE};
 
 type HmacSha256 = Hmac<Sha256>;
+type HmacSha384 = Hmac<Sha384>;
 type HmacSha1 = Hmac<Sha1>;
 type Sha1Output = digest::CtOutput<HmacSha1>;
 type Sha256Output = digest::CtOutput<HmacSha256>;
@@ -99,6 +100,26 @@ pub fn p_sha256(secret: &[u8], seed: &[u8], length: usize) -> Vec<u8> {
     result
 }
 
+/// HMAC based key derivation function using SHA256, as described in
+/// [IETF RFC 5869](https://tools.ietf.org/html/rfc5869). Creates `length` bytes of
+/// key material from the input key material `ikm`.
+pub fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8], length: usize) -> Vec<u8> {
+    let mut result = vec![0u8; length];
+    hkdf::Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut result)
+        .expect("Invalid HKDF output length");
+    result
+}
+
+/// HMAC based key derivation function using SHA384. See [`hkdf_sha256`].
+pub fn hkdf_sha384(salt: &[u8], ikm: &[u8], info: &[u8], length: usize) -> Vec<u8> {
+    let mut result = vec![0u8; length];
+    hkdf::Hkdf::<Sha384>::new(Some(salt), ikm)
+        .expand(info, &mut result)
+        .expect("Invalid HKDF output length");
+    result
+}
+
 /*
 fn hmac_vec(digest: hash::MessageDigest, key: &[u8], data: &[u8]) -> Vec<u8> {
     // Compute a signature
@@ -184,3 +205,30 @@ pub fn verify_hmac_sha256(key: &[u8], data: &[u8], signature: &[u8]) -> bool {
         mac.verify_slice(signature).is_ok()
     }
 }
+
+/// Write the SHA384 HMAC signature of `data` using `key` into `signature`.
+pub fn hmac_sha384(key: &[u8], data: &[u8], signature: &mut [u8]) -> Result<(), StatusCode> {
+    if signature.len() == SHA384_SIZE {
+        let mut mac = HmacSha384::new_from_slice(key).unwrap();
+        mac.update(data);
+        signature.copy_from_slice(&mac.finalize().into_bytes());
+        Ok(())
+    } else {
+        error!(
+            "Signature buffer length must be exactly {} bytes to receive hmac_sha384 signature",
+            SHA384_SIZE
+        );
+        Err(StatusCode::BadInvalidArgument)
+    }
+}
+
+/// Verify that the HMAC for the data block matches the supplied signature
+pub fn verify_hmac_sha384(key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+    if signature.len() != SHA384_SIZE {
+        false
+    } else {
+        let mut mac = HmacSha384::new_from_slice(key).unwrap();
+        mac.update(data);
+        mac.verify_slice(signature).is_ok()
+    }
+}
@@ -0,0 +1,59 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Salted password hashes, used to store the passwords of users in the server configuration
+//! instead of plain text.
+//!
+//! Hashes are strings in the PHC string format, for example
+//! `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`. Argon2 (`argon2i`, `argon2d` and `argon2id`)
+//! and PBKDF2 (`pbkdf2-sha256` and `pbkdf2-sha512`) hashes can be verified. New hashes are
+//! created with Argon2id.
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString},
+    Argon2,
+};
+use opcua_types::{status_code::StatusCode, Error};
+use pbkdf2::Pbkdf2;
+
+/// Hash a password with Argon2id and a random salt, returning the hash in the PHC string format.
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            Error::new(
+                StatusCode::BadInternalError,
+                format!("Failed to hash password: {e}"),
+            )
+        })
+}
+
+/// Test that a hash in the PHC string format can be used to verify passwords.
+pub fn validate_password_hash(hash: &str) -> Result<(), Error> {
+    let hash = PasswordHash::new(hash).map_err(|e| {
+        Error::new(
+            StatusCode::BadConfigurationError,
+            format!("Invalid password hash: {e}"),
+        )
+    })?;
+    match hash.algorithm.as_str() {
+        "argon2i" | "argon2d" | "argon2id" | "pbkdf2-sha256" | "pbkdf2-sha512" => Ok(()),
+        algorithm => Err(Error::new(
+            StatusCode::BadConfigurationError,
+            format!("Unsupported password hash algorithm {algorithm}"),
+        )),
+    }
+}
+
+/// Verify a password against a hash in the PHC string format. The hash of the password
+/// is compared in constant time. Returns `false` if the hash is invalid.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    let Ok(hash) = PasswordHash::new(hash) else {
+        return false;
+    };
+    hash.verify_password(&[&Argon2::default(), &Pbkdf2], password)
+        .is_ok()
+}
@@ -14,7 +14,7 @@ use rsa::pkcs1;
 use rsa::pkcs1v15;
 use rsa::pkcs8;
 use rsa::pss;
-use rsa::signature::{RandomizedSigner, SignatureEncoding, Verifier};
+use rsa::signature::{RandomizedSigner, SignatureEncoding, Signer, Verifier};
 use rsa::{Oaep, Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
 use sha1;
 use sha2;
@@ -22,7 +22,12 @@ use sha2;
 use x509_cert;
 use x509_cert::spki::SubjectPublicKeyInfoOwned;
 
-use opcua_types::{status_code::StatusCode, Error};
+use opcua_types::{status_code::StatusCode, Error, NodeId, ObjectTypeId};
+
+/// Brainpool P-256r1 public key.
+pub type BrainpoolP256r1PublicKey = bp256::elliptic_curve::PublicKey<bp256::BrainpoolP256r1>;
+/// Brainpool P-384r1 public key.
+pub type BrainpoolP384r1PublicKey = bp384::elliptic_curve::PublicKey<bp384::BrainpoolP384r1>;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 /// RSA padding variant.
@@ -65,6 +70,113 @@ impl From<rsa::Error> for PKeyError {
     }
 }
 
+impl From<p256::elliptic_curve::Error> for PKeyError {
+    fn from(_err: p256::elliptic_curve::Error) -> Self {
+        PKeyError
+    }
+}
+
+impl From<bp256::elliptic_curve::Error> for PKeyError {
+    fn from(_err: bp256::elliptic_curve::Error) -> Self {
+        PKeyError
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+/// Type of an asymmetric key.
+pub enum KeyType {
+    /// RSA key, used by the RSA security policies.
+    #[default]
+    Rsa,
+    /// Elliptic curve key on the NIST P-256 curve, used by `ECC_nistP256`.
+    NistP256,
+    /// Elliptic curve key on the NIST P-384 curve, used by `ECC_nistP384`.
+    NistP384,
+    /// Ed25519 key on curve25519, used by `ECC_curve25519`.
+    Curve25519,
+    /// Elliptic curve key on the brainpoolP256r1 curve, used by `ECC_brainpoolP256r1`.
+    BrainpoolP256r1,
+    /// Elliptic curve key on the brainpoolP384r1 curve, used by `ECC_brainpoolP384r1`.
+    BrainpoolP384r1,
+}
+
+impl KeyType {
+    /// Get the key type required by the application certificate type `certificate_type`,
+    /// or `None` if any key type is allowed.
+    ///
+    /// # Errors
+    ///
+    /// `BadNotSupported` if the certificate type is not supported.
+    pub fn from_certificate_type(certificate_type: &NodeId) -> Result<Option<KeyType>, StatusCode> {
+        if certificate_type.is_null()
+            || certificate_type == &ObjectTypeId::ApplicationCertificateType
+        {
+            Ok(None)
+        } else if certificate_type == &ObjectTypeId::RsaMinApplicationCertificateType
+            || certificate_type == &ObjectTypeId::RsaSha256ApplicationCertificateType
+        {
+            Ok(Some(KeyType::Rsa))
+        } else if certificate_type == &ObjectTypeId::EccNistP256ApplicationCertificateType {
+            Ok(Some(KeyType::NistP256))
+        } else if certificate_type == &ObjectTypeId::EccNistP384ApplicationCertificateType {
+            Ok(Some(KeyType::NistP384))
+        } else if certificate_type == &ObjectTypeId::EccCurve25519ApplicationCertificateType {
+            Ok(Some(KeyType::Curve25519))
+        } else if certificate_type == &ObjectTypeId::EccBrainpoolP256r1ApplicationCertificateType {
+            Ok(Some(KeyType::BrainpoolP256r1))
+        } else if certificate_type == &ObjectTypeId::EccBrainpoolP384r1ApplicationCertificateType {
+            Ok(Some(KeyType::BrainpoolP384r1))
+        } else {
+            Err(StatusCode::BadNotSupported)
+        }
+    }
+
+    /// Size of a coordinate on the curve in bytes, for elliptic curve keys. For Ed25519
+    /// keys this is the size of an encoded key.
+    pub(crate) fn coordinate_size(&self) -> usize {
+        match self {
+            KeyType::Rsa => 0,
+            KeyType::NistP256 | KeyType::Curve25519 | KeyType::BrainpoolP256r1 => 32,
+            KeyType::NistP384 | KeyType::BrainpoolP384r1 => 48,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+/// The private key held by a [`PrivateKey`].
+pub enum PrivateKeyValue {
+    /// RSA private key.
+    Rsa(RsaPrivateKey),
+    /// NIST P-256 private key.
+    NistP256(p256::SecretKey),
+    /// NIST P-384 private key.
+    NistP384(p384::SecretKey),
+    /// Ed25519 private key.
+    Curve25519(ed25519_dalek::SigningKey),
+    /// Brainpool P-256r1 private key.
+    BrainpoolP256r1(bp256::r1::SecretKey),
+    /// Brainpool P-384r1 private key.
+    BrainpoolP384r1(bp384::r1::SecretKey),
+}
+
+#[derive(Clone)]
+/// The public key held by a [`PublicKey`].
+pub enum PublicKeyValue {
+    /// RSA public key.
+    Rsa(RsaPublicKey),
+    /// NIST P-256 public key.
+    NistP256(p256::PublicKey),
+    /// NIST P-384 public key.
+    NistP384(p384::PublicKey),
+    /// Ed25519 public key.
+    Curve25519(ed25519_dalek::VerifyingKey),
+    /// Brainpool P-256r1 public key.
+    BrainpoolP256r1(BrainpoolP256r1PublicKey),
+    /// Brainpool P-384r1 public key.
+    BrainpoolP384r1(BrainpoolP384r1PublicKey),
+}
+
 /// This is a wrapper around an asymmetric key pair. Since the PKey is either
 /// a public or private key so we have to differentiate that as well.
 #[derive(Clone)]
@@ -73,9 +185,9 @@ pub struct PKey<T> {
 }
 
 /// A public key
-pub type PublicKey = PKey<RsaPublicKey>;
+pub type PublicKey = PKey<PublicKeyValue>;
 /// A private key
-pub type PrivateKey = PKey<RsaPrivateKey>;
+pub type PrivateKey = PKey<PrivateKeyValue>;
 
 impl<T> Debug for PKey<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -95,6 +207,12 @@ pub trait KeySize {
     /// Length in bytes.
     fn size(&self) -> usize;
 
+    /// Size of a signature made with this key in bytes. This is the key size for RSA keys,
+    /// and twice the key size for elliptic curve keys.
+    fn signature_size(&self) -> usize {
+        self.size()
+    }
+
     /// Get the cipher block size with given data size and padding.
     fn calculate_cipher_text_size(&self, data_size: usize, padding: RsaPadding) -> usize {
         let plain_text_block_size = self.plain_text_block_size(padding);
@@ -133,36 +251,78 @@ impl KeySize for PrivateKey {
     /// Length in bits
     fn size(&self) -> usize {
         use rsa::traits::PublicKeyParts;
-        self.value.size()
+        match &self.value {
+            PrivateKeyValue::Rsa(key) => key.size(),
+            _ => self.key_type().coordinate_size(),
+        }
+    }
+
+    fn signature_size(&self) -> usize {
+        match self.key_type() {
+            KeyType::Rsa => self.size(),
+            _ => self.size() * 2,
+        }
     }
 }
 
 impl PrivateKey {
-    /// Generate a new private key with the given length in bits.
+    /// Generate a new RSA private key with the given length in bits.
     pub fn new(bit_length: u32) -> Result<PrivateKey, rsa::Error> {
         let mut rng = rand::thread_rng();
 
         let key = RsaPrivateKey::new(&mut rng, bit_length as usize)?;
-        Ok(PKey { value: key })
+        Ok(PKey {
+            value: PrivateKeyValue::Rsa(key),
+        })
     }
 
-    /// Read a private key from the given path.
-    pub fn read_pem_file(path: &std::path::Path) -> Result<PrivateKey, PKeyError> {
-        use pkcs8::DecodePrivateKey;
-        use rsa::pkcs1::DecodeRsaPrivateKey;
+    /// Generate a new private key of the given type. The length in bits is only
+    /// used for RSA keys, elliptic curve keys have the size of their curve.
+    pub fn generate(key_type: KeyType, bit_length: u32) -> Result<PrivateKey, PKeyError> {
+        use bp256::elliptic_curve::Generate;
 
-        let r = RsaPrivateKey::read_pkcs8_pem_file(path);
-        match r {
-            Err(_) => {
-                let val = RsaPrivateKey::read_pkcs1_pem_file(path)?;
-                Ok(PKey { value: val })
+        let mut rng = rand::thread_rng();
+        let value = match key_type {
+            KeyType::Rsa => return Ok(Self::new(bit_length)?),
+            KeyType::NistP256 => PrivateKeyValue::NistP256(p256::SecretKey::random(&mut rng)),
+            KeyType::NistP384 => PrivateKeyValue::NistP384(p384::SecretKey::random(&mut rng)),
+            KeyType::Curve25519 => {
+                PrivateKeyValue::Curve25519(ed25519_dalek::SigningKey::generate(&mut rng))
+            }
+            KeyType::BrainpoolP256r1 => {
+                PrivateKeyValue::BrainpoolP256r1(bp256::r1::SecretKey::generate())
+            }
+            KeyType::BrainpoolP384r1 => {
+                PrivateKeyValue::BrainpoolP384r1(bp384::r1::SecretKey::generate())
             }
-            Ok(val) => Ok(PKey { value: val }),
+        };
+        Ok(PKey { value })
+    }
+
+    /// Get the type of this key.
+    pub fn key_type(&self) -> KeyType {
+        match &self.value {
+            PrivateKeyValue::Rsa(_) => KeyType::Rsa,
+            PrivateKeyValue::NistP256(_) => KeyType::NistP256,
+            PrivateKeyValue::NistP384(_) => KeyType::NistP384,
+            PrivateKeyValue::Curve25519(_) => KeyType::Curve25519,
+            PrivateKeyValue::BrainpoolP256r1(_) => KeyType::BrainpoolP256r1,
+            PrivateKeyValue::BrainpoolP384r1(_) => KeyType::BrainpoolP384r1,
         }
     }
 
+    /// Read a private key from the given path.
+    pub fn read_pem_file(path: &std::path::Path) -> Result<PrivateKey, PKeyError> {
+        let bytes = std::fs::read(path).map_err(|_| PKeyError)?;
+        Self::from_pem(&bytes)
+    }
+
     /// Create a private key from a pem file loaded into a byte array.
+    ///
+    /// RSA keys may be PKCS#8 or PKCS#1 encoded, elliptic curve keys
+    /// may be PKCS#8 or SEC1 encoded, and Ed25519 keys must be PKCS#8 encoded.
     pub fn from_pem(bytes: &[u8]) -> Result<PrivateKey, PKeyError> {
+        use bp256::pkcs8::DecodePrivateKey as _;
         use pkcs8::DecodePrivateKey;
         use rsa::pkcs1::DecodeRsaPrivateKey;
 
@@ -170,48 +330,124 @@ impl PrivateKey {
         match converted {
             Err(_) => Err(PKeyError),
             Ok(pem) => {
-                let r = RsaPrivateKey::from_pkcs8_pem(pem);
-                match r {
-                    Err(_) => {
-                        let val = RsaPrivateKey::from_pkcs1_pem(pem)?;
-                        Ok(PKey { value: val })
-                    }
-                    Ok(val) => Ok(PKey { value: val }),
-                }
+                let value = if let Ok(val) = RsaPrivateKey::from_pkcs8_pem(pem) {
+                    PrivateKeyValue::Rsa(val)
+                } else if let Ok(val) = RsaPrivateKey::from_pkcs1_pem(pem) {
+                    PrivateKeyValue::Rsa(val)
+                } else if let Ok(val) = p256::SecretKey::from_pkcs8_pem(pem) {
+                    PrivateKeyValue::NistP256(val)
+                } else if let Ok(val) = p384::SecretKey::from_pkcs8_pem(pem) {
+                    PrivateKeyValue::NistP384(val)
+                } else if let Ok(val) = ed25519_dalek::SigningKey::from_pkcs8_pem(pem) {
+                    PrivateKeyValue::Curve25519(val)
+                } else if let Ok(val) = bp256::r1::SecretKey::from_pkcs8_pem(pem) {
+                    PrivateKeyValue::BrainpoolP256r1(val)
+                } else if let Ok(val) = bp384::r1::SecretKey::from_pkcs8_pem(pem) {
+                    PrivateKeyValue::BrainpoolP384r1(val)
+                } else if let Ok(val) = p256::SecretKey::from_sec1_pem(pem) {
+                    PrivateKeyValue::NistP256(val)
+                } else if let Ok(val) = p384::SecretKey::from_sec1_pem(pem) {
+                    PrivateKeyValue::NistP384(val)
+                } else if let Ok(val) = bp256::r1::SecretKey::from_sec1_pem(pem) {
+                    PrivateKeyValue::BrainpoolP256r1(val)
+                } else {
+                    PrivateKeyValue::BrainpoolP384r1(
+                        bp384::r1::SecretKey::from_sec1_pem(pem).map_err(|_| PKeyError)?,
+                    )
+                };
+                Ok(PKey { value })
             }
         }
     }
 
     /// Serialize the private key to a der file.
     pub fn to_der(&self) -> pkcs8::Result<pkcs8::SecretDocument> {
+        use bp256::pkcs8::EncodePrivateKey as _;
         use pkcs8::EncodePrivateKey;
 
-        self.value.to_pkcs8_der()
+        // The brainpool curves use a newer version of `pkcs8`, so their
+        // documents are converted through the DER encoding.
+        let brainpool_der = match &self.value {
+            PrivateKeyValue::Rsa(key) => return key.to_pkcs8_der(),
+            PrivateKeyValue::NistP256(key) => return key.to_pkcs8_der(),
+            PrivateKeyValue::NistP384(key) => return key.to_pkcs8_der(),
+            PrivateKeyValue::Curve25519(key) => return key.to_pkcs8_der(),
+            PrivateKeyValue::BrainpoolP256r1(key) => key.to_pkcs8_der(),
+            PrivateKeyValue::BrainpoolP384r1(key) => key.to_pkcs8_der(),
+        }
+        .map_err(|_| pkcs8::Error::KeyMalformed)?;
+        Ok(pkcs8::SecretDocument::try_from(brainpool_der.as_bytes())?)
+    }
+
+    /// Serialize the private key to a PKCS#8 pem string.
+    pub fn to_pem(&self) -> pkcs8::Result<String> {
+        use x509_cert::der::pem::PemLabel;
+
+        let pem = self
+            .to_der()?
+            .to_pem(pkcs8::PrivateKeyInfo::PEM_LABEL, pkcs8::LineEnding::LF)?;
+        Ok(pem.to_string())
     }
 
     /// Get the public key info for this private key.
     pub fn public_key_to_info(&self) -> x509_cert::spki::Result<SubjectPublicKeyInfoOwned> {
+        use bp256::pkcs8::EncodePublicKey as _;
         use rsa::pkcs8::EncodePublicKey;
-        SubjectPublicKeyInfoOwned::try_from(
-            self.value
-                .to_public_key()
-                .to_public_key_der()
-                .unwrap()
-                .as_bytes(),
-        )
+        let der = match &self.value {
+            PrivateKeyValue::Rsa(key) => key.to_public_key().to_public_key_der(),
+            PrivateKeyValue::NistP256(key) => key.public_key().to_public_key_der(),
+            PrivateKeyValue::NistP384(key) => key.public_key().to_public_key_der(),
+            PrivateKeyValue::Curve25519(key) => key.verifying_key().to_public_key_der(),
+            PrivateKeyValue::BrainpoolP256r1(key) => {
+                return Self::brainpool_public_key_to_info(key.public_key().to_public_key_der())
+            }
+            PrivateKeyValue::BrainpoolP384r1(key) => {
+                return Self::brainpool_public_key_to_info(key.public_key().to_public_key_der())
+            }
+        }?;
+        SubjectPublicKeyInfoOwned::try_from(der.as_bytes())
+    }
+
+    fn brainpool_public_key_to_info(
+        der: bp256::pkcs8::spki::Result<bp256::pkcs8::Document>,
+    ) -> x509_cert::spki::Result<SubjectPublicKeyInfoOwned> {
+        // The brainpool curves use a newer version of `spki`, so the public key
+        // info is converted through the DER encoding.
+        let der = der.map_err(|_| x509_cert::spki::Error::KeyMalformed)?;
+        SubjectPublicKeyInfoOwned::try_from(der.as_bytes())
     }
 
     /// Create a public key based on this private key.
     pub fn to_public_key(&self) -> PublicKey {
-        PublicKey {
-            value: self.value.to_public_key(),
+        let value = match &self.value {
+            PrivateKeyValue::Rsa(key) => PublicKeyValue::Rsa(key.to_public_key()),
+            PrivateKeyValue::NistP256(key) => PublicKeyValue::NistP256(key.public_key()),
+            PrivateKeyValue::NistP384(key) => PublicKeyValue::NistP384(key.public_key()),
+            PrivateKeyValue::Curve25519(key) => PublicKeyValue::Curve25519(key.verifying_key()),
+            PrivateKeyValue::BrainpoolP256r1(key) => {
+                PublicKeyValue::BrainpoolP256r1(key.public_key())
+            }
+            PrivateKeyValue::BrainpoolP384r1(key) => {
+                PublicKeyValue::BrainpoolP384r1(key.public_key())
+            }
+        };
+        PublicKey { value }
+    }
+
+    fn rsa(&self) -> Result<&RsaPrivateKey, Error> {
+        match &self.value {
+            PrivateKeyValue::Rsa(key) => Ok(key),
+            _ => Err(Error::new(
+                StatusCode::BadSecurityChecksFailed,
+                "Private key is not an RSA key",
+            )),
         }
     }
 
     /// Signs the data using RSA-SHA1
     pub fn sign_sha1(&self, data: &[u8], signature: &mut [u8]) -> Result<usize, Error> {
         let mut rng = rand::thread_rng();
-        let signing_key = pkcs1v15::SigningKey::<sha1::Sha1>::new(self.value.clone());
+        let signing_key = pkcs1v15::SigningKey::<sha1::Sha1>::new(self.rsa()?.clone());
         match signing_key.try_sign_with_rng(&mut rng, data) {
             Err(e) => Err(Error::new(StatusCode::BadUnexpectedError, e)),
             Ok(signed) => {
@@ -225,7 +461,7 @@ impl PrivateKey {
     /// Signs the data using RSA-SHA256
     pub fn sign_sha256(&self, data: &[u8], signature: &mut [u8]) -> Result<usize, Error> {
         let mut rng = rand::thread_rng();
-        let signing_key = pkcs1v15::SigningKey::<sha2::Sha256>::new(self.value.clone());
+        let signing_key = pkcs1v15::SigningKey::<sha2::Sha256>::new(self.rsa()?.clone());
         match signing_key.try_sign_with_rng(&mut rng, data) {
             Err(e) => Err(Error::new(StatusCode::BadUnexpectedError, e)),
             Ok(signed) => {
@@ -239,7 +475,7 @@ impl PrivateKey {
     /// Signs the data using RSA-SHA256-PSS
     pub fn sign_sha256_pss(&self, data: &[u8], signature: &mut [u8]) -> Result<usize, Error> {
         let mut rng = rand::thread_rng();
-        let signing_key = pss::BlindedSigningKey::<sha2::Sha256>::new(self.value.clone());
+        let signing_key = pss::BlindedSigningKey::<sha2::Sha256>::new(self.rsa()?.clone());
         match signing_key.try_sign_with_rng(&mut rng, data) {
             Err(e) => Err(Error::new(StatusCode::BadUnexpectedError, e)),
             Ok(signed) => {
@@ -250,18 +486,65 @@ impl PrivateKey {
         }
     }
 
-    fn pkcs1_decrypt(&self, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
-        self.value.decrypt(Pkcs1v15Encrypt, src)
+    /// Signs the data using ECDSA, with SHA256 for P-256 keys and SHA384 for
+    /// P-384 keys. The signature is the concatenated `r` and `s` values.
+    /// Ed25519 keys sign the data with pure Ed25519 instead.
+    pub fn sign_ecdsa(&self, data: &[u8], signature: &mut [u8]) -> Result<usize, Error> {
+        use ecdsa::signature::Signer as _;
+
+        let signed = match &self.value {
+            PrivateKeyValue::NistP256(key) => p256::ecdsa::SigningKey::from(key)
+                .try_sign(data)
+                .map(|s: p256::ecdsa::Signature| s.to_vec()),
+            PrivateKeyValue::NistP384(key) => p384::ecdsa::SigningKey::from(key)
+                .try_sign(data)
+                .map(|s: p384::ecdsa::Signature| s.to_vec()),
+            PrivateKeyValue::Curve25519(key) => key
+                .try_sign(data)
+                .map(|s: ed25519_dalek::Signature| s.to_vec()),
+            // The brainpool curves use a newer version of `signature`.
+            PrivateKeyValue::BrainpoolP256r1(key) => ecdsa::SigningKey::from(key)
+                .try_sign(data)
+                .map(|s: ecdsa::Signature<bp256::BrainpoolP256r1>| s.to_bytes().to_vec())
+                .map_err(|_| rsa::signature::Error::new()),
+            PrivateKeyValue::BrainpoolP384r1(key) => ecdsa::SigningKey::from(key)
+                .try_sign(data)
+                .map(|s: ecdsa::Signature<bp384::BrainpoolP384r1>| s.to_bytes().to_vec())
+                .map_err(|_| rsa::signature::Error::new()),
+            PrivateKeyValue::Rsa(_) => {
+                return Err(Error::new(
+                    StatusCode::BadSecurityChecksFailed,
+                    "Private key is not an elliptic curve key",
+                ))
+            }
+        }
+        .map_err(|e| Error::new(StatusCode::BadUnexpectedError, e))?;
+        if signature.len() != signed.len() {
+            return Err(Error::new(
+                StatusCode::BadUnexpectedError,
+                format!(
+                    "Signature buffer is {} bytes, expected {}",
+                    signature.len(),
+                    signed.len()
+                ),
+            ));
+        }
+        signature.copy_from_slice(&signed);
+        Ok(signed.len())
+    }
+
+    fn pkcs1_decrypt(key: &RsaPrivateKey, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
+        key.decrypt(Pkcs1v15Encrypt, src)
     }
 
-    fn oaepsha1_decrypt(&self, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
+    fn oaepsha1_decrypt(key: &RsaPrivateKey, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
         let padding = Oaep::new::<sha1::Sha1>();
-        self.value.decrypt(padding, src)
+        key.decrypt(padding, src)
     }
 
-    fn oaepsha2_decrypt(&self, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
+    fn oaepsha2_decrypt(key: &RsaPrivateKey, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
         let padding = Oaep::new::<sha2::Sha256>();
-        self.value.decrypt(padding, src)
+        key.decrypt(padding, src)
     }
 
     /// Decrypts data in src to dst using the specified padding and returning the size of the decrypted
@@ -272,6 +555,7 @@ impl PrivateKey {
         dst: &mut [u8],
         padding: RsaPadding,
     ) -> Result<usize, PKeyError> {
+        let key = self.rsa().map_err(|_| PKeyError)?;
         let cipher_text_block_size = self.cipher_text_block_size();
 
         // Decrypt the data
@@ -288,9 +572,9 @@ impl PrivateKey {
                 let dst = &mut dst[dst_idx..(dst_idx + cipher_text_block_size)];
 
                 let decrypted = match padding {
-                    RsaPadding::OaepSha256 => self.oaepsha2_decrypt(src)?,
-                    RsaPadding::Pkcs1 => self.pkcs1_decrypt(src)?,
-                    RsaPadding::OaepSha1 => self.oaepsha1_decrypt(src)?,
+                    RsaPadding::OaepSha256 => Self::oaepsha2_decrypt(key, src)?,
+                    RsaPadding::Pkcs1 => Self::pkcs1_decrypt(key, src)?,
+                    RsaPadding::OaepSha1 => Self::oaepsha1_decrypt(key, src)?,
                 };
 
                 let size = decrypted.len();
@@ -311,14 +595,91 @@ impl KeySize for PublicKey {
     /// Length in bits
     fn size(&self) -> usize {
         use rsa::traits::PublicKeyParts;
-        self.value.size()
+        match &self.value {
+            PublicKeyValue::Rsa(key) => key.size(),
+            _ => self.key_type().coordinate_size(),
+        }
+    }
+
+    fn signature_size(&self) -> usize {
+        match self.key_type() {
+            KeyType::Rsa => self.size(),
+            _ => self.size() * 2,
+        }
     }
 }
 
 impl PublicKey {
+    /// Create an RSA public key from its big-endian modulus and public exponent.
+    pub fn from_rsa_components(modulus: &[u8], exponent: &[u8]) -> Result<PublicKey, PKeyError> {
+        let key = RsaPublicKey::new(
+            rsa::BigUint::from_bytes_be(modulus),
+            rsa::BigUint::from_bytes_be(exponent),
+        )?;
+        Ok(PublicKey {
+            value: PublicKeyValue::Rsa(key),
+        })
+    }
+
+    /// Create an elliptic curve public key from the big-endian coordinates of its point.
+    /// Ed25519 keys are not represented by coordinates and are rejected.
+    pub fn from_ec_coordinates(
+        key_type: KeyType,
+        x: &[u8],
+        y: &[u8],
+    ) -> Result<PublicKey, PKeyError> {
+        let size = key_type.coordinate_size();
+        if size == 0 || x.len() != size || y.len() != size {
+            return Err(PKeyError);
+        }
+        // Uncompressed SEC1 encoding of the point.
+        let mut point = Vec::with_capacity(1 + size * 2);
+        point.push(0x04);
+        point.extend_from_slice(x);
+        point.extend_from_slice(y);
+        let value = match key_type {
+            KeyType::NistP256 => {
+                PublicKeyValue::NistP256(p256::PublicKey::from_sec1_bytes(&point)?)
+            }
+            KeyType::NistP384 => {
+                PublicKeyValue::NistP384(p384::PublicKey::from_sec1_bytes(&point)?)
+            }
+            KeyType::BrainpoolP256r1 => {
+                PublicKeyValue::BrainpoolP256r1(BrainpoolP256r1PublicKey::from_sec1_bytes(&point)?)
+            }
+            KeyType::BrainpoolP384r1 => {
+                PublicKeyValue::BrainpoolP384r1(BrainpoolP384r1PublicKey::from_sec1_bytes(&point)?)
+            }
+            KeyType::Rsa | KeyType::Curve25519 => return Err(PKeyError),
+        };
+        Ok(PublicKey { value })
+    }
+
+    /// Get the type of this key.
+    pub fn key_type(&self) -> KeyType {
+        match &self.value {
+            PublicKeyValue::Rsa(_) => KeyType::Rsa,
+            PublicKeyValue::NistP256(_) => KeyType::NistP256,
+            PublicKeyValue::NistP384(_) => KeyType::NistP384,
+            PublicKeyValue::Curve25519(_) => KeyType::Curve25519,
+            PublicKeyValue::BrainpoolP256r1(_) => KeyType::BrainpoolP256r1,
+            PublicKeyValue::BrainpoolP384r1(_) => KeyType::BrainpoolP384r1,
+        }
+    }
+
+    fn rsa(&self) -> Result<&RsaPublicKey, Error> {
+        match &self.value {
+            PublicKeyValue::Rsa(key) => Ok(key),
+            _ => Err(Error::new(
+                StatusCode::BadSecurityChecksFailed,
+                "Public key is not an RSA key",
+            )),
+        }
+    }
+
     /// Verifies the data using RSA-SHA1
     pub fn verify_sha1(&self, data: &[u8], signature: &[u8]) -> Result<bool, Error> {
-        let verifying_key = pkcs1v15::VerifyingKey::<sha1::Sha1>::new(self.value.clone());
+        let verifying_key = pkcs1v15::VerifyingKey::<sha1::Sha1>::new(self.rsa()?.clone());
         let r = pkcs1v15::Signature::try_from(signature);
         match r {
             Err(e) => Err(Error::new(StatusCode::BadUnexpectedError, e)),
@@ -331,7 +692,7 @@ impl PublicKey {
 
     /// Verifies the data using RSA-SHA256
     pub fn verify_sha256(&self, data: &[u8], signature: &[u8]) -> Result<bool, Error> {
-        let verifying_key = pkcs1v15::VerifyingKey::<sha2::Sha256>::new(self.value.clone());
+        let verifying_key = pkcs1v15::VerifyingKey::<sha2::Sha256>::new(self.rsa()?.clone());
         let r = pkcs1v15::Signature::try_from(signature);
         match r {
             Err(e) => Err(Error::new(StatusCode::BadUnexpectedError, e)),
@@ -344,7 +705,7 @@ impl PublicKey {
 
     /// Verifies the data using RSA-SHA256-PSS
     pub fn verify_sha256_pss(&self, data: &[u8], signature: &[u8]) -> Result<bool, Error> {
-        let verifying_key = pss::VerifyingKey::<sha2::Sha256>::new(self.value.clone());
+        let verifying_key = pss::VerifyingKey::<sha2::Sha256>::new(self.rsa()?.clone());
         let r = pss::Signature::try_from(signature);
         match r {
             Err(e) => Err(Error::new(StatusCode::BadUnexpectedError, e)),
@@ -355,28 +716,163 @@ impl PublicKey {
         }
     }
 
-    fn pkcs1_encrypt(&self, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
+    /// Verifies the data using ECDSA, with SHA256 for P-256 keys and SHA384 for
+    /// P-384 keys. The signature is the concatenated `r` and `s` values.
+    /// Ed25519 keys verify pure Ed25519 signatures instead.
+    pub fn verify_ecdsa(&self, data: &[u8], signature: &[u8]) -> Result<bool, Error> {
+        use ecdsa::signature::Verifier as _;
+
+        // A malformed signature is treated the same as one that does not match.
+        match &self.value {
+            PublicKeyValue::NistP256(key) => Ok(p256::ecdsa::Signature::from_slice(signature)
+                .is_ok_and(|s| {
+                    p256::ecdsa::VerifyingKey::from(key)
+                        .verify(data, &s)
+                        .is_ok()
+                })),
+            PublicKeyValue::NistP384(key) => Ok(p384::ecdsa::Signature::from_slice(signature)
+                .is_ok_and(|s| {
+                    p384::ecdsa::VerifyingKey::from(key)
+                        .verify(data, &s)
+                        .is_ok()
+                })),
+            PublicKeyValue::Curve25519(key) => Ok(ed25519_dalek::Signature::from_slice(signature)
+                .is_ok_and(|s| key.verify_strict(data, &s).is_ok())),
+            PublicKeyValue::BrainpoolP256r1(key) => Ok(
+                ecdsa::Signature::<bp256::BrainpoolP256r1>::from_slice(signature)
+                    .is_ok_and(|s| ecdsa::VerifyingKey::from(key).verify(data, &s).is_ok()),
+            ),
+            PublicKeyValue::BrainpoolP384r1(key) => Ok(
+                ecdsa::Signature::<bp384::BrainpoolP384r1>::from_slice(signature)
+                    .is_ok_and(|s| ecdsa::VerifyingKey::from(key).verify(data, &s).is_ok()),
+            ),
+            PublicKeyValue::Rsa(_) => Err(Error::new(
+                StatusCode::BadSecurityChecksFailed,
+                "Public key is not an elliptic curve key",
+            )),
+        }
+    }
+
+    /// Verifies a signature made by the issuer of a certificate or CRL, where `algorithm` is
+    /// the X509 signature algorithm. ECDSA signatures are DER encoded, Ed25519 signatures are
+    /// raw. Returns false if the algorithm is unsupported or does not match this key.
+    pub(crate) fn verify_signed_data(
+        &self,
+        algorithm: &const_oid::ObjectIdentifier,
+        data: &[u8],
+        signature: &[u8],
+    ) -> bool {
+        use const_oid::db::rfc5912;
+        use sha2::Digest;
+
+        match &self.value {
+            PublicKeyValue::Rsa(key) => match *algorithm {
+                rfc5912::SHA_1_WITH_RSA_ENCRYPTION => {
+                    Self::verify_pkcs1v15::<sha1::Sha1>(key, data, signature)
+                }
+                rfc5912::SHA_256_WITH_RSA_ENCRYPTION => {
+                    Self::verify_pkcs1v15::<sha2::Sha256>(key, data, signature)
+                }
+                rfc5912::SHA_384_WITH_RSA_ENCRYPTION => {
+                    Self::verify_pkcs1v15::<sha2::Sha384>(key, data, signature)
+                }
+                rfc5912::SHA_512_WITH_RSA_ENCRYPTION => {
+                    Self::verify_pkcs1v15::<sha2::Sha512>(key, data, signature)
+                }
+                _ => false,
+            },
+            PublicKeyValue::NistP256(key) => {
+                use p256::ecdsa::signature::hazmat::PrehashVerifier;
+                let digest = match *algorithm {
+                    rfc5912::ECDSA_WITH_SHA_256 => sha2::Sha256::digest(data).to_vec(),
+                    rfc5912::ECDSA_WITH_SHA_384 => sha2::Sha384::digest(data).to_vec(),
+                    _ => return false,
+                };
+                p256::ecdsa::Signature::from_der(signature).is_ok_and(|s| {
+                    p256::ecdsa::VerifyingKey::from(key)
+                        .verify_prehash(&digest, &s)
+                        .is_ok()
+                })
+            }
+            PublicKeyValue::NistP384(key) => {
+                use p384::ecdsa::signature::hazmat::PrehashVerifier;
+                let digest = match *algorithm {
+                    rfc5912::ECDSA_WITH_SHA_256 => sha2::Sha256::digest(data).to_vec(),
+                    rfc5912::ECDSA_WITH_SHA_384 => sha2::Sha384::digest(data).to_vec(),
+                    _ => return false,
+                };
+                p384::ecdsa::Signature::from_der(signature).is_ok_and(|s| {
+                    p384::ecdsa::VerifyingKey::from(key)
+                        .verify_prehash(&digest, &s)
+                        .is_ok()
+                })
+            }
+            PublicKeyValue::BrainpoolP256r1(key) => {
+                use ecdsa::signature::hazmat::PrehashVerifier;
+                let digest = match *algorithm {
+                    rfc5912::ECDSA_WITH_SHA_256 => sha2::Sha256::digest(data).to_vec(),
+                    rfc5912::ECDSA_WITH_SHA_384 => sha2::Sha384::digest(data).to_vec(),
+                    _ => return false,
+                };
+                ecdsa::Signature::<bp256::BrainpoolP256r1>::from_der(signature).is_ok_and(|s| {
+                    ecdsa::VerifyingKey::from(key)
+                        .verify_prehash(&digest, &s)
+                        .is_ok()
+                })
+            }
+            PublicKeyValue::BrainpoolP384r1(key) => {
+                use ecdsa::signature::hazmat::PrehashVerifier;
+                let digest = match *algorithm {
+                    rfc5912::ECDSA_WITH_SHA_256 => sha2::Sha256::digest(data).to_vec(),
+                    rfc5912::ECDSA_WITH_SHA_384 => sha2::Sha384::digest(data).to_vec(),
+                    _ => return false,
+                };
+                ecdsa::Signature::<bp384::BrainpoolP384r1>::from_der(signature).is_ok_and(|s| {
+                    ecdsa::VerifyingKey::from(key)
+                        .verify_prehash(&digest, &s)
+                        .is_ok()
+                })
+            }
+            PublicKeyValue::Curve25519(key) => {
+                *algorithm == const_oid::db::rfc8410::ID_ED_25519
+                    && ed25519_dalek::Signature::from_slice(signature)
+                        .is_ok_and(|s| key.verify_strict(data, &s).is_ok())
+            }
+        }
+    }
+
+    fn verify_pkcs1v15<D>(key: &RsaPublicKey, data: &[u8], signature: &[u8]) -> bool
+    where
+        D: sha2::Digest + const_oid::AssociatedOid,
+    {
+        let verifying_key = pkcs1v15::VerifyingKey::<D>::new(key.clone());
+        pkcs1v15::Signature::try_from(signature)
+            .is_ok_and(|s| verifying_key.verify(data, &s).is_ok())
+    }
+
+    fn pkcs1_encrypt(key: &RsaPublicKey, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
         let mut rng = rand::thread_rng();
-        self.value.encrypt(&mut rng, Pkcs1v15Encrypt, src)
+        key.encrypt(&mut rng, Pkcs1v15Encrypt, src)
     }
 
-    fn oaepsha1_encrypt(&self, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
+    fn oaepsha1_encrypt(key: &RsaPublicKey, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
         let mut rng = rand::thread_rng();
         let padding = Oaep::new::<sha1::Sha1>();
-        self.value.encrypt(&mut rng, padding, src)
+        key.encrypt(&mut rng, padding, src)
     }
 
-    fn oaepsha2_encrypt(&self, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
+    fn oaepsha2_encrypt(key: &RsaPublicKey, src: &[u8]) -> rsa::errors::Result<Vec<u8>> {
         let mut rng = rand::thread_rng();
         let padding = Oaep::new::<sha2::Sha256>();
-        self.value.encrypt(&mut rng, padding, src)
+        key.encrypt(&mut rng, padding, src)
     }
 
     fn encrypt_data_chunk(&self, src: &[u8], padding: RsaPadding) -> Result<Vec<u8>, PKeyError> {
+        let key = self.rsa().map_err(|_| PKeyError)?;
         let r = match padding {
-            RsaPadding::OaepSha256 => self.oaepsha2_encrypt(src),
-            RsaPadding::Pkcs1 => self.pkcs1_encrypt(src),
-            RsaPadding::OaepSha1 => self.oaepsha1_encrypt(src),
+            RsaPadding::OaepSha256 => Self::oaepsha2_encrypt(key, src),
+            RsaPadding::Pkcs1 => Self::pkcs1_encrypt(key, src),
+            RsaPadding::OaepSha1 => Self::oaepsha1_encrypt(key, src),
         };
 
         match r {
@@ -393,6 +889,7 @@ impl PublicKey {
         dst: &mut [u8],
         padding: RsaPadding,
     ) -> Result<usize, PKeyError> {
+        self.rsa().map_err(|_| PKeyError)?;
         let cipher_text_block_size = self.cipher_text_block_size();
         let plain_text_block_size = self.plain_text_block_size(padding);
 
@@ -14,8 +14,8 @@ use opcua_types::{constants, status_code::StatusCode, ByteString, Error};
 use super::{
     aeskey::AesKey,
     hash,
-    pkey::{PrivateKey, PublicKey, RsaPadding},
-    random, SHA1_SIZE, SHA256_SIZE,
+    pkey::{KeyType, PrivateKey, PublicKey, RsaPadding},
+    random, SHA1_SIZE, SHA256_SIZE, SHA384_SIZE,
 };
 
 // These are constants that govern the different encryption / signing modes for OPC UA. In some
@@ -159,6 +159,143 @@ mod basic_256 {
     pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (1024, 2048);
 }
 
+/// ECC_nistP256 security policy
+///
+///   AsymmetricSignatureAlgorithm_ECDSA-SHA2-256
+///   CertificateSignatureAlgorithm_ECDSA-SHA2-256
+///   KeyAgreementAlgorithm_ECDH-nistP256
+///   KeyDerivationAlgorithm_HKDF-SHA2-256
+///   SymmetricEncryptionAlgorithm_AES128-CBC
+///   SymmetricSignatureAlgorithm_HMAC-SHA2-256
+///
+/// # Limits
+///
+///   DerivedSignatureKeyLength – 256 bits
+///   AsymmetricKeyLength - 256 bits
+///   SecureChannelNonceLength - 64 bytes
+mod ecc_nist_p256 {
+    use crate::algorithms::*;
+
+    pub const SECURITY_POLICY: &str = "ECC-nistP256";
+    pub const SECURITY_POLICY_URI: &str = "http://opcfoundation.org/UA/SecurityPolicy#ECC_nistP256";
+
+    pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA256;
+    pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_ECDSA_SHA256;
+    pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 256;
+    pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (256, 256);
+}
+
+/// ECC_nistP384 security policy
+///
+///   AsymmetricSignatureAlgorithm_ECDSA-SHA2-384
+///   CertificateSignatureAlgorithm_ECDSA-SHA2-384
+///   KeyAgreementAlgorithm_ECDH-nistP384
+///   KeyDerivationAlgorithm_HKDF-SHA2-384
+///   SymmetricEncryptionAlgorithm_AES256-CBC
+///   SymmetricSignatureAlgorithm_HMAC-SHA2-384
+///
+/// # Limits
+///
+///   DerivedSignatureKeyLength – 384 bits
+///   AsymmetricKeyLength - 384 bits
+///   SecureChannelNonceLength - 96 bytes
+mod ecc_nist_p384 {
+    use crate::algorithms::*;
+
+    pub const SECURITY_POLICY: &str = "ECC-nistP384";
+    pub const SECURITY_POLICY_URI: &str = "http://opcfoundation.org/UA/SecurityPolicy#ECC_nistP384";
+
+    pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA384;
+    pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_ECDSA_SHA384;
+    pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 384;
+    pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (384, 384);
+}
+
+/// ECC_brainpoolP256r1 security policy
+///
+///   AsymmetricSignatureAlgorithm_ECDSA-SHA2-256
+///   CertificateSignatureAlgorithm_ECDSA-SHA2-256
+///   KeyAgreementAlgorithm_ECDH-brainpoolP256r1
+///   KeyDerivationAlgorithm_HKDF-SHA2-256
+///   SymmetricEncryptionAlgorithm_AES128-CBC
+///   SymmetricSignatureAlgorithm_HMAC-SHA2-256
+///
+/// # Limits
+///
+///   DerivedSignatureKeyLength – 256 bits
+///   AsymmetricKeyLength - 256 bits
+///   SecureChannelNonceLength - 64 bytes
+mod ecc_brainpool_p256r1 {
+    use crate::algorithms::*;
+
+    pub const SECURITY_POLICY: &str = "ECC-brainpoolP256r1";
+    pub const SECURITY_POLICY_URI: &str =
+        "http://opcfoundation.org/UA/SecurityPolicy#ECC_brainpoolP256r1";
+
+    pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA256;
+    pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_ECDSA_SHA256;
+    pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 256;
+    pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (256, 256);
+}
+
+/// ECC_brainpoolP384r1 security policy
+///
+///   AsymmetricSignatureAlgorithm_ECDSA-SHA2-384
+///   CertificateSignatureAlgorithm_ECDSA-SHA2-384
+///   KeyAgreementAlgorithm_ECDH-brainpoolP384r1
+///   KeyDerivationAlgorithm_HKDF-SHA2-384
+///   SymmetricEncryptionAlgorithm_AES256-CBC
+///   SymmetricSignatureAlgorithm_HMAC-SHA2-384
+///
+/// # Limits
+///
+///   DerivedSignatureKeyLength – 384 bits
+///   AsymmetricKeyLength - 384 bits
+///   SecureChannelNonceLength - 96 bytes
+mod ecc_brainpool_p384r1 {
+    use crate::algorithms::*;
+
+    pub const SECURITY_POLICY: &str = "ECC-brainpoolP384r1";
+    pub const SECURITY_POLICY_URI: &str =
+        "http://opcfoundation.org/UA/SecurityPolicy#ECC_brainpoolP384r1";
+
+    pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_HMAC_SHA384;
+    pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_ECDSA_SHA384;
+    pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 384;
+    pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (384, 384);
+}
+
+/// ECC_curve25519 security policy
+///
+///   AsymmetricSignatureAlgorithm_EdDSA-curve25519
+///   CertificateSignatureAlgorithm_EdDSA-curve25519
+///   KeyAgreementAlgorithm_ECDH-curve25519
+///   KeyDerivationAlgorithm_HKDF-SHA2-256
+///   SymmetricEncryptionAlgorithm_ChaCha20Poly1305
+///   SymmetricSignatureAlgorithm_Poly1305
+///
+/// # Limits
+///
+///   DerivedSignatureKeyLength – 256 bits
+///   AsymmetricKeyLength - 256 bits
+///   SecureChannelNonceLength - 32 bytes
+mod ecc_curve25519 {
+    use crate::algorithms::*;
+
+    pub const SECURITY_POLICY: &str = "ECC-curve25519";
+    pub const SECURITY_POLICY_URI: &str =
+        "http://opcfoundation.org/UA/SecurityPolicy#ECC_curve25519";
+
+    pub const SYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_CHACHA20_POLY1305;
+    pub const ASYMMETRIC_SIGNATURE_ALGORITHM: &str = DSIG_EDDSA_ED25519;
+    pub const DERIVED_SIGNATURE_KEY_LENGTH: usize = 256;
+    pub const ASYMMETRIC_KEY_LENGTH: (usize, usize) = (256, 256);
+}
+
+/// Signing key, encrypting key and initialization vector used to secure
+/// messages sent by one side of a secure channel.
+type SecureChannelKeys = (Vec<u8>, AesKey, Vec<u8>);
+
 /// SecurityPolicy implies what encryption and signing algorithms and their relevant key strengths
 /// are used during an encrypted session.
 #[derive(Debug, Clone, PartialEq, Copy)]
@@ -177,6 +314,16 @@ pub enum SecurityPolicy {
     Basic128Rsa15,
     /// Basic256.
     Basic256,
+    /// ECC with the NIST P-256 curve.
+    EccNistP256,
+    /// ECC with the NIST P-384 curve.
+    EccNistP384,
+    /// ECC with curve25519.
+    EccCurve25519,
+    /// ECC with the brainpoolP256r1 curve.
+    EccBrainpoolP256r1,
+    /// ECC with the brainpoolP384r1 curve.
+    EccBrainpoolP384r1,
 }
 
 impl fmt::Display for SecurityPolicy {
@@ -204,6 +351,21 @@ impl FromStr for SecurityPolicy {
             | aes_128_sha_256_rsa_oaep::SECURITY_POLICY_URI => SecurityPolicy::Aes128Sha256RsaOaep,
             aes_256_sha_256_rsa_pss::SECURITY_POLICY
             | aes_256_sha_256_rsa_pss::SECURITY_POLICY_URI => SecurityPolicy::Aes256Sha256RsaPss,
+            ecc_nist_p256::SECURITY_POLICY | ecc_nist_p256::SECURITY_POLICY_URI => {
+                SecurityPolicy::EccNistP256
+            }
+            ecc_nist_p384::SECURITY_POLICY | ecc_nist_p384::SECURITY_POLICY_URI => {
+                SecurityPolicy::EccNistP384
+            }
+            ecc_curve25519::SECURITY_POLICY | ecc_curve25519::SECURITY_POLICY_URI => {
+                SecurityPolicy::EccCurve25519
+            }
+            ecc_brainpool_p256r1::SECURITY_POLICY | ecc_brainpool_p256r1::SECURITY_POLICY_URI => {
+                SecurityPolicy::EccBrainpoolP256r1
+            }
+            ecc_brainpool_p384r1::SECURITY_POLICY | ecc_brainpool_p384r1::SECURITY_POLICY_URI => {
+                SecurityPolicy::EccBrainpoolP384r1
+            }
             _ => {
                 error!("Specified security policy \"{}\" is not recognized", s);
                 SecurityPolicy::Unknown
@@ -230,6 +392,11 @@ impl SecurityPolicy {
             SecurityPolicy::Basic256Sha256 => basic_256_sha_256::SECURITY_POLICY_URI,
             SecurityPolicy::Aes128Sha256RsaOaep => aes_128_sha_256_rsa_oaep::SECURITY_POLICY_URI,
             SecurityPolicy::Aes256Sha256RsaPss => aes_256_sha_256_rsa_pss::SECURITY_POLICY_URI,
+            SecurityPolicy::EccNistP256 => ecc_nist_p256::SECURITY_POLICY_URI,
+            SecurityPolicy::EccNistP384 => ecc_nist_p384::SECURITY_POLICY_URI,
+            SecurityPolicy::EccCurve25519 => ecc_curve25519::SECURITY_POLICY_URI,
+            SecurityPolicy::EccBrainpoolP256r1 => ecc_brainpool_p256r1::SECURITY_POLICY_URI,
+            SecurityPolicy::EccBrainpoolP384r1 => ecc_brainpool_p384r1::SECURITY_POLICY_URI,
             _ => {
                 panic!("Shouldn't be turning an unknown policy into a uri");
             }
@@ -246,9 +413,51 @@ impl SecurityPolicy {
                 | SecurityPolicy::Basic256Sha256
                 | SecurityPolicy::Aes128Sha256RsaOaep
                 | SecurityPolicy::Aes256Sha256RsaPss
+                | SecurityPolicy::EccNistP256
+                | SecurityPolicy::EccNistP384
+                | SecurityPolicy::EccCurve25519
+                | SecurityPolicy::EccBrainpoolP256r1
+                | SecurityPolicy::EccBrainpoolP384r1
         )
     }
 
+    /// Returns true if the security policy uses elliptic curve cryptography. These policies
+    /// use ephemeral keys as secure channel nonces, and never use asymmetric encryption.
+    pub fn is_ecc(&self) -> bool {
+        matches!(
+            self,
+            SecurityPolicy::EccNistP256
+                | SecurityPolicy::EccNistP384
+                | SecurityPolicy::EccCurve25519
+                | SecurityPolicy::EccBrainpoolP256r1
+                | SecurityPolicy::EccBrainpoolP384r1
+        )
+    }
+
+    /// Returns true if the security policy uses authenticated encryption, where the tag of
+    /// the encryption replaces the symmetric signature and messages are not padded.
+    pub fn is_aead(&self) -> bool {
+        matches!(self, SecurityPolicy::EccCurve25519)
+    }
+
+    /// Returns the type of key the application instance certificate must have to be used
+    /// with this security policy, or `None` if the policy does not use certificates.
+    pub fn key_type(&self) -> Option<KeyType> {
+        match self {
+            SecurityPolicy::None | SecurityPolicy::Unknown => None,
+            SecurityPolicy::EccNistP256 => Some(KeyType::NistP256),
+            SecurityPolicy::EccNistP384 => Some(KeyType::NistP384),
+            SecurityPolicy::EccCurve25519 => Some(KeyType::Curve25519),
+            SecurityPolicy::EccBrainpoolP256r1 => Some(KeyType::BrainpoolP256r1),
+            SecurityPolicy::EccBrainpoolP384r1 => Some(KeyType::BrainpoolP384r1),
+            SecurityPolicy::Basic128Rsa15
+            | SecurityPolicy::Basic256
+            | SecurityPolicy::Basic256Sha256
+            | SecurityPolicy::Aes128Sha256RsaOaep
+            | SecurityPolicy::Aes256Sha256RsaPss => Some(KeyType::Rsa),
+        }
+    }
+
     /// Returns true if the security policy has been deprecated by the OPC UA specification
     pub fn is_deprecated(&self) -> bool {
         // Since 1.04 because SHA-1 is no longer considered safe
@@ -269,6 +478,11 @@ impl SecurityPolicy {
             SecurityPolicy::Basic256Sha256 => basic_256_sha_256::SECURITY_POLICY,
             SecurityPolicy::Aes128Sha256RsaOaep => aes_128_sha_256_rsa_oaep::SECURITY_POLICY,
             SecurityPolicy::Aes256Sha256RsaPss => aes_256_sha_256_rsa_pss::SECURITY_POLICY,
+            SecurityPolicy::EccNistP256 => ecc_nist_p256::SECURITY_POLICY,
+            SecurityPolicy::EccNistP384 => ecc_nist_p384::SECURITY_POLICY,
+            SecurityPolicy::EccCurve25519 => ecc_curve25519::SECURITY_POLICY,
+            SecurityPolicy::EccBrainpoolP256r1 => ecc_brainpool_p256r1::SECURITY_POLICY,
+            SecurityPolicy::EccBrainpoolP384r1 => ecc_brainpool_p384r1::SECURITY_POLICY,
             _ => {
                 panic!("Shouldn't be turning an unknown policy into a string");
             }
@@ -309,6 +523,15 @@ impl SecurityPolicy {
             SecurityPolicy::Aes256Sha256RsaPss => {
                 aes_256_sha_256_rsa_pss::ASYMMETRIC_SIGNATURE_ALGORITHM
             }
+            SecurityPolicy::EccNistP256 => ecc_nist_p256::ASYMMETRIC_SIGNATURE_ALGORITHM,
+            SecurityPolicy::EccNistP384 => ecc_nist_p384::ASYMMETRIC_SIGNATURE_ALGORITHM,
+            SecurityPolicy::EccCurve25519 => ecc_curve25519::ASYMMETRIC_SIGNATURE_ALGORITHM,
+            SecurityPolicy::EccBrainpoolP256r1 => {
+                ecc_brainpool_p256r1::ASYMMETRIC_SIGNATURE_ALGORITHM
+            }
+            SecurityPolicy::EccBrainpoolP384r1 => {
+                ecc_brainpool_p384r1::ASYMMETRIC_SIGNATURE_ALGORITHM
+            }
             _ => {
                 panic!("Invalid policy");
             }
@@ -329,6 +552,15 @@ impl SecurityPolicy {
             SecurityPolicy::Aes256Sha256RsaPss => {
                 aes_256_sha_256_rsa_pss::SYMMETRIC_SIGNATURE_ALGORITHM
             }
+            SecurityPolicy::EccNistP256 => ecc_nist_p256::SYMMETRIC_SIGNATURE_ALGORITHM,
+            SecurityPolicy::EccNistP384 => ecc_nist_p384::SYMMETRIC_SIGNATURE_ALGORITHM,
+            SecurityPolicy::EccCurve25519 => ecc_curve25519::SYMMETRIC_SIGNATURE_ALGORITHM,
+            SecurityPolicy::EccBrainpoolP256r1 => {
+                ecc_brainpool_p256r1::SYMMETRIC_SIGNATURE_ALGORITHM
+            }
+            SecurityPolicy::EccBrainpoolP384r1 => {
+                ecc_brainpool_p384r1::SYMMETRIC_SIGNATURE_ALGORITHM
+            }
             _ => {
                 panic!("Invalid policy");
             }
@@ -344,13 +576,30 @@ impl SecurityPolicy {
             | SecurityPolicy::Basic256
             | SecurityPolicy::Basic256Sha256
             | SecurityPolicy::Aes128Sha256RsaOaep
-            | SecurityPolicy::Aes256Sha256RsaPss => 16,
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccBrainpoolP256r1
+            | SecurityPolicy::EccBrainpoolP384r1 => 16,
+            // ChaCha20 is a stream cipher.
+            SecurityPolicy::EccCurve25519 => 1,
             _ => {
                 panic!("Invalid policy");
             }
         }
     }
 
+    /// Initialization vector size in bytes. This is the block size for AES, and the
+    /// nonce size for ChaCha20-Poly1305.
+    ///
+    /// This will panic if the security policy is `Unknown` or `None`.
+    pub fn initialization_vector_size(&self) -> usize {
+        match self {
+            SecurityPolicy::EccCurve25519 => 12,
+            _ => self.plain_block_size(),
+        }
+    }
+
     /// Signature size in bytes.
     ///
     /// This will panic if the security policy is `Unknown`.
@@ -360,7 +609,12 @@ impl SecurityPolicy {
             SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Basic256 => SHA1_SIZE,
             SecurityPolicy::Basic256Sha256
             | SecurityPolicy::Aes128Sha256RsaOaep
-            | SecurityPolicy::Aes256Sha256RsaPss => SHA256_SIZE,
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccBrainpoolP256r1 => SHA256_SIZE,
+            SecurityPolicy::EccNistP384 | SecurityPolicy::EccBrainpoolP384r1 => SHA384_SIZE,
+            // The Poly1305 tag.
+            SecurityPolicy::EccCurve25519 => 16,
             _ => {
                 panic!("Invalid policy");
             }
@@ -381,6 +635,15 @@ impl SecurityPolicy {
             SecurityPolicy::Aes256Sha256RsaPss => {
                 aes_256_sha_256_rsa_pss::DERIVED_SIGNATURE_KEY_LENGTH
             }
+            SecurityPolicy::EccNistP256 => ecc_nist_p256::DERIVED_SIGNATURE_KEY_LENGTH,
+            SecurityPolicy::EccNistP384 => ecc_nist_p384::DERIVED_SIGNATURE_KEY_LENGTH,
+            SecurityPolicy::EccCurve25519 => ecc_curve25519::DERIVED_SIGNATURE_KEY_LENGTH,
+            SecurityPolicy::EccBrainpoolP256r1 => {
+                ecc_brainpool_p256r1::DERIVED_SIGNATURE_KEY_LENGTH
+            }
+            SecurityPolicy::EccBrainpoolP384r1 => {
+                ecc_brainpool_p384r1::DERIVED_SIGNATURE_KEY_LENGTH
+            }
             _ => {
                 panic!("Invalid policy");
             }
@@ -388,6 +651,27 @@ impl SecurityPolicy {
         length / 8
     }
 
+    /// Returns the symmetric encryption key size in bytes.
+    ///
+    /// This will panic if the security policy is `Unknown` or `None`.
+    pub fn encrypting_key_size(&self) -> usize {
+        match self {
+            SecurityPolicy::Basic128Rsa15
+            | SecurityPolicy::Aes128Sha256RsaOaep
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccBrainpoolP256r1 => 16,
+            SecurityPolicy::Basic256
+            | SecurityPolicy::Basic256Sha256
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccCurve25519
+            | SecurityPolicy::EccBrainpoolP384r1 => 32,
+            _ => {
+                panic!("Invalid policy");
+            }
+        }
+    }
+
     /// Returns the min and max (inclusive) key length in bits
     pub fn min_max_asymmetric_keylength(&self) -> (usize, usize) {
         match self {
@@ -396,6 +680,11 @@ impl SecurityPolicy {
             SecurityPolicy::Basic256Sha256 => basic_256_sha_256::ASYMMETRIC_KEY_LENGTH,
             SecurityPolicy::Aes128Sha256RsaOaep => aes_128_sha_256_rsa_oaep::ASYMMETRIC_KEY_LENGTH,
             SecurityPolicy::Aes256Sha256RsaPss => aes_256_sha_256_rsa_pss::ASYMMETRIC_KEY_LENGTH,
+            SecurityPolicy::EccNistP256 => ecc_nist_p256::ASYMMETRIC_KEY_LENGTH,
+            SecurityPolicy::EccNistP384 => ecc_nist_p384::ASYMMETRIC_KEY_LENGTH,
+            SecurityPolicy::EccCurve25519 => ecc_curve25519::ASYMMETRIC_KEY_LENGTH,
+            SecurityPolicy::EccBrainpoolP256r1 => ecc_brainpool_p256r1::ASYMMETRIC_KEY_LENGTH,
+            SecurityPolicy::EccBrainpoolP384r1 => ecc_brainpool_p384r1::ASYMMETRIC_KEY_LENGTH,
             _ => {
                 panic!("Invalid policy");
             }
@@ -408,7 +697,10 @@ impl SecurityPolicy {
         keylength >= min_max.0 && keylength <= min_max.1
     }
 
-    /// Creates a random nonce in a bytestring with a length appropriate for the policy
+    /// Creates a random nonce in a bytestring with a length appropriate for the policy.
+    ///
+    /// Note that secure channels using an ECC security policy must use ephemeral keys
+    /// as nonces instead, see [`crate::EphemeralKey`].
     pub fn random_nonce(&self) -> ByteString {
         match self {
             SecurityPolicy::None => ByteString::null(),
@@ -424,6 +716,11 @@ impl SecurityPolicy {
             | SecurityPolicy::Basic256Sha256
             | SecurityPolicy::Aes128Sha256RsaOaep
             | SecurityPolicy::Aes256Sha256RsaPss => 32,
+            // The nonce is an ephemeral public key, i.e. two coordinates on the curve.
+            SecurityPolicy::EccNistP256 | SecurityPolicy::EccBrainpoolP256r1 => 64,
+            SecurityPolicy::EccNistP384 | SecurityPolicy::EccBrainpoolP384r1 => 96,
+            // The nonce is a raw X25519 public key.
+            SecurityPolicy::EccCurve25519 => 32,
             // The nonce can be used for password or X509 authentication
             // even when the security policy is None.
             // see https://github.com/advisories/GHSA-pq4w-qm9g-qx68
@@ -441,6 +738,11 @@ impl SecurityPolicy {
             basic_256_sha_256::SECURITY_POLICY_URI => SecurityPolicy::Basic256Sha256,
             aes_128_sha_256_rsa_oaep::SECURITY_POLICY_URI => SecurityPolicy::Aes128Sha256RsaOaep,
             aes_256_sha_256_rsa_pss::SECURITY_POLICY_URI => SecurityPolicy::Aes256Sha256RsaPss,
+            ecc_nist_p256::SECURITY_POLICY_URI => SecurityPolicy::EccNistP256,
+            ecc_nist_p384::SECURITY_POLICY_URI => SecurityPolicy::EccNistP384,
+            ecc_curve25519::SECURITY_POLICY_URI => SecurityPolicy::EccCurve25519,
+            ecc_brainpool_p256r1::SECURITY_POLICY_URI => SecurityPolicy::EccBrainpoolP256r1,
+            ecc_brainpool_p384r1::SECURITY_POLICY_URI => SecurityPolicy::EccBrainpoolP384r1,
             _ => {
                 error!(
                     "Specified security policy uri \"{}\" is not recognized",
@@ -504,11 +806,7 @@ impl SecurityPolicy {
     /// The Client keys are used to secure Messages sent by the Client. The Server keys
     /// are used to secure Messages sent by the Server.
     ///
-    pub fn make_secure_channel_keys(
-        &self,
-        secret: &[u8],
-        seed: &[u8],
-    ) -> (Vec<u8>, AesKey, Vec<u8>) {
+    pub fn make_secure_channel_keys(&self, secret: &[u8], seed: &[u8]) -> SecureChannelKeys {
         // Work out the length of stuff
         let signing_key_length = self.derived_signature_key_size();
         let (encrypting_key_length, encrypting_block_size) = match self {
@@ -534,6 +832,58 @@ impl SecurityPolicy {
         (signing_key, encrypting_key, iv)
     }
 
+    /// Part 6
+    /// 6.8.1
+    /// Deriving keys for ECC security policies. Both sides compute the same shared secret
+    /// from their own ephemeral key and the nonce of the other side, see [`crate::EphemeralKey`].
+    /// The keys are derived from the secret with HKDF, using the following salts as both
+    /// salt and info:
+    ///
+    /// ClientSalt = L | UTF8("opcua-client") | ClientNonce | ServerNonce
+    /// ServerSalt = L | UTF8("opcua-server") | ServerNonce | ClientNonce
+    ///
+    /// where L is the length of the derived key material as a little endian UInt16. The key
+    /// material is split into the signing key, encrypting key and initialization vector.
+    ///
+    /// Returns the client keys and the server keys, in that order.
+    pub fn make_ecc_secure_channel_keys(
+        &self,
+        shared_secret: &[u8],
+        client_nonce: &[u8],
+        server_nonce: &[u8],
+    ) -> (SecureChannelKeys, SecureChannelKeys) {
+        let signing_key_length = self.derived_signature_key_size();
+        let encrypting_key_length = self.encrypting_key_size();
+        let iv_length = self.initialization_vector_size();
+        let length = signing_key_length + encrypting_key_length + iv_length;
+
+        let derive = |label: &[u8], first_nonce: &[u8], second_nonce: &[u8]| {
+            let mut salt = Vec::with_capacity(2 + label.len() + first_nonce.len() * 2);
+            salt.extend_from_slice(&(length as u16).to_le_bytes());
+            salt.extend_from_slice(label);
+            salt.extend_from_slice(first_nonce);
+            salt.extend_from_slice(second_nonce);
+            let keys = match self {
+                SecurityPolicy::EccNistP384 | SecurityPolicy::EccBrainpoolP384r1 => {
+                    hash::hkdf_sha384(&salt, shared_secret, &salt, length)
+                }
+                _ => hash::hkdf_sha256(&salt, shared_secret, &salt, length),
+            };
+            let (signing_key, rest) = keys.split_at(signing_key_length);
+            let (encrypting_key, iv) = rest.split_at(encrypting_key_length);
+            (
+                signing_key.to_vec(),
+                AesKey::new(*self, encrypting_key),
+                iv.to_vec(),
+            )
+        };
+
+        (
+            derive(b"opcua-client", client_nonce, server_nonce),
+            derive(b"opcua-server", server_nonce, client_nonce),
+        )
+    }
+
     /// Produce a signature of the data using an asymmetric key. Stores the signature in the supplied
     /// `signature` buffer. Returns the size of the signature within that buffer.
     pub fn asymmetric_sign(
@@ -550,6 +900,11 @@ impl SecurityPolicy {
                 signing_key.sign_sha256(data, signature)?
             }
             SecurityPolicy::Aes256Sha256RsaPss => signing_key.sign_sha256_pss(data, signature)?,
+            SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccCurve25519
+            | SecurityPolicy::EccBrainpoolP256r1
+            | SecurityPolicy::EccBrainpoolP384r1 => signing_key.sign_ecdsa(data, signature)?,
             _ => {
                 panic!("Invalid policy");
             }
@@ -578,6 +933,13 @@ impl SecurityPolicy {
             SecurityPolicy::Aes256Sha256RsaPss => {
                 verification_key.verify_sha256_pss(data, signature)?
             }
+            SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccNistP384
+            | SecurityPolicy::EccCurve25519
+            | SecurityPolicy::EccBrainpoolP256r1
+            | SecurityPolicy::EccBrainpoolP384r1 => {
+                verification_key.verify_ecdsa(data, signature)?
+            }
             _ => {
                 panic!("Invalid policy");
             }
@@ -591,7 +953,7 @@ impl SecurityPolicy {
                 use crate::pkey::KeySize;
                 use log::trace;
                 // Calculate the signature using their key, see what we were expecting versus theirs
-                let mut their_signature = vec![0u8; their_key.size()];
+                let mut their_signature = vec![0u8; their_key.signature_size()];
                 self.asymmetric_sign(&their_key, data, their_signature.as_mut_slice())?;
                 trace!(
                     "Using their_key, signature should be {:?}",
@@ -629,6 +991,10 @@ impl SecurityPolicy {
         src: &[u8],
         dst: &mut [u8],
     ) -> Result<usize, StatusCode> {
+        if self.is_ecc() {
+            error!("Asymmetric encryption is not used by security policy {self}");
+            return Err(StatusCode::BadSecurityPolicyRejected);
+        }
         let padding = self.asymmetric_encryption_padding();
         encryption_key
             .public_encrypt(src, dst, padding)
@@ -644,6 +1010,12 @@ impl SecurityPolicy {
         src: &[u8],
         dst: &mut [u8],
     ) -> Result<usize, Error> {
+        if self.is_ecc() {
+            return Err(Error::new(
+                StatusCode::BadSecurityPolicyRejected,
+                format!("Asymmetric encryption is not used by security policy {self}"),
+            ));
+        }
         let padding = self.asymmetric_encryption_padding();
         decryption_key
             .private_decrypt(src, dst, padding)
@@ -652,6 +1024,9 @@ impl SecurityPolicy {
 
     /// Produce a signature of some data using the supplied symmetric key. Signing algorithm is determined
     /// by the security policy. Signature is stored in the supplied `signature` argument.
+    ///
+    /// Security policies using authenticated encryption have no separate signature, the data is
+    /// signed by [`AesKey::encrypt_aead`] instead, so this fails with `BadSecurityPolicyRejected`.
     pub fn symmetric_sign(
         &self,
         key: &[u8],
@@ -664,7 +1039,16 @@ impl SecurityPolicy {
             }
             SecurityPolicy::Basic256Sha256
             | SecurityPolicy::Aes128Sha256RsaOaep
-            | SecurityPolicy::Aes256Sha256RsaPss => hash::hmac_sha256(key, data, signature),
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccBrainpoolP256r1 => hash::hmac_sha256(key, data, signature),
+            SecurityPolicy::EccNistP384 | SecurityPolicy::EccBrainpoolP384r1 => {
+                hash::hmac_sha384(key, data, signature)
+            }
+            SecurityPolicy::EccCurve25519 => {
+                error!("Symmetric signatures are not used by security policy {self}");
+                Err(StatusCode::BadSecurityPolicyRejected)
+            }
             _ => {
                 panic!("Unsupported policy")
             }
@@ -672,20 +1056,34 @@ impl SecurityPolicy {
     }
 
     /// Verify the signature of a data block using the supplied symmetric key.
+    ///
+    /// Security policies using authenticated encryption have no separate signature, the data is
+    /// verified by [`AesKey::decrypt_aead`] instead, so this fails with `BadSecurityPolicyRejected`.
     pub fn symmetric_verify_signature(
         &self,
         key: &[u8],
         data: &[u8],
         signature: &[u8],
     ) -> Result<bool, Error> {
-        // Verify the signature using SHA-1 / SHA-256 HMAC
+        // Verify the signature using SHA-1 / SHA-256 / SHA-384 HMAC
         let verified = match self {
             SecurityPolicy::Basic128Rsa15 | SecurityPolicy::Basic256 => {
                 hash::verify_hmac_sha1(key, data, signature)
             }
             SecurityPolicy::Basic256Sha256
             | SecurityPolicy::Aes128Sha256RsaOaep
-            | SecurityPolicy::Aes256Sha256RsaPss => hash::verify_hmac_sha256(key, data, signature),
+            | SecurityPolicy::Aes256Sha256RsaPss
+            | SecurityPolicy::EccNistP256
+            | SecurityPolicy::EccBrainpoolP256r1 => hash::verify_hmac_sha256(key, data, signature),
+            SecurityPolicy::EccNistP384 | SecurityPolicy::EccBrainpoolP384r1 => {
+                hash::verify_hmac_sha384(key, data, signature)
+            }
+            SecurityPolicy::EccCurve25519 => {
+                return Err(Error::new(
+                    StatusCode::BadSecurityPolicyRejected,
+                    format!("Symmetric signatures are not used by security policy {self}"),
+                ));
+            }
             _ => {
                 panic!("Unsupported policy")
             }
@@ -1,8 +1,15 @@
-use opcua_types::{ByteString, UAString, UserNameIdentityToken, UserTokenType};
+use opcua_types::{
+    ByteString, ExtensionObject, StatusCode, UAString, UserNameIdentityToken, UserTokenType,
+};
 
 use crate::{
-    self as crypto, decrypt_user_identity_token_password, make_user_name_identity_token, random,
-    tests::*, SecurityPolicy,
+    self as crypto, decrypt_issued_identity_token, decrypt_user_identity_token_password,
+    decrypt_user_identity_token_password_with_policy, ecdh_key_from_response_header,
+    ecdh_key_request_header, ecdh_key_response_header, is_encrypted_secret,
+    make_issued_identity_token, make_user_name_identity_token,
+    make_user_name_identity_token_with_options, random, requested_ecdh_policy,
+    rsa_encrypted_secret_decrypt, rsa_encrypted_secret_encrypt, tests::*, EccSecretKeys,
+    EphemeralKey, SecretOptions, SecurityPolicy,
 };
 
 #[test]
@@ -187,3 +194,312 @@ fn user_name_identity_token_encrypted() {
     let password1 = decrypt_user_identity_token_password(&token, nonce.as_ref(), &pkey).unwrap();
     assert_eq!(password, password1);
 }
+
+#[test]
+fn issued_identity_token_encrypted() {
+    let token_data = b"header.payload.signature";
+    let nonce = random::byte_string(32);
+    let (cert, pkey) = make_test_cert_2048();
+    let cert = Some(cert);
+    let user_token_policy = opcua_types::UserTokenPolicy {
+        policy_id: UAString::from("issued"),
+        token_type: UserTokenType::IssuedToken,
+        ..Default::default()
+    };
+
+    for rsa_encrypted_secret in [false, true] {
+        let options = SecretOptions {
+            rsa_encrypted_secret,
+            ecc: None,
+        };
+        let token = make_issued_identity_token(
+            SecurityPolicy::Basic256Sha256,
+            &user_token_policy,
+            nonce.as_ref(),
+            &cert,
+            token_data,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(token.policy_id.as_ref(), "issued");
+        assert_ne!(token.token_data.as_ref(), token_data);
+        assert_eq!(token.encryption_algorithm.is_null(), rsa_encrypted_secret);
+        let token_data1 = decrypt_issued_identity_token(
+            &token,
+            nonce.as_ref(),
+            &pkey,
+            SecurityPolicy::Basic256Sha256,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(token_data1.as_ref(), token_data);
+    }
+
+    // Without a security policy the token is sent as is.
+    let token = make_issued_identity_token(
+        SecurityPolicy::None,
+        &user_token_policy,
+        nonce.as_ref(),
+        &None,
+        token_data,
+        &SecretOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(token.token_data.as_ref(), token_data);
+    let token_data1 = decrypt_issued_identity_token(
+        &token,
+        nonce.as_ref(),
+        &pkey,
+        SecurityPolicy::None,
+        None,
+        None,
+    )
+    .unwrap();
+    assert_eq!(token_data1.as_ref(), token_data);
+}
+
+#[test]
+fn user_name_identity_token_rsa_encrypted_secret() {
+    let password = String::from("abcdef123456");
+    let nonce = random::byte_string(32);
+    let (cert, pkey) = make_test_cert_2048();
+    let cert = Some(cert);
+    let user_token_policy = opcua_types::UserTokenPolicy {
+        policy_id: UAString::from("x"),
+        token_type: UserTokenType::UserName,
+        ..Default::default()
+    };
+    let options = SecretOptions {
+        rsa_encrypted_secret: true,
+        ecc: None,
+    };
+
+    for security_policy in [
+        SecurityPolicy::Basic128Rsa15,
+        SecurityPolicy::Basic256,
+        SecurityPolicy::Basic256Sha256,
+        SecurityPolicy::Aes128Sha256RsaOaep,
+        SecurityPolicy::Aes256Sha256RsaPss,
+    ] {
+        let token = make_user_name_identity_token_with_options(
+            security_policy,
+            &user_token_policy,
+            nonce.as_ref(),
+            &cert,
+            "user1",
+            &password,
+            &options,
+        )
+        .unwrap();
+        assert!(token.encryption_algorithm.is_null());
+        assert!(is_encrypted_secret(&token.password));
+        let password1 = decrypt_user_identity_token_password_with_policy(
+            &token,
+            nonce.as_ref(),
+            &pkey,
+            security_policy,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(password, password1);
+
+        // The secret must use the expected security policy and contain the server nonce.
+        let other_policy = if security_policy == SecurityPolicy::Basic256Sha256 {
+            SecurityPolicy::Aes128Sha256RsaOaep
+        } else {
+            SecurityPolicy::Basic256Sha256
+        };
+        decrypt_user_identity_token_password_with_policy(
+            &token,
+            nonce.as_ref(),
+            &pkey,
+            other_policy,
+            None,
+            None,
+        )
+        .unwrap_err();
+        let other_nonce = random::byte_string(32);
+        decrypt_user_identity_token_password_with_policy(
+            &token,
+            other_nonce.as_ref(),
+            &pkey,
+            security_policy,
+            None,
+            None,
+        )
+        .unwrap_err();
+    }
+}
+
+#[test]
+fn rsa_encrypted_secret_tampered() {
+    let nonce = random::byte_string(32);
+    let (cert, pkey) = make_test_cert_2048();
+    let security_policy = SecurityPolicy::Basic256Sha256;
+    let secret =
+        rsa_encrypted_secret_encrypt(security_policy, b"secret", nonce.as_ref(), &cert).unwrap();
+    let decrypted =
+        rsa_encrypted_secret_decrypt(&secret, nonce.as_ref(), &pkey, security_policy).unwrap();
+    assert_eq!(decrypted.as_ref(), b"secret");
+
+    // Changing any byte after the header must break the signature
+    let mut data = secret.value.clone().unwrap();
+    let last = data.len() - 40;
+    data[last] ^= 1;
+    let tampered = ByteString::from(data);
+    assert_eq!(
+        rsa_encrypted_secret_decrypt(&tampered, nonce.as_ref(), &pkey, security_policy)
+            .unwrap_err()
+            .status(),
+        StatusCode::BadSecurityChecksFailed
+    );
+
+    // Truncated secrets are rejected
+    let mut data = secret.value.unwrap();
+    data.truncate(data.len() - 1);
+    let truncated = ByteString::from(data);
+    rsa_encrypted_secret_decrypt(&truncated, nonce.as_ref(), &pkey, security_policy).unwrap_err();
+}
+
+fn ecc_encrypted_secret(security_policy: SecurityPolicy, key_type: KeyType) {
+    let password = String::from("abcdef123456");
+    let nonce = random::byte_string(32);
+    let (client_cert, client_pkey) = make_test_cert(key_type, 0);
+    let (server_cert, server_pkey) = make_test_cert(key_type, 0);
+    let user_token_policy = opcua_types::UserTokenPolicy {
+        policy_id: UAString::from("x"),
+        token_type: UserTokenType::UserName,
+        ..Default::default()
+    };
+
+    // Without an ephemeral key from the server, the password cannot be encrypted.
+    let err = make_user_name_identity_token_with_options(
+        security_policy,
+        &user_token_policy,
+        nonce.as_ref(),
+        &Some(server_cert.clone()),
+        "user1",
+        &password,
+        &SecretOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err, StatusCode::BadSecurityPolicyRejected);
+
+    // The client asks for an ephemeral key, and the server returns a signed one.
+    let request_header = ecdh_key_request_header(security_policy);
+    assert_eq!(
+        requested_ecdh_policy(&request_header),
+        Some(security_policy)
+    );
+    let server_key = EphemeralKey::new(security_policy).unwrap();
+    let response_header =
+        ecdh_key_response_header(security_policy, &server_key, &server_pkey).unwrap();
+    let receiver_key =
+        ecdh_key_from_response_header(&response_header, security_policy, &server_cert)
+            .unwrap()
+            .unwrap();
+    // The signature must be made by the server certificate.
+    ecdh_key_from_response_header(&response_header, security_policy, &client_cert).unwrap_err();
+
+    let options = SecretOptions {
+        rsa_encrypted_secret: false,
+        ecc: Some(EccSecretKeys {
+            certificate: &client_cert,
+            private_key: &client_pkey,
+            receiver_key: receiver_key.as_ref(),
+        }),
+    };
+    let token = make_user_name_identity_token_with_options(
+        security_policy,
+        &user_token_policy,
+        nonce.as_ref(),
+        &Some(server_cert),
+        "user1",
+        &password,
+        &options,
+    )
+    .unwrap();
+    assert!(token.encryption_algorithm.is_null());
+    assert!(is_encrypted_secret(&token.password));
+    let password1 = decrypt_user_identity_token_password_with_policy(
+        &token,
+        nonce.as_ref(),
+        &server_pkey,
+        security_policy,
+        Some(&server_key),
+        Some(&client_cert),
+    )
+    .unwrap();
+    assert_eq!(password, password1);
+
+    // Decrypting needs the ephemeral key sent to the client.
+    decrypt_user_identity_token_password_with_policy(
+        &token,
+        nonce.as_ref(),
+        &server_pkey,
+        security_policy,
+        None,
+        Some(&client_cert),
+    )
+    .unwrap_err();
+    let other_key = EphemeralKey::new(security_policy).unwrap();
+    decrypt_user_identity_token_password_with_policy(
+        &token,
+        nonce.as_ref(),
+        &server_pkey,
+        security_policy,
+        Some(&other_key),
+        Some(&client_cert),
+    )
+    .unwrap_err();
+
+    // The secret must be signed by the certificate of the client's secure channel.
+    decrypt_user_identity_token_password_with_policy(
+        &token,
+        nonce.as_ref(),
+        &server_pkey,
+        security_policy,
+        Some(&server_key),
+        None,
+    )
+    .unwrap_err();
+    let (other_cert, _) = make_test_cert(key_type, 0);
+    decrypt_user_identity_token_password_with_policy(
+        &token,
+        nonce.as_ref(),
+        &server_pkey,
+        security_policy,
+        Some(&server_key),
+        Some(&other_cert),
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn user_name_identity_token_ecc_encrypted_secret_nist_p256() {
+    ecc_encrypted_secret(SecurityPolicy::EccNistP256, KeyType::NistP256);
+}
+
+#[test]
+fn user_name_identity_token_ecc_encrypted_secret_nist_p384() {
+    ecc_encrypted_secret(SecurityPolicy::EccNistP384, KeyType::NistP384);
+}
+
+#[test]
+fn user_name_identity_token_ecc_encrypted_secret_curve25519() {
+    ecc_encrypted_secret(SecurityPolicy::EccCurve25519, KeyType::Curve25519);
+}
+
+#[test]
+fn user_name_identity_token_ecc_encrypted_secret_brainpool_p384r1() {
+    ecc_encrypted_secret(SecurityPolicy::EccBrainpoolP384r1, KeyType::BrainpoolP384r1);
+}
+
+#[test]
+fn ecdh_policy_must_be_ecc() {
+    let header = ecdh_key_request_header(SecurityPolicy::Basic256Sha256);
+    assert_eq!(requested_ecdh_policy(&header), None);
+    assert_eq!(requested_ecdh_policy(&ExtensionObject::null()), None);
+}
@@ -1,10 +1,11 @@
 use tempdir::TempDir;
 
-use crate::CertificateStore;
+use crate::{CertificateStore, FileSystemCertificateStoreBackend};
 
 use crate::{
-    pkey::PrivateKey,
-    x509::{X509Data, X509},
+    crl::X509Crl,
+    pkey::{KeyType, PrivateKey, PrivateKeyValue},
+    x509::{AlternateNames, X509Data, X509},
 };
 
 const APPLICATION_URI: &str = "urn:testapplication";
@@ -13,12 +14,19 @@ const APPLICATION_HOSTNAME: &str = "testhost";
 fn make_certificate_store() -> (TempDir, CertificateStore) {
     let tmp_dir = TempDir::new("pki").unwrap();
     let cert_store = CertificateStore::new(tmp_dir.path());
-    assert!(cert_store.ensure_pki_path().is_ok());
+    assert!(cert_store.backend().initialize().is_ok());
     (tmp_dir, cert_store)
 }
 
-fn make_test_cert(key_size: u32) -> (X509, PrivateKey) {
+/// Get the filesystem backend of a certificate store made by `make_certificate_store`,
+/// to find the paths used by the store.
+fn pki(tmp_dir: &TempDir) -> FileSystemCertificateStoreBackend {
+    FileSystemCertificateStoreBackend::new(tmp_dir.path())
+}
+
+fn make_test_cert(key_type: KeyType, key_size: u32) -> (X509, PrivateKey) {
     let args = X509Data {
+        key_type,
         key_size,
         common_name: "x".to_string(),
         organization: "x.org".to_string(),
@@ -40,11 +48,159 @@ fn make_test_cert(key_size: u32) -> (X509, PrivateKey) {
 }
 
 fn make_test_cert_1024() -> (X509, PrivateKey) {
-    make_test_cert(1024)
+    make_test_cert(KeyType::Rsa, 1024)
 }
 
 fn make_test_cert_2048() -> (X509, PrivateKey) {
-    make_test_cert(2048)
+    make_test_cert(KeyType::Rsa, 2048)
+}
+
+fn make_test_cert_nist_p256() -> (X509, PrivateKey) {
+    make_test_cert(KeyType::NistP256, 0)
+}
+
+fn make_test_cert_nist_p384() -> (X509, PrivateKey) {
+    make_test_cert(KeyType::NistP384, 0)
+}
+
+fn make_test_cert_curve25519() -> (X509, PrivateKey) {
+    make_test_cert(KeyType::Curve25519, 0)
+}
+
+fn make_test_cert_brainpool_p256r1() -> (X509, PrivateKey) {
+    make_test_cert(KeyType::BrainpoolP256r1, 0)
+}
+
+fn make_test_cert_brainpool_p384r1() -> (X509, PrivateKey) {
+    make_test_cert(KeyType::BrainpoolP384r1, 0)
+}
+
+/// Make a CA certificate, which is self-signed if `issuer` is `None`.
+fn make_ca_cert(common_name: &str, issuer: Option<(&X509, &PrivateKey)>) -> (X509, PrivateKey) {
+    use x509_cert::builder::Profile;
+
+    let key = PrivateKey::new(2048).unwrap();
+    let profile = match issuer {
+        Some((issuer, _)) => Profile::SubCA {
+            issuer: issuer.subject().clone(),
+            path_len_constraint: None,
+        },
+        None => Profile::Root,
+    };
+    let cert = build_cert(
+        common_name,
+        profile,
+        &key,
+        issuer.map(|i| i.1).unwrap_or(&key),
+    );
+    (cert, key)
+}
+
+/// Make an application instance certificate issued by a CA.
+fn make_issued_cert(issuer: &X509, issuer_key: &PrivateKey) -> (X509, PrivateKey) {
+    use x509_cert::builder::Profile;
+
+    let key = PrivateKey::new(2048).unwrap();
+    let profile = Profile::Leaf {
+        issuer: issuer.subject().clone(),
+        enable_key_agreement: false,
+        enable_key_encipherment: true,
+        include_subject_key_identifier: true,
+    };
+    let cert = build_cert("x", profile, &key, issuer_key);
+    (cert, key)
+}
+
+fn build_cert(
+    common_name: &str,
+    profile: x509_cert::builder::Profile,
+    key: &PrivateKey,
+    issuer_key: &PrivateKey,
+) -> X509 {
+    use std::{str::FromStr, time::Duration};
+    use x509_cert::{
+        builder::{Builder, CertificateBuilder},
+        name::Name,
+        serial_number::SerialNumber,
+        time::Validity,
+    };
+
+    let signing_key = rsa_signing_key(issuer_key);
+    let mut builder = CertificateBuilder::new(
+        profile,
+        SerialNumber::from(rand::random::<u32>()),
+        Validity::from_now(Duration::from_secs(86400)).unwrap(),
+        Name::from_str(&format!("CN={common_name},O=x.org")).unwrap(),
+        key.public_key_to_info().unwrap(),
+        &signing_key,
+    )
+    .unwrap();
+    let mut alt_host_names = AlternateNames::new();
+    alt_host_names.add_uri(APPLICATION_URI);
+    alt_host_names.add_dns(APPLICATION_HOSTNAME);
+    builder.add_extension(&alt_host_names.names).unwrap();
+    let cert = builder.build::<rsa::pkcs1v15::Signature>().unwrap();
+    X509::from_der(&x509_cert::der::Encode::to_der(&cert).unwrap()).unwrap()
+}
+
+/// Make a CRL issued by a CA, revoking the `revoked` certificates. The next CRL
+/// is due in a day.
+fn make_crl(issuer: &X509, issuer_key: &PrivateKey, revoked: &[&X509]) -> X509Crl {
+    let next_update = std::time::SystemTime::now() + std::time::Duration::from_secs(86400);
+    make_crl_with_next_update(issuer, issuer_key, revoked, next_update)
+}
+
+/// Make a CRL issued by a CA a day before `next_update`, revoking the `revoked` certificates.
+fn make_crl_with_next_update(
+    issuer: &X509,
+    issuer_key: &PrivateKey,
+    revoked: &[&X509],
+    next_update: std::time::SystemTime,
+) -> X509Crl {
+    use rsa::signature::{SignatureEncoding, Signer};
+    use std::time::Duration;
+    use x509_cert::{
+        crl::{CertificateList, RevokedCert, TbsCertList},
+        der::{asn1::BitString, Encode},
+        spki::DynSignatureAlgorithmIdentifier,
+        time::Time,
+        Version,
+    };
+
+    let signing_key = rsa_signing_key(issuer_key);
+    let now = Time::try_from(next_update - Duration::from_secs(86400)).unwrap();
+    let tbs_cert_list = TbsCertList {
+        version: Version::V2,
+        signature: signing_key.signature_algorithm_identifier().unwrap(),
+        issuer: issuer.subject().clone(),
+        this_update: now,
+        next_update: Some(Time::try_from(next_update).unwrap()),
+        revoked_certificates: Some(
+            revoked
+                .iter()
+                .map(|cert| RevokedCert {
+                    serial_number: cert.serial_number().clone(),
+                    revocation_date: now,
+                    crl_entry_extensions: None,
+                })
+                .collect(),
+        ),
+        crl_extensions: None,
+    };
+    let signature = signing_key.sign(&tbs_cert_list.to_der().unwrap());
+    let crl = CertificateList {
+        signature_algorithm: tbs_cert_list.signature.clone(),
+        tbs_cert_list,
+        signature: BitString::from_bytes(&signature.to_bytes()).unwrap(),
+    };
+    X509Crl::from_der(&crl.to_der().unwrap()).unwrap()
+}
+
+fn rsa_signing_key(key: &PrivateKey) -> rsa::pkcs1v15::SigningKey<sha2::Sha256> {
+    let PrivateKeyValue::Rsa(key) = &key.value else {
+        panic!("Not an RSA key");
+    };
+    rsa::pkcs1v15::SigningKey::new(key.clone())
 }
 
 mod authentication;
@@ -12,6 +12,8 @@ fn is_deprecated() {
     assert!(!SecurityPolicy::Basic256Sha256.is_deprecated());
     assert!(!SecurityPolicy::Aes128Sha256RsaOaep.is_deprecated());
     assert!(!SecurityPolicy::Aes256Sha256RsaPss.is_deprecated());
+    assert!(!SecurityPolicy::EccNistP256.is_deprecated());
+    assert!(!SecurityPolicy::EccNistP384.is_deprecated());
 }
 
 #[test]
@@ -93,6 +95,51 @@ fn from_str() {
             .unwrap(),
         SecurityPolicy::Aes256Sha256RsaPss
     );
+    assert_eq!(
+        SecurityPolicy::from_str("ECC-nistP256").unwrap(),
+        SecurityPolicy::EccNistP256
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("http://opcfoundation.org/UA/SecurityPolicy#ECC_nistP256")
+            .unwrap(),
+        SecurityPolicy::EccNistP256
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("ECC-nistP384").unwrap(),
+        SecurityPolicy::EccNistP384
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("http://opcfoundation.org/UA/SecurityPolicy#ECC_nistP384")
+            .unwrap(),
+        SecurityPolicy::EccNistP384
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("ECC-curve25519").unwrap(),
+        SecurityPolicy::EccCurve25519
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("http://opcfoundation.org/UA/SecurityPolicy#ECC_curve25519")
+            .unwrap(),
+        SecurityPolicy::EccCurve25519
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("ECC-brainpoolP256r1").unwrap(),
+        SecurityPolicy::EccBrainpoolP256r1
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("http://opcfoundation.org/UA/SecurityPolicy#ECC_brainpoolP256r1")
+            .unwrap(),
+        SecurityPolicy::EccBrainpoolP256r1
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("ECC-brainpoolP384r1").unwrap(),
+        SecurityPolicy::EccBrainpoolP384r1
+    );
+    assert_eq!(
+        SecurityPolicy::from_str("http://opcfoundation.org/UA/SecurityPolicy#ECC_brainpoolP384r1")
+            .unwrap(),
+        SecurityPolicy::EccBrainpoolP384r1
+    );
 }
 
 #[test]
@@ -121,6 +168,26 @@ fn to_uri() {
         SecurityPolicy::Aes256Sha256RsaPss.to_uri(),
         "http://opcfoundation.org/UA/SecurityPolicy#Aes256_Sha256_RsaPss"
     );
+    assert_eq!(
+        SecurityPolicy::EccNistP256.to_uri(),
+        "http://opcfoundation.org/UA/SecurityPolicy#ECC_nistP256"
+    );
+    assert_eq!(
+        SecurityPolicy::EccNistP384.to_uri(),
+        "http://opcfoundation.org/UA/SecurityPolicy#ECC_nistP384"
+    );
+    assert_eq!(
+        SecurityPolicy::EccCurve25519.to_uri(),
+        "http://opcfoundation.org/UA/SecurityPolicy#ECC_curve25519"
+    );
+    assert_eq!(
+        SecurityPolicy::EccBrainpoolP256r1.to_uri(),
+        "http://opcfoundation.org/UA/SecurityPolicy#ECC_brainpoolP256r1"
+    );
+    assert_eq!(
+        SecurityPolicy::EccBrainpoolP384r1.to_uri(),
+        "http://opcfoundation.org/UA/SecurityPolicy#ECC_brainpoolP384r1"
+    );
 }
 
 #[test]
@@ -149,4 +216,21 @@ fn is_valid_keylength() {
     assert!(SecurityPolicy::Aes256Sha256RsaPss.is_valid_keylength(4096));
     assert!(!SecurityPolicy::Aes256Sha256RsaPss.is_valid_keylength(1024));
     assert!(!SecurityPolicy::Aes256Sha256RsaPss.is_valid_keylength(8192));
+    assert!(SecurityPolicy::EccNistP256.is_valid_keylength(256));
+    assert!(!SecurityPolicy::EccNistP256.is_valid_keylength(384));
+    assert!(SecurityPolicy::EccNistP384.is_valid_keylength(384));
+    assert!(!SecurityPolicy::EccNistP384.is_valid_keylength(2048));
+    assert!(SecurityPolicy::EccCurve25519.is_valid_keylength(256));
+    assert!(!SecurityPolicy::EccCurve25519.is_valid_keylength(384));
+    assert!(SecurityPolicy::EccBrainpoolP256r1.is_valid_keylength(256));
+    assert!(!SecurityPolicy::EccBrainpoolP256r1.is_valid_keylength(384));
+    assert!(SecurityPolicy::EccBrainpoolP384r1.is_valid_keylength(384));
+    assert!(!SecurityPolicy::EccBrainpoolP384r1.is_valid_keylength(256));
+}
+
+#[test]
+fn is_aead() {
+    assert!(SecurityPolicy::EccCurve25519.is_aead());
+    assert!(!SecurityPolicy::EccNistP256.is_aead());
+    assert!(!SecurityPolicy::Basic256Sha256.is_aead());
 }
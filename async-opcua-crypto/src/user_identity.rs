@@ -2,10 +2,17 @@
 // SPDX-License-Identifier: MPL-2.0
 // Copyright (C) 2017-2024 Adam Lock
 
-//! Functions related to encrypting / decrypting passwords in a UserNameIdentityToken.
+//! Functions related to encrypting / decrypting passwords in a UserNameIdentityToken, and the
+//! token data of an IssuedIdentityToken.
 //!
 //! The code here determines how or if to encrypt the password depending on the security policy
-//! and user token policy.
+//! and user token policy. Issued tokens are encrypted the same way as passwords.
+//!
+//! Passwords are encrypted in one of two formats, described in OPC UA Part 4 7.41.2. The legacy
+//! format is only defined for RSA security policies. The newer `EncryptedSecret` format is
+//! `RsaEncryptedSecret` for RSA security policies, and `EccEncryptedSecret` for ECC security
+//! policies. The latter requires an ephemeral key from the server, which is exchanged
+//! in the `AdditionalHeader` of the CreateSession and ActivateSession services.
 
 use std::io::{Cursor, Write};
 use std::str::FromStr;
@@ -13,15 +20,52 @@ use std::str::FromStr;
 use log::{error, warn};
 use opcua_types::Error;
 use opcua_types::{
-    encoding::{read_u32, write_u32},
+    encoding::{read_i32, read_u16, read_u32, read_u8, write_i32, write_u16, write_u32, write_u8},
     status_code::StatusCode,
-    ByteString, UAString,
+    AdditionalParametersType, BinaryDecodable, BinaryEncodable, ByteString, ContextOwned,
+    DataTypeId, DateTime, EphemeralKeyType, ExtensionObject, IssuedIdentityToken, KeyValuePair,
+    NodeId, QualifiedName, UAString, Variant,
     {SignatureData, UserNameIdentityToken, UserTokenPolicy, X509IdentityToken},
 };
 
-use super::{KeySize, PrivateKey, RsaPadding, SecurityPolicy, X509};
+use super::{
+    hash, random, AesKey, EphemeralKey, KeySize, KeyType, PrivateKey, RsaPadding, SecurityPolicy,
+    X509,
+};
+
+/// Name of the additional header parameter used to request an ephemeral key from the server.
+const ECDH_POLICY_URI: &str = "ECDHPolicyUri";
+/// Name of the additional header parameter containing the ephemeral key of the server.
+const ECDH_KEY: &str = "ECDHKey";
+
+/// Keys used to create an `EccEncryptedSecret`, which is how passwords are encrypted
+/// with ECC security policies.
+#[derive(Clone, Copy)]
+pub struct EccSecretKeys<'a> {
+    /// Application instance certificate of the sender, included in the secret.
+    pub certificate: &'a X509,
+    /// Private key of the sender, used to sign the secret.
+    pub private_key: &'a PrivateKey,
+    /// Ephemeral public key of the receiver, see [`ecdh_key_from_response_header`].
+    pub receiver_key: &'a [u8],
+}
+
+/// Options for encrypting the password of a user identity token.
+#[derive(Clone, Copy, Default)]
+pub struct SecretOptions<'a> {
+    /// Encrypt passwords as an `RsaEncryptedSecret` with RSA security policies, instead of
+    /// using the legacy format. Servers implementing earlier versions of the standard only
+    /// support the legacy format.
+    pub rsa_encrypted_secret: bool,
+    /// Keys used to encrypt passwords with ECC security policies, which always use the
+    /// `EccEncryptedSecret` format.
+    pub ecc: Option<EccSecretKeys<'a>>,
+}
 
 /// Create a filled in UserNameIdentityToken by using the supplied channel security policy, user token policy, nonce, cert, user name and password.
+///
+/// Passwords are encrypted in the legacy format, see [`make_user_name_identity_token_with_options`]
+/// to use the `EncryptedSecret` formats.
 pub fn make_user_name_identity_token(
     channel_security_policy: SecurityPolicy,
     user_token_policy: &UserTokenPolicy,
@@ -30,6 +74,80 @@ pub fn make_user_name_identity_token(
     user: &str,
     pass: &str,
 ) -> Result<UserNameIdentityToken, StatusCode> {
+    make_user_name_identity_token_with_options(
+        channel_security_policy,
+        user_token_policy,
+        nonce,
+        cert,
+        user,
+        pass,
+        &SecretOptions::default(),
+    )
+}
+
+/// Create a filled in UserNameIdentityToken like [`make_user_name_identity_token`], encrypting
+/// the password as described by `options`.
+pub fn make_user_name_identity_token_with_options(
+    channel_security_policy: SecurityPolicy,
+    user_token_policy: &UserTokenPolicy,
+    nonce: &[u8],
+    cert: &Option<X509>,
+    user: &str,
+    pass: &str,
+    options: &SecretOptions<'_>,
+) -> Result<UserNameIdentityToken, StatusCode> {
+    let (password, encryption_algorithm) = encrypt_secret(
+        channel_security_policy,
+        user_token_policy,
+        nonce,
+        cert,
+        pass.as_bytes(),
+        options,
+    )?;
+    Ok(UserNameIdentityToken {
+        policy_id: user_token_policy.policy_id.clone(),
+        user_name: UAString::from(user),
+        password,
+        encryption_algorithm,
+    })
+}
+
+/// Create a filled in IssuedIdentityToken by using the supplied channel security policy, user
+/// token policy, nonce and cert. The token data, for example a JSON Web Token, is encrypted
+/// the same way as the password of a UserNameIdentityToken.
+pub fn make_issued_identity_token(
+    channel_security_policy: SecurityPolicy,
+    user_token_policy: &UserTokenPolicy,
+    nonce: &[u8],
+    cert: &Option<X509>,
+    token_data: &[u8],
+    options: &SecretOptions<'_>,
+) -> Result<IssuedIdentityToken, StatusCode> {
+    let (token_data, encryption_algorithm) = encrypt_secret(
+        channel_security_policy,
+        user_token_policy,
+        nonce,
+        cert,
+        token_data,
+        options,
+    )?;
+    Ok(IssuedIdentityToken {
+        policy_id: user_token_policy.policy_id.clone(),
+        token_data,
+        encryption_algorithm,
+    })
+}
+
+/// Encrypt the secret of a user identity token, returning the encrypted secret and
+/// the encryption algorithm, which is only set for the legacy format.
+fn encrypt_secret(
+    channel_security_policy: SecurityPolicy,
+    user_token_policy: &UserTokenPolicy,
+    nonce: &[u8],
+    cert: &Option<X509>,
+    secret: &[u8],
+    options: &SecretOptions<'_>,
+) -> Result<(ByteString, UAString), StatusCode> {
     // This is a condensed version of Table 187 Opc Part 4 that details the EncryptionAlgorithm
     // selection.
     //
@@ -55,70 +173,185 @@ pub fn make_user_name_identity_token(
 
     // Now it should be a matter of using the policy (or lack thereof) to encrypt the password
     // using the secure channel's cert and nonce.
-    let (password, encryption_algorithm) = match security_policy {
+    let encrypted = match security_policy {
         SecurityPolicy::None => {
             // Plain text
             if channel_security_policy == SecurityPolicy::None {
-                warn!("A user identity's password is being sent over the network in plain text. This could be a serious security issue");
+                warn!("A user identity's secret is being sent over the network in plain text. This could be a serious security issue");
             }
-            (ByteString::from(pass.as_bytes()), UAString::null())
+            (ByteString::from(secret), UAString::null())
         }
         SecurityPolicy::Unknown => {
             // This should only happen if channel_security_policy were Unknown when it shouldn't be
             panic!("Don't know how to make the token for this server");
         }
+        security_policy if security_policy.is_ecc() => {
+            // ECC policies can only encrypt passwords as an EccEncryptedSecret.
+            let Some(keys) = &options.ecc else {
+                error!("Cannot encrypt a user identity's secret with security policy {security_policy}, the server did not provide an ephemeral key");
+                return Err(StatusCode::BadSecurityPolicyRejected);
+            };
+            let secret = ecc_encrypted_secret_encrypt(security_policy, secret, nonce, keys)?;
+            (secret, UAString::null())
+        }
         security_policy => {
-            // Create a password which is encrypted using the secure channel info and the user token policy for the endpoint
-            let password = legacy_password_encrypt(
-                pass,
-                nonce,
-                cert.as_ref().unwrap(),
-                security_policy.asymmetric_encryption_padding(),
-            )?;
-            let encryption_algorithm =
-                UAString::from(security_policy.asymmetric_encryption_algorithm());
-            (password, encryption_algorithm)
+            let Some(cert) = cert else {
+                error!("Cannot encrypt a user identity's secret without a server certificate");
+                return Err(StatusCode::BadCertificateInvalid);
+            };
+            if options.rsa_encrypted_secret {
+                // The EncryptedSecret format is identified by its type ID, so the encryption
+                // algorithm is left empty.
+                let secret = rsa_encrypted_secret_encrypt(security_policy, secret, nonce, cert)?;
+                (secret, UAString::null())
+            } else {
+                // Create a secret which is encrypted using the secure channel info and the user token policy for the endpoint
+                let secret = legacy_secret_encrypt(
+                    secret,
+                    nonce,
+                    cert,
+                    security_policy.asymmetric_encryption_padding(),
+                )?;
+                let encryption_algorithm =
+                    UAString::from(security_policy.asymmetric_encryption_algorithm());
+                (secret, encryption_algorithm)
+            }
         }
     };
-
-    Ok(UserNameIdentityToken {
-        policy_id: user_token_policy.policy_id.clone(),
-        user_name: UAString::from(user),
-        password,
-        encryption_algorithm,
-    })
+    Ok(encrypted)
 }
 
-/// Decrypt the password inside of a user identity token.
+/// Decrypt the password inside of a user identity token, which is either plain text or
+/// encrypted in the legacy RSA format. Use [`decrypt_user_identity_token_password_with_policy`]
+/// to also accept passwords in the `EncryptedSecret` formats.
 pub fn decrypt_user_identity_token_password(
     user_identity_token: &UserNameIdentityToken,
     server_nonce: &[u8],
     server_key: &PrivateKey,
 ) -> Result<String, Error> {
-    if user_identity_token.encryption_algorithm.is_empty() {
+    decrypt_user_identity_token_password_with_policy(
+        user_identity_token,
+        server_nonce,
+        server_key,
+        SecurityPolicy::None,
+        None,
+        None,
+    )
+}
+
+/// Decrypt the password inside of a user identity token, in plain text, the legacy format
+/// or the `EncryptedSecret` formats.
+///
+/// `security_policy` is the security policy of the user token policy the token was created for.
+/// Passwords in the `EncryptedSecret` format must use this policy. `ephemeral_key` is the
+/// ephemeral key last sent to the client, and `client_certificate` the certificate of the
+/// client's secure channel. Both are needed to decrypt an `EccEncryptedSecret`.
+pub fn decrypt_user_identity_token_password_with_policy(
+    user_identity_token: &UserNameIdentityToken,
+    server_nonce: &[u8],
+    server_key: &PrivateKey,
+    security_policy: SecurityPolicy,
+    ephemeral_key: Option<&EphemeralKey>,
+    client_certificate: Option<&X509>,
+) -> Result<String, Error> {
+    if user_identity_token.encryption_algorithm.is_empty()
+        && !is_encrypted_secret(&user_identity_token.password)
+    {
         // Assumed to be UTF-8 plain text
-        user_identity_token.plaintext_password()
+        return user_identity_token.plaintext_password();
+    }
+    let password = decrypt_secret(
+        &user_identity_token.password,
+        &user_identity_token.encryption_algorithm,
+        server_nonce,
+        server_key,
+        security_policy,
+        ephemeral_key,
+        client_certificate,
+    )?;
+    String::from_utf8(password.value.unwrap_or_default()).map_err(Error::decoding)
+}
+
+/// Decrypt the token data inside of an issued identity token. Token data that is not
+/// encrypted is returned as is.
+///
+/// `security_policy`, `ephemeral_key` and `client_certificate` are used the same way as in
+/// [`decrypt_user_identity_token_password_with_policy`].
+pub fn decrypt_issued_identity_token(
+    issued_identity_token: &IssuedIdentityToken,
+    server_nonce: &[u8],
+    server_key: &PrivateKey,
+    security_policy: SecurityPolicy,
+    ephemeral_key: Option<&EphemeralKey>,
+    client_certificate: Option<&X509>,
+) -> Result<ByteString, Error> {
+    if issued_identity_token.encryption_algorithm.is_empty()
+        && !is_encrypted_secret(&issued_identity_token.token_data)
+    {
+        return Ok(issued_identity_token.token_data.clone());
+    }
+    decrypt_secret(
+        &issued_identity_token.token_data,
+        &issued_identity_token.encryption_algorithm,
+        server_nonce,
+        server_key,
+        security_policy,
+        ephemeral_key,
+        client_certificate,
+    )
+}
+
+/// Decrypt the secret of a user identity token, in the legacy format if `encryption_algorithm`
+/// is set, otherwise in the `EncryptedSecret` format.
+fn decrypt_secret(
+    secret: &ByteString,
+    encryption_algorithm: &UAString,
+    server_nonce: &[u8],
+    server_key: &PrivateKey,
+    security_policy: SecurityPolicy,
+    ephemeral_key: Option<&EphemeralKey>,
+    client_certificate: Option<&X509>,
+) -> Result<ByteString, Error> {
+    if encryption_algorithm.is_empty() {
+        if security_policy.is_ecc() {
+            let Some(ephemeral_key) = ephemeral_key else {
+                return Err(Error::new(
+                    StatusCode::BadIdentityTokenInvalid,
+                    "Identity token rejected, no ephemeral key was sent to the client",
+                ));
+            };
+            let Some(client_certificate) = client_certificate else {
+                return Err(Error::new(
+                    StatusCode::BadIdentityTokenInvalid,
+                    "Identity token rejected, the client has no certificate",
+                ));
+            };
+            ecc_encrypted_secret_decrypt(
+                secret,
+                server_nonce,
+                ephemeral_key,
+                client_certificate,
+                security_policy,
+            )
+        } else {
+            rsa_encrypted_secret_decrypt(secret, server_nonce, server_key, security_policy)
+        }
     } else {
         // Determine the padding from the algorithm.
-        let encryption_algorithm = user_identity_token.encryption_algorithm.as_ref();
+        let encryption_algorithm = encryption_algorithm.as_ref();
         let padding = match encryption_algorithm {
             super::algorithms::ENC_RSA_15 => RsaPadding::Pkcs1,
             super::algorithms::ENC_RSA_OAEP => RsaPadding::OaepSha1,
             super::algorithms::ENC_RSA_OAEP_SHA256 => RsaPadding::OaepSha256,
             r => {
-                error!("decrypt_user_identity_token_password has rejected unsupported user identity encryption algorithm \"{}\"", encryption_algorithm);
+                error!("decrypt_secret has rejected unsupported user identity encryption algorithm \"{}\"", encryption_algorithm);
                 return Err(Error::new(
                     StatusCode::BadIdentityTokenInvalid,
                     format!("Identity token rejected, unsupported encryption algorithm {r}"),
                 ));
             }
         };
-        legacy_password_decrypt(
-            &user_identity_token.password,
-            server_nonce,
-            server_key,
-            padding,
-        )
+        legacy_secret_decrypt(secret, server_nonce, server_key, padding)
     }
 }
 
@@ -130,17 +363,32 @@ pub fn legacy_password_encrypt(
     server_cert: &X509,
     padding: RsaPadding,
 ) -> Result<ByteString, Error> {
-    // Message format is size, password, nonce
-    let plaintext_size = 4 + password.len() + server_nonce.len();
+    legacy_secret_encrypt(password.as_bytes(), server_nonce, server_cert, padding)
+}
+
+fn legacy_secret_encrypt(
+    secret: &[u8],
+    server_nonce: &[u8],
+    server_cert: &X509,
+    padding: RsaPadding,
+) -> Result<ByteString, Error> {
+    // Message format is size, secret, nonce
+    let plaintext_size = 4 + secret.len() + server_nonce.len();
     let mut src = Cursor::new(vec![0u8; plaintext_size]);
 
     // Write the length of the data to be encrypted excluding the length itself)
     write_u32(&mut src, (plaintext_size - 4) as u32)?;
-    src.write(password.as_bytes()).map_err(Error::decoding)?;
+    src.write(secret).map_err(Error::decoding)?;
     src.write(server_nonce).map_err(Error::decoding)?;
 
     // Encrypt the data with the public key from the server's certificate
     let public_key = server_cert.public_key()?;
+    if public_key.key_type() != KeyType::Rsa {
+        return Err(Error::new(
+            StatusCode::BadCertificateInvalid,
+            "Passwords can only be encrypted with an RSA certificate",
+        ));
+    }
 
     let cipher_size = public_key.calculate_cipher_text_size(plaintext_size, padding);
     let mut dst = vec![0u8; cipher_size];
@@ -161,6 +409,16 @@ pub fn legacy_password_decrypt(
     server_key: &PrivateKey,
     padding: RsaPadding,
 ) -> Result<String, Error> {
+    let password = legacy_secret_decrypt(secret, server_nonce, server_key, padding)?;
+    String::from_utf8(password.value.unwrap_or_default()).map_err(Error::decoding)
+}
+
+fn legacy_secret_decrypt(
+    secret: &ByteString,
+    server_nonce: &[u8],
+    server_key: &PrivateKey,
+    padding: RsaPadding,
+) -> Result<ByteString, Error> {
     if secret.is_null() {
         Err(Error::decoding("Missing server secret"))
     } else {
@@ -183,14 +441,524 @@ pub fn legacy_password_decrypt(
             if nonce != server_nonce {
                 Err(Error::decoding("Invalid nonce"))
             } else {
-                let password = &dst[4..nonce_begin];
-                let password = String::from_utf8(password.to_vec()).map_err(Error::decoding)?;
-                Ok(password)
+                Ok(ByteString::from(&dst[4..nonce_begin]))
             }
         }
     }
 }
 
+/// Check if a user identity token secret is in the `EncryptedSecret` format, i.e. if it starts
+/// with the type ID of `RsaEncryptedSecret` or `EccEncryptedSecret`.
+pub fn is_encrypted_secret(secret: &ByteString) -> bool {
+    let Some(data) = secret.value.as_ref() else {
+        return false;
+    };
+    let ctx = ContextOwned::default();
+    let mut stream = Cursor::new(data);
+    let Ok(type_id) = NodeId::decode(&mut stream, &ctx.context()) else {
+        return false;
+    };
+    (type_id == DataTypeId::RsaEncryptedSecret || type_id == DataTypeId::EccEncryptedSecret)
+        && read_u8(&mut stream).is_ok_and(|mask| mask == 1)
+}
+
+/// Encrypt a secret in the `RsaEncryptedSecret` format, described in OPC UA Part 4 7.41.2.3.
+///
+/// The secret is encrypted with random symmetric keys, which are themselves encrypted with the
+/// public key of the receiver's certificate.
+pub fn rsa_encrypted_secret_encrypt(
+    security_policy: SecurityPolicy,
+    secret: &[u8],
+    nonce: &[u8],
+    receiver_cert: &X509,
+) -> Result<ByteString, Error> {
+    if !is_secret_policy(security_policy) || security_policy.is_ecc() {
+        return Err(Error::new(
+            StatusCode::BadSecurityPolicyRejected,
+            format!("Security policy {security_policy} cannot be used for an RsaEncryptedSecret"),
+        ));
+    }
+    let public_key = receiver_cert.public_key()?;
+    if public_key.key_type() != KeyType::Rsa {
+        return Err(Error::new(
+            StatusCode::BadCertificateInvalid,
+            "An RsaEncryptedSecret can only be encrypted with an RSA certificate",
+        ));
+    }
+
+    let signing_key = random::byte_string(security_policy.derived_signature_key_size());
+    let encrypting_key = random::byte_string(security_policy.encrypting_key_size());
+    let iv = random::byte_string(security_policy.plain_block_size());
+
+    let ctx = ContextOwned::default();
+    let ctx = ctx.context();
+    let mut key_data = Vec::new();
+    signing_key.encode(&mut key_data, &ctx)?;
+    encrypting_key.encode(&mut key_data, &ctx)?;
+    iv.encode(&mut key_data, &ctx)?;
+
+    let padding = security_policy.asymmetric_encryption_padding();
+    let mut encrypted_key_data =
+        vec![0u8; public_key.calculate_cipher_text_size(key_data.len(), padding)];
+    let size = security_policy
+        .asymmetric_encrypt(&public_key, &key_data, &mut encrypted_key_data)
+        .map_err(|e| Error::new(e, "Failed to encrypt the key data of an RsaEncryptedSecret"))?;
+    encrypted_key_data.truncate(size);
+
+    let header = EncryptedSecretHeader {
+        type_id: DataTypeId::RsaEncryptedSecret,
+        security_policy,
+        certificate: ByteString::null(),
+        key_data: encrypted_key_data,
+    };
+    let encrypting_key = AesKey::new(security_policy, encrypting_key.as_ref());
+    header.encode(
+        nonce,
+        secret,
+        &encrypting_key,
+        iv.as_ref(),
+        security_policy.symmetric_signature_size(),
+        |data, signature| {
+            security_policy
+                .symmetric_sign(signing_key.as_ref(), data, signature)
+                .map_err(|e| Error::new(e, "Failed to sign an RsaEncryptedSecret"))
+        },
+    )
+}
+
+/// Decrypt a secret in the `RsaEncryptedSecret` format, using the private key of the receiver.
+/// The secret must be encrypted with `security_policy`, and contain `nonce`.
+pub fn rsa_encrypted_secret_decrypt(
+    secret: &ByteString,
+    nonce: &[u8],
+    private_key: &PrivateKey,
+    security_policy: SecurityPolicy,
+) -> Result<ByteString, Error> {
+    let secret = EncryptedSecret::decode(secret, DataTypeId::RsaEncryptedSecret, security_policy)?;
+
+    let mut key_data = vec![0u8; secret.key_data.len()];
+    let size = security_policy.asymmetric_decrypt(private_key, secret.key_data, &mut key_data)?;
+    let ctx = ContextOwned::default();
+    let ctx = ctx.context();
+    let mut stream = Cursor::new(&key_data[..size]);
+    let signing_key = ByteString::decode(&mut stream, &ctx)?;
+    let encrypting_key = ByteString::decode(&mut stream, &ctx)?;
+    let iv = ByteString::decode(&mut stream, &ctx)?;
+    if signing_key.as_ref().len() != security_policy.derived_signature_key_size()
+        || encrypting_key.as_ref().len() != security_policy.encrypting_key_size()
+        || iv.as_ref().len() != security_policy.plain_block_size()
+    {
+        return Err(secret_error("Invalid key data"));
+    }
+
+    let (data, signature) = secret.signed_data(security_policy.symmetric_signature_size())?;
+    security_policy.symmetric_verify_signature(signing_key.as_ref(), data, signature)?;
+
+    let encrypting_key = AesKey::new(security_policy, encrypting_key.as_ref());
+    secret.decrypt_payload(&encrypting_key, iv.as_ref(), signature.len(), nonce)
+}
+
+/// Encrypt a secret in the `EccEncryptedSecret` format, described in OPC UA Part 4 7.41.2.3.
+///
+/// The secret is encrypted with keys derived from a new ephemeral key and the ephemeral key of
+/// the receiver, and signed with the private key of the sender.
+pub fn ecc_encrypted_secret_encrypt(
+    security_policy: SecurityPolicy,
+    secret: &[u8],
+    nonce: &[u8],
+    keys: &EccSecretKeys<'_>,
+) -> Result<ByteString, Error> {
+    let Some(ephemeral_key) = EphemeralKey::new(security_policy) else {
+        return Err(Error::new(
+            StatusCode::BadSecurityPolicyRejected,
+            format!("Security policy {security_policy} cannot be used for an EccEncryptedSecret"),
+        ));
+    };
+    let sender_key = ephemeral_key.nonce();
+    let shared_secret = ephemeral_key.shared_secret(keys.receiver_key)?;
+    let (encrypting_key, iv) = ecc_secret_keys(
+        security_policy,
+        &shared_secret,
+        &sender_key,
+        keys.receiver_key,
+    );
+
+    let ctx = ContextOwned::default();
+    let ctx = ctx.context();
+    let mut key_data = Vec::new();
+    ByteString::from(sender_key).encode(&mut key_data, &ctx)?;
+    ByteString::from(keys.receiver_key).encode(&mut key_data, &ctx)?;
+
+    let header = EncryptedSecretHeader {
+        type_id: DataTypeId::EccEncryptedSecret,
+        security_policy,
+        certificate: keys.certificate.as_byte_string(),
+        key_data,
+    };
+    header.encode(
+        nonce,
+        secret,
+        &encrypting_key,
+        &iv,
+        keys.private_key.signature_size(),
+        |data, signature| {
+            security_policy
+                .asymmetric_sign(keys.private_key, data, signature)
+                .map(|_| ())
+        },
+    )
+}
+
+/// Decrypt a secret in the `EccEncryptedSecret` format, using the ephemeral key the receiver
+/// sent to the sender. The secret must be encrypted with `security_policy`, and contain `nonce`.
+///
+/// The certificate contained in the secret must be `sender_certificate`, normally the
+/// certificate of the sender's secure channel, and the signature is verified with it.
+pub fn ecc_encrypted_secret_decrypt(
+    secret: &ByteString,
+    nonce: &[u8],
+    ephemeral_key: &EphemeralKey,
+    sender_certificate: &X509,
+    security_policy: SecurityPolicy,
+) -> Result<ByteString, Error> {
+    let secret = EncryptedSecret::decode(secret, DataTypeId::EccEncryptedSecret, security_policy)?;
+
+    let ctx = ContextOwned::default();
+    let ctx = ctx.context();
+    let mut stream = Cursor::new(secret.key_data);
+    let sender_key = ByteString::decode(&mut stream, &ctx)?;
+    let receiver_key = ByteString::decode(&mut stream, &ctx)?;
+    if receiver_key.as_ref() != ephemeral_key.nonce().as_slice() {
+        return Err(secret_error(
+            "Secret was not encrypted for this ephemeral key",
+        ));
+    }
+
+    let certificate = X509::from_byte_string(&secret.certificate)?;
+    if certificate.thumbprint() != sender_certificate.thumbprint() {
+        return Err(secret_error(
+            "Secret was not signed by the sender's certificate",
+        ));
+    }
+    let public_key = certificate.public_key()?;
+    let (data, signature) = secret.signed_data(public_key.signature_size())?;
+    security_policy.asymmetric_verify_signature(&public_key, data, signature, None)?;
+
+    let shared_secret = ephemeral_key.shared_secret(sender_key.as_ref())?;
+    let (encrypting_key, iv) = ecc_secret_keys(
+        security_policy,
+        &shared_secret,
+        sender_key.as_ref(),
+        receiver_key.as_ref(),
+    );
+    secret.decrypt_payload(&encrypting_key, &iv, signature.len(), nonce)
+}
+
+/// Derive the key and initialization vector used to encrypt an `EccEncryptedSecret`. This uses
+/// HKDF like the ECC secure channel keys, with the salt
+///
+/// Salt = L | UTF8("opcua-secret") | SenderPublicKey | ReceiverPublicKey
+///
+/// where L is the length of the derived key material as a little endian UInt16.
+fn ecc_secret_keys(
+    security_policy: SecurityPolicy,
+    shared_secret: &[u8],
+    sender_key: &[u8],
+    receiver_key: &[u8],
+) -> (AesKey, Vec<u8>) {
+    let encrypting_key_length = security_policy.encrypting_key_size();
+    let length = encrypting_key_length + security_policy.initialization_vector_size();
+    let label = b"opcua-secret";
+    let mut salt = Vec::with_capacity(2 + label.len() + sender_key.len() + receiver_key.len());
+    salt.extend_from_slice(&(length as u16).to_le_bytes());
+    salt.extend_from_slice(label);
+    salt.extend_from_slice(sender_key);
+    salt.extend_from_slice(receiver_key);
+    let keys = match security_policy {
+        SecurityPolicy::EccNistP384 | SecurityPolicy::EccBrainpoolP384r1 => {
+            hash::hkdf_sha384(&salt, shared_secret, &salt, length)
+        }
+        _ => hash::hkdf_sha256(&salt, shared_secret, &salt, length),
+    };
+    let (encrypting_key, iv) = keys.split_at(encrypting_key_length);
+    (AesKey::new(security_policy, encrypting_key), iv.to_vec())
+}
+
+/// Check if the security policy can be used to encrypt secrets at all.
+fn is_secret_policy(security_policy: SecurityPolicy) -> bool {
+    !matches!(
+        security_policy,
+        SecurityPolicy::None | SecurityPolicy::Unknown
+    )
+}
+
+fn secret_error(message: &str) -> Error {
+    Error::new(
+        StatusCode::BadIdentityTokenInvalid,
+        format!("Invalid encrypted secret: {message}"),
+    )
+}
+
+/// The fields of an `EncryptedSecret` preceding the encrypted payload.
+///
+/// The full layout is
+///
+/// TypeId | EncodingMask | Length | SecurityPolicyUri | Certificate | SigningTime |
+/// KeyDataLength | KeyData | Nonce | Secret | PayloadPadding | PayloadPaddingSize | Signature
+///
+/// where everything from the Nonce to the PayloadPaddingSize is encrypted, and the signature
+/// covers everything before it, after encryption.
+struct EncryptedSecretHeader {
+    type_id: DataTypeId,
+    security_policy: SecurityPolicy,
+    certificate: ByteString,
+    key_data: Vec<u8>,
+}
+
+impl EncryptedSecretHeader {
+    /// Encode the secret, encrypting the payload with `encrypting_key` and appending a
+    /// signature of `signature_size` bytes produced by `sign`.
+    fn encode(
+        &self,
+        nonce: &[u8],
+        secret: &[u8],
+        encrypting_key: &AesKey,
+        iv: &[u8],
+        signature_size: usize,
+        sign: impl FnOnce(&[u8], &mut [u8]) -> Result<(), Error>,
+    ) -> Result<ByteString, Error> {
+        let ctx = ContextOwned::default();
+        let ctx = ctx.context();
+
+        let Ok(key_data_length) = u16::try_from(self.key_data.len()) else {
+            return Err(Error::encoding("Key data of encrypted secret is too long"));
+        };
+        let mut body = Vec::new();
+        UAString::from(self.security_policy.to_uri()).encode(&mut body, &ctx)?;
+        self.certificate.encode(&mut body, &ctx)?;
+        DateTime::now().encode(&mut body, &ctx)?;
+        write_u16(&mut body, key_data_length)?;
+        body.extend_from_slice(&self.key_data);
+
+        // The payload is padded to a whole number of blocks, each padding byte being the
+        // least significant byte of the padding size. Stream ciphers have a block size of one,
+        // so there is no padding.
+        let mut payload = Vec::new();
+        ByteString::from(nonce).encode(&mut payload, &ctx)?;
+        ByteString::from(secret).encode(&mut payload, &ctx)?;
+        let block_size = encrypting_key.block_size();
+        let padding_size = (block_size - (payload.len() + 2) % block_size) % block_size;
+        payload.extend(std::iter::repeat_n(padding_size as u8, padding_size));
+        write_u16(&mut payload, padding_size as u16)?;
+
+        let mut encrypted = vec![0u8; payload.len() + block_size + encrypting_key.tag_size()];
+        let size = encrypting_key.encrypt(&payload, iv, &mut encrypted)?;
+        body.extend_from_slice(&encrypted[..size]);
+
+        let mut data = Vec::new();
+        NodeId::from(self.type_id).encode(&mut data, &ctx)?;
+        write_u8(&mut data, 1u8)?;
+        write_i32(&mut data, (body.len() + signature_size) as i32)?;
+        data.extend_from_slice(&body);
+
+        let mut signature = vec![0u8; signature_size];
+        sign(&data, &mut signature)?;
+        data.extend_from_slice(&signature);
+        Ok(ByteString::from(data))
+    }
+}
+
+/// A decoded `EncryptedSecret`, with the payload still encrypted.
+struct EncryptedSecret<'a> {
+    certificate: ByteString,
+    key_data: &'a [u8],
+    /// The full encoded secret.
+    data: &'a [u8],
+    /// Offset of the encrypted payload in `data`.
+    payload_offset: usize,
+}
+
+impl<'a> EncryptedSecret<'a> {
+    /// Decode the unencrypted fields of the secret, checking that it has the expected type
+    /// and security policy.
+    fn decode(
+        secret: &'a ByteString,
+        type_id: DataTypeId,
+        security_policy: SecurityPolicy,
+    ) -> Result<Self, Error> {
+        if !is_secret_policy(security_policy) {
+            return Err(secret_error(&format!(
+                "Security policy {security_policy} cannot be used for encrypted secrets"
+            )));
+        }
+        let Some(data) = secret.value.as_deref() else {
+            return Err(secret_error("Missing secret"));
+        };
+        let ctx = ContextOwned::default();
+        let ctx = ctx.context();
+        let mut stream = Cursor::new(data);
+        if NodeId::decode(&mut stream, &ctx)? != type_id {
+            return Err(secret_error("Unexpected type ID"));
+        }
+        if read_u8(&mut stream)? != 1 {
+            return Err(secret_error("Unexpected encoding mask"));
+        }
+        let length = read_i32(&mut stream)?;
+        if usize::try_from(length).ok() != data.len().checked_sub(stream.position() as usize) {
+            return Err(secret_error("Invalid length"));
+        }
+        let security_policy_uri = UAString::decode(&mut stream, &ctx)?;
+        if SecurityPolicy::from_uri(security_policy_uri.as_ref()) != security_policy {
+            return Err(secret_error(&format!(
+                "Expected security policy {security_policy}, got {security_policy_uri}"
+            )));
+        }
+        let certificate = ByteString::decode(&mut stream, &ctx)?;
+        let _signing_time = DateTime::decode(&mut stream, &ctx)?;
+        let key_data_length = read_u16(&mut stream)? as usize;
+        let key_data_offset = stream.position() as usize;
+        let payload_offset = key_data_offset + key_data_length;
+        if payload_offset > data.len() {
+            return Err(secret_error("Invalid key data length"));
+        }
+        Ok(Self {
+            certificate,
+            key_data: &data[key_data_offset..payload_offset],
+            data,
+            payload_offset,
+        })
+    }
+
+    /// Split the secret into the signed data and the signature.
+    fn signed_data(&self, signature_size: usize) -> Result<(&'a [u8], &'a [u8]), Error> {
+        if self.data.len() < self.payload_offset + signature_size {
+            return Err(secret_error("Secret is too short"));
+        }
+        Ok(self.data.split_at(self.data.len() - signature_size))
+    }
+
+    /// Decrypt the payload, check the nonce and return the secret.
+    fn decrypt_payload(
+        &self,
+        encrypting_key: &AesKey,
+        iv: &[u8],
+        signature_size: usize,
+        nonce: &[u8],
+    ) -> Result<ByteString, Error> {
+        let encrypted = &self.data[self.payload_offset..self.data.len() - signature_size];
+        let block_size = encrypting_key.block_size();
+        if encrypted.is_empty() || !encrypted.len().is_multiple_of(block_size) {
+            return Err(secret_error("Payload is not a whole number of blocks"));
+        }
+        let mut payload = vec![0u8; encrypted.len() + block_size];
+        let size = encrypting_key.decrypt(encrypted, iv, &mut payload)?;
+        payload.truncate(size);
+        if size < 2 {
+            return Err(secret_error("Payload is too short"));
+        }
+
+        let padding_size = u16::from_le_bytes([payload[size - 2], payload[size - 1]]) as usize;
+        let Some(payload_end) = (size - 2).checked_sub(padding_size) else {
+            return Err(secret_error("Invalid padding"));
+        };
+        let ctx = ContextOwned::default();
+        let ctx = ctx.context();
+        let mut stream = Cursor::new(&payload[..payload_end]);
+        let secret_nonce = ByteString::decode(&mut stream, &ctx)?;
+        if secret_nonce.as_ref() != nonce {
+            return Err(secret_error("Invalid nonce"));
+        }
+        ByteString::decode(&mut stream, &ctx)
+    }
+}
+
+/// Create the `AdditionalHeader` of a CreateSession or ActivateSession request, asking the
+/// server for an ephemeral key to encrypt user identity token secrets with `security_policy`.
+pub fn ecdh_key_request_header(security_policy: SecurityPolicy) -> ExtensionObject {
+    ExtensionObject::from_message(AdditionalParametersType {
+        parameters: Some(vec![KeyValuePair {
+            key: QualifiedName::new(0, ECDH_POLICY_URI),
+            value: Variant::from(security_policy.to_uri()),
+        }]),
+    })
+}
+
+/// Get the ECC security policy requested in the `AdditionalHeader` of a CreateSession or
+/// ActivateSession request, if the client asked for an ephemeral key.
+pub fn requested_ecdh_policy(additional_header: &ExtensionObject) -> Option<SecurityPolicy> {
+    let parameters = additional_header.inner_as::<AdditionalParametersType>()?;
+    parameters
+        .parameters
+        .iter()
+        .flatten()
+        .find(|p| p.key.name.as_ref() == ECDH_POLICY_URI)
+        .and_then(|p| match &p.value {
+            Variant::String(uri) => Some(SecurityPolicy::from_uri(uri.as_ref())),
+            _ => None,
+        })
+        .filter(|policy| policy.is_ecc())
+}
+
+/// Create the `AdditionalHeader` of a CreateSession or ActivateSession response, containing
+/// the public part of `ephemeral_key` signed with the private key of the server.
+pub fn ecdh_key_response_header(
+    security_policy: SecurityPolicy,
+    ephemeral_key: &EphemeralKey,
+    signing_key: &PrivateKey,
+) -> Result<ExtensionObject, Error> {
+    let public_key = ephemeral_key.nonce();
+    let mut signature = vec![0u8; signing_key.signature_size()];
+    security_policy.asymmetric_sign(signing_key, &public_key, &mut signature)?;
+    let key = EphemeralKeyType {
+        public_key: ByteString::from(public_key),
+        signature: ByteString::from(signature),
+    };
+    Ok(ExtensionObject::from_message(AdditionalParametersType {
+        parameters: Some(vec![KeyValuePair {
+            key: QualifiedName::new(0, ECDH_KEY),
+            value: Variant::from(ExtensionObject::from_message(key)),
+        }]),
+    }))
+}
+
+/// Get the ephemeral key of the server from the `AdditionalHeader` of a CreateSession or
+/// ActivateSession response, verifying its signature with the certificate of the server.
+/// Returns `Ok(None)` if the response contains no ephemeral key.
+pub fn ecdh_key_from_response_header(
+    additional_header: &ExtensionObject,
+    security_policy: SecurityPolicy,
+    server_cert: &X509,
+) -> Result<Option<ByteString>, Error> {
+    let Some(parameters) = additional_header.inner_as::<AdditionalParametersType>() else {
+        return Ok(None);
+    };
+    let Some(parameter) = parameters
+        .parameters
+        .iter()
+        .flatten()
+        .find(|p| p.key.name.as_ref() == ECDH_KEY)
+    else {
+        return Ok(None);
+    };
+    let key = match &parameter.value {
+        Variant::ExtensionObject(obj) => obj.inner_as::<EphemeralKeyType>(),
+        _ => None,
+    };
+    let Some(key) = key else {
+        return Err(Error::new(
+            StatusCode::BadDecodingError,
+            "ECDHKey parameter does not contain an EphemeralKeyType",
+        ));
+    };
+    security_policy.asymmetric_verify_signature(
+        &server_cert.public_key()?,
+        key.public_key.as_ref(),
+        key.signature.as_ref(),
+        None,
+    )?;
+    Ok(Some(key.public_key.clone()))
+}
+
 /// Verify that the X509 identity token supplied to a server contains a valid signature.
 pub fn verify_x509_identity_token(
     token: &X509IdentityToken,
@@ -18,11 +18,13 @@ type ChronoUtc = DateTime<Utc>;
 
 use rsa;
 use rsa::pkcs1v15;
+use rsa::signature::{Keypair, SignatureEncoding, Signer};
 use rsa::RsaPublicKey;
 use x509_cert::{
     self as x509,
     der::asn1::{Ia5String, OctetString},
     ext::pkix::name::GeneralName,
+    spki::{DynSignatureAlgorithmIdentifier, EncodePublicKey, SignatureBitStringEncoding},
 };
 
 use const_oid;
@@ -33,7 +35,7 @@ use opcua_types::{status_code::StatusCode, ApplicationDescription, ByteString, E
 
 use super::{
     hostname,
-    pkey::{PrivateKey, PublicKey},
+    pkey::{KeyType, PrivateKey, PrivateKeyValue, PublicKey, PublicKeyValue},
     thumbprint::Thumbprint,
 };
 
@@ -199,7 +201,9 @@ impl From<Vec<String>> for AlternateNames {
 
 /// Data for constructing an X509 certificate.
 pub struct X509Data {
-    /// Requested key size.
+    /// Requested key type.
+    pub key_type: KeyType,
+    /// Requested key size, only used for RSA keys.
     pub key_size: u32,
     /// Certificate CN.
     pub common_name: String,
@@ -236,6 +240,7 @@ impl From<(ApplicationDescription, Option<Vec<String>>)> for X509Data {
             true,
         );
         X509Data {
+            key_type: KeyType::Rsa,
             key_size: DEFAULT_KEYSIZE,
             common_name: application_description.application_name.to_string(),
             organization: application_description.application_name.to_string(),
@@ -363,6 +368,7 @@ impl X509Data {
         let mut alt_host_names = AlternateNames::new();
         Self::compute_alt_host_names(&mut alt_host_names, "urn:OPCUADemo", None, true, true, true);
         X509Data {
+            key_type: KeyType::Rsa,
             key_size: 2048,
             common_name: "OPC UA Demo Key".to_string(),
             organization: "OPC UA for Rust".to_string(),
@@ -393,6 +399,128 @@ impl From<x509::der::Error> for X509Error {
     }
 }
 
+/// Signature which X509 stores as the signature bit string as is, either an EdDSA
+/// signature or an ECDSA signature that is already DER encoded.
+struct RawSignature(Vec<u8>);
+
+impl SignatureBitStringEncoding for RawSignature {
+    fn to_bitstring(&self) -> x509::der::Result<x509::der::asn1::BitString> {
+        x509::der::asn1::BitString::from_bytes(&self.0)
+    }
+}
+
+/// Signs certificates and signing requests with the Ed25519 key `K`, which
+/// makes signatures of type `S` with no bit string encoding of their own.
+struct EdDsaSigner<'a, K, S> {
+    key: &'a K,
+    signature: std::marker::PhantomData<S>,
+}
+
+impl<'a, K, S> EdDsaSigner<'a, K, S> {
+    fn new(key: &'a K) -> Self {
+        Self {
+            key,
+            signature: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<K: Keypair, S> Keypair for EdDsaSigner<'_, K, S> {
+    type VerifyingKey = K::VerifyingKey;
+
+    fn verifying_key(&self) -> Self::VerifyingKey {
+        self.key.verifying_key()
+    }
+}
+
+impl<K: DynSignatureAlgorithmIdentifier, S> DynSignatureAlgorithmIdentifier
+    for EdDsaSigner<'_, K, S>
+{
+    fn signature_algorithm_identifier(
+        &self,
+    ) -> x509::spki::Result<x509::spki::AlgorithmIdentifierOwned> {
+        self.key.signature_algorithm_identifier()
+    }
+}
+
+impl<K: Signer<S>, S: SignatureEncoding> Signer<RawSignature> for EdDsaSigner<'_, K, S> {
+    fn try_sign(&self, msg: &[u8]) -> Result<RawSignature, rsa::signature::Error> {
+        self.key.try_sign(msg).map(|s| RawSignature(s.to_vec()))
+    }
+}
+
+/// Signs certificates and signing requests with a brainpool key. The brainpool curves
+/// use newer versions of the signature and key traits than the X509 builder, so the
+/// public key and signatures are passed on DER encoded.
+struct BrainpoolSigner<'a> {
+    key: &'a PrivateKey,
+    public_key: x509::spki::SubjectPublicKeyInfoOwned,
+}
+
+impl<'a> BrainpoolSigner<'a> {
+    fn new(key: &'a PrivateKey) -> x509::spki::Result<Self> {
+        Ok(Self {
+            key,
+            public_key: key.public_key_to_info()?,
+        })
+    }
+}
+
+/// Public key of a [`BrainpoolSigner`].
+#[derive(Clone)]
+struct BrainpoolVerifyingKey(x509::spki::SubjectPublicKeyInfoOwned);
+
+impl EncodePublicKey for BrainpoolVerifyingKey {
+    fn to_public_key_der(&self) -> x509::spki::Result<x509::der::Document> {
+        Ok(x509::der::Document::encode_msg(&self.0)?)
+    }
+}
+
+impl Keypair for BrainpoolSigner<'_> {
+    type VerifyingKey = BrainpoolVerifyingKey;
+
+    fn verifying_key(&self) -> Self::VerifyingKey {
+        BrainpoolVerifyingKey(self.public_key.clone())
+    }
+}
+
+impl DynSignatureAlgorithmIdentifier for BrainpoolSigner<'_> {
+    fn signature_algorithm_identifier(
+        &self,
+    ) -> x509::spki::Result<x509::spki::AlgorithmIdentifierOwned> {
+        use const_oid::db::rfc5912;
+
+        let oid = match self.key.key_type() {
+            KeyType::BrainpoolP256r1 => rfc5912::ECDSA_WITH_SHA_256,
+            KeyType::BrainpoolP384r1 => rfc5912::ECDSA_WITH_SHA_384,
+            _ => return Err(x509::spki::Error::KeyMalformed),
+        };
+        Ok(x509::spki::AlgorithmIdentifierOwned {
+            oid,
+            parameters: None,
+        })
+    }
+}
+
+impl Signer<RawSignature> for BrainpoolSigner<'_> {
+    fn try_sign(&self, msg: &[u8]) -> Result<RawSignature, rsa::signature::Error> {
+        use ecdsa::signature::Signer as _;
+
+        let signature = match &self.key.value {
+            PrivateKeyValue::BrainpoolP256r1(key) => ecdsa::SigningKey::from(key)
+                .try_sign(msg)
+                .map(|s: ecdsa::Signature<bp256::BrainpoolP256r1>| s.to_der().to_bytes().to_vec()),
+            PrivateKeyValue::BrainpoolP384r1(key) => ecdsa::SigningKey::from(key)
+                .try_sign(msg)
+                .map(|s: ecdsa::Signature<bp384::BrainpoolP384r1>| s.to_der().to_bytes().to_vec()),
+            _ => return Err(rsa::signature::Error::new()),
+        };
+        signature
+            .map(RawSignature)
+            .map_err(|_| rsa::signature::Error::new())
+    }
+}
+
 #[derive(Clone)]
 /// Wrapper around an X509 certificate.
 pub struct X509 {
@@ -461,8 +589,8 @@ impl X509 {
     pub fn cert_and_pkey(x509_data: &X509Data) -> Result<(Self, PrivateKey), String> {
         // Create a key pair
 
-        let pkey = PrivateKey::new(x509_data.key_size)
-            .map_err(|e| format!("Failed to generate RSA private key: {e}"))?;
+        let pkey = PrivateKey::generate(x509_data.key_type, x509_data.key_size)
+            .map_err(|e| format!("Failed to generate private key: {e}"))?;
 
         // Create an X509 cert to hold the public key
         let cert = Self::from_pkey(&pkey, x509_data)?;
@@ -497,6 +625,48 @@ impl X509 {
     }
 
     fn create_from_pkey(pkey: &PrivateKey, x509_data: &X509Data) -> Result<Self, BuilderError> {
+        match &pkey.value {
+            PrivateKeyValue::Rsa(key) => {
+                let signing_key = pkcs1v15::SigningKey::<sha2::Sha256>::new(key.clone());
+                Self::build_certificate::<_, pkcs1v15::Signature>(pkey, x509_data, &signing_key)
+            }
+            PrivateKeyValue::NistP256(key) => {
+                let signing_key = p256::ecdsa::SigningKey::from(key);
+                Self::build_certificate::<_, p256::ecdsa::DerSignature>(
+                    pkey,
+                    x509_data,
+                    &signing_key,
+                )
+            }
+            PrivateKeyValue::NistP384(key) => {
+                let signing_key = p384::ecdsa::SigningKey::from(key);
+                Self::build_certificate::<_, p384::ecdsa::DerSignature>(
+                    pkey,
+                    x509_data,
+                    &signing_key,
+                )
+            }
+            PrivateKeyValue::Curve25519(key) => {
+                let signing_key = EdDsaSigner::<_, ed25519_dalek::Signature>::new(key);
+                Self::build_certificate::<_, RawSignature>(pkey, x509_data, &signing_key)
+            }
+            PrivateKeyValue::BrainpoolP256r1(_) | PrivateKeyValue::BrainpoolP384r1(_) => {
+                let signing_key = BrainpoolSigner::new(pkey)?;
+                Self::build_certificate::<_, RawSignature>(pkey, x509_data, &signing_key)
+            }
+        }
+    }
+
+    fn build_certificate<S, Signature>(
+        pkey: &PrivateKey,
+        x509_data: &X509Data,
+        signing_key: &S,
+    ) -> Result<Self, BuilderError>
+    where
+        S: Keypair + DynSignatureAlgorithmIdentifier + Signer<Signature>,
+        S::VerifyingKey: EncodePublicKey,
+        Signature: SignatureBitStringEncoding,
+    {
         use std::time::Duration;
         use x509_cert::builder::{CertificateBuilder, Profile};
         use x509_cert::name::Name;
@@ -518,8 +688,6 @@ impl X509 {
         ))
         .unwrap();
 
-        let signing_key = pkcs1v15::SigningKey::<sha2::Sha256>::new(pkey.value.clone());
-
         let serial_number = SerialNumber::from(42u32);
 
         let subject;
@@ -559,7 +727,7 @@ impl X509 {
             validity,
             subject.clone(),
             pub_key,
-            &signing_key,
+            signing_key,
         )?;
 
         builder.add_extension(&x509::ext::pkix::SubjectKeyIdentifier(
@@ -579,11 +747,16 @@ impl X509 {
             use x509::ext::pkix::KeyUsage;
             use x509::ext::pkix::KeyUsages;
 
-            let key_usage = KeyUsages::DigitalSignature
-                | KeyUsages::NonRepudiation
-                | KeyUsages::KeyEncipherment
-                | KeyUsages::DataEncipherment
-                | KeyUsages::KeyCertSign;
+            // Elliptic curve keys cannot be used for encryption
+            let key_usage = if pkey.key_type() == KeyType::Rsa {
+                KeyUsages::DigitalSignature
+                    | KeyUsages::NonRepudiation
+                    | KeyUsages::KeyEncipherment
+                    | KeyUsages::DataEncipherment
+                    | KeyUsages::KeyCertSign
+            } else {
+                KeyUsages::DigitalSignature | KeyUsages::NonRepudiation | KeyUsages::KeyCertSign
+            };
             builder.add_extension(&KeyUsage(key_usage))?;
         }
 
@@ -603,7 +776,7 @@ impl X509 {
         }
 
         use x509_cert::builder::Builder;
-        let built = builder.build()?;
+        let built = builder.build::<Signature>()?;
 
         Ok(X509 { value: built })
     }
@@ -634,18 +807,46 @@ impl X509 {
     pub fn public_key(&self) -> Result<PublicKey, Error> {
         use x509_cert::der::referenced::OwnedToRef;
 
-        let r = RsaPublicKey::try_from(
-            self.value
-                .tbs_certificate
-                .subject_public_key_info
-                .owned_to_ref(),
-        );
+        let info = self
+            .value
+            .tbs_certificate
+            .subject_public_key_info
+            .owned_to_ref();
+        let r = RsaPublicKey::try_from(info.clone())
+            .map(PublicKeyValue::Rsa)
+            .or_else(|_| p256::PublicKey::try_from(info.clone()).map(PublicKeyValue::NistP256))
+            .or_else(|_| p384::PublicKey::try_from(info.clone()).map(PublicKeyValue::NistP384))
+            .or_else(|_| {
+                ed25519_dalek::VerifyingKey::try_from(info).map(PublicKeyValue::Curve25519)
+            })
+            .or_else(|e| self.brainpool_public_key().ok_or(e));
         match r {
             Err(e) => Err(Error::new(StatusCode::BadCertificateInvalid, e)),
             Ok(v) => Ok(PublicKey { value: v }),
         }
     }
 
+    /// Try to get a brainpool public key from this certificate. The brainpool curves use
+    /// a newer version of `spki`, so the key is decoded from the DER encoded key info.
+    fn brainpool_public_key(&self) -> Option<PublicKeyValue> {
+        use bp256::pkcs8::DecodePublicKey;
+        use x509_cert::der::Encode;
+
+        let der = self
+            .value
+            .tbs_certificate
+            .subject_public_key_info
+            .to_der()
+            .ok()?;
+        if let Ok(key) = crate::pkey::BrainpoolP256r1PublicKey::from_public_key_der(&der) {
+            Some(PublicKeyValue::BrainpoolP256r1(key))
+        } else if let Ok(key) = crate::pkey::BrainpoolP384r1PublicKey::from_public_key_der(&der) {
+            Some(PublicKeyValue::BrainpoolP384r1(key))
+        } else {
+            None
+        }
+    }
+
     /// Returns the key length in bits (if possible)
     pub fn key_length(&self) -> Result<usize, X509Error> {
         use crate::pkey::KeySize;
@@ -813,6 +1014,145 @@ impl X509 {
         Thumbprint::new(&digest)
     }
 
+    /// Produces an issuer name string such as "CN=foo/C=IE"
+    pub fn issuer_name(&self) -> String {
+        let r = self.value.tbs_certificate.issuer.to_string();
+        r.replace(";", "/")
+    }
+
+    /// Tests if the issuer and subject of the certificate are the same, and the certificate
+    /// is signed by its own key.
+    pub fn is_self_signed(&self) -> bool {
+        self.value.tbs_certificate.issuer == self.value.tbs_certificate.subject
+            && self.is_issued_by(self)
+    }
+
+    /// Tests if this certificate was issued by `issuer`, i.e. the issuer name of this
+    /// certificate is the subject of `issuer` and the signature is made by its key.
+    pub fn is_issued_by(&self, issuer: &X509) -> bool {
+        use x509_cert::der::Encode;
+
+        if self.value.tbs_certificate.issuer != issuer.value.tbs_certificate.subject {
+            return false;
+        }
+        let (Ok(public_key), Ok(tbs), Some(signature)) = (
+            issuer.public_key(),
+            self.value.tbs_certificate.to_der(),
+            self.value.signature.as_bytes(),
+        ) else {
+            return false;
+        };
+        public_key.verify_signed_data(&self.value.signature_algorithm.oid, &tbs, signature)
+    }
+
+    /// Tests if the certificate is a certificate authority, i.e. it has the basic
+    /// constraints extension with `cA` set.
+    pub fn is_ca(&self) -> bool {
+        use x509::ext::pkix::BasicConstraints;
+
+        matches!(
+            self.value.tbs_certificate.get::<BasicConstraints>(),
+            Ok(Some((_, BasicConstraints { ca: true, .. })))
+        )
+    }
+
+    /// Tests if `pkey` is the private key belonging to the public key of this certificate.
+    pub fn matches_private_key(&self, pkey: &PrivateKey) -> bool {
+        pkey.public_key_to_info()
+            .is_ok_and(|info| info == self.value.tbs_certificate.subject_public_key_info)
+    }
+
+    /// Create a PKCS#10 certificate signing request in DER form, asking for a new certificate
+    /// for the key pair `pkey`. The request has the subject alternative names of this
+    /// certificate, and its subject unless `subject_name` is set, e.g. to `"CN=foo,O=bar"`.
+    pub fn create_signing_request(
+        &self,
+        pkey: &PrivateKey,
+        subject_name: Option<&str>,
+    ) -> Result<Vec<u8>, String> {
+        use std::str::FromStr;
+        use x509::name::Name;
+
+        let subject = match subject_name {
+            Some(name) => {
+                Name::from_str(name).map_err(|e| format!("Invalid subject name {name}: {e}"))?
+            }
+            None => self.value.tbs_certificate.subject.clone(),
+        };
+        let alt_names = self.get_alternate_names();
+        let result = match &pkey.value {
+            PrivateKeyValue::Rsa(key) => {
+                let signing_key = pkcs1v15::SigningKey::<sha2::Sha256>::new(key.clone());
+                Self::build_signing_request::<_, pkcs1v15::Signature>(
+                    subject,
+                    alt_names,
+                    &signing_key,
+                )
+            }
+            PrivateKeyValue::NistP256(key) => {
+                let signing_key = p256::ecdsa::SigningKey::from(key);
+                Self::build_signing_request::<_, p256::ecdsa::DerSignature>(
+                    subject,
+                    alt_names,
+                    &signing_key,
+                )
+            }
+            PrivateKeyValue::NistP384(key) => {
+                let signing_key = p384::ecdsa::SigningKey::from(key);
+                Self::build_signing_request::<_, p384::ecdsa::DerSignature>(
+                    subject,
+                    alt_names,
+                    &signing_key,
+                )
+            }
+            PrivateKeyValue::Curve25519(key) => {
+                let signing_key = EdDsaSigner::<_, ed25519_dalek::Signature>::new(key);
+                Self::build_signing_request::<_, RawSignature>(subject, alt_names, &signing_key)
+            }
+            PrivateKeyValue::BrainpoolP256r1(_) | PrivateKeyValue::BrainpoolP384r1(_) => {
+                BrainpoolSigner::new(pkey)
+                    .map_err(BuilderError::from)
+                    .and_then(|signing_key| {
+                        Self::build_signing_request::<_, RawSignature>(
+                            subject,
+                            alt_names,
+                            &signing_key,
+                        )
+                    })
+            }
+        };
+        result.map_err(|e| format!("Failed to create signing request: {e}"))
+    }
+
+    fn build_signing_request<S, Signature>(
+        subject: x509::name::Name,
+        alt_names: Option<x509::ext::pkix::name::GeneralNames>,
+        signing_key: &S,
+    ) -> Result<Vec<u8>, BuilderError>
+    where
+        S: Keypair + DynSignatureAlgorithmIdentifier + Signer<Signature>,
+        S::VerifyingKey: EncodePublicKey,
+        Signature: SignatureBitStringEncoding,
+    {
+        use x509::builder::{Builder, RequestBuilder};
+        use x509::der::Encode;
+
+        let mut builder = RequestBuilder::new(subject, signing_key)?;
+        if let Some(alt_names) = alt_names {
+            builder.add_extension(&x509::ext::pkix::SubjectAltName(alt_names))?;
+        }
+        let request = builder.build::<Signature>()?;
+        Ok(request.to_der()?)
+    }
+
+    pub(crate) fn serial_number(&self) -> &x509::serial_number::SerialNumber {
+        &self.value.tbs_certificate.serial_number
+    }
+
+    pub(crate) fn subject(&self) -> &x509::name::Name {
+        &self.value.tbs_certificate.subject
+    }
+
     /// Turn the Asn1 values into useful portable types
     pub fn not_before(&self) -> Result<ChronoUtc, X509Error> {
         let dur = self
@@ -880,6 +1220,7 @@ mod tests {
 
         // Create a cert with alt hostnames which are both IP and DNS entries
         let args = X509Data {
+            key_type: KeyType::Rsa,
             key_size: 2048,
             common_name: "x".to_string(),
             organization: "x.org".to_string(),
@@ -4,12 +4,14 @@
 
 mod encoding;
 mod events;
+mod objects;
 mod utils;
 
 use encoding::{
     derive_all_inner, derive_ua_nullable_inner, generate_encoding_impl, EncodingToImpl,
 };
 use events::{derive_event_field_inner, derive_event_inner};
+use objects::derive_ua_object_inner;
 use proc_macro::TokenStream;
 use syn::parse_macro_input;
 
@@ -74,6 +76,49 @@ pub fn derive_event_field(item: TokenStream) -> TokenStream {
     }
 }
 
+#[proc_macro_derive(UaObject, attributes(opcua))]
+/// Derive the `UaObject` trait, letting the struct be inserted into the address space
+/// as an object instance.
+///
+/// Each field becomes a child of the object. By default fields are variables of type
+/// `BaseDataVariableType`, use `opcua(property)` to make a field a property instead,
+/// or `opcua(object)` for nested objects, whose types must also implement `UaObject`.
+/// Variable fields must implement `MethodArg`, `Clone`, and `Into<Variant>`.
+///
+/// The type definition of the object is `BaseObjectType` by default, this can be changed
+/// with `opcua(type_definition = ...)`, which takes an expression convertible to a `NodeId`.
+///
+/// By default, fields will be given `PascalCase` names, you may use `opcua[rename = ...]`
+/// to rename individual fields, or `opcua(ignore)` to skip them.
+///
+/// # Example
+///
+/// ```ignore
+/// #[derive(UaObject)]
+/// struct Motor {
+///     speed: f64,
+///     #[opcua(property)]
+///     serial_number: String,
+/// }
+///
+/// #[derive(UaObject)]
+/// #[opcua(type_definition = ObjectTypeId::FolderType)]
+/// struct Station {
+///     #[opcua(rename = "FlowRate")]
+///     flow: f64,
+///     #[opcua(object)]
+///     motor: Motor,
+///     #[opcua(ignore)]
+///     internal: u32,
+/// }
+/// ```
+pub fn derive_ua_object(item: TokenStream) -> TokenStream {
+    match derive_ua_object_inner(parse_macro_input!(item)) {
+        Ok(r) => r.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 #[cfg(feature = "json")]
 #[proc_macro_derive(JsonEncodable, attributes(opcua))]
 /// Derive the `JsonEncodable` trait on this struct or enum, creating code
@@ -0,0 +1,68 @@
+use convert_case::{Case, Casing};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use super::parse::ObjectStruct;
+
+pub fn generate_object_impls(object: ObjectStruct) -> syn::Result<TokenStream> {
+    let ident = object.ident;
+    let mut insert_children = quote! {};
+    let mut collect_values = quote! {};
+
+    for field in object.fields {
+        if field.attr.ignore {
+            continue;
+        }
+
+        let name = field
+            .attr
+            .rename
+            .unwrap_or_else(|| field.ident.to_string().to_case(Case::Pascal));
+        let ident = field.ident;
+
+        if field.attr.object {
+            insert_children.extend(quote! {
+                binding.add_object(address_space, #name, &self.#ident);
+            });
+            collect_values.extend(quote! {
+                opcua::nodes::UaObject::collect_values(&self.#ident, values);
+            });
+        } else {
+            let property = field.attr.property;
+            insert_children.extend(quote! {
+                binding.add_variable(address_space, #name, self.#ident.clone(), #property);
+            });
+            collect_values.extend(quote! {
+                values.push(self.#ident.clone().into());
+            });
+        }
+    }
+
+    let type_definition = object.attribute.type_definition.map(|t| {
+        quote! {
+            fn type_definition() -> opcua::types::NodeId {
+                (#t).into()
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl opcua::nodes::UaObject for #ident {
+            #type_definition
+
+            #[allow(unused_variables)]
+            fn insert_children(
+                &self,
+                address_space: &mut impl opcua::nodes::NodeInsertTarget,
+                binding: &mut opcua::nodes::ObjectBinding,
+            ) {
+                #insert_children
+            }
+
+            #[allow(unused_variables)]
+            fn collect_values(&self, values: &mut Vec<opcua::types::Variant>) {
+                #collect_values
+            }
+        }
+    })
+}
@@ -0,0 +1,12 @@
+mod gen;
+mod parse;
+
+use gen::generate_object_impls;
+use parse::parse_object_struct;
+use proc_macro2::TokenStream;
+use syn::DeriveInput;
+
+pub fn derive_ua_object_inner(input: DeriveInput) -> syn::Result<TokenStream> {
+    let struct_data = parse_object_struct(input)?;
+    generate_object_impls(struct_data)
+}
@@ -0,0 +1,93 @@
+use syn::{parse::Parse, DeriveInput, Expr, Ident, LitStr, Token};
+
+use crate::utils::{expect_struct, ItemAttr, StructItem};
+
+#[derive(Default, Debug)]
+pub(super) struct ObjectFieldAttribute {
+    pub ignore: bool,
+    pub rename: Option<String>,
+    pub property: bool,
+    pub object: bool,
+}
+
+impl Parse for ObjectFieldAttribute {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut slf = Self::default();
+        loop {
+            let ident: Ident = input.parse()?;
+            match ident.to_string().as_str() {
+                "ignore" => slf.ignore = true,
+                "rename" => {
+                    input.parse::<Token![=]>()?;
+                    let val: LitStr = input.parse()?;
+                    slf.rename = Some(val.value());
+                }
+                "property" => slf.property = true,
+                "object" => slf.object = true,
+                _ => return Err(syn::Error::new_spanned(ident, "Unknown attribute value")),
+            }
+            if !input.peek(Token![,]) {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        if slf.property && slf.object {
+            return Err(syn::Error::new(
+                input.span(),
+                "Field cannot be both a property and an object",
+            ));
+        }
+        Ok(slf)
+    }
+}
+
+impl ItemAttr for ObjectFieldAttribute {
+    fn combine(&mut self, other: Self) {
+        self.ignore |= other.ignore;
+        self.property |= other.property;
+        self.object |= other.object;
+        if other.rename.is_some() {
+            self.rename = other.rename;
+        }
+    }
+}
+
+#[derive(Default)]
+pub(super) struct ObjectAttribute {
+    pub type_definition: Option<Expr>,
+}
+
+impl Parse for ObjectAttribute {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut slf = Self::default();
+        loop {
+            let ident: Ident = input.parse()?;
+            match ident.to_string().as_str() {
+                "type_definition" => {
+                    input.parse::<Token![=]>()?;
+                    slf.type_definition = Some(input.parse()?);
+                }
+                _ => return Err(syn::Error::new_spanned(ident, "Unknown attribute value")),
+            }
+            if !input.peek(Token![,]) {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        Ok(slf)
+    }
+}
+
+impl ItemAttr for ObjectAttribute {
+    fn combine(&mut self, other: Self) {
+        if other.type_definition.is_some() {
+            self.type_definition = other.type_definition;
+        }
+    }
+}
+
+pub type ObjectStruct = StructItem<ObjectFieldAttribute, ObjectAttribute>;
+
+pub fn parse_object_struct(input: DeriveInput) -> syn::Result<ObjectStruct> {
+    ObjectStruct::from_input(expect_struct(input.data)?, input.attrs, input.ident)
+}
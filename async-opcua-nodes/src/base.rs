@@ -3,8 +3,9 @@
 // Copyright (C) 2017-2024 Adam Lock
 
 use opcua_types::{
-    status_code::StatusCode, AttributeId, DataEncoding, DataValue, LocalizedText, NodeClass,
-    NodeId, NumericRange, QualifiedName, TimestampsToReturn, Variant, WriteMask,
+    status_code::StatusCode, AttributeId, DataEncoding, DataValue, ExtensionObject, LocalizedText,
+    NodeClass, NodeId, NumericRange, QualifiedName, RolePermissionType, TimestampsToReturn,
+    Variant, WriteMask,
 };
 
 use super::node::{Node, NodeBase};
@@ -26,6 +27,8 @@ pub struct Base {
     pub(super) write_mask: Option<u32>,
     /// User write mask bits (optional)
     pub(super) user_write_mask: Option<u32>,
+    /// Permissions of each role on this node (optional)
+    pub(super) role_permissions: Option<Vec<RolePermissionType>>,
 }
 
 impl NodeBase for Base {
@@ -72,6 +75,14 @@ impl NodeBase for Base {
     fn set_user_write_mask(&mut self, user_write_mask: WriteMask) {
         self.user_write_mask = Some(user_write_mask.bits());
     }
+
+    fn role_permissions(&self) -> Option<&[RolePermissionType]> {
+        self.role_permissions.as_deref()
+    }
+
+    fn set_role_permissions(&mut self, role_permissions: Option<Vec<RolePermissionType>>) {
+        self.role_permissions = role_permissions;
+    }
 }
 
 impl Node for Base {
@@ -94,6 +105,15 @@ impl Node for Base {
                 .map(|description| description.into()),
             AttributeId::WriteMask => self.write_mask.map(|v| v.into()),
             AttributeId::UserWriteMask => self.user_write_mask.map(|v| v.into()),
+            AttributeId::RolePermissions => self.role_permissions.as_ref().map(|v| {
+                Variant::from(
+                    v.iter()
+                        .cloned()
+                        .map(ExtensionObject::from_message)
+                        .collect::<Vec<_>>(),
+                )
+                .into()
+            }),
             _ => None,
         }
     }
@@ -174,6 +194,28 @@ impl Node for Base {
                     Err(StatusCode::BadTypeMismatch)
                 }
             }
+            AttributeId::RolePermissions => match value {
+                Variant::Empty => {
+                    self.role_permissions = None;
+                    Ok(())
+                }
+                Variant::Array(array) => {
+                    let role_permissions = array
+                        .values
+                        .into_iter()
+                        .map(|v| match v {
+                            Variant::ExtensionObject(o) => o
+                                .into_inner_as::<RolePermissionType>()
+                                .map(|r| *r)
+                                .ok_or(StatusCode::BadTypeMismatch),
+                            _ => Err(StatusCode::BadTypeMismatch),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.role_permissions = Some(role_permissions);
+                    Ok(())
+                }
+                _ => Err(StatusCode::BadTypeMismatch),
+            },
             _ => Err(StatusCode::BadAttributeIdInvalid),
         }
     }
@@ -195,6 +237,7 @@ impl Base {
             description: None,
             write_mask: None,
             user_write_mask: None,
+            role_permissions: None,
         }
     }
 
@@ -217,6 +260,7 @@ impl Base {
             description,
             write_mask,
             user_write_mask,
+            role_permissions: None,
         }
     }
 
@@ -72,6 +72,7 @@ macro_rules! bw_op {
 ///
 /// Implemented by `dyn Event`. Types passed to a content filter must
 /// implement this.
+#[allow(unused_variables)]
 pub trait AttributeQueryable: Copy {
     /// Get an attribute value from the item.
     fn get_attribute(
@@ -81,6 +82,24 @@ pub trait AttributeQueryable: Copy {
         attribute_id: AttributeId,
         index_range: &NumericRange,
     ) -> Variant;
+
+    /// Return `true` if the item is an instance of `type_definition_id`, or,
+    /// if `include_subtypes` is set, of a subtype of it.
+    ///
+    /// This is used by the `OfType` and `RelatedTo` operators. The default
+    /// implementation returns `false`.
+    fn is_of_type(&self, type_definition_id: &NodeId, include_subtypes: bool) -> bool {
+        false
+    }
+
+    /// Get the items referenced by this item through forward references of type
+    /// `reference_type_id`, or, if `include_subtypes` is set, a subtype of it.
+    ///
+    /// This is used by the `RelatedTo` operator. The default implementation
+    /// returns nothing.
+    fn get_related(&self, reference_type_id: &NodeId, include_subtypes: bool) -> Vec<Self> {
+        Vec::new()
+    }
 }
 
 impl AttributeQueryable for &dyn Event {
@@ -95,6 +114,9 @@ impl AttributeQueryable for &dyn Event {
     }
 }
 
+/// Maximum number of items visited when evaluating a single `RelatedTo` operator.
+const MAX_RELATED_TO_ITEMS: usize = 10_000;
+
 enum BitOperation {
     And,
     Or,
@@ -170,10 +192,78 @@ impl ParsedContentFilter {
                 self.evaluate_operand(item, &op.operands[1]),
                 BitOperation::Or,
             ),
+            FilterOperator::OfType => match self.evaluate_operand(item, &op.operands[0]) {
+                Variant::NodeId(type_id) => item.is_of_type(&type_id, true).into(),
+                Variant::ExpandedNodeId(type_id) => item.is_of_type(&type_id.node_id, true).into(),
+                _ => false.into(),
+            },
+            FilterOperator::RelatedTo => self.related_to(item, &op.operands).into(),
             _ => Variant::Empty,
         }
     }
 
+    /// Check if `item` matches an operand of `RelatedTo` describing a node type.
+    /// This is either the ID of a type definition, or a reference to another
+    /// `RelatedTo` element which the item must pass.
+    fn matches_type_operand(
+        &self,
+        item: impl AttributeQueryable,
+        op: &ParsedOperand,
+        include_subtypes: bool,
+    ) -> bool {
+        if let ParsedOperand::ElementOperand(o) = op {
+            return matches!(
+                self.evulate_element(item, o.index as usize),
+                Variant::Boolean(true)
+            );
+        }
+        match self.evaluate_operand(item, op) {
+            Variant::NodeId(type_id) => item.is_of_type(&type_id, include_subtypes),
+            Variant::ExpandedNodeId(type_id) => item.is_of_type(&type_id.node_id, include_subtypes),
+            _ => false,
+        }
+    }
+
+    fn related_to<T: AttributeQueryable>(&self, item: T, operands: &[ParsedOperand]) -> bool {
+        // Operands are: source type, target type, reference type, number of hops,
+        // include type definition subtypes, include reference type subtypes.
+        let include_type_subtypes =
+            as_type!(self.evaluate_operand(item, &operands[4]), Boolean, false);
+        if !self.matches_type_operand(item, &operands[0], include_type_subtypes) {
+            return false;
+        }
+        let reference_type_id = match self.evaluate_operand(item, &operands[2]) {
+            Variant::NodeId(id) => *id,
+            Variant::ExpandedNodeId(id) => id.node_id,
+            _ => return false,
+        };
+        let hops = as_type!(self.evaluate_operand(item, &operands[3]), UInt32, false).max(1);
+        let include_reference_subtypes =
+            as_type!(self.evaluate_operand(item, &operands[5]), Boolean, false);
+
+        let mut current = vec![item];
+        let mut visited = 0;
+        for _ in 0..hops {
+            let mut next = Vec::new();
+            for it in current {
+                for related in it.get_related(&reference_type_id, include_reference_subtypes) {
+                    if self.matches_type_operand(related, &operands[1], include_type_subtypes) {
+                        return true;
+                    }
+                    next.push(related);
+                }
+            }
+            // The items are not deduplicated, so put a bound on the amount of work
+            // a single evaluation can do in a graph with cycles.
+            visited += next.len();
+            if next.is_empty() || visited > MAX_RELATED_TO_ITEMS {
+                break;
+            }
+            current = next;
+        }
+        false
+    }
+
     fn evaluate_operand(&self, item: impl AttributeQueryable, op: &ParsedOperand) -> Variant {
         match op {
             ParsedOperand::ElementOperand(o) => self.evulate_element(item, o.index as usize),
@@ -391,11 +481,13 @@ mod tests {
     use regex::Regex;
 
     use crate::{
-        events::evaluate::like_to_regex, BaseEventType, DefaultTypeTree, Event, ParsedContentFilter,
+        events::evaluate::like_to_regex, AttributeQueryable, BaseEventType, DefaultTypeTree, Event,
+        ParsedContentFilter, TypeTree,
     };
     use opcua_types::{
         AttributeId, ByteString, ContentFilter, ContentFilterElement, DateTime, FilterOperator,
-        LocalizedText, NodeClass, NodeId, NumericRange, ObjectTypeId, Operand,
+        LocalizedText, NodeClass, NodeId, NumericRange, ObjectTypeId, Operand, QualifiedName,
+        ReferenceTypeId, Variant,
     };
 
     fn compare_regex(r1: Regex, r2: Regex) {
@@ -959,4 +1051,194 @@ mod tests {
         let evt = event(4);
         assert!(!f.evaluate(&evt as &dyn Event));
     }
+
+    struct TestGraph {
+        type_tree: DefaultTypeTree,
+        types: Vec<NodeId>,
+        references: Vec<(usize, usize, NodeId)>,
+    }
+
+    #[derive(Clone, Copy)]
+    struct TestNode<'a> {
+        index: usize,
+        graph: &'a TestGraph,
+    }
+
+    impl AttributeQueryable for TestNode<'_> {
+        fn get_attribute(
+            &self,
+            _type_definition_id: &NodeId,
+            _browse_path: &[QualifiedName],
+            _attribute_id: AttributeId,
+            _index_range: &NumericRange,
+        ) -> Variant {
+            Variant::Empty
+        }
+
+        fn is_of_type(&self, type_definition_id: &NodeId, include_subtypes: bool) -> bool {
+            let own_type = &self.graph.types[self.index];
+            if include_subtypes {
+                self.graph
+                    .type_tree
+                    .is_subtype_of(own_type, type_definition_id)
+            } else {
+                own_type == type_definition_id
+            }
+        }
+
+        fn get_related(&self, reference_type_id: &NodeId, include_subtypes: bool) -> Vec<Self> {
+            self.graph
+                .references
+                .iter()
+                .filter(|(source, _, ref_type)| {
+                    *source == self.index
+                        && (ref_type == reference_type_id
+                            || include_subtypes
+                                && self
+                                    .graph
+                                    .type_tree
+                                    .is_subtype_of(ref_type, reference_type_id))
+                })
+                .map(|(_, target, _)| TestNode {
+                    index: *target,
+                    graph: self.graph,
+                })
+                .collect()
+        }
+    }
+
+    fn test_graph() -> TestGraph {
+        let mut type_tree = DefaultTypeTree::new();
+        let base = NodeId::new(1, "base");
+        let derived = NodeId::new(1, "derived");
+        let other = NodeId::new(1, "other");
+        type_tree.add_type_node(
+            &base,
+            &ObjectTypeId::BaseObjectType.into(),
+            NodeClass::ObjectType,
+        );
+        type_tree.add_type_node(&derived, &base, NodeClass::ObjectType);
+        type_tree.add_type_node(
+            &other,
+            &ObjectTypeId::BaseObjectType.into(),
+            NodeClass::ObjectType,
+        );
+        type_tree.add_type_node(
+            &ReferenceTypeId::Organizes.into(),
+            &ReferenceTypeId::HierarchicalReferences.into(),
+            NodeClass::ReferenceType,
+        );
+
+        // base -> derived -> other, all connected with Organizes references.
+        TestGraph {
+            type_tree,
+            types: vec![base, derived, other],
+            references: vec![
+                (0, 1, ReferenceTypeId::Organizes.into()),
+                (1, 2, ReferenceTypeId::Organizes.into()),
+            ],
+        }
+    }
+
+    fn complex_filter(
+        elements: Vec<ContentFilterElement>,
+        type_tree: &DefaultTypeTree,
+    ) -> ParsedContentFilter {
+        let (_, f) = ParsedContentFilter::parse(
+            ContentFilter {
+                elements: Some(elements),
+            },
+            type_tree,
+            false,
+            true,
+        );
+        f.unwrap()
+    }
+
+    #[test]
+    fn test_of_type() {
+        let graph = test_graph();
+        let f = complex_filter(
+            vec![filter_elem(
+                &[Operand::literal(NodeId::new(1, "base"))],
+                FilterOperator::OfType,
+            )],
+            &graph.type_tree,
+        );
+        let node = |index| TestNode {
+            index,
+            graph: &graph,
+        };
+        assert!(f.evaluate(node(0)));
+        // Subtypes are always included.
+        assert!(f.evaluate(node(1)));
+        assert!(!f.evaluate(node(2)));
+    }
+
+    #[test]
+    fn test_related_to() {
+        let graph = test_graph();
+        let related_to =
+            |source: &'static str, target: &'static str, hops: u32, include_subtypes: bool| {
+                complex_filter(
+                    vec![filter_elem(
+                        &[
+                            Operand::literal(NodeId::new(1, source)),
+                            Operand::literal(NodeId::new(1, target)),
+                            Operand::literal(NodeId::from(ReferenceTypeId::HierarchicalReferences)),
+                            Operand::literal(hops),
+                            Operand::literal(true),
+                            Operand::literal(include_subtypes),
+                        ],
+                        FilterOperator::RelatedTo,
+                    )],
+                    &graph.type_tree,
+                )
+            };
+        let node = |index| TestNode {
+            index,
+            graph: &graph,
+        };
+
+        // Reference type subtypes must be requested.
+        assert!(!related_to("base", "derived", 1, false).evaluate(node(0)));
+        assert!(related_to("base", "derived", 1, true).evaluate(node(0)));
+        // Type definition subtypes are included, so `derived` is also a `base`.
+        assert!(related_to("base", "other", 1, true).evaluate(node(1)));
+        // `other` is two hops away from the first node.
+        assert!(!related_to("base", "other", 1, true).evaluate(node(0)));
+        assert!(related_to("base", "other", 2, true).evaluate(node(0)));
+        // The source must match the first operand.
+        assert!(!related_to("other", "derived", 1, true).evaluate(node(0)));
+
+        // The target can be another RelatedTo element, which the target must pass.
+        let f = complex_filter(
+            vec![
+                filter_elem(
+                    &[
+                        Operand::literal(NodeId::new(1, "base")),
+                        Operand::element(1),
+                        Operand::literal(NodeId::from(ReferenceTypeId::Organizes)),
+                        Operand::literal(1u32),
+                        Operand::literal(false),
+                        Operand::literal(false),
+                    ],
+                    FilterOperator::RelatedTo,
+                ),
+                filter_elem(
+                    &[
+                        Operand::literal(NodeId::new(1, "derived")),
+                        Operand::literal(NodeId::new(1, "other")),
+                        Operand::literal(NodeId::from(ReferenceTypeId::Organizes)),
+                        Operand::literal(1u32),
+                        Operand::literal(false),
+                        Operand::literal(false),
+                    ],
+                    FilterOperator::RelatedTo,
+                ),
+            ],
+            &graph.type_tree,
+        );
+        assert!(f.evaluate(node(0)));
+    }
 }
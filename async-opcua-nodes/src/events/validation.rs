@@ -223,6 +223,24 @@ fn validate_select_clause(
         });
     }
 
+    // An empty browse path refers to the node itself. For events this is the object the
+    // event was reported for, which is how clients select the `ConditionId` of condition
+    // events. For queries it is the node being evaluated.
+    if path.is_empty() {
+        if !matches!(
+            type_tree.get(&clause.type_definition_id),
+            Some(NodeClass::ObjectType | NodeClass::VariableType)
+        ) {
+            return Err(StatusCode::BadNodeIdUnknown);
+        }
+        return Ok(ParsedSimpleAttributeOperand {
+            type_definition_id: clause.type_definition_id,
+            browse_path: path,
+            attribute_id,
+            index_range: clause.index_range,
+        });
+    }
+
     let Some(node) = type_tree.find_type_prop_by_browse_path(&clause.type_definition_id, &path)
     else {
         return Err(StatusCode::BadNodeIdUnknown);
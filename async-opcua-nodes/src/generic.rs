@@ -70,6 +70,7 @@ macro_rules! base {
                 $attrs,
                 user_write_mask
             ),
+            role_permissions: None,
         }
     }};
 }
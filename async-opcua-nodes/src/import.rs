@@ -4,7 +4,7 @@ use super::NodeType;
 
 pub use opcua_types::NodeSetNamespaceMapper;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// A reference produced by a type implementing [`NodeSetImport`].
 /// Note that the source of this reference is given by the node in the outer [`ImportedItem`]
 pub struct ImportedReference {
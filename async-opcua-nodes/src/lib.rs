@@ -12,6 +12,7 @@ mod generic;
 mod import;
 mod references;
 mod type_tree;
+mod ua_object;
 #[cfg(feature = "xml")]
 mod xml;
 #[cfg(feature = "xml")]
@@ -33,11 +34,12 @@ pub use references::{Reference, ReferenceRef, References};
 pub use type_tree::{
     DefaultTypeTree, TypeProperty, TypePropertyInverseRef, TypeTree, TypeTreeNode,
 };
+pub use ua_object::{ObjectBinding, UaObject};
 pub use variable::{Variable, VariableBuilder};
 pub use variable_type::{VariableType, VariableTypeBuilder};
 pub use view::{View, ViewBuilder};
 
-pub use opcua_macros::{Event, EventField};
+pub use opcua_macros::{Event, EventField, UaObject};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 /// Direction of a reference in the address space.
@@ -141,6 +143,15 @@ macro_rules! node_builder_impl {
                 self
             }
 
+            /// Sets the permissions of each role on the node
+            pub fn role_permissions(
+                mut self,
+                role_permissions: Vec<opcua_types::RolePermissionType>,
+            ) -> Self {
+                self.node.set_role_permissions(Some(role_permissions));
+                self
+            }
+
             /// Adds a reference to the node
             pub fn reference<T>(
                 mut self,
@@ -337,7 +348,7 @@ macro_rules! node_builder_impl_property_of {
 macro_rules! node_base_impl {
     ( $node_struct:ident ) => {
         use crate::NodeType;
-        use opcua_types::{NodeClass, WriteMask};
+        use opcua_types::{NodeClass, RolePermissionType, WriteMask};
 
         impl From<$node_struct> for NodeType {
             fn from(value: $node_struct) -> Self {
@@ -389,6 +400,14 @@ macro_rules! node_base_impl {
             fn set_user_write_mask(&mut self, user_write_mask: WriteMask) {
                 self.base.set_user_write_mask(user_write_mask)
             }
+
+            fn role_permissions(&self) -> Option<&[RolePermissionType]> {
+                self.base.role_permissions()
+            }
+
+            fn set_role_permissions(&mut self, role_permissions: Option<Vec<RolePermissionType>>) {
+                self.base.set_role_permissions(role_permissions)
+            }
         }
     };
 }
@@ -4,7 +4,8 @@
 
 use opcua_types::{
     status_code::StatusCode, AttributeId, DataEncoding, DataValue, LocalizedText, NodeClass,
-    NodeId, NumericRange, QualifiedName, TimestampsToReturn, Variant, WriteMask,
+    NodeId, NumericRange, QualifiedName, RolePermissionType, TimestampsToReturn, Variant,
+    WriteMask,
 };
 
 use super::{DataType, Method, Object, ObjectType, ReferenceType, Variable, VariableType, View};
@@ -121,6 +122,13 @@ pub trait NodeBase {
 
     /// Set the user write mask for this node.
     fn set_user_write_mask(&mut self, write_mask: WriteMask);
+
+    /// Get the permissions of each role on this node. If this is not set,
+    /// the default role permissions of the namespace apply.
+    fn role_permissions(&self) -> Option<&[RolePermissionType]>;
+
+    /// Set the permissions of each role on this node.
+    fn set_role_permissions(&mut self, role_permissions: Option<Vec<RolePermissionType>>);
 }
 
 /// Implemented by each node type's to provide a generic way to set or get attributes, e.g.
@@ -264,6 +264,35 @@ impl References {
             .unwrap_or_default()
     }
 
+    /// Return an iterator over all references from or to the given node,
+    /// with `node_id` as the source.
+    pub fn node_references<'a>(
+        &'a self,
+        node_id: &NodeId,
+    ) -> impl Iterator<Item = ReferenceRef<'a>> + 'a {
+        let forward = self
+            .by_source
+            .get(node_id)
+            .into_iter()
+            .flatten()
+            .map(|r| ReferenceRef {
+                reference_type: &r.reference_type,
+                target_node: &r.target_node,
+                direction: ReferenceDirection::Forward,
+            });
+        let inverse = self
+            .by_target
+            .get(node_id)
+            .into_iter()
+            .flatten()
+            .map(|r| ReferenceRef {
+                reference_type: &r.reference_type,
+                target_node: &r.target_node,
+                direction: ReferenceDirection::Inverse,
+            });
+        forward.chain(inverse)
+    }
+
     /// Return an iterator over references matching the given filters.
     pub fn find_references<'a: 'b, 'b>(
         &'a self,
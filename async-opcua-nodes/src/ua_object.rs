@@ -0,0 +1,392 @@
+//! Contains the [UaObject] trait, used to insert rust structs into the address space
+//! as object instances, and the [ObjectBinding] connecting the struct to the created nodes.
+
+use std::collections::HashMap;
+
+use opcua_types::{
+    DataValue, DateTime, Identifier, MethodArg, NodeId, NumericRange, ObjectTypeId, QualifiedName,
+    VariableTypeId, Variant,
+};
+
+use crate::{NodeInsertTarget, ObjectBuilder, VariableBuilder};
+
+/// Trait for rust structs that can be inserted into the address space as an object
+/// instance. This is usually derived using `#[derive(UaObject)]`.
+///
+/// Each field is represented by a child node of the object, the values of variables
+/// and properties are taken from the struct when inserting the object, and later
+/// through [ObjectBinding::values].
+pub trait UaObject {
+    /// The type definition of the object, `BaseObjectType` by default.
+    fn type_definition() -> NodeId {
+        ObjectTypeId::BaseObjectType.into()
+    }
+
+    /// Insert the children of this object into the address space, registering
+    /// them with `binding`.
+    fn insert_children(
+        &self,
+        address_space: &mut impl NodeInsertTarget,
+        binding: &mut ObjectBinding,
+    );
+
+    /// Append the current value of each variable and property of this object,
+    /// including those of child objects, in the order they were inserted by
+    /// [UaObject::insert_children].
+    fn collect_values(&self, values: &mut Vec<Variant>);
+
+    /// Insert the object built by `builder` into the address space, along with all its
+    /// children. A `HasTypeDefinition` reference to [UaObject::type_definition] is
+    /// added to the object.
+    ///
+    /// The node IDs of child nodes are derived from the node ID of the object, see
+    /// [ObjectBinding::child_id].
+    fn insert(
+        &self,
+        address_space: &mut impl NodeInsertTarget,
+        builder: ObjectBuilder,
+    ) -> ObjectBinding {
+        let node_id = builder.get_node_id().clone();
+        builder
+            .has_type_definition(Self::type_definition())
+            .insert(address_space);
+        let mut binding = ObjectBinding::new(node_id);
+        self.insert_children(address_space, &mut binding);
+        binding
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The nodes created for an object implementing [UaObject].
+///
+/// Use [ObjectBinding::values] to write the current state of the struct to the
+/// address space, for example with `InMemoryNodeManager::set_values`.
+///
+/// The binding only goes one way, values written to the nodes by clients are not
+/// copied back to the struct. To handle writes, register write callbacks for the
+/// nodes returned by [ObjectBinding::get] with the node manager.
+pub struct ObjectBinding {
+    node_id: NodeId,
+    nodes: HashMap<String, NodeId>,
+    variables: Vec<NodeId>,
+    parent: NodeId,
+    path: String,
+}
+
+impl ObjectBinding {
+    /// Create a new empty binding for the object with ID `node_id`.
+    pub fn new(node_id: NodeId) -> Self {
+        Self {
+            parent: node_id.clone(),
+            node_id,
+            nodes: HashMap::new(),
+            variables: Vec::new(),
+            path: String::new(),
+        }
+    }
+
+    /// Get the node ID of the object.
+    pub fn node_id(&self) -> &NodeId {
+        &self.node_id
+    }
+
+    /// Get the node ID of a child node by its path relative to the object,
+    /// which is the browse names of the nodes separated by `/`, e.g. `Motor/Speed`.
+    pub fn get(&self, path: &str) -> Option<&NodeId> {
+        self.nodes.get(path)
+    }
+
+    /// Get the node IDs of all variables and properties of the object, in the
+    /// same order as the values returned by [UaObject::collect_values].
+    pub fn variables(&self) -> &[NodeId] {
+        &self.variables
+    }
+
+    /// Get the node ID of a child with browse name `name` of the node `parent`.
+    ///
+    /// This is a node in the same namespace, with a string identifier on the form
+    /// `[parent]/[name]`, where `[parent]` is the identifier of the parent without prefix.
+    /// GUIDs are formatted with hyphens, and byte strings as base64.
+    pub fn child_id(parent: &NodeId, name: &str) -> NodeId {
+        let parent_id = match &parent.identifier {
+            Identifier::String(s) => s.as_ref().to_owned(),
+            Identifier::Numeric(n) => n.to_string(),
+            Identifier::Guid(g) => g.to_string(),
+            Identifier::ByteString(b) => b.as_base64(),
+        };
+        NodeId::new(parent.namespace, format!("{parent_id}/{name}"))
+    }
+
+    /// Get the current values of all variables and properties in `object`, on a form
+    /// that can be passed to `InMemoryNodeManager::set_values`.
+    ///
+    /// `object` must be the same object, or an object of the same type, as the one used
+    /// to create this binding.
+    pub fn values<'a>(
+        &'a self,
+        object: &impl UaObject,
+    ) -> impl Iterator<Item = (&'a NodeId, Option<&'a NumericRange>, DataValue)> {
+        let mut values = Vec::with_capacity(self.variables.len());
+        object.collect_values(&mut values);
+        let now = DateTime::now();
+        self.variables
+            .iter()
+            .zip(values)
+            .map(move |(id, value)| (id, None, DataValue::new_at(value, now)))
+    }
+
+    fn child_path(&self, name: &str) -> String {
+        if self.path.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}/{}", self.path, name)
+        }
+    }
+
+    fn add_node(&mut self, name: &str) -> NodeId {
+        let node_id = Self::child_id(&self.parent, name);
+        self.nodes.insert(self.child_path(name), node_id.clone());
+        node_id
+    }
+
+    /// Add a variable with browse name `name` as a child of the current object.
+    /// The data type and value rank are given by the [MethodArg] implementation of `T`.
+    ///
+    /// If `property` is true the variable is a property with a `HasProperty` reference,
+    /// otherwise it is a `BaseDataVariableType` component of the object.
+    pub fn add_variable<T: MethodArg + Into<Variant>>(
+        &mut self,
+        address_space: &mut impl NodeInsertTarget,
+        name: &str,
+        value: T,
+        property: bool,
+    ) {
+        let node_id = self.add_node(name);
+        let browse_name = QualifiedName::new(node_id.namespace, name);
+        let mut builder = VariableBuilder::new(&node_id, browse_name, name)
+            .data_type(T::data_type())
+            .value_rank(T::value_rank())
+            .value(value);
+        if let Some(dims) = T::array_dimensions() {
+            builder = builder.array_dimensions(&dims);
+        }
+        builder = if property {
+            builder
+                .has_type_definition(VariableTypeId::PropertyType)
+                .property_of(self.parent.clone())
+        } else {
+            builder
+                .has_type_definition(VariableTypeId::BaseDataVariableType)
+                .component_of(self.parent.clone())
+        };
+        builder.insert(address_space);
+        self.variables.push(node_id);
+    }
+
+    /// Add an object with browse name `name` as a component of the current object,
+    /// then insert the children of `object` under it.
+    pub fn add_object<T: UaObject>(
+        &mut self,
+        address_space: &mut impl NodeInsertTarget,
+        name: &str,
+        object: &T,
+    ) {
+        let node_id = self.add_node(name);
+        let browse_name = QualifiedName::new(node_id.namespace, name);
+        ObjectBuilder::new(&node_id, browse_name, name)
+            .has_type_definition(T::type_definition())
+            .component_of(self.parent.clone())
+            .insert(address_space);
+
+        let path = self.child_path(name);
+        let parent = std::mem::replace(&mut self.parent, node_id);
+        let path = std::mem::replace(&mut self.path, path);
+        object.insert_children(address_space, self);
+        self.parent = parent;
+        self.path = path;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod opcua {
+        pub use crate as nodes;
+        pub use opcua_types as types;
+    }
+
+    use std::str::FromStr;
+
+    use opcua_types::{
+        ByteString, DataTypeId, Guid, NodeId, ObjectId, ObjectTypeId, ReferenceTypeId,
+        VariableTypeId, Variant,
+    };
+
+    use crate::{
+        NodeInsertTarget, NodeType, ObjectBinding, ObjectBuilder, ReferenceDirection, UaObject,
+    };
+
+    type InsertedNode = (NodeType, Vec<(NodeId, NodeId, ReferenceDirection)>);
+
+    #[derive(Default)]
+    struct Nodes {
+        nodes: Vec<InsertedNode>,
+    }
+
+    impl NodeInsertTarget for Nodes {
+        fn insert<'a>(
+            &mut self,
+            node: impl Into<NodeType>,
+            references: Option<&'a [(&'a NodeId, &NodeId, ReferenceDirection)]>,
+        ) -> bool {
+            let references = references
+                .unwrap_or_default()
+                .iter()
+                .map(|(t, r, d)| ((*t).clone(), (*r).clone(), *d))
+                .collect();
+            self.nodes.push((node.into(), references));
+            true
+        }
+    }
+
+    impl Nodes {
+        fn get(&self, id: &NodeId) -> &InsertedNode {
+            self.nodes
+                .iter()
+                .find(|n| n.0.as_node().node_id() == id)
+                .unwrap_or_else(|| panic!("Node {id} not found"))
+        }
+
+        fn has_reference(
+            &self,
+            id: &NodeId,
+            target: impl Into<NodeId>,
+            ty: ReferenceTypeId,
+        ) -> bool {
+            let target = target.into();
+            let ty: NodeId = ty.into();
+            self.get(id).1.iter().any(|r| r.0 == target && r.1 == ty)
+        }
+    }
+
+    #[derive(UaObject)]
+    struct Motor {
+        speed: f64,
+        #[opcua(property)]
+        serial_number: String,
+    }
+
+    #[derive(UaObject)]
+    #[opcua(type_definition = ObjectTypeId::FolderType)]
+    struct Station {
+        #[opcua(rename = "FlowRate")]
+        flow: f64,
+        levels: Vec<i32>,
+        #[opcua(object)]
+        motor: Motor,
+        #[opcua(ignore)]
+        _internal: u32,
+    }
+
+    #[test]
+    fn insert_object() {
+        let mut station = Station {
+            flow: 1.5,
+            levels: vec![1, 2],
+            motor: Motor {
+                speed: 100.0,
+                serial_number: "ABC".to_owned(),
+            },
+            _internal: 0,
+        };
+        let mut nodes = Nodes::default();
+        let id = NodeId::new(2, "Station");
+        let binding = station.insert(
+            &mut nodes,
+            ObjectBuilder::new(&id, "Station", "Station").organized_by(ObjectId::ObjectsFolder),
+        );
+
+        assert_eq!(nodes.nodes.len(), 6);
+        assert_eq!(binding.node_id(), &id);
+        assert!(nodes.has_reference(
+            &id,
+            ObjectTypeId::FolderType,
+            ReferenceTypeId::HasTypeDefinition
+        ));
+
+        let flow = binding.get("FlowRate").unwrap();
+        assert_eq!(flow, &NodeId::new(2, "Station/FlowRate"));
+        assert!(nodes.has_reference(flow, id.clone(), ReferenceTypeId::HasComponent));
+        assert!(nodes.has_reference(
+            flow,
+            VariableTypeId::BaseDataVariableType,
+            ReferenceTypeId::HasTypeDefinition
+        ));
+        let NodeType::Variable(v) = &nodes.get(flow).0 else {
+            panic!("Expected variable");
+        };
+        assert_eq!(v.data_type(), DataTypeId::Double);
+        assert_eq!(v.value_rank(), -1);
+
+        let levels = binding.get("Levels").unwrap();
+        let NodeType::Variable(v) = &nodes.get(levels).0 else {
+            panic!("Expected variable");
+        };
+        assert_eq!(v.data_type(), DataTypeId::Int32);
+        assert_eq!(v.value_rank(), 1);
+
+        let motor = binding.get("Motor").unwrap();
+        assert!(matches!(nodes.get(motor).0, NodeType::Object(_)));
+        assert!(nodes.has_reference(motor, id.clone(), ReferenceTypeId::HasComponent));
+        let serial = binding.get("Motor/SerialNumber").unwrap();
+        assert_eq!(serial, &NodeId::new(2, "Station/Motor/SerialNumber"));
+        assert!(nodes.has_reference(serial, motor.clone(), ReferenceTypeId::HasProperty));
+        assert!(nodes.has_reference(
+            serial,
+            VariableTypeId::PropertyType,
+            ReferenceTypeId::HasTypeDefinition
+        ));
+        assert!(binding.get("Internal").is_none());
+
+        station.motor.speed = 120.0;
+        let values: Vec<_> = binding
+            .values(&station)
+            .map(|(id, range, value)| {
+                assert!(range.is_none());
+                (id.clone(), value.value.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (flow.clone(), Variant::Double(1.5)),
+                (levels.clone(), Variant::from(vec![1, 2])),
+                (
+                    binding.get("Motor/Speed").unwrap().clone(),
+                    Variant::Double(120.0)
+                ),
+                (serial.clone(), Variant::from("ABC")),
+            ]
+        );
+    }
+
+    #[test]
+    fn child_id() {
+        assert_eq!(
+            ObjectBinding::child_id(&NodeId::new(1, 5), "Child"),
+            NodeId::new(1, "5/Child")
+        );
+        assert_eq!(
+            ObjectBinding::child_id(&NodeId::new(1, "Parent"), "Child"),
+            NodeId::new(1, "Parent/Child")
+        );
+        let guid = Guid::from_str("72962b91-fa75-4ae6-8d28-b404dc7daf63").unwrap();
+        assert_eq!(
+            ObjectBinding::child_id(&NodeId::new(1, guid), "Child"),
+            NodeId::new(1, "72962b91-fa75-4ae6-8d28-b404dc7daf63/Child")
+        );
+        assert_eq!(
+            ObjectBinding::child_id(&NodeId::new(1, ByteString::from(b"abc")), "Child"),
+            NodeId::new(1, "YWJj/Child")
+        );
+    }
+}
@@ -8,8 +8,8 @@ use hashbrown::HashMap;
 use log::warn;
 use opcua_types::{
     Context, DataTypeDefinition, DataValue, DecodingOptions, EnumDefinition, EnumField, Error,
-    LocalizedText, NodeClass, NodeId, QualifiedName, StructureDefinition, StructureField,
-    StructureType, TypeLoader, TypeLoaderCollection, Variant,
+    LocalizedText, NodeClass, NodeId, PermissionType, QualifiedName, RolePermissionType,
+    StructureDefinition, StructureField, StructureType, TypeLoader, TypeLoaderCollection, Variant,
 };
 use opcua_xml::{
     load_nodeset2_file,
@@ -22,8 +22,8 @@ use opcua_xml::{
 use regex::Regex;
 
 use crate::{
-    Base, DataType, EventNotifier, ImportedItem, ImportedReference, Method, NodeSetImport, Object,
-    ObjectType, ReferenceType, Variable, VariableType, View,
+    Base, DataType, EventNotifier, ImportedItem, ImportedReference, Method, NodeBase,
+    NodeSetImport, Object, ObjectType, ReferenceType, Variable, VariableType, View,
 };
 
 /// [`NodeSetImport`] implementation for dynamically loading NodeSet2 files at
@@ -276,7 +276,7 @@ impl NodeSet2Import {
         base: &ua_node_set::UANodeBase,
         node_class: NodeClass,
     ) -> Result<Base, Error> {
-        Ok(Base::new_full(
+        let mut res = Base::new_full(
             self.make_node_id(&base.node_id, ctx)?,
             node_class,
             self.make_qualified_name(&base.browse_name, ctx)?,
@@ -285,7 +285,21 @@ impl NodeSet2Import {
             self.select_localized_text(&base.description),
             Some(base.write_mask.0),
             Some(base.user_write_mask.0),
-        ))
+        );
+        if let Some(role_permissions) = &base.role_permissions {
+            let role_permissions = role_permissions
+                .role_permissions
+                .iter()
+                .map(|p| {
+                    Ok(RolePermissionType {
+                        role_id: self.make_node_id(&p.node_id, ctx)?,
+                        permissions: PermissionType::from_bits_truncate(p.permissions as i32),
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            res.set_role_permissions(Some(role_permissions));
+        }
+        Ok(res)
     }
 
     fn make_references(
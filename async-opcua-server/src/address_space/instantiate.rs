@@ -0,0 +1,715 @@
+//! Creating instances of object and variable types, see [TypeInstance].
+
+use hashbrown::HashSet;
+use opcua_nodes::{
+    HasNodeId, ImportedItem, ImportedReference, NodeBase, NodeType, ObjectBinding, ObjectBuilder,
+    ReferenceDirection, TypeTree, VariableBuilder,
+};
+use opcua_types::{
+    BrowseDirection, DataEncoding, LocalizedText, NodeId, NumericRange, ObjectId, QualifiedName,
+    ReferenceTypeId, StatusCode, TimestampsToReturn,
+};
+
+use super::AddressSpace;
+
+type ChildIdFn = Box<dyn Fn(&NodeId, &QualifiedName) -> NodeId + Send + Sync>;
+
+/// Description of an instance of an `ObjectType` or `VariableType`.
+///
+/// The instance is created with all the children of the type, and its supertypes,
+/// that have the `Mandatory` modelling rule, and optionally those that have the
+/// `Optional` modelling rule. Children are copied recursively, including the
+/// mandatory children of their own type definitions. Methods are not copied,
+/// instead the instance references the method declared on the type.
+///
+/// By default, the node IDs of children are given by [ObjectBinding::child_id],
+/// so the child `Speed` of an object `ns=2;s=Pump` gets the ID `ns=2;s=Pump/Speed`.
+///
+/// # Example
+///
+/// ```ignore
+/// let instance = TypeInstance::new(&NodeId::new(2, "Pump"), "Pump", "Pump", pump_type_id)
+///     .organized_by(ObjectId::ObjectsFolder)
+///     .include_optional(true);
+/// address_space.instantiate_type(&type_tree, &instance)?;
+/// ```
+pub struct TypeInstance {
+    node_id: NodeId,
+    browse_name: QualifiedName,
+    display_name: LocalizedText,
+    type_definition: NodeId,
+    references: Vec<ImportedReference>,
+    include_optional: bool,
+    child_id: ChildIdFn,
+}
+
+impl TypeInstance {
+    /// Create a new instance description of the type `type_definition`.
+    pub fn new(
+        node_id: &NodeId,
+        browse_name: impl Into<QualifiedName>,
+        display_name: impl Into<LocalizedText>,
+        type_definition: impl Into<NodeId>,
+    ) -> Self {
+        Self {
+            node_id: node_id.clone(),
+            browse_name: browse_name.into(),
+            display_name: display_name.into(),
+            type_definition: type_definition.into(),
+            references: Vec::new(),
+            include_optional: false,
+            child_id: Box::new(|parent, name| ObjectBinding::child_id(parent, name.name.as_ref())),
+        }
+    }
+
+    /// Set whether to create children with the `Optional` modelling rule.
+    pub fn include_optional(mut self, include_optional: bool) -> Self {
+        self.include_optional = include_optional;
+        self
+    }
+
+    /// Set the function used to create the node ID of a child, given the node ID
+    /// of its parent and its browse name.
+    pub fn child_ids(
+        mut self,
+        child_id: impl Fn(&NodeId, &QualifiedName) -> NodeId + Send + Sync + 'static,
+    ) -> Self {
+        self.child_id = Box::new(child_id);
+        self
+    }
+
+    /// Add a reference from the instance to another node.
+    pub fn reference(
+        mut self,
+        target_id: impl Into<NodeId>,
+        reference_type: impl Into<NodeId>,
+        direction: ReferenceDirection,
+    ) -> Self {
+        self.references.push(ImportedReference {
+            target_id: target_id.into(),
+            type_id: reference_type.into(),
+            is_forward: matches!(direction, ReferenceDirection::Forward),
+        });
+        self
+    }
+
+    /// Indicates the instance is organized by the given node.
+    pub fn organized_by(self, parent_id: impl Into<NodeId>) -> Self {
+        self.reference(
+            parent_id,
+            ReferenceTypeId::Organizes,
+            ReferenceDirection::Inverse,
+        )
+    }
+
+    /// Indicates the instance is a component of the given node.
+    pub fn component_of(self, parent_id: impl Into<NodeId>) -> Self {
+        self.reference(
+            parent_id,
+            ReferenceTypeId::HasComponent,
+            ReferenceDirection::Inverse,
+        )
+    }
+
+    /// Create the nodes of the instance, reading the type and its children from `types`.
+    ///
+    /// `types` may be a different address space than the one the instance is inserted
+    /// into, for example one that the generated core namespace or a `NodeSet2.xml`
+    /// file has been imported into. Children of supertypes that are not in `types`
+    /// are not created.
+    ///
+    /// Returns `BadTypeDefinitionInvalid` if the type is not a known non-abstract
+    /// `ObjectType` or `VariableType`.
+    pub fn build(
+        &self,
+        types: &AddressSpace,
+        type_tree: &dyn TypeTree,
+    ) -> Result<Vec<ImportedItem>, StatusCode> {
+        let root = match types.find_node(&self.type_definition) {
+            Some(NodeType::ObjectType(t)) if !t.is_abstract() => {
+                let mut builder = ObjectBuilder::new(
+                    &self.node_id,
+                    self.browse_name.clone(),
+                    self.display_name.clone(),
+                );
+                if let Some(description) = t.description() {
+                    builder = builder.description(description.clone());
+                }
+                NodeType::Object(Box::new(builder.build()))
+            }
+            Some(NodeType::VariableType(t)) if !t.is_abstract() => {
+                let mut builder = VariableBuilder::new(
+                    &self.node_id,
+                    self.browse_name.clone(),
+                    self.display_name.clone(),
+                )
+                .data_type(t.data_type().clone())
+                .value_rank(t.value_rank());
+                if let Some(dims) = t.array_dimensions() {
+                    builder = builder.array_dimensions(&dims);
+                }
+                if let Some(value) = t.value().and_then(|v| v.value.clone()) {
+                    builder = builder.value(value);
+                }
+                if let Some(description) = t.description() {
+                    builder = builder.description(description.clone());
+                }
+                NodeType::Variable(Box::new(builder.build()))
+            }
+            _ => return Err(StatusCode::BadTypeDefinitionInvalid),
+        };
+
+        let mut references = self.references.clone();
+        references.push(ImportedReference {
+            target_id: self.type_definition.clone(),
+            type_id: ReferenceTypeId::HasTypeDefinition.into(),
+            is_forward: true,
+        });
+        let mut items = vec![ImportedItem {
+            node: root,
+            references,
+        }];
+
+        let sources = supertypes(types, type_tree, &self.type_definition);
+        let mut type_stack = vec![self.type_definition.clone()];
+        self.add_children(types, type_tree, 0, &sources, &mut type_stack, &mut items);
+
+        Ok(items)
+    }
+
+    fn add_children(
+        &self,
+        types: &AddressSpace,
+        type_tree: &dyn TypeTree,
+        parent_idx: usize,
+        sources: &[NodeId],
+        type_stack: &mut Vec<NodeId>,
+        items: &mut Vec<ImportedItem>,
+    ) {
+        let parent_id = items[parent_idx].node.node_id().clone();
+        for (decl, reference_type) in self.declarations(types, type_tree, sources) {
+            if let NodeType::Method(m) = decl {
+                items[parent_idx].references.push(ImportedReference {
+                    target_id: m.node_id().clone(),
+                    type_id: reference_type,
+                    is_forward: true,
+                });
+                continue;
+            }
+
+            let child_id = (self.child_id)(&parent_id, decl.as_node().browse_name());
+            let Some(node) = copy_node(decl, &child_id) else {
+                continue;
+            };
+
+            let mut references = vec![ImportedReference {
+                target_id: parent_id.clone(),
+                type_id: reference_type,
+                is_forward: false,
+            }];
+            let type_definition = types
+                .find_references(
+                    decl.node_id(),
+                    Some((ReferenceTypeId::HasTypeDefinition, false)),
+                    type_tree,
+                    BrowseDirection::Forward,
+                )
+                .next()
+                .map(|r| r.target_node.clone());
+            if let Some(type_definition) = &type_definition {
+                references.push(ImportedReference {
+                    target_id: type_definition.clone(),
+                    type_id: ReferenceTypeId::HasTypeDefinition.into(),
+                    is_forward: true,
+                });
+            }
+            items.push(ImportedItem { node, references });
+            let idx = items.len() - 1;
+
+            // Children declared directly on the instance declaration override
+            // those of its type definition.
+            let mut sources = vec![decl.node_id().clone()];
+            match type_definition {
+                // Avoid infinite recursion if a type contains an instance of itself.
+                Some(t) if !type_stack.contains(&t) => {
+                    sources.extend(supertypes(types, type_tree, &t));
+                    type_stack.push(t);
+                    self.add_children(types, type_tree, idx, &sources, type_stack, items);
+                    type_stack.pop();
+                }
+                _ => self.add_children(types, type_tree, idx, &sources, type_stack, items),
+            }
+        }
+    }
+
+    /// Find the instance declarations of `sources` that should be created,
+    /// along with the reference type used to reference them. Declarations in earlier
+    /// sources override later declarations with the same browse name.
+    fn declarations<'a>(
+        &self,
+        types: &'a AddressSpace,
+        type_tree: &dyn TypeTree,
+        sources: &[NodeId],
+    ) -> Vec<(&'a NodeType, NodeId)> {
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+        for source in sources {
+            for rf in types.find_references(
+                source,
+                None::<(NodeId, bool)>,
+                type_tree,
+                BrowseDirection::Forward,
+            ) {
+                let is_child = type_tree
+                    .is_subtype_of(rf.reference_type, &ReferenceTypeId::HasComponent.into())
+                    || type_tree
+                        .is_subtype_of(rf.reference_type, &ReferenceTypeId::HasProperty.into());
+                if !is_child {
+                    continue;
+                }
+                let Some(node) = types.find_node(rf.target_node) else {
+                    continue;
+                };
+                if !seen.insert(node.as_node().browse_name().clone()) {
+                    continue;
+                }
+                let rule = types
+                    .find_references(
+                        rf.target_node,
+                        Some((ReferenceTypeId::HasModellingRule, false)),
+                        type_tree,
+                        BrowseDirection::Forward,
+                    )
+                    .next()
+                    .map(|r| r.target_node);
+                let include = match rule {
+                    Some(r) if r == &ObjectId::ModellingRule_Mandatory => true,
+                    Some(r) if r == &ObjectId::ModellingRule_Optional => self.include_optional,
+                    _ => false,
+                };
+                if include {
+                    result.push((node, rf.reference_type.clone()));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Get `type_id` followed by each of its supertypes, looking up supertypes
+/// in the address space first, then in the type tree.
+fn supertypes(types: &AddressSpace, type_tree: &dyn TypeTree, type_id: &NodeId) -> Vec<NodeId> {
+    let mut result = vec![type_id.clone()];
+    let mut current = type_id.clone();
+    loop {
+        let parent = types
+            .find_references(
+                &current,
+                Some((ReferenceTypeId::HasSubtype, false)),
+                type_tree,
+                BrowseDirection::Inverse,
+            )
+            .next()
+            .map(|r| r.target_node.clone())
+            .or_else(|| type_tree.get_supertype(&current).cloned());
+        match parent {
+            Some(p) if !result.contains(&p) => {
+                result.push(p.clone());
+                current = p;
+            }
+            _ => break result,
+        }
+    }
+}
+
+/// Copy an object or variable instance declaration to a new node with ID `node_id`.
+fn copy_node(decl: &NodeType, node_id: &NodeId) -> Option<NodeType> {
+    let node = decl.as_node();
+    let browse_name = node.browse_name().clone();
+    let display_name = node.display_name().clone();
+    let mut node = match decl {
+        NodeType::Object(o) => NodeType::Object(Box::new(
+            ObjectBuilder::new(node_id, browse_name, display_name)
+                .event_notifier(o.event_notifier())
+                .build(),
+        )),
+        NodeType::Variable(v) => {
+            let mut builder = VariableBuilder::new(node_id, browse_name, display_name)
+                .data_type(v.data_type())
+                .value_rank(v.value_rank())
+                .access_level(v.access_level())
+                .user_access_level(v.user_access_level())
+                .historizing(v.historizing());
+            if let Some(dims) = v.array_dimensions() {
+                builder = builder.array_dimensions(&dims);
+            }
+            if let Some(interval) = v.minimum_sampling_interval() {
+                builder = builder.minimum_sampling_interval(interval);
+            }
+            let value = v.value(
+                TimestampsToReturn::Neither,
+                &NumericRange::None,
+                &DataEncoding::Binary,
+                0.0,
+            );
+            if let Some(value) = value.value {
+                builder = builder.value(value);
+            }
+            NodeType::Variable(Box::new(builder.build()))
+        }
+        _ => return None,
+    };
+    if let Some(description) = decl.as_node().description() {
+        node.as_mut_node().set_description(description.clone());
+    }
+    if let Some(write_mask) = decl.as_node().write_mask() {
+        node.as_mut_node().set_write_mask(write_mask);
+    }
+    Some(node)
+}
+
+impl AddressSpace {
+    /// Create an instance of an `ObjectType` or `VariableType` defined in this
+    /// address space, see [TypeInstance]. Returns the node IDs of the created nodes,
+    /// starting with the instance itself.
+    ///
+    /// Returns `BadNodeIdExists` if any of the nodes already exist, in which case
+    /// nothing is inserted.
+    pub fn instantiate_type(
+        &mut self,
+        type_tree: &dyn TypeTree,
+        instance: &TypeInstance,
+    ) -> Result<Vec<NodeId>, StatusCode> {
+        let items = instance.build(self, type_tree)?;
+        self.insert_instance(items)
+    }
+
+    /// Insert the nodes created by [TypeInstance::build]. Returns the node IDs of the
+    /// inserted nodes, or `BadNodeIdExists` if any of them already exist.
+    pub fn insert_instance(&mut self, items: Vec<ImportedItem>) -> Result<Vec<NodeId>, StatusCode> {
+        if items.iter().any(|i| self.node_exists(i.node.node_id())) {
+            return Err(StatusCode::BadNodeIdExists);
+        }
+        let mut ids = Vec::with_capacity(items.len());
+        for item in items {
+            ids.push(item.node.node_id().clone());
+            self.import_node(item);
+        }
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_nodes::{
+        DefaultTypeTree, NamespaceMap, NodeType, ObjectBuilder, ObjectTypeBuilder,
+        ReferenceDirection, VariableBuilder,
+    };
+    use opcua_types::{
+        BrowseDirection, DataEncoding, DataTypeId, MethodId, NodeId, NumericRange, ObjectId,
+        ObjectTypeId, QualifiedName, ReferenceTypeId, StatusCode, TimestampsToReturn,
+        VariableTypeId, Variant,
+    };
+
+    use crate::address_space::{AddressSpace, CoreNamespace};
+
+    use super::TypeInstance;
+
+    fn make_address_space() -> (AddressSpace, DefaultTypeTree) {
+        let mut address_space = AddressSpace::new();
+        address_space.add_namespace("http://opcfoundation.org/UA/", 0);
+        let mut namespaces = NamespaceMap::default();
+        address_space.import_node_set(&CoreNamespace, &mut namespaces);
+        address_space.add_namespace("urn:test", 1);
+        add_types(&mut address_space);
+        let mut type_tree = DefaultTypeTree::new();
+        address_space.load_into_type_tree(&mut type_tree);
+        (address_space, type_tree)
+    }
+
+    fn add_declaration(
+        address_space: &mut AddressSpace,
+        parent: &NodeId,
+        name: &str,
+        rule: ObjectId,
+        property: bool,
+        value: i32,
+    ) {
+        let id = NodeId::new(1, format!("{}.{name}", parent.identifier));
+        let builder = VariableBuilder::new(&id, QualifiedName::new(1, name), name)
+            .data_type(DataTypeId::Int32)
+            .value(value)
+            .has_modelling_rule(rule);
+        let builder = if property {
+            builder
+                .has_type_definition(VariableTypeId::PropertyType)
+                .property_of(parent.clone())
+        } else {
+            builder
+                .has_type_definition(VariableTypeId::BaseDataVariableType)
+                .component_of(parent.clone())
+        };
+        builder.insert(address_space);
+    }
+
+    // MotorType has a mandatory Speed variable.
+    // PumpBaseType has a mandatory Flow variable and an optional Serial property.
+    // PumpType is a subtype of PumpBaseType, with a mandatory Motor, overriding
+    // the speed of the motor, and an optional Serial, overriding the base type.
+    fn add_types(address_space: &mut AddressSpace) {
+        let motor_type = NodeId::new(1, "MotorType");
+        ObjectTypeBuilder::new(&motor_type, "MotorType", "MotorType")
+            .subtype_of(ObjectTypeId::BaseObjectType)
+            .insert(address_space);
+        add_declaration(
+            address_space,
+            &motor_type,
+            "Speed",
+            ObjectId::ModellingRule_Mandatory,
+            false,
+            1,
+        );
+
+        let base_type = NodeId::new(1, "PumpBaseType");
+        ObjectTypeBuilder::new(&base_type, "PumpBaseType", "PumpBaseType")
+            .is_abstract(true)
+            .subtype_of(ObjectTypeId::BaseObjectType)
+            .insert(address_space);
+        add_declaration(
+            address_space,
+            &base_type,
+            "Flow",
+            ObjectId::ModellingRule_Mandatory,
+            false,
+            1,
+        );
+        add_declaration(
+            address_space,
+            &base_type,
+            "Serial",
+            ObjectId::ModellingRule_Optional,
+            true,
+            2,
+        );
+
+        let pump_type = NodeId::new(1, "PumpType");
+        ObjectTypeBuilder::new(&pump_type, "PumpType", "PumpType")
+            .subtype_of(base_type.clone())
+            .insert(address_space);
+        let motor = NodeId::new(1, "PumpType.Motor");
+        ObjectBuilder::new(&motor, QualifiedName::new(1, "Motor"), "Motor")
+            .has_type_definition(motor_type)
+            .reference(
+                ObjectId::ModellingRule_Mandatory,
+                ReferenceTypeId::HasModellingRule,
+                ReferenceDirection::Forward,
+            )
+            .component_of(pump_type.clone())
+            .insert(address_space);
+        add_declaration(
+            address_space,
+            &motor,
+            "Speed",
+            ObjectId::ModellingRule_Mandatory,
+            false,
+            10,
+        );
+        add_declaration(
+            address_space,
+            &pump_type,
+            "Serial",
+            ObjectId::ModellingRule_Optional,
+            true,
+            2,
+        );
+    }
+
+    fn value(address_space: &AddressSpace, id: &NodeId) -> Option<Variant> {
+        let Some(NodeType::Variable(v)) = address_space.find_node(id) else {
+            panic!("Expected variable {id}");
+        };
+        v.value(
+            TimestampsToReturn::Neither,
+            &NumericRange::None,
+            &DataEncoding::Binary,
+            0.0,
+        )
+        .value
+    }
+
+    fn has_child(
+        address_space: &AddressSpace,
+        type_tree: &DefaultTypeTree,
+        parent: &NodeId,
+        name: QualifiedName,
+    ) -> bool {
+        address_space
+            .find_node_by_browse_name(
+                parent,
+                Some((ReferenceTypeId::HierarchicalReferences, true)),
+                type_tree,
+                BrowseDirection::Forward,
+                name,
+            )
+            .is_some()
+    }
+
+    #[test]
+    fn instantiate_custom_type() {
+        let (mut address_space, type_tree) = make_address_space();
+        let id = NodeId::new(1, "Pump");
+        let ids = address_space
+            .instantiate_type(
+                &type_tree,
+                &TypeInstance::new(&id, "Pump", "Pump", NodeId::new(1, "PumpType"))
+                    .organized_by(ObjectId::ObjectsFolder),
+            )
+            .unwrap();
+        assert_eq!(
+            ids,
+            vec![
+                id.clone(),
+                NodeId::new(1, "Pump/Motor"),
+                NodeId::new(1, "Pump/Motor/Speed"),
+                NodeId::new(1, "Pump/Flow"),
+            ]
+        );
+        assert!(address_space.has_reference(
+            &ObjectId::ObjectsFolder.into(),
+            &id,
+            ReferenceTypeId::Organizes
+        ));
+        assert!(address_space.has_reference(
+            &id,
+            &NodeId::new(1, "PumpType"),
+            ReferenceTypeId::HasTypeDefinition
+        ));
+        let motor = NodeId::new(1, "Pump/Motor");
+        assert!(address_space.has_reference(&id, &motor, ReferenceTypeId::HasComponent));
+        assert!(address_space.has_reference(
+            &motor,
+            &NodeId::new(1, "MotorType"),
+            ReferenceTypeId::HasTypeDefinition
+        ));
+        // Instances do not have modelling rules.
+        assert!(!address_space.has_reference(
+            &motor,
+            &ObjectId::ModellingRule_Mandatory.into(),
+            ReferenceTypeId::HasModellingRule
+        ));
+        // The declaration on PumpType overrides the one on MotorType.
+        let speed = NodeId::new(1, "Pump/Motor/Speed");
+        assert_eq!(value(&address_space, &speed), Some(Variant::Int32(10)));
+        assert!(has_child(
+            &address_space,
+            &type_tree,
+            &motor,
+            QualifiedName::new(1, "Speed")
+        ));
+        // Children of supertypes are included, optional children are not.
+        let flow = NodeId::new(1, "Pump/Flow");
+        assert_eq!(value(&address_space, &flow), Some(Variant::Int32(1)));
+        assert!(!address_space.node_exists(&NodeId::new(1, "Pump/Serial")));
+
+        // Creating the same instance again fails.
+        assert_eq!(
+            address_space.instantiate_type(
+                &type_tree,
+                &TypeInstance::new(&id, "Pump", "Pump", NodeId::new(1, "PumpType"))
+            ),
+            Err(StatusCode::BadNodeIdExists)
+        );
+        // So does creating an instance of an abstract type.
+        assert_eq!(
+            address_space.instantiate_type(
+                &type_tree,
+                &TypeInstance::new(
+                    &NodeId::new(1, "Pump2"),
+                    "Pump",
+                    "Pump",
+                    NodeId::new(1, "PumpBaseType")
+                )
+            ),
+            Err(StatusCode::BadTypeDefinitionInvalid)
+        );
+    }
+
+    #[test]
+    fn instantiate_optional() {
+        let (mut address_space, type_tree) = make_address_space();
+        let id = NodeId::new(1, 5);
+        address_space
+            .instantiate_type(
+                &type_tree,
+                &TypeInstance::new(&id, "Pump", "Pump", NodeId::new(1, "PumpType"))
+                    .include_optional(true)
+                    .child_ids(|parent, name| {
+                        NodeId::new(1, format!("{}.{}", parent.identifier, name.name))
+                    }),
+            )
+            .unwrap();
+        // The serial number is taken from PumpType, overriding PumpBaseType.
+        let serial = NodeId::new(1, "i=5.Serial");
+        assert_eq!(value(&address_space, &serial), Some(Variant::Int32(2)));
+        assert!(address_space.has_reference(&id, &serial, ReferenceTypeId::HasProperty));
+        assert!(address_space.has_reference(
+            &serial,
+            &VariableTypeId::PropertyType.into(),
+            ReferenceTypeId::HasTypeDefinition
+        ));
+        assert!(address_space.node_exists(&NodeId::new(1, "i=5.Motor")));
+    }
+
+    #[test]
+    fn instantiate_generated_type() {
+        let (mut address_space, type_tree) = make_address_space();
+        let id = NodeId::new(1, "Metadata");
+        address_space
+            .instantiate_type(
+                &type_tree,
+                &TypeInstance::new(
+                    &id,
+                    "Metadata",
+                    "Metadata",
+                    ObjectTypeId::NamespaceMetadataType,
+                )
+                .include_optional(true),
+            )
+            .unwrap();
+
+        for name in ["NamespaceUri", "NamespaceVersion", "IsNamespaceSubset"] {
+            assert!(has_child(&address_space, &type_tree, &id, name.into()));
+        }
+        // The optional NamespaceFile object has the mandatory children of FileType,
+        // the supertype of its type definition.
+        let file = NodeId::new(1, "Metadata/NamespaceFile");
+        assert!(address_space.has_reference(
+            &file,
+            &ObjectTypeId::AddressSpaceFileType.into(),
+            ReferenceTypeId::HasTypeDefinition
+        ));
+        assert!(has_child(&address_space, &type_tree, &file, "Size".into()));
+        // Methods reference the declaration on the type.
+        assert!(address_space.has_reference(
+            &file,
+            &MethodId::NamespaceMetadataType_NamespaceFile_Open.into(),
+            ReferenceTypeId::HasComponent
+        ));
+
+        // Variable types are instantiated as variables.
+        let id = NodeId::new(1, "Analog");
+        address_space
+            .instantiate_type(
+                &type_tree,
+                &TypeInstance::new(&id, "Analog", "Analog", VariableTypeId::AnalogItemType)
+                    .organized_by(ObjectId::ObjectsFolder),
+            )
+            .unwrap();
+        let Some(NodeType::Variable(v)) = address_space.find_node(&id) else {
+            panic!("Expected variable");
+        };
+        assert_eq!(v.data_type(), DataTypeId::Number);
+        assert!(has_child(&address_space, &type_tree, &id, "EURange".into()));
+        assert!(!address_space.node_exists(&NodeId::new(1, "Analog/EngineeringUnits")));
+    }
+}
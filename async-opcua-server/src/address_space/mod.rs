@@ -1,7 +1,9 @@
 //! Implementation of [AddressSpace], and in-memory OPC-UA address space.
 
+mod instantiate;
 mod utils;
 
+pub use instantiate::TypeInstance;
 pub use opcua_nodes::*;
 pub use utils::*;
 
@@ -291,6 +293,15 @@ impl AddressSpace {
             .find_references(source_node, filter, type_tree, direction)
     }
 
+    /// Return an iterator over all references from or to `node_id`,
+    /// regardless of reference type.
+    pub fn node_references<'a>(
+        &'a self,
+        node_id: &NodeId,
+    ) -> impl Iterator<Item = ReferenceRef<'a>> + 'a {
+        self.references.node_references(node_id)
+    }
+
     /// Find a child of `source_node` matching the given `filter` with
     /// browse name equal to `browse_name`.
     pub fn find_node_by_browse_name<'a: 'b, 'b>(
@@ -375,6 +386,11 @@ impl AddressSpace {
         self.node_map.get_mut(node_id)
     }
 
+    /// Return an iterator over all the nodes in this address space, in no particular order.
+    pub fn nodes(&self) -> impl Iterator<Item = &NodeType> {
+        self.node_map.values()
+    }
+
     /// Check if the read is allowed.
     pub fn validate_node_read<'a>(
         &'a self,
@@ -2,8 +2,8 @@ use crate::node_manager::{ParsedReadValueId, ParsedWriteValue, RequestContext, S
 use log::debug;
 use opcua_nodes::TypeTree;
 use opcua_types::{
-    AttributeId, DataEncoding, DataTypeId, DataValue, DateTime, NumericRange, StatusCode,
-    TimestampsToReturn, Variant, WriteMask,
+    AttributeId, DataEncoding, DataTypeId, DataValue, DateTime, ExtensionObject, NumericRange,
+    PermissionType, StatusCode, TimestampsToReturn, Variant, WriteMask,
 };
 
 use super::{AccessLevel, AddressSpace, HasNodeId, NodeType, Variable};
@@ -25,6 +25,21 @@ pub fn is_writable(
     node: &NodeType,
     attribute_id: AttributeId,
 ) -> Result<(), StatusCode> {
+    let permissions = context.info.roles.permissions(context, node);
+    if !permissions.contains(PermissionType::Browse) {
+        return Err(StatusCode::BadNodeIdUnknown);
+    }
+    let required = match (node, attribute_id) {
+        // Writing the value of a variable is covered by the user access level.
+        (NodeType::Variable(_), AttributeId::Value) => PermissionType::empty(),
+        (_, AttributeId::RolePermissions) => PermissionType::WriteRolePermissions,
+        (_, AttributeId::Historizing) => PermissionType::WriteHistorizing,
+        _ => PermissionType::WriteAttribute,
+    };
+    if !permissions.contains(required) {
+        return Err(StatusCode::BadUserAccessDenied);
+    }
+
     if let (NodeType::Variable(_), AttributeId::Value) = (node, attribute_id) {
         if !user_access_level(context, node).contains(AccessLevel::CURRENT_WRITE) {
             return Err(StatusCode::BadUserAccessDenied);
@@ -72,8 +87,8 @@ pub fn is_writable(
 
 /// Get the effective user access level for `node`.
 pub fn user_access_level(context: &RequestContext, node: &NodeType) -> AccessLevel {
-    let user_access_level = if let NodeType::Variable(ref node) = node {
-        node.user_access_level()
+    let user_access_level = if let NodeType::Variable(ref var) = node {
+        role_access_level(context, node, var.user_access_level())
     } else {
         AccessLevel::CURRENT_READ
     };
@@ -84,6 +99,33 @@ pub fn user_access_level(context: &RequestContext, node: &NodeType) -> AccessLev
     )
 }
 
+/// Remove the parts of `access_level` that the roles of the user given by `context`
+/// are not permitted on `node`.
+fn role_access_level(
+    context: &RequestContext,
+    node: &NodeType,
+    mut access_level: AccessLevel,
+) -> AccessLevel {
+    let permissions = context.info.roles.permissions(context, node);
+    if !permissions.contains(PermissionType::Read) {
+        access_level.remove(AccessLevel::CURRENT_READ);
+    }
+    if !permissions.contains(PermissionType::Write) {
+        access_level.remove(AccessLevel::CURRENT_WRITE);
+    }
+    if !permissions.contains(PermissionType::ReadHistory) {
+        access_level.remove(AccessLevel::HISTORY_READ);
+    }
+    if !permissions.intersects(
+        PermissionType::InsertHistory
+            | PermissionType::ModifyHistory
+            | PermissionType::DeleteHistory,
+    ) {
+        access_level.remove(AccessLevel::HISTORY_WRITE);
+    }
+    access_level
+}
+
 /// Validate that the user given by `context` is allowed to read
 /// the value of `node`.
 pub fn validate_node_read(
@@ -91,7 +133,23 @@ pub fn validate_node_read(
     context: &RequestContext,
     node_to_read: &ParsedReadValueId,
 ) -> Result<(), StatusCode> {
-    is_readable(context, node)?;
+    let permissions = context.info.roles.permissions(context, node);
+    if !permissions.contains(PermissionType::Browse) {
+        return Err(StatusCode::BadNodeIdUnknown);
+    }
+    if node_to_read.attribute_id == AttributeId::RolePermissions
+        && !permissions.contains(PermissionType::ReadRolePermissions)
+    {
+        return Err(StatusCode::BadUserAccessDenied);
+    }
+
+    // With role permissions, the `Read` permission only covers the value of the node,
+    // the other attributes can be read by any user allowed to browse it.
+    if node.as_node().role_permissions().is_none()
+        || node_to_read.attribute_id == AttributeId::Value
+    {
+        is_readable(context, node)?;
+    }
 
     if node_to_read.attribute_id != AttributeId::Value
         && node_to_read.index_range != NumericRange::None
@@ -204,6 +262,27 @@ pub fn read_node_value(
 ) -> DataValue {
     let mut result_value = DataValue::null();
 
+    // The role permissions that apply to the user depend on the roles of the user.
+    if node_to_read.attribute_id == AttributeId::UserRolePermissions {
+        let Some(role_permissions) = node.as_node().role_permissions() else {
+            result_value.status = Some(StatusCode::BadAttributeIdInvalid);
+            return result_value;
+        };
+        let user_role_permissions = context
+            .info
+            .roles
+            .user_roles(context)
+            .user_role_permissions(role_permissions);
+        result_value.value = Some(Variant::from(
+            user_role_permissions
+                .into_iter()
+                .map(ExtensionObject::from_message)
+                .collect::<Vec<_>>(),
+        ));
+        result_value.status = Some(StatusCode::Good);
+        return result_value;
+    }
+
     let Some(attribute) = node.as_node().get_attribute_max_age(
         timestamps_to_return,
         node_to_read.attribute_id,
@@ -219,6 +298,7 @@ pub fn read_node_value(
         match attribute.value {
             Some(Variant::Byte(val)) => {
                 let access_level = AccessLevel::from_bits_truncate(val);
+                let access_level = role_access_level(context, node, access_level);
                 let access_level = context.authenticator.effective_user_access_level(
                     &context.token,
                     access_level,
@@ -238,7 +318,12 @@ pub fn read_node_value(
             Some(Variant::Boolean(val)) => Some(Variant::from(
                 val && context
                     .authenticator
-                    .is_user_executable(&context.token, node.node_id()),
+                    .is_user_executable(&context.token, node.node_id())
+                    && context
+                        .info
+                        .roles
+                        .permissions(context, node)
+                        .contains(PermissionType::Call),
             )),
             r => r,
         }
@@ -3,14 +3,19 @@
 use async_trait::async_trait;
 
 use log::{debug, error};
-use opcua_crypto::{SecurityPolicy, Thumbprint};
+use opcua_crypto::{password::verify_password, SecurityPolicy, Thumbprint};
 use opcua_types::{
-    Error, MessageSecurityMode, NodeId, StatusCode, UAString, UserTokenPolicy, UserTokenType,
+    ByteString, Error, IdentityCriteriaType, IdentityMappingRuleType, MessageSecurityMode, NodeId,
+    StatusCode, UAString, UserTokenPolicy, UserTokenType,
 };
 
-use crate::identity_token::{
-    POLICY_ID_ANONYMOUS, POLICY_ID_USER_PASS_NONE, POLICY_ID_USER_PASS_RSA_15,
-    POLICY_ID_USER_PASS_RSA_OAEP, POLICY_ID_X509,
+use crate::{
+    identity_token::{
+        POLICY_ID_ANONYMOUS, POLICY_ID_ISSUED_TOKEN, POLICY_ID_USER_PASS_ECC,
+        POLICY_ID_USER_PASS_NONE, POLICY_ID_USER_PASS_RSA_15, POLICY_ID_USER_PASS_RSA_OAEP,
+        POLICY_ID_X509,
+    },
+    jwt::{JwtValidator, JWT_ISSUED_TOKEN_TYPE},
 };
 
 use super::{
@@ -66,6 +71,29 @@ impl UserToken {
     }
 }
 
+/// A user authenticated with an identity token, together with the roles granted by
+/// the token itself, such as the roles in the claims of a JSON Web Token.
+///
+/// The granted roles belong to the session activated with the token, and are dropped
+/// when the session closes or is activated with another token. Other sessions of the
+/// same user are not affected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedUser {
+    /// The user token identifying the user.
+    pub token: UserToken,
+    /// Roles granted to the session by the identity token.
+    pub roles: Vec<String>,
+}
+
+impl From<UserToken> for AuthenticatedUser {
+    fn from(token: UserToken) -> Self {
+        Self {
+            token,
+            roles: Vec::new(),
+        }
+    }
+}
+
 #[allow(unused)]
 #[async_trait]
 /// The AuthManager trait is used to let servers control access to the server.
@@ -119,6 +147,23 @@ pub trait AuthManager: Send + Sync + 'static {
         ))
     }
 
+    /// Validate the issued token for `endpoint`, with the user token policy given by `policy_id`.
+    /// The token data has already been decrypted, and is for example a JSON Web Token,
+    /// which can be validated with a [`JwtValidator`].
+    /// This should return a user token associated with the user, and the roles granted
+    /// by the token to the session being activated.
+    async fn authenticate_issued_identity_token(
+        &self,
+        endpoint: &ServerEndpoint,
+        policy_id: &str,
+        token_data: &ByteString,
+    ) -> Result<AuthenticatedUser, Error> {
+        Err(Error::new(
+            StatusCode::BadIdentityTokenRejected,
+            "Issued identity token unsupported",
+        ))
+    }
+
     /// Return the effective user access level for the given node ID
     fn effective_user_access_level(
         &self,
@@ -135,6 +180,22 @@ pub trait AuthManager: Send + Sync + 'static {
         true
     }
 
+    /// Return whether the user has the `SecurityAdmin` role, which is required to call
+    /// the certificate management methods on the `ServerConfiguration` object.
+    /// Users granted the `SecurityAdmin` role through its identity mapping rules are
+    /// security admins as well.
+    fn is_security_admin(&self, token: &UserToken) -> bool {
+        false
+    }
+
+    /// Return whether the user matches an identity mapping rule of a role, granting
+    /// the user that role. This is called for rules of type `UserName`, `Thumbprint`,
+    /// `Role`, `GroupId` and `X509Subject`, the other rule types are evaluated by the
+    /// server's [`RoleManager`](crate::roles::RoleManager).
+    fn matches_identity(&self, token: &UserToken, rule: &IdentityMappingRuleType) -> bool {
+        false
+    }
+
     /// Return the valid user token policies for the given endpoint.
     /// Only valid tokens will be passed to the authenticator.
     fn user_token_policies(&self, endpoint: &ServerEndpoint) -> Vec<UserTokenPolicy>;
@@ -159,20 +220,63 @@ pub trait AuthManager: Send + Sync + 'static {
             .iter()
             .any(|e| e.token_type == UserTokenType::Certificate)
     }
+
+    /// Return whether the endpoint supports issued token authentication.
+    fn supports_issued_token(&self, endpoint: &ServerEndpoint) -> bool {
+        self.user_token_policies(endpoint)
+            .iter()
+            .any(|e| e.token_type == UserTokenType::IssuedToken)
+    }
 }
 
 /// A simple authenticator that keeps a map of valid users in memory.
 /// In production applications you will almost always want to create your own
 /// custom authenticator.
+///
+/// Users authenticated with a JSON Web Token are identified by the ID of the user token
+/// and the subject of the JWT, separated by a `/`. The roles mapped from the scopes of the
+/// token are granted to the session activated with it, see [`AuthenticatedUser`].
+///
+/// Identity mapping rules of type `Role` match the `roles` of the user token,
+/// `UserName` rules match the user name of username/password users, and `Thumbprint`
+/// rules match the hex encoded thumbprint of X509 users.
 pub struct DefaultAuthenticator {
     users: BTreeMap<String, ServerUserToken>,
+    jwt_validators: BTreeMap<String, JwtValidator>,
 }
 
 impl DefaultAuthenticator {
     /// Create a new default authenticator with the given set of users.
     pub fn new(users: BTreeMap<String, ServerUserToken>) -> Self {
-        Self { users }
+        let mut jwt_validators = BTreeMap::new();
+        for (id, token) in &users {
+            let Some(config) = &token.jwt else {
+                continue;
+            };
+            match JwtValidator::new(config.clone()) {
+                Ok(validator) => {
+                    jwt_validators.insert(id.clone(), validator);
+                }
+                Err(e) => error!("Failed to load JWT validation for user token {id}: {e}"),
+            }
+        }
+        Self {
+            users,
+            jwt_validators,
+        }
     }
+
+    /// Return whether the user has the given role in the configuration of its user token.
+    fn has_role(&self, token: &UserToken, role: &str) -> bool {
+        self.users
+            .get(&token.0)
+            .is_some_and(|u| u.roles.iter().any(|r| r == role))
+    }
+}
+
+/// Get the issued token policy ID for the user token with the given ID.
+fn issued_token_policy_id(user_token_id: &str) -> String {
+    format!("{POLICY_ID_ISSUED_TOKEN}_{user_token_id}")
 }
 
 #[async_trait]
@@ -201,7 +305,9 @@ impl AuthManager for DefaultAuthenticator {
             if let Some(server_user_token) = self.users.get(user_token_id) {
                 if server_user_token.is_user_pass() && server_user_token.user == username {
                     // test for empty password
-                    let valid = if let Some(server_password) = server_user_token.pass.as_ref() {
+                    let valid = if let Some(pass_hash) = server_user_token.pass_hash.as_ref() {
+                        verify_password(token_password, pass_hash)
+                    } else if let Some(server_password) = server_user_token.pass.as_ref() {
                         server_password.as_bytes() == token_password.as_bytes()
                     } else {
                         token_password.is_empty()
@@ -254,6 +360,54 @@ impl AuthManager for DefaultAuthenticator {
         ))
     }
 
+    async fn authenticate_issued_identity_token(
+        &self,
+        endpoint: &ServerEndpoint,
+        policy_id: &str,
+        token_data: &ByteString,
+    ) -> Result<AuthenticatedUser, Error> {
+        for user_token_id in &endpoint.user_token_ids {
+            let Some(validator) = self.jwt_validators.get(user_token_id) else {
+                continue;
+            };
+            if issued_token_policy_id(user_token_id) != policy_id {
+                continue;
+            }
+            let claims = validator.validate(token_data).inspect_err(|e| {
+                error!("Cannot authenticate JSON Web Token for user token {user_token_id}: {e}")
+            })?;
+            return Ok(AuthenticatedUser {
+                token: UserToken(format!("{user_token_id}/{}", claims.subject)),
+                roles: claims.roles,
+            });
+        }
+        Err(Error::new(
+            StatusCode::BadIdentityTokenRejected,
+            "Authentication failed",
+        ))
+    }
+
+    fn is_security_admin(&self, token: &UserToken) -> bool {
+        self.has_role(token, "SecurityAdmin")
+    }
+
+    fn matches_identity(&self, token: &UserToken, rule: &IdentityMappingRuleType) -> bool {
+        match rule.criteria_type {
+            IdentityCriteriaType::Role => self.has_role(token, rule.criteria.as_ref()),
+            IdentityCriteriaType::UserName => self
+                .users
+                .get(&token.0)
+                .is_some_and(|u| u.is_user_pass() && u.user == rule.criteria.as_ref()),
+            IdentityCriteriaType::Thumbprint => self.users.get(&token.0).is_some_and(|u| {
+                u.thumbprint.as_ref().is_some_and(|t| {
+                    t.as_hex_string()
+                        .eq_ignore_ascii_case(rule.criteria.as_ref())
+                })
+            }),
+            _ => false,
+        }
+    }
+
     fn user_token_policies(&self, endpoint: &ServerEndpoint) -> Vec<UserTokenPolicy> {
         let mut user_identity_tokens = Vec::with_capacity(3);
 
@@ -293,6 +447,21 @@ impl AuthManager for DefaultAuthenticator {
                 security_policy_uri: UAString::from(SecurityPolicy::Basic128Rsa15.to_uri()),
             });
         }
+        // Issued token policies, one for each authorization service
+        for id in &endpoint.user_token_ids {
+            if let Some(validator) = self.jwt_validators.get(id) {
+                // Issued tokens are encrypted the same way as passwords.
+                user_identity_tokens.push(UserTokenPolicy {
+                    policy_id: UAString::from(issued_token_policy_id(id)),
+                    token_type: UserTokenType::IssuedToken,
+                    issued_token_type: UAString::from(JWT_ISSUED_TOKEN_TYPE),
+                    issuer_endpoint_url: UAString::from(
+                        validator.config().issuer_endpoint_url.clone(),
+                    ),
+                    security_policy_uri: user_pass_security_policy_uri(endpoint),
+                });
+            }
+        }
 
         if user_identity_tokens.is_empty() {
             debug!(
@@ -315,6 +484,11 @@ pub fn user_pass_security_policy_id(endpoint: &ServerEndpoint) -> UAString {
         SecurityPolicy::Aes128Sha256RsaOaep | SecurityPolicy::Aes256Sha256RsaPss => {
             POLICY_ID_USER_PASS_RSA_OAEP
         }
+        SecurityPolicy::EccNistP256
+        | SecurityPolicy::EccNistP384
+        | SecurityPolicy::EccCurve25519
+        | SecurityPolicy::EccBrainpoolP256r1
+        | SecurityPolicy::EccBrainpoolP384r1 => POLICY_ID_USER_PASS_ECC,
         _ => {
             panic!()
         }
@@ -5,12 +5,13 @@ use tokio_util::sync::CancellationToken;
 
 use crate::{constants, node_manager::TypeTreeForUser};
 use opcua_core::config::Config;
-use opcua_crypto::SecurityPolicy;
+use opcua_crypto::{CertificateStoreBackend, SecurityPolicy};
 use opcua_types::{BuildInfo, MessageSecurityMode, TypeLoader, TypeLoaderCollection};
 
 use super::{
-    authenticator::AuthManager, node_manager::NodeManagerBuilder, Limits, Server, ServerConfig,
-    ServerEndpoint, ServerHandle, ServerUserToken, ANONYMOUS_USER_TOKEN_ID,
+    authenticator::AuthManager, node_manager::NodeManagerBuilder, HttpsConfig, Limits,
+    ReverseConnectTarget, Server, ServerConfig, ServerEndpoint, ServerHandle, ServerUserToken,
+    WebSocketConfig, ANONYMOUS_USER_TOKEN_ID,
 };
 
 /// Server builder, used to configure the server programatically,
@@ -20,6 +21,7 @@ pub struct ServerBuilder {
     pub(crate) config: ServerConfig,
     pub(crate) node_managers: Vec<Box<dyn NodeManagerBuilder>>,
     pub(crate) authenticator: Option<Arc<dyn AuthManager>>,
+    pub(crate) certificate_store_backend: Option<Arc<dyn CertificateStoreBackend>>,
     pub(crate) type_tree_getter: Option<Arc<dyn TypeTreeForUser>>,
     pub(crate) type_loaders: TypeLoaderCollection,
     pub(crate) token: CancellationToken,
@@ -32,6 +34,7 @@ impl Default for ServerBuilder {
             config: Default::default(),
             node_managers: Default::default(),
             authenticator: None,
+            certificate_store_backend: None,
             token: CancellationToken::new(),
             type_tree_getter: None,
             build_info: BuildInfo::default(),
@@ -271,6 +274,17 @@ impl ServerBuilder {
         self
     }
 
+    /// Set a custom backend for storing the server's own key pair and the trusted and
+    /// rejected client certificates. If this is set, `pki_dir`, `certificate_path` and
+    /// `private_key_path` are ignored.
+    pub fn with_certificate_store_backend(
+        mut self,
+        backend: Arc<dyn CertificateStoreBackend>,
+    ) -> Self {
+        self.certificate_store_backend = Some(backend);
+        self
+    }
+
     /// Set a custom type tree getter. Most servers do not need to touch this.
     ///
     /// The type tree getter gets a type tree for a specific user, letting you have different type trees
@@ -343,6 +357,14 @@ impl ServerBuilder {
         self
     }
 
+    /// Accept client certificates issued by a CA that has no CRL, or only an out of
+    /// date CRL, in the trusted or issuer lists. By default such certificates are
+    /// rejected, since they cannot be checked for revocation.
+    pub fn allow_missing_crls(mut self, allow_missing_crls: bool) -> Self {
+        self.config.certificate_validation.allow_missing_crls = allow_missing_crls;
+        self
+    }
+
     /// PKI folder, either absolute or relative to executable.
     pub fn pki_dir(mut self, pki_dir: impl Into<PathBuf>) -> Self {
         self.config.pki_dir = pki_dir.into();
@@ -356,6 +378,108 @@ impl ServerBuilder {
         self
     }
 
+    /// Run the server as a local discovery server, allowing other servers to register
+    /// with it. Registered servers are returned from `FindServers` and `FindServersOnNetwork`.
+    pub fn local_discovery_server(mut self, enabled: bool) -> Self {
+        self.config.local_discovery.enabled = enabled;
+        self
+    }
+
+    /// Time in milliseconds before a registration with the local discovery server expires,
+    /// if the registered server does not register again. Set to 0 to never expire registrations.
+    pub fn registration_timeout_ms(mut self, timeout: u64) -> Self {
+        self.config.local_discovery.registration_timeout_ms = timeout;
+        self
+    }
+
+    /// Add a client the server should connect to using reverse connect. The server
+    /// opens a connection to `client_url` and sends a reverse hello message, after which
+    /// the client establishes a secure channel as usual.
+    ///
+    /// `endpoint_url` is the endpoint URL sent to the client, if this is `None` the
+    /// base endpoint of the server is used.
+    pub fn reverse_connect(
+        mut self,
+        client_url: impl Into<String>,
+        endpoint_url: Option<String>,
+    ) -> Self {
+        self.config
+            .reverse_connect
+            .targets
+            .push(ReverseConnectTarget {
+                client_url: client_url.into(),
+                endpoint_url,
+            });
+        self
+    }
+
+    /// Time in milliseconds to wait before reconnecting to a reverse connect client
+    /// after a connection attempt fails or a connection is closed.
+    pub fn reverse_connect_retry_interval_ms(mut self, interval: u64) -> Self {
+        self.config.reverse_connect.retry_interval_ms = interval;
+        self
+    }
+
+    /// Listen for OPC-UA over WebSockets connections on `port`. If `tls` is true, endpoints
+    /// are exposed as `opc.wss`, otherwise as `opc.ws`. All configured endpoints are
+    /// available on both the TCP and WebSocket transports.
+    ///
+    /// Requires the `websocket` feature.
+    pub fn websocket(mut self, port: u16, tls: bool) -> Self {
+        self.config.websocket_config = Some(WebSocketConfig {
+            port,
+            tls,
+            certificate_path: None,
+            private_key_path: None,
+        });
+        self
+    }
+
+    /// Use a separate certificate and private key for WebSocket TLS connections,
+    /// instead of the application instance certificate. Has no effect unless
+    /// the WebSocket transport is enabled with [`ServerBuilder::websocket`].
+    pub fn websocket_certificate(
+        mut self,
+        certificate_path: impl Into<PathBuf>,
+        private_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        if let Some(websocket) = &mut self.config.websocket_config {
+            websocket.certificate_path = Some(certificate_path.into());
+            websocket.private_key_path = Some(private_key_path.into());
+        }
+        self
+    }
+
+    /// Accept OPC-UA service requests over HTTPS on `port`, using either binary or JSON
+    /// encoding. Only endpoints without message security are available over HTTPS,
+    /// messages are protected by TLS instead.
+    ///
+    /// Requires the `https` feature.
+    pub fn https(mut self, port: u16) -> Self {
+        self.config.https_config = Some(HttpsConfig {
+            port,
+            certificate_path: None,
+            private_key_path: None,
+            idle_timeout_ms: constants::DEFAULT_HTTPS_IDLE_TIMEOUT_MS,
+        });
+        self
+    }
+
+    /// Use a separate certificate and private key for HTTPS connections,
+    /// instead of the application instance certificate. Has no effect unless
+    /// the HTTPS transport is enabled with [`ServerBuilder::https`].
+    pub fn https_certificate(
+        mut self,
+        certificate_path: impl Into<PathBuf>,
+        private_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        if let Some(https) = &mut self.config.https_config {
+            https.certificate_path = Some(certificate_path.into());
+            https.private_key_path = Some(private_key_path.into());
+        }
+        self
+    }
+
     /// Timeout for new connections to send a `HELLO` message, in seconds.
     /// After this timeout expires without a valid hello message, the connection
     /// is closed.
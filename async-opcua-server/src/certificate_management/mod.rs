@@ -0,0 +1,632 @@
+//! Push certificate management, as described in OPC-UA part 12.
+//!
+//! The [`CertificateManager`] handles the methods on the `ServerConfiguration` object and
+//! on the `TrustList` of its default application group. These let a client replace the
+//! server's application instance certificate and edit the trust lists without restarting
+//! the server.
+
+mod trust_list;
+
+use std::sync::Arc;
+
+use log::{error, info};
+use opcua_core::{sync::Mutex, sync::RwLock, trace_lock, trace_read_lock, trace_write_lock};
+use opcua_crypto::{CertificateListKind, CertificateStore, KeySize, KeyType, PrivateKey, X509};
+use opcua_types::{
+    BinaryDecodable, BinaryEncodable, ByteString, DateTime, MessageSecurityMode, MethodId, NodeId,
+    ObjectId, OpenFileMode, StatusCode, TrustListDataType, TrustListMasks, UAString, Variant,
+    VariantScalarTypeId, VariantTypeId,
+};
+
+use crate::{
+    info::ServerInfo,
+    load_method_args,
+    node_manager::{check_argument_count, MethodCall, RequestContext},
+};
+
+use trust_list::TrustListFiles;
+
+/// A new certificate given through `UpdateCertificate`, waiting for `ApplyChanges`.
+struct PendingCertificate {
+    cert: X509,
+    pkey: PrivateKey,
+    issuers: Vec<X509>,
+}
+
+#[derive(Default)]
+struct CertificateManagerState {
+    pending: Option<PendingCertificate>,
+    signing_request_key: Option<PrivateKey>,
+    trust_list: TrustListFiles,
+    trust_list_last_update: DateTime,
+}
+
+/// Handles the certificate management methods of the `ServerConfiguration` object.
+///
+/// All methods require a session with the `SecurityAdmin` role, see
+/// [`RoleManager::is_security_admin`](crate::roles::RoleManager::is_security_admin),
+/// over a secure channel using `SignAndEncrypt`.
+///
+/// A new certificate given with `UpdateCertificate` is written to the certificate store
+/// when `ApplyChanges` is called, and is used by every secure channel created after that.
+/// Existing secure channels, and the sessions on them, keep the certificate they were
+/// opened with. Changes to the trust list are applied immediately.
+pub struct CertificateManager {
+    certificate_store: Arc<RwLock<CertificateStore>>,
+    state: Mutex<CertificateManagerState>,
+}
+
+impl CertificateManager {
+    pub(crate) fn new(certificate_store: Arc<RwLock<CertificateStore>>) -> Self {
+        Self {
+            certificate_store,
+            state: Mutex::new(CertificateManagerState {
+                trust_list_last_update: DateTime::now(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Get the time the trust list was last changed through the certificate
+    /// management methods, or the time the server started.
+    pub fn trust_list_last_update(&self) -> DateTime {
+        trace_lock!(self.state).trust_list_last_update
+    }
+
+    /// Get the number of open handles on the trust list.
+    pub fn trust_list_open_count(&self) -> usize {
+        trace_lock!(self.state).trust_list.open_count()
+    }
+
+    /// Close any trust list handles held by a session that was closed.
+    pub(crate) fn session_closed(&self, session_id: u32) {
+        trace_lock!(self.state).trust_list.close_session(session_id);
+    }
+
+    /// Handle a call to one of the certificate management methods, returning `false`
+    /// if the call is not for a certificate management method. The call must already
+    /// have been validated by the node manager owning the method.
+    pub(crate) fn call(&self, context: &RequestContext, call: &mut MethodCall) -> bool {
+        let Ok(method) = call.method_id().as_method_id() else {
+            return false;
+        };
+        let handled = match method {
+            MethodId::ServerConfiguration_UpdateCertificate
+            | MethodId::ServerConfiguration_CreateSigningRequest
+            | MethodId::ServerConfiguration_GetRejectedList
+            | MethodId::ServerConfiguration_ApplyChanges => {
+                call.object_id() == &ObjectId::ServerConfiguration
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Open
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_OpenWithMasks
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Read
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Write
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_GetPosition
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_SetPosition
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Close
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_CloseAndUpdate
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_AddCertificate
+            | MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_RemoveCertificate => {
+                call.object_id()
+                    == &ObjectId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList
+            }
+            _ => false,
+        };
+        if !handled {
+            return false;
+        }
+
+        if let Err(e) = Self::check_access(context, call) {
+            call.set_status(e);
+            return true;
+        }
+
+        match self.call_method(context, method, call) {
+            Ok(outputs) => {
+                call.set_outputs(outputs);
+                call.set_status(StatusCode::Good);
+            }
+            Err(e) => call.set_status(e),
+        }
+        true
+    }
+
+    fn check_access(context: &RequestContext, call: &MethodCall) -> Result<(), StatusCode> {
+        if !context
+            .authenticator
+            .is_user_executable(&context.token, call.method_id())
+            || !context.info.roles.is_security_admin(context)
+        {
+            return Err(StatusCode::BadUserAccessDenied);
+        }
+        let security_mode = trace_read_lock!(context.session).message_security_mode();
+        if security_mode != MessageSecurityMode::SignAndEncrypt {
+            return Err(StatusCode::BadSecurityModeInsufficient);
+        }
+        Ok(())
+    }
+
+    fn call_method(
+        &self,
+        context: &RequestContext,
+        method: MethodId,
+        call: &MethodCall,
+    ) -> Result<Vec<Variant>, StatusCode> {
+        let session_id = context.session_id;
+        match method {
+            MethodId::ServerConfiguration_UpdateCertificate => {
+                check_argument_count(call, 6)?;
+                let (group, certificate_type, certificate) =
+                    load_method_args!(call, NodeId, NodeId, ByteString)?;
+                let args = call.arguments();
+                let issuers = byte_string_array(&args[3])?;
+                let private_key_format = string_arg(&args[4])?;
+                let private_key = byte_string_arg(&args[5])?;
+                self.update_certificate(
+                    context,
+                    &group,
+                    &certificate_type,
+                    &certificate,
+                    &issuers,
+                    &private_key_format,
+                    &private_key,
+                )
+                .map(|r| vec![r.into()])
+            }
+            MethodId::ServerConfiguration_CreateSigningRequest => {
+                check_argument_count(call, 5)?;
+                let (group, certificate_type, subject_name, regenerate_private_key) =
+                    load_method_args!(call, NodeId, NodeId, String, Boolean)?;
+                self.create_signing_request(
+                    context,
+                    &group,
+                    &certificate_type,
+                    &subject_name,
+                    regenerate_private_key,
+                )
+                .map(|r| vec![r.into()])
+            }
+            MethodId::ServerConfiguration_GetRejectedList => {
+                check_argument_count(call, 0)?;
+                let store = trace_read_lock!(self.certificate_store);
+                let rejected: Vec<_> = store
+                    .backend()
+                    .certificates(CertificateListKind::Rejected)
+                    .iter()
+                    .map(|c| c.as_byte_string())
+                    .collect();
+                Ok(vec![rejected.into()])
+            }
+            MethodId::ServerConfiguration_ApplyChanges => {
+                check_argument_count(call, 0)?;
+                self.apply_changes(context)?;
+                Ok(Vec::new())
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Open => {
+                check_argument_count(call, 1)?;
+                let mode = load_method_args!(call, Byte)?;
+                let read = OpenFileMode::Read as u8;
+                let write = OpenFileMode::Write as u8 | OpenFileMode::EraseExisting as u8;
+                if mode == read {
+                    self.open_trust_list_for_reading(context, TrustListMasks::All as u32)
+                } else if mode == write {
+                    trace_lock!(self.state)
+                        .trust_list
+                        .open(session_id, true, Vec::new())
+                } else {
+                    Err(StatusCode::BadInvalidArgument)
+                }
+                .map(|handle| vec![handle.into()])
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_OpenWithMasks => {
+                check_argument_count(call, 1)?;
+                let masks = load_method_args!(call, UInt32)?;
+                self.open_trust_list_for_reading(context, masks)
+                    .map(|handle| vec![handle.into()])
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Read => {
+                check_argument_count(call, 2)?;
+                let (handle, length) = load_method_args!(call, UInt32, Int32)?;
+                trace_lock!(self.state)
+                    .trust_list
+                    .read(session_id, handle, length)
+                    .map(|data| vec![data.into()])
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Write => {
+                check_argument_count(call, 2)?;
+                let (handle, data) = load_method_args!(call, UInt32, ByteString)?;
+                let max_size = context.info.config.limits.max_message_size;
+                trace_lock!(self.state)
+                    .trust_list
+                    .write(session_id, handle, &data, max_size)?;
+                Ok(Vec::new())
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_GetPosition => {
+                check_argument_count(call, 1)?;
+                let handle = load_method_args!(call, UInt32)?;
+                trace_lock!(self.state)
+                    .trust_list
+                    .get_position(session_id, handle)
+                    .map(|position| vec![position.into()])
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_SetPosition => {
+                check_argument_count(call, 2)?;
+                let (handle, position) = load_method_args!(call, UInt32, UInt64)?;
+                trace_lock!(self.state)
+                    .trust_list
+                    .set_position(session_id, handle, position)?;
+                Ok(Vec::new())
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Close => {
+                check_argument_count(call, 1)?;
+                let handle = load_method_args!(call, UInt32)?;
+                trace_lock!(self.state)
+                    .trust_list
+                    .close(session_id, handle)?;
+                Ok(Vec::new())
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_CloseAndUpdate => {
+                check_argument_count(call, 1)?;
+                let handle = load_method_args!(call, UInt32)?;
+                self.close_and_update_trust_list(context, handle)?;
+                // The new trust list is used immediately, so ApplyChanges is not required.
+                Ok(vec![false.into()])
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_AddCertificate => {
+                check_argument_count(call, 2)?;
+                let (certificate, is_trusted) = load_method_args!(call, ByteString, Boolean)?;
+                self.add_certificate(&certificate, is_trusted)?;
+                Ok(Vec::new())
+            }
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_RemoveCertificate => {
+                check_argument_count(call, 2)?;
+                let (thumbprint, is_trusted) = load_method_args!(call, String, Boolean)?;
+                self.remove_certificate(&thumbprint, is_trusted)?;
+                Ok(Vec::new())
+            }
+            _ => Err(StatusCode::BadMethodInvalid),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn update_certificate(
+        &self,
+        context: &RequestContext,
+        group: &NodeId,
+        certificate_type: &NodeId,
+        certificate: &ByteString,
+        issuers: &[ByteString],
+        private_key_format: &UAString,
+        private_key: &ByteString,
+    ) -> Result<bool, StatusCode> {
+        check_certificate_group(group)?;
+        let cert =
+            X509::from_byte_string(certificate).map_err(|_| StatusCode::BadCertificateInvalid)?;
+        let key_type = cert
+            .public_key()
+            .map_err(|_| StatusCode::BadCertificateInvalid)?
+            .key_type();
+        check_certificate_type(certificate_type, key_type)?;
+        cert.is_time_valid(&chrono::Utc::now())?;
+        cert.is_application_uri_valid(context.info.application_uri.as_ref())?;
+        let issuers = issuers
+            .iter()
+            .map(X509::from_byte_string)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| StatusCode::BadCertificateInvalid)?;
+
+        let mut state = trace_lock!(self.state);
+        let pkey = if private_key.is_null_or_empty() {
+            // Without a new private key, the certificate must belong to the key of the last
+            // signing request, or to the current key.
+            state
+                .signing_request_key
+                .clone()
+                .into_iter()
+                .chain(
+                    context
+                        .info
+                        .server_certificate_for_key_type(key_type)
+                        .map(|(_, k)| (*k).clone()),
+                )
+                .find(|k| cert.matches_private_key(k))
+                .ok_or(StatusCode::BadSecurityChecksFailed)?
+        } else {
+            let pkey = match private_key_format.as_ref() {
+                "PEM" => PrivateKey::from_pem(private_key.as_ref())
+                    .map_err(|_| StatusCode::BadInvalidArgument)?,
+                "PFX" => return Err(StatusCode::BadNotSupported),
+                _ => return Err(StatusCode::BadInvalidArgument),
+            };
+            if !cert.matches_private_key(&pkey) {
+                return Err(StatusCode::BadSecurityChecksFailed);
+            }
+            pkey
+        };
+
+        info!(
+            "New server certificate {} received, waiting for ApplyChanges",
+            cert.thumbprint().as_hex_string()
+        );
+        state.pending = Some(PendingCertificate {
+            cert,
+            pkey,
+            issuers,
+        });
+        Ok(true)
+    }
+
+    fn create_signing_request(
+        &self,
+        context: &RequestContext,
+        group: &NodeId,
+        certificate_type: &NodeId,
+        subject_name: &UAString,
+        regenerate_private_key: bool,
+    ) -> Result<ByteString, StatusCode> {
+        check_certificate_group(group)?;
+        let key_type = KeyType::from_certificate_type(certificate_type)?;
+        // The first certificate for a key type is requested with the subject of the
+        // default certificate, and needs a new private key.
+        let (cert, current_key) = key_type
+            .and_then(|key_type| context.info.server_certificate_for_key_type(key_type))
+            .or_else(|| {
+                context
+                    .info
+                    .server_certificate()
+                    .zip(context.info.server_pkey())
+            })
+            .ok_or(StatusCode::BadInvalidState)?;
+        let key_type = key_type.unwrap_or(current_key.key_type());
+        if !regenerate_private_key && key_type != current_key.key_type() {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+
+        let new_key = if regenerate_private_key {
+            let bit_length = if key_type == current_key.key_type() {
+                current_key.bit_length() as u32
+            } else {
+                // Only used for RSA keys, this is the size of newly created certificates.
+                2048
+            };
+            let key = PrivateKey::generate(key_type, bit_length)
+                .map_err(|_| StatusCode::BadInternalError)?;
+            Some(key)
+        } else {
+            None
+        };
+        let subject_name = (!subject_name.is_empty()).then(|| subject_name.as_ref());
+        let request = cert
+            .create_signing_request(new_key.as_ref().unwrap_or(&current_key), subject_name)
+            .map_err(|e| {
+                error!("{e}");
+                StatusCode::BadInvalidArgument
+            })?;
+
+        trace_lock!(self.state).signing_request_key = new_key;
+        Ok(ByteString::from(request))
+    }
+
+    fn apply_changes(&self, context: &RequestContext) -> Result<(), StatusCode> {
+        let mut state = trace_lock!(self.state);
+        let Some(pending) = state.pending.take() else {
+            return Ok(());
+        };
+        self.store_server_certificate(&context.info, pending.cert, pending.pkey, &pending.issuers)?;
+        state.signing_request_key = None;
+        Ok(())
+    }
+
+    /// Replace the server certificate and private key, see
+    /// [`ServerHandle::set_server_certificate`](crate::ServerHandle::set_server_certificate).
+    pub(crate) fn set_server_certificate(
+        &self,
+        info: &ServerInfo,
+        cert: X509,
+        pkey: PrivateKey,
+    ) -> Result<(), StatusCode> {
+        if !cert.matches_private_key(&pkey) {
+            return Err(StatusCode::BadSecurityChecksFailed);
+        }
+        let _state = trace_lock!(self.state);
+        self.store_server_certificate(info, cert, pkey, &[])
+    }
+
+    /// Reload the server certificate and private key from the certificate store, see
+    /// [`ServerHandle::reload_server_certificate`](crate::ServerHandle::reload_server_certificate).
+    pub(crate) fn reload_server_certificate(&self, info: &ServerInfo) -> Result<(), StatusCode> {
+        let _state = trace_lock!(self.state);
+        let (cert, pkey, others) = {
+            let store = trace_read_lock!(self.certificate_store);
+            match (store.read_own_cert(), store.read_own_pkey()) {
+                (Ok(cert), Ok(pkey)) => {
+                    let others = crate::server::read_other_server_certificates(
+                        &store,
+                        &info.config,
+                        pkey.key_type(),
+                        None,
+                    );
+                    (cert, pkey, others)
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    error!("Failed to read server certificate: {e}");
+                    return Err(StatusCode::BadConfigurationError);
+                }
+            }
+        };
+        if !cert.matches_private_key(&pkey) {
+            return Err(StatusCode::BadSecurityChecksFailed);
+        }
+        info!(
+            "Server certificate reloaded as {}",
+            cert.thumbprint().as_hex_string()
+        );
+        info.set_server_certificate(cert, pkey);
+        info.other_server_certificates.store(Arc::new(others));
+        Ok(())
+    }
+
+    /// Write a new server certificate, its private key and its issuers to the certificate
+    /// store, and make it the current server certificate. The store is locked for writing
+    /// while it is updated, so new secure channels never see a mismatched certificate and key.
+    fn store_server_certificate(
+        &self,
+        info: &ServerInfo,
+        cert: X509,
+        pkey: PrivateKey,
+        issuers: &[X509],
+    ) -> Result<(), StatusCode> {
+        let store = trace_write_lock!(self.certificate_store);
+        let backend = store.backend();
+        let result = backend
+            .store_own_cert_and_pkey(&cert, &pkey, true)
+            .and_then(|_| {
+                issuers
+                    .iter()
+                    .filter(|c| !backend.contains_certificate(CertificateListKind::Issuer, c))
+                    .try_for_each(|c| backend.store_certificate(CertificateListKind::Issuer, c))
+            });
+        if let Err(e) = result {
+            error!("Failed to store new server certificate: {e}");
+            return Err(StatusCode::BadInternalError);
+        }
+
+        info!(
+            "Server certificate changed to {}",
+            cert.thumbprint().as_hex_string()
+        );
+        info.set_server_certificate(cert, pkey);
+        Ok(())
+    }
+
+    fn open_trust_list_for_reading(
+        &self,
+        context: &RequestContext,
+        masks: u32,
+    ) -> Result<u32, StatusCode> {
+        let trust_list = {
+            let store = trace_read_lock!(self.certificate_store);
+            store.read_trust_list(masks)
+        };
+        let ctx = context.info.initial_encoding_context();
+        let data = trust_list.encode_to_vec(&ctx.context());
+        trace_lock!(self.state)
+            .trust_list
+            .open(context.session_id, false, data)
+    }
+
+    fn close_and_update_trust_list(
+        &self,
+        context: &RequestContext,
+        handle: u32,
+    ) -> Result<(), StatusCode> {
+        let mut state = trace_lock!(self.state);
+        let Some(data) = state.trust_list.close(context.session_id, handle)? else {
+            return Err(StatusCode::BadInvalidState);
+        };
+        let ctx = context.info.initial_encoding_context();
+        let trust_list =
+            TrustListDataType::decode(&mut data.as_slice(), &ctx.context()).map_err(|e| {
+                error!("Failed to decode trust list: {e}");
+                StatusCode::BadDecodingError
+            })?;
+        let store = trace_read_lock!(self.certificate_store);
+        store.update_trust_list(trust_list)?;
+        info!("Trust list updated");
+        state.trust_list_last_update = DateTime::now();
+        Ok(())
+    }
+
+    fn add_certificate(
+        &self,
+        certificate: &ByteString,
+        is_trusted: bool,
+    ) -> Result<(), StatusCode> {
+        let cert =
+            X509::from_byte_string(certificate).map_err(|_| StatusCode::BadCertificateInvalid)?;
+        // Certificates that are only used to build chains must be CA certificates.
+        if !is_trusted && !cert.is_ca() {
+            return Err(StatusCode::BadCertificateInvalid);
+        }
+        let mut state = trace_lock!(self.state);
+        if state.trust_list.is_open_for_writing() {
+            return Err(StatusCode::BadInvalidState);
+        }
+        let kind = list_kind(is_trusted);
+        let store = trace_read_lock!(self.certificate_store);
+        let backend = store.backend();
+        if !backend.contains_certificate(kind, &cert) {
+            backend.store_certificate(kind, &cert).map_err(|e| {
+                error!("Failed to add certificate to trust list: {e}");
+                StatusCode::BadInternalError
+            })?;
+        }
+        state.trust_list_last_update = DateTime::now();
+        Ok(())
+    }
+
+    fn remove_certificate(
+        &self,
+        thumbprint: &UAString,
+        is_trusted: bool,
+    ) -> Result<(), StatusCode> {
+        let mut state = trace_lock!(self.state);
+        if state.trust_list.is_open_for_writing() {
+            return Err(StatusCode::BadInvalidState);
+        }
+        let store = trace_read_lock!(self.certificate_store);
+        trust_list::remove_certificate(&store, list_kind(is_trusted), thumbprint.as_ref())?;
+        state.trust_list_last_update = DateTime::now();
+        Ok(())
+    }
+}
+
+fn list_kind(is_trusted: bool) -> CertificateListKind {
+    if is_trusted {
+        CertificateListKind::Trusted
+    } else {
+        CertificateListKind::Issuer
+    }
+}
+
+/// Only the default application group is supported. A null node ID refers to it as well.
+fn check_certificate_group(group: &NodeId) -> Result<(), StatusCode> {
+    if group.is_null()
+        || group == &ObjectId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::BadInvalidArgument)
+    }
+}
+
+fn check_certificate_type(certificate_type: &NodeId, key_type: KeyType) -> Result<(), StatusCode> {
+    match KeyType::from_certificate_type(certificate_type)? {
+        Some(required) if required != key_type => Err(StatusCode::BadCertificateInvalid),
+        _ => Ok(()),
+    }
+}
+
+fn string_arg(arg: &Variant) -> Result<UAString, StatusCode> {
+    match arg.convert(VariantTypeId::Scalar(VariantScalarTypeId::String)) {
+        Variant::String(s) => Ok(s),
+        Variant::Empty => Ok(UAString::null()),
+        _ => Err(StatusCode::BadInvalidArgument),
+    }
+}
+
+fn byte_string_arg(arg: &Variant) -> Result<ByteString, StatusCode> {
+    match arg.convert(VariantTypeId::Scalar(VariantScalarTypeId::ByteString)) {
+        Variant::ByteString(s) => Ok(s),
+        Variant::Empty => Ok(ByteString::null()),
+        _ => Err(StatusCode::BadInvalidArgument),
+    }
+}
+
+fn byte_string_array(arg: &Variant) -> Result<Vec<ByteString>, StatusCode> {
+    match arg {
+        Variant::Empty => Ok(Vec::new()),
+        Variant::Array(array) => array.values.iter().map(byte_string_arg).collect(),
+        _ => Err(StatusCode::BadInvalidArgument),
+    }
+}
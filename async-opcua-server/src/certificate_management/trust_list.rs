@@ -0,0 +1,175 @@
+//! Implementation of the `TrustList` file object of the default application group.
+//!
+//! Reading the trust list gives a binary encoded `TrustListDataType` snapshot taken when
+//! the file was opened. Writing it replaces the lists given in the uploaded
+//! `TrustListDataType` when the file is closed with `CloseAndUpdate`.
+
+use hashbrown::HashMap;
+use log::error;
+use opcua_crypto::{CertificateListKind, CertificateStore};
+use opcua_types::{ByteString, StatusCode};
+
+/// A trust list opened through `Open` or `OpenWithMasks`.
+struct OpenTrustList {
+    session_id: u32,
+    writing: bool,
+    data: Vec<u8>,
+    position: usize,
+}
+
+/// The set of open file handles on the trust list.
+#[derive(Default)]
+pub(super) struct TrustListFiles {
+    files: HashMap<u32, OpenTrustList>,
+    next_handle: u32,
+}
+
+impl TrustListFiles {
+    /// Open the trust list for `session_id`. Only a single session may have the trust
+    /// list open for writing, and it may not be read at the same time.
+    pub(super) fn open(
+        &mut self,
+        session_id: u32,
+        writing: bool,
+        data: Vec<u8>,
+    ) -> Result<u32, StatusCode> {
+        if self.is_open_for_writing() || (writing && !self.files.is_empty()) {
+            return Err(StatusCode::BadInvalidState);
+        }
+        self.next_handle = self.next_handle.wrapping_add(1).max(1);
+        let handle = self.next_handle;
+        self.files.insert(
+            handle,
+            OpenTrustList {
+                session_id,
+                writing,
+                data,
+                position: 0,
+            },
+        );
+        Ok(handle)
+    }
+
+    pub(super) fn is_open_for_writing(&self) -> bool {
+        self.files.values().any(|f| f.writing)
+    }
+
+    pub(super) fn open_count(&self) -> usize {
+        self.files.len()
+    }
+
+    fn get_mut(&mut self, session_id: u32, handle: u32) -> Result<&mut OpenTrustList, StatusCode> {
+        match self.files.get_mut(&handle) {
+            Some(file) if file.session_id == session_id => Ok(file),
+            _ => Err(StatusCode::BadInvalidArgument),
+        }
+    }
+
+    pub(super) fn read(
+        &mut self,
+        session_id: u32,
+        handle: u32,
+        length: i32,
+    ) -> Result<ByteString, StatusCode> {
+        let file = self.get_mut(session_id, handle)?;
+        if file.writing {
+            return Err(StatusCode::BadInvalidState);
+        }
+        let Ok(length) = usize::try_from(length) else {
+            return Err(StatusCode::BadInvalidArgument);
+        };
+        let start = file.position.min(file.data.len());
+        let end = start.saturating_add(length).min(file.data.len());
+        file.position = end;
+        Ok(ByteString::from(&file.data[start..end]))
+    }
+
+    pub(super) fn write(
+        &mut self,
+        session_id: u32,
+        handle: u32,
+        data: &ByteString,
+        max_size: usize,
+    ) -> Result<(), StatusCode> {
+        let file = self.get_mut(session_id, handle)?;
+        if !file.writing {
+            return Err(StatusCode::BadInvalidState);
+        }
+        let data = data.as_ref();
+        let end = file.position + data.len();
+        if end > max_size {
+            return Err(StatusCode::BadEncodingLimitsExceeded);
+        }
+        if end > file.data.len() {
+            file.data.resize(end, 0);
+        }
+        file.data[file.position..end].copy_from_slice(data);
+        file.position = end;
+        Ok(())
+    }
+
+    pub(super) fn get_position(&mut self, session_id: u32, handle: u32) -> Result<u64, StatusCode> {
+        Ok(self.get_mut(session_id, handle)?.position as u64)
+    }
+
+    pub(super) fn set_position(
+        &mut self,
+        session_id: u32,
+        handle: u32,
+        position: u64,
+    ) -> Result<(), StatusCode> {
+        let file = self.get_mut(session_id, handle)?;
+        // Setting the position past the end moves it to the end of the file.
+        file.position = usize::try_from(position)
+            .unwrap_or(usize::MAX)
+            .min(file.data.len());
+        Ok(())
+    }
+
+    /// Close the file, returning the written data if it was open for writing.
+    pub(super) fn close(
+        &mut self,
+        session_id: u32,
+        handle: u32,
+    ) -> Result<Option<Vec<u8>>, StatusCode> {
+        self.get_mut(session_id, handle)?;
+        let file = self.files.remove(&handle).unwrap();
+        Ok(file.writing.then_some(file.data))
+    }
+
+    /// Close every file opened by `session_id`, discarding any written data.
+    pub(super) fn close_session(&mut self, session_id: u32) {
+        self.files.retain(|_, f| f.session_id != session_id);
+    }
+}
+
+/// Remove the certificate with the hex encoded `thumbprint` from the list `kind`. If it is
+/// a CA certificate, the CRLs it issued are removed as well.
+pub(super) fn remove_certificate(
+    store: &CertificateStore,
+    kind: CertificateListKind,
+    thumbprint: &str,
+) -> Result<(), StatusCode> {
+    let backend = store.backend();
+    let Some(cert) = backend.certificates(kind).into_iter().find(|c| {
+        c.thumbprint()
+            .as_hex_string()
+            .eq_ignore_ascii_case(thumbprint)
+    }) else {
+        return Err(StatusCode::BadInvalidArgument);
+    };
+    let result = backend.remove_certificate(kind, &cert).and_then(|_| {
+        if !cert.is_ca() {
+            return Ok(());
+        }
+        backend
+            .crls(kind)
+            .iter()
+            .filter(|crl| crl.is_issued_by(&cert))
+            .try_for_each(|crl| backend.remove_crl(kind, crl).map(|_| ()))
+    });
+    result.map_err(|e| {
+        error!("Failed to remove certificate from trust list: {e}");
+        StatusCode::BadInternalError
+    })
+}
@@ -0,0 +1,683 @@
+use std::sync::Arc;
+
+use opcua_nodes::BaseEventType;
+use opcua_types::{
+    ByteString, DateTime, Guid, LocalizedText, NodeId, ObjectId, ObjectTypeId, StatusCode, UAString,
+};
+
+use super::event::{
+    ConditionEvent, ConditionVariable, FiniteStateVariable, ShelvingStateMachine, TwoStateVariable,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Shelving state of an alarm.
+pub enum ShelvingState {
+    /// The alarm is not shelved.
+    Unshelved,
+    /// The alarm is shelved until it next returns to the inactive state.
+    OneShotShelved,
+    /// The alarm is shelved until the given time.
+    TimedShelved {
+        /// Time at which the alarm is automatically unshelved.
+        unshelve_time: DateTime,
+    },
+}
+
+impl ShelvingState {
+    /// Get whether this state is one of the shelved states.
+    pub fn is_shelved(&self) -> bool {
+        !matches!(self, Self::Unshelved)
+    }
+
+    fn to_event_field(self) -> ShelvingStateMachine {
+        let (name, id, unshelve_time) = match self {
+            ShelvingState::Unshelved => (
+                "Unshelved",
+                ObjectId::ShelvedStateMachineType_Unshelved,
+                None,
+            ),
+            ShelvingState::OneShotShelved => (
+                "OneShotShelved",
+                ObjectId::ShelvedStateMachineType_OneShotShelved,
+                None,
+            ),
+            ShelvingState::TimedShelved { unshelve_time } => (
+                "TimedShelved",
+                ObjectId::ShelvedStateMachineType_TimedShelved,
+                Some(
+                    ((unshelve_time.ticks() - DateTime::now().ticks()) as f64 / 10_000.0).max(0.0),
+                ),
+            ),
+        };
+        ShelvingStateMachine {
+            current_state: FiniteStateVariable {
+                value: name.into(),
+                id: id.into(),
+            },
+            unshelve_time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TwoState {
+    value: bool,
+    transition_time: DateTime,
+}
+
+impl TwoState {
+    fn new(value: bool) -> Self {
+        Self {
+            value,
+            transition_time: DateTime::now(),
+        }
+    }
+
+    /// Set the state, returning `false` if it was already set to `value`.
+    fn set(&mut self, value: bool, time: DateTime) -> bool {
+        if self.value == value {
+            return false;
+        }
+        self.value = value;
+        self.transition_time = time;
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+/// State of a single branch of a condition. The main branch has a null branch ID.
+struct ConditionBranch {
+    branch_id: NodeId,
+    message: LocalizedText,
+    severity: u16,
+    last_severity: u16,
+    severity_time: DateTime,
+    quality: StatusCode,
+    quality_time: DateTime,
+    retain: bool,
+    comment: LocalizedText,
+    comment_time: DateTime,
+    client_user_id: UAString,
+    acked: Option<TwoState>,
+    confirmed: Option<TwoState>,
+    active: Option<TwoState>,
+    /// The last event reported for this branch. Method calls from clients
+    /// refer to branches through the event ID of this event.
+    last_event: Option<Arc<ConditionEvent>>,
+}
+
+impl ConditionBranch {
+    /// Get whether this branch still needs an acknowledge or confirm.
+    fn needs_action(&self) -> bool {
+        self.acked.is_some_and(|s| !s.value) || self.confirmed.is_some_and(|s| !s.value)
+    }
+
+    /// Compute the value of `Retain` for this branch.
+    fn is_retained(&self, enabled: bool) -> bool {
+        enabled && (self.retain || self.active.is_some_and(|s| s.value) || self.needs_action())
+    }
+
+    fn event_id(&self) -> Option<&ByteString> {
+        self.last_event.as_ref().map(|e| &e.base.event_id)
+    }
+
+    fn set_comment(&mut self, comment: LocalizedText, user: UAString, time: DateTime) {
+        if !comment.text.is_null() {
+            self.comment = comment;
+            self.comment_time = time;
+        }
+        self.client_user_id = user;
+    }
+
+    fn set_acked(&mut self, time: DateTime) -> Result<(), StatusCode> {
+        let Some(acked) = &mut self.acked else {
+            return Err(StatusCode::BadMethodInvalid);
+        };
+        if !acked.set(true, time) {
+            return Err(StatusCode::BadConditionBranchAlreadyAcked);
+        }
+        Ok(())
+    }
+
+    fn set_confirmed(&mut self, time: DateTime) -> Result<(), StatusCode> {
+        let Some(confirmed) = &mut self.confirmed else {
+            return Err(StatusCode::BadMethodInvalid);
+        };
+        if !confirmed.set(true, time) {
+            return Err(StatusCode::BadConditionBranchAlreadyConfirmed);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A condition managed by the [`ConditionManager`](super::ConditionManager).
+///
+/// Create a condition with one of the constructors, depending on whether
+/// it is a plain `ConditionType`, an `AcknowledgeableConditionType` or an
+/// `AlarmConditionType`, then register it with
+/// [`ConditionManager::add_condition`](super::ConditionManager::add_condition).
+pub struct Condition {
+    node_id: NodeId,
+    event_type: NodeId,
+    condition_name: UAString,
+    source_node: NodeId,
+    source_name: UAString,
+    condition_class_id: NodeId,
+    condition_class_name: LocalizedText,
+    enabled: TwoState,
+    shelving: Option<ShelvingState>,
+    main: ConditionBranch,
+    branches: Vec<ConditionBranch>,
+}
+
+impl Condition {
+    /// Create a new plain condition. `event_type` should be `ConditionType` or
+    /// a subtype of it.
+    ///
+    /// A plain condition has no state other than `Retain`, which is controlled
+    /// with [`ConditionManager::set_retain`](super::ConditionManager::set_retain).
+    pub fn new(
+        node_id: impl Into<NodeId>,
+        event_type: impl Into<NodeId>,
+        condition_name: impl Into<UAString>,
+    ) -> Self {
+        let now = DateTime::now();
+        Self {
+            node_id: node_id.into(),
+            event_type: event_type.into(),
+            condition_name: condition_name.into(),
+            source_node: NodeId::null(),
+            source_name: UAString::null(),
+            condition_class_id: ObjectTypeId::BaseConditionClassType.into(),
+            condition_class_name: "BaseConditionClass".into(),
+            enabled: TwoState::new(true),
+            shelving: None,
+            main: ConditionBranch {
+                branch_id: NodeId::null(),
+                message: LocalizedText::null(),
+                severity: 0,
+                last_severity: 0,
+                severity_time: now,
+                quality: StatusCode::Good,
+                quality_time: now,
+                retain: false,
+                comment: LocalizedText::null(),
+                comment_time: now,
+                client_user_id: UAString::null(),
+                acked: None,
+                confirmed: None,
+                active: None,
+                last_event: None,
+            },
+            branches: Vec::new(),
+        }
+    }
+
+    /// Create a new acknowledgeable condition. `event_type` should be
+    /// `AcknowledgeableConditionType` or a subtype of it.
+    ///
+    /// If `supports_confirm` is true, clients must confirm the condition
+    /// after acknowledging it.
+    pub fn new_acknowledgeable(
+        node_id: impl Into<NodeId>,
+        event_type: impl Into<NodeId>,
+        condition_name: impl Into<UAString>,
+        supports_confirm: bool,
+    ) -> Self {
+        let mut condition = Self::new(node_id, event_type, condition_name);
+        condition.main.acked = Some(TwoState::new(true));
+        if supports_confirm {
+            condition.main.confirmed = Some(TwoState::new(true));
+        }
+        condition
+    }
+
+    /// Create a new alarm. `event_type` should be `AlarmConditionType` or a
+    /// subtype of it. The alarm starts out inactive and unshelved.
+    ///
+    /// If `supports_confirm` is true, clients must confirm the alarm
+    /// after acknowledging it.
+    pub fn new_alarm(
+        node_id: impl Into<NodeId>,
+        event_type: impl Into<NodeId>,
+        condition_name: impl Into<UAString>,
+        supports_confirm: bool,
+    ) -> Self {
+        let mut condition =
+            Self::new_acknowledgeable(node_id, event_type, condition_name, supports_confirm);
+        condition.main.active = Some(TwoState::new(false));
+        condition.shelving = Some(ShelvingState::Unshelved);
+        condition
+    }
+
+    /// Set the source node of the condition. Events for the condition are
+    /// reported to monitored items on the source node, as well as the `Server` object.
+    pub fn set_source_node(mut self, source_node: impl Into<NodeId>) -> Self {
+        self.source_node = source_node.into();
+        self
+    }
+
+    /// Set the source name of the condition.
+    pub fn set_source_name(mut self, source_name: impl Into<UAString>) -> Self {
+        self.source_name = source_name.into();
+        self
+    }
+
+    /// Set the condition class. The default is `BaseConditionClassType`.
+    pub fn set_condition_class(
+        mut self,
+        condition_class_id: impl Into<NodeId>,
+        condition_class_name: impl Into<LocalizedText>,
+    ) -> Self {
+        self.condition_class_id = condition_class_id.into();
+        self.condition_class_name = condition_class_name.into();
+        self
+    }
+
+    /// Set the initial severity of the condition.
+    pub fn set_severity(mut self, severity: u16) -> Self {
+        self.main.severity = severity;
+        self.main.last_severity = severity;
+        self
+    }
+
+    /// Set the initial message of the condition.
+    pub fn set_message(mut self, message: impl Into<LocalizedText>) -> Self {
+        self.main.message = message.into();
+        self
+    }
+
+    /// Get the node ID of the condition.
+    pub fn node_id(&self) -> &NodeId {
+        &self.node_id
+    }
+
+    /// Get the event type of the condition.
+    pub fn event_type(&self) -> &NodeId {
+        &self.event_type
+    }
+
+    /// Get the node events for this condition are reported to.
+    pub fn notifier(&self) -> NodeId {
+        if self.source_node.is_null() {
+            ObjectId::Server.into()
+        } else {
+            self.source_node.clone()
+        }
+    }
+
+    /// Get whether the condition is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.value
+    }
+
+    /// Get the current value of `Retain` for the main branch.
+    pub fn retain(&self) -> bool {
+        self.main.is_retained(self.enabled.value)
+    }
+
+    /// Get the current severity of the condition.
+    pub fn severity(&self) -> u16 {
+        self.main.severity
+    }
+
+    /// Get the current quality of the condition.
+    pub fn quality(&self) -> StatusCode {
+        self.main.quality
+    }
+
+    /// Get the last comment added to the condition.
+    pub fn comment(&self) -> &LocalizedText {
+        &self.main.comment
+    }
+
+    /// Get whether the condition is acknowledged, or `None` if the condition
+    /// is not acknowledgeable.
+    pub fn is_acked(&self) -> Option<bool> {
+        self.main.acked.map(|s| s.value)
+    }
+
+    /// Get whether the condition is confirmed, or `None` if the condition
+    /// does not support confirm.
+    pub fn is_confirmed(&self) -> Option<bool> {
+        self.main.confirmed.map(|s| s.value)
+    }
+
+    /// Get whether the alarm is active, or `None` if the condition is not an alarm.
+    pub fn is_active(&self) -> Option<bool> {
+        self.main.active.map(|s| s.value)
+    }
+
+    /// Get the shelving state of the alarm, or `None` if the condition is not an alarm.
+    pub fn shelving_state(&self) -> Option<ShelvingState> {
+        self.shelving
+    }
+
+    /// Get the event ID of the last event reported for the main branch.
+    pub fn event_id(&self) -> Option<&ByteString> {
+        self.main.event_id()
+    }
+
+    /// Get the IDs of the branches of this condition, other than the main branch.
+    pub fn branch_ids(&self) -> impl Iterator<Item = &NodeId> {
+        self.branches.iter().map(|b| &b.branch_id)
+    }
+
+    /// Get the event ID of the last event reported for the given branch.
+    pub fn branch_event_id(&self, branch_id: &NodeId) -> Option<&ByteString> {
+        self.branches
+            .iter()
+            .find(|b| &b.branch_id == branch_id)
+            .and_then(|b| b.event_id())
+    }
+
+    /// Report the given branch, if the condition is enabled.
+    fn report_enabled(&mut self, index: Option<usize>, time: DateTime) -> Vec<Arc<ConditionEvent>> {
+        if self.enabled.value {
+            vec![self.report(index, time)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub(super) fn update_enabled(
+        &mut self,
+        enabled: bool,
+        time: DateTime,
+    ) -> Result<Vec<Arc<ConditionEvent>>, StatusCode> {
+        if !self.enabled.set(enabled, time) {
+            return Err(if enabled {
+                StatusCode::BadConditionAlreadyEnabled
+            } else {
+                StatusCode::BadConditionAlreadyDisabled
+            });
+        }
+        Ok(self.report_all(time))
+    }
+
+    pub(super) fn update_active(
+        &mut self,
+        active: bool,
+        message: LocalizedText,
+        time: DateTime,
+    ) -> Result<Vec<Arc<ConditionEvent>>, StatusCode> {
+        let Some(current) = &self.main.active else {
+            return Err(StatusCode::BadNotSupported);
+        };
+        if current.value == active {
+            return Ok(Vec::new());
+        }
+        let mut events = Vec::new();
+        // If the previous activation has not been dealt with yet, keep
+        // it around as a separate branch.
+        if active && self.main.needs_action() {
+            let idx = self.create_branch();
+            events.extend(self.report_enabled(Some(idx), time));
+        }
+
+        let main = &mut self.main;
+        main.message = message;
+        if let Some(state) = &mut main.active {
+            state.set(active, time);
+        }
+        if active {
+            for state in [&mut main.acked, &mut main.confirmed].into_iter().flatten() {
+                state.set(false, time);
+            }
+        } else if self.shelving == Some(ShelvingState::OneShotShelved) {
+            self.shelving = Some(ShelvingState::Unshelved);
+        }
+        events.extend(self.report_enabled(None, time));
+        Ok(events)
+    }
+
+    pub(super) fn update_severity(
+        &mut self,
+        severity: u16,
+        message: LocalizedText,
+        time: DateTime,
+    ) -> Vec<Arc<ConditionEvent>> {
+        self.main.last_severity = self.main.severity;
+        self.main.severity = severity;
+        self.main.severity_time = time;
+        self.main.message = message;
+        self.report_enabled(None, time)
+    }
+
+    pub(super) fn update_quality(
+        &mut self,
+        quality: StatusCode,
+        message: LocalizedText,
+        time: DateTime,
+    ) -> Vec<Arc<ConditionEvent>> {
+        self.main.quality = quality;
+        self.main.quality_time = time;
+        self.main.message = message;
+        self.report_enabled(None, time)
+    }
+
+    pub(super) fn update_retain(
+        &mut self,
+        retain: bool,
+        message: LocalizedText,
+        time: DateTime,
+    ) -> Vec<Arc<ConditionEvent>> {
+        self.main.retain = retain;
+        self.main.message = message;
+        self.report_enabled(None, time)
+    }
+
+    pub(super) fn update_shelving(
+        &mut self,
+        state: ShelvingState,
+        time: DateTime,
+    ) -> Result<Vec<Arc<ConditionEvent>>, StatusCode> {
+        let Some(shelving) = &mut self.shelving else {
+            return Err(StatusCode::BadNotSupported);
+        };
+        match (*shelving, state) {
+            (ShelvingState::Unshelved, ShelvingState::Unshelved) => {
+                return Err(StatusCode::BadConditionNotShelved)
+            }
+            (ShelvingState::OneShotShelved, ShelvingState::OneShotShelved) => {
+                return Err(StatusCode::BadConditionAlreadyShelved)
+            }
+            _ => (),
+        }
+        *shelving = state;
+        Ok(self.report_enabled(None, time))
+    }
+
+    /// Expire a timed shelve, if the alarm is still shelved until `unshelve_time`.
+    pub(super) fn expire_shelving(
+        &mut self,
+        unshelve_time: DateTime,
+        time: DateTime,
+    ) -> Vec<Arc<ConditionEvent>> {
+        if self.shelving != Some(ShelvingState::TimedShelved { unshelve_time }) {
+            return Vec::new();
+        }
+        self.shelving = Some(ShelvingState::Unshelved);
+        self.report_enabled(None, time)
+    }
+
+    pub(super) fn acknowledge(
+        &mut self,
+        event_id: &ByteString,
+        comment: LocalizedText,
+        user: UAString,
+        time: DateTime,
+    ) -> Result<Vec<Arc<ConditionEvent>>, StatusCode> {
+        self.modify_branch(event_id, time, |branch| {
+            branch.set_acked(time)?;
+            branch.set_comment(comment, user, time);
+            Ok(())
+        })
+    }
+
+    pub(super) fn confirm(
+        &mut self,
+        event_id: &ByteString,
+        comment: LocalizedText,
+        user: UAString,
+        time: DateTime,
+    ) -> Result<Vec<Arc<ConditionEvent>>, StatusCode> {
+        self.modify_branch(event_id, time, |branch| {
+            branch.set_confirmed(time)?;
+            branch.set_comment(comment, user, time);
+            Ok(())
+        })
+    }
+
+    pub(super) fn add_comment(
+        &mut self,
+        event_id: &ByteString,
+        comment: LocalizedText,
+        user: UAString,
+        time: DateTime,
+    ) -> Result<Vec<Arc<ConditionEvent>>, StatusCode> {
+        self.modify_branch(event_id, time, |branch| {
+            branch.set_comment(comment, user, time);
+            Ok(())
+        })
+    }
+
+    fn modify_branch(
+        &mut self,
+        event_id: &ByteString,
+        time: DateTime,
+        f: impl FnOnce(&mut ConditionBranch) -> Result<(), StatusCode>,
+    ) -> Result<Vec<Arc<ConditionEvent>>, StatusCode> {
+        if !self.enabled.value {
+            return Err(StatusCode::BadConditionDisabled);
+        }
+        let index = self.find_branch(event_id)?;
+        f(self.branch_mut(index))?;
+        Ok(vec![self.report(index, time)])
+    }
+
+    /// Find the index of a branch by the ID of its last event, `None` is the main branch.
+    fn find_branch(&self, event_id: &ByteString) -> Result<Option<usize>, StatusCode> {
+        if self.main.event_id() == Some(event_id) {
+            return Ok(None);
+        }
+        self.branches
+            .iter()
+            .position(|b| b.event_id() == Some(event_id))
+            .map(Some)
+            .ok_or(StatusCode::BadEventIdUnknown)
+    }
+
+    fn branch_mut(&mut self, index: Option<usize>) -> &mut ConditionBranch {
+        match index {
+            Some(idx) => &mut self.branches[idx],
+            None => &mut self.main,
+        }
+    }
+
+    /// Copy the current state of the main branch into a new branch.
+    fn create_branch(&mut self) -> usize {
+        let mut branch = self.main.clone();
+        branch.branch_id = NodeId::new(self.node_id.namespace, Guid::new());
+        self.branches.push(branch);
+        self.branches.len() - 1
+    }
+
+    /// Report the current state of a branch, returning the new event.
+    /// Branches that no longer need to be retained are removed.
+    fn report(&mut self, index: Option<usize>, time: DateTime) -> Arc<ConditionEvent> {
+        let evt = Arc::new(self.build_event(index, time));
+        let branch = self.branch_mut(index);
+        branch.last_event = Some(evt.clone());
+        // Branches are removed once they no longer need attention, disabling the
+        // condition only hides them.
+        if let Some(idx) = index {
+            if !self.branches[idx].is_retained(true) {
+                self.branches.remove(idx);
+            }
+        }
+        evt
+    }
+
+    /// Report every branch, main branch first.
+    fn report_all(&mut self, time: DateTime) -> Vec<Arc<ConditionEvent>> {
+        let mut events = vec![self.report(None, time)];
+        // Iterate in reverse since branches may be removed.
+        for idx in (0..self.branches.len()).rev() {
+            events.push(self.report(Some(idx), time));
+        }
+        events
+    }
+
+    /// Report every branch with `Retain` set to false, when the condition is removed.
+    pub(super) fn remove(&mut self, time: DateTime) -> Vec<Arc<ConditionEvent>> {
+        let mut events = Vec::new();
+        for index in std::iter::once(None).chain((0..self.branches.len()).map(Some)) {
+            let mut evt = self.build_event(index, time);
+            evt.retain = false;
+            let evt = Arc::new(evt);
+            self.branch_mut(index).last_event = Some(evt.clone());
+            events.push(evt);
+        }
+        events
+    }
+
+    /// Get the last event of each retained branch, for condition refresh.
+    pub(super) fn retained_events(&self) -> impl Iterator<Item = &Arc<ConditionEvent>> {
+        let enabled = self.enabled.value;
+        std::iter::once(&self.main)
+            .chain(self.branches.iter())
+            .filter(move |b| b.is_retained(enabled))
+            .filter_map(|b| b.last_event.as_ref())
+    }
+
+    fn build_event(&self, index: Option<usize>, time: DateTime) -> ConditionEvent {
+        let branch = match index {
+            Some(idx) => &self.branches[idx],
+            None => &self.main,
+        };
+        let enabled = self.enabled.value;
+        let event_id = ByteString::from(Guid::new().as_bytes().to_vec());
+        let mut base = BaseEventType::new(
+            self.event_type.clone(),
+            event_id,
+            branch.message.clone(),
+            time,
+        )
+        .set_source_node(self.source_node.clone())
+        .set_source_name(self.source_name.clone())
+        .set_severity(branch.severity);
+        base.condition_class_id = Some(self.condition_class_id.clone());
+        base.condition_class_name = Some(self.condition_class_name.clone());
+
+        ConditionEvent {
+            base,
+            node_id: self.node_id.clone(),
+            condition_name: self.condition_name.clone(),
+            branch_id: branch.branch_id.clone(),
+            retain: branch.is_retained(enabled),
+            enabled_state: TwoStateVariable::new(
+                enabled,
+                self.enabled.transition_time,
+                "Enabled",
+                "Disabled",
+            ),
+            quality: ConditionVariable::new(branch.quality, branch.quality_time),
+            last_severity: ConditionVariable::new(branch.last_severity, branch.severity_time),
+            comment: ConditionVariable::new(branch.comment.clone(), branch.comment_time),
+            client_user_id: branch.client_user_id.clone(),
+            acked_state: branch.acked.map(|s| {
+                TwoStateVariable::new(s.value, s.transition_time, "Acknowledged", "Unacknowledged")
+            }),
+            confirmed_state: branch.confirmed.map(|s| {
+                TwoStateVariable::new(s.value, s.transition_time, "Confirmed", "Unconfirmed")
+            }),
+            active_state: branch
+                .active
+                .map(|s| TwoStateVariable::new(s.value, s.transition_time, "Active", "Inactive")),
+            shelving_state: self.shelving.map(|s| s.to_event_field()),
+            suppressed_or_shelved: self.shelving.map(|s| s.is_shelved()),
+        }
+    }
+}
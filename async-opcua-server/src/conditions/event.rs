@@ -0,0 +1,153 @@
+use opcua_nodes::{BaseEventType, Event, EventField};
+use opcua_types::{
+    AttributeId, DateTime, LocalizedText, NodeId, NumericRange, ObjectTypeId, QualifiedName,
+    UAString, Variant,
+};
+
+// The derive macros refer to `opcua::nodes` and `opcua::types`.
+mod opcua {
+    pub use opcua_nodes as nodes;
+    pub use opcua_types as types;
+}
+
+#[derive(Debug, Default, EventField)]
+/// Event field for a `TwoStateVariableType`, such as `EnabledState` or `AckedState`.
+pub struct TwoStateVariable {
+    /// Display value of the state, e.g. `Enabled` or `Disabled`.
+    pub value: LocalizedText,
+    /// Boolean value of the state.
+    pub id: bool,
+    /// Time of the last transition of this state.
+    pub transition_time: DateTime,
+    /// Display value used when `id` is true.
+    pub true_state: LocalizedText,
+    /// Display value used when `id` is false.
+    pub false_state: LocalizedText,
+}
+
+impl TwoStateVariable {
+    pub(super) fn new(
+        id: bool,
+        transition_time: DateTime,
+        true_state: &'static str,
+        false_state: &'static str,
+    ) -> Self {
+        Self {
+            value: if id { true_state } else { false_state }.into(),
+            id,
+            transition_time,
+            true_state: true_state.into(),
+            false_state: false_state.into(),
+        }
+    }
+}
+
+#[derive(Debug, Default, EventField)]
+/// Event field for a `ConditionVariableType`, such as `Quality` or `Comment`.
+pub struct ConditionVariable {
+    /// Current value of the variable.
+    pub value: Variant,
+    /// Time the value last changed.
+    pub source_timestamp: DateTime,
+}
+
+impl ConditionVariable {
+    pub(super) fn new(value: impl Into<Variant>, source_timestamp: DateTime) -> Self {
+        Self {
+            value: value.into(),
+            source_timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Default, EventField)]
+/// Event field for a `FiniteStateVariableType`.
+pub struct FiniteStateVariable {
+    /// Display value of the current state.
+    pub value: LocalizedText,
+    /// Node ID of the current state.
+    pub id: NodeId,
+}
+
+#[derive(Debug, Default, EventField)]
+/// Event field for the `ShelvingState` of an alarm.
+pub struct ShelvingStateMachine {
+    /// The current shelving state.
+    pub current_state: FiniteStateVariable,
+    /// Remaining time in milliseconds before a timed shelve expires.
+    pub unshelve_time: Option<f64>,
+}
+
+#[derive(Debug, EventField)]
+/// Event reported for a condition or one of its branches.
+///
+/// This covers the fields of `ConditionType`, `AcknowledgeableConditionType`
+/// and `AlarmConditionType`. Fields that do not apply to the condition, such as
+/// `ActiveState` on a condition that is not an alarm, are empty.
+pub struct ConditionEvent {
+    /// Base event fields.
+    pub base: BaseEventType,
+    /// The node ID of the condition, reported as the `ConditionId`.
+    pub node_id: NodeId,
+    /// Name of the condition.
+    pub condition_name: UAString,
+    /// Branch ID, null for the main branch.
+    pub branch_id: NodeId,
+    /// Whether the condition is currently of interest to clients.
+    pub retain: bool,
+    /// Enabled state of the condition.
+    pub enabled_state: TwoStateVariable,
+    /// Quality of the source data.
+    pub quality: ConditionVariable,
+    /// Previous severity of the condition.
+    pub last_severity: ConditionVariable,
+    /// Last comment added to the condition.
+    pub comment: ConditionVariable,
+    /// User that added the last comment.
+    pub client_user_id: UAString,
+    /// Acknowledged state, for acknowledgeable conditions.
+    pub acked_state: Option<TwoStateVariable>,
+    /// Confirmed state, for acknowledgeable conditions that support confirm.
+    pub confirmed_state: Option<TwoStateVariable>,
+    /// Active state, for alarms.
+    pub active_state: Option<TwoStateVariable>,
+    /// Shelving state, for alarms.
+    pub shelving_state: Option<ShelvingStateMachine>,
+    /// Whether the alarm is shelved, for alarms.
+    pub suppressed_or_shelved: Option<bool>,
+}
+
+impl ConditionEvent {
+    fn has_type(&self, type_definition_id: &NodeId) -> bool {
+        type_definition_id == &ObjectTypeId::ConditionType
+            || type_definition_id == &self.base.event_type
+            || (type_definition_id == &ObjectTypeId::AcknowledgeableConditionType
+                && self.acked_state.is_some())
+            || (type_definition_id == &ObjectTypeId::AlarmConditionType
+                && self.active_state.is_some())
+    }
+}
+
+impl Event for ConditionEvent {
+    fn get_field(
+        &self,
+        type_definition_id: &NodeId,
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+        browse_path: &[QualifiedName],
+    ) -> Variant {
+        if type_definition_id == &ObjectTypeId::BaseEventType {
+            return self
+                .base
+                .get_field(type_definition_id, attribute_id, index_range, browse_path);
+        }
+        if !self.has_type(type_definition_id) {
+            return Variant::Empty;
+        }
+        self.get_value(attribute_id, index_range, browse_path)
+    }
+
+    fn time(&self) -> &DateTime {
+        &self.base.time
+    }
+}
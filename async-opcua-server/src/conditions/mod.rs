@@ -0,0 +1,423 @@
+//! Support for alarms and conditions, as described in OPC-UA part 9.
+//!
+//! The [`ConditionManager`] keeps the state of every condition registered with it,
+//! reports condition events to subscriptions, and handles the standard condition
+//! methods, as well as `ConditionRefresh` and `ConditionRefresh2`.
+
+mod condition;
+mod event;
+
+use std::{sync::Arc, time::Duration};
+
+use hashbrown::HashMap;
+use opcua_core::{sync::RwLock, trace_lock, trace_read_lock, trace_write_lock};
+use opcua_nodes::{BaseEventType, Event};
+use opcua_types::{
+    AttributeId, ByteString, DateTime, Guid, LocalizedText, MethodId, NodeId, ObjectId,
+    ObjectTypeId, StatusCode, UAString, Variant, VariantScalarTypeId, VariantTypeId,
+};
+
+pub use condition::{Condition, ShelvingState};
+pub use event::{
+    ConditionEvent, ConditionVariable, FiniteStateVariable, ShelvingStateMachine, TwoStateVariable,
+};
+
+use crate::{
+    load_method_args,
+    node_manager::{check_argument_count, MethodCall, RequestContext},
+    SubscriptionCache,
+};
+
+/// Structure storing the state of all alarms and conditions on the server.
+///
+/// Conditions are registered with [`ConditionManager::add_condition`], after which
+/// the server application reports changes to the underlying process through methods such as
+/// [`ConditionManager::set_active`]. Each change is reported as an event to monitored
+/// items on the source node of the condition, and on the `Server` object.
+///
+/// The `Enable`, `Disable`, `AddComment`, `Acknowledge` and `Confirm` methods are handled
+/// automatically for registered conditions, as are `ConditionRefresh` and `ConditionRefresh2`.
+pub struct ConditionManager {
+    conditions: RwLock<HashMap<NodeId, Condition>>,
+    subscriptions: Arc<SubscriptionCache>,
+}
+
+impl ConditionManager {
+    pub(crate) fn new(subscriptions: Arc<SubscriptionCache>) -> Self {
+        Self {
+            conditions: RwLock::new(HashMap::new()),
+            subscriptions,
+        }
+    }
+
+    /// Register a condition, replacing any existing condition with the same node ID.
+    ///
+    /// No event is reported until the state of the condition changes.
+    pub fn add_condition(&self, condition: Condition) {
+        let mut conditions = trace_write_lock!(self.conditions);
+        conditions.insert(condition.node_id().clone(), condition);
+    }
+
+    /// Remove a condition. If the condition is retained, a final event is
+    /// reported with `Retain` set to false.
+    pub fn remove_condition(&self, node_id: &NodeId) -> Option<Condition> {
+        let mut condition = {
+            let mut conditions = trace_write_lock!(self.conditions);
+            conditions.remove(node_id)?
+        };
+        if condition.retain() {
+            let events = condition.remove(DateTime::now());
+            self.notify(&condition.notifier(), &events);
+        }
+        Some(condition)
+    }
+
+    /// Get a copy of the current state of a condition.
+    pub fn get_condition(&self, node_id: &NodeId) -> Option<Condition> {
+        let conditions = trace_read_lock!(self.conditions);
+        conditions.get(node_id).cloned()
+    }
+
+    /// Get the node IDs of all registered conditions.
+    pub fn condition_ids(&self) -> Vec<NodeId> {
+        let conditions = trace_read_lock!(self.conditions);
+        conditions.keys().cloned().collect()
+    }
+
+    /// Set the active state of an alarm.
+    ///
+    /// When an alarm becomes active while the previous activation has not yet been
+    /// acknowledged or confirmed, the previous state is kept as a separate branch, which
+    /// is removed once clients have acknowledged and confirmed it.
+    pub fn set_active(
+        &self,
+        node_id: &NodeId,
+        active: bool,
+        message: impl Into<LocalizedText>,
+    ) -> Result<(), StatusCode> {
+        let message = message.into();
+        self.update(node_id, |c, time| c.update_active(active, message, time))
+    }
+
+    /// Set the severity of a condition. The previous severity becomes the `LastSeverity`.
+    pub fn set_severity(
+        &self,
+        node_id: &NodeId,
+        severity: u16,
+        message: impl Into<LocalizedText>,
+    ) -> Result<(), StatusCode> {
+        let message = message.into();
+        self.update(node_id, |c, time| {
+            Ok(c.update_severity(severity, message, time))
+        })
+    }
+
+    /// Set the quality of the source data of a condition.
+    pub fn set_quality(
+        &self,
+        node_id: &NodeId,
+        quality: StatusCode,
+        message: impl Into<LocalizedText>,
+    ) -> Result<(), StatusCode> {
+        let message = message.into();
+        self.update(node_id, |c, time| {
+            Ok(c.update_quality(quality, message, time))
+        })
+    }
+
+    /// Set whether a condition should be retained, regardless of its other state.
+    ///
+    /// This is how the state of plain conditions is controlled. Acknowledgeable
+    /// conditions and alarms are also retained while they need attention.
+    pub fn set_retain(
+        &self,
+        node_id: &NodeId,
+        retain: bool,
+        message: impl Into<LocalizedText>,
+    ) -> Result<(), StatusCode> {
+        let message = message.into();
+        self.update(
+            node_id,
+            |c, time| Ok(c.update_retain(retain, message, time)),
+        )
+    }
+
+    /// Set the shelving state of an alarm. Timed shelves are automatically
+    /// removed once the unshelve time is reached.
+    ///
+    /// Timed shelving schedules the unshelve on the current Tokio runtime, so
+    /// this returns `BadInvalidState` if called outside of a runtime.
+    pub fn set_shelving_state(
+        self: &Arc<Self>,
+        node_id: &NodeId,
+        state: ShelvingState,
+    ) -> Result<(), StatusCode> {
+        let runtime = if let ShelvingState::TimedShelved { unshelve_time } = state {
+            if unshelve_time <= DateTime::now() {
+                return Err(StatusCode::BadShelvingTimeOutOfRange);
+            }
+            let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+                return Err(StatusCode::BadInvalidState);
+            };
+            Some((runtime, unshelve_time))
+        } else {
+            None
+        };
+        self.update(node_id, |c, time| c.update_shelving(state, time))?;
+
+        if let Some((runtime, unshelve_time)) = runtime {
+            let manager = Arc::downgrade(self);
+            let node_id = node_id.clone();
+            let delay = (unshelve_time.ticks() - DateTime::now().ticks()).max(0) as u64 * 100;
+            runtime.spawn(async move {
+                tokio::time::sleep(Duration::from_nanos(delay)).await;
+                if let Some(manager) = manager.upgrade() {
+                    // The alarm may have been unshelved or shelved again in the meantime,
+                    // that's handled by `expire_shelving`.
+                    let _ = manager.update(&node_id, |c, time| {
+                        Ok(c.expire_shelving(unshelve_time, time))
+                    });
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Enable a condition. This reports the current state of every branch of the condition.
+    pub fn enable(&self, node_id: &NodeId) -> Result<(), StatusCode> {
+        self.update(node_id, |c, time| c.update_enabled(true, time))
+    }
+
+    /// Disable a condition. This reports every branch of the condition with
+    /// `Retain` set to false, and no further events are reported until it is enabled again.
+    /// State changes made while the condition is disabled are still kept.
+    pub fn disable(&self, node_id: &NodeId) -> Result<(), StatusCode> {
+        self.update(node_id, |c, time| c.update_enabled(false, time))
+    }
+
+    /// Acknowledge the branch of a condition identified by `event_id`, which must
+    /// be the ID of the last event reported for that branch.
+    pub fn acknowledge(
+        &self,
+        node_id: &NodeId,
+        event_id: &ByteString,
+        comment: LocalizedText,
+        client_user_id: UAString,
+    ) -> Result<(), StatusCode> {
+        self.update(node_id, |c, time| {
+            c.acknowledge(event_id, comment, client_user_id, time)
+        })
+    }
+
+    /// Confirm the branch of a condition identified by `event_id`, which must
+    /// be the ID of the last event reported for that branch.
+    pub fn confirm(
+        &self,
+        node_id: &NodeId,
+        event_id: &ByteString,
+        comment: LocalizedText,
+        client_user_id: UAString,
+    ) -> Result<(), StatusCode> {
+        self.update(node_id, |c, time| {
+            c.confirm(event_id, comment, client_user_id, time)
+        })
+    }
+
+    /// Add a comment to the branch of a condition identified by `event_id`, which must
+    /// be the ID of the last event reported for that branch.
+    pub fn add_comment(
+        &self,
+        node_id: &NodeId,
+        event_id: &ByteString,
+        comment: LocalizedText,
+        client_user_id: UAString,
+    ) -> Result<(), StatusCode> {
+        self.update(node_id, |c, time| {
+            c.add_comment(event_id, comment, client_user_id, time)
+        })
+    }
+
+    fn update(
+        &self,
+        node_id: &NodeId,
+        f: impl FnOnce(&mut Condition, DateTime) -> Result<Vec<Arc<ConditionEvent>>, StatusCode>,
+    ) -> Result<(), StatusCode> {
+        let (events, notifier) = {
+            let mut conditions = trace_write_lock!(self.conditions);
+            let condition = conditions
+                .get_mut(node_id)
+                .ok_or(StatusCode::BadNodeIdUnknown)?;
+            (f(condition, DateTime::now())?, condition.notifier())
+        };
+        self.notify(&notifier, &events);
+        Ok(())
+    }
+
+    fn notify(&self, notifier: &NodeId, events: &[Arc<ConditionEvent>]) {
+        if events.is_empty() {
+            return;
+        }
+        self.subscriptions
+            .notify_events(events.iter().map(|e| (e.as_ref() as &dyn Event, notifier)));
+    }
+
+    /// Get the event type of a registered condition.
+    pub(crate) fn condition_type(&self, node_id: &NodeId) -> Option<NodeId> {
+        let conditions = trace_read_lock!(self.conditions);
+        conditions.get(node_id).map(|c| c.event_type().clone())
+    }
+
+    /// Handle a call to one of the standard condition methods, returning `false` if
+    /// the call is not for a condition method. The call must already have been
+    /// validated by the node manager owning the method.
+    pub(crate) fn call(&self, context: &RequestContext, call: &mut MethodCall) -> bool {
+        let Ok(method) = call.method_id().as_method_id() else {
+            return false;
+        };
+        let handled = match method {
+            MethodId::ConditionType_ConditionRefresh
+            | MethodId::ConditionType_ConditionRefresh2 => {
+                call.object_id() == &ObjectTypeId::ConditionType
+            }
+            MethodId::ConditionType_Enable
+            | MethodId::ConditionType_Disable
+            | MethodId::ConditionType_AddComment
+            | MethodId::AcknowledgeableConditionType_Acknowledge
+            | MethodId::AcknowledgeableConditionType_Confirm => {
+                trace_read_lock!(self.conditions).contains_key(call.object_id())
+            }
+            _ => false,
+        };
+        if !handled {
+            return false;
+        }
+
+        match self.call_method(context, method, call) {
+            Ok(()) => call.set_status(StatusCode::Good),
+            Err(e) => call.set_status(e),
+        }
+        true
+    }
+
+    fn call_method(
+        &self,
+        context: &RequestContext,
+        method: MethodId,
+        call: &MethodCall,
+    ) -> Result<(), StatusCode> {
+        let user = UAString::from(context.token.0.as_str());
+        match method {
+            MethodId::ConditionType_ConditionRefresh => {
+                check_argument_count(call, 1)?;
+                let subscription_id = load_method_args!(call, UInt32)?;
+                self.condition_refresh(context, subscription_id, None)
+            }
+            MethodId::ConditionType_ConditionRefresh2 => {
+                check_argument_count(call, 2)?;
+                let (subscription_id, monitored_item_id) = load_method_args!(call, UInt32, UInt32)?;
+                self.condition_refresh(context, subscription_id, Some(monitored_item_id))
+            }
+            MethodId::ConditionType_Enable => {
+                check_argument_count(call, 0)?;
+                self.enable(call.object_id())
+            }
+            MethodId::ConditionType_Disable => {
+                check_argument_count(call, 0)?;
+                self.disable(call.object_id())
+            }
+            MethodId::ConditionType_AddComment => {
+                check_argument_count(call, 2)?;
+                let (event_id, comment) = load_method_args!(call, ByteString, LocalizedText)?;
+                self.add_comment(call.object_id(), &event_id, *comment, user)
+            }
+            MethodId::AcknowledgeableConditionType_Acknowledge => {
+                check_argument_count(call, 2)?;
+                let (event_id, comment) = load_method_args!(call, ByteString, LocalizedText)?;
+                self.acknowledge(call.object_id(), &event_id, *comment, user)
+            }
+            MethodId::AcknowledgeableConditionType_Confirm => {
+                check_argument_count(call, 2)?;
+                let (event_id, comment) = load_method_args!(call, ByteString, LocalizedText)?;
+                self.confirm(call.object_id(), &event_id, *comment, user)
+            }
+            _ => Err(StatusCode::BadMethodInvalid),
+        }
+    }
+
+    /// Report the current state of all retained conditions to the event monitored items
+    /// of a subscription, or to a single monitored item if `monitored_item_id` is set.
+    ///
+    /// The conditions are framed by a `RefreshStartEvent` and a `RefreshEndEvent`.
+    fn condition_refresh(
+        &self,
+        context: &RequestContext,
+        subscription_id: u32,
+        monitored_item_id: Option<u32>,
+    ) -> Result<(), StatusCode> {
+        let subscriptions = context
+            .subscriptions
+            .get_session_subscriptions(context.session_id)
+            .ok_or(StatusCode::BadSubscriptionIdInvalid)?;
+
+        // Hold the lock on conditions throughout, so that the refresh is not
+        // interleaved with events for the conditions being refreshed.
+        let conditions = trace_read_lock!(self.conditions);
+        let mut subscriptions = trace_lock!(subscriptions);
+        let subscription = subscriptions
+            .get_mut(subscription_id)
+            .ok_or(StatusCode::BadSubscriptionIdInvalid)?;
+
+        let items: Vec<_> = subscription
+            .items()
+            .filter(|i| {
+                i.item_to_monitor().attribute_id == AttributeId::EventNotifier
+                    && monitored_item_id.is_none_or(|id| id == i.id())
+            })
+            .map(|i| (i.id(), i.item_to_monitor().node_id.clone()))
+            .collect();
+        if let Some(id) = monitored_item_id {
+            if items.is_empty() {
+                return Err(if subscription.contains_key(&id) {
+                    StatusCode::BadNotSupported
+                } else {
+                    StatusCode::BadMonitoredItemIdInvalid
+                });
+            }
+        }
+
+        let server_id: NodeId = ObjectId::Server.into();
+        let start = refresh_event(
+            ObjectTypeId::RefreshStartEventType,
+            "Condition refresh start",
+        );
+        for (id, _) in &items {
+            subscription.notify_event(id, &start);
+        }
+        for condition in conditions.values() {
+            let notifier = condition.notifier();
+            for evt in condition.retained_events() {
+                for (id, node_id) in &items {
+                    if node_id == &server_id || node_id == &notifier {
+                        subscription.notify_event(id, evt.as_ref());
+                    }
+                }
+            }
+        }
+        let end = refresh_event(ObjectTypeId::RefreshEndEventType, "Condition refresh end");
+        for (id, _) in &items {
+            subscription.notify_event(id, &end);
+        }
+
+        Ok(())
+    }
+}
+
+fn refresh_event(event_type: ObjectTypeId, message: &str) -> BaseEventType {
+    BaseEventType::new_now(
+        event_type,
+        ByteString::from(Guid::new().as_bytes().to_vec()),
+        message,
+    )
+    .set_source_node(ObjectId::Server.into())
+    .set_source_name("Server".into())
+}
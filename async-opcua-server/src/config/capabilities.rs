@@ -36,7 +36,8 @@ pub struct HistoryServerCapabilities {
     pub update_data: bool,
     /// Able to update historical events.
     pub update_event: bool,
-    /// Supported history aggregates
+    /// Supported history aggregates. See [AggregateType](crate::node_manager::AggregateType)
+    /// for the aggregates implemented by the server library.
     pub aggregates: Vec<NodeId>,
 }
 
@@ -6,4 +6,7 @@ mod server;
 pub use capabilities::{HistoryServerCapabilities, ServerCapabilities};
 pub use endpoint::{EndpointIdentifier, ServerEndpoint};
 pub use limits::{Limits, OperationalLimits, SubscriptionLimits};
-pub use server::{ServerConfig, ServerUserToken, ANONYMOUS_USER_TOKEN_ID};
+pub use server::{
+    HttpsConfig, LocalDiscoveryConfig, ReverseConnectConfig, ReverseConnectTarget, ServerConfig,
+    ServerUserToken, WebSocketConfig, ANONYMOUS_USER_TOKEN_ID,
+};
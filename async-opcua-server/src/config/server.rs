@@ -12,9 +12,12 @@ use std::{
 use log::{trace, warn};
 use serde::{Deserialize, Serialize};
 
-use crate::constants;
-use opcua_core::{comms::url::url_matches_except_host, config::Config};
-use opcua_crypto::{CertificateStore, SecurityPolicy, Thumbprint};
+use crate::{constants, jwt::JwtConfig, roles::WellKnownRole};
+use opcua_core::{
+    comms::url::{is_opc_ua_binary_url, url_matches_except_host, OPC_WSS_SCHEME, OPC_WS_SCHEME},
+    config::Config,
+};
+use opcua_crypto::{password, CertificateStore, SecurityPolicy, Thumbprint};
 use opcua_types::{
     ApplicationDescription, ApplicationType, DecodingOptions, LocalizedText, MessageSecurityMode,
     UAString,
@@ -43,12 +46,25 @@ pub struct ServerUserToken {
     /// Password
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pass: Option<String>,
+    /// Salted hash of the password in the PHC string format, used instead of `pass`.
+    /// Argon2 and PBKDF2 hashes are supported, see [`opcua_crypto::password`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pass_hash: Option<String>,
     /// X509 file path (as a string)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub x509: Option<String>,
     /// X509 thumbprint.
     #[serde(skip)]
     pub thumbprint: Option<Thumbprint>,
+    /// Validation of JSON Web Tokens, sent as issued identity tokens. The user name
+    /// is only used to describe the token, the user is the subject of each token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt: Option<JwtConfig>,
+    /// Names of the well-known roles granted to this user, for example `Operator`
+    /// or `SecurityAdmin`. Users authenticated with a JWT also get the roles mapped
+    /// from the scopes of their token.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
 }
 
 impl ServerUserToken {
@@ -60,8 +76,28 @@ impl ServerUserToken {
         ServerUserToken {
             user: user.into(),
             pass: Some(pass.into()),
+            pass_hash: None,
             x509: None,
             thumbprint: None,
+            jwt: None,
+            roles: Vec::new(),
+        }
+    }
+
+    /// Create a user pass token with a password hash in the PHC string format,
+    /// for example created with [`opcua_crypto::password::hash_password`].
+    pub fn user_pass_hash<T>(user: T, pass_hash: T) -> Self
+    where
+        T: Into<String>,
+    {
+        ServerUserToken {
+            user: user.into(),
+            pass: None,
+            pass_hash: Some(pass_hash.into()),
+            x509: None,
+            thumbprint: None,
+            jwt: None,
+            roles: Vec::new(),
         }
     }
 
@@ -73,8 +109,27 @@ impl ServerUserToken {
         ServerUserToken {
             user: user.into(),
             pass: None,
+            pass_hash: None,
             x509: Some(cert_path.to_string_lossy().to_string()),
             thumbprint: None,
+            jwt: None,
+            roles: Vec::new(),
+        }
+    }
+
+    /// Create a token accepting JSON Web Tokens, validated with the given configuration.
+    pub fn jwt<T>(user: T, config: JwtConfig) -> Self
+    where
+        T: Into<String>,
+    {
+        ServerUserToken {
+            user: user.into(),
+            pass: None,
+            pass_hash: None,
+            x509: None,
+            thumbprint: None,
+            jwt: Some(config),
+            roles: Vec::new(),
         }
     }
 
@@ -105,17 +160,49 @@ impl ServerUserToken {
         if self.user.is_empty() {
             errors.push(format!("User token {} has an empty user name.", id));
         }
-        if self.pass.is_some() && self.x509.is_some() {
+        if self.pass.is_some() && self.pass_hash.is_some() {
             errors.push(format!(
-                "User token {} holds a password and certificate info - it cannot be both.",
+                "User token {} holds a password and a password hash - it cannot be both.",
                 id
             ));
-        } else if self.pass.is_none() && self.x509.is_none() {
+        }
+        if let Some(Err(e)) = self
+            .pass_hash
+            .as_ref()
+            .map(|hash| password::validate_password_hash(hash))
+        {
             errors.push(format!(
-                "User token {} fails to provide a password or certificate info.",
-                id
+                "User token {} has an invalid password hash: {}",
+                id, e
             ));
         }
+        let kinds = [
+            self.pass.is_some() || self.pass_hash.is_some(),
+            self.x509.is_some(),
+            self.jwt.is_some(),
+        ];
+        match kinds.iter().filter(|k| **k).count() {
+            0 => errors.push(format!(
+                "User token {} fails to provide a password, certificate info or JWT validation.",
+                id
+            )),
+            1 => {}
+            _ => errors.push(format!(
+                "User token {} holds more than one of a password, certificate info and JWT validation - it can only be one.",
+                id
+            )),
+        }
+        if let Some(Err(e)) = self.jwt.as_ref().map(|jwt| jwt.validate(id)) {
+            errors.extend(e);
+        }
+        for role in &self.roles {
+            if WellKnownRole::from_name(role).is_none() {
+                errors.push(format!(
+                    "User token {} has the role {}, which is not a well-known role.",
+                    id, role
+                ));
+            }
+        }
         if errors.is_empty() {
             Ok(())
         } else {
@@ -125,13 +212,18 @@ impl ServerUserToken {
 
     /// Return `true` if this token is for username/password auth.
     pub fn is_user_pass(&self) -> bool {
-        self.x509.is_none()
+        self.x509.is_none() && self.jwt.is_none()
     }
 
     /// Return `true` if this token is for X509-based auth.
     pub fn is_x509(&self) -> bool {
         self.x509.is_some()
     }
+
+    /// Return `true` if this token is for JSON Web Token auth.
+    pub fn is_jwt(&self) -> bool {
+        self.jwt.is_some()
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -141,6 +233,10 @@ pub struct CertificateValidation {
     pub trust_client_certs: bool,
     /// Check the valid from/to fields of a certificate
     pub check_time: bool,
+    /// Accept client certificates issued by a CA without a CRL, or with an out of
+    /// date CRL, whose revocation status is unknown.
+    #[serde(default)]
+    pub allow_missing_crls: bool,
 }
 
 impl Default for CertificateValidation {
@@ -148,10 +244,123 @@ impl Default for CertificateValidation {
         Self {
             trust_client_certs: false,
             check_time: true,
+            allow_missing_crls: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+/// Configuration for running the server as a local discovery server (LDS).
+pub struct LocalDiscoveryConfig {
+    /// Run the server as a local discovery server. Other servers may then register
+    /// themselves using `RegisterServer` and `RegisterServer2`, and are returned from
+    /// `FindServers` and `FindServersOnNetwork`.
+    pub enabled: bool,
+    /// Time in milliseconds before a registration expires if the server does not
+    /// register again. Servers are expected to register at least every 10 minutes.
+    /// Set to 0 to never expire registrations.
+    #[serde(default = "defaults::registration_timeout_ms")]
+    pub registration_timeout_ms: u64,
+    /// Remove registrations if their semaphore file does not exist.
+    #[serde(default = "defaults::check_semaphore_files")]
+    pub check_semaphore_files: bool,
+}
+
+impl Default for LocalDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            registration_timeout_ms: defaults::registration_timeout_ms(),
+            check_semaphore_files: defaults::check_semaphore_files(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+/// A client the server should connect to using reverse connect.
+pub struct ReverseConnectTarget {
+    /// URL the client is listening on, for example `opc.tcp://client-host:4841`.
+    pub client_url: String,
+    /// Endpoint URL sent to the client in the reverse hello message, the client
+    /// uses this to pick an endpoint. If this is not set, the base endpoint
+    /// of the server is used.
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+/// Configuration for reverse connect, where the server opens connections
+/// to clients, for example when the server is behind a firewall.
+pub struct ReverseConnectConfig {
+    /// Clients the server should connect to.
+    #[serde(default)]
+    pub targets: Vec<ReverseConnectTarget>,
+    /// Time in milliseconds to wait before reconnecting to a client after
+    /// a connection attempt fails or a connection is closed.
+    #[serde(default = "defaults::reverse_connect_retry_interval_ms")]
+    pub retry_interval_ms: u64,
+}
+
+impl Default for ReverseConnectConfig {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            retry_interval_ms: defaults::reverse_connect_retry_interval_ms(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+/// Configuration for the OPC-UA over WebSockets transport. Requires the
+/// `websocket` feature.
+pub struct WebSocketConfig {
+    /// The port number to listen for WebSocket connections on.
+    pub port: u16,
+    /// Use TLS for WebSocket connections, giving `opc.wss` endpoints.
+    /// If this is false, endpoints use unencrypted `opc.ws`.
+    #[serde(default = "defaults::websocket_tls")]
+    pub tls: bool,
+    /// Path to the TLS certificate. If this is not set, the application
+    /// instance certificate is used.
+    #[serde(default)]
+    pub certificate_path: Option<PathBuf>,
+    /// Path to the TLS private key. If this is not set, the application
+    /// instance private key is used.
+    #[serde(default)]
+    pub private_key_path: Option<PathBuf>,
+}
+
+impl WebSocketConfig {
+    /// Get the URL scheme for endpoints on this transport.
+    pub fn scheme(&self) -> &'static str {
+        if self.tls {
+            OPC_WSS_SCHEME
+        } else {
+            OPC_WS_SCHEME
         }
     }
 }
 
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+/// Configuration for the OPC-UA over HTTPS transport. Requires the
+/// `https` feature.
+pub struct HttpsConfig {
+    /// The port number to listen for HTTPS connections on.
+    pub port: u16,
+    /// Path to the TLS certificate. If this is not set, the application
+    /// instance certificate is used.
+    #[serde(default)]
+    pub certificate_path: Option<PathBuf>,
+    /// Path to the TLS private key. If this is not set, the application
+    /// instance private key is used.
+    #[serde(default)]
+    pub private_key_path: Option<PathBuf>,
+    /// Time in milliseconds to wait for a complete request on an HTTPS connection,
+    /// after which the connection is closed.
+    #[serde(default = "defaults::https_idle_timeout_ms")]
+    pub idle_timeout_ms: u64,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 /// Server configuration object.
 pub struct ServerConfig {
@@ -180,8 +389,23 @@ pub struct ServerConfig {
     /// register the server with a discovery server.
     #[serde(default)]
     pub discovery_server_url: Option<String>,
+    /// Local discovery server configuration. If enabled, the server acts as a
+    /// discovery server for other servers.
+    #[serde(default)]
+    pub local_discovery: LocalDiscoveryConfig,
     /// tcp configuration information
     pub tcp_config: TcpConfig,
+    /// Reverse connect configuration, clients the server should connect to.
+    #[serde(default)]
+    pub reverse_connect: ReverseConnectConfig,
+    /// WebSocket configuration. If this is set the server also listens for
+    /// `opc.ws` or `opc.wss` connections, and exposes all endpoints over WebSockets.
+    #[serde(default)]
+    pub websocket_config: Option<WebSocketConfig>,
+    /// HTTPS configuration. If this is set the server also accepts service requests
+    /// over `https`, for endpoints without message security.
+    #[serde(default)]
+    pub https_config: Option<HttpsConfig>,
     /// Server OPA UA limits
     #[serde(default)]
     pub limits: Limits,
@@ -247,6 +471,26 @@ mod defaults {
     pub fn max_session_timeout_ms() -> u64 {
         constants::MAX_SESSION_TIMEOUT
     }
+
+    pub fn registration_timeout_ms() -> u64 {
+        constants::DEFAULT_REGISTRATION_TIMEOUT_MS
+    }
+
+    pub fn check_semaphore_files() -> bool {
+        true
+    }
+
+    pub fn reverse_connect_retry_interval_ms() -> u64 {
+        constants::DEFAULT_REVERSE_CONNECT_RETRY_INTERVAL_MS
+    }
+
+    pub fn websocket_tls() -> bool {
+        true
+    }
+
+    pub fn https_idle_timeout_ms() -> u64 {
+        constants::DEFAULT_HTTPS_IDLE_TIMEOUT_MS
+    }
 }
 
 impl Config for ServerConfig {
@@ -301,6 +545,75 @@ impl Config for ServerConfig {
         if self.discovery_urls.is_empty() {
             errors.push("Server configuration is invalid. Discovery urls not set".to_owned());
         }
+        if self.local_discovery.enabled
+            && self
+                .endpoints
+                .values()
+                .all(|e| e.message_security_mode() == MessageSecurityMode::None)
+        {
+            warn!("Local discovery server is enabled, but there are no secure endpoints. Servers will not be able to register");
+        }
+        for target in &self.reverse_connect.targets {
+            if !is_opc_ua_binary_url(&target.client_url) {
+                errors.push(format!(
+                    "Reverse connect client url {} is not a valid opc.tcp url",
+                    target.client_url
+                ));
+            }
+        }
+        if let Some(websocket) = &self.websocket_config {
+            if !cfg!(feature = "websocket") {
+                errors.push(
+                    "WebSocket transport is configured, but the websocket feature is not enabled"
+                        .to_owned(),
+                );
+            }
+            if websocket.certificate_path.is_some() != websocket.private_key_path.is_some() {
+                errors.push(
+                    "WebSocket TLS certificate and private key must be set together".to_owned(),
+                );
+            }
+            if websocket.port != 0 && websocket.port == self.tcp_config.port {
+                errors.push(format!(
+                    "WebSocket port {} is the same as the TCP port",
+                    websocket.port
+                ));
+            }
+        }
+        if let Some(https) = &self.https_config {
+            if !cfg!(feature = "https") {
+                errors.push(
+                    "HTTPS transport is configured, but the https feature is not enabled"
+                        .to_owned(),
+                );
+            }
+            if https.certificate_path.is_some() != https.private_key_path.is_some() {
+                errors
+                    .push("HTTPS TLS certificate and private key must be set together".to_owned());
+            }
+            if https.port != 0
+                && (https.port == self.tcp_config.port
+                    || self
+                        .websocket_config
+                        .as_ref()
+                        .is_some_and(|w| w.port == https.port))
+            {
+                errors.push(format!(
+                    "HTTPS port {} is already used by another transport",
+                    https.port
+                ));
+            }
+            if !self
+                .endpoints
+                .values()
+                .any(|e| e.security_policy() == SecurityPolicy::None)
+            {
+                errors.push(
+                    "HTTPS transport is configured, but there are no endpoints without security"
+                        .to_owned(),
+                );
+            }
+        }
 
         if errors.is_empty() {
             Ok(())
@@ -322,7 +635,11 @@ impl Config for ServerConfig {
     }
 
     fn application_type(&self) -> ApplicationType {
-        ApplicationType::Server
+        if self.local_discovery.enabled {
+            ApplicationType::DiscoveryServer
+        } else {
+            ApplicationType::Server
+        }
     }
 
     fn discovery_urls(&self) -> Option<Vec<UAString>> {
@@ -359,11 +676,15 @@ impl Default for ServerConfig {
             pki_dir,
             certificate_validation: CertificateValidation::default(),
             discovery_server_url: None,
+            local_discovery: LocalDiscoveryConfig::default(),
             tcp_config: TcpConfig {
                 host: "127.0.0.1".to_string(),
                 port: constants::DEFAULT_RUST_OPC_UA_SERVER_PORT,
                 hello_timeout: constants::DEFAULT_HELLO_TIMEOUT_SECONDS,
             },
+            reverse_connect: ReverseConnectConfig::default(),
+            websocket_config: None,
+            https_config: None,
             limits: Limits::default(),
             user_tokens: BTreeMap::new(),
             locale_ids: vec!["en".to_string()],
@@ -410,10 +731,7 @@ impl ServerConfig {
             application_name,
             application_uri,
             product_uri,
-            certificate_validation: CertificateValidation {
-                trust_client_certs: false,
-                check_time: true,
-            },
+            certificate_validation: CertificateValidation::default(),
             pki_dir,
             discovery_server_url,
             tcp_config: TcpConfig {
@@ -1,7 +1,8 @@
 use log::{debug, error};
 use opcua_client::{Client, ClientBuilder};
+use opcua_crypto::CertificateStoreBackend;
 use opcua_types::RegisteredServer;
-use std::{path::PathBuf, time::Duration};
+use std::{sync::Arc, time::Duration};
 
 use futures::never::Never;
 
@@ -61,7 +62,7 @@ the discovery server and vice versa. The default discovery server PKI directory
 fn periodic_discovery_server_registration(
     discovery_server_url: &str,
     _registered_server: RegisteredServer,
-    _pki_dir: PathBuf,
+    _certificate_store_backend: Arc<dyn CertificateStoreBackend>,
     _interval: Duration,
 ) -> Never {
     info!(
@@ -75,7 +76,7 @@ fn periodic_discovery_server_registration(
 pub(crate) async fn periodic_discovery_server_registration(
     discovery_server_url: &str,
     registered_server: RegisteredServer,
-    pki_dir: PathBuf,
+    certificate_store_backend: Arc<dyn CertificateStoreBackend>,
     interval: Duration,
 ) -> Never {
     let mut interval = tokio::time::interval(interval);
@@ -84,7 +85,7 @@ pub(crate) async fn periodic_discovery_server_registration(
     let client = ClientBuilder::new()
         .application_name("DiscoveryClient")
         .application_uri("urn:DiscoveryClient")
-        .pki_dir(pki_dir)
+        .certificate_store_backend(certificate_store_backend)
         .session_retry_limit(1)
         .client();
 
@@ -3,15 +3,18 @@
 // Copyright (C) 2017-2024 Adam Lock
 
 use opcua_types::{
-    match_extension_object_owned, AnonymousIdentityToken, ExtensionObject, UAString,
-    UserNameIdentityToken, X509IdentityToken,
+    match_extension_object_owned, AnonymousIdentityToken, ExtensionObject, IssuedIdentityToken,
+    UAString, UserNameIdentityToken, X509IdentityToken,
 };
 
 pub(crate) const POLICY_ID_ANONYMOUS: &str = "anonymous";
 pub(crate) const POLICY_ID_USER_PASS_NONE: &str = "userpass_none";
 pub(crate) const POLICY_ID_USER_PASS_RSA_15: &str = "userpass_rsa_15";
 pub(crate) const POLICY_ID_USER_PASS_RSA_OAEP: &str = "userpass_rsa_oaep";
+pub(crate) const POLICY_ID_USER_PASS_ECC: &str = "userpass_ecc";
 pub(crate) const POLICY_ID_X509: &str = "x509";
+/// Prefix of the policy IDs of issued tokens, followed by the ID of the user token.
+pub(crate) const POLICY_ID_ISSUED_TOKEN: &str = "issued_token";
 
 /// Identity token representation on the server, decoded from the client.
 pub enum IdentityToken {
@@ -19,6 +22,7 @@ pub enum IdentityToken {
     Anonymous(AnonymousIdentityToken),
     UserName(UserNameIdentityToken),
     X509(X509IdentityToken),
+    IssuedToken(IssuedIdentityToken),
     Invalid(ExtensionObject),
 }
 
@@ -36,6 +40,7 @@ impl IdentityToken {
                 v: AnonymousIdentityToken => Self::Anonymous(v),
                 v: UserNameIdentityToken => Self::UserName(v),
                 v: X509IdentityToken => Self::X509(v),
+                v: IssuedIdentityToken => Self::IssuedToken(v),
                 _ => Self::Invalid(o)
             )
         }
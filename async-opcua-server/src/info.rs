@@ -7,21 +7,26 @@
 use std::sync::atomic::{AtomicU16, AtomicU8, Ordering};
 use std::sync::Arc;
 
-use arc_swap::ArcSwap;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use log::{debug, error, warn};
 use opcua_nodes::DefaultTypeTree;
 
 use crate::authenticator::{user_pass_security_policy_id, Password};
+use crate::certificate_management::CertificateManager;
+use crate::conditions::ConditionManager;
+use crate::local_discovery::LocalDiscoveryServer;
 use crate::node_manager::TypeTreeForUser;
-use opcua_core::comms::url::{hostname_from_url, url_matches_except_host};
+use crate::roles::RoleManager;
+use opcua_core::comms::secure_channel::SecureChannel;
+use opcua_core::comms::url::{hostname_from_url, url_matches_except_host, OPC_HTTPS_SCHEME};
 use opcua_core::handle::AtomicHandle;
 use opcua_core::sync::RwLock;
-use opcua_crypto::{user_identity, PrivateKey, SecurityPolicy, X509};
+use opcua_crypto::{user_identity, EphemeralKey, KeyType, PrivateKey, SecurityPolicy, X509};
 use opcua_types::{
     profiles, status_code::StatusCode, ActivateSessionRequest, AnonymousIdentityToken,
-    ApplicationDescription, ApplicationType, EndpointDescription, RegisteredServer,
-    ServerState as ServerStateType, SignatureData, UserNameIdentityToken, UserTokenType,
-    X509IdentityToken,
+    ApplicationDescription, ApplicationType, EndpointDescription, IssuedIdentityToken,
+    RegisteredServer, ServerState as ServerStateType, SignatureData, UserNameIdentityToken,
+    UserTokenType, X509IdentityToken,
 };
 use opcua_types::{
     ByteString, ContextOwned, DateTime, DecodingOptions, Error, ExtensionObject, LocalizedText,
@@ -30,7 +35,7 @@ use opcua_types::{
 
 use crate::config::{ServerConfig, ServerEndpoint};
 
-use super::authenticator::{AuthManager, UserToken};
+use super::authenticator::{AuthManager, AuthenticatedUser, UserToken};
 use super::identity_token::{IdentityToken, POLICY_ID_ANONYMOUS, POLICY_ID_X509};
 use super::{OperationalLimits, ServerCapabilities, ANONYMOUS_USER_TOKEN_ID};
 
@@ -49,10 +54,14 @@ pub struct ServerInfo {
     pub servers: Vec<String>,
     /// Server configuration
     pub config: Arc<ServerConfig>,
-    /// Server public certificate read from config location or null if there is none
-    pub server_certificate: Option<X509>,
+    /// Server public certificate read from config location or null if there is none.
+    /// This may be replaced at runtime through the certificate management methods.
+    pub server_certificate: ArcSwapOption<X509>,
     /// Server private key
-    pub server_pkey: Option<PrivateKey>,
+    pub server_pkey: ArcSwapOption<PrivateKey>,
+    /// Server certificates and private keys for key types other than the one of
+    /// `server_certificate`, used by endpoints with security policies that need them.
+    pub other_server_certificates: ArcSwap<Vec<(Arc<X509>, Arc<PrivateKey>)>>,
     /// Operational limits
     pub(crate) operational_limits: OperationalLimits,
     /// Current state
@@ -83,8 +92,20 @@ pub struct ServerInfo {
     pub service_level: Arc<AtomicU8>,
     /// Currently active local port.
     pub port: AtomicU16,
+    /// Currently active local port for WebSocket connections, if enabled.
+    pub websocket_port: AtomicU16,
+    /// Currently active local port for HTTPS connections, if enabled.
+    pub https_port: AtomicU16,
     /// List of active type loaders
     pub type_loaders: RwLock<TypeLoaderCollection>,
+    /// State of alarms and conditions on the server.
+    pub conditions: Arc<ConditionManager>,
+    /// Handler for the certificate management methods on the `ServerConfiguration` object.
+    pub certificate_manager: Arc<CertificateManager>,
+    /// Identity mapping rules of the well-known roles, used to check role permissions.
+    pub roles: Arc<RoleManager>,
+    /// Registered servers, if the server is running as a local discovery server.
+    pub local_discovery: Option<Arc<LocalDiscoveryServer>>,
 }
 
 impl ServerInfo {
@@ -99,16 +120,19 @@ impl ServerInfo {
             "Endpoints requested, transport profile uris {:?}",
             transport_profile_uris
         );
+        let mut base_endpoints = self.base_endpoints();
         if let Some(ref transport_profile_uris) = *transport_profile_uris {
             // Note - some clients pass an empty array
             if !transport_profile_uris.is_empty() {
-                // As we only support binary transport, the result is None if the supplied profile_uris does not contain that profile
-                let found_binary_transport = transport_profile_uris.iter().any(|profile_uri| {
-                    profile_uri.as_ref() == profiles::TRANSPORT_PROFILE_URI_BINARY
+                // The result is None if the supplied profile_uris does not contain any supported profile
+                base_endpoints.retain(|(_, profile)| {
+                    transport_profile_uris
+                        .iter()
+                        .any(|profile_uri| profile_uri.as_ref() == *profile)
                 });
-                if !found_binary_transport {
+                if base_endpoints.is_empty() {
                     error!(
-                        "Client wants to connect with a non binary transport {:#?}",
+                        "Client wants to connect with an unsupported transport {:#?}",
                         transport_profile_uris
                     );
                     return None;
@@ -120,11 +144,15 @@ impl ServerInfo {
             if !hostname.eq_ignore_ascii_case(&self.config.tcp_config.host) {
                 debug!("Endpoint url \"{}\" hostname supplied by caller does not match server's hostname \"{}\"", endpoint_url, &self.config.tcp_config.host);
             }
-            let endpoints = self
-                .config
-                .endpoints
-                .values()
-                .map(|e| self.new_endpoint_description(e, true))
+            let endpoints = base_endpoints
+                .iter()
+                .flat_map(|(base, profile)| {
+                    self.config
+                        .endpoints
+                        .values()
+                        .filter(|e| Self::transport_supports_endpoint(profile, e))
+                        .map(|e| self.new_endpoint_description(e, base, profile, true))
+                })
                 .collect();
             Some(endpoints)
         } else {
@@ -133,7 +161,15 @@ impl ServerInfo {
                 endpoint_url
             );
             if let Some(e) = self.config.default_endpoint() {
-                Some(vec![self.new_endpoint_description(e, true)])
+                Some(
+                    base_endpoints
+                        .iter()
+                        .filter(|(_, profile)| Self::transport_supports_endpoint(profile, e))
+                        .map(|(base, profile)| {
+                            self.new_endpoint_description(e, base, profile, true)
+                        })
+                        .collect(),
+                )
             } else {
                 Some(vec![])
             }
@@ -148,16 +184,25 @@ impl ServerInfo {
         security_policy: SecurityPolicy,
         security_mode: MessageSecurityMode,
     ) -> bool {
-        self.config
-            .find_endpoint(
-                endpoint_url,
-                &self.base_endpoint(),
-                security_policy,
-                security_mode,
-            )
+        self.find_endpoint(endpoint_url, security_policy, security_mode)
             .is_some()
     }
 
+    /// Find the endpoint given by `endpoint_url`, `security_policy`, and `security_mode`
+    /// on any of the enabled transports.
+    fn find_endpoint(
+        &self,
+        endpoint_url: &str,
+        security_policy: SecurityPolicy,
+        security_mode: MessageSecurityMode,
+    ) -> Option<&ServerEndpoint> {
+        self.base_endpoints().iter().find_map(|(base, profile)| {
+            self.config
+                .find_endpoint(endpoint_url, base, security_policy, security_mode)
+                .filter(|e| Self::transport_supports_endpoint(profile, e))
+        })
+    }
+
     /// Make matching endpoint descriptions for the specified url.
     /// If none match then None will be passed, therefore if Some is returned it will be guaranteed
     /// to contain at least one result.
@@ -166,16 +211,20 @@ impl ServerInfo {
         endpoint_url: &str,
     ) -> Option<Vec<EndpointDescription>> {
         debug!("find_endpoint, url = {}", endpoint_url);
-        let base_endpoint_url = self.base_endpoint();
         let endpoints: Vec<EndpointDescription> = self
-            .config
-            .endpoints
+            .base_endpoints()
             .iter()
-            .filter(|&(_, e)| {
-                // Test end point's security_policy_uri and matching url
-                url_matches_except_host(&e.endpoint_url(&base_endpoint_url), endpoint_url)
+            .flat_map(|(base, profile)| {
+                self.config
+                    .endpoints
+                    .values()
+                    .filter(|e| {
+                        // Test end point's security_policy_uri and matching url
+                        url_matches_except_host(&e.endpoint_url(base), endpoint_url)
+                            && Self::transport_supports_endpoint(profile, e)
+                    })
+                    .map(|e| self.new_endpoint_description(e, base, profile, false))
             })
-            .map(|(_, e)| self.new_endpoint_description(e, false))
             .collect();
         if endpoints.is_empty() {
             None
@@ -188,10 +237,10 @@ impl ServerInfo {
     fn new_endpoint_description(
         &self,
         endpoint: &ServerEndpoint,
+        base_endpoint_url: &str,
+        transport_profile_uri: &str,
         all_fields: bool,
     ) -> EndpointDescription {
-        let base_endpoint_url = self.base_endpoint();
-
         let user_identity_tokens = self.authenticator.user_token_policies(endpoint);
 
         // CreateSession doesn't need all the endpoint description
@@ -208,7 +257,9 @@ impl ServerInfo {
                     discovery_profile_uri: UAString::null(),
                     discovery_urls: self.discovery_urls(),
                 },
-                self.server_certificate_as_byte_string(),
+                self.server_certificate_for_policy(endpoint.security_policy())
+                    .map(|cert| cert.as_byte_string())
+                    .unwrap_or_default(),
             )
         } else {
             (
@@ -226,13 +277,13 @@ impl ServerInfo {
         };
 
         EndpointDescription {
-            endpoint_url: endpoint.endpoint_url(&base_endpoint_url).into(),
+            endpoint_url: endpoint.endpoint_url(base_endpoint_url).into(),
             server,
             server_certificate,
             security_mode: endpoint.message_security_mode(),
             security_policy_uri: UAString::from(endpoint.security_policy().to_uri()),
             user_identity_tokens: Some(user_identity_tokens),
-            transport_profile_uri: UAString::from(profiles::TRANSPORT_PROFILE_URI_BINARY),
+            transport_profile_uri: UAString::from(transport_profile_uri),
             security_level: endpoint.security_level,
         }
     }
@@ -252,9 +303,14 @@ impl ServerInfo {
         }
     }
 
-    /// Get the application type, will be `Server`.
+    /// Get the application type, will be `Server`, or `DiscoveryServer` if the
+    /// server is running as a local discovery server.
     pub fn application_type(&self) -> ApplicationType {
-        ApplicationType::Server
+        if self.local_discovery.is_some() {
+            ApplicationType::DiscoveryServer
+        } else {
+            ApplicationType::Server
+        }
     }
 
     /// Get the gateway server URI.
@@ -281,9 +337,127 @@ impl ServerInfo {
         )
     }
 
+    /// Get the base endpoint of the WebSocket transport, i.e. the configured host + current
+    /// WebSocket port, with the `opc.ws` or `opc.wss` scheme. Returns `None` if the
+    /// WebSocket transport is not enabled.
+    pub fn websocket_base_endpoint(&self) -> Option<String> {
+        let websocket = self.config.websocket_config.as_ref()?;
+        Some(format!(
+            "{}://{}:{}",
+            websocket.scheme(),
+            self.config.tcp_config.host,
+            self.websocket_port.load(Ordering::Relaxed)
+        ))
+    }
+
+    /// Get the base endpoint of the HTTPS transport, i.e. the configured host + current
+    /// HTTPS port. Returns `None` if the HTTPS transport is not enabled.
+    pub fn https_base_endpoint(&self) -> Option<String> {
+        self.config.https_config.as_ref()?;
+        Some(format!(
+            "{}://{}:{}",
+            OPC_HTTPS_SCHEME,
+            self.config.tcp_config.host,
+            self.https_port.load(Ordering::Relaxed)
+        ))
+    }
+
+    /// Get the base endpoints of all enabled transports, along with
+    /// their transport profile URI.
+    fn base_endpoints(&self) -> Vec<(String, &'static str)> {
+        let mut base_endpoints =
+            vec![(self.base_endpoint(), profiles::TRANSPORT_PROFILE_URI_BINARY)];
+        if let Some(websocket) = self.websocket_base_endpoint() {
+            base_endpoints.push((websocket, profiles::TRANSPORT_PROFILE_URI_WSS_BINARY));
+        }
+        if let Some(https) = self.https_base_endpoint() {
+            base_endpoints.push((https.clone(), profiles::TRANSPORT_PROFILE_URI_HTTPS_BINARY));
+            base_endpoints.push((https, profiles::TRANSPORT_PROFILE_URI_HTTPS_JSON));
+        }
+        base_endpoints
+    }
+
+    /// Check if `endpoint` is available on the transport with the given transport profile.
+    /// HTTPS has no secure channel, messages are only protected by TLS, so it only
+    /// exposes endpoints without message security.
+    fn transport_supports_endpoint(transport_profile_uri: &str, endpoint: &ServerEndpoint) -> bool {
+        match transport_profile_uri {
+            profiles::TRANSPORT_PROFILE_URI_HTTPS_BINARY
+            | profiles::TRANSPORT_PROFILE_URI_HTTPS_JSON => {
+                endpoint.security_policy() == SecurityPolicy::None
+            }
+            _ => true,
+        }
+    }
+
+    /// Get the current server certificate, if there is one.
+    pub fn server_certificate(&self) -> Option<Arc<X509>> {
+        self.server_certificate.load_full()
+    }
+
+    /// Get the current server private key, if there is one.
+    pub fn server_pkey(&self) -> Option<Arc<PrivateKey>> {
+        self.server_pkey.load_full()
+    }
+
+    /// Get the server certificate and private key for keys of type `key_type`, if there is one.
+    pub fn server_certificate_for_key_type(
+        &self,
+        key_type: KeyType,
+    ) -> Option<(Arc<X509>, Arc<PrivateKey>)> {
+        if let (Some(cert), Some(pkey)) = (self.server_certificate(), self.server_pkey()) {
+            if pkey.key_type() == key_type {
+                return Some((cert, pkey));
+            }
+        }
+        self.other_server_certificates
+            .load()
+            .iter()
+            .find(|(_, pkey)| pkey.key_type() == key_type)
+            .cloned()
+    }
+
+    /// Get the server certificate for endpoints with the security policy `security_policy`.
+    /// Policies that do not use certificates get the default server certificate.
+    pub fn server_certificate_for_policy(
+        &self,
+        security_policy: SecurityPolicy,
+    ) -> Option<Arc<X509>> {
+        match security_policy.key_type() {
+            Some(key_type) => self
+                .server_certificate_for_key_type(key_type)
+                .map(|(cert, _)| cert),
+            None => self.server_certificate(),
+        }
+    }
+
+    /// Replace the server certificate and private key for the key type of `pkey`. These are
+    /// the certificates given in endpoint descriptions, secure channels use the certificates
+    /// in the certificate store.
+    pub(crate) fn set_server_certificate(&self, cert: X509, pkey: PrivateKey) {
+        let key_type = pkey.key_type();
+        if self
+            .server_pkey()
+            .is_none_or(|current| current.key_type() == key_type)
+        {
+            self.server_certificate.store(Some(Arc::new(cert)));
+            self.server_pkey.store(Some(Arc::new(pkey)));
+        } else {
+            let mut others: Vec<_> = self
+                .other_server_certificates
+                .load()
+                .iter()
+                .filter(|(_, p)| p.key_type() != key_type)
+                .cloned()
+                .collect();
+            others.push((Arc::new(cert), Arc::new(pkey)));
+            self.other_server_certificates.store(Arc::new(others));
+        }
+    }
+
     /// Get the server certificate as a byte string.
     pub fn server_certificate_as_byte_string(&self) -> ByteString {
-        if let Some(ref server_certificate) = self.server_certificate {
+        if let Some(server_certificate) = self.server_certificate() {
             server_certificate.as_byte_string()
         } else {
             ByteString::null()
@@ -318,22 +492,47 @@ impl ServerInfo {
     /// It is possible that the endpoint does not exist, or that the token is invalid / unsupported
     /// or that the token cannot be used with the end point. The return codes reflect the responses
     /// that ActivateSession would expect from a service call.
+    ///
+    /// Encrypted tokens are decrypted with the certificate and private key of `channel`, which
+    /// may differ from the current server certificate if it was replaced after the channel opened.
+    /// Secrets encrypted with an ECC security policy are decrypted with `ephemeral_key`, the
+    /// ephemeral key last sent to the client.
     pub async fn authenticate_endpoint(
         &self,
         request: &ActivateSessionRequest,
         endpoint_url: &str,
-        security_policy: SecurityPolicy,
-        security_mode: MessageSecurityMode,
+        channel: &SecureChannel,
         user_identity_token: ExtensionObject,
         server_nonce: &ByteString,
+        ephemeral_key: Option<&EphemeralKey>,
     ) -> Result<UserToken, Error> {
-        // Get security from endpoint url
-        if let Some(endpoint) = self.config.find_endpoint(
+        self.authenticate_endpoint_user(
+            request,
             endpoint_url,
-            &self.base_endpoint(),
-            security_policy,
-            security_mode,
-        ) {
+            channel,
+            user_identity_token,
+            server_nonce,
+            ephemeral_key,
+        )
+        .await
+        .map(|user| user.token)
+    }
+
+    /// Authenticates access to an endpoint like [`ServerInfo::authenticate_endpoint`], also
+    /// returning the roles granted by the identity token to the session being activated.
+    pub(crate) async fn authenticate_endpoint_user(
+        &self,
+        request: &ActivateSessionRequest,
+        endpoint_url: &str,
+        channel: &SecureChannel,
+        user_identity_token: ExtensionObject,
+        server_nonce: &ByteString,
+        ephemeral_key: Option<&EphemeralKey>,
+    ) -> Result<AuthenticatedUser, Error> {
+        let security_policy = channel.security_policy();
+        let security_mode = channel.security_mode();
+        // Get security from endpoint url
+        if let Some(endpoint) = self.find_endpoint(endpoint_url, security_policy, security_mode) {
             // Now validate the user identity token
             match IdentityToken::new(user_identity_token) {
                 IdentityToken::None => {
@@ -343,27 +542,41 @@ impl ServerInfo {
                         "User identity token type unsupported",
                     ))
                 }
-                IdentityToken::Anonymous(token) => {
-                    self.authenticate_anonymous_token(endpoint, &token).await
-                }
-                IdentityToken::UserName(token) => {
-                    self.authenticate_username_identity_token(
+                IdentityToken::Anonymous(token) => self
+                    .authenticate_anonymous_token(endpoint, &token)
+                    .await
+                    .map(AuthenticatedUser::from),
+                IdentityToken::UserName(token) => self
+                    .authenticate_username_identity_token(
                         endpoint,
                         &token,
-                        &self.server_pkey,
+                        channel.private_key().as_ref(),
                         server_nonce,
+                        ephemeral_key,
+                        channel.remote_cert().as_ref(),
                     )
                     .await
-                }
-                IdentityToken::X509(token) => {
-                    self.authenticate_x509_identity_token(
+                    .map(AuthenticatedUser::from),
+                IdentityToken::X509(token) => self
+                    .authenticate_x509_identity_token(
                         endpoint,
                         &token,
                         &request.user_token_signature,
-                        &self.server_certificate,
+                        channel.cert().as_ref(),
                         server_nonce,
                     )
                     .await
+                    .map(AuthenticatedUser::from),
+                IdentityToken::IssuedToken(token) => {
+                    self.authenticate_issued_identity_token(
+                        endpoint,
+                        &token,
+                        channel.private_key().as_ref(),
+                        server_nonce,
+                        ephemeral_key,
+                        channel.remote_cert().as_ref(),
+                    )
+                    .await
                 }
                 IdentityToken::Invalid(o) => Err(Error::new(
                     StatusCode::BadIdentityTokenInvalid,
@@ -414,8 +627,10 @@ impl ServerInfo {
         &self,
         endpoint: &ServerEndpoint,
         token: &UserNameIdentityToken,
-        server_key: &Option<PrivateKey>,
+        server_key: Option<&PrivateKey>,
         server_nonce: &ByteString,
+        ephemeral_key: Option<&EphemeralKey>,
+        client_certificate: Option<&X509>,
     ) -> Result<UserToken, Error> {
         if !self.authenticator.supports_user_pass(endpoint) {
             Err(Error::new(
@@ -438,12 +653,17 @@ impl ServerInfo {
                 token.policy_id.as_ref(),
                 token.encryption_algorithm.as_ref()
             );
-            let token_password = if !token.encryption_algorithm.is_null() {
-                if let Some(ref server_key) = server_key {
-                    user_identity::decrypt_user_identity_token_password(
+            let is_encrypted = !token.encryption_algorithm.is_null()
+                || user_identity::is_encrypted_secret(&token.password);
+            let token_password = if is_encrypted {
+                if let Some(server_key) = server_key {
+                    user_identity::decrypt_user_identity_token_password_with_policy(
                         token,
                         server_nonce.as_ref(),
                         server_key,
+                        endpoint.password_security_policy(),
+                        ephemeral_key,
+                        client_certificate,
                     )?
                 } else {
                     error!("Identity token password is encrypted but no server private key was supplied");
@@ -473,7 +693,7 @@ impl ServerInfo {
         endpoint: &ServerEndpoint,
         token: &X509IdentityToken,
         user_token_signature: &SignatureData,
-        server_certificate: &Option<X509>,
+        server_certificate: Option<&X509>,
         server_nonce: &ByteString,
     ) -> Result<UserToken, Error> {
         if !self.authenticator.supports_x509(endpoint) {
@@ -490,7 +710,7 @@ impl ServerInfo {
             ))
         } else {
             match server_certificate {
-                Some(ref server_certificate) => {
+                Some(server_certificate) => {
                     // Find the security policy used for verifying tokens
                     let user_identity_tokens = self.authenticator.user_token_policies(endpoint);
                     let security_policy = user_identity_tokens
@@ -533,6 +753,61 @@ impl ServerInfo {
         }
     }
 
+    /// Authenticate the issued token against the endpoint. The function returns the user token identifier
+    /// that matches the identity token, and the roles granted by the token.
+    async fn authenticate_issued_identity_token(
+        &self,
+        endpoint: &ServerEndpoint,
+        token: &IssuedIdentityToken,
+        server_key: Option<&PrivateKey>,
+        server_nonce: &ByteString,
+        ephemeral_key: Option<&EphemeralKey>,
+        client_certificate: Option<&X509>,
+    ) -> Result<AuthenticatedUser, Error> {
+        if !self.authenticator.supports_issued_token(endpoint) {
+            return Err(Error::new(
+                StatusCode::BadIdentityTokenRejected,
+                "Endpoint doesn't support issued tokens",
+            ));
+        }
+        if !self
+            .authenticator
+            .user_token_policies(endpoint)
+            .iter()
+            .any(|p| p.token_type == UserTokenType::IssuedToken && p.policy_id == token.policy_id)
+        {
+            return Err(Error::new(
+                StatusCode::BadIdentityTokenRejected,
+                "Token doesn't possess the correct policy id",
+            ));
+        }
+        let is_encrypted = !token.encryption_algorithm.is_null()
+            || user_identity::is_encrypted_secret(&token.token_data);
+        let token_data = if is_encrypted {
+            let Some(server_key) = server_key else {
+                error!("Issued token is encrypted but no server private key was supplied");
+                return Err(Error::new(
+                    StatusCode::BadIdentityTokenInvalid,
+                    "Failed to decrypt issued token",
+                ));
+            };
+            user_identity::decrypt_issued_identity_token(
+                token,
+                server_nonce.as_ref(),
+                server_key,
+                endpoint.password_security_policy(),
+                ephemeral_key,
+                client_certificate,
+            )?
+        } else {
+            token.token_data.clone()
+        };
+
+        self.authenticator
+            .authenticate_issued_identity_token(endpoint, token.policy_id.as_ref(), &token_data)
+            .await
+    }
+
     pub(crate) fn initial_encoding_context(&self) -> ContextOwned {
         // The namespace map is populated later, once the session is connected.
         ContextOwned::new(
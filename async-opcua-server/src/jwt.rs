@@ -0,0 +1,525 @@
+//! Validation of JSON Web Tokens (JWT), sent by clients as an `IssuedIdentityToken`.
+//!
+//! A JWT is issued to the user by an authorization service trusted by the server, as described
+//! in OPC UA Part 6 6.5. The server checks that the token is signed with one of the keys
+//! published by the authorization service as a JSON Web Key Set (JWKS), that it is meant
+//! for this server, and that it has not expired. The scopes granted in the token are mapped
+//! to roles.
+
+use std::collections::BTreeMap;
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use log::{debug, warn};
+use opcua_crypto::{KeyType, PublicKey, X509};
+use opcua_types::{ByteString, DateTime, Error, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The issued token type of a JSON Web Token, used in the user token policy.
+pub const JWT_ISSUED_TOKEN_TYPE: &str = "http://opcfoundation.org/UA/UserToken#JWT";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(default)]
+/// Configuration of a [`JwtValidator`], accepting tokens issued by a single authorization service.
+pub struct JwtConfig {
+    /// Paths of JSON Web Key Set files, containing the keys used by the authorization service
+    /// to sign tokens.
+    pub jwks_files: Vec<String>,
+    /// Expected issuer of tokens, the `iss` claim. Not checked if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// Expected audience of tokens, which must be one of the values of the `aud` claim.
+    /// This is typically the application URI of the server.
+    pub audience: String,
+    /// Clock skew in seconds allowed when checking the expiry of tokens.
+    pub leeway: u32,
+    /// Roles granted to the user for each scope in the `scope` or `scp` claim of the token.
+    pub scope_roles: BTreeMap<String, Vec<String>>,
+    /// Description of the authorization service, returned to clients as the issuer endpoint URL
+    /// of the user token policy. For JWTs this is a JSON object, see OPC UA Part 6 6.5.1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer_endpoint_url: Option<String>,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            jwks_files: Vec::new(),
+            issuer: None,
+            audience: String::new(),
+            leeway: 60,
+            scope_roles: BTreeMap::new(),
+            issuer_endpoint_url: None,
+        }
+    }
+}
+
+impl JwtConfig {
+    /// Test if the configuration is valid.
+    pub fn validate(&self, id: &str) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.jwks_files.is_empty() {
+            errors.push(format!("User token {id} does not specify any JWKS files."));
+        }
+        if self.audience.is_empty() {
+            errors.push(format!("User token {id} has an empty JWT audience."));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The claims of a validated JSON Web Token.
+#[derive(Debug, Clone)]
+pub struct JwtClaims {
+    /// The user the token was issued to, the `sub` claim.
+    pub subject: String,
+    /// The issuer of the token, the `iss` claim.
+    pub issuer: Option<String>,
+    /// The time the token expires, the `exp` claim.
+    pub expires: DateTime,
+    /// The scopes granted to the user, from the `scope` or `scp` claim.
+    pub scopes: Vec<String>,
+    /// The roles of the user, mapped from the scopes with [`JwtConfig::scope_roles`].
+    pub roles: Vec<String>,
+    /// All the claims of the token.
+    pub claims: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct JsonWebKeySet {
+    keys: Vec<JsonWebKey>,
+}
+
+#[derive(Deserialize)]
+struct JsonWebKey {
+    kty: String,
+    kid: Option<String>,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+    x5c: Option<Vec<String>>,
+}
+
+impl JsonWebKey {
+    fn public_key(&self) -> Option<PublicKey> {
+        // A certificate takes precedence over the key parameters.
+        if let Some(cert) = self.x5c.as_ref().and_then(|c| c.first()) {
+            let der = STANDARD.decode(cert).ok()?;
+            return X509::from_der(&der).ok()?.public_key().ok();
+        }
+        let param = |p: &Option<String>| p.as_ref().and_then(|p| URL_SAFE_NO_PAD.decode(p).ok());
+        match self.kty.as_str() {
+            "RSA" => PublicKey::from_rsa_components(&param(&self.n)?, &param(&self.e)?).ok(),
+            "EC" => {
+                let key_type = match self.crv.as_deref() {
+                    Some("P-256") => KeyType::NistP256,
+                    Some("P-384") => KeyType::NistP384,
+                    _ => return None,
+                };
+                PublicKey::from_ec_coordinates(key_type, &param(&self.x)?, &param(&self.y)?).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+struct SigningKey {
+    kid: Option<String>,
+    key: PublicKey,
+}
+
+/// Validates JSON Web Tokens issued by a single authorization service, see [`JwtConfig`].
+pub struct JwtValidator {
+    config: JwtConfig,
+    keys: Vec<SigningKey>,
+}
+
+fn rejected(reason: impl Into<String>) -> Error {
+    Error::new(StatusCode::BadIdentityTokenRejected, reason.into())
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::new(StatusCode::BadIdentityTokenInvalid, reason.into())
+}
+
+impl JwtValidator {
+    /// Create a new validator, reading the signing keys from the JWKS files in `config`.
+    pub fn new(config: JwtConfig) -> Result<Self, Error> {
+        let mut jwks = Vec::with_capacity(config.jwks_files.len());
+        for path in &config.jwks_files {
+            let data = std::fs::read_to_string(path).map_err(|e| {
+                Error::new(
+                    StatusCode::BadConfigurationError,
+                    format!("Failed to read JWKS file {path}: {e}"),
+                )
+            })?;
+            jwks.push(data);
+        }
+        Self::with_jwks(config, &jwks)
+    }
+
+    /// Create a new validator with the signing keys from the given JSON Web Key Sets,
+    /// ignoring the JWKS files in `config`.
+    pub fn with_jwks(config: JwtConfig, jwks: &[impl AsRef<str>]) -> Result<Self, Error> {
+        let mut keys = Vec::new();
+        for set in jwks {
+            let set: JsonWebKeySet = serde_json::from_str(set.as_ref()).map_err(|e| {
+                Error::new(
+                    StatusCode::BadConfigurationError,
+                    format!("Invalid JSON Web Key Set: {e}"),
+                )
+            })?;
+            for jwk in set.keys {
+                if jwk.key_use.as_deref().is_some_and(|u| u != "sig") {
+                    continue;
+                }
+                match jwk.public_key() {
+                    Some(key) => keys.push(SigningKey { kid: jwk.kid, key }),
+                    None => warn!(
+                        "Ignoring unsupported JSON web key {}",
+                        jwk.kid.as_deref().unwrap_or(&jwk.kty)
+                    ),
+                }
+            }
+        }
+        if keys.is_empty() {
+            return Err(Error::new(
+                StatusCode::BadConfigurationError,
+                "No supported signing keys in JSON Web Key Sets",
+            ));
+        }
+        Ok(Self { config, keys })
+    }
+
+    /// Get the configuration of the validator.
+    pub fn config(&self) -> &JwtConfig {
+        &self.config
+    }
+
+    /// Validate a token, checking its signature, issuer, audience and expiry.
+    pub fn validate(&self, token: &ByteString) -> Result<JwtClaims, Error> {
+        let token = std::str::from_utf8(token.as_ref())
+            .map_err(|_| invalid("Issued token is not a JSON Web Token"))?;
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("Issued token is not a JSON Web Token"));
+        };
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| invalid("Invalid encoding of JSON Web Token"))
+        };
+        let parse = |part: &str| {
+            serde_json::from_slice::<Map<String, Value>>(&decode(part)?)
+                .map_err(|_| invalid("Invalid JSON in JSON Web Token"))
+        };
+
+        let header = parse(header)?;
+        let alg = header.get("alg").and_then(|a| a.as_str()).unwrap_or("");
+        let kid = header.get("kid").and_then(|k| k.as_str());
+        let signed = &token[..token.len() - signature.len() - 1];
+        self.verify_signature(alg, kid, signed.as_bytes(), &decode(signature)?)?;
+
+        let claims = parse(payload)?;
+        self.validate_claims(claims)
+    }
+
+    fn verify_signature(
+        &self,
+        alg: &str,
+        kid: Option<&str>,
+        data: &[u8],
+        signature: &[u8],
+    ) -> Result<(), Error> {
+        let key_type = match alg {
+            "RS256" | "PS256" => KeyType::Rsa,
+            "ES256" => KeyType::NistP256,
+            "ES384" => KeyType::NistP384,
+            alg => {
+                return Err(rejected(format!(
+                    "Unsupported JSON Web Token algorithm \"{alg}\""
+                )))
+            }
+        };
+        let verified = self
+            .keys
+            .iter()
+            .filter(|k| kid.is_none() || k.kid.as_deref() == kid)
+            .filter(|k| k.key.key_type() == key_type)
+            .any(|k| {
+                match alg {
+                    "RS256" => k.key.verify_sha256(data, signature),
+                    "PS256" => k.key.verify_sha256_pss(data, signature),
+                    _ => k.key.verify_ecdsa(data, signature),
+                }
+                .unwrap_or(false)
+            });
+        if verified {
+            Ok(())
+        } else {
+            Err(rejected("JSON Web Token signature is invalid"))
+        }
+    }
+
+    fn validate_claims(&self, claims: Map<String, Value>) -> Result<JwtClaims, Error> {
+        let now = chrono::Utc::now().timestamp();
+        let leeway = self.config.leeway as i64;
+        let time = |name: &str| claims.get(name).and_then(|t| t.as_f64()).map(|t| t as i64);
+
+        let Some(expires) = time("exp") else {
+            return Err(invalid("JSON Web Token has no expiry"));
+        };
+        if expires.saturating_add(leeway) < now {
+            return Err(rejected("JSON Web Token has expired"));
+        }
+        if time("nbf").is_some_and(|nbf| nbf.saturating_sub(leeway) > now) {
+            return Err(rejected("JSON Web Token is not valid yet"));
+        }
+
+        let issuer = claims
+            .get("iss")
+            .and_then(|i| i.as_str())
+            .map(str::to_owned);
+        if let Some(expected) = &self.config.issuer {
+            if issuer.as_ref() != Some(expected) {
+                return Err(rejected("JSON Web Token has the wrong issuer"));
+            }
+        }
+
+        let audience_matches = match claims.get("aud") {
+            Some(Value::String(aud)) => *aud == self.config.audience,
+            Some(Value::Array(aud)) => aud
+                .iter()
+                .any(|a| a.as_str() == Some(&self.config.audience)),
+            _ => false,
+        };
+        if !audience_matches {
+            return Err(rejected("JSON Web Token is not meant for this server"));
+        }
+
+        let Some(subject) = claims.get("sub").and_then(|s| s.as_str()) else {
+            return Err(invalid("JSON Web Token has no subject"));
+        };
+
+        // Scopes are a space separated string in `scope`, some services use `scp` instead.
+        let scopes: Vec<String> = match claims.get("scope").or_else(|| claims.get("scp")) {
+            Some(Value::String(scopes)) => scopes.split_whitespace().map(str::to_owned).collect(),
+            Some(Value::Array(scopes)) => scopes
+                .iter()
+                .filter_map(|s| s.as_str())
+                .map(str::to_owned)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut roles = Vec::new();
+        for role in scopes
+            .iter()
+            .filter_map(|s| self.config.scope_roles.get(s))
+            .flatten()
+        {
+            if !roles.contains(role) {
+                roles.push(role.clone());
+            }
+        }
+        debug!("Validated JSON Web Token for {subject} with roles {roles:?}");
+
+        Ok(JwtClaims {
+            subject: subject.to_owned(),
+            issuer,
+            expires: chrono::DateTime::from_timestamp(expires, 0)
+                .map(DateTime::from)
+                .unwrap_or_else(DateTime::endtimes),
+            scopes,
+            roles,
+            claims,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use base64::{
+        engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+        Engine,
+    };
+    use opcua_crypto::{KeySize, KeyType, PrivateKey, X509Data, X509};
+    use opcua_types::{ByteString, StatusCode};
+    use serde_json::json;
+
+    use super::{JwtConfig, JwtValidator};
+
+    const AUDIENCE: &str = "urn:server";
+
+    fn key_pair(key_type: KeyType) -> (X509, PrivateKey) {
+        let mut data = X509Data::sample_cert();
+        data.key_type = key_type;
+        X509::cert_and_pkey(&data).unwrap()
+    }
+
+    fn jwks(kid: &str, cert: &X509) -> String {
+        let kty = match cert.public_key().unwrap().key_type() {
+            KeyType::Rsa => "RSA",
+            _ => "EC",
+        };
+        json!({
+            "keys": [{
+                "kty": kty,
+                "kid": kid,
+                "use": "sig",
+                "x5c": [STANDARD.encode(cert.as_byte_string().as_ref())],
+            }]
+        })
+        .to_string()
+    }
+
+    fn sign(alg: &str, kid: &str, key: &PrivateKey, claims: serde_json::Value) -> ByteString {
+        let header = json!({ "alg": alg, "kid": kid, "typ": "JWT" });
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let mut signature = vec![0u8; key.signature_size()];
+        let len = match alg {
+            "RS256" => key.sign_sha256(signed.as_bytes(), &mut signature),
+            _ => key.sign_ecdsa(signed.as_bytes(), &mut signature),
+        }
+        .unwrap();
+        format!("{signed}.{}", URL_SAFE_NO_PAD.encode(&signature[..len]))
+            .as_bytes()
+            .into()
+    }
+
+    fn claims(aud: &str, expires_in: i64) -> serde_json::Value {
+        let now = chrono::Utc::now().timestamp();
+        json!({
+            "iss": "urn:issuer",
+            "sub": "user1",
+            "aud": [aud, "urn:other"],
+            "exp": now + expires_in,
+            "scope": "opcua.read opcua.admin",
+        })
+    }
+
+    fn validator(cert: &X509) -> JwtValidator {
+        let config = JwtConfig {
+            issuer: Some("urn:issuer".to_owned()),
+            audience: AUDIENCE.to_owned(),
+            leeway: 0,
+            scope_roles: BTreeMap::from([
+                ("opcua.read".to_owned(), vec!["Observer".to_owned()]),
+                (
+                    "opcua.admin".to_owned(),
+                    vec!["Observer".to_owned(), "SecurityAdmin".to_owned()],
+                ),
+            ]),
+            ..Default::default()
+        };
+        JwtValidator::with_jwks(config, &[jwks("key1", cert)]).unwrap()
+    }
+
+    #[test]
+    fn validate_rsa_token() {
+        let (cert, key) = key_pair(KeyType::Rsa);
+        let validator = validator(&cert);
+
+        let claims = validator
+            .validate(&sign("RS256", "key1", &key, claims(AUDIENCE, 300)))
+            .unwrap();
+        assert_eq!(claims.subject, "user1");
+        assert_eq!(claims.issuer.as_deref(), Some("urn:issuer"));
+        assert_eq!(claims.scopes, vec!["opcua.read", "opcua.admin"]);
+        assert_eq!(claims.roles, vec!["Observer", "SecurityAdmin"]);
+    }
+
+    #[test]
+    fn validate_ecc_token() {
+        let (cert, key) = key_pair(KeyType::NistP256);
+        let validator = validator(&cert);
+
+        let validated = validator
+            .validate(&sign("ES256", "key1", &key, claims(AUDIENCE, 300)))
+            .unwrap();
+        assert_eq!(validated.subject, "user1");
+        // The key does not match the algorithm.
+        let err = validator
+            .validate(&sign("ES384", "key1", &key, claims(AUDIENCE, 300)))
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BadIdentityTokenRejected);
+    }
+
+    #[test]
+    fn validate_extreme_timestamps() {
+        let (cert, key) = key_pair(KeyType::Rsa);
+        let mut validator = validator(&cert);
+        validator.config.leeway = 60;
+
+        // Timestamps at the limits of the range do not overflow when the leeway is applied.
+        let mut c = claims(AUDIENCE, 300);
+        c["exp"] = json!(i64::MAX);
+        c["nbf"] = json!(i64::MIN);
+        let claims = validator.validate(&sign("RS256", "key1", &key, c)).unwrap();
+        assert_eq!(claims.subject, "user1");
+    }
+
+    #[test]
+    fn reject_invalid_tokens() {
+        let (cert, key) = key_pair(KeyType::Rsa);
+        let (_, other_key) = key_pair(KeyType::Rsa);
+        let validator = validator(&cert);
+
+        let rejected = |token: ByteString| validator.validate(&token).unwrap_err().status();
+        // Expired
+        assert_eq!(
+            rejected(sign("RS256", "key1", &key, claims(AUDIENCE, -10))),
+            StatusCode::BadIdentityTokenRejected
+        );
+        // Wrong audience
+        assert_eq!(
+            rejected(sign("RS256", "key1", &key, claims("urn:other2", 300))),
+            StatusCode::BadIdentityTokenRejected
+        );
+        // Signed with another key
+        assert_eq!(
+            rejected(sign("RS256", "key1", &other_key, claims(AUDIENCE, 300))),
+            StatusCode::BadIdentityTokenRejected
+        );
+        // Unknown key ID
+        assert_eq!(
+            rejected(sign("RS256", "key2", &key, claims(AUDIENCE, 300))),
+            StatusCode::BadIdentityTokenRejected
+        );
+        // Unsigned
+        let token = sign("RS256", "key1", &key, claims(AUDIENCE, 300));
+        let token = std::str::from_utf8(token.as_ref()).unwrap();
+        let unsigned = format!(
+            "{}.{}.",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+            token.split('.').nth(1).unwrap()
+        );
+        assert_eq!(
+            rejected(unsigned.as_bytes().into()),
+            StatusCode::BadIdentityTokenRejected
+        );
+        // Garbage
+        assert_eq!(
+            rejected(b"not a token".into()),
+            StatusCode::BadIdentityTokenInvalid
+        );
+    }
+}
@@ -13,12 +13,17 @@
 pub mod address_space;
 pub mod authenticator;
 mod builder;
+pub mod certificate_management;
+pub mod conditions;
 mod config;
 #[cfg(feature = "discovery-server-registration")]
 mod discovery;
 mod identity_token;
 mod info;
+pub mod jwt;
+pub mod local_discovery;
 pub mod node_manager;
+pub mod roles;
 mod server;
 mod server_handle;
 mod server_status;
@@ -50,6 +55,13 @@ pub mod constants {
     pub const DEFAULT_MAX_MONITORED_ITEMS_PER_SUB: usize = 1000;
     /// Default, well known address for TCP discovery server
     pub const DEFAULT_DISCOVERY_SERVER_URL: &str = "opc.tcp://localhost:4840/UADiscovery";
+    /// Default time in milliseconds before a registration with a local discovery server expires
+    pub const DEFAULT_REGISTRATION_TIMEOUT_MS: u64 = 15 * 60 * 1000;
+    /// Default time in milliseconds between reverse connect attempts to a client
+    pub const DEFAULT_REVERSE_CONNECT_RETRY_INTERVAL_MS: u64 = 5000;
+    /// Default time in milliseconds to wait for the next request on an HTTPS connection
+    /// before closing it
+    pub const DEFAULT_HTTPS_IDLE_TIMEOUT_MS: u64 = 60_000;
 
     // Internally controlled values
 
@@ -0,0 +1,431 @@
+//! Local discovery server (LDS) mode.
+//!
+//! When enabled with [`LocalDiscoveryConfig::enabled`](crate::LocalDiscoveryConfig::enabled),
+//! the server accepts registrations from other servers through `RegisterServer` and
+//! `RegisterServer2`, and returns them from `FindServers` and `FindServersOnNetwork`.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
+use opcua_types::{
+    ApplicationDescription, ApplicationType, DateTime, ExtensionObject, LocalizedText,
+    MdnsDiscoveryConfiguration, RegisteredServer, ServerOnNetwork, StatusCode, UAString,
+};
+
+use crate::config::LocalDiscoveryConfig;
+
+struct Registration {
+    server: RegisteredServer,
+    mdns_server_name: UAString,
+    server_capabilities: Vec<UAString>,
+    /// Record ID for each discovery URL, used in `FindServersOnNetwork`.
+    records: Vec<(u32, UAString)>,
+    last_registered: Instant,
+}
+
+impl Registration {
+    fn server_name(&self) -> UAString {
+        if !self.mdns_server_name.is_empty() {
+            return self.mdns_server_name.clone();
+        }
+        self.server
+            .server_names
+            .as_ref()
+            .and_then(|n| n.first())
+            .map(|n| n.text.clone())
+            .unwrap_or_default()
+    }
+
+    fn application_description(&self, locale_ids: &[UAString]) -> ApplicationDescription {
+        let names = self.server.server_names.as_deref().unwrap_or_default();
+        let application_name = locale_ids
+            .iter()
+            .find_map(|l| names.iter().find(|n| &n.locale == l))
+            .or_else(|| names.first())
+            .cloned()
+            .unwrap_or_else(LocalizedText::null);
+
+        ApplicationDescription {
+            application_uri: self.server.server_uri.clone(),
+            product_uri: self.server.product_uri.clone(),
+            application_name,
+            application_type: self.server.server_type,
+            gateway_server_uri: self.server.gateway_server_uri.clone(),
+            discovery_profile_uri: UAString::null(),
+            discovery_urls: self.server.discovery_urls.clone(),
+        }
+    }
+
+    fn has_capabilities(&self, filter: &[UAString]) -> bool {
+        filter.iter().all(|f| {
+            self.server_capabilities
+                .iter()
+                .any(|c| c.as_ref().eq_ignore_ascii_case(f.as_ref()))
+        })
+    }
+}
+
+struct LocalDiscoveryState {
+    servers: BTreeMap<String, Registration>,
+    next_record_id: u32,
+}
+
+/// Store of servers registered with this server, when it is running as a
+/// local discovery server.
+///
+/// Registrations are removed when the server registers as offline, when the
+/// registration expires, or when the semaphore file given by the server is deleted.
+pub struct LocalDiscoveryServer {
+    state: RwLock<LocalDiscoveryState>,
+    registration_timeout: Option<Duration>,
+    check_semaphore_files: bool,
+    last_counter_reset_time: DateTime,
+}
+
+impl LocalDiscoveryServer {
+    pub(crate) fn new(config: &LocalDiscoveryConfig) -> Self {
+        Self {
+            state: RwLock::new(LocalDiscoveryState {
+                servers: BTreeMap::new(),
+                next_record_id: 1,
+            }),
+            registration_timeout: (config.registration_timeout_ms > 0)
+                .then(|| Duration::from_millis(config.registration_timeout_ms)),
+            check_semaphore_files: config.check_semaphore_files,
+            last_counter_reset_time: DateTime::now(),
+        }
+    }
+
+    /// Register a server, or update an existing registration.
+    ///
+    /// `discovery_configuration` is the list of discovery configurations given to
+    /// `RegisterServer2`. The returned list contains a status code for each of these.
+    /// Only `MdnsDiscoveryConfiguration` is supported.
+    ///
+    /// If the server is not online, its registration is removed.
+    pub fn register(
+        &self,
+        server: RegisteredServer,
+        discovery_configuration: Option<&[ExtensionObject]>,
+    ) -> Result<Vec<StatusCode>, StatusCode> {
+        if server.server_uri.is_empty() {
+            return Err(StatusCode::BadServerUriInvalid);
+        }
+        if server.server_names.as_ref().is_none_or(|n| n.is_empty()) {
+            return Err(StatusCode::BadServerNameMissing);
+        }
+        if server.discovery_urls.as_ref().is_none_or(|n| n.is_empty()) {
+            return Err(StatusCode::BadDiscoveryUrlMissing);
+        }
+        if server.server_type == ApplicationType::Client {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        if self.check_semaphore_files
+            && !server.semaphore_file_path.is_empty()
+            && !Path::new(server.semaphore_file_path.as_ref()).exists()
+        {
+            return Err(StatusCode::BadSempahoreFileMissing);
+        }
+
+        let mut mdns_server_name = UAString::null();
+        let mut server_capabilities = Vec::new();
+        let configuration_results = discovery_configuration
+            .unwrap_or_default()
+            .iter()
+            .map(|c| {
+                if let Some(mdns) = c.inner_as::<MdnsDiscoveryConfiguration>() {
+                    mdns_server_name = mdns.mdns_server_name.clone();
+                    server_capabilities = mdns.server_capabilities.clone().unwrap_or_default();
+                    StatusCode::Good
+                } else {
+                    StatusCode::BadNotSupported
+                }
+            })
+            .collect();
+
+        let mut state = trace_write_lock!(self.state);
+        if !server.is_online {
+            debug!("Removing registration for server {}", server.server_uri);
+            state.servers.remove(server.server_uri.as_ref());
+            return Ok(configuration_results);
+        }
+
+        debug!("Registering server {}", server.server_uri);
+        // Keep the record IDs of discovery URLs that were already registered.
+        let old_records = state
+            .servers
+            .remove(server.server_uri.as_ref())
+            .map(|r| r.records)
+            .unwrap_or_default();
+        let mut records = Vec::new();
+        for url in server.discovery_urls.iter().flatten() {
+            let record_id = match old_records.iter().find(|(_, u)| u == url) {
+                Some((id, _)) => *id,
+                None => {
+                    let id = state.next_record_id;
+                    state.next_record_id += 1;
+                    id
+                }
+            };
+            records.push((record_id, url.clone()));
+        }
+
+        state.servers.insert(
+            server.server_uri.as_ref().to_owned(),
+            Registration {
+                server,
+                mdns_server_name,
+                server_capabilities,
+                records,
+                last_registered: Instant::now(),
+            },
+        );
+
+        Ok(configuration_results)
+    }
+
+    /// Remove registrations that have expired, or whose semaphore file has been deleted.
+    fn remove_stale(&self) {
+        let now = Instant::now();
+        let is_stale = |r: &Registration| {
+            self.registration_timeout
+                .is_some_and(|t| now.duration_since(r.last_registered) > t)
+                || (self.check_semaphore_files
+                    && !r.server.semaphore_file_path.is_empty()
+                    && !Path::new(r.server.semaphore_file_path.as_ref()).exists())
+        };
+
+        if !trace_read_lock!(self.state).servers.values().any(is_stale) {
+            return;
+        }
+        let mut state = trace_write_lock!(self.state);
+        state.servers.retain(|uri, r| {
+            if is_stale(r) {
+                debug!("Registration for server {uri} is stale, removing it");
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Get the list of currently registered servers.
+    pub fn registered_servers(&self) -> Vec<RegisteredServer> {
+        self.remove_stale();
+        trace_read_lock!(self.state)
+            .servers
+            .values()
+            .map(|r| r.server.clone())
+            .collect()
+    }
+
+    /// Get application descriptions for each registered server, for `FindServers`.
+    /// Server names are localized using the first matching locale in `locale_ids`.
+    pub fn application_descriptions(&self, locale_ids: &[UAString]) -> Vec<ApplicationDescription> {
+        self.remove_stale();
+        trace_read_lock!(self.state)
+            .servers
+            .values()
+            .map(|r| r.application_description(locale_ids))
+            .collect()
+    }
+
+    /// Get the registered servers for `FindServersOnNetwork`, with one record per discovery URL.
+    ///
+    /// Only records with an ID greater than `starting_record_id`, and with all the
+    /// capabilities in `server_capability_filter` are returned, ordered by record ID.
+    /// If `max_records_to_return` is zero, all matching records are returned.
+    pub fn servers_on_network(
+        &self,
+        starting_record_id: u32,
+        max_records_to_return: u32,
+        server_capability_filter: &[UAString],
+    ) -> Vec<ServerOnNetwork> {
+        self.remove_stale();
+        let state = trace_read_lock!(self.state);
+        let mut servers: Vec<_> = state
+            .servers
+            .values()
+            .filter(|r| r.has_capabilities(server_capability_filter))
+            .flat_map(|r| {
+                r.records
+                    .iter()
+                    .filter(|(id, _)| *id > starting_record_id)
+                    .map(|(id, url)| ServerOnNetwork {
+                        record_id: *id,
+                        server_name: r.server_name(),
+                        discovery_url: url.clone(),
+                        server_capabilities: Some(r.server_capabilities.clone()),
+                    })
+            })
+            .collect();
+        servers.sort_by_key(|s| s.record_id);
+        if max_records_to_return > 0 {
+            servers.truncate(max_records_to_return as usize);
+        }
+        servers
+    }
+
+    /// Time the record IDs returned from `FindServersOnNetwork` were last reset,
+    /// which is when the server started.
+    pub fn last_counter_reset_time(&self) -> DateTime {
+        self.last_counter_reset_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opcua_types::{
+        ApplicationType, ExtensionObject, LocalizedText, MdnsDiscoveryConfiguration,
+        RegisteredServer, StatusCode, UAString,
+    };
+
+    use crate::config::LocalDiscoveryConfig;
+
+    use super::LocalDiscoveryServer;
+
+    fn server(uri: &str, urls: &[&str]) -> RegisteredServer {
+        RegisteredServer {
+            server_uri: uri.into(),
+            product_uri: "urn:product".into(),
+            server_names: Some(vec![
+                LocalizedText::new("en", uri),
+                LocalizedText::new("de", &format!("{uri} (de)")),
+            ]),
+            server_type: ApplicationType::Server,
+            gateway_server_uri: UAString::null(),
+            discovery_urls: Some(urls.iter().map(|u| (*u).into()).collect()),
+            semaphore_file_path: UAString::null(),
+            is_online: true,
+        }
+    }
+
+    fn mdns(name: &str, capabilities: &[&str]) -> ExtensionObject {
+        ExtensionObject::from_message(MdnsDiscoveryConfiguration {
+            mdns_server_name: name.into(),
+            server_capabilities: Some(capabilities.iter().map(|c| (*c).into()).collect()),
+        })
+    }
+
+    #[test]
+    fn register_and_remove() {
+        let lds = LocalDiscoveryServer::new(&LocalDiscoveryConfig::default());
+
+        let mut invalid = server("urn:s1", &["opc.tcp://s1"]);
+        invalid.discovery_urls = None;
+        assert_eq!(
+            lds.register(invalid, None).unwrap_err(),
+            StatusCode::BadDiscoveryUrlMissing
+        );
+        let mut invalid = server("urn:s1", &["opc.tcp://s1"]);
+        invalid.server_names = Some(Vec::new());
+        assert_eq!(
+            lds.register(invalid, None).unwrap_err(),
+            StatusCode::BadServerNameMissing
+        );
+        let mut invalid = server("urn:s1", &["opc.tcp://s1"]);
+        invalid.semaphore_file_path = "/does/not/exist".into();
+        assert_eq!(
+            lds.register(invalid, None).unwrap_err(),
+            StatusCode::BadSempahoreFileMissing
+        );
+
+        lds.register(server("urn:s1", &["opc.tcp://s1"]), None)
+            .unwrap();
+        lds.register(server("urn:s2", &["opc.tcp://s2"]), None)
+            .unwrap();
+        assert_eq!(lds.registered_servers().len(), 2);
+
+        let descs = lds.application_descriptions(&["de".into()]);
+        assert_eq!(descs[0].application_uri.as_ref(), "urn:s1");
+        assert_eq!(descs[0].application_name.text.as_ref(), "urn:s1 (de)");
+
+        let mut offline = server("urn:s1", &["opc.tcp://s1"]);
+        offline.is_online = false;
+        lds.register(offline, None).unwrap();
+        let servers = lds.registered_servers();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].server_uri.as_ref(), "urn:s2");
+    }
+
+    #[test]
+    fn registration_expiry() {
+        let lds = LocalDiscoveryServer::new(&LocalDiscoveryConfig {
+            enabled: true,
+            registration_timeout_ms: 50,
+            check_semaphore_files: true,
+        });
+        lds.register(server("urn:s1", &["opc.tcp://s1"]), None)
+            .unwrap();
+        assert_eq!(lds.registered_servers().len(), 1);
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(lds.registered_servers().is_empty());
+    }
+
+    #[test]
+    fn semaphore_file() {
+        let path = std::env::temp_dir().join(format!("opcua-lds-semaphore-{}", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+
+        let lds = LocalDiscoveryServer::new(&LocalDiscoveryConfig::default());
+        let mut s = server("urn:s1", &["opc.tcp://s1"]);
+        s.semaphore_file_path = path.to_string_lossy().as_ref().into();
+        lds.register(s, None).unwrap();
+        assert_eq!(lds.registered_servers().len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(lds.registered_servers().is_empty());
+    }
+
+    #[test]
+    fn servers_on_network() {
+        let lds = LocalDiscoveryServer::new(&LocalDiscoveryConfig::default());
+        let results = lds
+            .register(
+                server("urn:s1", &["opc.tcp://s1a", "opc.tcp://s1b"]),
+                Some(&[mdns("s1", &["DA", "HD"]), ExtensionObject::null()]),
+            )
+            .unwrap();
+        assert_eq!(results, vec![StatusCode::Good, StatusCode::BadNotSupported]);
+        lds.register(
+            server("urn:s2", &["opc.tcp://s2"]),
+            Some(&[mdns("s2", &["DA"])]),
+        )
+        .unwrap();
+
+        let all = lds.servers_on_network(0, 0, &[]);
+        assert_eq!(
+            all.iter().map(|s| s.record_id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(all[0].server_name.as_ref(), "s1");
+        assert_eq!(all[2].discovery_url.as_ref(), "opc.tcp://s2");
+
+        let filtered = lds.servers_on_network(0, 0, &["hd".into()]);
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered.iter().all(|s| s.server_name.as_ref() == "s1"));
+
+        let paged = lds.servers_on_network(1, 1, &[]);
+        assert_eq!(paged.len(), 1);
+        assert_eq!(paged[0].record_id, 2);
+
+        // Registering again keeps existing record IDs, and assigns new ones to new URLs.
+        lds.register(
+            server("urn:s1", &["opc.tcp://s1b", "opc.tcp://s1c"]),
+            Some(&[mdns("s1", &["DA", "HD"])]),
+        )
+        .unwrap();
+        let all = lds.servers_on_network(0, 0, &[]);
+        assert_eq!(
+            all.iter().map(|s| s.record_id).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+    }
+}
@@ -16,6 +16,7 @@ pub struct HistoryNode {
     data_encoding: QualifiedName,
     input_continuation_point: Option<ContinuationPoint>,
     next_continuation_point: Option<ContinuationPoint>,
+    aggregate_type: Option<NodeId>,
     result: Option<ExtensionObject>,
     status: StatusCode,
 }
@@ -116,11 +117,16 @@ impl HistoryNode {
             data_encoding: node.data_encoding,
             input_continuation_point: cp,
             next_continuation_point: None,
+            aggregate_type: None,
             result: None,
             status,
         }
     }
 
+    pub(crate) fn set_aggregate_type(&mut self, aggregate_type: NodeId) {
+        self.aggregate_type = Some(aggregate_type);
+    }
+
     /// Get the node ID to read history from.
     pub fn node_id(&self) -> &NodeId {
         &self.node_id
@@ -136,6 +142,11 @@ impl HistoryNode {
         &self.data_encoding
     }
 
+    /// Get the aggregate to compute for this node, when reading processed history.
+    pub fn aggregate_type(&self) -> Option<&NodeId> {
+        self.aggregate_type.as_ref()
+    }
+
     /// Get the current continuation point.
     pub fn continuation_point(&self) -> Option<&ContinuationPoint> {
         self.input_continuation_point.as_ref()
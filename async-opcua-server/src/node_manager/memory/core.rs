@@ -12,6 +12,7 @@ use crate::{
         MethodCall, MonitoredItemRef, MonitoredItemUpdateRef, NodeManagersRef, ParsedReadValueId,
         RequestContext, ServerContext, SyncSampler,
     },
+    roles::WellKnownRole,
     subscriptions::CreateMonitoredItem,
     ServerCapabilities, ServerStatusWrapper,
 };
@@ -19,7 +20,7 @@ use opcua_core::{sync::RwLock, trace_lock};
 use opcua_types::{
     DataValue, DateTime, ExtensionObject, IdType, Identifier, MethodId, MonitoringMode,
     NumericRange, ObjectId, ReferenceTypeId, StatusCode, TimeZoneDataType, TimestampsToReturn,
-    VariableId, Variant, VariantScalarTypeId, VariantTypeId,
+    UAString, VariableId, Variant, VariantScalarTypeId, VariantTypeId,
 };
 
 use super::{
@@ -80,6 +81,43 @@ impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
         // Some core methods should be generally executable
         Self::set_method_executable(address_space, MethodId::Server_GetMonitoredItems);
         Self::set_method_executable(address_space, MethodId::Server_ResendData);
+        // Condition methods are handled by the server's `ConditionManager`.
+        for method in [
+            MethodId::ConditionType_ConditionRefresh,
+            MethodId::ConditionType_ConditionRefresh2,
+            MethodId::ConditionType_Enable,
+            MethodId::ConditionType_Disable,
+            MethodId::ConditionType_AddComment,
+            MethodId::AcknowledgeableConditionType_Acknowledge,
+            MethodId::AcknowledgeableConditionType_Confirm,
+        ] {
+            Self::set_method_executable(address_space, method);
+        }
+        // Certificate management methods are handled by the server's `CertificateManager`,
+        // which checks that the user is a security admin.
+        for method in [
+            MethodId::ServerConfiguration_UpdateCertificate,
+            MethodId::ServerConfiguration_CreateSigningRequest,
+            MethodId::ServerConfiguration_GetRejectedList,
+            MethodId::ServerConfiguration_ApplyChanges,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Open,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_OpenWithMasks,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Read,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Write,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_GetPosition,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_SetPosition,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Close,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_CloseAndUpdate,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_AddCertificate,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_RemoveCertificate,
+        ] {
+            Self::set_method_executable(address_space, method);
+        }
+        // Methods managing the identities of roles are handled by the server's `RoleManager`,
+        // which also checks that the user is a security admin.
+        for method in WellKnownRole::identity_methods() {
+            Self::set_method_executable(address_space, method);
+        }
     }
 
     fn namespaces(&self) -> Vec<NamespaceMetadata> {
@@ -125,7 +163,16 @@ impl InMemoryNodeManagerImpl for CoreNodeManagerImpl {
         _address_space: &RwLock<AddressSpace>,
         methods_to_call: &mut [&mut &mut MethodCall],
     ) -> Result<(), StatusCode> {
+        let info = &context.info;
         for method in methods_to_call {
+            // Methods on conditions, certificate management and roles are handled by the
+            // server itself, once the calls have been validated here.
+            if info.conditions.call(context, method)
+                || info.certificate_manager.call(context, method)
+                || info.roles.call(context, method)
+            {
+                continue;
+            }
             if let Err(e) = self.call_builtin_method(method, context) {
                 method.set_status(e);
             }
@@ -450,7 +497,21 @@ impl CoreNodeManagerImpl {
                 namespaces.into()
             }
 
-            _ => return None,
+            VariableId::ServerConfiguration_SupportedPrivateKeyFormats => {
+                vec![UAString::from("PEM")].into()
+            }
+            VariableId::ServerConfiguration_MaxTrustListSize => {
+                (limits.max_message_size as u32).into()
+            }
+            VariableId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_OpenCount => {
+                (context.info.certificate_manager.trust_list_open_count() as u16).into()
+            }
+            VariableId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_LastUpdateTime => {
+                context.info.certificate_manager.trust_list_last_update().into()
+            }
+
+            // The identities of the well-known roles are kept by the `RoleManager`.
+            _ => context.info.roles.read_identities(var_id)?,
         };
 
         let v = if !matches!(node.index_range, NumericRange::None) {
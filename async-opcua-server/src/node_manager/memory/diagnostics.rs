@@ -6,6 +6,7 @@ use std::{
 
 use async_trait::async_trait;
 use opcua_core::trace_read_lock;
+use opcua_nodes::{AttributeQueryable, TypeTree};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -19,7 +20,7 @@ use crate::{
             NodeMetadata,
         },
         BrowseNode, BrowsePathItem, DefaultTypeTree, DynNodeManager, NodeManager, NodeManagersRef,
-        ReadNode, RequestContext, ServerContext, SyncSampler,
+        QueryNode, QueryRequest, ReadNode, RequestContext, ServerContext, SyncSampler,
     },
 };
 use opcua_types::{
@@ -79,6 +80,20 @@ pub struct NamespaceMetadata {
     pub namespace_index: u16,
 }
 
+/// Properties of each `NamespaceMetadataType` node.
+const NAMESPACE_PROPERTIES: [&str; 10] = [
+    "DefaultAccessRestrictions",
+    "DefaultRolePermissions",
+    "DefaultUserRolePermissions",
+    "IsNamespaceSubset",
+    "NamespacePublicationDate",
+    "NamespaceUri",
+    "NamespaceVersion",
+    "StaticNodeIdTypes",
+    "StaticNumericNodeIdRange",
+    "StaticStringNodeIdPattern",
+];
+
 #[derive(Default)]
 struct BrowseContinuationPoint {
     nodes: VecDeque<ReferenceDescription>,
@@ -95,6 +110,122 @@ enum DiagnosticsNode {
     Namespace(NamespaceNode),
 }
 
+/// Reference to a namespace node or one of its properties, used to evaluate queries.
+#[derive(Clone, Copy)]
+struct DiagnosticsQueryNode<'a> {
+    node_manager: &'a DiagnosticsNodeManager,
+    type_tree: &'a DefaultTypeTree,
+    namespace: &'a NamespaceMetadata,
+    property: Option<&'static str>,
+}
+
+impl AttributeQueryable for DiagnosticsQueryNode<'_> {
+    fn get_attribute(
+        &self,
+        type_definition_id: &NodeId,
+        browse_path: &[QualifiedName],
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+    ) -> Variant {
+        self.query_attribute(type_definition_id, browse_path, attribute_id, index_range)
+    }
+
+    fn is_of_type(&self, type_definition_id: &NodeId, include_subtypes: bool) -> bool {
+        let Some(own_type) = self.type_definition() else {
+            return false;
+        };
+        if include_subtypes {
+            self.type_tree.is_subtype_of(&own_type, type_definition_id)
+        } else {
+            &own_type == type_definition_id
+        }
+    }
+
+    fn get_related(&self, reference_type_id: &NodeId, include_subtypes: bool) -> Vec<Self> {
+        self.references(reference_type_id, include_subtypes, false)
+    }
+}
+
+impl QueryNode for DiagnosticsQueryNode<'_> {
+    fn node_id(&self) -> NodeId {
+        as_opaque_node_id(
+            &DiagnosticsNode::Namespace(NamespaceNode {
+                namespace: self.namespace.namespace_uri.clone(),
+                property: self.property.map(|p| p.to_owned()),
+            }),
+            self.node_manager.namespace_index,
+        )
+        .unwrap_or_default()
+    }
+
+    fn browse_name(&self) -> QualifiedName {
+        match self.property {
+            Some(prop) => QualifiedName::new(0, prop),
+            None => QualifiedName::new(
+                self.namespace.namespace_index,
+                &self.namespace.namespace_uri,
+            ),
+        }
+    }
+
+    fn type_definition(&self) -> Option<NodeId> {
+        Some(match self.property {
+            Some(_) => VariableTypeId::PropertyType.into(),
+            None => ObjectTypeId::NamespaceMetadataType.into(),
+        })
+    }
+
+    fn references(
+        &self,
+        reference_type_id: &NodeId,
+        include_subtypes: bool,
+        is_inverse: bool,
+    ) -> Vec<Self> {
+        // The only references between nodes in this node manager are the
+        // `HasProperty` references from each namespace to its properties.
+        let has_property: NodeId = ReferenceTypeId::HasProperty.into();
+        let matches_type = reference_type_id.is_null()
+            || &has_property == reference_type_id
+            || include_subtypes
+                && self
+                    .type_tree
+                    .is_subtype_of(&has_property, reference_type_id);
+        if !matches_type {
+            return Vec::new();
+        }
+
+        match (self.property, is_inverse) {
+            (None, false) => NAMESPACE_PROPERTIES
+                .into_iter()
+                .map(|prop| Self {
+                    property: Some(prop),
+                    ..*self
+                })
+                .collect(),
+            (Some(_), true) => vec![Self {
+                property: None,
+                ..*self
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn read_attribute(&self, attribute_id: AttributeId, index_range: &NumericRange) -> Variant {
+        let value = match self.property {
+            Some(prop) => {
+                self.node_manager
+                    .namespace_property_attribute(self.namespace, prop, attribute_id)
+            }
+            None => self
+                .node_manager
+                .namespace_metadata_attribute(self.namespace, attribute_id),
+        };
+        value
+            .and_then(|v| v.range_of_owned(index_range))
+            .unwrap_or_default()
+    }
+}
+
 /// Builder for the diagnostics node manager.
 pub struct DiagnosticsNodeManagerBuilder;
 
@@ -212,19 +343,7 @@ impl DiagnosticsNodeManager {
     }
 
     fn is_valid_property(prop: &str) -> bool {
-        matches!(
-            prop,
-            "DefaultAccessRestrictions"
-                | "DefaultRolePermissions"
-                | "DefaultUserRolePermissions"
-                | "IsNamespaceSubset"
-                | "NamespacePublicationDate"
-                | "NamespaceUri"
-                | "NamespaceVersion"
-                | "StaticNodeIdTypes"
-                | "StaticNumericNodeIdRange"
-                | "StaticStringNodeIdPattern"
-        )
+        NAMESPACE_PROPERTIES.contains(&prop)
     }
 
     fn browse_namespace_metadata_node(
@@ -242,18 +361,7 @@ impl DiagnosticsNodeManager {
             if node_to_browse.allows_reference_type(&ReferenceTypeId::HasProperty.into(), type_tree)
                 && node_to_browse.allows_node_class(NodeClass::Variable)
             {
-                for prop in [
-                    "DefaultAccessRestrictions",
-                    "DefaultRolePermissions",
-                    "DefaultUserRolePermissions",
-                    "IsNamespaceSubset",
-                    "NamespacePublicationDate",
-                    "NamespaceUri",
-                    "NamespaceVersion",
-                    "StaticNodeIdTypes",
-                    "StaticNumericNodeIdRange",
-                    "StaticStringNodeIdPattern",
-                ] {
+                for prop in NAMESPACE_PROPERTIES {
                     let meta = self.property_node_metadata(&meta.namespace_uri, prop);
                     let ref_desc = meta.into_ref_desc(true, ReferenceTypeId::HasProperty);
 
@@ -366,13 +474,12 @@ impl DiagnosticsNodeManager {
         }
     }
 
-    fn read_namespace_metadata_node(
+    fn namespace_metadata_attribute(
         &self,
-        start_time: DateTime,
-        node_to_read: &mut ReadNode,
         namespace: &NamespaceMetadata,
-    ) {
-        let v: Variant = match node_to_read.node().attribute_id {
+        attribute_id: AttributeId,
+    ) -> Result<Variant, StatusCode> {
+        Ok(match attribute_id {
             AttributeId::NodeId => as_opaque_node_id(
                 &DiagnosticsNode::Namespace(NamespaceNode {
                     namespace: namespace.namespace_uri.clone(),
@@ -389,8 +496,21 @@ impl DiagnosticsNodeManager {
             AttributeId::DisplayName => LocalizedText::new("", &namespace.namespace_uri).into(),
             AttributeId::EventNotifier => 0u8.into(),
             AttributeId::WriteMask | AttributeId::UserWriteMask => 0u32.into(),
-            _ => {
-                node_to_read.set_error(StatusCode::BadAttributeIdInvalid);
+            _ => return Err(StatusCode::BadAttributeIdInvalid),
+        })
+    }
+
+    fn read_namespace_metadata_node(
+        &self,
+        start_time: DateTime,
+        node_to_read: &mut ReadNode,
+        namespace: &NamespaceMetadata,
+    ) {
+        let v = match self.namespace_metadata_attribute(namespace, node_to_read.node().attribute_id)
+        {
+            Ok(v) => v,
+            Err(e) => {
+                node_to_read.set_error(e);
                 return;
             }
         };
@@ -405,19 +525,13 @@ impl DiagnosticsNodeManager {
         });
     }
 
-    fn read_namespace_property_node(
+    fn namespace_property_attribute(
         &self,
-        start_time: DateTime,
-        node_to_read: &mut ReadNode,
         namespace: &NamespaceMetadata,
         prop: &str,
-    ) {
-        if !Self::is_valid_property(prop) {
-            node_to_read.set_error(StatusCode::BadNodeIdUnknown);
-            return;
-        }
-
-        let v: Variant = match node_to_read.node().attribute_id {
+        attribute_id: AttributeId,
+    ) -> Result<Variant, StatusCode> {
+        Ok(match attribute_id {
             AttributeId::NodeId => as_opaque_node_id(
                 &DiagnosticsNode::Namespace(NamespaceNode {
                     namespace: namespace.namespace_uri.clone(),
@@ -469,10 +583,7 @@ impl DiagnosticsNodeManager {
                 "StaticStringNodeIdPattern" => {
                     namespace.static_string_node_id_pattern.clone().into()
                 }
-                _ => {
-                    node_to_read.set_error(StatusCode::BadNodeIdUnknown);
-                    return;
-                }
+                _ => return Err(StatusCode::BadNodeIdUnknown),
             },
             AttributeId::DataType => match prop {
                 "DefaultAccessRestrictions" => {
@@ -492,10 +603,7 @@ impl DiagnosticsNodeManager {
                 "StaticNumericNodeIdRange" => {
                     Variant::NodeId(Box::new(DataTypeId::NumericRange.into()))
                 }
-                _ => {
-                    node_to_read.set_error(StatusCode::BadNodeIdUnknown);
-                    return;
-                }
+                _ => return Err(StatusCode::BadNodeIdUnknown),
             },
             AttributeId::ValueRank => match prop {
                 "DefaultRolePermissions" | "DefaultUserRolePermissions" | "StaticNodeIdTypes" => {
@@ -516,8 +624,30 @@ impl DiagnosticsNodeManager {
             AttributeId::MinimumSamplingInterval => 0.0.into(),
             AttributeId::Historizing => false.into(),
             AttributeId::WriteMask | AttributeId::UserWriteMask => 0u32.into(),
-            _ => {
-                node_to_read.set_error(StatusCode::BadAttributeIdInvalid);
+            _ => return Err(StatusCode::BadAttributeIdInvalid),
+        })
+    }
+
+    fn read_namespace_property_node(
+        &self,
+        start_time: DateTime,
+        node_to_read: &mut ReadNode,
+        namespace: &NamespaceMetadata,
+        prop: &str,
+    ) {
+        if !Self::is_valid_property(prop) {
+            node_to_read.set_error(StatusCode::BadNodeIdUnknown);
+            return;
+        }
+
+        let v = match self.namespace_property_attribute(
+            namespace,
+            prop,
+            node_to_read.node().attribute_id,
+        ) {
+            Ok(v) => v,
+            Err(e) => {
+                node_to_read.set_error(e);
                 return;
             }
         };
@@ -705,4 +835,37 @@ impl NodeManager for DiagnosticsNodeManager {
     ) -> Result<(), StatusCode> {
         impl_translate_browse_paths_using_browse(self, context, nodes).await
     }
+
+    async fn query(
+        &self,
+        context: &RequestContext,
+        request: &mut QueryRequest,
+    ) -> Result<(), StatusCode> {
+        if request.resume_data_sets()? {
+            return Ok(());
+        }
+
+        let namespaces = self.namespaces(context);
+        let data_sets: Vec<_> = {
+            let type_tree = trace_read_lock!(context.type_tree);
+            namespaces
+                .values()
+                // The namespace node for the core namespace is handled by the core node manager.
+                .filter(|ns| ns.namespace_index != 0)
+                .flat_map(|namespace| {
+                    let node = DiagnosticsQueryNode {
+                        node_manager: self,
+                        type_tree: &type_tree,
+                        namespace,
+                        property: None,
+                    };
+                    std::iter::once(node).chain(node.references(&NodeId::null(), false, false))
+                })
+                .filter_map(|node| request.evaluate_node(node))
+                .collect()
+        };
+        request.add_data_sets(data_sets);
+
+        Ok(())
+    }
 }
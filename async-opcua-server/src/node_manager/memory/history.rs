@@ -0,0 +1,983 @@
+//! Storage of historical values for variables in the [InMemoryNodeManager](super::InMemoryNodeManager).
+//!
+//! The node manager records value changes made through
+//! [InMemoryNodeManager::set_values](super::InMemoryNodeManager::set_values) in a
+//! [HistoryStorage], and uses it to answer `HistoryRead` and `HistoryUpdate` requests.
+
+use std::{collections::VecDeque, ops::Bound};
+
+use hashbrown::HashMap;
+use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
+use opcua_types::{
+    DataValue, DateTime, HistoryData, HistoryModifiedData, HistoryUpdateType, ModificationInfo,
+    NodeId, PerformUpdateType, ReadAtTimeDetails, ReadProcessedDetails, ReadRawModifiedDetails,
+    StatusCode, StatusCodeValueType, TimestampsToReturn, UAString, Variant,
+};
+
+use crate::{
+    node_manager::{
+        history_timestamp, AggregateCalculator, AggregateInterval, HistoryNode,
+        HistoryUpdateDetails, HistoryUpdateNode, ProcessingIntervals, RequestContext,
+    },
+    ContinuationPoint,
+};
+
+/// Maximum number of values returned per node in a single history read,
+/// used when the client does not specify a limit.
+const MAX_HISTORY_VALUES_PER_NODE: usize = 10_000;
+
+/// Maximum number of raw values used to calculate a single processed value. Intervals
+/// with more raw values than this get the status `BadAggregateInvalidInputs`.
+const MAX_RAW_VALUES_PER_INTERVAL: usize = 10_000;
+
+/// Maximum number of raw values read per node in a single processed read. Once this
+/// is reached, the remaining intervals are returned after a continuation point.
+const MAX_RAW_VALUES_PER_PROCESSED_READ: usize = 100_000;
+
+/// A query for a range of historical values from a [HistoryStorage].
+#[derive(Debug, Clone)]
+pub struct HistoryQuery {
+    /// Chronological range of source timestamps to read.
+    pub range: (Bound<DateTime>, Bound<DateTime>),
+    /// Whether to return values oldest first. If `false`, values are
+    /// returned newest first.
+    pub is_forward: bool,
+    /// Number of matching values to skip, in the direction of the read.
+    pub skip: usize,
+    /// Maximum number of values to return.
+    pub max_values: usize,
+}
+
+/// Trait for a backend storing the value history of variables.
+///
+/// Values are ordered by their source timestamp, falling back to the server timestamp
+/// if the source timestamp is not set.
+///
+/// Implementations are called synchronously with the address space lock held
+/// when recording values, so they should avoid blocking for long.
+pub trait HistoryStorage: Send + Sync + 'static {
+    /// Record a new value for the variable given by `node_id`.
+    fn record_value(&self, node_id: &NodeId, value: DataValue);
+
+    /// Read raw values for the variable given by `node_id`.
+    fn read_raw(&self, node_id: &NodeId, query: &HistoryQuery) -> Vec<DataValue>;
+
+    /// Read values that have been replaced or deleted for the variable given by `node_id`,
+    /// along with information about the modification.
+    fn read_modified(
+        &self,
+        node_id: &NodeId,
+        query: &HistoryQuery,
+    ) -> Vec<(DataValue, ModificationInfo)>;
+
+    /// Insert, replace, or update a single historical value.
+    ///
+    /// This should return `GoodEntryInserted`, `GoodEntryReplaced`, `BadEntryExists`
+    /// or `BadNoEntryExists` as appropriate. Replaced values should be kept as modified values,
+    /// described by `info`.
+    fn update_value(
+        &self,
+        node_id: &NodeId,
+        value: DataValue,
+        update_type: PerformUpdateType,
+        info: ModificationInfo,
+    ) -> StatusCode;
+
+    /// Delete values in the given chronological range. If `is_delete_modified` is `true`,
+    /// delete modified values instead. Deleted raw values should be kept as modified values,
+    /// described by `info`.
+    fn delete_range(
+        &self,
+        node_id: &NodeId,
+        range: (Bound<DateTime>, Bound<DateTime>),
+        is_delete_modified: bool,
+        info: ModificationInfo,
+    ) -> StatusCode;
+
+    /// Delete the value with the exact timestamp `time`.
+    fn delete_at_time(
+        &self,
+        node_id: &NodeId,
+        time: DateTime,
+        info: ModificationInfo,
+    ) -> StatusCode;
+}
+
+fn is_before_range(time: &DateTime, bound: &Bound<DateTime>) -> bool {
+    match bound {
+        Bound::Included(s) => time < s,
+        Bound::Excluded(s) => time <= s,
+        Bound::Unbounded => false,
+    }
+}
+
+fn is_in_range_end(time: &DateTime, bound: &Bound<DateTime>) -> bool {
+    match bound {
+        Bound::Included(e) => time <= e,
+        Bound::Excluded(e) => time < e,
+        Bound::Unbounded => true,
+    }
+}
+
+/// Get the start and end index of values in the given range from a chronologically
+/// sorted list.
+fn range_indices<T>(
+    values: &VecDeque<T>,
+    range: &(Bound<DateTime>, Bound<DateTime>),
+    time: impl Fn(&T) -> DateTime,
+) -> (usize, usize) {
+    let start = values.partition_point(|v| is_before_range(&time(v), &range.0));
+    let end = values.partition_point(|v| is_in_range_end(&time(v), &range.1));
+    (start, end.max(start))
+}
+
+fn query_range<T: Clone>(
+    values: &VecDeque<T>,
+    query: &HistoryQuery,
+    time: impl Fn(&T) -> DateTime,
+) -> Vec<T> {
+    let (start, end) = range_indices(values, &query.range, time);
+    let it = values.range(start..end);
+    if query.is_forward {
+        it.skip(query.skip)
+            .take(query.max_values)
+            .cloned()
+            .collect()
+    } else {
+        it.rev()
+            .skip(query.skip)
+            .take(query.max_values)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct NodeHistory {
+    values: VecDeque<DataValue>,
+    modified: VecDeque<(DataValue, ModificationInfo)>,
+}
+
+impl NodeHistory {
+    fn find_exact(&self, time: &DateTime) -> Result<usize, usize> {
+        let idx = self
+            .values
+            .partition_point(|v| history_timestamp(v) < *time);
+        if self
+            .values
+            .get(idx)
+            .is_some_and(|v| history_timestamp(v) == *time)
+        {
+            Ok(idx)
+        } else {
+            Err(idx)
+        }
+    }
+
+    fn push_modified(&mut self, value: DataValue, info: ModificationInfo, capacity: usize) {
+        let time = history_timestamp(&value);
+        let idx = self
+            .modified
+            .partition_point(|(v, _)| history_timestamp(v) <= time);
+        self.modified.insert(idx, (value, info));
+        while self.modified.len() > capacity {
+            self.modified.pop_front();
+        }
+    }
+}
+
+/// A simple [HistoryStorage] keeping a fixed number of values per node in memory,
+/// discarding the oldest values when full.
+pub struct InMemoryHistoryStorage {
+    capacity: usize,
+    nodes: RwLock<HashMap<NodeId, NodeHistory>>,
+}
+
+impl InMemoryHistoryStorage {
+    /// Create a new in-memory history storage, keeping up to `capacity`
+    /// values and `capacity` modified values for each node.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            nodes: Default::default(),
+        }
+    }
+
+    /// Get the maximum number of values stored per node.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of values currently stored for the given node.
+    pub fn len(&self, node_id: &NodeId) -> usize {
+        let nodes = trace_read_lock!(self.nodes);
+        nodes
+            .get(node_id)
+            .map(|n| n.values.len())
+            .unwrap_or_default()
+    }
+
+    /// Return `true` if no values are stored for the given node.
+    pub fn is_empty(&self, node_id: &NodeId) -> bool {
+        self.len(node_id) == 0
+    }
+
+    /// Remove all history for the given node.
+    pub fn clear(&self, node_id: &NodeId) {
+        let mut nodes = trace_write_lock!(self.nodes);
+        nodes.remove(node_id);
+    }
+}
+
+impl Default for InMemoryHistoryStorage {
+    fn default() -> Self {
+        Self::new(1000)
+    }
+}
+
+impl HistoryStorage for InMemoryHistoryStorage {
+    fn record_value(&self, node_id: &NodeId, value: DataValue) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut nodes = trace_write_lock!(self.nodes);
+        let history = nodes.entry(node_id.clone()).or_default();
+        let time = history_timestamp(&value);
+        // Values are almost always recorded in order, so this is usually just a push.
+        if history
+            .values
+            .back()
+            .is_none_or(|v| history_timestamp(v) <= time)
+        {
+            history.values.push_back(value);
+        } else {
+            let idx = history
+                .values
+                .partition_point(|v| history_timestamp(v) <= time);
+            history.values.insert(idx, value);
+        }
+        while history.values.len() > self.capacity {
+            history.values.pop_front();
+        }
+    }
+
+    fn read_raw(&self, node_id: &NodeId, query: &HistoryQuery) -> Vec<DataValue> {
+        let nodes = trace_read_lock!(self.nodes);
+        let Some(history) = nodes.get(node_id) else {
+            return Vec::new();
+        };
+        query_range(&history.values, query, history_timestamp)
+    }
+
+    fn read_modified(
+        &self,
+        node_id: &NodeId,
+        query: &HistoryQuery,
+    ) -> Vec<(DataValue, ModificationInfo)> {
+        let nodes = trace_read_lock!(self.nodes);
+        let Some(history) = nodes.get(node_id) else {
+            return Vec::new();
+        };
+        query_range(&history.modified, query, |(v, _)| history_timestamp(v))
+    }
+
+    fn update_value(
+        &self,
+        node_id: &NodeId,
+        value: DataValue,
+        update_type: PerformUpdateType,
+        info: ModificationInfo,
+    ) -> StatusCode {
+        let mut nodes = trace_write_lock!(self.nodes);
+        let history = nodes.entry(node_id.clone()).or_default();
+        let time = history_timestamp(&value);
+
+        match (history.find_exact(&time), update_type) {
+            (Ok(_), PerformUpdateType::Insert) => StatusCode::BadEntryExists,
+            (Err(_), PerformUpdateType::Replace) => StatusCode::BadNoEntryExists,
+            (Ok(idx), PerformUpdateType::Replace | PerformUpdateType::Update) => {
+                let old = std::mem::replace(&mut history.values[idx], value);
+                history.push_modified(old, info, self.capacity);
+                StatusCode::GoodEntryReplaced
+            }
+            (Err(idx), PerformUpdateType::Insert | PerformUpdateType::Update) => {
+                if history.values.len() >= self.capacity && idx == 0 {
+                    // The value would be evicted immediately.
+                    return StatusCode::BadOutOfMemory;
+                }
+                history.values.insert(idx, value);
+                while history.values.len() > self.capacity {
+                    history.values.pop_front();
+                }
+                StatusCode::GoodEntryInserted
+            }
+            _ => StatusCode::BadHistoryOperationInvalid,
+        }
+    }
+
+    fn delete_range(
+        &self,
+        node_id: &NodeId,
+        range: (Bound<DateTime>, Bound<DateTime>),
+        is_delete_modified: bool,
+        info: ModificationInfo,
+    ) -> StatusCode {
+        let mut nodes = trace_write_lock!(self.nodes);
+        let Some(history) = nodes.get_mut(node_id) else {
+            return StatusCode::GoodNoData;
+        };
+
+        if is_delete_modified {
+            let (start, end) =
+                range_indices(&history.modified, &range, |(v, _)| history_timestamp(v));
+            if start == end {
+                return StatusCode::GoodNoData;
+            }
+            history.modified.drain(start..end);
+        } else {
+            let (start, end) = range_indices(&history.values, &range, history_timestamp);
+            if start == end {
+                return StatusCode::GoodNoData;
+            }
+            let removed: Vec<_> = history.values.drain(start..end).collect();
+            for value in removed {
+                history.push_modified(value, info.clone(), self.capacity);
+            }
+        }
+        StatusCode::Good
+    }
+
+    fn delete_at_time(
+        &self,
+        node_id: &NodeId,
+        time: DateTime,
+        info: ModificationInfo,
+    ) -> StatusCode {
+        let mut nodes = trace_write_lock!(self.nodes);
+        let Some(history) = nodes.get_mut(node_id) else {
+            return StatusCode::BadNoEntryExists;
+        };
+        let Ok(idx) = history.find_exact(&time) else {
+            return StatusCode::BadNoEntryExists;
+        };
+        if let Some(value) = history.values.remove(idx) {
+            history.push_modified(value, info, self.capacity);
+        }
+        StatusCode::Good
+    }
+}
+
+fn apply_timestamps_to_return(value: &mut DataValue, timestamps_to_return: TimestampsToReturn) {
+    match timestamps_to_return {
+        TimestampsToReturn::Neither | TimestampsToReturn::Invalid => {
+            value.source_timestamp = None;
+            value.source_picoseconds = None;
+            value.server_timestamp = None;
+            value.server_picoseconds = None
+        }
+        TimestampsToReturn::Server => {
+            value.source_timestamp = None;
+            value.source_picoseconds = None;
+        }
+        TimestampsToReturn::Source => {
+            value.server_timestamp = None;
+            value.server_picoseconds = None
+        }
+        TimestampsToReturn::Both => {}
+    }
+}
+
+/// Continuation point for raw/modified reads from a [HistoryStorage].
+struct RawModifiedContinuationPoint {
+    query: HistoryQuery,
+}
+
+/// Continuation point for at-time reads from a [HistoryStorage].
+struct AtTimeContinuationPoint {
+    index: usize,
+}
+
+/// Continuation point for processed reads from a [HistoryStorage].
+struct ProcessedContinuationPoint {
+    index: usize,
+}
+
+/// Compute the query for the next page, given the values returned for the current page.
+/// We use the timestamp of the last value as the new start of the range, and skip any values
+/// with that same timestamp we have already returned. This is robust against values being
+/// discarded while reading.
+fn next_page_query(query: &HistoryQuery, page: &[DateTime]) -> Option<HistoryQuery> {
+    let last = *page.last()?;
+    let mut skip = page.iter().rev().take_while(|t| **t == last).count();
+    let leading_bound = if query.is_forward {
+        &query.range.0
+    } else {
+        &query.range.1
+    };
+    if skip == page.len() && leading_bound == &Bound::Included(last) {
+        skip += query.skip;
+    }
+    let mut next = query.clone();
+    if query.is_forward {
+        next.range.0 = Bound::Included(last);
+    } else {
+        next.range.1 = Bound::Included(last);
+    }
+    next.skip = skip;
+    Some(next)
+}
+
+fn bound_value(
+    storage: &dyn HistoryStorage,
+    node_id: &NodeId,
+    time: DateTime,
+    is_before: bool,
+) -> DataValue {
+    let range = if is_before {
+        (Bound::Unbounded, Bound::Included(time))
+    } else {
+        (Bound::Included(time), Bound::Unbounded)
+    };
+    let value = storage
+        .read_raw(
+            node_id,
+            &HistoryQuery {
+                range,
+                is_forward: !is_before,
+                skip: 0,
+                max_values: 1,
+            },
+        )
+        .into_iter()
+        .next();
+    value.unwrap_or_else(|| DataValue {
+        status: Some(StatusCode::BadBoundNotFound),
+        source_timestamp: Some(time),
+        server_timestamp: Some(time),
+        ..Default::default()
+    })
+}
+
+/// Read raw or modified values from `storage`, writing results and
+/// continuation points to `nodes`.
+pub(super) fn read_raw_modified(
+    storage: &dyn HistoryStorage,
+    details: &ReadRawModifiedDetails,
+    nodes: &mut [&mut &mut HistoryNode],
+    timestamps_to_return: TimestampsToReturn,
+) {
+    let start_null = details.start_time.is_null();
+    let end_null = details.end_time.is_null();
+    if (start_null && end_null) || ((start_null || end_null) && details.num_values_per_node == 0) {
+        for node in nodes {
+            node.set_status(StatusCode::BadInvalidTimestampArgument);
+        }
+        return;
+    }
+
+    let is_forward = if start_null {
+        false
+    } else if end_null {
+        true
+    } else {
+        details.start_time <= details.end_time
+    };
+    let start = if start_null {
+        Bound::Unbounded
+    } else {
+        Bound::Included(details.start_time)
+    };
+    let end = if end_null {
+        Bound::Unbounded
+    } else if details.start_time == details.end_time {
+        Bound::Included(details.end_time)
+    } else {
+        Bound::Excluded(details.end_time)
+    };
+    // The range is always chronological, `is_forward` only gives the direction
+    // values are read in.
+    let range = if start_null || is_forward {
+        (start, end)
+    } else {
+        (end, start)
+    };
+    let max_values = if details.num_values_per_node == 0 {
+        MAX_HISTORY_VALUES_PER_NODE
+    } else {
+        (details.num_values_per_node as usize).min(MAX_HISTORY_VALUES_PER_NODE)
+    };
+    // Bounds are ignored when reading modified values.
+    let return_bounds = details.return_bounds && !details.is_read_modified;
+
+    for node in nodes {
+        let (query, is_first_page) = match node.continuation_point() {
+            Some(cp) => {
+                let Some(cp) = cp.get::<RawModifiedContinuationPoint>() else {
+                    node.set_status(StatusCode::BadContinuationPointInvalid);
+                    continue;
+                };
+                (cp.query.clone(), false)
+            }
+            None => (
+                HistoryQuery {
+                    range,
+                    is_forward,
+                    skip: 0,
+                    max_values,
+                },
+                true,
+            ),
+        };
+
+        // Read one more value than requested, to know if there is more data.
+        let mut read_query = query.clone();
+        read_query.max_values = max_values.saturating_add(1);
+
+        let (mut values, infos) = if details.is_read_modified {
+            let (values, infos) = storage
+                .read_modified(node.node_id(), &read_query)
+                .into_iter()
+                .unzip();
+            (values, Some(infos))
+        } else {
+            (storage.read_raw(node.node_id(), &read_query), None)
+        };
+
+        let has_more = values.len() > max_values;
+        values.truncate(max_values);
+        let mut infos: Option<Vec<_>> = infos.map(|mut i: Vec<_>| {
+            i.truncate(max_values);
+            i
+        });
+
+        let next = if has_more {
+            let times: Vec<_> = values.iter().map(history_timestamp).collect();
+            next_page_query(&query, &times)
+        } else {
+            None
+        };
+
+        if return_bounds && is_first_page && !start_null {
+            let time = details.start_time;
+            if values.first().is_none_or(|v| history_timestamp(v) != time) {
+                values.insert(0, bound_value(storage, node.node_id(), time, is_forward));
+            }
+        }
+        if return_bounds && next.is_none() && !end_null {
+            let time = details.end_time;
+            values.push(bound_value(storage, node.node_id(), time, !is_forward));
+        }
+
+        for value in &mut values {
+            apply_timestamps_to_return(value, timestamps_to_return);
+        }
+
+        node.set_next_continuation_point(
+            next.map(|query| {
+                ContinuationPoint::new(Box::new(RawModifiedContinuationPoint { query }))
+            }),
+        );
+        node.set_status(if values.is_empty() {
+            StatusCode::GoodNoData
+        } else {
+            StatusCode::Good
+        });
+        if let Some(infos) = infos.take() {
+            node.set_result(HistoryModifiedData {
+                data_values: Some(values),
+                modification_infos: Some(infos),
+            });
+        } else {
+            node.set_result(HistoryData {
+                data_values: Some(values),
+            });
+        }
+    }
+}
+
+/// Interpolate a value at `time` between the two values `before` and `after`.
+/// Numeric values are interpolated linearly, others use the value of `before`.
+pub(crate) fn interpolate(
+    before: &DataValue,
+    after: Option<&DataValue>,
+    time: DateTime,
+) -> DataValue {
+    let before_time = history_timestamp(before);
+    let value = match (
+        after,
+        before.value.as_ref().and_then(|v| v.as_f64()),
+        after
+            .and_then(|a| a.value.as_ref())
+            .and_then(|v| v.as_f64()),
+    ) {
+        (Some(after), Some(v1), Some(v2)) if history_timestamp(after) > before_time => {
+            let t1 = before_time.ticks() as f64;
+            let t2 = history_timestamp(after).ticks() as f64;
+            let v = v1 + (v2 - v1) * ((time.ticks() as f64 - t1) / (t2 - t1));
+            let target = before.value.as_ref().map(|v| v.type_id());
+            match target {
+                Some(t) => Variant::Double(v).cast(t),
+                None => Variant::Double(v),
+            }
+        }
+        _ => before.value.clone().unwrap_or_default(),
+    };
+
+    let bounds_good = before.status().is_good() && after.is_some_and(|a| a.status().is_good());
+    let status = if bounds_good {
+        StatusCode::Good
+    } else {
+        StatusCode::UncertainDataSubNormal
+    };
+
+    DataValue {
+        value: Some(value),
+        status: Some(status.set_value_type(StatusCodeValueType::Interpolated)),
+        source_timestamp: Some(time),
+        server_timestamp: Some(time),
+        ..Default::default()
+    }
+}
+
+/// Find the value closest to `time` in the given direction. Unless `use_simple_bounds`
+/// is set, this skips values with bad status.
+fn find_bound(
+    storage: &dyn HistoryStorage,
+    node_id: &NodeId,
+    time: DateTime,
+    is_before: bool,
+    use_simple_bounds: bool,
+) -> Option<DataValue> {
+    let mut query = HistoryQuery {
+        range: if is_before {
+            (Bound::Unbounded, Bound::Excluded(time))
+        } else {
+            (Bound::Excluded(time), Bound::Unbounded)
+        },
+        is_forward: !is_before,
+        skip: 0,
+        max_values: if use_simple_bounds { 1 } else { 100 },
+    };
+    loop {
+        let values = storage.read_raw(node_id, &query);
+        if values.is_empty() {
+            return None;
+        }
+        let len = values.len();
+        if let Some(v) = values
+            .into_iter()
+            .find(|v| use_simple_bounds || !v.status().is_bad())
+        {
+            return Some(v);
+        }
+        query.skip += len;
+    }
+}
+
+/// Read values at specific times from `storage`, writing results and continuation
+/// points to `nodes`.
+pub(super) fn read_at_time(
+    storage: &dyn HistoryStorage,
+    details: &ReadAtTimeDetails,
+    nodes: &mut [&mut &mut HistoryNode],
+    timestamps_to_return: TimestampsToReturn,
+) {
+    let req_times = details.req_times.as_deref().unwrap_or_default();
+    if req_times.is_empty() {
+        for node in nodes {
+            node.set_status(StatusCode::BadInvalidTimestampArgument);
+        }
+        return;
+    }
+
+    for node in nodes {
+        let start_index = match node.continuation_point() {
+            Some(cp) => {
+                let Some(cp) = cp.get::<AtTimeContinuationPoint>() else {
+                    node.set_status(StatusCode::BadContinuationPointInvalid);
+                    continue;
+                };
+                cp.index
+            }
+            None => 0,
+        };
+        let end_index = start_index
+            .saturating_add(MAX_HISTORY_VALUES_PER_NODE)
+            .min(req_times.len());
+
+        let mut values = Vec::with_capacity(end_index.saturating_sub(start_index));
+        for time in req_times
+            .iter()
+            .skip(start_index)
+            .take(end_index - start_index)
+        {
+            let exact = storage
+                .read_raw(
+                    node.node_id(),
+                    &HistoryQuery {
+                        range: (Bound::Included(*time), Bound::Included(*time)),
+                        is_forward: true,
+                        skip: 0,
+                        max_values: 1,
+                    },
+                )
+                .into_iter()
+                .next();
+
+            let mut value = if let Some(exact) = exact {
+                exact
+            } else {
+                let before = find_bound(
+                    storage,
+                    node.node_id(),
+                    *time,
+                    true,
+                    details.use_simple_bounds,
+                );
+                let after = find_bound(
+                    storage,
+                    node.node_id(),
+                    *time,
+                    false,
+                    details.use_simple_bounds,
+                );
+                match before {
+                    Some(before) => interpolate(&before, after.as_ref(), *time),
+                    None => DataValue {
+                        status: Some(StatusCode::BadNoData),
+                        source_timestamp: Some(*time),
+                        server_timestamp: Some(*time),
+                        ..Default::default()
+                    },
+                }
+            };
+            apply_timestamps_to_return(&mut value, timestamps_to_return);
+            values.push(value);
+        }
+
+        if end_index < req_times.len() {
+            node.set_next_continuation_point(Some(ContinuationPoint::new(Box::new(
+                AtTimeContinuationPoint { index: end_index },
+            ))));
+        }
+        node.set_status(StatusCode::Good);
+        node.set_result(HistoryData {
+            data_values: Some(values),
+        });
+    }
+}
+
+/// Read the raw values needed to calculate the aggregate for `interval`, that is the values
+/// in the interval and the closest usable values on either side, so that the calculator can
+/// compute bounding values. At most [MAX_RAW_VALUES_PER_INTERVAL] + 1 values are read.
+fn read_interval_values(
+    storage: &dyn HistoryStorage,
+    node_id: &NodeId,
+    interval: &AggregateInterval,
+) -> Vec<DataValue> {
+    let range = (
+        find_bound(storage, node_id, interval.start, true, false)
+            .map(|v| Bound::Included(history_timestamp(&v)))
+            .unwrap_or(Bound::Unbounded),
+        find_bound(storage, node_id, interval.end, false, false)
+            .map(|v| Bound::Included(history_timestamp(&v)))
+            .unwrap_or(Bound::Unbounded),
+    );
+    storage.read_raw(
+        node_id,
+        &HistoryQuery {
+            range,
+            is_forward: true,
+            skip: 0,
+            max_values: MAX_RAW_VALUES_PER_INTERVAL + 1,
+        },
+    )
+}
+
+/// Read processed values from `storage`, calculating the requested aggregate
+/// for each node. `is_stepped` returns whether a node uses stepped interpolation.
+pub(super) fn read_processed(
+    storage: &dyn HistoryStorage,
+    details: &ReadProcessedDetails,
+    nodes: &mut [&mut &mut HistoryNode],
+    timestamps_to_return: TimestampsToReturn,
+    is_stepped: impl Fn(&NodeId) -> bool,
+) {
+    let intervals = match ProcessingIntervals::new(
+        details.start_time,
+        details.end_time,
+        details.processing_interval,
+    ) {
+        Ok(i) => i,
+        Err(e) => {
+            for node in nodes {
+                node.set_status(e);
+            }
+            return;
+        }
+    };
+
+    for node in nodes {
+        let calculator = match node.aggregate_type().map(|a| {
+            AggregateCalculator::new(
+                a,
+                &details.aggregate_configuration,
+                is_stepped(node.node_id()),
+            )
+        }) {
+            Some(Ok(c)) => c,
+            Some(Err(e)) => {
+                node.set_status(e);
+                continue;
+            }
+            None => {
+                node.set_status(StatusCode::BadAggregateNotSupported);
+                continue;
+            }
+        };
+        let start_index = match node.continuation_point() {
+            Some(cp) => {
+                let Some(cp) = cp.get::<ProcessedContinuationPoint>() else {
+                    node.set_status(StatusCode::BadContinuationPointInvalid);
+                    continue;
+                };
+                cp.index
+            }
+            None => 0,
+        };
+
+        // Calculate one interval at a time, so that only the raw values for a single
+        // interval are kept in memory.
+        let mut results = Vec::new();
+        let mut end_index = start_index;
+        let mut raw_count = 0;
+        for interval in intervals
+            .iter_from(start_index)
+            .take(MAX_HISTORY_VALUES_PER_NODE)
+        {
+            if raw_count >= MAX_RAW_VALUES_PER_PROCESSED_READ {
+                break;
+            }
+            end_index += 1;
+            let values = read_interval_values(storage, node.node_id(), &interval);
+            raw_count += values.len();
+            if values.len() > MAX_RAW_VALUES_PER_INTERVAL {
+                results.push(DataValue {
+                    value: None,
+                    status: Some(StatusCode::BadAggregateInvalidInputs),
+                    source_timestamp: Some(interval.timestamp),
+                    server_timestamp: Some(interval.timestamp),
+                    ..Default::default()
+                });
+            } else {
+                results.extend(calculator.calculate(std::iter::once(interval), &values));
+            }
+        }
+        if results.is_empty() {
+            node.set_status(StatusCode::GoodNoData);
+            continue;
+        }
+
+        for value in &mut results {
+            apply_timestamps_to_return(value, timestamps_to_return);
+        }
+
+        if end_index < intervals.len() {
+            node.set_next_continuation_point(Some(ContinuationPoint::new(Box::new(
+                ProcessedContinuationPoint { index: end_index },
+            ))));
+        }
+        node.set_status(StatusCode::Good);
+        node.set_result(HistoryData {
+            data_values: Some(results),
+        });
+    }
+}
+
+/// Apply history updates to `storage`, setting status codes on `nodes`.
+pub(super) fn update(
+    storage: &dyn HistoryStorage,
+    context: &RequestContext,
+    nodes: &mut [&mut &mut HistoryUpdateNode],
+) {
+    let info = |update_type| ModificationInfo {
+        modification_time: DateTime::now(),
+        update_type,
+        user_name: UAString::from(context.token.0.as_str()),
+    };
+
+    for node in nodes {
+        let details = node.details().clone();
+        let results: Vec<_> = match details {
+            HistoryUpdateDetails::UpdateData(d) => {
+                let update_type = match d.perform_insert_replace {
+                    PerformUpdateType::Insert => HistoryUpdateType::Insert,
+                    PerformUpdateType::Replace => HistoryUpdateType::Replace,
+                    PerformUpdateType::Update => HistoryUpdateType::Update,
+                    PerformUpdateType::Remove => {
+                        node.set_status(StatusCode::BadHistoryOperationInvalid);
+                        continue;
+                    }
+                };
+                d.update_values
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|v| {
+                        if v.source_timestamp.is_none() {
+                            return StatusCode::BadInvalidTimestamp;
+                        }
+                        storage.update_value(
+                            &d.node_id,
+                            v,
+                            d.perform_insert_replace,
+                            info(update_type),
+                        )
+                    })
+                    .collect()
+            }
+            HistoryUpdateDetails::DeleteRawModified(d) => {
+                if d.start_time.is_null() && d.end_time.is_null() {
+                    node.set_status(StatusCode::BadInvalidTimestampArgument);
+                    continue;
+                }
+                let (lo, hi) = if d.end_time.is_null() || d.start_time <= d.end_time {
+                    (d.start_time, d.end_time)
+                } else {
+                    (d.end_time, d.start_time)
+                };
+                let range = (
+                    if lo.is_null() {
+                        Bound::Unbounded
+                    } else {
+                        Bound::Included(lo)
+                    },
+                    if hi.is_null() {
+                        Bound::Unbounded
+                    } else {
+                        Bound::Excluded(hi)
+                    },
+                );
+                let status = storage.delete_range(
+                    &d.node_id,
+                    range,
+                    d.is_delete_modified,
+                    info(HistoryUpdateType::Delete),
+                );
+                node.set_status(status);
+                continue;
+            }
+            HistoryUpdateDetails::DeleteAtTime(d) => d
+                .req_times
+                .unwrap_or_default()
+                .into_iter()
+                .map(|t| storage.delete_at_time(&d.node_id, t, info(HistoryUpdateType::Delete)))
+                .collect(),
+            _ => {
+                node.set_status(StatusCode::BadHistoryOperationUnsupported);
+                continue;
+            }
+        };
+        node.set_status(StatusCode::Good);
+        node.set_operation_results(Some(results));
+    }
+}
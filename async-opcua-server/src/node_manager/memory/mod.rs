@@ -3,8 +3,11 @@
 //! details to a type implementing [InMemoryNodeManagerImpl].
 
 mod diagnostics;
+mod history;
 mod memory_mgr_impl;
+mod query;
 mod simple;
+mod storage;
 
 #[cfg(feature = "generated-address-space")]
 mod core;
@@ -13,10 +16,13 @@ mod core;
 pub use core::{CoreNodeManager, CoreNodeManagerBuilder, CoreNodeManagerImpl};
 
 pub use diagnostics::{DiagnosticsNodeManager, DiagnosticsNodeManagerBuilder, NamespaceMetadata};
-use log::warn;
+pub use history::{HistoryQuery, HistoryStorage, InMemoryHistoryStorage};
+use log::{error, warn};
 pub use memory_mgr_impl::*;
 use opcua_core::{trace_read_lock, trace_write_lock};
 pub use simple::*;
+use storage::NodeStorageWriter;
+pub use storage::{FileNodeStorage, NodeChange, NodeStorage, StoredNode};
 
 use std::{
     collections::{HashSet, VecDeque},
@@ -35,12 +41,13 @@ use crate::{
     SubscriptionCache,
 };
 use opcua_core::sync::RwLock;
+use opcua_nodes::TypeTree;
 use opcua_types::{
     argument::Argument, AttributeId, BrowseDescriptionResultMask, BrowseDirection, DataEncoding,
-    DataValue, DateTime, ExpandedNodeId, MonitoringMode, NodeClass, NodeId, NumericRange,
-    ReadAnnotationDataDetails, ReadAtTimeDetails, ReadEventDetails, ReadProcessedDetails,
-    ReadRawModifiedDetails, ReferenceDescription, ReferenceTypeId, StatusCode, TimestampsToReturn,
-    Variant,
+    DataValue, DateTime, ExpandedNodeId, MonitoringMode, NamespaceMap, NodeClass, NodeId,
+    NumericRange, PermissionType, ReadAnnotationDataDetails, ReadAtTimeDetails, ReadEventDetails,
+    ReadProcessedDetails, ReadRawModifiedDetails, ReferenceDescription, ReferenceTypeId,
+    StatusCode, TimestampsToReturn, Variant,
 };
 
 use super::{
@@ -48,11 +55,13 @@ use super::{
     view::{AddReferenceResult, ExternalReference, ExternalReferenceRequest, NodeMetadata},
     AddNodeItem, AddReferenceItem, BrowseNode, BrowsePathItem, DefaultTypeTree, DeleteNodeItem,
     DeleteReferenceItem, DynNodeManager, HistoryNode, HistoryUpdateDetails, HistoryUpdateNode,
-    MethodCall, MonitoredItemRef, MonitoredItemUpdateRef, NodeManager, ReadNode, RegisterNodeItem,
-    RequestContext, ServerContext, WriteNode,
+    MethodCall, MonitoredItemRef, MonitoredItemUpdateRef, NodeManager, QueryRequest, ReadNode,
+    RegisterNodeItem, RequestContext, ServerContext, WriteNode,
 };
 
-use crate::address_space::AddressSpace;
+use crate::{address_space::AddressSpace, roles::UserRoles};
+
+use query::AddressSpaceQueryNode;
 
 #[derive(Default)]
 struct BrowseContinuationPoint {
@@ -67,19 +76,57 @@ struct BrowseContinuationPoint {
 pub struct InMemoryNodeManager<TImpl> {
     address_space: Arc<RwLock<AddressSpace>>,
     namespaces: HashMap<u16, String>,
+    history: Option<Arc<dyn HistoryStorage>>,
+    storage: Option<Arc<NodeStorageWriter>>,
+    persisted_values: RwLock<HashSet<NodeId>>,
     inner: TImpl,
 }
 
 /// Builder for the in-memory node manager.
 pub struct InMemoryNodeManagerBuilder<T> {
     impl_builder: T,
+    history: Option<Arc<dyn HistoryStorage>>,
+    storage: Option<Arc<dyn NodeStorage>>,
 }
 
 impl<T: InMemoryNodeManagerImplBuilder> InMemoryNodeManagerBuilder<T> {
     /// Create a new in memory node manager builder with the given
     /// builder for the [InMemoryNodeManagerImpl].
     pub fn new(impl_builder: T) -> Self {
-        Self { impl_builder }
+        Self {
+            impl_builder,
+            history: None,
+            storage: None,
+        }
+    }
+
+    /// Store the history of variables with the `Historizing` attribute set
+    /// in `storage`.
+    ///
+    /// Values set through [InMemoryNodeManager::set_values] are recorded automatically,
+    /// and `HistoryRead` for raw, modified and at-time data, as well as `HistoryUpdate`
+    /// for data, are answered from the storage instead of being passed to the
+    /// [InMemoryNodeManagerImpl].
+    pub fn with_history_storage(mut self, storage: impl HistoryStorage) -> Self {
+        self.history = Some(Arc::new(storage));
+        self
+    }
+
+    /// Persist changes to nodes in `storage`, and restore them when the node
+    /// manager is initialized.
+    ///
+    /// Nodes added or deleted through the node management services, attributes
+    /// written through the `Write` service, and changes made through
+    /// [InMemoryNodeManager::set_attributes] are stored. Values set through
+    /// [InMemoryNodeManager::set_values] are only stored for variables enabled with
+    /// [InMemoryNodeManager::set_value_persistence]. Nodes created by the
+    /// [InMemoryNodeManagerImpl] on startup are replaced by their stored state.
+    ///
+    /// Changes are written to the storage in the background, use
+    /// [InMemoryNodeManager::flush_node_storage] to wait for them.
+    pub fn with_node_storage(mut self, storage: impl NodeStorage) -> Self {
+        self.storage = Some(Arc::new(storage));
+        self
     }
 }
 
@@ -87,7 +134,10 @@ impl<T: InMemoryNodeManagerImplBuilder> NodeManagerBuilder for InMemoryNodeManag
     fn build(self: Box<Self>, context: ServerContext) -> Arc<DynNodeManager> {
         let mut address_space = AddressSpace::new();
         let inner = self.impl_builder.build(context, &mut address_space);
-        Arc::new(InMemoryNodeManager::new(inner, address_space))
+        let mut node_manager = InMemoryNodeManager::new(inner, address_space);
+        node_manager.history = self.history;
+        node_manager.storage = self.storage.map(|s| Arc::new(NodeStorageWriter::new(s)));
+        Arc::new(node_manager)
     }
 }
 
@@ -96,10 +146,46 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
         Self {
             namespaces: address_space.namespaces().clone(),
             address_space: Arc::new(RwLock::new(address_space)),
+            history: None,
+            storage: None,
+            persisted_values: Default::default(),
             inner,
         }
     }
 
+    /// Get the history storage used by this node manager, if any.
+    pub fn history_storage(&self) -> Option<&Arc<dyn HistoryStorage>> {
+        self.history.as_ref()
+    }
+
+    /// Get the node storage used by this node manager, if any.
+    pub fn node_storage(&self) -> Option<&Arc<dyn NodeStorage>> {
+        self.storage.as_ref().map(|s| s.storage())
+    }
+
+    /// Set whether values of the node with ID `id` set through
+    /// [InMemoryNodeManager::set_values] are stored in the [NodeStorage].
+    ///
+    /// This is off by default, since values may change frequently, and each
+    /// change causes the node to be stored again.
+    pub fn set_value_persistence(&self, id: &NodeId, persist: bool) {
+        let mut persisted = trace_write_lock!(self.persisted_values);
+        if persist {
+            persisted.insert(id.clone());
+        } else {
+            persisted.remove(id);
+        }
+    }
+
+    /// Write any changes waiting to be stored in the [NodeStorage], blocking
+    /// until they are written. Use this to make sure all changes are stored
+    /// before shutting down the server.
+    pub fn flush_node_storage(&self) {
+        if let Some(storage) = &self.storage {
+            storage.flush();
+        }
+    }
+
     /// Return the inner [InMemoryNodeManagerImpl].
     pub fn inner(&self) -> &TImpl {
         &self.inner
@@ -119,6 +205,8 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
     /// Set the attributes given in `values` and notify any subscriptions
     /// about the changes.
     ///
+    /// If the node manager has a [NodeStorage], the changed nodes are stored.
+    ///
     /// To set values, use [InMemoryNodeManager::set_values].
     pub fn set_attributes<'a>(
         &self,
@@ -135,15 +223,17 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
 
             let node_mut = node.as_mut_node();
             node_mut.set_attribute(attribute_id, value)?;
-            // Don't notify on changes to event notifier, subscribing to that
-            // specific attribute means subscribing to events.
-            if attribute_id != AttributeId::EventNotifier {
-                output.push((id, attribute_id));
-            }
+            output.push((id, attribute_id));
         }
 
+        let write = self.queue_nodes(&address_space, output.iter().map(|(id, _)| (*id).clone()));
+
         subscriptions.maybe_notify(
-            output.into_iter(),
+            // Don't notify on changes to event notifier, subscribing to that
+            // specific attribute means subscribing to events.
+            output
+                .into_iter()
+                .filter(|(_, attribute_id)| *attribute_id != AttributeId::EventNotifier),
             |node_id, attribute_id, index_range, data_encoding| {
                 let node = address_space.find(node_id)?;
                 let node_ref = node.as_node();
@@ -156,6 +246,10 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 )
             },
         );
+        drop(address_space);
+        if write {
+            self.start_node_storage_write();
+        }
 
         Ok(())
     }
@@ -174,6 +268,10 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
 
     /// Set variable values with updates given by `values`, notifying any
     /// subscriptions of the changes.
+    ///
+    /// If the node manager has a [HistoryStorage], new values of variables
+    /// with `Historizing` set are recorded. If it has a [NodeStorage], the
+    /// changed nodes enabled with [InMemoryNodeManager::set_value_persistence] are stored.
     pub fn set_values<'a>(
         &self,
         subscriptions: &SubscriptionCache,
@@ -204,6 +302,20 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                     } else {
                         v.set_data_value(value)
                     }
+
+                    if let Some(history) = &self.history {
+                        if v.historizing() {
+                            let mut value = v.value(
+                                TimestampsToReturn::Both,
+                                &NumericRange::None,
+                                &DataEncoding::Binary,
+                                0.0,
+                            );
+                            value.server_timestamp.get_or_insert(now);
+                            value.source_timestamp.get_or_insert(now);
+                            history.record_value(id, value);
+                        }
+                    }
                 }
                 NodeType::VariableType(v) => v.set_value(value.value.unwrap_or_default()),
                 _ => return Err(StatusCode::BadAttributeIdInvalid),
@@ -212,6 +324,17 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
             output.push((id, AttributeId::Value));
         }
 
+        let write = self.storage.is_some() && {
+            let persisted = trace_read_lock!(self.persisted_values);
+            self.queue_nodes(
+                &address_space,
+                output
+                    .iter()
+                    .filter(|(id, _)| persisted.contains(*id))
+                    .map(|(id, _)| (*id).clone()),
+            )
+        };
+
         subscriptions.maybe_notify(
             output.into_iter(),
             |node_id, attribute_id, index_range, data_encoding| {
@@ -226,6 +349,10 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 )
             },
         );
+        drop(address_space);
+        if write {
+            self.start_node_storage_write();
+        }
 
         Ok(())
     }
@@ -242,6 +369,119 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
         self.set_values(subscriptions, [(id, index_range, value)].into_iter())
     }
 
+    /// Queue the current state of the nodes given by `ids` to be stored in the
+    /// [NodeStorage], if any. Nodes that no longer exist are stored as deleted, nodes
+    /// not owned by this node manager are ignored.
+    ///
+    /// This must be called with the address space locked, so that changes are stored
+    /// in order. Returns `true` if [InMemoryNodeManager::start_node_storage_write] must
+    /// be called once the lock is released.
+    fn queue_nodes(
+        &self,
+        address_space: &AddressSpace,
+        ids: impl IntoIterator<Item = NodeId>,
+    ) -> bool {
+        let Some(storage) = &self.storage else {
+            return false;
+        };
+        let mut seen = HashSet::new();
+        storage.queue(
+            ids.into_iter()
+                .filter(|id| self.owns_node(id) && seen.insert(id.clone()))
+                .map(
+                    |id| match StoredNode::from_address_space(address_space, &id) {
+                        Some(node) => NodeChange::Stored(node),
+                        None => NodeChange::Deleted(id),
+                    },
+                ),
+        )
+    }
+
+    fn start_node_storage_write(&self) {
+        if let Some(storage) = &self.storage {
+            storage.start_write();
+        }
+    }
+
+    /// Store the nodes given by `ids` after a service call, if the node manager has
+    /// a [NodeStorage].
+    fn store_changed_nodes(&self, ids: impl IntoIterator<Item = NodeId>) {
+        if self.storage.is_none() {
+            return;
+        }
+        let write = {
+            let address_space = trace_read_lock!(self.address_space);
+            self.queue_nodes(&address_space, ids)
+        };
+        if write {
+            self.start_node_storage_write();
+        }
+    }
+
+    /// Get the IDs of all nodes referencing or referenced by the nodes in `ids`,
+    /// if the node manager has a [NodeStorage]. These must be stored again when
+    /// the nodes in `ids` are deleted.
+    fn referenced_nodes<'a>(&self, ids: impl Iterator<Item = &'a NodeId>) -> Vec<NodeId> {
+        if self.storage.is_none() {
+            return Vec::new();
+        }
+        let address_space = trace_read_lock!(self.address_space);
+        ids.flat_map(|id| {
+            address_space
+                .node_references(id)
+                .map(|r| r.target_node.clone())
+        })
+        .collect()
+    }
+
+    /// Replay the changes stored in `storage` into `address_space`, using the
+    /// namespace indices in `namespaces`.
+    fn restore_nodes(
+        address_space: &mut AddressSpace,
+        storage: &NodeStorageWriter,
+        namespaces: &NamespaceMap,
+    ) {
+        let changes = match storage.load(namespaces) {
+            Ok(changes) => changes,
+            Err(e) => {
+                error!("Failed to load stored nodes: {e}");
+                return;
+            }
+        };
+        let changes: Vec<_> = changes
+            .into_iter()
+            .filter(|c| {
+                let known = address_space
+                    .namespaces()
+                    .contains_key(&c.node_id().namespace);
+                if !known {
+                    warn!(
+                        "Stored node {} is not in a namespace of this node manager",
+                        c.node_id()
+                    );
+                }
+                known
+            })
+            .collect();
+
+        // Remove all changed nodes first, so that changes can be applied in any order.
+        for change in &changes {
+            address_space.delete(change.node_id(), true);
+        }
+        for change in changes {
+            let NodeChange::Stored(node) = change else {
+                continue;
+            };
+            let node_id = node.node_id.clone();
+            match node.into_imported_item() {
+                Ok(item) => {
+                    address_space.import_node(item);
+                }
+                Err(e) => warn!("Failed to restore stored node {node_id}: {e}"),
+            }
+        }
+    }
+
     fn get_reference(
         address_space: &AddressSpace,
         type_tree: &DefaultTypeTree,
@@ -287,12 +527,27 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
     }
 
     /// Browses a single node, returns any external references found.
+    /// Nodes the user is not permitted to browse by `user_roles` are left out.
     fn browse_node(
         address_space: &AddressSpace,
         type_tree: &DefaultTypeTree,
         node: &mut BrowseNode,
         namespaces: &hashbrown::HashMap<u16, String>,
+        user_roles: &UserRoles,
     ) {
+        let can_browse = |n: &NodeType| {
+            user_roles
+                .permissions(n.as_node().role_permissions())
+                .contains(PermissionType::Browse)
+        };
+        if address_space
+            .find_node(node.node_id())
+            .is_some_and(|n| !can_browse(n))
+        {
+            node.set_status(StatusCode::BadNodeIdUnknown);
+            return;
+        }
+
         let reference_type_id = if node.reference_type_id().is_null() {
             None
         } else if let Ok(reference_type_id) = node.reference_type_id().as_reference_type_id() {
@@ -339,6 +594,10 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 continue;
             };
 
+            if !can_browse(target_node) {
+                continue;
+            }
+
             let r_node =
                 Self::get_reference(address_space, type_tree, target_node, node.result_mask());
 
@@ -484,6 +743,40 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
         valid
     }
 
+    /// Check the `Stepped` property of the historical configuration of a variable.
+    fn is_stepped(
+        address_space: &AddressSpace,
+        type_tree: &DefaultTypeTree,
+        node_id: &NodeId,
+    ) -> bool {
+        let stepped = address_space
+            .find_references(
+                node_id,
+                Some((ReferenceTypeId::HasHistoricalConfiguration, false)),
+                type_tree,
+                BrowseDirection::Forward,
+            )
+            .find_map(|r| {
+                address_space.find_node_by_browse_name(
+                    r.target_node,
+                    Some((ReferenceTypeId::HasProperty, false)),
+                    type_tree,
+                    BrowseDirection::Forward,
+                    "Stepped",
+                )
+            });
+        let Some(NodeType::Variable(stepped)) = stepped else {
+            return false;
+        };
+        let value = stepped.value(
+            TimestampsToReturn::Neither,
+            &NumericRange::None,
+            &DataEncoding::Binary,
+            0.0,
+        );
+        matches!(value.value, Some(Variant::Boolean(true)))
+    }
+
     fn validate_history_write_nodes<'a, 'b>(
         &self,
         context: &RequestContext,
@@ -536,6 +829,33 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
         valid
     }
 
+    /// Check whether `method` is a call to a method of a condition registered with the
+    /// server's `ConditionManager`. Conditions need not exist in any address space, so
+    /// the method must instead be a component of the condition type or one of its supertypes.
+    fn is_condition_method(
+        context: &RequestContext,
+        address_space: &AddressSpace,
+        type_tree: &dyn TypeTree,
+        method: &MethodCall,
+    ) -> bool {
+        let mut condition_type = context.info.conditions.condition_type(method.object_id());
+        while let Some(type_id) = condition_type {
+            if address_space
+                .find_references(
+                    &type_id,
+                    Some((ReferenceTypeId::HasComponent, false)),
+                    type_tree,
+                    BrowseDirection::Forward,
+                )
+                .any(|r| r.target_node == method.method_id())
+            {
+                return true;
+            }
+            condition_type = type_tree.get_supertype(&type_id).cloned();
+        }
+        false
+    }
+
     fn validate_method_calls<'a, 'b>(
         &self,
         context: &RequestContext,
@@ -546,20 +866,22 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
         let mut valid = Vec::with_capacity(methods.len());
 
         for method in methods {
-            let Some(method_ref) = address_space
+            let is_component = address_space
                 .find_references(
                     method.object_id(),
                     Some((ReferenceTypeId::HasComponent, false)),
                     &*type_tree,
                     BrowseDirection::Forward,
                 )
-                .find(|r| r.target_node == method.method_id())
-            else {
+                .any(|r| r.target_node == method.method_id());
+            if !is_component
+                && !Self::is_condition_method(context, &address_space, &*type_tree, method)
+            {
                 method.set_status(StatusCode::BadMethodInvalid);
                 continue;
-            };
+            }
 
-            let Some(NodeType::Method(method_node)) = address_space.find(method_ref.target_node)
+            let Some(node @ NodeType::Method(method_node)) = address_space.find(method.method_id())
             else {
                 method.set_status(StatusCode::BadMethodInvalid);
                 continue;
@@ -569,6 +891,11 @@ impl<TImpl: InMemoryNodeManagerImpl> InMemoryNodeManager<TImpl> {
                 || !context
                     .authenticator
                     .is_user_executable(&context.token, method.method_id())
+                || !context
+                    .info
+                    .roles
+                    .permissions(context, node)
+                    .contains(PermissionType::Call)
             {
                 method.set_status(StatusCode::BadUserAccessDenied);
                 continue;
@@ -667,6 +994,10 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
 
         self.inner.init(&mut address_space, context).await;
 
+        if let Some(storage) = &self.storage {
+            Self::restore_nodes(&mut address_space, storage, type_tree.namespaces());
+        }
+
         address_space.load_into_type_tree(type_tree);
     }
 
@@ -709,6 +1040,7 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
     ) -> Result<(), StatusCode> {
         let address_space = trace_read_lock!(self.address_space);
         let type_tree = trace_read_lock!(context.type_tree);
+        let user_roles = context.info.roles.user_roles(context);
 
         for node in nodes_to_browse.iter_mut() {
             if node.node_id().is_null() {
@@ -732,7 +1064,13 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
                     node.set_next_continuation_point(point);
                 }
             } else {
-                Self::browse_node(&address_space, &type_tree, node, &self.namespaces);
+                Self::browse_node(
+                    &address_space,
+                    &type_tree,
+                    node,
+                    &self.namespaces,
+                    &user_roles,
+                );
             }
         }
 
@@ -960,6 +1298,10 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         timestamps_to_return: TimestampsToReturn,
     ) -> Result<(), StatusCode> {
         let mut nodes = self.validate_history_read_nodes(context, nodes, false);
+        if let Some(history) = &self.history {
+            history::read_raw_modified(&**history, details, &mut nodes, timestamps_to_return);
+            return Ok(());
+        }
         self.inner
             .history_read_raw_modified(context, details, &mut nodes, timestamps_to_return)
             .await
@@ -973,6 +1315,18 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         timestamps_to_return: TimestampsToReturn,
     ) -> Result<(), StatusCode> {
         let mut nodes = self.validate_history_read_nodes(context, nodes, false);
+        if let Some(history) = &self.history {
+            let address_space = trace_read_lock!(self.address_space);
+            let type_tree = trace_read_lock!(context.type_tree);
+            history::read_processed(
+                &**history,
+                details,
+                &mut nodes,
+                timestamps_to_return,
+                |node_id| Self::is_stepped(&address_space, &type_tree, node_id),
+            );
+            return Ok(());
+        }
         self.inner
             .history_read_processed(context, details, &mut nodes, timestamps_to_return)
             .await
@@ -986,6 +1340,10 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         timestamps_to_return: TimestampsToReturn,
     ) -> Result<(), StatusCode> {
         let mut nodes = self.validate_history_read_nodes(context, nodes, false);
+        if let Some(history) = &self.history {
+            history::read_at_time(&**history, details, &mut nodes, timestamps_to_return);
+            return Ok(());
+        }
         self.inner
             .history_read_at_time(context, details, &mut nodes, timestamps_to_return)
             .await
@@ -1022,9 +1380,17 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         nodes_to_write: &mut [&mut WriteNode],
     ) -> Result<(), StatusCode> {
-        self.inner
+        let res = self
+            .inner
             .write(context, &self.address_space, nodes_to_write)
-            .await
+            .await;
+        self.store_changed_nodes(
+            nodes_to_write
+                .iter()
+                .filter(|n| n.status().is_good())
+                .map(|n| n.value().node_id.clone()),
+        );
+        res
     }
 
     async fn history_update(
@@ -1033,9 +1399,47 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         nodes: &mut [&mut HistoryUpdateNode],
     ) -> Result<(), StatusCode> {
         let mut nodes = self.validate_history_write_nodes(context, nodes);
+        if let Some(history) = &self.history {
+            history::update(&**history, context, &mut nodes);
+            return Ok(());
+        }
         self.inner.history_update(context, &mut nodes).await
     }
 
+    /// Evaluate the query against every node in the address space.
+    ///
+    /// Values are read directly from the address space, and only references between
+    /// nodes in this node manager are followed.
+    async fn query(
+        &self,
+        context: &RequestContext,
+        request: &mut QueryRequest,
+    ) -> Result<(), StatusCode> {
+        if request.resume_data_sets()? {
+            return Ok(());
+        }
+
+        let data_sets: Vec<_> = {
+            let address_space = trace_read_lock!(self.address_space);
+            let type_tree = trace_read_lock!(context.type_tree);
+            address_space
+                .nodes()
+                .filter(|node| self.owns_node(node.as_node().node_id()))
+                .filter_map(|node| {
+                    request.evaluate_node(AddressSpaceQueryNode {
+                        node,
+                        address_space: &address_space,
+                        type_tree: &type_tree,
+                        context,
+                    })
+                })
+                .collect()
+        };
+        request.add_data_sets(data_sets);
+
+        Ok(())
+    }
+
     async fn call(
         &self,
         context: &RequestContext,
@@ -1056,9 +1460,19 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         nodes_to_add: &mut [&mut AddNodeItem],
     ) -> Result<(), StatusCode> {
-        self.inner
+        let res = self
+            .inner
             .add_nodes(context, &self.address_space, nodes_to_add)
-            .await
+            .await;
+        let added: Vec<_> = nodes_to_add
+            .iter()
+            .filter(|n| n.status().is_good())
+            .map(|n| n.added_node_id().clone())
+            .collect();
+        // References to the new nodes are added to their parents as well.
+        let referenced = self.referenced_nodes(added.iter());
+        self.store_changed_nodes(added.into_iter().chain(referenced));
+        res
     }
 
     async fn add_references(
@@ -1066,9 +1480,22 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         references_to_add: &mut [&mut AddReferenceItem],
     ) -> Result<(), StatusCode> {
-        self.inner
+        let res = self
+            .inner
             .add_references(context, &self.address_space, references_to_add)
-            .await
+            .await;
+        self.store_changed_nodes(
+            references_to_add
+                .iter()
+                .filter(|r| r.result_status().is_good())
+                .flat_map(|r| {
+                    [
+                        r.source_node_id().clone(),
+                        r.target_node_id().node_id.clone(),
+                    ]
+                }),
+        );
+        res
     }
 
     async fn delete_nodes(
@@ -1076,9 +1503,19 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         nodes_to_delete: &mut [&mut DeleteNodeItem],
     ) -> Result<(), StatusCode> {
-        self.inner
+        let referenced = self.referenced_nodes(nodes_to_delete.iter().map(|n| n.node_id()));
+        let res = self
+            .inner
             .delete_nodes(context, &self.address_space, nodes_to_delete)
-            .await
+            .await;
+        self.store_changed_nodes(
+            nodes_to_delete
+                .iter()
+                .filter(|n| n.status().is_good())
+                .map(|n| n.node_id().clone())
+                .chain(referenced),
+        );
+        res
     }
 
     async fn delete_node_references(
@@ -1086,9 +1523,11 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         to_delete: &[&DeleteNodeItem],
     ) {
+        let referenced = self.referenced_nodes(to_delete.iter().map(|n| n.node_id()));
         self.inner
             .delete_node_references(context, &self.address_space, to_delete)
-            .await
+            .await;
+        self.store_changed_nodes(referenced);
     }
 
     async fn delete_references(
@@ -1096,8 +1535,21 @@ impl<TImpl: InMemoryNodeManagerImpl> NodeManager for InMemoryNodeManager<TImpl>
         context: &RequestContext,
         references_to_delete: &mut [&mut DeleteReferenceItem],
     ) -> Result<(), StatusCode> {
-        self.inner
+        let res = self
+            .inner
             .delete_references(context, &self.address_space, references_to_delete)
-            .await
+            .await;
+        self.store_changed_nodes(
+            references_to_delete
+                .iter()
+                .filter(|r| r.result_status().is_good())
+                .flat_map(|r| {
+                    [
+                        r.source_node_id().clone(),
+                        r.target_node_id().node_id.clone(),
+                    ]
+                }),
+        );
+        res
     }
 }
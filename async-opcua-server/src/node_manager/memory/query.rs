@@ -0,0 +1,124 @@
+use opcua_nodes::{AttributeQueryable, TypeTree};
+use opcua_types::{
+    AttributeId, BrowseDirection, DataEncoding, NodeClass, NodeId, NumericRange, QualifiedName,
+    ReferenceTypeId, TimestampsToReturn, Variant,
+};
+
+use crate::{
+    address_space::{read_node_value, validate_node_read, AddressSpace, NodeType},
+    node_manager::{DefaultTypeTree, ParsedReadValueId, QueryNode, RequestContext},
+};
+
+/// Reference to a node in an [AddressSpace], used to evaluate queries.
+#[derive(Clone, Copy)]
+pub(super) struct AddressSpaceQueryNode<'a> {
+    pub node: &'a NodeType,
+    pub address_space: &'a AddressSpace,
+    pub type_tree: &'a DefaultTypeTree,
+    pub context: &'a RequestContext,
+}
+
+impl AttributeQueryable for AddressSpaceQueryNode<'_> {
+    fn get_attribute(
+        &self,
+        type_definition_id: &NodeId,
+        browse_path: &[QualifiedName],
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+    ) -> Variant {
+        self.query_attribute(type_definition_id, browse_path, attribute_id, index_range)
+    }
+
+    fn is_of_type(&self, type_definition_id: &NodeId, include_subtypes: bool) -> bool {
+        self.type_definition().is_some_and(|t| {
+            if include_subtypes {
+                self.type_tree.is_subtype_of(&t, type_definition_id)
+            } else {
+                &t == type_definition_id
+            }
+        })
+    }
+
+    fn get_related(&self, reference_type_id: &NodeId, include_subtypes: bool) -> Vec<Self> {
+        self.references(reference_type_id, include_subtypes, false)
+    }
+}
+
+impl QueryNode for AddressSpaceQueryNode<'_> {
+    fn node_id(&self) -> NodeId {
+        self.node.as_node().node_id().clone()
+    }
+
+    fn browse_name(&self) -> QualifiedName {
+        self.node.as_node().browse_name().clone()
+    }
+
+    fn type_definition(&self) -> Option<NodeId> {
+        // Only objects and variables have type definitions.
+        if !matches!(
+            self.node.as_node().node_class(),
+            NodeClass::Object | NodeClass::Variable
+        ) {
+            return None;
+        }
+        self.address_space
+            .find_references(
+                self.node.as_node().node_id(),
+                Some((ReferenceTypeId::HasTypeDefinition, false)),
+                self.type_tree,
+                BrowseDirection::Forward,
+            )
+            .next()
+            .map(|r| r.target_node.clone())
+    }
+
+    fn references(
+        &self,
+        reference_type_id: &NodeId,
+        include_subtypes: bool,
+        is_inverse: bool,
+    ) -> Vec<Self> {
+        let filter = if reference_type_id.is_null() {
+            None
+        } else {
+            Some((reference_type_id.clone(), include_subtypes))
+        };
+        self.address_space
+            .find_references(
+                self.node.as_node().node_id(),
+                filter,
+                self.type_tree,
+                if is_inverse {
+                    BrowseDirection::Inverse
+                } else {
+                    BrowseDirection::Forward
+                },
+            )
+            .filter_map(|r| self.address_space.find_node(r.target_node))
+            .map(|node| Self { node, ..*self })
+            .collect()
+    }
+
+    fn read_attribute(&self, attribute_id: AttributeId, index_range: &NumericRange) -> Variant {
+        let node_to_read = ParsedReadValueId {
+            node_id: self.node_id(),
+            attribute_id,
+            index_range: index_range.clone(),
+            data_encoding: DataEncoding::Binary,
+        };
+        if validate_node_read(self.node, self.context, &node_to_read).is_err() {
+            return Variant::Empty;
+        }
+        let value = read_node_value(
+            self.node,
+            self.context,
+            &node_to_read,
+            0.0,
+            TimestampsToReturn::Neither,
+        );
+        if value.status.is_some_and(|s| s.is_bad()) {
+            return Variant::Empty;
+        }
+        value.value.unwrap_or_default()
+    }
+}
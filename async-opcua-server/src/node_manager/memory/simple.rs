@@ -1,18 +1,28 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use async_trait::async_trait;
+use futures::future::{join_all, BoxFuture};
 use opcua_core::{trace_read_lock, trace_write_lock};
-use opcua_nodes::{HasNodeId, NodeSetImport};
+use opcua_nodes::{HasNodeId, NodeSetImport, NodeType};
 
 use crate::{
     address_space::{read_node_value, write_node_value, AddressSpace},
     node_manager::{
         DefaultTypeTree, MethodCall, MonitoredItemRef, MonitoredItemUpdateRef, NodeManagerBuilder,
-        NodeManagersRef, ParsedReadValueId, RequestContext, ServerContext, SyncSampler, WriteNode,
+        NodeManagersRef, ParsedReadValueId, RequestContext, ServerContext, SyncSampler,
+        TypedMethod, WriteNode,
     },
     CreateMonitoredItem,
 };
-use opcua_core::sync::RwLock;
+use opcua_core::sync::{Mutex, RwLock};
 use opcua_types::{
     AttributeId, DataValue, MonitoringMode, NodeClass, NodeId, NumericRange, StatusCode,
     TimestampsToReturn, Variant,
@@ -35,6 +45,48 @@ type ReadCB = Arc<
         + 'static,
 >;
 type MethodCB = Arc<dyn Fn(&[Variant]) -> Result<Vec<Variant>, StatusCode> + Send + Sync + 'static>;
+type AsyncWriteCB = Arc<
+    dyn Fn(RequestContext, DataValue, NumericRange) -> BoxFuture<'static, StatusCode>
+        + Send
+        + Sync
+        + 'static,
+>;
+type AsyncReadCB = Arc<
+    dyn Fn(
+            RequestContext,
+            NumericRange,
+            TimestampsToReturn,
+            f64,
+        ) -> BoxFuture<'static, Result<DataValue, StatusCode>>
+        + Send
+        + Sync
+        + 'static,
+>;
+type AsyncMethodCB = Arc<
+    dyn Fn(RequestContext, Vec<Variant>) -> BoxFuture<'static, Result<Vec<Variant>, StatusCode>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+#[derive(Clone)]
+enum Callback<TSync, TAsync> {
+    Sync(TSync),
+    Async(TAsync),
+}
+
+#[derive(Clone)]
+enum MethodCallback {
+    Sync(MethodCB),
+    Async(AsyncMethodCB),
+    Typed(Arc<TypedMethod>),
+}
+
+/// Result of reading a value, which may need to be completed by an async callback.
+enum ReadValue {
+    Done(DataValue),
+    Async(AsyncReadCB),
+}
 
 /// Builder for the [SimpleNodeManager].
 pub struct SimpleNodeManagerBuilder {
@@ -109,17 +161,21 @@ pub fn simple_node_manager_imports(
     InMemoryNodeManagerBuilder::new(SimpleNodeManagerBuilder::new_imports(imports, name))
 }
 
-/// Node manager designed to deal with simple, entirely in-memory, synchronous OPC-UA servers.
+/// Node manager designed to deal with simple, entirely in-memory OPC-UA servers.
 ///
 /// Use this if
 ///
 ///  - Your node hierarchy is known and small enough to fit in memory.
-///  - No read, write, or method call operations are async or particularly time consuming.
 ///  - and you don't need to be able to write attributes other than `Value`.
+///
+/// Values and methods can be backed by callbacks. Synchronous callbacks are called
+/// while holding the lock on the address space, so they should be fast. Async callbacks
+/// receive the [RequestContext], and are called after the lock has been released,
+/// so they may perform I/O, for example to communicate with a device.
 pub struct SimpleNodeManagerImpl {
-    write_cbs: RwLock<HashMap<NodeId, WriteCB>>,
-    read_cbs: RwLock<HashMap<NodeId, ReadCB>>,
-    method_cbs: RwLock<HashMap<NodeId, MethodCB>>,
+    write_cbs: RwLock<HashMap<NodeId, Callback<WriteCB, AsyncWriteCB>>>,
+    read_cbs: RwLock<HashMap<NodeId, Callback<ReadCB, AsyncReadCB>>>,
+    method_cbs: RwLock<HashMap<NodeId, MethodCallback>>,
     namespaces: Vec<NamespaceMetadata>,
     #[allow(unused)]
     node_managers: NodeManagersRef,
@@ -159,22 +215,48 @@ impl InMemoryNodeManagerImpl for SimpleNodeManagerImpl {
         max_age: f64,
         timestamps_to_return: TimestampsToReturn,
     ) -> Vec<DataValue> {
-        let address_space = address_space.read();
-        let cbs = trace_read_lock!(self.read_cbs);
+        let mut results = Vec::with_capacity(nodes.len());
+        let mut pending = Vec::new();
+        {
+            let address_space = address_space.read();
+            let cbs = trace_read_lock!(self.read_cbs);
 
-        nodes
-            .iter()
-            .map(|n| {
-                self.read_node_value(
+            for (idx, node) in nodes.iter().enumerate() {
+                match self.read_node_value(
                     &cbs,
                     context,
                     &address_space,
-                    n,
+                    node,
                     max_age,
                     timestamps_to_return,
-                )
-            })
-            .collect()
+                ) {
+                    ReadValue::Done(v) => results.push(v),
+                    ReadValue::Async(cb) => {
+                        results.push(DataValue::null());
+                        pending.push((idx, cb));
+                    }
+                }
+            }
+        }
+
+        // Async callbacks are called after releasing the lock on the address space.
+        let futures = pending.into_iter().map(|(idx, cb)| {
+            let fut = cb(
+                context.clone(),
+                nodes[idx].index_range.clone(),
+                timestamps_to_return,
+                max_age,
+            );
+            async move { (idx, fut.await) }
+        });
+        for (idx, res) in join_all(futures).await {
+            results[idx] = res.unwrap_or_else(|e| DataValue {
+                status: Some(e),
+                ..Default::default()
+            });
+        }
+
+        results
     }
 
     async fn create_value_monitored_items(
@@ -203,27 +285,60 @@ impl InMemoryNodeManagerImpl for SimpleNodeManagerImpl {
             node.set_status(StatusCode::Good);
             let rf = &node.item_to_monitor().node_id;
 
-            if let Some(cb) = cbs.get(rf).cloned() {
-                let tss = node.timestamps_to_return();
-                let index_range = node.item_to_monitor().index_range.clone();
-
-                self.samplers.add_sampler(
-                    node.item_to_monitor().node_id.clone(),
-                    AttributeId::Value,
-                    move || {
-                        Some(match cb(&index_range, tss, 0.0) {
-                            Err(e) => DataValue {
+            let Some(cb) = cbs.get(rf).cloned() else {
+                continue;
+            };
+            let tss = node.timestamps_to_return();
+            let index_range = node.item_to_monitor().index_range.clone();
+
+            let sampler: Box<dyn FnMut() -> Option<DataValue> + Send> = match cb {
+                Callback::Sync(cb) => Box::new(move || {
+                    Some(match cb(&index_range, tss, 0.0) {
+                        Err(e) => DataValue {
+                            status: Some(e),
+                            ..Default::default()
+                        },
+                        Ok(v) => v,
+                    })
+                }),
+                Callback::Async(cb) => {
+                    // Async callbacks are sampled in a background task. The sampler returns
+                    // `None` until the sample completes, so it is retried on the next tick.
+                    // Skip starting new samples while the last one is still running.
+                    let context = context.clone();
+                    let running = Arc::new(AtomicBool::new(false));
+                    let result = Arc::new(Mutex::new(None));
+                    Box::new(move || {
+                        if let Some(value) = result.lock().take() {
+                            return Some(value);
+                        }
+                        if running.swap(true, Ordering::AcqRel) {
+                            return None;
+                        }
+                        let fut = cb(context.clone(), index_range.clone(), tss, 0.0);
+                        let running = running.clone();
+                        let result = result.clone();
+                        tokio::spawn(async move {
+                            let value = fut.await.unwrap_or_else(|e| DataValue {
                                 status: Some(e),
                                 ..Default::default()
-                            },
-                            Ok(v) => v,
-                        })
-                    },
-                    node.monitoring_mode(),
-                    node.handle(),
-                    Duration::from_millis(node.sampling_interval() as u64),
-                )
-            }
+                            });
+                            *result.lock() = Some(value);
+                            running.store(false, Ordering::Release);
+                        });
+                        None
+                    })
+                }
+            };
+
+            self.samplers.add_sampler(
+                rf.clone(),
+                AttributeId::Value,
+                sampler,
+                node.monitoring_mode(),
+                node.handle(),
+                Duration::from_millis(node.sampling_interval() as u64),
+            )
         }
     }
 
@@ -267,12 +382,45 @@ impl InMemoryNodeManagerImpl for SimpleNodeManagerImpl {
         address_space: &RwLock<AddressSpace>,
         nodes_to_write: &mut [&mut WriteNode],
     ) -> Result<(), StatusCode> {
-        let mut address_space = trace_write_lock!(address_space);
-        let type_tree = trace_read_lock!(context.type_tree);
-        let cbs = trace_read_lock!(self.write_cbs);
+        let mut pending = Vec::new();
+        {
+            let mut address_space = trace_write_lock!(address_space);
+            let type_tree = trace_read_lock!(context.type_tree);
+            let cbs = trace_read_lock!(self.write_cbs);
+
+            for (idx, write) in nodes_to_write.iter_mut().enumerate() {
+                if let Some(cb) =
+                    self.write_node_value(&cbs, context, &mut address_space, &type_tree, write)
+                {
+                    pending.push((idx, cb));
+                }
+            }
+        }
+        if pending.is_empty() {
+            return Ok(());
+        }
 
-        for write in nodes_to_write {
-            self.write_node_value(&cbs, context, &mut address_space, &type_tree, write);
+        // Async callbacks are called after releasing the lock on the address space.
+        let futures = pending.into_iter().map(|(idx, cb)| {
+            let value = nodes_to_write[idx].value();
+            let fut = cb(
+                context.clone(),
+                value.value.clone(),
+                value.index_range.clone(),
+            );
+            async move { (idx, fut.await) }
+        });
+        let results = join_all(futures).await;
+
+        let address_space = trace_read_lock!(address_space);
+        for (idx, status) in results {
+            let write = &mut nodes_to_write[idx];
+            write.set_status(status);
+            if status.is_good() {
+                if let Some(node) = address_space.find(&write.value().node_id) {
+                    Self::notify_value_change(context, node, write);
+                }
+            }
         }
 
         Ok(())
@@ -280,23 +428,46 @@ impl InMemoryNodeManagerImpl for SimpleNodeManagerImpl {
 
     async fn call(
         &self,
-        _context: &RequestContext,
+        context: &RequestContext,
         _address_space: &RwLock<AddressSpace>,
         methods_to_call: &mut [&mut &mut MethodCall],
     ) -> Result<(), StatusCode> {
-        let cbs = trace_read_lock!(self.method_cbs);
-        for method in methods_to_call {
-            if let Some(cb) = cbs.get(method.method_id()) {
-                match cb(method.arguments()) {
-                    Ok(r) => {
-                        method.set_outputs(r);
-                        method.set_status(StatusCode::Good);
-                    }
-                    Err(e) => method.set_status(e),
+        let mut pending = Vec::new();
+        {
+            let cbs = trace_read_lock!(self.method_cbs);
+            for method in methods_to_call.iter_mut() {
+                match cbs.get(method.method_id()) {
+                    Some(MethodCallback::Sync(cb)) => match cb(method.arguments()) {
+                        Ok(r) => {
+                            method.set_outputs(r);
+                            method.set_status(StatusCode::Good);
+                        }
+                        Err(e) => method.set_status(e),
+                    },
+                    Some(cb) => pending.push((method, cb.clone())),
+                    None => (),
                 }
             }
         }
 
+        // Async and typed methods are called after releasing the locks.
+        let futures = pending.into_iter().map(|(method, cb)| async move {
+            match cb {
+                MethodCallback::Async(cb) => {
+                    match cb(context.clone(), method.arguments().to_vec()).await {
+                        Ok(r) => {
+                            method.set_outputs(r);
+                            method.set_status(StatusCode::Good);
+                        }
+                        Err(e) => method.set_status(e),
+                    }
+                }
+                MethodCallback::Typed(typed_method) => typed_method.call(method).await,
+                MethodCallback::Sync(_) => (),
+            }
+        });
+        join_all(futures).await;
+
         Ok(())
     }
 }
@@ -316,51 +487,62 @@ impl SimpleNodeManagerImpl {
 
     fn read_node_value(
         &self,
-        cbs: &HashMap<NodeId, ReadCB>,
+        cbs: &HashMap<NodeId, Callback<ReadCB, AsyncReadCB>>,
         context: &RequestContext,
         address_space: &AddressSpace,
         node_to_read: &ParsedReadValueId,
         max_age: f64,
         timestamps_to_return: TimestampsToReturn,
-    ) -> DataValue {
+    ) -> ReadValue {
         let mut result_value = DataValue::null();
         // Check that the read is permitted.
         let node = match address_space.validate_node_read(context, node_to_read) {
             Ok(n) => n,
             Err(e) => {
                 result_value.status = Some(e);
-                return result_value;
+                return ReadValue::Done(result_value);
             }
         };
 
         // If there is a callback registered, call that, otherwise read it from the node hierarchy.
-        if let Some(cb) = cbs.get(&node_to_read.node_id) {
-            match cb(&node_to_read.index_range, timestamps_to_return, max_age) {
-                Err(e) => DataValue {
-                    status: Some(e),
-                    ..Default::default()
+        match cbs.get(&node_to_read.node_id) {
+            Some(Callback::Sync(cb)) => ReadValue::Done(
+                match cb(&node_to_read.index_range, timestamps_to_return, max_age) {
+                    Err(e) => DataValue {
+                        status: Some(e),
+                        ..Default::default()
+                    },
+                    Ok(v) => v,
                 },
-                Ok(v) => v,
+            ),
+            Some(Callback::Async(cb)) if node_to_read.attribute_id == AttributeId::Value => {
+                ReadValue::Async(cb.clone())
             }
-        } else {
             // If it can't be found, read it from the node hierarchy.
-            read_node_value(node, context, node_to_read, max_age, timestamps_to_return)
+            _ => ReadValue::Done(read_node_value(
+                node,
+                context,
+                node_to_read,
+                max_age,
+                timestamps_to_return,
+            )),
         }
     }
 
+    /// Write a value, returning the async callback to call if the node has one.
     fn write_node_value(
         &self,
-        cbs: &HashMap<NodeId, WriteCB>,
+        cbs: &HashMap<NodeId, Callback<WriteCB, AsyncWriteCB>>,
         context: &RequestContext,
         address_space: &mut AddressSpace,
         type_tree: &DefaultTypeTree,
         write: &mut WriteNode,
-    ) {
+    ) -> Option<AsyncWriteCB> {
         let node = match address_space.validate_node_write(context, write.value(), type_tree) {
             Ok(v) => v,
             Err(e) => {
                 write.set_status(e);
-                return;
+                return None;
             }
         };
 
@@ -368,10 +550,14 @@ impl SimpleNodeManagerImpl {
             || write.value().attribute_id != AttributeId::Value
         {
             write.set_status(StatusCode::BadNotWritable);
-            return;
+            return None;
         }
 
-        if let Some(cb) = cbs.get(node.as_node().node_id()) {
+        let cb = cbs.get(node.as_node().node_id());
+        if let Some(Callback::Async(cb)) = cb {
+            return Some(cb.clone());
+        }
+        if let Some(Callback::Sync(cb)) = cb {
             // If there is a callback registered, call that.
             write.set_status(cb(write.value().value.clone(), &write.value().index_range));
         } else if write.value().value.value.is_some() {
@@ -385,16 +571,21 @@ impl SimpleNodeManagerImpl {
             write.set_status(StatusCode::BadNothingToDo);
         }
         if write.status().is_good() {
-            if let Some(val) = node.as_mut_node().get_attribute(
-                TimestampsToReturn::Both,
-                write.value().attribute_id,
-                &NumericRange::None,
-                &opcua_types::DataEncoding::Binary,
-            ) {
-                context.subscriptions.notify_data_change(
-                    [(val, node.node_id(), write.value().attribute_id)].into_iter(),
-                );
-            }
+            Self::notify_value_change(context, node, write);
+        }
+        None
+    }
+
+    fn notify_value_change(context: &RequestContext, node: &NodeType, write: &WriteNode) {
+        if let Some(val) = node.as_node().get_attribute(
+            TimestampsToReturn::Both,
+            write.value().attribute_id,
+            &NumericRange::None,
+            &opcua_types::DataEncoding::Binary,
+        ) {
+            context.subscriptions.notify_data_change(
+                [(val, node.node_id(), write.value().attribute_id)].into_iter(),
+            );
         }
     }
 
@@ -405,7 +596,26 @@ impl SimpleNodeManagerImpl {
         cb: impl Fn(DataValue, &NumericRange) -> StatusCode + Send + Sync + 'static,
     ) {
         let mut cbs = trace_write_lock!(self.write_cbs);
-        cbs.insert(id, Arc::new(cb));
+        cbs.insert(id, Callback::Sync(Arc::new(cb)));
+    }
+
+    /// Add an async callback called on `Write` for the node given by `id`.
+    ///
+    /// The callback is called after the lock on the address space has been released.
+    pub fn add_async_write_callback<Fut>(
+        &self,
+        id: NodeId,
+        cb: impl Fn(RequestContext, DataValue, NumericRange) -> Fut + Send + Sync + 'static,
+    ) where
+        Fut: Future<Output = StatusCode> + Send + 'static,
+    {
+        let mut cbs = trace_write_lock!(self.write_cbs);
+        cbs.insert(
+            id,
+            Callback::Async(Arc::new(move |ctx, value, range| {
+                Box::pin(cb(ctx, value, range))
+            })),
+        );
     }
 
     /// Add a callback for `Read` on the node given by `id`.
@@ -418,7 +628,30 @@ impl SimpleNodeManagerImpl {
             + 'static,
     ) {
         let mut cbs = trace_write_lock!(self.read_cbs);
-        cbs.insert(id, Arc::new(cb));
+        cbs.insert(id, Callback::Sync(Arc::new(cb)));
+    }
+
+    /// Add an async callback for `Read` of the value of the node given by `id`.
+    ///
+    /// The callback is called after the lock on the address space has been released.
+    /// Monitored items on the node are sampled by calling the callback in a background task.
+    pub fn add_async_read_callback<Fut>(
+        &self,
+        id: NodeId,
+        cb: impl Fn(RequestContext, NumericRange, TimestampsToReturn, f64) -> Fut
+            + Send
+            + Sync
+            + 'static,
+    ) where
+        Fut: Future<Output = Result<DataValue, StatusCode>> + Send + 'static,
+    {
+        let mut cbs = trace_write_lock!(self.read_cbs);
+        cbs.insert(
+            id,
+            Callback::Async(Arc::new(move |ctx, range, tss, max_age| {
+                Box::pin(cb(ctx, range, tss, max_age))
+            })),
+        );
     }
 
     /// Add a callback for `Call` on the method given by `id`.
@@ -428,6 +661,33 @@ impl SimpleNodeManagerImpl {
         cb: impl Fn(&[Variant]) -> Result<Vec<Variant>, StatusCode> + Send + Sync + 'static,
     ) {
         let mut cbs = trace_write_lock!(self.method_cbs);
-        cbs.insert(id, Arc::new(cb));
+        cbs.insert(id, MethodCallback::Sync(Arc::new(cb)));
+    }
+
+    /// Add an async callback for `Call` on the method given by `id`.
+    ///
+    /// The callback is called after the lock on the address space has been released.
+    pub fn add_async_method_callback<Fut>(
+        &self,
+        id: NodeId,
+        cb: impl Fn(RequestContext, Vec<Variant>) -> Fut + Send + Sync + 'static,
+    ) where
+        Fut: Future<Output = Result<Vec<Variant>, StatusCode>> + Send + 'static,
+    {
+        let mut cbs = trace_write_lock!(self.method_cbs);
+        cbs.insert(
+            id,
+            MethodCallback::Async(Arc::new(move |ctx, args| Box::pin(cb(ctx, args)))),
+        );
+    }
+
+    /// Add a [TypedMethod] called on `Call` for the method given by `id`.
+    ///
+    /// Use [TypedMethod::input_arguments] and [TypedMethod::output_arguments]
+    /// to create the `InputArguments` and `OutputArguments` properties
+    /// of the method node.
+    pub fn add_typed_method(&self, id: NodeId, method: TypedMethod) {
+        let mut cbs = trace_write_lock!(self.method_cbs);
+        cbs.insert(id, MethodCallback::Typed(Arc::new(method)));
     }
 }
@@ -0,0 +1,778 @@
+//! Persistent storage of nodes for the [InMemoryNodeManager](super::InMemoryNodeManager).
+//!
+//! The node manager records changes made through the node management services, the
+//! `Write` service, and [InMemoryNodeManager::set_attributes](super::InMemoryNodeManager::set_attributes)
+//! in a [NodeStorage], and replays the stored changes into its address space on startup.
+//! Values set through [InMemoryNodeManager::set_values](super::InMemoryNodeManager::set_values)
+//! are only recorded for variables opted in with
+//! [InMemoryNodeManager::set_value_persistence](super::InMemoryNodeManager::set_value_persistence).
+
+use std::{
+    fs::File,
+    io::{Cursor, Read, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use hashbrown::HashMap;
+use log::{error, warn};
+use opcua_core::{
+    sync::{Mutex, RwLock},
+    trace_lock, trace_read_lock, trace_write_lock,
+};
+use opcua_nodes::{ImportedItem, ImportedReference};
+use opcua_types::{
+    read_i32, read_u16, read_u32, write_i32, write_u16, write_u32, AttributeId, BinaryDecodable,
+    BinaryEncodable, Context, ContextOwned, DataEncoding, DataValue, EncodingResult, Error,
+    NamespaceMap, NodeClass, NodeId, NumericRange, StatusCode, TimestampsToReturn, TypeLoader,
+    UAString, Variant,
+};
+
+use crate::address_space::{AddressSpace, NodeType, ReferenceDirection};
+
+/// A snapshot of a node and all its references.
+#[derive(Debug, Clone)]
+pub struct StoredNode {
+    /// ID of the node.
+    pub node_id: NodeId,
+    /// Node class of the node.
+    pub node_class: NodeClass,
+    /// All attributes of the node except `NodeId` and `NodeClass`. For variables,
+    /// the `Value` attribute includes the timestamps and status of the value.
+    pub attributes: Vec<(AttributeId, DataValue)>,
+    /// References from or to the node.
+    pub references: Vec<ImportedReference>,
+}
+
+impl StoredNode {
+    /// Take a snapshot of the node with ID `node_id` in `address_space`,
+    /// returns `None` if the node does not exist.
+    pub fn from_address_space(address_space: &AddressSpace, node_id: &NodeId) -> Option<Self> {
+        let node = address_space.find(node_id)?.as_node();
+        let attributes = (AttributeId::BrowseName as u32..=AttributeId::AccessLevelEx as u32)
+            .filter_map(|id| AttributeId::from_u32(id).ok())
+            .filter_map(|id| {
+                let value = node.get_attribute(
+                    TimestampsToReturn::Both,
+                    id,
+                    &NumericRange::None,
+                    &DataEncoding::Binary,
+                )?;
+                Some((id, value))
+            })
+            .collect();
+        let references = address_space
+            .node_references(node_id)
+            .map(|r| ImportedReference {
+                target_id: r.target_node.clone(),
+                type_id: r.reference_type.clone(),
+                is_forward: matches!(r.direction, ReferenceDirection::Forward),
+            })
+            .collect();
+
+        Some(Self {
+            node_id: node_id.clone(),
+            node_class: node.node_class(),
+            attributes,
+            references,
+        })
+    }
+
+    /// Create a node from the stored attributes, along with its references.
+    pub fn into_imported_item(self) -> Result<ImportedItem, StatusCode> {
+        let mut node = match self.node_class {
+            NodeClass::Object => NodeType::Object(Box::default()),
+            NodeClass::Variable => NodeType::Variable(Box::default()),
+            NodeClass::Method => NodeType::Method(Box::default()),
+            NodeClass::ObjectType => NodeType::ObjectType(Box::default()),
+            NodeClass::VariableType => NodeType::VariableType(Box::default()),
+            NodeClass::ReferenceType => NodeType::ReferenceType(Box::default()),
+            NodeClass::DataType => NodeType::DataType(Box::default()),
+            NodeClass::View => NodeType::View(Box::default()),
+            NodeClass::Unspecified => return Err(StatusCode::BadNodeClassInvalid),
+        };
+        node.as_mut_node()
+            .set_attribute(AttributeId::NodeId, self.node_id.into())?;
+
+        for (id, value) in self.attributes {
+            match (&mut node, id) {
+                (NodeType::Variable(v), AttributeId::Value) => v.set_data_value(value),
+                _ => node
+                    .as_mut_node()
+                    .set_attribute(id, value.value.unwrap_or_default())?,
+            }
+        }
+
+        Ok(ImportedItem {
+            node,
+            references: self.references,
+        })
+    }
+}
+
+/// A change to a single node, as recorded in a [NodeStorage].
+#[derive(Debug, Clone)]
+pub enum NodeChange {
+    /// The node was created or modified, and now looks like the given snapshot.
+    Stored(StoredNode),
+    /// The node was deleted.
+    Deleted(NodeId),
+}
+
+impl NodeChange {
+    /// Get the ID of the changed node.
+    pub fn node_id(&self) -> &NodeId {
+        match self {
+            NodeChange::Stored(n) => &n.node_id,
+            NodeChange::Deleted(id) => id,
+        }
+    }
+}
+
+/// Trait for a backend persisting changes to the nodes of an
+/// [InMemoryNodeManager](super::InMemoryNodeManager).
+///
+/// Only the latest change to each node needs to be kept. When a node changes,
+/// every node it references, or used to reference, in the same node manager is
+/// stored again as well, so the stored changes can be replayed in any order.
+///
+/// Changes are stored from a blocking thread, outside of the address space lock.
+/// Calls to `store` are never made concurrently, and changes made while a call is
+/// in progress are passed together in the next call. If `store` fails, its changes
+/// are passed again in the next call.
+///
+/// Namespace indices are assigned when the server starts, so they may differ between
+/// runs. Both methods receive the namespaces of the server, so that a storage can keep
+/// the namespace URIs of the stored nodes and map them to the current indices on load.
+pub trait NodeStorage: Send + Sync + 'static {
+    /// Load the latest change to each stored node, using the namespace indices in
+    /// `namespaces`. Called once when the node manager is initialized.
+    fn load(&self, namespaces: &NamespaceMap) -> Result<Vec<NodeChange>, StatusCode>;
+
+    /// Store a list of changes, replacing any earlier changes to the same nodes.
+    /// The namespace indices of the changes are the ones in `namespaces`.
+    fn store(&self, changes: Vec<NodeChange>, namespaces: &NamespaceMap) -> Result<(), StatusCode>;
+}
+
+#[derive(Default)]
+struct PendingChanges {
+    changes: HashMap<NodeId, NodeChange>,
+    scheduled: bool,
+}
+
+/// Queue of changes waiting to be written to a [NodeStorage].
+///
+/// Changes are queued while the address space is locked, so that they are stored in
+/// the order they were made, and written on a blocking thread once the lock is released.
+pub(super) struct NodeStorageWriter {
+    storage: Arc<dyn NodeStorage>,
+    namespaces: RwLock<NamespaceMap>,
+    pending: Mutex<PendingChanges>,
+    write: Mutex<()>,
+}
+
+impl NodeStorageWriter {
+    pub(super) fn new(storage: Arc<dyn NodeStorage>) -> Self {
+        Self {
+            storage,
+            namespaces: Default::default(),
+            pending: Default::default(),
+            write: Mutex::new(()),
+        }
+    }
+
+    pub(super) fn storage(&self) -> &Arc<dyn NodeStorage> {
+        &self.storage
+    }
+
+    /// Load the stored changes, and keep `namespaces` to pass to the storage
+    /// with later changes.
+    pub(super) fn load(&self, namespaces: &NamespaceMap) -> Result<Vec<NodeChange>, StatusCode> {
+        *trace_write_lock!(self.namespaces) = namespaces.clone();
+        self.storage.load(namespaces)
+    }
+
+    /// Queue a list of changes, replacing any queued changes to the same nodes.
+    /// Returns `true` if a write must be started with [NodeStorageWriter::start_write].
+    pub(super) fn queue(&self, changes: impl IntoIterator<Item = NodeChange>) -> bool {
+        let mut pending = trace_lock!(self.pending);
+        for change in changes {
+            pending.changes.insert(change.node_id().clone(), change);
+        }
+        if pending.scheduled || pending.changes.is_empty() {
+            return false;
+        }
+        pending.scheduled = true;
+        true
+    }
+
+    /// Write the queued changes on a blocking thread. Outside of a Tokio runtime,
+    /// they are written immediately instead.
+    pub(super) fn start_write(self: &Arc<Self>) {
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let writer = self.clone();
+                runtime.spawn_blocking(move || writer.flush());
+            }
+            Err(_) => self.flush(),
+        }
+    }
+
+    /// Write all queued changes to the storage, waiting for any write in progress.
+    /// If the write fails, the changes are queued again, to be written with the next
+    /// change or flush.
+    pub(super) fn flush(&self) {
+        let _write = trace_lock!(self.write);
+        let changes: Vec<_> = {
+            let mut pending = trace_lock!(self.pending);
+            pending.scheduled = false;
+            std::mem::take(&mut pending.changes).into_values().collect()
+        };
+        if changes.is_empty() {
+            return;
+        }
+        let namespaces = trace_read_lock!(self.namespaces);
+        if let Err(e) = self.storage.store(changes.clone(), &namespaces) {
+            error!("Failed to store changed nodes, they are kept for the next write: {e}");
+            // Nodes changed again since are queued with their latest state.
+            let mut pending = trace_lock!(self.pending);
+            for change in changes {
+                pending
+                    .changes
+                    .entry(change.node_id().clone())
+                    .or_insert(change);
+            }
+        }
+    }
+}
+
+/// Version of the file format used by [FileNodeStorage]. Version 1 files
+/// have no namespace table.
+const FILE_FORMAT_VERSION: u32 = 2;
+
+/// Map the namespace index of `id` with `index_map`, returns `false` if the
+/// namespace is unknown.
+fn remap_node_id(id: &mut NodeId, index_map: &HashMap<u16, u16>) -> bool {
+    match index_map.get(&id.namespace) {
+        Some(ns) => {
+            id.namespace = *ns;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Map the namespace indices of a stored node with `index_map`. Returns `None` if
+/// the node is in an unknown namespace, references to unknown namespaces are dropped.
+fn remap_node(mut node: StoredNode, index_map: &HashMap<u16, u16>) -> Option<StoredNode> {
+    if !remap_node_id(&mut node.node_id, index_map) {
+        return None;
+    }
+    node.references.retain_mut(|r| {
+        remap_node_id(&mut r.target_id, index_map) && remap_node_id(&mut r.type_id, index_map)
+    });
+    for (_, value) in &mut node.attributes {
+        match &mut value.value {
+            Some(Variant::NodeId(id)) => {
+                remap_node_id(id, index_map);
+            }
+            Some(Variant::QualifiedName(name)) => {
+                if let Some(ns) = index_map.get(&name.namespace_index) {
+                    name.namespace_index = *ns;
+                }
+            }
+            _ => (),
+        }
+    }
+    Some(node)
+}
+
+/// A [NodeStorage] keeping a snapshot of all stored nodes in a single file,
+/// using OPC UA binary encoding.
+///
+/// The stored nodes are kept in memory, and the file is rewritten on each change,
+/// by writing and syncing a temporary file that then replaces the old one. This is simple and
+/// robust, but is best suited for a moderate number of stored nodes and changes.
+///
+/// The file includes the URI of each namespace, so node IDs, references, browse names
+/// and node ID attributes such as `DataType` are mapped to the current namespace indices
+/// when loaded. Node IDs nested in other values are stored as is. Nodes in namespaces
+/// that no longer exist on the server are dropped.
+pub struct FileNodeStorage {
+    path: PathBuf,
+    context: ContextOwned,
+    nodes: Mutex<HashMap<NodeId, NodeChange>>,
+}
+
+impl FileNodeStorage {
+    /// Create a new node storage using the file at `path`. The file is created
+    /// when the first change is stored.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            context: ContextOwned::default(),
+            nodes: Default::default(),
+        }
+    }
+
+    /// Add a type loader used to encode and decode custom types in values.
+    pub fn with_type_loader(mut self, loader: impl TypeLoader + 'static) -> Self {
+        self.context.loaders_mut().add_type_loader(loader);
+        self
+    }
+
+    /// Get the path to the storage file.
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    fn encode_node<S: Write + ?Sized>(
+        node: &StoredNode,
+        stream: &mut S,
+        ctx: &Context<'_>,
+    ) -> EncodingResult<()> {
+        write_i32(stream, node.node_class as i32)?;
+        write_u32(stream, node.attributes.len() as u32)?;
+        for (id, value) in &node.attributes {
+            write_u32(stream, *id as u32)?;
+            value.encode(stream, ctx)?;
+        }
+        write_u32(stream, node.references.len() as u32)?;
+        for r in &node.references {
+            r.target_id.encode(stream, ctx)?;
+            r.type_id.encode(stream, ctx)?;
+            r.is_forward.encode(stream, ctx)?;
+        }
+        Ok(())
+    }
+
+    fn decode_node<S: Read + ?Sized>(
+        node_id: NodeId,
+        stream: &mut S,
+        ctx: &Context<'_>,
+    ) -> EncodingResult<StoredNode> {
+        let node_class = NodeClass::try_from(read_i32(stream)?)
+            .map_err(|_| Error::decoding("Invalid node class"))?;
+        let num_attributes = read_u32(stream)?;
+        let mut attributes = Vec::new();
+        for _ in 0..num_attributes {
+            let id = AttributeId::from_u32(read_u32(stream)?)
+                .map_err(|_| Error::decoding("Invalid attribute ID"))?;
+            attributes.push((id, DataValue::decode(stream, ctx)?));
+        }
+        let num_references = read_u32(stream)?;
+        let mut references = Vec::new();
+        for _ in 0..num_references {
+            references.push(ImportedReference {
+                target_id: NodeId::decode(stream, ctx)?,
+                type_id: NodeId::decode(stream, ctx)?,
+                is_forward: bool::decode(stream, ctx)?,
+            });
+        }
+        Ok(StoredNode {
+            node_id,
+            node_class,
+            attributes,
+            references,
+        })
+    }
+
+    fn encode(
+        &self,
+        nodes: &HashMap<NodeId, NodeChange>,
+        namespaces: &NamespaceMap,
+    ) -> EncodingResult<Vec<u8>> {
+        let ctx = self.context.context();
+        let mut stream = Vec::new();
+        write_u32(&mut stream, FILE_FORMAT_VERSION)?;
+        let mut table: Vec<_> = namespaces.known_namespaces().iter().collect();
+        table.sort_by_key(|(_, idx)| **idx);
+        write_u32(&mut stream, table.len() as u32)?;
+        for (uri, idx) in table {
+            write_u16(&mut stream, *idx)?;
+            UAString::from(uri.as_str()).encode(&mut stream, &ctx)?;
+        }
+        write_u32(&mut stream, nodes.len() as u32)?;
+        for change in nodes.values() {
+            change.node_id().encode(&mut stream, &ctx)?;
+            match change {
+                NodeChange::Stored(node) => {
+                    false.encode(&mut stream, &ctx)?;
+                    Self::encode_node(node, &mut stream, &ctx)?;
+                }
+                NodeChange::Deleted(_) => true.encode(&mut stream, &ctx)?,
+            }
+        }
+        Ok(stream)
+    }
+
+    /// Decode the stored changes, mapping the namespace indices in the file
+    /// to the ones in `namespaces`.
+    fn decode(&self, data: &[u8], namespaces: &NamespaceMap) -> EncodingResult<Vec<NodeChange>> {
+        let ctx = self.context.context();
+        let mut stream = Cursor::new(data);
+        let version = read_u32(&mut stream)?;
+        // Namespace 0 is always the base namespace.
+        let mut index_map = HashMap::from([(0, 0)]);
+        let mut uris = HashMap::new();
+        match version {
+            1 => {
+                for idx in namespaces.known_namespaces().values() {
+                    index_map.insert(*idx, *idx);
+                }
+            }
+            FILE_FORMAT_VERSION => {
+                for _ in 0..read_u32(&mut stream)? {
+                    let idx = read_u16(&mut stream)?;
+                    let uri = UAString::decode(&mut stream, &ctx)?;
+                    if let Some(new_idx) = namespaces.get_index(uri.as_ref()) {
+                        index_map.insert(idx, new_idx);
+                    }
+                    uris.insert(idx, uri);
+                }
+            }
+            _ => {
+                return Err(Error::decoding(format!(
+                    "Unsupported node storage version {version}"
+                )))
+            }
+        }
+        let count = read_u32(&mut stream)?;
+        let mut changes = Vec::new();
+        for _ in 0..count {
+            let node_id = NodeId::decode(&mut stream, &ctx)?;
+            let change = if bool::decode(&mut stream, &ctx)? {
+                NodeChange::Deleted(node_id)
+            } else {
+                NodeChange::Stored(Self::decode_node(node_id, &mut stream, &ctx)?)
+            };
+            let stored_id = change.node_id().clone();
+            let change = match change {
+                NodeChange::Deleted(mut id) => {
+                    remap_node_id(&mut id, &index_map).then_some(NodeChange::Deleted(id))
+                }
+                NodeChange::Stored(node) => remap_node(node, &index_map).map(NodeChange::Stored),
+            };
+            match change {
+                Some(change) => changes.push(change),
+                None => warn!(
+                    "Dropping stored node {stored_id}, its namespace {} is not on the server",
+                    uris.get(&stored_id.namespace)
+                        .map(|u: &UAString| u.as_ref())
+                        .unwrap_or_default()
+                ),
+            }
+        }
+        Ok(changes)
+    }
+
+    fn write_file(&self, data: &[u8]) -> std::io::Result<()> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        // Make sure the new file is on disk before it replaces the old one, and that
+        // the rename itself is persisted, so a crash leaves either the old or the new file.
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&tmp_path, &self.path)?;
+        #[cfg(unix)]
+        if let Some(parent) = self.path.parent() {
+            let parent = if parent.as_os_str().is_empty() {
+                std::path::Path::new(".")
+            } else {
+                parent
+            };
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl NodeStorage for FileNodeStorage {
+    fn load(&self, namespaces: &NamespaceMap) -> Result<Vec<NodeChange>, StatusCode> {
+        let data = match std::fs::read(&self.path) {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                error!(
+                    "Failed to read node storage file {}: {e}",
+                    self.path.display()
+                );
+                return Err(StatusCode::BadInternalError);
+            }
+        };
+        let changes = self.decode(&data, namespaces)?;
+        let mut nodes = trace_lock!(self.nodes);
+        *nodes = changes
+            .iter()
+            .map(|c| (c.node_id().clone(), c.clone()))
+            .collect();
+        Ok(changes)
+    }
+
+    fn store(&self, changes: Vec<NodeChange>, namespaces: &NamespaceMap) -> Result<(), StatusCode> {
+        let mut nodes = trace_lock!(self.nodes);
+        for change in changes {
+            nodes.insert(change.node_id().clone(), change);
+        }
+        let data = self.encode(&nodes, namespaces)?;
+        self.write_file(&data).map_err(|e| {
+            error!(
+                "Failed to write node storage file {}: {e}",
+                self.path.display()
+            );
+            StatusCode::BadInternalError
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use opcua_core::{sync::Mutex, trace_lock};
+    use opcua_nodes::{ObjectBuilder, VariableBuilder};
+    use opcua_types::{
+        AttributeId, DataEncoding, DataTypeId, DataValue, DateTime, NamespaceMap, NodeClass,
+        NodeId, NumericRange, ObjectId, ObjectTypeId, QualifiedName, ReferenceTypeId, StatusCode,
+        TimestampsToReturn, Variant,
+    };
+
+    use crate::address_space::{AddressSpace, NodeBase, NodeType};
+
+    use super::{FileNodeStorage, NodeChange, NodeStorage, NodeStorageWriter, StoredNode};
+
+    fn namespaces(uris: &[&str]) -> NamespaceMap {
+        let mut namespaces = NamespaceMap::new();
+        for uri in uris {
+            namespaces.add_namespace(uri);
+        }
+        namespaces
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("opcua-{name}-{}", std::process::id()))
+    }
+
+    fn new_address_space() -> AddressSpace {
+        let mut address_space = AddressSpace::new();
+        address_space.add_namespace("http://opcfoundation.org/UA/", 0);
+        address_space.add_namespace("urn:test", 1);
+        address_space
+    }
+
+    #[test]
+    fn store_and_load_nodes() {
+        let mut address_space = new_address_space();
+        let obj_id = NodeId::new(1, "Object");
+        let var_id = NodeId::new(1, "Variable");
+        ObjectBuilder::new(&obj_id, "Object", "Object")
+            .description("An object")
+            .organized_by(ObjectId::ObjectsFolder)
+            .has_type_definition(ObjectTypeId::FolderType)
+            .insert(&mut address_space);
+        let time = DateTime::now();
+        VariableBuilder::new(&var_id, "Variable", "Variable")
+            .data_type(DataTypeId::Int32)
+            .value(0)
+            .component_of(obj_id.clone())
+            .insert(&mut address_space);
+        let NodeType::Variable(v) = address_space.find_mut(&var_id).unwrap() else {
+            panic!("Expected variable");
+        };
+        v.set_data_value(DataValue::new_at(5, time));
+
+        let path = temp_path("node-storage");
+        let namespaces = namespaces(&["urn:test"]);
+        let storage = FileNodeStorage::new(&path);
+        assert!(storage.load(&namespaces).unwrap().is_empty());
+        storage
+            .store(
+                vec![
+                    NodeChange::Stored(
+                        StoredNode::from_address_space(&address_space, &obj_id).unwrap(),
+                    ),
+                    NodeChange::Stored(
+                        StoredNode::from_address_space(&address_space, &var_id).unwrap(),
+                    ),
+                    NodeChange::Deleted(NodeId::new(1, "Deleted")),
+                ],
+                &namespaces,
+            )
+            .unwrap();
+
+        let changes = FileNodeStorage::new(&path).load(&namespaces).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(changes.len(), 3);
+
+        let mut restored = new_address_space();
+        for change in changes {
+            match change {
+                NodeChange::Stored(node) => {
+                    assert!(restored.import_node(node.into_imported_item().unwrap()));
+                }
+                NodeChange::Deleted(id) => assert_eq!(id, NodeId::new(1, "Deleted")),
+            }
+        }
+
+        let NodeType::Object(o) = restored.find(&obj_id).unwrap() else {
+            panic!("Expected object");
+        };
+        assert_eq!(o.browse_name(), &"Object".into());
+        assert_eq!(o.description(), Some(&"An object".into()));
+        assert!(restored.has_reference(
+            &ObjectId::ObjectsFolder.into(),
+            &obj_id,
+            ReferenceTypeId::Organizes
+        ));
+        assert!(restored.has_reference(
+            &obj_id,
+            &ObjectTypeId::FolderType.into(),
+            ReferenceTypeId::HasTypeDefinition
+        ));
+        assert!(restored.has_reference(&obj_id, &var_id, ReferenceTypeId::HasComponent));
+
+        let NodeType::Variable(v) = restored.find(&var_id).unwrap() else {
+            panic!("Expected variable");
+        };
+        assert_eq!(v.data_type(), DataTypeId::Int32);
+        let value = v.value(
+            TimestampsToReturn::Both,
+            &NumericRange::None,
+            &DataEncoding::Binary,
+            0.0,
+        );
+        assert_eq!(value.value, Some(Variant::Int32(5)));
+        assert_eq!(value.source_timestamp, Some(time));
+    }
+
+    #[test]
+    fn remap_namespaces() {
+        let mut address_space = new_address_space();
+        let obj_id = NodeId::new(1, "Object");
+        ObjectBuilder::new(&obj_id, QualifiedName::new(1, "Object"), "Object")
+            .organized_by(ObjectId::ObjectsFolder)
+            .has_type_definition(ObjectTypeId::FolderType)
+            .insert(&mut address_space);
+
+        let path = temp_path("node-storage-remap");
+        let storage = FileNodeStorage::new(&path);
+        storage
+            .store(
+                vec![
+                    NodeChange::Stored(
+                        StoredNode::from_address_space(&address_space, &obj_id).unwrap(),
+                    ),
+                    NodeChange::Deleted(NodeId::new(1, "Deleted")),
+                ],
+                &namespaces(&["urn:test"]),
+            )
+            .unwrap();
+
+        // The namespace of the stored nodes moved to index 2.
+        let changes = FileNodeStorage::new(&path)
+            .load(&namespaces(&["urn:other", "urn:test"]))
+            .unwrap();
+        // Without the namespace, the nodes are dropped.
+        let dropped = FileNodeStorage::new(&path)
+            .load(&namespaces(&["urn:other"]))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(changes.len(), 2);
+
+        let moved_id = NodeId::new(2, "Object");
+        let node = changes
+            .iter()
+            .find_map(|c| match c {
+                NodeChange::Stored(n) => Some(n),
+                NodeChange::Deleted(id) => {
+                    assert_eq!(id, &NodeId::new(2, "Deleted"));
+                    None
+                }
+            })
+            .unwrap();
+        assert_eq!(node.node_id, moved_id);
+        let (_, browse_name) = node
+            .attributes
+            .iter()
+            .find(|(id, _)| *id == AttributeId::BrowseName)
+            .unwrap();
+        assert_eq!(
+            browse_name.value,
+            Some(Variant::from(QualifiedName::new(2, "Object")))
+        );
+        // References to the base namespace are unchanged.
+        assert!(node
+            .references
+            .iter()
+            .any(|r| r.target_id == ObjectId::ObjectsFolder
+                && r.type_id == ReferenceTypeId::Organizes
+                && !r.is_forward));
+    }
+
+    /// A storage that fails to store changes while `fail` is set.
+    #[derive(Default)]
+    struct FailingStorage {
+        fail: AtomicBool,
+        stored: Mutex<Vec<NodeChange>>,
+    }
+
+    impl NodeStorage for FailingStorage {
+        fn load(&self, _namespaces: &NamespaceMap) -> Result<Vec<NodeChange>, StatusCode> {
+            Ok(Vec::new())
+        }
+
+        fn store(
+            &self,
+            changes: Vec<NodeChange>,
+            _namespaces: &NamespaceMap,
+        ) -> Result<(), StatusCode> {
+            if self.fail.load(Ordering::Relaxed) {
+                return Err(StatusCode::BadInternalError);
+            }
+            trace_lock!(self.stored).extend(changes);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_writes_are_retried() {
+        let storage = Arc::new(FailingStorage::default());
+        let writer = NodeStorageWriter::new(storage.clone());
+
+        storage.fail.store(true, Ordering::Relaxed);
+        assert!(writer.queue([NodeChange::Deleted(NodeId::new(1, "A"))]));
+        writer.flush();
+        assert!(trace_lock!(storage.stored).is_empty());
+
+        // The failed changes are written with the next change.
+        storage.fail.store(false, Ordering::Relaxed);
+        assert!(writer.queue([NodeChange::Deleted(NodeId::new(1, "B"))]));
+        writer.flush();
+        let mut stored: Vec<_> = trace_lock!(storage.stored)
+            .iter()
+            .map(|c| c.node_id().clone())
+            .collect();
+        stored.sort_by_key(|id| id.to_string());
+        assert_eq!(stored, vec![NodeId::new(1, "A"), NodeId::new(1, "B")]);
+    }
+
+    #[test]
+    fn invalid_node_class() {
+        let node = StoredNode {
+            node_id: NodeId::new(1, "Node"),
+            node_class: NodeClass::Unspecified,
+            attributes: Vec::new(),
+            references: Vec::new(),
+        };
+        assert_eq!(
+            node.into_imported_item().unwrap_err(),
+            StatusCode::BadNodeClassInvalid
+        );
+    }
+}
@@ -28,6 +28,7 @@ mod method;
 mod monitored_items;
 mod node_management;
 mod query;
+mod typed_method;
 mod utils;
 mod view;
 
@@ -48,7 +49,10 @@ pub use {
     method::MethodCall,
     monitored_items::{MonitoredItemRef, MonitoredItemUpdateRef},
     node_management::{AddNodeItem, AddReferenceItem, DeleteNodeItem, DeleteReferenceItem},
-    query::{ParsedNodeTypeDescription, ParsedQueryDataDescription, QueryRequest},
+    query::{ParsedNodeTypeDescription, ParsedQueryDataDescription, QueryNode, QueryRequest},
+    typed_method::{
+        ArgumentError, AsyncMethodFn, MethodArgs, MethodFn, MethodOutputs, TypedMethod,
+    },
     utils::*,
     view::{AddReferenceResult, BrowseNode, BrowsePathItem, ExternalReference, RegisterNodeItem},
 };
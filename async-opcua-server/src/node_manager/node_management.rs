@@ -137,6 +137,11 @@ impl AddNodeItem {
         self.status
     }
 
+    /// Node ID of the created node, set by [AddNodeItem::set_result].
+    pub fn added_node_id(&self) -> &NodeId {
+        &self.result_node_id
+    }
+
     /// Header diagnostic bits for requesting operation-level diagnostics.
     pub fn diagnostic_bits(&self) -> DiagnosticBits {
         self.diagnostic_bits
@@ -1,12 +1,15 @@
+use std::collections::VecDeque;
+
 use crate::session::{
     continuation_points::{ContinuationPoint, EmptyContinuationPoint},
     instance::Session,
 };
 use opcua_crypto::random;
-use opcua_nodes::ParsedContentFilter;
+use opcua_nodes::{AttributeQueryable, ParsedContentFilter};
 use opcua_types::{
-    AttributeId, ByteString, ExpandedNodeId, NodeTypeDescription, NumericRange, ParsingResult,
-    QueryDataDescription, QueryDataSet, RelativePath, StatusCode,
+    AttributeId, ByteString, ExpandedNodeId, NodeId, NodeTypeDescription, NumericRange,
+    ParsingResult, QualifiedName, QueryDataDescription, QueryDataSet, ReferenceTypeId,
+    RelativePath, StatusCode, Variant,
 };
 
 pub(crate) struct QueryContinuationPoint {
@@ -20,6 +23,73 @@ pub(crate) struct QueryContinuationPoint {
     max_references_to_return: usize,
 }
 
+/// Continuation point used by [QueryRequest::add_data_sets].
+struct QueryDataSetsContinuationPoint {
+    data_sets: VecDeque<QueryDataSet>,
+}
+
+/// A node that a query can be evaluated against.
+///
+/// Node managers implement this for a lightweight reference to one of their nodes,
+/// and pass it to [QueryRequest::evaluate_node]. The `AttributeQueryable` implementation
+/// is used to evaluate the query filter, and can usually be implemented using
+/// [QueryNode::query_attribute], and [QueryNode::references] for `get_related`.
+pub trait QueryNode: AttributeQueryable {
+    /// Get the node ID of the node.
+    fn node_id(&self) -> NodeId;
+
+    /// Get the browse name of the node.
+    fn browse_name(&self) -> QualifiedName;
+
+    /// Get the type definition of the node, if it has one.
+    fn type_definition(&self) -> Option<NodeId>;
+
+    /// Get the nodes referenced by this node through references of type `reference_type_id`,
+    /// or a subtype of it if `include_subtypes` is set. If `reference_type_id` is null,
+    /// all references should be followed.
+    ///
+    /// Only nodes that are known to this node manager need to be returned.
+    fn references(
+        &self,
+        reference_type_id: &NodeId,
+        include_subtypes: bool,
+        is_inverse: bool,
+    ) -> Vec<Self>;
+
+    /// Read an attribute of the node, returning `Variant::Empty` if the attribute
+    /// does not exist or may not be read by the current user.
+    fn read_attribute(&self, attribute_id: AttributeId, index_range: &NumericRange) -> Variant;
+
+    /// Get an attribute of a node given by a browse path of hierarchical references
+    /// starting at this node, if the node is an instance of `type_definition_id`.
+    ///
+    /// This implements the semantics of a `SimpleAttributeOperand` in a query.
+    fn query_attribute(
+        &self,
+        type_definition_id: &NodeId,
+        browse_path: &[QualifiedName],
+        attribute_id: AttributeId,
+        index_range: &NumericRange,
+    ) -> Variant {
+        if !self.is_of_type(type_definition_id, true) {
+            return Variant::Empty;
+        }
+        let hierarchical: NodeId = ReferenceTypeId::HierarchicalReferences.into();
+        let mut node = *self;
+        for name in browse_path {
+            let Some(child) = node
+                .references(&hierarchical, true, false)
+                .into_iter()
+                .find(|n| &n.browse_name() == name)
+            else {
+                return Variant::Empty;
+            };
+            node = child;
+        }
+        node.read_attribute(attribute_id, index_range)
+    }
+}
+
 #[derive(Debug)]
 /// Parsed and validated version of the OPC-UA `QueryDataDescription`.
 pub struct ParsedQueryDataDescription {
@@ -259,6 +329,106 @@ impl QueryRequest {
         self.status = status;
     }
 
+    /// Consume the continuation point created by this node manager during the last request.
+    ///
+    /// Returns `None` if there is no continuation point, or if it has a different type.
+    /// The query starts at this node manager if there is no continuation point, but a
+    /// continuation point of a different type is an error.
+    pub fn take_continuation_point<T: Send + Sync + 'static>(&mut self) -> Option<Box<T>> {
+        self.continuation_point.take().and_then(|c| c.take())
+    }
+
+    /// Evaluate the query against `node`, returning a data set if the node is
+    /// an instance of one of the requested node types and passes the filter.
+    pub fn evaluate_node<T: QueryNode>(&self, node: T) -> Option<QueryDataSet> {
+        let node_type = self.node_types.iter().find(|t| {
+            t.type_definition_node.server_index == 0
+                && node.is_of_type(&t.type_definition_node.node_id, t.include_sub_types)
+        })?;
+        if !self.filter.evaluate(node) {
+            return None;
+        }
+
+        let values = node_type
+            .data_to_return
+            .iter()
+            .map(|d| self.read_data_description(node, d))
+            .collect();
+
+        Some(QueryDataSet {
+            node_id: node.node_id().into(),
+            type_definition_node: node.type_definition().unwrap_or_default().into(),
+            values: Some(values),
+        })
+    }
+
+    fn read_data_description<T: QueryNode>(
+        &self,
+        node: T,
+        desc: &ParsedQueryDataDescription,
+    ) -> Variant {
+        let mut targets = vec![node];
+        for element in desc.relative_path.elements.iter().flatten() {
+            targets = targets
+                .into_iter()
+                .flat_map(|n| {
+                    n.references(
+                        &element.reference_type_id,
+                        element.include_subtypes,
+                        element.is_inverse,
+                    )
+                })
+                .filter(|n| element.target_name.is_null() || n.browse_name() == element.target_name)
+                .collect();
+        }
+
+        match targets.len() {
+            0 => Variant::Empty,
+            1 => targets[0].read_attribute(desc.attribute_id, &desc.index_range),
+            _ => targets
+                .into_iter()
+                .take(self.max_references_to_return)
+                .map(|n| n.read_attribute(desc.attribute_id, &desc.index_range))
+                .collect::<Vec<_>>()
+                .into(),
+        }
+    }
+
+    /// Add a list of data sets to the result.
+    ///
+    /// Data sets that do not fit in this response are stored in a continuation
+    /// point, and returned by [QueryRequest::resume_data_sets] in the next `QueryNext` call.
+    pub fn add_data_sets(&mut self, data_sets: impl IntoIterator<Item = QueryDataSet>) {
+        let mut data_sets: VecDeque<_> = data_sets.into_iter().collect();
+        let count = self.remaining_data_sets().min(data_sets.len());
+        self.data_sets.extend(data_sets.drain(..count));
+        if !data_sets.is_empty() {
+            self.set_next_continuation_point(Some(ContinuationPoint::new(Box::new(
+                QueryDataSetsContinuationPoint { data_sets },
+            ))));
+        }
+    }
+
+    /// Continue a query where the node manager used [QueryRequest::add_data_sets]
+    /// to add results.
+    ///
+    /// Returns `true` if data sets were stored by a previous call, in which case
+    /// the query should not be evaluated again, and `false` if the node manager
+    /// should start evaluating the query.
+    pub fn resume_data_sets(&mut self) -> Result<bool, StatusCode> {
+        let Some(point) = self.continuation_point.take() else {
+            return Ok(false);
+        };
+        if point.get::<EmptyContinuationPoint>().is_some() {
+            return Ok(false);
+        }
+        let Some(point) = point.take::<QueryDataSetsContinuationPoint>() else {
+            return Err(StatusCode::BadContinuationPointInvalid);
+        };
+        self.add_data_sets(point.data_sets);
+        Ok(true)
+    }
+
     /// Set the next continuation point for this query.
     pub fn set_next_continuation_point(
         &mut self,
@@ -270,4 +440,291 @@ impl QueryRequest {
     pub(crate) fn node_manager_index(&self) -> usize {
         self.node_manager_index
     }
+
+    /// Clear the input continuation point, which only applies to the
+    /// first node manager called in a `QueryNext` request.
+    pub(crate) fn clear_continuation_point(&mut self) {
+        self.continuation_point = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opcua_nodes::{AttributeQueryable, DefaultTypeTree, ParsedContentFilter, TypeTree};
+    use opcua_types::{
+        AttributeId, ContentFilter, ContentFilterElement, ExpandedNodeId, FilterOperator,
+        NodeClass, NodeId, NumericRange, ObjectTypeId, Operand, QualifiedName, QueryDataSet,
+        ReferenceTypeId, RelativePath, RelativePathElement, VariableTypeId, Variant,
+    };
+
+    use crate::session::continuation_points::{ContinuationPoint, EmptyContinuationPoint};
+
+    use super::{ParsedNodeTypeDescription, ParsedQueryDataDescription, QueryNode, QueryRequest};
+
+    struct TestNodeInfo {
+        browse_name: &'static str,
+        type_definition: NodeId,
+        value: Variant,
+    }
+
+    struct TestAddressSpace {
+        type_tree: DefaultTypeTree,
+        nodes: Vec<TestNodeInfo>,
+        // Source, target, reference type.
+        references: Vec<(usize, usize, NodeId)>,
+    }
+
+    #[derive(Clone, Copy)]
+    struct TestNode<'a> {
+        index: usize,
+        space: &'a TestAddressSpace,
+    }
+
+    impl AttributeQueryable for TestNode<'_> {
+        fn get_attribute(
+            &self,
+            type_definition_id: &NodeId,
+            browse_path: &[QualifiedName],
+            attribute_id: AttributeId,
+            index_range: &NumericRange,
+        ) -> Variant {
+            self.query_attribute(type_definition_id, browse_path, attribute_id, index_range)
+        }
+
+        fn is_of_type(&self, type_definition_id: &NodeId, include_subtypes: bool) -> bool {
+            let own_type = &self.space.nodes[self.index].type_definition;
+            if include_subtypes {
+                self.space
+                    .type_tree
+                    .is_subtype_of(own_type, type_definition_id)
+            } else {
+                own_type == type_definition_id
+            }
+        }
+    }
+
+    impl QueryNode for TestNode<'_> {
+        fn node_id(&self) -> NodeId {
+            NodeId::new(1, self.index as u32)
+        }
+
+        fn browse_name(&self) -> QualifiedName {
+            self.space.nodes[self.index].browse_name.into()
+        }
+
+        fn type_definition(&self) -> Option<NodeId> {
+            Some(self.space.nodes[self.index].type_definition.clone())
+        }
+
+        fn references(
+            &self,
+            reference_type_id: &NodeId,
+            include_subtypes: bool,
+            is_inverse: bool,
+        ) -> Vec<Self> {
+            self.space
+                .references
+                .iter()
+                .filter(|(_, _, ref_type)| {
+                    reference_type_id.is_null()
+                        || ref_type == reference_type_id
+                        || include_subtypes
+                            && self
+                                .space
+                                .type_tree
+                                .is_subtype_of(ref_type, reference_type_id)
+                })
+                .filter_map(|(source, target, _)| match is_inverse {
+                    false if *source == self.index => Some(*target),
+                    true if *target == self.index => Some(*source),
+                    _ => None,
+                })
+                .map(|index| TestNode {
+                    index,
+                    space: self.space,
+                })
+                .collect()
+        }
+
+        fn read_attribute(
+            &self,
+            attribute_id: AttributeId,
+            _index_range: &NumericRange,
+        ) -> Variant {
+            match attribute_id {
+                AttributeId::Value => self.space.nodes[self.index].value.clone(),
+                AttributeId::NodeId => self.node_id().into(),
+                _ => Variant::Empty,
+            }
+        }
+    }
+
+    fn pump_type() -> NodeId {
+        NodeId::new(1, "PumpType")
+    }
+
+    // Two pumps with a speed property, and a folder organizing them.
+    fn test_address_space() -> TestAddressSpace {
+        let mut type_tree = DefaultTypeTree::new();
+        type_tree.add_type_node(
+            &pump_type(),
+            &ObjectTypeId::BaseObjectType.into(),
+            NodeClass::ObjectType,
+        );
+        type_tree.add_type_property(
+            &NodeId::new(1, "PumpType_Speed"),
+            &pump_type(),
+            &[&"Speed".into()],
+            NodeClass::Variable,
+        );
+        type_tree.add_type_node(
+            &ReferenceTypeId::HasProperty.into(),
+            &ReferenceTypeId::HierarchicalReferences.into(),
+            NodeClass::ReferenceType,
+        );
+
+        let pump = |name| TestNodeInfo {
+            browse_name: name,
+            type_definition: pump_type(),
+            value: Variant::Empty,
+        };
+        let speed = |value: i32| TestNodeInfo {
+            browse_name: "Speed",
+            type_definition: VariableTypeId::PropertyType.into(),
+            value: value.into(),
+        };
+        TestAddressSpace {
+            type_tree,
+            nodes: vec![
+                TestNodeInfo {
+                    browse_name: "Pumps",
+                    type_definition: ObjectTypeId::FolderType.into(),
+                    value: Variant::Empty,
+                },
+                pump("Pump1"),
+                speed(10),
+                pump("Pump2"),
+                speed(20),
+            ],
+            references: vec![
+                (0, 1, ReferenceTypeId::Organizes.into()),
+                (0, 3, ReferenceTypeId::Organizes.into()),
+                (1, 2, ReferenceTypeId::HasProperty.into()),
+                (3, 4, ReferenceTypeId::HasProperty.into()),
+            ],
+        }
+    }
+
+    fn data_set(index: u32) -> QueryDataSet {
+        QueryDataSet {
+            node_id: NodeId::new(1, index).into(),
+            type_definition_node: ExpandedNodeId::null(),
+            values: None,
+        }
+    }
+
+    #[test]
+    fn evaluate_node() {
+        let space = test_address_space();
+        let (_, filter) = ParsedContentFilter::parse(
+            ContentFilter {
+                elements: Some(vec![ContentFilterElement::from((
+                    FilterOperator::GreaterThan,
+                    vec![
+                        Operand::simple_attribute(
+                            pump_type(),
+                            "Speed",
+                            AttributeId::Value,
+                            NumericRange::None,
+                        ),
+                        Operand::literal(15),
+                    ],
+                ))]),
+            },
+            &space.type_tree,
+            false,
+            true,
+        );
+        let request = QueryRequest::new(
+            vec![ParsedNodeTypeDescription {
+                type_definition_node: pump_type().into(),
+                include_sub_types: true,
+                data_to_return: vec![
+                    ParsedQueryDataDescription {
+                        relative_path: RelativePath {
+                            elements: Some(vec![RelativePathElement {
+                                reference_type_id: ReferenceTypeId::HasProperty.into(),
+                                is_inverse: false,
+                                include_subtypes: true,
+                                target_name: "Speed".into(),
+                            }]),
+                        },
+                        attribute_id: AttributeId::Value,
+                        index_range: NumericRange::None,
+                    },
+                    // The folder organizing the pump.
+                    ParsedQueryDataDescription {
+                        relative_path: RelativePath {
+                            elements: Some(vec![RelativePathElement {
+                                reference_type_id: ReferenceTypeId::Organizes.into(),
+                                is_inverse: true,
+                                include_subtypes: false,
+                                target_name: QualifiedName::null(),
+                            }]),
+                        },
+                        attribute_id: AttributeId::NodeId,
+                        index_range: NumericRange::None,
+                    },
+                ],
+            }],
+            filter.unwrap(),
+            100,
+            100,
+        );
+
+        let data_sets: Vec<_> = (0..space.nodes.len())
+            .filter_map(|index| {
+                request.evaluate_node(TestNode {
+                    index,
+                    space: &space,
+                })
+            })
+            .collect();
+        assert_eq!(data_sets.len(), 1);
+        let data_set = &data_sets[0];
+        assert_eq!(data_set.node_id, NodeId::new(1, 3u32).into());
+        assert_eq!(data_set.type_definition_node, pump_type().into());
+        assert_eq!(
+            data_set.values,
+            Some(vec![Variant::Int32(20), NodeId::new(1, 0u32).into()])
+        );
+    }
+
+    #[test]
+    fn data_set_continuation() {
+        let mut request = QueryRequest::new(Vec::new(), ParsedContentFilter::empty(), 2, 100);
+        assert!(!request.resume_data_sets().unwrap());
+        request.add_data_sets((0..5).map(data_set));
+        assert_eq!(request.data_sets().len(), 2);
+        assert!(request.is_completed());
+
+        // Simulate the next two QueryNext requests.
+        for expected in [2, 1] {
+            let point = request.next_continuation_point.take().unwrap();
+            request = QueryRequest::new(Vec::new(), ParsedContentFilter::empty(), 2, 100);
+            request.continuation_point = Some(point);
+            assert!(request.resume_data_sets().unwrap());
+            assert_eq!(request.data_sets().len(), expected);
+        }
+        assert!(request.next_continuation_point.is_none());
+
+        // A continuation point passed on from a previous node manager means
+        // the query should start from the beginning.
+        request.continuation_point = Some(ContinuationPoint::new(Box::new(EmptyContinuationPoint)));
+        assert!(!request.resume_data_sets().unwrap());
+
+        // A continuation point of a different type is an error.
+        request.continuation_point = Some(ContinuationPoint::new(Box::new(5u32)));
+        assert!(request.resume_data_sets().is_err());
+    }
 }
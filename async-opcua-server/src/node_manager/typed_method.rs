@@ -0,0 +1,244 @@
+//! Typed method handlers, letting methods be implemented by plain rust functions
+//! whose arguments and results are converted to and from variants automatically.
+
+use std::{future::Future, sync::Arc};
+
+use futures::future::BoxFuture;
+use opcua_types::{Argument, MethodArg, StatusCode, Variant};
+
+use super::MethodCall;
+
+/// Error converting the input arguments of a method call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentError {
+    /// The method was called with the wrong number of arguments,
+    /// `BadArgumentsMissing` or `BadTooManyArguments`.
+    Count(StatusCode),
+    /// One or more arguments could not be converted. Contains a result
+    /// for each argument.
+    Invalid(Vec<StatusCode>),
+}
+
+/// Trait for the input arguments of a typed method, implemented for tuples
+/// of types implementing [MethodArg].
+pub trait MethodArgs: Sized {
+    /// Describe the arguments, named `Argument1`, `Argument2`, etc.
+    fn arguments() -> Vec<Argument>;
+
+    /// Convert the given list of variants to the arguments.
+    fn from_variants(args: &[Variant]) -> Result<Self, ArgumentError>;
+}
+
+/// Trait for the results of a typed method, implemented for tuples
+/// of types implementing [MethodArg] that can be converted into a [Variant].
+pub trait MethodOutputs {
+    /// Describe the output arguments, named `Output1`, `Output2`, etc.
+    fn arguments() -> Vec<Argument>;
+
+    /// Convert the results to a list of variants.
+    fn into_variants(self) -> Vec<Variant>;
+}
+
+fn check_count(args: &[Variant], count: usize) -> Result<(), ArgumentError> {
+    match args.len() {
+        n if n < count => Err(ArgumentError::Count(StatusCode::BadArgumentsMissing)),
+        n if n > count => Err(ArgumentError::Count(StatusCode::BadTooManyArguments)),
+        _ => Ok(()),
+    }
+}
+
+fn convert<T: MethodArg>(arg: &Variant, result: &mut StatusCode) -> Option<T> {
+    match T::try_from_variant(arg.clone()) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            *result = e.status();
+            None
+        }
+    }
+}
+
+macro_rules! impl_method_tuple {
+    ($count:expr; $($tp:ident $idx:tt),*) => {
+        impl<$($tp: MethodArg),*> MethodArgs for ($($tp,)*) {
+            fn arguments() -> Vec<Argument> {
+                vec![$($tp::argument(&format!("Argument{}", $idx + 1))),*]
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn from_variants(args: &[Variant]) -> Result<Self, ArgumentError> {
+                check_count(args, $count)?;
+                let mut results = vec![StatusCode::Good; $count];
+                $(let $tp = convert::<$tp>(&args[$idx], &mut results[$idx]);)*
+                match ($($tp,)*) {
+                    ($(Some($tp),)*) => Ok(($($tp,)*)),
+                    #[allow(unreachable_patterns)]
+                    _ => Err(ArgumentError::Invalid(results)),
+                }
+            }
+        }
+
+        impl<$($tp: MethodArg + Into<Variant>),*> MethodOutputs for ($($tp,)*) {
+            fn arguments() -> Vec<Argument> {
+                vec![$($tp::argument(&format!("Output{}", $idx + 1))),*]
+            }
+
+            #[allow(non_snake_case)]
+            fn into_variants(self) -> Vec<Variant> {
+                let ($($tp,)*) = self;
+                vec![$($tp.into()),*]
+            }
+        }
+
+        impl<F, Out, $($tp),*> MethodFn<($($tp,)*), Out> for F
+        where
+            F: Fn($($tp),*) -> Result<Out, StatusCode> + Send + Sync + 'static,
+        {
+            #[allow(non_snake_case)]
+            fn call(&self, ($($tp,)*): ($($tp,)*)) -> Result<Out, StatusCode> {
+                self($($tp),*)
+            }
+        }
+
+        impl<F, Fut, Out, $($tp),*> AsyncMethodFn<($($tp,)*), Out> for F
+        where
+            F: Fn($($tp),*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Result<Out, StatusCode>> + Send + 'static,
+        {
+            #[allow(non_snake_case)]
+            fn call(&self, ($($tp,)*): ($($tp,)*)) -> BoxFuture<'static, Result<Out, StatusCode>> {
+                Box::pin(self($($tp),*))
+            }
+        }
+    };
+}
+
+/// Trait for synchronous functions that can be used as typed methods.
+///
+/// Implemented for functions taking up to 10 arguments implementing [MethodArg],
+/// and returning `Result<Out, StatusCode>` where `Out` is a tuple of results.
+pub trait MethodFn<Args, Out>: Send + Sync + 'static {
+    /// Call the function.
+    fn call(&self, args: Args) -> Result<Out, StatusCode>;
+}
+
+/// Trait for asynchronous functions that can be used as typed methods.
+///
+/// Implemented for functions taking up to 10 arguments implementing [MethodArg],
+/// and returning a future resolving to `Result<Out, StatusCode>` where `Out`
+/// is a tuple of results.
+pub trait AsyncMethodFn<Args, Out>: Send + Sync + 'static {
+    /// Call the function.
+    fn call(&self, args: Args) -> BoxFuture<'static, Result<Out, StatusCode>>;
+}
+
+impl_method_tuple!(0;);
+impl_method_tuple!(1; A1 0);
+impl_method_tuple!(2; A1 0, A2 1);
+impl_method_tuple!(3; A1 0, A2 1, A3 2);
+impl_method_tuple!(4; A1 0, A2 1, A3 2, A4 3);
+impl_method_tuple!(5; A1 0, A2 1, A3 2, A4 3, A5 4);
+impl_method_tuple!(6; A1 0, A2 1, A3 2, A4 3, A5 4, A6 5);
+impl_method_tuple!(7; A1 0, A2 1, A3 2, A4 3, A5 4, A6 5, A7 6);
+impl_method_tuple!(8; A1 0, A2 1, A3 2, A4 3, A5 4, A6 5, A7 6, A8 7);
+impl_method_tuple!(9; A1 0, A2 1, A3 2, A4 3, A5 4, A6 5, A7 6, A8 7, A9 8);
+impl_method_tuple!(10; A1 0, A2 1, A3 2, A4 3, A5 4, A6 5, A7 6, A8 7, A9 8, A10 9);
+
+type MethodFuture = BoxFuture<'static, Result<Vec<Variant>, StatusCode>>;
+type MethodHandler =
+    Arc<dyn Fn(&[Variant]) -> Result<MethodFuture, ArgumentError> + Send + Sync + 'static>;
+
+#[derive(Clone)]
+/// A method implemented by a rust function.
+///
+/// The input arguments are converted from variants using [MethodArg], each argument
+/// that fails to convert gets a `BadTypeMismatch` result. The function returns a tuple
+/// of results, which are converted into the output arguments of the method call.
+///
+/// Use [TypedMethod::input_arguments] and [TypedMethod::output_arguments] to create
+/// the `InputArguments` and `OutputArguments` properties of the method.
+///
+/// # Example
+///
+/// ```ignore
+/// let method = TypedMethod::new(|a: i32, b: i32| Ok((a + b,)))
+///     .input_names(["A", "B"])
+///     .output_names(["Sum"]);
+/// ```
+pub struct TypedMethod {
+    handler: MethodHandler,
+    input_arguments: Vec<Argument>,
+    output_arguments: Vec<Argument>,
+}
+
+impl TypedMethod {
+    /// Create a typed method from a synchronous function.
+    pub fn new<Args: MethodArgs, Out: MethodOutputs>(func: impl MethodFn<Args, Out>) -> Self {
+        Self {
+            handler: Arc::new(move |args| {
+                let args = Args::from_variants(args)?;
+                let res = func.call(args).map(MethodOutputs::into_variants);
+                Ok(Box::pin(std::future::ready(res)))
+            }),
+            input_arguments: Args::arguments(),
+            output_arguments: Out::arguments(),
+        }
+    }
+
+    /// Create a typed method from an asynchronous function.
+    pub fn new_async<Args: MethodArgs, Out: MethodOutputs + Send + 'static>(
+        func: impl AsyncMethodFn<Args, Out>,
+    ) -> Self {
+        Self {
+            handler: Arc::new(move |args| {
+                let args = Args::from_variants(args)?;
+                let fut = func.call(args);
+                Ok(Box::pin(async move {
+                    fut.await.map(MethodOutputs::into_variants)
+                }))
+            }),
+            input_arguments: Args::arguments(),
+            output_arguments: Out::arguments(),
+        }
+    }
+
+    /// Set the names of the input arguments, in order.
+    pub fn input_names<T: Into<String>>(mut self, names: impl IntoIterator<Item = T>) -> Self {
+        for (arg, name) in self.input_arguments.iter_mut().zip(names) {
+            arg.name = name.into().into();
+        }
+        self
+    }
+
+    /// Set the names of the output arguments, in order.
+    pub fn output_names<T: Into<String>>(mut self, names: impl IntoIterator<Item = T>) -> Self {
+        for (arg, name) in self.output_arguments.iter_mut().zip(names) {
+            arg.name = name.into().into();
+        }
+        self
+    }
+
+    /// Get the description of the input arguments of this method.
+    pub fn input_arguments(&self) -> &[Argument] {
+        &self.input_arguments
+    }
+
+    /// Get the description of the output arguments of this method.
+    pub fn output_arguments(&self) -> &[Argument] {
+        &self.output_arguments
+    }
+
+    /// Call the method, setting the status and outputs of `call`.
+    pub async fn call(&self, call: &mut MethodCall) {
+        match (self.handler)(call.arguments()) {
+            Ok(fut) => match fut.await {
+                Ok(outputs) => {
+                    call.set_outputs(outputs);
+                    call.set_status(StatusCode::Good);
+                }
+                Err(e) => call.set_status(e),
+            },
+            Err(ArgumentError::Count(e)) => call.set_status(e),
+            Err(ArgumentError::Invalid(results)) => call.set_argument_error(results),
+        }
+    }
+}
@@ -0,0 +1,991 @@
+//! Calculation of the standard aggregates defined in OPC-UA part 13.
+//!
+//! The [AggregateCalculator] computes processed values from a list of raw values,
+//! so any node manager able to supply raw history can implement `HistoryReadProcessed`.
+//!
+//! Aggregates based on the raw values in each interval, such as `Average`, `Minimum`
+//! and `Count`, compute their quality from the number of good and bad values. Time based
+//! aggregates, such as `TimeAverage` and `DurationGood`, compute it from the time
+//! spent in good and bad states, where each raw value holds its status until the next one.
+
+use opcua_types::{
+    AggregateConfiguration, DataValue, DateTime, NodeId, ObjectId, StatusCode, StatusCodeValueType,
+    Variant,
+};
+
+/// Number of `DateTime` ticks in a millisecond.
+pub(crate) const TICKS_PER_MS: f64 = 10_000.0;
+
+/// Get the timestamp used to order historical values, the source timestamp
+/// if it is set, or the server timestamp.
+pub(crate) fn history_timestamp(value: &DataValue) -> DateTime {
+    value
+        .source_timestamp
+        .or(value.server_timestamp)
+        .unwrap_or_else(DateTime::null)
+}
+
+/// A standard aggregate supported by the [AggregateCalculator].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AggregateType {
+    /// The value at the start of each interval, interpolated from the surrounding values.
+    Interpolative,
+    /// The arithmetic mean of the good values in each interval.
+    Average,
+    /// The time weighted average of each interval, using interpolated bounding values.
+    TimeAverage,
+    /// The smallest good value in each interval.
+    Minimum,
+    /// The largest good value in each interval.
+    Maximum,
+    /// The number of good values in each interval.
+    Count,
+    /// The first value in each interval.
+    Start,
+    /// The last value in each interval.
+    End,
+    /// The difference between the last and first good value in each interval.
+    Delta,
+    /// The time in milliseconds spent with good status in each interval.
+    DurationGood,
+    /// The time in milliseconds spent with bad status in each interval.
+    DurationBad,
+    /// The percentage of each interval spent with good status.
+    PercentGood,
+    /// The percentage of each interval spent with bad status.
+    PercentBad,
+}
+
+impl AggregateType {
+    /// All aggregates supported by the [AggregateCalculator].
+    pub const ALL: [AggregateType; 13] = [
+        AggregateType::Interpolative,
+        AggregateType::Average,
+        AggregateType::TimeAverage,
+        AggregateType::Minimum,
+        AggregateType::Maximum,
+        AggregateType::Count,
+        AggregateType::Start,
+        AggregateType::End,
+        AggregateType::Delta,
+        AggregateType::DurationGood,
+        AggregateType::DurationBad,
+        AggregateType::PercentGood,
+        AggregateType::PercentBad,
+    ];
+
+    /// Get the aggregate type from the node ID of an `AggregateFunction` object.
+    pub fn from_node_id(node_id: &NodeId) -> Option<Self> {
+        Some(match node_id.as_object_id().ok()? {
+            ObjectId::AggregateFunction_Interpolative => Self::Interpolative,
+            ObjectId::AggregateFunction_Average => Self::Average,
+            ObjectId::AggregateFunction_TimeAverage => Self::TimeAverage,
+            ObjectId::AggregateFunction_Minimum => Self::Minimum,
+            ObjectId::AggregateFunction_Maximum => Self::Maximum,
+            ObjectId::AggregateFunction_Count => Self::Count,
+            ObjectId::AggregateFunction_Start => Self::Start,
+            ObjectId::AggregateFunction_End => Self::End,
+            ObjectId::AggregateFunction_Delta => Self::Delta,
+            ObjectId::AggregateFunction_DurationGood => Self::DurationGood,
+            ObjectId::AggregateFunction_DurationBad => Self::DurationBad,
+            ObjectId::AggregateFunction_PercentGood => Self::PercentGood,
+            ObjectId::AggregateFunction_PercentBad => Self::PercentBad,
+            _ => return None,
+        })
+    }
+
+    /// Get the node ID of the `AggregateFunction` object for this aggregate.
+    pub fn node_id(&self) -> NodeId {
+        match self {
+            Self::Interpolative => ObjectId::AggregateFunction_Interpolative,
+            Self::Average => ObjectId::AggregateFunction_Average,
+            Self::TimeAverage => ObjectId::AggregateFunction_TimeAverage,
+            Self::Minimum => ObjectId::AggregateFunction_Minimum,
+            Self::Maximum => ObjectId::AggregateFunction_Maximum,
+            Self::Count => ObjectId::AggregateFunction_Count,
+            Self::Start => ObjectId::AggregateFunction_Start,
+            Self::End => ObjectId::AggregateFunction_End,
+            Self::Delta => ObjectId::AggregateFunction_Delta,
+            Self::DurationGood => ObjectId::AggregateFunction_DurationGood,
+            Self::DurationBad => ObjectId::AggregateFunction_DurationBad,
+            Self::PercentGood => ObjectId::AggregateFunction_PercentGood,
+            Self::PercentBad => ObjectId::AggregateFunction_PercentBad,
+        }
+        .into()
+    }
+}
+
+/// A single interval to calculate an aggregate for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AggregateInterval {
+    /// Chronological start of the interval, inclusive.
+    pub start: DateTime,
+    /// Chronological end of the interval, exclusive.
+    pub end: DateTime,
+    /// Timestamp of the calculated value. This is `start` when reading forwards,
+    /// and `end` when reading backwards.
+    pub timestamp: DateTime,
+    /// Whether the interval is shorter than the processing interval.
+    pub is_partial: bool,
+}
+
+/// The list of intervals given by the start time, end time and processing interval
+/// of a `HistoryReadProcessed` request.
+///
+/// If the start time is after the end time, intervals are in reverse chronological order.
+#[derive(Debug, Clone)]
+pub struct ProcessingIntervals {
+    start: i64,
+    end: i64,
+    step: i64,
+    len: usize,
+}
+
+impl ProcessingIntervals {
+    /// Create a new list of intervals. `processing_interval` is in milliseconds. If it is
+    /// zero, or longer than the time between `start` and `end`, there is a single interval.
+    pub fn new(
+        start: DateTime,
+        end: DateTime,
+        processing_interval: f64,
+    ) -> Result<Self, StatusCode> {
+        if start.is_null() || end.is_null() || start == end {
+            return Err(StatusCode::BadInvalidTimestampArgument);
+        }
+        if !processing_interval.is_finite() || processing_interval < 0.0 {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        let (start, end) = (start.ticks(), end.ticks());
+        let total = start.abs_diff(end);
+        let step = (processing_interval * TICKS_PER_MS) as u64;
+        let step = if step == 0 || step >= total {
+            total
+        } else {
+            step
+        };
+
+        Ok(Self {
+            start,
+            end,
+            step: step as i64,
+            len: total.div_ceil(step) as usize,
+        })
+    }
+
+    /// Get the number of intervals.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return `true` if there are no intervals.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the interval at `index`.
+    pub fn get(&self, index: usize) -> Option<AggregateInterval> {
+        if index >= self.len {
+            return None;
+        }
+        let offset = self.step * index as i64;
+        let (start, end, timestamp) = if self.start < self.end {
+            let start = self.start + offset;
+            let end = (start + self.step).min(self.end);
+            (start, end, start)
+        } else {
+            let end = self.start - offset;
+            let start = (end - self.step).max(self.end);
+            (start, end, end)
+        };
+        Some(AggregateInterval {
+            start: DateTime::from(start),
+            end: DateTime::from(end),
+            timestamp: DateTime::from(timestamp),
+            is_partial: end - start < self.step,
+        })
+    }
+
+    /// Iterate over the intervals, starting at `index`.
+    pub fn iter_from(&self, index: usize) -> impl Iterator<Item = AggregateInterval> + '_ {
+        (index..self.len).filter_map(|i| self.get(i))
+    }
+}
+
+/// A raw value prepared for aggregate calculation.
+struct Sample<'a> {
+    time: i64,
+    value: &'a DataValue,
+    good: bool,
+}
+
+impl Sample<'_> {
+    fn numeric(&self) -> Option<f64> {
+        self.value.value.as_ref().and_then(|v| v.as_f64())
+    }
+}
+
+/// A bounding value computed at some point in time.
+struct BoundingValue<'a> {
+    /// The value, in the data type of the raw values.
+    value: Variant,
+    /// The raw value the bound was computed from.
+    source: &'a Sample<'a>,
+    /// Whether the bound is uncertain, because it is extrapolated,
+    /// or bad values were skipped to compute it.
+    uncertain: bool,
+}
+
+/// Calculator for a single standard aggregate.
+#[derive(Debug, Clone)]
+pub struct AggregateCalculator {
+    aggregate_type: AggregateType,
+    configuration: AggregateConfiguration,
+    stepped: bool,
+}
+
+impl AggregateCalculator {
+    /// Create a new aggregate calculator.
+    ///
+    /// `stepped` should be the value of the `Stepped` property in the historical
+    /// configuration of the variable. If it is `false`, values are interpolated linearly
+    /// between raw values.
+    ///
+    /// If `use_server_capabilities_defaults` is set in `configuration`, the
+    /// [default configuration](Self::default_configuration) is used instead.
+    pub fn new(
+        aggregate_type: &NodeId,
+        configuration: &AggregateConfiguration,
+        stepped: bool,
+    ) -> Result<Self, StatusCode> {
+        let aggregate_type = AggregateType::from_node_id(aggregate_type)
+            .ok_or(StatusCode::BadAggregateNotSupported)?;
+        let configuration = if configuration.use_server_capabilities_defaults {
+            Self::default_configuration()
+        } else {
+            configuration.clone()
+        };
+        // Part 13 requires that the good and bad ranges cover all possible percentages.
+        if configuration.percent_data_good > 100
+            || configuration.percent_data_bad > 100
+            || configuration.percent_data_good + configuration.percent_data_bad < 100
+        {
+            return Err(StatusCode::BadAggregateConfigurationRejected);
+        }
+
+        Ok(Self {
+            aggregate_type,
+            configuration,
+            stepped,
+        })
+    }
+
+    /// The aggregate configuration used when clients request the server defaults.
+    pub fn default_configuration() -> AggregateConfiguration {
+        AggregateConfiguration {
+            use_server_capabilities_defaults: true,
+            treat_uncertain_as_bad: true,
+            percent_data_bad: 100,
+            percent_data_good: 100,
+            use_sloped_extrapolation: false,
+        }
+    }
+
+    /// Get the aggregate calculated by this calculator.
+    pub fn aggregate_type(&self) -> AggregateType {
+        self.aggregate_type
+    }
+
+    /// Get the configuration used by this calculator.
+    pub fn configuration(&self) -> &AggregateConfiguration {
+        &self.configuration
+    }
+
+    /// Calculate the aggregate for each interval in `intervals`.
+    ///
+    /// `values` must be the raw values of the variable in chronological order, as given by
+    /// their source timestamp, or server timestamp if the source timestamp is not set. It must
+    /// contain every value within the intervals, as well as values before and after the
+    /// intervals up to and including the closest good value, if one exists, so that
+    /// bounding values can be computed.
+    pub fn calculate(
+        &self,
+        intervals: impl IntoIterator<Item = AggregateInterval>,
+        values: &[DataValue],
+    ) -> Vec<DataValue> {
+        let samples: Vec<_> = values
+            .iter()
+            .map(|value| Sample {
+                time: history_timestamp(value).ticks(),
+                value,
+                good: self.is_good(value.status()),
+            })
+            .collect();
+
+        intervals
+            .into_iter()
+            .map(|interval| self.calculate_interval(&interval, &samples))
+            .collect()
+    }
+
+    fn is_good(&self, status: StatusCode) -> bool {
+        status.is_good() || status.is_uncertain() && !self.configuration.treat_uncertain_as_bad
+    }
+
+    fn calculate_interval(
+        &self,
+        interval: &AggregateInterval,
+        samples: &[Sample<'_>],
+    ) -> DataValue {
+        let (start, end) = (interval.start.ticks(), interval.end.ticks());
+        let first = samples.partition_point(|s| s.time < start);
+        let last = samples.partition_point(|s| s.time < end);
+        let in_interval = &samples[first..last];
+
+        match self.aggregate_type {
+            AggregateType::Interpolative => self.interpolative(interval, samples),
+            AggregateType::Average => self.count_based(interval, in_interval, |good| {
+                let sum: f64 = good.iter().filter_map(|s| s.numeric()).sum();
+                Some(Variant::Double(sum / good.len() as f64))
+            }),
+            AggregateType::Minimum => self.count_based(interval, in_interval, |good| {
+                good.iter()
+                    .min_by(|a, b| {
+                        a.numeric()
+                            .unwrap_or_default()
+                            .total_cmp(&b.numeric().unwrap_or_default())
+                    })
+                    .and_then(|s| s.value.value.clone())
+            }),
+            AggregateType::Maximum => self.count_based(interval, in_interval, |good| {
+                good.iter()
+                    .max_by(|a, b| {
+                        a.numeric()
+                            .unwrap_or_default()
+                            .total_cmp(&b.numeric().unwrap_or_default())
+                    })
+                    .and_then(|s| s.value.value.clone())
+            }),
+            AggregateType::Delta => self.count_based(interval, in_interval, |good| {
+                let (first, last) = (good.first()?, good.last()?);
+                let delta = last.numeric()? - first.numeric()?;
+                Some(cast_to_source(delta, first))
+            }),
+            AggregateType::Count => {
+                let count = in_interval.iter().filter(|s| s.good).count();
+                let status = if in_interval.is_empty() {
+                    StatusCode::Good
+                } else {
+                    self.quality(count as f64, (in_interval.len() - count) as f64)
+                };
+                calculated(interval, Some(Variant::Int32(count as i32)), status)
+            }
+            AggregateType::Start => raw_or_no_data(interval, in_interval.first()),
+            AggregateType::End => raw_or_no_data(interval, in_interval.last()),
+            AggregateType::TimeAverage => self.time_average(interval, samples, in_interval),
+            AggregateType::DurationGood
+            | AggregateType::DurationBad
+            | AggregateType::PercentGood
+            | AggregateType::PercentBad => {
+                let (good, bad) = status_durations(samples, start, end);
+                let duration = match self.aggregate_type {
+                    AggregateType::DurationGood | AggregateType::PercentGood => good,
+                    _ => bad,
+                };
+                let value = match self.aggregate_type {
+                    AggregateType::DurationGood | AggregateType::DurationBad => {
+                        duration as f64 / TICKS_PER_MS
+                    }
+                    _ => duration as f64 * 100.0 / (end - start) as f64,
+                };
+                calculated(interval, Some(Variant::Double(value)), StatusCode::Good)
+            }
+        }
+    }
+
+    /// Compute the quality of an aggregate from the amount of good and bad data,
+    /// either as number of values or as duration.
+    fn quality(&self, good: f64, bad: f64) -> StatusCode {
+        let total = good + bad;
+        if total <= 0.0 {
+            StatusCode::BadNoData
+        } else if good * 100.0 >= self.configuration.percent_data_good as f64 * total {
+            StatusCode::Good
+        } else if bad * 100.0 >= self.configuration.percent_data_bad as f64 * total {
+            StatusCode::Bad
+        } else {
+            StatusCode::UncertainDataSubNormal
+        }
+    }
+
+    /// Calculate an aggregate from the good values in the interval, with quality
+    /// based on the number of good and bad values.
+    fn count_based(
+        &self,
+        interval: &AggregateInterval,
+        in_interval: &[Sample<'_>],
+        calculate: impl FnOnce(&[&Sample<'_>]) -> Option<Variant>,
+    ) -> DataValue {
+        let good: Vec<_> = in_interval.iter().filter(|s| s.good).collect();
+        if good.iter().any(|s| s.numeric().is_none()) {
+            return calculated(interval, None, StatusCode::BadAggregateInvalidInputs);
+        }
+        if good.is_empty() {
+            let status = if in_interval.is_empty() {
+                StatusCode::BadNoData
+            } else {
+                StatusCode::Bad
+            };
+            return calculated(interval, None, status);
+        }
+        let status = self.quality(good.len() as f64, (in_interval.len() - good.len()) as f64);
+        if status.is_bad() {
+            return calculated(interval, None, status);
+        }
+        match calculate(&good) {
+            Some(value) => calculated(interval, Some(value), status),
+            None => calculated(interval, None, StatusCode::BadNoData),
+        }
+    }
+
+    fn interpolative(&self, interval: &AggregateInterval, samples: &[Sample<'_>]) -> DataValue {
+        let Some(bound) = self.bounding_value(samples, interval.timestamp.ticks()) else {
+            return calculated(interval, None, StatusCode::BadNoData);
+        };
+        let status = if bound.uncertain || !bound.source.value.status().is_good() {
+            StatusCode::UncertainDataSubNormal
+        } else {
+            StatusCode::Good
+        };
+        DataValue {
+            value: Some(bound.value),
+            status: Some(status.set_value_type(StatusCodeValueType::Interpolated)),
+            source_timestamp: Some(interval.timestamp),
+            server_timestamp: Some(interval.timestamp),
+            ..Default::default()
+        }
+    }
+
+    fn time_average(
+        &self,
+        interval: &AggregateInterval,
+        samples: &[Sample<'_>],
+        in_interval: &[Sample<'_>],
+    ) -> DataValue {
+        let (start, end) = (interval.start.ticks(), interval.end.ticks());
+        if in_interval.iter().any(|s| s.good && s.numeric().is_none()) {
+            return calculated(interval, None, StatusCode::BadAggregateInvalidInputs);
+        }
+
+        // Build the list of points in the interval, with `None` for bad values.
+        let mut points = Vec::with_capacity(in_interval.len() + 2);
+        let start_bound = self
+            .bounding_value(samples, start)
+            .and_then(|b| b.value.as_f64().map(|v| (v, b.uncertain)));
+        match start_bound {
+            // When stepped, the bound is only good if the last value before the interval is good.
+            Some((v, uncertain)) if !self.stepped || !uncertain => points.push((start, Some(v))),
+            _ => points.push((start, None)),
+        }
+        points.extend(
+            in_interval
+                .iter()
+                .filter(|s| s.time > start)
+                .map(|s| (s.time, if s.good { s.numeric() } else { None })),
+        );
+        let end_bound = self
+            .bounding_value(samples, end)
+            .and_then(|b| b.value.as_f64());
+        points.push((end, end_bound));
+
+        let mut area = 0.0;
+        let mut duration = 0i64;
+        if self.stepped {
+            for pair in points.windows(2) {
+                if let ((t1, Some(v)), (t2, _)) = (pair[0], pair[1]) {
+                    area += v * (t2 - t1) as f64;
+                    duration += t2 - t1;
+                }
+            }
+        } else {
+            // Bad values are skipped, and good values on either side are connected.
+            let good: Vec<_> = points
+                .iter()
+                .filter_map(|(t, v)| v.map(|v| (*t, v)))
+                .collect();
+            for pair in good.windows(2) {
+                let ((t1, v1), (t2, v2)) = (pair[0], pair[1]);
+                area += (v1 + v2) / 2.0 * (t2 - t1) as f64;
+                duration += t2 - t1;
+            }
+        }
+
+        let (good, bad) = status_durations(samples, start, end);
+        let status = self.quality(good as f64, bad as f64);
+        if duration == 0 || status.is_bad() {
+            let status = if status.is_bad() {
+                status
+            } else {
+                StatusCode::BadNoData
+            };
+            return calculated(interval, None, status);
+        }
+        calculated(
+            interval,
+            Some(Variant::Double(area / duration as f64)),
+            status,
+        )
+    }
+
+    /// Compute the bounding value at `time`, using stepped or sloped interpolation.
+    fn bounding_value<'a>(
+        &self,
+        samples: &'a [Sample<'a>],
+        time: i64,
+    ) -> Option<BoundingValue<'a>> {
+        let split = samples.partition_point(|s| s.time <= time);
+        let (before, after) = samples.split_at(split);
+
+        if let Some(exact) = before.last().filter(|s| s.time == time && s.good) {
+            return Some(BoundingValue {
+                value: exact.value.value.clone().unwrap_or_default(),
+                source: exact,
+                uncertain: false,
+            });
+        }
+
+        let prior_idx = before.iter().rposition(|s| s.good)?;
+        let prior = &before[prior_idx];
+        // Skipping over bad values makes the bound uncertain.
+        let skipped_before = prior_idx + 1 < before.len();
+        let prior_value = prior.value.value.clone().unwrap_or_default();
+
+        if self.stepped {
+            return Some(BoundingValue {
+                value: prior_value,
+                source: prior,
+                uncertain: skipped_before,
+            });
+        }
+
+        let Some(prior_num) = prior.numeric() else {
+            // Non-numeric values cannot be interpolated.
+            return Some(BoundingValue {
+                value: prior_value,
+                source: prior,
+                uncertain: skipped_before,
+            });
+        };
+
+        let next_idx = after.iter().position(|s| s.good);
+        match next_idx.map(|i| (i, &after[i])) {
+            Some((idx, next)) => {
+                let Some(next_num) = next.numeric() else {
+                    return Some(BoundingValue {
+                        value: prior_value,
+                        source: prior,
+                        uncertain: true,
+                    });
+                };
+                let value = interpolate(prior.time, prior_num, next.time, next_num, time);
+                Some(BoundingValue {
+                    value: cast_to_source(value, prior),
+                    source: prior,
+                    uncertain: skipped_before || idx > 0,
+                })
+            }
+            None => {
+                // No later good value, so we need to extrapolate.
+                let earlier = before[..prior_idx]
+                    .iter()
+                    .rev()
+                    .find(|s| s.good)
+                    .and_then(|s| s.numeric().map(|v| (s.time, v)));
+                let value = match earlier {
+                    Some((t1, v1))
+                        if self.configuration.use_sloped_extrapolation && t1 < prior.time =>
+                    {
+                        cast_to_source(interpolate(t1, v1, prior.time, prior_num, time), prior)
+                    }
+                    _ => prior_value,
+                };
+                Some(BoundingValue {
+                    value,
+                    source: prior,
+                    uncertain: true,
+                })
+            }
+        }
+    }
+}
+
+fn interpolate(t1: i64, v1: f64, t2: i64, v2: f64, time: i64) -> f64 {
+    v1 + (v2 - v1) * ((time - t1) as f64 / (t2 - t1) as f64)
+}
+
+/// Convert a calculated value back to the data type of the raw value it was computed from.
+fn cast_to_source(value: f64, source: &Sample<'_>) -> Variant {
+    match &source.value.value {
+        Some(v) => Variant::Double(value).cast(v.type_id()),
+        None => Variant::Double(value),
+    }
+}
+
+/// Get the time spent in good and bad states between `start` and `end`, in ticks.
+/// Each value keeps its status until the next value, and time before the
+/// first value is bad.
+fn status_durations(samples: &[Sample<'_>], start: i64, end: i64) -> (i64, i64) {
+    let first = samples.partition_point(|s| s.time <= start);
+    let mut is_good = first > 0 && samples[first - 1].good;
+    let mut cursor = start;
+    let (mut good, mut bad) = (0, 0);
+    for sample in samples[first..].iter().take_while(|s| s.time < end) {
+        if is_good {
+            good += sample.time - cursor;
+        } else {
+            bad += sample.time - cursor;
+        }
+        cursor = sample.time;
+        is_good = sample.good;
+    }
+    if is_good {
+        good += end - cursor;
+    } else {
+        bad += end - cursor;
+    }
+    (good, bad)
+}
+
+fn calculated(
+    interval: &AggregateInterval,
+    value: Option<Variant>,
+    status: StatusCode,
+) -> DataValue {
+    let status = status
+        .set_value_type(StatusCodeValueType::Calculated)
+        .set_partial(interval.is_partial);
+    DataValue {
+        value,
+        status: Some(status),
+        source_timestamp: Some(interval.timestamp),
+        server_timestamp: Some(interval.timestamp),
+        ..Default::default()
+    }
+}
+
+fn raw_or_no_data(interval: &AggregateInterval, sample: Option<&Sample<'_>>) -> DataValue {
+    match sample {
+        Some(s) => s.value.clone(),
+        None => calculated(interval, None, StatusCode::BadNoData),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use opcua_types::{
+        AggregateConfiguration, DataValue, DateTime, NodeId, ObjectId, StatusCode,
+        StatusCodeValueType, Variant,
+    };
+
+    use super::{AggregateCalculator, AggregateType, ProcessingIntervals};
+
+    fn start() -> DateTime {
+        DateTime::ymd_hms(2024, 1, 1, 0, 0, 0)
+    }
+
+    fn secs(secs: i64) -> DateTime {
+        start() + TimeDelta::try_seconds(secs).unwrap()
+    }
+
+    fn value(time: i64, value: f64, status: StatusCode) -> DataValue {
+        DataValue {
+            value: Some(Variant::Double(value)),
+            status: Some(status),
+            source_timestamp: Some(secs(time)),
+            ..Default::default()
+        }
+    }
+
+    fn calculator(aggregate: ObjectId, stepped: bool) -> AggregateCalculator {
+        AggregateCalculator::new(
+            &aggregate.into(),
+            &AggregateConfiguration {
+                use_server_capabilities_defaults: true,
+                ..Default::default()
+            },
+            stepped,
+        )
+        .unwrap()
+    }
+
+    fn calculate(
+        calculator: &AggregateCalculator,
+        start: i64,
+        end: i64,
+        interval_secs: f64,
+        values: &[DataValue],
+    ) -> Vec<DataValue> {
+        let intervals =
+            ProcessingIntervals::new(secs(start), secs(end), interval_secs * 1000.0).unwrap();
+        calculator.calculate(intervals.iter_from(0), values)
+    }
+
+    fn as_f64(values: &[DataValue]) -> Vec<Option<f64>> {
+        values
+            .iter()
+            .map(|v| v.value.as_ref().and_then(|v| v.as_f64()))
+            .collect()
+    }
+
+    #[test]
+    fn processing_intervals() {
+        let intervals = ProcessingIntervals::new(secs(0), secs(25), 10_000.0).unwrap();
+        assert_eq!(intervals.len(), 3);
+        let last = intervals.get(2).unwrap();
+        assert_eq!(last.start, secs(20));
+        assert_eq!(last.end, secs(25));
+        assert!(last.is_partial);
+        assert!(intervals.get(3).is_none());
+
+        // Backwards, the timestamp of each interval is its end.
+        let intervals = ProcessingIntervals::new(secs(25), secs(0), 10_000.0).unwrap();
+        let first = intervals.get(0).unwrap();
+        assert_eq!(first.start, secs(15));
+        assert_eq!(first.end, secs(25));
+        assert_eq!(first.timestamp, secs(25));
+
+        // A processing interval of 0 gives a single interval.
+        let intervals = ProcessingIntervals::new(secs(0), secs(25), 0.0).unwrap();
+        assert_eq!(intervals.len(), 1);
+
+        assert_eq!(
+            ProcessingIntervals::new(secs(0), secs(0), 0.0).unwrap_err(),
+            StatusCode::BadInvalidTimestampArgument
+        );
+    }
+
+    #[test]
+    fn aggregate_node_ids() {
+        for aggregate in AggregateType::ALL {
+            assert_eq!(
+                AggregateType::from_node_id(&aggregate.node_id()),
+                Some(aggregate)
+            );
+        }
+        assert_eq!(
+            AggregateCalculator::new(
+                &ObjectId::AggregateFunction_StandardDeviationSample.into(),
+                &AggregateCalculator::default_configuration(),
+                false
+            )
+            .unwrap_err(),
+            StatusCode::BadAggregateNotSupported
+        );
+        assert_eq!(
+            AggregateCalculator::new(
+                &NodeId::from(ObjectId::AggregateFunction_Average),
+                &AggregateConfiguration {
+                    percent_data_good: 40,
+                    percent_data_bad: 40,
+                    ..Default::default()
+                },
+                false
+            )
+            .unwrap_err(),
+            StatusCode::BadAggregateConfigurationRejected
+        );
+    }
+
+    #[test]
+    fn simple_aggregates() {
+        let values: Vec<_> = (0..20)
+            .map(|i| value(i, i as f64, StatusCode::Good))
+            .collect();
+
+        let avg = calculate(
+            &calculator(ObjectId::AggregateFunction_Average, false),
+            0,
+            20,
+            10.0,
+            &values,
+        );
+        assert_eq!(as_f64(&avg), vec![Some(4.5), Some(14.5)]);
+        assert_eq!(
+            avg[0].status().value_type(),
+            StatusCodeValueType::Calculated
+        );
+        assert_eq!(avg[0].source_timestamp, Some(secs(0)));
+
+        let min = calculate(
+            &calculator(ObjectId::AggregateFunction_Minimum, false),
+            0,
+            20,
+            10.0,
+            &values,
+        );
+        assert_eq!(as_f64(&min), vec![Some(0.0), Some(10.0)]);
+        let max = calculate(
+            &calculator(ObjectId::AggregateFunction_Maximum, false),
+            0,
+            20,
+            10.0,
+            &values,
+        );
+        assert_eq!(as_f64(&max), vec![Some(9.0), Some(19.0)]);
+        let delta = calculate(
+            &calculator(ObjectId::AggregateFunction_Delta, false),
+            0,
+            20,
+            10.0,
+            &values,
+        );
+        assert_eq!(as_f64(&delta), vec![Some(9.0), Some(9.0)]);
+        let start = calculate(
+            &calculator(ObjectId::AggregateFunction_Start, false),
+            0,
+            20,
+            10.0,
+            &values,
+        );
+        assert_eq!(start[1], values[10]);
+        let end = calculate(
+            &calculator(ObjectId::AggregateFunction_End, false),
+            0,
+            20,
+            10.0,
+            &values,
+        );
+        assert_eq!(end[1], values[19]);
+
+        // No data after the last value.
+        let count = calculate(
+            &calculator(ObjectId::AggregateFunction_Count, false),
+            15,
+            25,
+            5.0,
+            &values,
+        );
+        assert_eq!(as_f64(&count), vec![Some(5.0), Some(0.0)]);
+        let avg = calculate(
+            &calculator(ObjectId::AggregateFunction_Average, false),
+            15,
+            25,
+            5.0,
+            &values,
+        );
+        assert_eq!(avg[1].status().sub_code(), StatusCode::BadNoData.sub_code());
+    }
+
+    #[test]
+    fn bad_values() {
+        let values = vec![
+            value(0, 1.0, StatusCode::Good),
+            value(2, 100.0, StatusCode::Bad),
+            value(4, 3.0, StatusCode::Good),
+            value(6, 5.0, StatusCode::UncertainLastUsableValue),
+        ];
+
+        // Uncertain is treated as bad by default.
+        let avg = calculate(
+            &calculator(ObjectId::AggregateFunction_Average, false),
+            0,
+            10,
+            0.0,
+            &values,
+        );
+        assert_eq!(as_f64(&avg), vec![Some(2.0)]);
+        assert_eq!(
+            avg[0].status().sub_code(),
+            StatusCode::UncertainDataSubNormal.sub_code()
+        );
+
+        let avg = AggregateCalculator::new(
+            &ObjectId::AggregateFunction_Average.into(),
+            &AggregateConfiguration {
+                treat_uncertain_as_bad: false,
+                percent_data_good: 50,
+                percent_data_bad: 50,
+                ..Default::default()
+            },
+            false,
+        )
+        .unwrap();
+        let res = calculate(&avg, 0, 10, 0.0, &values);
+        assert_eq!(as_f64(&res), vec![Some(3.0)]);
+        assert!(res[0].status().is_good());
+
+        let good = calculate(
+            &calculator(ObjectId::AggregateFunction_DurationGood, false),
+            0,
+            10,
+            0.0,
+            &values,
+        );
+        assert_eq!(as_f64(&good), vec![Some(4000.0)]);
+        let bad = calculate(
+            &calculator(ObjectId::AggregateFunction_PercentBad, false),
+            0,
+            10,
+            0.0,
+            &values,
+        );
+        assert_eq!(as_f64(&bad), vec![Some(60.0)]);
+    }
+
+    #[test]
+    fn interpolation() {
+        let values = vec![
+            value(0, 0.0, StatusCode::Good),
+            value(10, 10.0, StatusCode::Good),
+            value(20, 0.0, StatusCode::Good),
+        ];
+
+        let sloped = calculate(
+            &calculator(ObjectId::AggregateFunction_Interpolative, false),
+            5,
+            30,
+            5.0,
+            &values,
+        );
+        assert_eq!(
+            as_f64(&sloped),
+            vec![Some(5.0), Some(10.0), Some(5.0), Some(0.0), Some(0.0)]
+        );
+        assert_eq!(
+            sloped[0].status().value_type(),
+            StatusCodeValueType::Interpolated
+        );
+        assert!(sloped[0].status().is_good());
+        // Extrapolated past the last value.
+        assert_eq!(
+            sloped[4].status().sub_code(),
+            StatusCode::UncertainDataSubNormal.sub_code()
+        );
+
+        let stepped = calculate(
+            &calculator(ObjectId::AggregateFunction_Interpolative, true),
+            5,
+            20,
+            5.0,
+            &values,
+        );
+        assert_eq!(as_f64(&stepped), vec![Some(0.0), Some(10.0), Some(10.0)]);
+
+        let sloped = calculate(
+            &calculator(ObjectId::AggregateFunction_TimeAverage, false),
+            5,
+            15,
+            10.0,
+            &values,
+        );
+        assert_eq!(as_f64(&sloped), vec![Some(7.5)]);
+        let stepped = calculate(
+            &calculator(ObjectId::AggregateFunction_TimeAverage, true),
+            5,
+            15,
+            10.0,
+            &values,
+        );
+        assert_eq!(as_f64(&stepped), vec![Some(5.0)]);
+    }
+}
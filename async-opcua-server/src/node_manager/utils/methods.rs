@@ -0,0 +1,13 @@
+use opcua_types::StatusCode;
+
+use crate::node_manager::MethodCall;
+
+/// Check that a method call has exactly `count` input arguments, returning
+/// `BadArgumentsMissing` or `BadTooManyArguments` otherwise.
+pub(crate) fn check_argument_count(call: &MethodCall, count: usize) -> Result<(), StatusCode> {
+    match call.arguments().len() {
+        n if n < count => Err(StatusCode::BadArgumentsMissing),
+        n if n > count => Err(StatusCode::BadTooManyArguments),
+        _ => Ok(()),
+    }
+}
@@ -1,8 +1,13 @@
+mod aggregates;
+mod methods;
 mod opaque_node_id;
 mod operations;
 mod result;
 mod sync_sampler;
 
+pub(crate) use aggregates::{history_timestamp, TICKS_PER_MS};
+pub use aggregates::{AggregateCalculator, AggregateInterval, AggregateType, ProcessingIntervals};
+pub(crate) use methods::check_argument_count;
 pub use opaque_node_id::*;
 pub use operations::{get_namespaces_for_user, get_node_metadata};
 pub(crate) use result::{consume_results, IntoResult};
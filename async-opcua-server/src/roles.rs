@@ -0,0 +1,461 @@
+//! Role-based access control, as described in OPC-UA part 18.
+//!
+//! The [`RoleManager`] maps users to the well-known roles in the `RoleSet` of the server
+//! through identity mapping rules, and handles the `AddIdentity` and `RemoveIdentity` methods
+//! on each role. Nodes with the `RolePermissions` attribute set can only be accessed by users
+//! in a role that is granted the required permission, see [`RoleManager::permissions`].
+
+use std::collections::HashMap;
+
+use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
+use opcua_nodes::NodeType;
+use opcua_types::{
+    ExtensionObject, IdentityCriteriaType, IdentityMappingRuleType, MessageSecurityMode, MethodId,
+    NodeId, ObjectId, PermissionType, RolePermissionType, StatusCode, UAString, VariableId,
+    Variant, VariantScalarTypeId, VariantTypeId,
+};
+
+use crate::{
+    load_method_args,
+    node_manager::{check_argument_count, MethodCall, RequestContext},
+};
+
+/// One of the well-known roles defined in OPC-UA part 18.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WellKnownRole {
+    /// Role of sessions with anonymous credentials.
+    Anonymous,
+    /// Role of sessions with any credentials other than anonymous.
+    AuthenticatedUser,
+    /// Allowed to browse, read live data, read historical data and subscribe to events.
+    Observer,
+    /// Allowed to browse, read and write live data, read historical data, call methods
+    /// and subscribe to events.
+    Operator,
+    /// Allowed to browse, read and write configuration data, read historical data,
+    /// call methods and subscribe to events.
+    Engineer,
+    /// Allowed to browse, read live data, read historical data, call methods and
+    /// subscribe to events.
+    Supervisor,
+    /// Allowed to change the non-security related configuration settings.
+    ConfigureAdmin,
+    /// Allowed to change security related settings.
+    SecurityAdmin,
+}
+
+impl WellKnownRole {
+    /// All well-known roles.
+    pub const ALL: [WellKnownRole; 8] = [
+        WellKnownRole::Anonymous,
+        WellKnownRole::AuthenticatedUser,
+        WellKnownRole::Observer,
+        WellKnownRole::Operator,
+        WellKnownRole::Engineer,
+        WellKnownRole::Supervisor,
+        WellKnownRole::ConfigureAdmin,
+        WellKnownRole::SecurityAdmin,
+    ];
+
+    /// Get the name of the role, which is also the browse name of its object.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WellKnownRole::Anonymous => "Anonymous",
+            WellKnownRole::AuthenticatedUser => "AuthenticatedUser",
+            WellKnownRole::Observer => "Observer",
+            WellKnownRole::Operator => "Operator",
+            WellKnownRole::Engineer => "Engineer",
+            WellKnownRole::Supervisor => "Supervisor",
+            WellKnownRole::ConfigureAdmin => "ConfigureAdmin",
+            WellKnownRole::SecurityAdmin => "SecurityAdmin",
+        }
+    }
+
+    /// Get the role with the given name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.name() == name)
+    }
+
+    /// Get the role with the given role ID.
+    pub fn from_node_id(node_id: &NodeId) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| node_id == &r.object_id())
+    }
+
+    /// Get the ID of the object representing the role, which is used as role ID
+    /// in `RolePermissions`.
+    pub fn object_id(&self) -> ObjectId {
+        match self {
+            WellKnownRole::Anonymous => ObjectId::WellKnownRole_Anonymous,
+            WellKnownRole::AuthenticatedUser => ObjectId::WellKnownRole_AuthenticatedUser,
+            WellKnownRole::Observer => ObjectId::WellKnownRole_Observer,
+            WellKnownRole::Operator => ObjectId::WellKnownRole_Operator,
+            WellKnownRole::Engineer => ObjectId::WellKnownRole_Engineer,
+            WellKnownRole::Supervisor => ObjectId::WellKnownRole_Supervisor,
+            WellKnownRole::ConfigureAdmin => ObjectId::WellKnownRole_ConfigureAdmin,
+            WellKnownRole::SecurityAdmin => ObjectId::WellKnownRole_SecurityAdmin,
+        }
+    }
+
+    fn identities_id(&self) -> VariableId {
+        match self {
+            WellKnownRole::Anonymous => VariableId::WellKnownRole_Anonymous_Identities,
+            WellKnownRole::AuthenticatedUser => {
+                VariableId::WellKnownRole_AuthenticatedUser_Identities
+            }
+            WellKnownRole::Observer => VariableId::WellKnownRole_Observer_Identities,
+            WellKnownRole::Operator => VariableId::WellKnownRole_Operator_Identities,
+            WellKnownRole::Engineer => VariableId::WellKnownRole_Engineer_Identities,
+            WellKnownRole::Supervisor => VariableId::WellKnownRole_Supervisor_Identities,
+            WellKnownRole::ConfigureAdmin => VariableId::WellKnownRole_ConfigureAdmin_Identities,
+            WellKnownRole::SecurityAdmin => VariableId::WellKnownRole_SecurityAdmin_Identities,
+        }
+    }
+
+    fn add_identity_id(&self) -> MethodId {
+        match self {
+            WellKnownRole::Anonymous => MethodId::WellKnownRole_Anonymous_AddIdentity,
+            WellKnownRole::AuthenticatedUser => {
+                MethodId::WellKnownRole_AuthenticatedUser_AddIdentity
+            }
+            WellKnownRole::Observer => MethodId::WellKnownRole_Observer_AddIdentity,
+            WellKnownRole::Operator => MethodId::WellKnownRole_Operator_AddIdentity,
+            WellKnownRole::Engineer => MethodId::WellKnownRole_Engineer_AddIdentity,
+            WellKnownRole::Supervisor => MethodId::WellKnownRole_Supervisor_AddIdentity,
+            WellKnownRole::ConfigureAdmin => MethodId::WellKnownRole_ConfigureAdmin_AddIdentity,
+            WellKnownRole::SecurityAdmin => MethodId::WellKnownRole_SecurityAdmin_AddIdentity,
+        }
+    }
+
+    fn remove_identity_id(&self) -> MethodId {
+        match self {
+            WellKnownRole::Anonymous => MethodId::WellKnownRole_Anonymous_RemoveIdentity,
+            WellKnownRole::AuthenticatedUser => {
+                MethodId::WellKnownRole_AuthenticatedUser_RemoveIdentity
+            }
+            WellKnownRole::Observer => MethodId::WellKnownRole_Observer_RemoveIdentity,
+            WellKnownRole::Operator => MethodId::WellKnownRole_Operator_RemoveIdentity,
+            WellKnownRole::Engineer => MethodId::WellKnownRole_Engineer_RemoveIdentity,
+            WellKnownRole::Supervisor => MethodId::WellKnownRole_Supervisor_RemoveIdentity,
+            WellKnownRole::ConfigureAdmin => MethodId::WellKnownRole_ConfigureAdmin_RemoveIdentity,
+            WellKnownRole::SecurityAdmin => MethodId::WellKnownRole_SecurityAdmin_RemoveIdentity,
+        }
+    }
+
+    /// Get the methods that manage the identities of each role.
+    pub(crate) fn identity_methods() -> impl Iterator<Item = MethodId> {
+        Self::ALL
+            .into_iter()
+            .flat_map(|r| [r.add_identity_id(), r.remove_identity_id()])
+    }
+}
+
+/// The roles of a user, used to compute the permissions of the user on nodes.
+#[derive(Debug, Clone, Default)]
+pub struct UserRoles {
+    roles: Vec<NodeId>,
+}
+
+impl UserRoles {
+    /// Create a new set of user roles from a list of role IDs.
+    pub fn new(roles: Vec<NodeId>) -> Self {
+        Self { roles }
+    }
+
+    /// Get the IDs of the roles of the user.
+    pub fn roles(&self) -> &[NodeId] {
+        &self.roles
+    }
+
+    /// Return `true` if the user has the given role.
+    pub fn contains(&self, role_id: &NodeId) -> bool {
+        self.roles.contains(role_id)
+    }
+
+    /// Get the permissions of the user given the role permissions of a node.
+    /// If the node has no role permissions, the user has every permission.
+    pub fn permissions(&self, role_permissions: Option<&[RolePermissionType]>) -> PermissionType {
+        let Some(role_permissions) = role_permissions else {
+            return PermissionType::all();
+        };
+        role_permissions
+            .iter()
+            .filter(|p| self.contains(&p.role_id))
+            .fold(PermissionType::empty(), |acc, p| acc | p.permissions)
+    }
+
+    /// Get the role permissions of a node that apply to the user, i.e. the
+    /// value of the `UserRolePermissions` attribute.
+    pub fn user_role_permissions(
+        &self,
+        role_permissions: &[RolePermissionType],
+    ) -> Vec<RolePermissionType> {
+        role_permissions
+            .iter()
+            .filter(|p| self.contains(&p.role_id))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Manages the identity mapping rules of the well-known roles, and the methods
+/// on the `RoleSet` used to edit them.
+///
+/// By default, anonymous users get the `Anonymous` role, any other user gets the
+/// `AuthenticatedUser` role, and each role is granted to users with an identity
+/// mapping rule of type `Role` with the name of the role, which the
+/// [`DefaultAuthenticator`](crate::authenticator::DefaultAuthenticator) matches against
+/// the `roles` of the user token and the roles in JSON Web Tokens. Rules of type
+/// `UserName`, `Thumbprint`, `Role`, `GroupId` and `X509Subject` are matched by
+/// [`AuthManager::matches_identity`](crate::authenticator::AuthManager::matches_identity).
+///
+/// `AddIdentity` and `RemoveIdentity` require a session with the `SecurityAdmin` role,
+/// over a secure channel using `SignAndEncrypt`.
+pub struct RoleManager {
+    identities: RwLock<HashMap<WellKnownRole, Vec<IdentityMappingRuleType>>>,
+}
+
+impl Default for RoleManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RoleManager {
+    /// Create a new role manager with the default identity mapping rules.
+    pub fn new() -> Self {
+        let identities = WellKnownRole::ALL
+            .into_iter()
+            .map(|role| {
+                let mut rules = vec![IdentityMappingRuleType {
+                    criteria_type: IdentityCriteriaType::Role,
+                    criteria: role.name().into(),
+                }];
+                match role {
+                    WellKnownRole::Anonymous => rules.push(IdentityMappingRuleType {
+                        criteria_type: IdentityCriteriaType::Anonymous,
+                        criteria: UAString::null(),
+                    }),
+                    WellKnownRole::AuthenticatedUser => rules.push(IdentityMappingRuleType {
+                        criteria_type: IdentityCriteriaType::AuthenticatedUser,
+                        criteria: UAString::null(),
+                    }),
+                    _ => (),
+                }
+                (role, rules)
+            })
+            .collect();
+        Self {
+            identities: RwLock::new(identities),
+        }
+    }
+
+    /// Get the identity mapping rules of `role`.
+    pub fn identities(&self, role: WellKnownRole) -> Vec<IdentityMappingRuleType> {
+        trace_read_lock!(self.identities)
+            .get(&role)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Add an identity mapping rule to `role`. Adding a rule that already exists does nothing.
+    pub fn add_identity(
+        &self,
+        role: WellKnownRole,
+        rule: IdentityMappingRuleType,
+    ) -> Result<(), StatusCode> {
+        Self::validate_rule(&rule)?;
+        let mut identities = trace_write_lock!(self.identities);
+        let rules = identities.entry(role).or_default();
+        if !rules.contains(&rule) {
+            rules.push(rule);
+        }
+        Ok(())
+    }
+
+    /// Remove an identity mapping rule from `role`.
+    pub fn remove_identity(
+        &self,
+        role: WellKnownRole,
+        rule: &IdentityMappingRuleType,
+    ) -> Result<(), StatusCode> {
+        let mut identities = trace_write_lock!(self.identities);
+        let rules = identities.entry(role).or_default();
+        let Some(idx) = rules.iter().position(|r| r == rule) else {
+            return Err(StatusCode::BadNotFound);
+        };
+        rules.remove(idx);
+        Ok(())
+    }
+
+    fn validate_rule(rule: &IdentityMappingRuleType) -> Result<(), StatusCode> {
+        let needs_criteria = match rule.criteria_type {
+            IdentityCriteriaType::Anonymous
+            | IdentityCriteriaType::AuthenticatedUser
+            | IdentityCriteriaType::TrustedApplication => false,
+            IdentityCriteriaType::UserName
+            | IdentityCriteriaType::Thumbprint
+            | IdentityCriteriaType::Role
+            | IdentityCriteriaType::GroupId
+            | IdentityCriteriaType::Application
+            | IdentityCriteriaType::X509Subject => true,
+        };
+        if needs_criteria == rule.criteria.is_empty() {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        Ok(())
+    }
+
+    /// Get the roles of the user given by `context`.
+    pub fn user_roles(&self, context: &RequestContext) -> UserRoles {
+        let identities = trace_read_lock!(self.identities);
+        let roles = WellKnownRole::ALL
+            .into_iter()
+            .filter(|role| {
+                identities
+                    .get(role)
+                    .is_some_and(|rules| rules.iter().any(|r| Self::matches(context, r)))
+            })
+            .map(|role| role.object_id().into())
+            .collect();
+        UserRoles::new(roles)
+    }
+
+    /// Return `true` if the user given by `context` has `role`.
+    pub fn has_role(&self, context: &RequestContext, role: WellKnownRole) -> bool {
+        trace_read_lock!(self.identities)
+            .get(&role)
+            .is_some_and(|rules| rules.iter().any(|r| Self::matches(context, r)))
+    }
+
+    /// Return `true` if the user given by `context` is a security administrator,
+    /// either through [`AuthManager::is_security_admin`](crate::authenticator::AuthManager::is_security_admin),
+    /// through a `SecurityAdmin` role granted to the session by its identity token, or by
+    /// having the `SecurityAdmin` role.
+    pub fn is_security_admin(&self, context: &RequestContext) -> bool {
+        context.authenticator.is_security_admin(&context.token)
+            || Self::has_identity_role(context, "SecurityAdmin")
+            || self.has_role(context, WellKnownRole::SecurityAdmin)
+    }
+
+    /// Return `true` if the identity token of the session given by `context`
+    /// granted it `role`, see [`Session::identity_roles`](crate::session::instance::Session::identity_roles).
+    fn has_identity_role(context: &RequestContext, role: &str) -> bool {
+        trace_read_lock!(context.session)
+            .identity_roles()
+            .iter()
+            .any(|r| r == role)
+    }
+
+    /// Get the permissions of the user given by `context` on `node`. If the node
+    /// has no role permissions, the user has every permission.
+    pub fn permissions(&self, context: &RequestContext, node: &NodeType) -> PermissionType {
+        let role_permissions = node.as_node().role_permissions();
+        if role_permissions.is_none() {
+            return PermissionType::all();
+        }
+        self.user_roles(context).permissions(role_permissions)
+    }
+
+    fn matches(context: &RequestContext, rule: &IdentityMappingRuleType) -> bool {
+        match rule.criteria_type {
+            IdentityCriteriaType::Anonymous => context.token.is_anonymous(),
+            IdentityCriteriaType::AuthenticatedUser => !context.token.is_anonymous(),
+            IdentityCriteriaType::Application => {
+                let session = trace_read_lock!(context.session);
+                session.application_description().application_uri == rule.criteria
+            }
+            IdentityCriteriaType::TrustedApplication => {
+                // The client certificate is validated against the trust list when
+                // the session is created.
+                let session = trace_read_lock!(context.session);
+                session.client_certificate().is_some()
+                    && session.message_security_mode() != MessageSecurityMode::None
+            }
+            IdentityCriteriaType::Role => {
+                Self::has_identity_role(context, rule.criteria.as_ref())
+                    || context.authenticator.matches_identity(&context.token, rule)
+            }
+            _ => context.authenticator.matches_identity(&context.token, rule),
+        }
+    }
+
+    /// Read the value of the `Identities` variable of a role, returning `None`
+    /// if `var_id` is not one of them.
+    pub(crate) fn read_identities(&self, var_id: VariableId) -> Option<Variant> {
+        let role = WellKnownRole::ALL
+            .into_iter()
+            .find(|r| r.identities_id() == var_id)?;
+        Some(
+            self.identities(role)
+                .into_iter()
+                .map(ExtensionObject::from_message)
+                .collect::<Vec<_>>()
+                .into(),
+        )
+    }
+
+    /// Handle a call to `AddIdentity` or `RemoveIdentity` on one of the well-known roles,
+    /// returning `false` if the call is not for one of these methods. The call must already
+    /// have been validated by the node manager owning the method.
+    pub(crate) fn call(&self, context: &RequestContext, call: &mut MethodCall) -> bool {
+        let Ok(method) = call.method_id().as_method_id() else {
+            return false;
+        };
+        let Some((role, add)) = WellKnownRole::ALL.into_iter().find_map(|r| {
+            if r.add_identity_id() == method {
+                Some((r, true))
+            } else if r.remove_identity_id() == method {
+                Some((r, false))
+            } else {
+                None
+            }
+        }) else {
+            return false;
+        };
+        if call.object_id() != &role.object_id() {
+            return false;
+        }
+
+        if let Err(e) = self.check_access(context, call) {
+            call.set_status(e);
+            return true;
+        }
+
+        let res = check_argument_count(call, 1)
+            .and_then(|_| load_method_args!(call, ExtensionObject))
+            .and_then(|rule| {
+                rule.into_inner_as::<IdentityMappingRuleType>()
+                    .ok_or(StatusCode::BadInvalidArgument)
+            })
+            .and_then(|rule| {
+                if add {
+                    self.add_identity(role, *rule)
+                } else {
+                    self.remove_identity(role, &rule)
+                }
+            });
+        match res {
+            Ok(()) => {
+                call.set_outputs(Vec::new());
+                call.set_status(StatusCode::Good);
+            }
+            Err(e) => call.set_status(e),
+        }
+        true
+    }
+
+    fn check_access(&self, context: &RequestContext, call: &MethodCall) -> Result<(), StatusCode> {
+        if !context
+            .authenticator
+            .is_user_executable(&context.token, call.method_id())
+            || !self.is_security_admin(context)
+        {
+            return Err(StatusCode::BadUserAccessDenied);
+        }
+        let security_mode = trace_read_lock!(context.session).message_security_mode();
+        if security_mode != MessageSecurityMode::SignAndEncrypt {
+            return Err(StatusCode::BadSecurityModeInsufficient);
+        }
+        Ok(())
+    }
+}
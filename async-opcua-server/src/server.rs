@@ -8,8 +8,10 @@ use std::{
     time::Duration,
 };
 
-use arc_swap::ArcSwap;
-use futures::{future::Either, never::Never, stream::FuturesUnordered, FutureExt, StreamExt};
+use arc_swap::{ArcSwap, ArcSwapOption};
+use futures::{
+    future::Either, never::Never, stream::FuturesUnordered, Future, FutureExt, StreamExt,
+};
 use log::{error, info, warn};
 use opcua_core::{sync::RwLock, trace_read_lock, trace_write_lock};
 use opcua_nodes::DefaultTypeTree;
@@ -21,16 +23,23 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
-use opcua_core::{config::Config, handle::AtomicHandle};
-use opcua_crypto::CertificateStore;
+use opcua_core::{comms::stream::BoxedStream, config::Config, handle::AtomicHandle};
+use opcua_crypto::{CertificateStore, KeyType, PrivateKey, X509Data, X509};
 
 use crate::{
+    certificate_management::CertificateManager,
+    conditions::ConditionManager,
+    local_discovery::LocalDiscoveryServer,
     node_manager::{DefaultTypeTreeGetter, ServerContext},
+    roles::RoleManager,
     session::controller::{ControllerCommand, SessionStarter},
-    transport::tcp::{TcpConnector, TransportConfig},
+    transport::{
+        reverse_connect::run_reverse_connect,
+        tcp::{TcpConnector, TransportConfig},
+    },
     ServerStatusWrapper,
 };
-use opcua_types::{DateTime, LocalizedText, ServerState, UAString};
+use opcua_types::{ApplicationDescription, DateTime, LocalizedText, ServerState, UAString};
 
 use super::{
     authenticator::DefaultAuthenticator,
@@ -106,17 +115,34 @@ impl Server {
         };
 
         let (mut certificate_store, server_certificate, server_pkey) =
-            CertificateStore::new_with_x509_data(
-                &config.pki_dir,
-                false,
-                config.certificate_path.as_deref(),
-                config.private_key_path.as_deref(),
-                application_description,
-            );
+            match builder.certificate_store_backend {
+                Some(backend) => CertificateStore::new_with_backend_and_x509_data(
+                    backend,
+                    false,
+                    application_description.clone(),
+                ),
+                None => CertificateStore::new_with_x509_data(
+                    &config.pki_dir,
+                    false,
+                    config.certificate_path.as_deref(),
+                    config.private_key_path.as_deref(),
+                    application_description.clone(),
+                ),
+            };
 
         if server_certificate.is_none() || server_pkey.is_none() {
             warn!("Server is missing its application instance certificate and/or its private key. Encrypted endpoints will not function correctly.");
         }
+        // Endpoints with security policies that need another key type use their own certificate.
+        let other_server_certificates = match &server_pkey {
+            Some(pkey) => read_other_server_certificates(
+                &certificate_store,
+                &config,
+                pkey.key_type(),
+                application_description.as_ref(),
+            ),
+            None => Vec::new(),
+        };
 
         config.read_x509_thumbprints();
 
@@ -125,6 +151,7 @@ impl Server {
             certificate_store.set_trust_unknown_certs(true);
         }
         certificate_store.set_check_time(config.certificate_validation.check_time);
+        certificate_store.set_allow_missing_crls(config.certificate_validation.allow_missing_crls);
 
         let config = Arc::new(config);
 
@@ -132,6 +159,10 @@ impl Server {
 
         let type_tree = Arc::new(RwLock::new(DefaultTypeTree::new()));
 
+        let subscriptions = Arc::new(SubscriptionCache::new(config.limits.subscriptions));
+
+        let certificate_store = Arc::new(RwLock::new(certificate_store));
+
         let info = ServerInfo {
             authenticator: builder
                 .authenticator
@@ -145,8 +176,9 @@ impl Server {
             start_time: ArcSwap::new(Arc::new(opcua_types::DateTime::now())),
             servers,
             config: config.clone(),
-            server_certificate,
-            server_pkey,
+            server_certificate: ArcSwapOption::new(server_certificate.map(Arc::new)),
+            server_pkey: ArcSwapOption::new(server_pkey.map(Arc::new)),
+            other_server_certificates: ArcSwap::new(Arc::new(other_server_certificates)),
             operational_limits: config.limits.operational.clone(),
             state: ArcSwap::new(Arc::new(ServerState::Shutdown)),
             send_buffer_size,
@@ -158,16 +190,22 @@ impl Server {
             capabilities: ServerCapabilities::default(),
             service_level: service_level.clone(),
             port: AtomicU16::new(0),
+            websocket_port: AtomicU16::new(0),
+            https_port: AtomicU16::new(0),
             type_tree_getter: builder
                 .type_tree_getter
                 .unwrap_or_else(|| Arc::new(DefaultTypeTreeGetter)),
             type_loaders: RwLock::new(builder.type_loaders),
+            conditions: Arc::new(ConditionManager::new(subscriptions.clone())),
+            certificate_manager: Arc::new(CertificateManager::new(certificate_store.clone())),
+            roles: Arc::new(RoleManager::new()),
+            local_discovery: config
+                .local_discovery
+                .enabled
+                .then(|| Arc::new(LocalDiscoveryServer::new(&config.local_discovery))),
         };
 
-        let certificate_store = Arc::new(RwLock::new(certificate_store));
-
         let info = Arc::new(info);
-        let subscriptions = Arc::new(SubscriptionCache::new(config.limits.subscriptions));
 
         let node_managers_ref = NodeManagersRef::new_empty();
         let status_wrapper = Arc::new(ServerStatusWrapper::new(
@@ -252,17 +290,25 @@ impl Server {
     }
 
     #[cfg(feature = "discovery-server-registration")]
-    async fn run_discovery_server_registration(info: Arc<ServerInfo>) -> Never {
+    async fn run_discovery_server_registration(
+        info: Arc<ServerInfo>,
+        certificate_store: Arc<RwLock<CertificateStore>>,
+    ) -> Never {
         let registered_server = info.registered_server();
-        let Some(discovery_server_url) = info.config.discovery_server_url.as_ref() else {
+        // A local discovery server does not register itself anywhere.
+        let (Some(discovery_server_url), None) = (
+            info.config.discovery_server_url.as_ref(),
+            info.local_discovery.as_ref(),
+        ) else {
             loop {
                 futures::future::pending::<()>().await;
             }
         };
+        let certificate_store_backend = trace_read_lock!(certificate_store).backend().clone();
         crate::discovery::periodic_discovery_server_registration(
             discovery_server_url,
             registered_server,
-            info.config.pki_dir.clone(),
+            certificate_store_backend,
             Duration::from_secs(5 * 60),
         )
         .await
@@ -299,12 +345,23 @@ impl Server {
             .port
             .store(addr.port(), std::sync::atomic::Ordering::Relaxed);
 
+        let (websocket_send, mut websocket_recv) = tokio::sync::mpsc::channel(5);
+        let websocket_fut =
+            Self::start_websocket_listener(self.info.clone(), websocket_send).await?;
+        pin!(websocket_fut);
+
+        let https_fut = self.start_https_listener().await?;
+        pin!(https_fut);
+
         self.log_endpoint_info();
 
         let mut connection_counter = 0;
 
         #[cfg(feature = "discovery-server-registration")]
-        let discovery_fut = Self::run_discovery_server_registration(self.info.clone());
+        let discovery_fut = Self::run_discovery_server_registration(
+            self.info.clone(),
+            self.certificate_store.clone(),
+        );
 
         #[cfg(not(feature = "discovery-server-registration"))]
         let discovery_fut = futures::future::pending();
@@ -315,10 +372,15 @@ impl Server {
             Self::run_subscription_ticks(self.config.subscription_poll_interval_ms, &context);
         pin!(subscription_fut);
 
-        let session_expiry_fut =
-            Self::run_session_expiry(&self.session_manager, &self.session_notify);
+        let session_manager = self.session_manager.clone();
+        let session_notify = self.session_notify.clone();
+        let session_expiry_fut = Self::run_session_expiry(&session_manager, &session_notify);
         pin!(session_expiry_fut);
 
+        let (reverse_send, mut reverse_recv) = tokio::sync::mpsc::channel(5);
+        let reverse_connect_fut = run_reverse_connect(self.info.clone(), reverse_send);
+        pin!(reverse_connect_fut);
+
         loop {
             let conn_fut = if self.connections.is_empty() {
                 if self.token.is_cancelled() {
@@ -342,31 +404,14 @@ impl Server {
                 _ = &mut subscription_fut => {}
                 _ = &mut discovery_fut => {}
                 _ = &mut session_expiry_fut => {}
+                _ = &mut reverse_connect_fut => {}
+                _ = &mut websocket_fut => {}
+                _ = &mut https_fut => {}
                 rs = listener.accept() => {
                     match rs {
                         Ok((socket, addr)) => {
                             info!("Accept new connection from {addr} ({connection_counter})");
-                            let conn = SessionStarter::new(
-                                TcpConnector::new(socket, TransportConfig {
-                                    send_buffer_size: self.info.config.limits.send_buffer_size,
-                                    max_message_size: self.info.config.limits.max_message_size,
-                                    max_chunk_count: self.info.config.limits.max_chunk_count,
-                                    receive_buffer_size: self.info.config.limits.receive_buffer_size,
-                                    hello_timeout: Duration::from_secs(self.info.config.tcp_config.hello_timeout as u64),
-                                }, self.info.decoding_options()),
-                                self.info.clone(),
-                                self.session_manager.clone(),
-                                self.certificate_store.clone(),
-                                self.node_managers.clone(),
-                                self.subscriptions.clone()
-                            );
-
-                            let (send, recv) = tokio::sync::mpsc::channel(5);
-                            let handle = tokio::spawn(conn.run(recv).map(move |_| connection_counter));
-                            self.connections.push(handle);
-                            self.connection_map.insert(connection_counter, ConnectionInfo {
-                                command_send: send
-                            });
+                            self.start_connection(Box::new(socket), connection_counter, None);
                             connection_counter += 1;
                         }
                         Err(e) => {
@@ -374,6 +419,16 @@ impl Server {
                         }
                     }
                 }
+                Some(conn) = reverse_recv.recv() => {
+                    info!("Opened reverse connection to {} ({connection_counter})", conn.client_url);
+                    self.start_connection(Box::new(conn.socket), connection_counter, Some(conn.closed));
+                    connection_counter += 1;
+                }
+                Some((stream, addr)) = websocket_recv.recv() => {
+                    info!("Accept new WebSocket connection from {addr} ({connection_counter})");
+                    self.start_connection(stream, connection_counter, None);
+                    connection_counter += 1;
+                }
                 _ = self.token.cancelled() => {
                     for conn in self.connection_map.values() {
                         let _ = conn.command_send.send(ControllerCommand::Close).await;
@@ -385,6 +440,126 @@ impl Server {
         Ok(())
     }
 
+    /// Start the WebSocket listener, if the WebSocket transport is configured.
+    /// Returns a future accepting connections, sending them on `send`.
+    #[cfg(feature = "websocket")]
+    async fn start_websocket_listener(
+        info: Arc<ServerInfo>,
+        send: tokio::sync::mpsc::Sender<(BoxedStream, SocketAddr)>,
+    ) -> Result<impl Future<Output = Never>, String> {
+        use crate::transport::websocket::{run_websocket_listener, tls_acceptor};
+
+        let Some(config) = &info.config.websocket_config else {
+            return Ok(Either::Left(futures::future::pending()));
+        };
+        let tls = tls_acceptor(&info)?;
+        let listener = TcpListener::bind((info.config.tcp_config.host.as_str(), config.port))
+            .await
+            .map_err(|e| format!("Failed to bind WebSocket socket: {e:?}"))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to bind WebSocket socket: {e:?}"))?;
+        info!("Now listening for WebSocket connections on {addr}");
+        info.websocket_port
+            .store(addr.port(), std::sync::atomic::Ordering::Relaxed);
+
+        Ok(Either::Right(run_websocket_listener(
+            info.clone(),
+            listener,
+            tls,
+            send,
+        )))
+    }
+
+    #[cfg(not(feature = "websocket"))]
+    async fn start_websocket_listener(
+        _info: Arc<ServerInfo>,
+        _send: tokio::sync::mpsc::Sender<(BoxedStream, SocketAddr)>,
+    ) -> Result<impl Future<Output = Never>, String> {
+        Ok(futures::future::pending())
+    }
+
+    /// Start the HTTPS listener, if the HTTPS transport is configured.
+    /// Returns a future accepting and serving connections.
+    #[cfg(feature = "https")]
+    async fn start_https_listener(&self) -> Result<impl Future<Output = Never>, String> {
+        use crate::transport::https::{run_https_listener, tls_acceptor, HttpsContext};
+
+        let (Some(config), Some(tls)) = (&self.info.config.https_config, tls_acceptor(&self.info)?)
+        else {
+            return Ok(Either::Left(futures::future::pending()));
+        };
+        let listener = TcpListener::bind((self.info.config.tcp_config.host.as_str(), config.port))
+            .await
+            .map_err(|e| format!("Failed to bind HTTPS socket: {e:?}"))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to bind HTTPS socket: {e:?}"))?;
+        info!("Now listening for HTTPS connections on {addr}");
+        self.info
+            .https_port
+            .store(addr.port(), std::sync::atomic::Ordering::Relaxed);
+
+        let context = HttpsContext {
+            info: self.info.clone(),
+            session_manager: self.session_manager.clone(),
+            certificate_store: self.certificate_store.clone(),
+            node_managers: self.node_managers.clone(),
+            subscriptions: self.subscriptions.clone(),
+            secure_channel_id: self.info.secure_channel_id_handle.next(),
+        };
+        Ok(Either::Right(run_https_listener(
+            context,
+            listener,
+            tls,
+            self.token.clone(),
+        )))
+    }
+
+    #[cfg(not(feature = "https"))]
+    async fn start_https_listener(&self) -> Result<impl Future<Output = Never>, String> {
+        Ok(futures::future::pending())
+    }
+
+    /// Start a new connection on the given stream. `closed` is dropped once the
+    /// connection terminates.
+    fn start_connection(
+        &mut self,
+        socket: BoxedStream,
+        connection_id: u32,
+        closed: Option<tokio::sync::oneshot::Sender<()>>,
+    ) {
+        let conn = SessionStarter::new(
+            TcpConnector::new(
+                socket,
+                TransportConfig {
+                    send_buffer_size: self.info.config.limits.send_buffer_size,
+                    max_message_size: self.info.config.limits.max_message_size,
+                    max_chunk_count: self.info.config.limits.max_chunk_count,
+                    receive_buffer_size: self.info.config.limits.receive_buffer_size,
+                    hello_timeout: Duration::from_secs(
+                        self.info.config.tcp_config.hello_timeout as u64,
+                    ),
+                },
+                self.info.decoding_options(),
+            ),
+            self.info.clone(),
+            self.session_manager.clone(),
+            self.certificate_store.clone(),
+            self.node_managers.clone(),
+            self.subscriptions.clone(),
+        );
+
+        let (send, recv) = tokio::sync::mpsc::channel(5);
+        let handle = tokio::spawn(conn.run(recv).map(move |_| {
+            drop(closed);
+            connection_id
+        }));
+        self.connections.push(handle);
+        self.connection_map
+            .insert(connection_id, ConnectionInfo { command_send: send });
+    }
+
     /// Run the server. The provided `token` can be used to stop the server gracefully.
     pub async fn run(self) -> Result<(), String> {
         let addr = self.get_socket_address();
@@ -445,6 +620,12 @@ impl Server {
     fn log_endpoint_info(&self) {
         info!("OPC UA Server: {}", self.info.application_name);
         info!("Base url: {}", self.info.base_endpoint());
+        if let Some(websocket) = self.info.websocket_base_endpoint() {
+            info!("WebSocket base url: {websocket}");
+        }
+        if let Some(https) = self.info.https_base_endpoint() {
+            info!("HTTPS base url: {https}");
+        }
         info!("Supported endpoints:");
         for (id, endpoint) in &self.config.endpoints {
             let users: Vec<String> = endpoint.user_token_ids.iter().cloned().collect();
@@ -470,3 +651,56 @@ impl Server {
         }
     }
 }
+
+/// Read the server certificates for the key types needed by the configured endpoints, other
+/// than `default_key_type`, which is the key type of the default server certificate. Missing
+/// certificates are created for `application_description` if it is set.
+pub(crate) fn read_other_server_certificates(
+    store: &CertificateStore,
+    config: &ServerConfig,
+    default_key_type: KeyType,
+    application_description: Option<&ApplicationDescription>,
+) -> Vec<(Arc<X509>, Arc<PrivateKey>)> {
+    let mut key_types = Vec::new();
+    for key_type in config
+        .endpoints
+        .values()
+        .filter_map(|e| e.security_policy().key_type())
+    {
+        if key_type != default_key_type && !key_types.contains(&key_type) {
+            key_types.push(key_type);
+        }
+    }
+
+    let mut certificates = Vec::new();
+    for key_type in key_types {
+        let backend = store.backend();
+        let (cert, pkey) = match (
+            backend.read_own_cert_for_key_type(key_type),
+            backend.read_own_pkey_for_key_type(key_type),
+            application_description,
+        ) {
+            (Ok(cert), Ok(pkey), _) => (cert, pkey),
+            (_, _, Some(application_description)) => {
+                info!(
+                    "Creating sample {key_type:?} application instance certificate and private key"
+                );
+                let mut x509_data = X509Data::from(application_description.clone());
+                x509_data.key_type = key_type;
+                match store.create_and_store_application_instance_cert(&x509_data, false) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        error!("Certificate creation failed, error = {e}");
+                        continue;
+                    }
+                }
+            }
+            (Err(e), _, None) | (_, Err(e), None) => {
+                error!("Failed to get {key_type:?} server certificate or private key: {e}");
+                continue;
+            }
+        };
+        certificates.push((Arc::new(cert), Arc::new(pkey)));
+    }
+    certificates
+}
@@ -8,9 +8,12 @@ use opcua_nodes::{DefaultTypeTree, TypeTree};
 use tokio_util::sync::CancellationToken;
 
 use opcua_core::sync::RwLock;
-use opcua_types::{AttributeId, DataValue, LocalizedText, ServerState, VariableId};
+use opcua_crypto::{PrivateKey, X509};
+use opcua_types::{AttributeId, DataValue, LocalizedText, ServerState, StatusCode, VariableId};
 
-use crate::ServerStatusWrapper;
+use crate::{
+    conditions::ConditionManager, local_discovery::LocalDiscoveryServer, ServerStatusWrapper,
+};
 
 use super::{
     info::ServerInfo, node_manager::NodeManagers, session::manager::SessionManager,
@@ -55,6 +58,32 @@ impl ServerHandle {
         }
     }
 
+    /// Replace the server's application instance certificate and private key at runtime,
+    /// for example when the certificate is renewed. The new certificate is written to the
+    /// certificate store and used by new secure channels and in `GetEndpoints` responses.
+    /// Existing secure channels keep the certificate they were opened with until they close.
+    ///
+    /// TLS listeners for `opc.wss://` and `https://` keep the certificate they were started with.
+    ///
+    /// Returns `BadSecurityChecksFailed` if `pkey` is not the private key of `cert`.
+    pub fn set_server_certificate(&self, cert: X509, pkey: PrivateKey) -> Result<(), StatusCode> {
+        self.info
+            .certificate_manager
+            .set_server_certificate(&self.info, cert, pkey)
+    }
+
+    /// Reload the server's application instance certificate and private key from the
+    /// certificate store, after they were replaced in the store by some other means.
+    /// This has the same effect as [`ServerHandle::set_server_certificate`].
+    ///
+    /// Secure channels read the certificate from the store when they are opened, so they
+    /// use a replaced certificate even before this is called.
+    pub fn reload_server_certificate(&self) -> Result<(), StatusCode> {
+        self.info
+            .certificate_manager
+            .reload_server_certificate(&self.info)
+    }
+
     /// Get a reference to the ServerInfo, containing configuration and other shared server data.
     pub fn info(&self) -> &Arc<ServerInfo> {
         &self.info
@@ -65,6 +94,18 @@ impl ServerHandle {
         &self.subscriptions
     }
 
+    /// Get a reference to the condition manager, containing the state of all
+    /// alarms and conditions on the server.
+    pub fn conditions(&self) -> &Arc<ConditionManager> {
+        &self.info.conditions
+    }
+
+    /// Get a reference to the servers registered with this server, if it is
+    /// running as a local discovery server.
+    pub fn local_discovery(&self) -> Option<&Arc<LocalDiscoveryServer>> {
+        self.info.local_discovery.as_ref()
+    }
+
     /// Set the service level, properly notifying subscribed clients of the change.
     pub fn set_service_level(&self, sl: u8) {
         self.service_level
@@ -6,26 +6,23 @@ use std::{
 
 use futures::{future::Either, stream::FuturesUnordered, Future, StreamExt};
 use log::{debug, error, trace, warn};
-use opcua_core::{trace_read_lock, trace_write_lock, Message, RequestMessage, ResponseMessage};
+use opcua_core::{RequestMessage, ResponseMessage};
 
 use opcua_core::{
     comms::{
         secure_channel::SecureChannel, security_header::SecurityHeader, tcp_types::ErrorMessage,
     },
-    config::Config,
     handle::AtomicHandle,
     sync::RwLock,
 };
 use opcua_crypto::{CertificateStore, SecurityPolicy};
 use opcua_types::{
-    ChannelSecurityToken, DateTime, FindServersResponse, GetEndpointsResponse, MessageSecurityMode,
-    OpenSecureChannelRequest, OpenSecureChannelResponse, ResponseHeader, SecurityTokenRequestType,
-    ServiceFault, StatusCode,
+    ChannelSecurityToken, DateTime, MessageSecurityMode, OpenSecureChannelRequest,
+    OpenSecureChannelResponse, ResponseHeader, SecurityTokenRequestType, ServiceFault, StatusCode,
 };
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    authenticator::UserToken,
     info::ServerInfo,
     node_manager::NodeManagers,
     subscriptions::SubscriptionCache,
@@ -34,9 +31,8 @@ use crate::{
 };
 
 use super::{
-    instance::Session,
-    manager::{activate_session, close_session, SessionManager},
-    message_handler::MessageHandler,
+    manager::SessionManager,
+    processor::{ProcessResult, RequestProcessor},
 };
 
 pub(crate) struct Response {
@@ -67,16 +63,15 @@ pub(crate) enum ControllerCommand {
     Close,
 }
 
-type PendingMessageResponse = dyn Future<Output = Result<Response, String>> + Send + Sync + 'static;
+pub(crate) type PendingMessageResponse =
+    dyn Future<Output = Result<Response, String>> + Send + Sync + 'static;
 
 /// Master type managing a single connection.
 pub(crate) struct SessionController {
     channel: SecureChannel,
     transport: TcpTransport,
     secure_channel_state: SecureChannelState,
-    session_manager: Arc<RwLock<SessionManager>>,
-    certificate_store: Arc<RwLock<CertificateStore>>,
-    message_handler: MessageHandler,
+    processor: RequestProcessor,
     pending_messages: FuturesUnordered<Pin<Box<PendingMessageResponse>>>,
     info: Arc<ServerInfo>,
     deadline: Instant,
@@ -171,9 +166,13 @@ impl SessionController {
             channel,
             transport,
             secure_channel_state: SecureChannelState::new(info.secure_channel_id_handle.clone()),
-            session_manager,
-            certificate_store,
-            message_handler: MessageHandler::new(info.clone(), node_managers, subscriptions),
+            processor: RequestProcessor::new(
+                session_manager,
+                certificate_store,
+                info.clone(),
+                node_managers,
+                subscriptions,
+            ),
             deadline: Instant::now()
                 + Duration::from_secs(info.config.tcp_config.hello_timeout as u64),
             info,
@@ -292,248 +291,26 @@ impl SessionController {
 
             RequestMessage::CloseSecureChannel(_r) => RequestProcessResult::Close,
 
-            RequestMessage::CreateSession(request) => {
-                let mut mgr = trace_write_lock!(self.session_manager);
-                let res = mgr.create_session(&mut self.channel, &self.certificate_store, &request);
-                drop(mgr);
-                self.process_service_result(res, request.request_header.request_handle, id)
-            }
-
-            RequestMessage::ActivateSession(request) => {
-                let res = activate_session(
-                    &self.session_manager,
-                    &mut self.channel,
-                    &request,
-                    &mut self.message_handler,
-                )
-                .await;
-                self.process_service_result(res, request.request_header.request_handle, id)
-            }
-
-            RequestMessage::CloseSession(request) => {
-                let res = close_session(
-                    &self.session_manager,
-                    &mut self.channel,
-                    &mut self.message_handler,
-                    &request,
-                )
-                .await;
-                self.process_service_result(res, request.request_header.request_handle, id)
-            }
-            RequestMessage::GetEndpoints(request) => {
-                // TODO some of the arguments in the request are ignored
-                //  localeIds - list of locales to use for human readable strings (in the endpoint descriptions)
-
-                // TODO audit - generate event for failed service invocation
-
-                let endpoints = self
-                    .info
-                    .endpoints(&request.endpoint_url, &request.profile_uris);
-                self.process_service_result(
-                    Ok(GetEndpointsResponse {
-                        response_header: ResponseHeader::new_good(&request.request_header),
-                        endpoints,
-                    }),
-                    request.request_header.request_handle,
-                    id,
-                )
-            }
-            RequestMessage::FindServers(request) => {
-                let desc = self.info.config.application_description();
-                let mut servers = vec![desc];
-
-                // TODO endpoint URL
-
-                // TODO localeids, filter out servers that do not support locale ids
-
-                // Filter servers that do not have a matching application uri
-                if let Some(ref server_uris) = request.server_uris {
-                    if !server_uris.is_empty() {
-                        // Filter the servers down
-                        servers.retain(|server| {
-                            server_uris.iter().any(|uri| *uri == server.application_uri)
-                        });
-                    }
-                }
-
-                let servers = Some(servers);
-
-                self.process_service_result(
-                    Ok(FindServersResponse {
-                        response_header: ResponseHeader::new_good(&request.request_header),
-                        servers,
-                    }),
-                    request.request_header.request_handle,
-                    id,
-                )
-            }
-            RequestMessage::FindServersOnNetwork(request) => {
-                if let Err(e) = self.transport.enqueue_message_for_send(
-                    &mut self.channel,
-                    ServiceFault::new(&request.request_header, StatusCode::BadServiceUnsupported)
-                        .into(),
-                    id,
-                ) {
-                    error!("Failed to send request response: {e}");
-                    RequestProcessResult::Close
-                } else {
-                    RequestProcessResult::Ok
-                }
-            }
-            RequestMessage::RegisterServer(request) => {
-                if let Err(e) = self.transport.enqueue_message_for_send(
-                    &mut self.channel,
-                    ServiceFault::new(&request.request_header, StatusCode::BadServiceUnsupported)
-                        .into(),
-                    id,
-                ) {
-                    error!("Failed to send request response: {e}");
-                    RequestProcessResult::Close
-                } else {
-                    RequestProcessResult::Ok
-                }
-            }
-            RequestMessage::RegisterServer2(request) => {
-                if let Err(e) = self.transport.enqueue_message_for_send(
-                    &mut self.channel,
-                    ServiceFault::new(&request.request_header, StatusCode::BadServiceUnsupported)
-                        .into(),
-                    id,
-                ) {
-                    error!("Failed to send request response: {e}");
-                    RequestProcessResult::Close
-                } else {
-                    RequestProcessResult::Ok
-                }
-            }
-
-            message => {
-                let now = Instant::now();
-                let mgr = trace_read_lock!(self.session_manager);
-                let session = mgr.find_by_token(&message.request_header().authentication_token);
-
-                let (session_id, session, user_token) =
-                    match Self::validate_request(&message, session, &self.channel) {
-                        Ok(s) => s,
-                        Err(e) => {
-                            match self
-                                .transport
-                                .enqueue_message_for_send(&mut self.channel, e, id)
-                            {
-                                Ok(_) => return RequestProcessResult::Ok,
-                                Err(e) => {
-                                    error!("Failed to send request response: {e}");
-                                    return RequestProcessResult::Close;
-                                }
-                            }
-                        }
-                    };
-                let deadline = {
-                    let timeout = message.request_header().timeout_hint;
-                    let max_timeout = self.info.config.max_timeout_ms;
-                    let timeout = if max_timeout == 0 {
-                        timeout
-                    } else {
-                        max_timeout.max(timeout)
-                    };
-                    if timeout == 0 {
-                        // Just set some huge value. A request taking a day can probably
-                        // be safely canceled...
-                        now + Duration::from_secs(60 * 60 * 24)
+            message => match self.processor.process(&mut self.channel, message, id).await {
+                ProcessResult::Response(message) => {
+                    if let Err(e) =
+                        self.transport
+                            .enqueue_message_for_send(&mut self.channel, message, id)
+                    {
+                        error!("Failed to send request response: {e}");
+                        RequestProcessResult::Close
                     } else {
-                        now + Duration::from_millis(timeout.into())
-                    }
-                };
-                let request_handle = message.request_handle();
-
-                match self
-                    .message_handler
-                    .handle_message(message, session_id, session, user_token, id)
-                {
-                    super::message_handler::HandleMessageResult::AsyncMessage(mut handle) => {
-                        self.pending_messages
-                            .push(Box::pin(async move {
-                                // Select biased because if for some reason there's a long time between polls,
-                                // we want to return the response even if the timeout expired. We only want to send a timeout
-                                // if the call has not been finished yet.
-                                tokio::select! {
-                                    biased;
-                                    r = &mut handle => {
-                                        r.map_err(|e| e.to_string())
-                                    }
-                                    _ = tokio::time::sleep_until(deadline.into()) => {
-                                        handle.abort();
-                                        Ok(Response { message: ServiceFault::new(request_handle, StatusCode::BadTimeout).into(), request_id: id })
-                                    }
-                                }
-                            }));
-                        RequestProcessResult::Ok
-                    }
-                    super::message_handler::HandleMessageResult::SyncMessage(s) => {
-                        if let Err(e) = self.transport.enqueue_message_for_send(
-                            &mut self.channel,
-                            s.message,
-                            s.request_id,
-                        ) {
-                            error!("Failed to send response: {e}");
-                            return RequestProcessResult::Close;
-                        }
-                        RequestProcessResult::Ok
-                    }
-                    super::message_handler::HandleMessageResult::PublishResponse(resp) => {
-                        self.pending_messages.push(Box::pin(resp.recv()));
                         RequestProcessResult::Ok
                     }
                 }
-            }
-        }
-    }
-
-    fn process_service_result(
-        &mut self,
-        res: Result<impl Into<ResponseMessage>, StatusCode>,
-        request_handle: u32,
-        request_id: u32,
-    ) -> RequestProcessResult {
-        let message = match res {
-            Ok(m) => m.into(),
-            Err(e) => ServiceFault::new(request_handle, e).into(),
-        };
-        if let Err(e) =
-            self.transport
-                .enqueue_message_for_send(&mut self.channel, message, request_id)
-        {
-            error!("Failed to send request response: {e}");
-            RequestProcessResult::Close
-        } else {
-            RequestProcessResult::Ok
+                ProcessResult::Pending(fut) => {
+                    self.pending_messages.push(fut);
+                    RequestProcessResult::Ok
+                }
+            },
         }
     }
 
-    fn validate_request(
-        message: &RequestMessage,
-        session: Option<Arc<RwLock<Session>>>,
-        channel: &SecureChannel,
-    ) -> Result<(u32, Arc<RwLock<Session>>, UserToken), ResponseMessage> {
-        let header = message.request_header();
-
-        let Some(session) = session else {
-            return Err(ServiceFault::new(header, StatusCode::BadSessionIdInvalid).into());
-        };
-
-        let session_lock = trace_read_lock!(session);
-        let id = session_lock.session_id_numeric();
-
-        let user_token = (move || {
-            let token = session_lock.validate_activated()?;
-            session_lock.validate_secure_channel_id(channel.secure_channel_id())?;
-            session_lock.validate_timed_out()?;
-            Ok(token.clone())
-        })()
-        .map_err(|e| ServiceFault::new(header, e))?;
-        Ok((id, session, user_token))
-    }
-
     fn open_secure_channel(
         &mut self,
         security_header: &SecurityHeader,
@@ -650,7 +427,7 @@ impl SessionController {
             && (security_mode == MessageSecurityMode::Sign
                 || security_mode == MessageSecurityMode::SignAndEncrypt)
         {
-            self.channel.derive_keys();
+            self.channel.derive_keys()?;
         }
 
         let response = OpenSecureChannelResponse {
@@ -7,11 +7,11 @@ use log::error;
 
 use super::continuation_points::ContinuationPoint;
 use super::manager::next_session_id;
-use crate::authenticator::UserToken;
+use crate::authenticator::{AuthenticatedUser, UserToken};
 use crate::identity_token::IdentityToken;
 use crate::info::ServerInfo;
 use crate::node_manager::{BrowseContinuationPoint, QueryContinuationPoint};
-use opcua_crypto::X509;
+use opcua_crypto::{EphemeralKey, X509};
 use opcua_types::{
     ApplicationDescription, ByteString, MessageSecurityMode, NodeId, StatusCode, UAString,
 };
@@ -66,8 +66,12 @@ pub struct Session {
     query_continuation_points: HashMap<ByteString, QueryContinuationPoint>,
     /// User token.
     user_token: Option<UserToken>,
+    /// Roles granted to this session by its identity token.
+    identity_roles: Vec<String>,
     /// Whether the session has been closed.
     is_closed: bool,
+    /// Ephemeral key last sent to the client, used to decrypt an `EccEncryptedSecret`.
+    ephemeral_key: Option<EphemeralKey>,
 }
 
 impl Session {
@@ -117,9 +121,11 @@ impl Session {
             history_continuation_points: Default::default(),
             query_continuation_points: Default::default(),
             user_token: None,
+            identity_roles: Vec::new(),
             application_description,
             message_security_mode,
             is_closed: false,
+            ephemeral_key: None,
         }
     }
 
@@ -178,9 +184,10 @@ impl Session {
         server_nonce: ByteString,
         identity: IdentityToken,
         locale_ids: Option<Vec<UAString>>,
-        user_token: UserToken,
+        user: AuthenticatedUser,
     ) {
-        self.user_token = Some(user_token);
+        self.user_token = Some(user.token);
+        self.identity_roles = user.roles;
         self.secure_channel_id = secure_channel_id;
         self.session_nonce = server_nonce;
         self.user_identity = identity;
@@ -215,6 +222,16 @@ impl Session {
         &self.session_nonce
     }
 
+    /// Get the ephemeral key last sent to the client, if it asked for one.
+    pub(crate) fn ephemeral_key(&self) -> Option<&EphemeralKey> {
+        self.ephemeral_key.as_ref()
+    }
+
+    /// Set the ephemeral key sent to the client.
+    pub(crate) fn set_ephemeral_key(&mut self, ephemeral_key: EphemeralKey) {
+        self.ephemeral_key = Some(ephemeral_key);
+    }
+
     /// Whether this session is activated.
     pub fn is_activated(&self) -> bool {
         self.user_token.is_some() && !self.is_closed
@@ -301,6 +318,13 @@ impl Session {
         self.user_token.as_ref()
     }
 
+    /// Get the roles granted to this session by the identity token it was activated with,
+    /// such as the roles in the claims of a JSON Web Token. Other sessions of the same
+    /// user do not share these roles.
+    pub fn identity_roles(&self) -> &[String] {
+        &self.identity_roles
+    }
+
     /// Get the message security mode used by this session.
     pub fn message_security_mode(&self) -> MessageSecurityMode {
         self.message_security_mode
@@ -9,15 +9,17 @@ use std::{
 
 use log::{error, info};
 use opcua_core::{comms::secure_channel::SecureChannel, trace_read_lock, trace_write_lock};
-use opcua_crypto::{random, security_policy::SecurityPolicy, CertificateStore};
+use opcua_crypto::{
+    random, security_policy::SecurityPolicy, user_identity, CertificateStore, EphemeralKey, X509,
+};
 use parking_lot::RwLock;
 use tokio::sync::Notify;
 
 use crate::{identity_token::IdentityToken, info::ServerInfo};
 use opcua_types::{
     ActivateSessionRequest, ActivateSessionResponse, CloseSessionRequest, CloseSessionResponse,
-    CreateSessionRequest, CreateSessionResponse, Error, NodeId, ResponseHeader, SignatureData,
-    StatusCode,
+    CreateSessionRequest, CreateSessionResponse, Error, ExtensionObject, NodeId, RequestHeader,
+    ResponseHeader, SignatureData, StatusCode,
 };
 
 use super::{instance::Session, message_handler::MessageHandler};
@@ -30,6 +32,25 @@ pub(super) fn next_session_id() -> (NodeId, u32) {
     (NodeId::new(1, session_id), session_id)
 }
 
+/// Create a new ephemeral key if the client asked for one in the request header, returning the
+/// key and the additional header of the response. Clients need the key to encrypt user identity
+/// token secrets with ECC security policies.
+fn ephemeral_key_for_request(
+    request_header: &RequestHeader,
+    channel: &SecureChannel,
+) -> Option<(EphemeralKey, ExtensionObject)> {
+    let security_policy = user_identity::requested_ecdh_policy(&request_header.additional_header)?;
+    let ephemeral_key = EphemeralKey::new(security_policy)?;
+    let pkey = channel.private_key()?;
+    match user_identity::ecdh_key_response_header(security_policy, &ephemeral_key, &pkey) {
+        Ok(header) => Some((ephemeral_key, header)),
+        Err(e) => {
+            error!("Cannot create an ephemeral key for security policy {security_policy}: {e}");
+            None
+        }
+    }
+}
+
 /// Manages all sessions on the server.
 pub struct SessionManager {
     sessions: HashMap<NodeId, Arc<RwLock<Session>>>,
@@ -109,9 +130,11 @@ impl SessionManager {
             .min(request.requested_session_timeout.floor() as u64);
         let max_request_message_size = self.info.config.limits.max_message_size as u32;
 
-        let server_signature = if let Some(ref pkey) = self.info.server_pkey {
+        // Sessions use the certificate of their secure channel, which is not necessarily the
+        // current server certificate if it was replaced after the channel was opened.
+        let server_signature = if let Some(pkey) = channel.private_key() {
             opcua_crypto::create_signature_data(
-                pkey,
+                &pkey,
                 security_policy,
                 &request.client_certificate,
                 &request.client_nonce,
@@ -129,10 +152,13 @@ impl SessionManager {
 
         let authentication_token = NodeId::new(0, random::byte_string(32));
         let server_nonce = security_policy.random_nonce();
-        let server_certificate = self.info.server_certificate_as_byte_string();
+        let server_certificate = channel
+            .cert()
+            .map(|cert| cert.as_byte_string())
+            .unwrap_or_default();
         let server_endpoints = Some(endpoints);
 
-        let session = Session::create(
+        let mut session = Session::create(
             &self.info,
             authentication_token.clone(),
             channel.secure_channel_id(),
@@ -150,6 +176,14 @@ impl SessionManager {
         );
         info!("Created new session with ID {}", session.session_id());
 
+        let mut response_header = ResponseHeader::new_good(&request.request_header);
+        if let Some((ephemeral_key, header)) =
+            ephemeral_key_for_request(&request.request_header, channel)
+        {
+            session.set_ephemeral_key(ephemeral_key);
+            response_header.additional_header = header;
+        }
+
         let session_id = session.session_id().clone();
         self.sessions
             .insert(session_id.clone(), Arc::new(RwLock::new(session)));
@@ -157,7 +191,7 @@ impl SessionManager {
         self.notify.notify_waiters();
 
         Ok(CreateSessionResponse {
-            response_header: ResponseHeader::new_good(&request.request_header),
+            response_header,
             session_id,
             authentication_token,
             revised_session_timeout: session_timeout as f64,
@@ -172,12 +206,12 @@ impl SessionManager {
 
     fn verify_client_signature(
         security_policy: SecurityPolicy,
-        info: &ServerInfo,
+        server_certificate: Option<&X509>,
         session: &Session,
         client_signature: &SignatureData,
     ) -> Result<(), Error> {
         if let Some(client_certificate) = session.client_certificate() {
-            if let Some(ref server_certificate) = info.server_certificate {
+            if let Some(server_certificate) = server_certificate {
                 opcua_crypto::verify_signature_data(
                     client_signature,
                     security_policy,
@@ -209,6 +243,9 @@ impl SessionManager {
 
         let mut session = trace_write_lock!(session);
         session.close();
+        self.info
+            .certificate_manager
+            .session_closed(session.session_id_numeric());
     }
 
     pub(crate) fn check_session_expiry(&self) -> (Instant, Vec<NodeId>) {
@@ -261,6 +298,7 @@ pub(crate) async fn close_session(
             let mut session_lck = trace_write_lock!(session);
             session_lck.close();
         }
+        mgr.info.certificate_manager.session_closed(id);
         (session, id, token)
     };
 
@@ -288,14 +326,14 @@ pub(crate) async fn activate_session(
     let security_mode = channel.security_mode();
     let secure_channel_id = channel.secure_channel_id();
     let server_nonce = security_policy.random_nonce();
-    let (endpoint_url, session_nonce, session_lck, info) = {
+    let (endpoint_url, session_nonce, ephemeral_key, session_lck, info) = {
         let mgr = trace_read_lock!(mgr_lck);
         let Some(session_lck) = mgr.find_by_token(&request.request_header.authentication_token)
         else {
             return Err(StatusCode::BadSessionIdInvalid);
         };
 
-        let (endpoint_url, session_nonce) = {
+        let (endpoint_url, session_nonce, ephemeral_key) = {
             let session = trace_read_lock!(session_lck);
             session.validate_timed_out()?;
 
@@ -313,27 +351,39 @@ pub(crate) async fn activate_session(
             if security_policy != SecurityPolicy::None {
                 SessionManager::verify_client_signature(
                     security_policy,
-                    &mgr.info,
+                    channel.cert().as_ref(),
                     &session,
                     &request.client_signature,
                 )?;
             }
-            (endpoint_url, session.session_nonce().clone())
+            (
+                endpoint_url,
+                session.session_nonce().clone(),
+                session.ephemeral_key().cloned(),
+            )
         };
-        (endpoint_url, session_nonce, session_lck, mgr.info.clone())
+        (
+            endpoint_url,
+            session_nonce,
+            ephemeral_key,
+            session_lck,
+            mgr.info.clone(),
+        )
     };
 
-    let user_token = info
-        .authenticate_endpoint(
+    let user = info
+        .authenticate_endpoint_user(
             request,
             &endpoint_url,
-            security_policy,
-            security_mode,
+            channel,
             request.user_identity_token.clone(),
             &session_nonce,
+            ephemeral_key.as_ref(),
         )
         .await?;
 
+    let mut response_header = ResponseHeader::new_good(&request.request_header);
+
     let (server_nonce, session_id) = {
         let mut session = trace_write_lock!(session_lck);
 
@@ -355,15 +405,22 @@ pub(crate) async fn activate_session(
             server_nonce,
             IdentityToken::new(request.user_identity_token.clone()),
             request.locale_ids.clone(),
-            user_token.clone(),
+            user.clone(),
         );
+        // The client gets a new ephemeral key for the next activation.
+        if let Some((ephemeral_key, header)) =
+            ephemeral_key_for_request(&request.request_header, channel)
+        {
+            session.set_ephemeral_key(ephemeral_key);
+            response_header.additional_header = header;
+        }
         (
             session.session_nonce().clone(),
             session.session_id_numeric(),
         )
     };
 
-    let namespaces = handler.get_namespaces_for_user(session_lck.clone(), session_id, user_token);
+    let namespaces = handler.get_namespaces_for_user(session_lck.clone(), session_id, user.token);
     {
         channel.set_namespaces(namespaces);
     }
@@ -371,7 +428,7 @@ pub(crate) async fn activate_session(
     // TODO: Audit
 
     Ok(ActivateSessionResponse {
-        response_header: ResponseHeader::new_good(&request.request_header),
+        response_header,
         server_nonce,
         results: None,
         diagnostic_infos: None,
@@ -4,4 +4,5 @@ pub mod instance;
 pub mod manager;
 #[macro_use]
 pub mod message_handler;
+pub(crate) mod processor;
 mod services;
@@ -0,0 +1,318 @@
+use std::{
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::error;
+use opcua_core::{
+    comms::secure_channel::SecureChannel, config::Config, sync::RwLock, trace_read_lock,
+    trace_write_lock, Message, RequestMessage, ResponseMessage,
+};
+use opcua_crypto::CertificateStore;
+use opcua_types::{
+    ExtensionObject, FindServersOnNetworkResponse, FindServersResponse, GetEndpointsResponse,
+    MessageSecurityMode, RegisterServer2Response, RegisterServerResponse, RegisteredServer,
+    ResponseHeader, ServiceFault, StatusCode,
+};
+
+use crate::{
+    authenticator::UserToken, info::ServerInfo, node_manager::NodeManagers,
+    subscriptions::SubscriptionCache,
+};
+
+use super::{
+    controller::{PendingMessageResponse, Response},
+    instance::Session,
+    manager::{activate_session, close_session, SessionManager},
+    message_handler::{HandleMessageResult, MessageHandler},
+};
+
+/// Result of processing a service request.
+pub(crate) enum ProcessResult {
+    /// The response is available immediately.
+    Response(ResponseMessage),
+    /// The response is returned by a future, once the request completes.
+    Pending(Pin<Box<PendingMessageResponse>>),
+}
+
+/// Processes service requests received on a secure channel, independent of the
+/// transport. Secure channel requests must be handled by the caller.
+pub(crate) struct RequestProcessor {
+    session_manager: Arc<RwLock<SessionManager>>,
+    certificate_store: Arc<RwLock<CertificateStore>>,
+    message_handler: MessageHandler,
+    info: Arc<ServerInfo>,
+}
+
+impl RequestProcessor {
+    pub fn new(
+        session_manager: Arc<RwLock<SessionManager>>,
+        certificate_store: Arc<RwLock<CertificateStore>>,
+        info: Arc<ServerInfo>,
+        node_managers: NodeManagers,
+        subscriptions: Arc<SubscriptionCache>,
+    ) -> Self {
+        Self {
+            session_manager,
+            certificate_store,
+            message_handler: MessageHandler::new(info.clone(), node_managers, subscriptions),
+            info,
+        }
+    }
+
+    /// Process the service request `message` with ID `id`, received on `channel`.
+    pub async fn process(
+        &mut self,
+        channel: &mut SecureChannel,
+        message: RequestMessage,
+        id: u32,
+    ) -> ProcessResult {
+        let request_handle = message.request_handle();
+        match message {
+            RequestMessage::OpenSecureChannel(_) | RequestMessage::CloseSecureChannel(_) => {
+                Self::service_result(
+                    Err::<ResponseMessage, _>(StatusCode::BadServiceUnsupported),
+                    request_handle,
+                )
+            }
+
+            RequestMessage::CreateSession(request) => {
+                let mut mgr = trace_write_lock!(self.session_manager);
+                let res = mgr.create_session(channel, &self.certificate_store, &request);
+                drop(mgr);
+                Self::service_result(res, request_handle)
+            }
+
+            RequestMessage::ActivateSession(request) => {
+                let res = activate_session(
+                    &self.session_manager,
+                    channel,
+                    &request,
+                    &mut self.message_handler,
+                )
+                .await;
+                Self::service_result(res, request_handle)
+            }
+
+            RequestMessage::CloseSession(request) => {
+                let res = close_session(
+                    &self.session_manager,
+                    channel,
+                    &mut self.message_handler,
+                    &request,
+                )
+                .await;
+                Self::service_result(res, request_handle)
+            }
+            RequestMessage::GetEndpoints(request) => {
+                // TODO some of the arguments in the request are ignored
+                //  localeIds - list of locales to use for human readable strings (in the endpoint descriptions)
+
+                // TODO audit - generate event for failed service invocation
+
+                let endpoints = self
+                    .info
+                    .endpoints(&request.endpoint_url, &request.profile_uris);
+                Self::service_result(
+                    Ok(GetEndpointsResponse {
+                        response_header: ResponseHeader::new_good(&request.request_header),
+                        endpoints,
+                    }),
+                    request_handle,
+                )
+            }
+            RequestMessage::FindServers(request) => {
+                let desc = self.info.config.application_description();
+                let mut servers = vec![desc];
+
+                // TODO endpoint URL
+
+                if let Some(lds) = &self.info.local_discovery {
+                    servers.extend(lds.application_descriptions(
+                        request.locale_ids.as_deref().unwrap_or_default(),
+                    ));
+                }
+
+                // Filter servers that do not have a matching application uri
+                if let Some(ref server_uris) = request.server_uris {
+                    if !server_uris.is_empty() {
+                        // Filter the servers down
+                        servers.retain(|server| {
+                            server_uris.iter().any(|uri| *uri == server.application_uri)
+                        });
+                    }
+                }
+
+                let servers = Some(servers);
+
+                Self::service_result(
+                    Ok(FindServersResponse {
+                        response_header: ResponseHeader::new_good(&request.request_header),
+                        servers,
+                    }),
+                    request_handle,
+                )
+            }
+            RequestMessage::FindServersOnNetwork(request) => {
+                let res = match &self.info.local_discovery {
+                    Some(lds) => Ok(FindServersOnNetworkResponse {
+                        response_header: ResponseHeader::new_good(&request.request_header),
+                        last_counter_reset_time: lds.last_counter_reset_time(),
+                        servers: Some(
+                            lds.servers_on_network(
+                                request.starting_record_id,
+                                request.max_records_to_return,
+                                request
+                                    .server_capability_filter
+                                    .as_deref()
+                                    .unwrap_or_default(),
+                            ),
+                        ),
+                    }),
+                    None => Err(StatusCode::BadServiceUnsupported),
+                };
+                Self::service_result(res, request_handle)
+            }
+            RequestMessage::RegisterServer(request) => {
+                let res = self
+                    .register_server(channel, request.server, None)
+                    .map(|_| RegisterServerResponse {
+                        response_header: ResponseHeader::new_good(&request.request_header),
+                    });
+                Self::service_result(res, request_handle)
+            }
+            RequestMessage::RegisterServer2(request) => {
+                let res = self
+                    .register_server(
+                        channel,
+                        request.server,
+                        Some(
+                            request
+                                .discovery_configuration
+                                .as_deref()
+                                .unwrap_or_default(),
+                        ),
+                    )
+                    .map(|configuration_results| RegisterServer2Response {
+                        response_header: ResponseHeader::new_good(&request.request_header),
+                        configuration_results: Some(configuration_results),
+                        diagnostic_infos: None,
+                    });
+                Self::service_result(res, request_handle)
+            }
+
+            message => {
+                let now = Instant::now();
+                let mgr = trace_read_lock!(self.session_manager);
+                let session = mgr.find_by_token(&message.request_header().authentication_token);
+
+                let (session_id, session, user_token) =
+                    match Self::validate_request(&message, session, channel) {
+                        Ok(s) => s,
+                        Err(e) => return ProcessResult::Response(e),
+                    };
+                let deadline = {
+                    let timeout = message.request_header().timeout_hint;
+                    let max_timeout = self.info.config.max_timeout_ms;
+                    let timeout = if max_timeout == 0 {
+                        timeout
+                    } else {
+                        max_timeout.max(timeout)
+                    };
+                    if timeout == 0 {
+                        // Just set some huge value. A request taking a day can probably
+                        // be safely canceled...
+                        now + Duration::from_secs(60 * 60 * 24)
+                    } else {
+                        now + Duration::from_millis(timeout.into())
+                    }
+                };
+
+                match self
+                    .message_handler
+                    .handle_message(message, session_id, session, user_token, id)
+                {
+                    HandleMessageResult::AsyncMessage(mut handle) => {
+                        ProcessResult::Pending(Box::pin(async move {
+                            // Select biased because if for some reason there's a long time between polls,
+                            // we want to return the response even if the timeout expired. We only want to send a timeout
+                            // if the call has not been finished yet.
+                            tokio::select! {
+                                biased;
+                                r = &mut handle => {
+                                    r.map_err(|e| e.to_string())
+                                }
+                                _ = tokio::time::sleep_until(deadline.into()) => {
+                                    handle.abort();
+                                    Ok(Response { message: ServiceFault::new(request_handle, StatusCode::BadTimeout).into(), request_id: id })
+                                }
+                            }
+                        }))
+                    }
+                    HandleMessageResult::SyncMessage(s) => ProcessResult::Response(s.message),
+                    HandleMessageResult::PublishResponse(resp) => {
+                        ProcessResult::Pending(Box::pin(resp.recv()))
+                    }
+                }
+            }
+        }
+    }
+
+    fn register_server(
+        &self,
+        channel: &SecureChannel,
+        server: RegisteredServer,
+        discovery_configuration: Option<&[ExtensionObject]>,
+    ) -> Result<Vec<StatusCode>, StatusCode> {
+        let Some(lds) = &self.info.local_discovery else {
+            return Err(StatusCode::BadServiceUnsupported);
+        };
+        // Registration requires a secure channel with a certificate identifying the
+        // registered server.
+        if channel.security_mode() == MessageSecurityMode::None {
+            error!("RegisterServer was called on a secure channel without security");
+            return Err(StatusCode::BadSecurityModeInsufficient);
+        }
+        let Some(cert) = channel.remote_cert() else {
+            return Err(StatusCode::BadSecurityModeInsufficient);
+        };
+        cert.is_application_uri_valid(server.server_uri.as_ref())?;
+
+        lds.register(server, discovery_configuration)
+    }
+
+    fn service_result(
+        res: Result<impl Into<ResponseMessage>, StatusCode>,
+        request_handle: u32,
+    ) -> ProcessResult {
+        ProcessResult::Response(match res {
+            Ok(m) => m.into(),
+            Err(e) => ServiceFault::new(request_handle, e).into(),
+        })
+    }
+
+    fn validate_request(
+        message: &RequestMessage,
+        session: Option<Arc<RwLock<Session>>>,
+        channel: &SecureChannel,
+    ) -> Result<(u32, Arc<RwLock<Session>>, UserToken), ResponseMessage> {
+        let header = message.request_header();
+
+        let Some(session) = session else {
+            return Err(ServiceFault::new(header, StatusCode::BadSessionIdInvalid).into());
+        };
+
+        let session_lock = trace_read_lock!(session);
+        let id = session_lock.session_id_numeric();
+
+        let user_token = (move || {
+            let token = session_lock.validate_activated()?;
+            session_lock.validate_secure_channel_id(channel.secure_channel_id())?;
+            session_lock.validate_timed_out()?;
+            Ok(token.clone())
+        })()
+        .map_err(|e| ServiceFault::new(header, e))?;
+        Ok((id, session, user_token))
+    }
+}
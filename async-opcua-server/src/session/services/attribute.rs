@@ -158,6 +158,18 @@ pub async fn history_read(
     {
         return service_fault!(request, StatusCode::BadTooManyOperations);
     }
+    // Processed reads must specify one aggregate per node.
+    let aggregate_types = match &details {
+        HistoryReadDetails::Processed(d) => {
+            let aggregates = d.aggregate_type.as_deref().unwrap_or_default();
+            if aggregates.len() != items.len() {
+                return service_fault!(request, StatusCode::BadAggregateListMismatch);
+            }
+            Some(aggregates)
+        }
+        _ => None,
+    };
+
     let mut nodes: Vec<_> = {
         let mut session = trace_write_lock!(request.session);
         items
@@ -183,6 +195,11 @@ pub async fn history_read(
             })
             .collect()
     };
+    if let Some(aggregate_types) = aggregate_types {
+        for (node, aggregate_type) in nodes.iter_mut().zip(aggregate_types) {
+            node.set_aggregate_type(aggregate_type.clone());
+        }
+    }
 
     // If we are releasing continuation points we should not return any data.
     if request.request.release_continuation_points {
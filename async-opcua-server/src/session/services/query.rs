@@ -54,7 +54,7 @@ pub async fn query_first(
 
     let (filter_result, filter) = {
         let type_tree = context.get_type_tree_for_user();
-        ParsedContentFilter::parse(request.request.filter, type_tree.get(), false, false)
+        ParsedContentFilter::parse(request.request.filter, type_tree.get(), false, true)
     };
 
     let content_filter = match filter {
@@ -94,8 +94,6 @@ pub async fn query_first(
         context.current_node_manager_index = index;
         // All node managers must succeed. Partial success is really
         // hard to quantify for query...
-        // TODO: This is pretty much impossible to implement
-        // until we actually implement this in the core node manager.
         if let Err(e) = node_manager.query(&context, &mut query_request).await {
             return Response {
                 message: QueryFirstResponse {
@@ -173,6 +171,8 @@ pub async fn query_next(
         if let Err(e) = node_manager.query(&context, &mut query_request).await {
             return service_fault!(request, e);
         }
+        // Any following node managers start from the beginning.
+        query_request.clear_continuation_point();
 
         if query_request.is_completed() {
             break;
@@ -4,10 +4,15 @@ use log::error;
 use opcua_nodes::{Event, ParsedEventFilter, TypeTree};
 
 use super::MonitoredItemHandle;
-use crate::{info::ServerInfo, node_manager::ParsedReadValueId};
+use crate::{
+    info::ServerInfo,
+    node_manager::{
+        history_timestamp, AggregateCalculator, AggregateInterval, ParsedReadValueId, TICKS_PER_MS,
+    },
+};
 use opcua_types::{
-    match_extension_object_owned, DataChangeFilter, DataValue, DateTime, EventFieldList,
-    EventFilter, EventFilterResult, ExtensionObject, MonitoredItemCreateRequest,
+    match_extension_object_owned, AggregateFilter, AggregateFilterResult, DataChangeFilter,
+    DataValue, DateTime, EventFieldList, EventFilter, ExtensionObject, MonitoredItemCreateRequest,
     MonitoredItemModifyRequest, MonitoredItemNotification, MonitoringMode, NumericRange,
     ParsedDataChangeFilter, StatusCode, TimestampsToReturn, Variant,
 };
@@ -30,22 +35,135 @@ impl From<EventFieldList> for Notification {
     }
 }
 
+#[derive(Debug, Clone)]
+/// Parsed aggregate filter for a monitored item. This holds the raw values
+/// received in the current processing interval, and computes the aggregate
+/// once the interval has passed.
+pub struct ParsedAggregateFilter {
+    calculator: AggregateCalculator,
+    processing_interval: f64,
+    /// Start of the next interval to calculate.
+    next_start: DateTime,
+    /// Raw values in chronological order, starting with the last usable value
+    /// before `next_start`, if any.
+    values: Vec<DataValue>,
+}
+
+impl ParsedAggregateFilter {
+    /// Parse an aggregate filter. The processing interval is revised to be at least
+    /// `min_processing_interval`, and a start time in the past is moved forward to the
+    /// start of the current interval.
+    pub fn parse(
+        filter: AggregateFilter,
+        min_processing_interval: f64,
+        now: DateTime,
+    ) -> Result<(Self, AggregateFilterResult), StatusCode> {
+        // We don't know the historical configuration of the monitored node here,
+        // so always use sloped interpolation. Non-numeric values are still stepped.
+        let calculator = AggregateCalculator::new(
+            &filter.aggregate_type,
+            &filter.aggregate_configuration,
+            false,
+        )?;
+        let processing_interval = filter.processing_interval.max(min_processing_interval);
+        let step = ((processing_interval * TICKS_PER_MS) as i64).max(1);
+
+        let next_start = if filter.start_time.is_null() {
+            now
+        } else if filter.start_time < now {
+            let start = filter.start_time.ticks();
+            DateTime::from(start + (now.ticks() - start) / step * step)
+        } else {
+            filter.start_time
+        };
+
+        let result = AggregateFilterResult {
+            revised_start_time: next_start,
+            revised_processing_interval: processing_interval,
+            revised_aggregate_configuration: calculator.configuration().clone(),
+        };
+        Ok((
+            Self {
+                calculator,
+                processing_interval,
+                next_start,
+                values: Vec::new(),
+            },
+            result,
+        ))
+    }
+
+    /// Get the revised processing interval in milliseconds.
+    pub fn processing_interval(&self) -> f64 {
+        self.processing_interval
+    }
+
+    /// Get the calculator used for this filter.
+    pub fn calculator(&self) -> &AggregateCalculator {
+        &self.calculator
+    }
+
+    fn add_value(&mut self, value: DataValue) {
+        let time = history_timestamp(&value);
+        let idx = self
+            .values
+            .partition_point(|v| history_timestamp(v) <= time);
+        self.values.insert(idx, value);
+    }
+
+    /// Calculate the aggregate for every interval that ended before `now`.
+    fn calculate(&mut self, now: DateTime) -> Vec<DataValue> {
+        let step = ((self.processing_interval * TICKS_PER_MS) as i64).max(1);
+        let mut start = self.next_start.ticks();
+        let mut intervals = Vec::new();
+        while start + step <= now.ticks() {
+            intervals.push(AggregateInterval {
+                start: DateTime::from(start),
+                end: DateTime::from(start + step),
+                timestamp: DateTime::from(start),
+                is_partial: false,
+            });
+            start += step;
+        }
+        if intervals.is_empty() {
+            return Vec::new();
+        }
+        let results = self.calculator.calculate(intervals, &self.values);
+        self.next_start = DateTime::from(start);
+
+        // Keep the last usable value before the next interval, for bounding values.
+        let split = self
+            .values
+            .partition_point(|v| history_timestamp(v) < self.next_start);
+        let keep_from = self.values[..split]
+            .iter()
+            .rposition(|v| !v.status().is_bad())
+            .unwrap_or(split.saturating_sub(1));
+        self.values.drain(..keep_from);
+
+        results
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Parsed filter type for a monitored item.
 pub enum FilterType {
     None,
     DataChangeFilter(ParsedDataChangeFilter),
     EventFilter(ParsedEventFilter),
+    AggregateFilter(ParsedAggregateFilter),
 }
 
 impl FilterType {
     /// Try to create a filter from an extension object, returning
-    /// an `EventFilterResult` if the filter is for events.
+    /// the filter result if the filter type has one.
+    /// `min_processing_interval` is the smallest processing interval allowed for aggregate filters.
     pub fn from_filter(
         filter: ExtensionObject,
         eu_range: Option<(f64, f64)>,
         type_tree: &dyn TypeTree,
-    ) -> (Option<EventFilterResult>, Result<FilterType, StatusCode>) {
+        min_processing_interval: f64,
+    ) -> (Option<ExtensionObject>, Result<FilterType, StatusCode>) {
         // Check if the filter is a supported filter type
         if filter.is_null() {
             return (None, Ok(FilterType::None));
@@ -58,7 +176,19 @@ impl FilterType {
             },
             v: EventFilter => {
                 let (res, filter_res) = ParsedEventFilter::new(v, type_tree);
-                (Some(res), filter_res.map(FilterType::EventFilter))
+                (
+                    Some(ExtensionObject::from_message(res)),
+                    filter_res.map(FilterType::EventFilter),
+                )
+            },
+            v: AggregateFilter => {
+                match ParsedAggregateFilter::parse(v, min_processing_interval, DateTime::now()) {
+                    Ok((filter, res)) => (
+                        Some(ExtensionObject::from_message(res)),
+                        Ok(FilterType::AggregateFilter(filter)),
+                    ),
+                    Err(e) => (None, Err(e)),
+                }
             },
             _ => {
                 error!(
@@ -89,7 +219,7 @@ pub struct CreateMonitoredItem {
     initial_value: Option<DataValue>,
     status_code: StatusCode,
     filter: FilterType,
-    filter_res: Option<EventFilterResult>,
+    filter_res: Option<ExtensionObject>,
     timestamps_to_return: TimestampsToReturn,
     eu_range: Option<(f64, f64)>,
 }
@@ -110,6 +240,12 @@ fn sanitize_sampling_interval(info: &ServerInfo, requested_sampling_interval: f6
     }
 }
 
+/// Get the smallest processing interval allowed for aggregate filters
+/// on a monitored item with the given sampling interval.
+fn min_processing_interval(info: &ServerInfo, sampling_interval: f64) -> f64 {
+    sampling_interval.max(info.config.limits.subscriptions.min_sampling_interval_ms)
+}
+
 /// Takes the requested queue size and ensures it is within the range supported by the server
 fn sanitize_queue_size(info: &ServerInfo, requested_queue_size: usize) -> usize {
     if requested_queue_size == 0 || requested_queue_size == 1 {
@@ -145,10 +281,14 @@ impl CreateMonitoredItem {
         type_tree: &dyn TypeTree,
         eu_range: Option<(f64, f64)>,
     ) -> Self {
-        let (filter_res, filter) =
-            FilterType::from_filter(req.requested_parameters.filter, eu_range, type_tree);
         let sampling_interval =
             sanitize_sampling_interval(info, req.requested_parameters.sampling_interval);
+        let (filter_res, filter) = FilterType::from_filter(
+            req.requested_parameters.filter,
+            eu_range,
+            type_tree,
+            min_processing_interval(info, sampling_interval),
+        );
         let queue_size = sanitize_queue_size(info, req.requested_parameters.queue_size as usize);
 
         let (filter, mut status) = match filter {
@@ -258,7 +398,7 @@ impl CreateMonitoredItem {
         self.status_code
     }
 
-    pub(crate) fn filter_res(&self) -> Option<&EventFilterResult> {
+    pub(crate) fn filter_res(&self) -> Option<&ExtensionObject> {
         self.filter_res.as_ref()
     }
 }
@@ -328,19 +468,21 @@ impl MonitoredItem {
         timestamps_to_return: TimestampsToReturn,
         request: &MonitoredItemModifyRequest,
         type_tree: &dyn TypeTree,
-    ) -> (Option<EventFilterResult>, StatusCode) {
+    ) -> (Option<ExtensionObject>, StatusCode) {
         self.timestamps_to_return = timestamps_to_return;
+        let sampling_interval =
+            sanitize_sampling_interval(info, request.requested_parameters.sampling_interval);
         let (filter_res, filter) = FilterType::from_filter(
             request.requested_parameters.filter.clone(),
             self.eu_range,
             type_tree,
+            min_processing_interval(info, sampling_interval),
         );
         self.filter = match filter {
             Ok(f) => f,
             Err(e) => return (filter_res, e),
         };
-        self.sampling_interval =
-            sanitize_sampling_interval(info, request.requested_parameters.sampling_interval);
+        self.sampling_interval = sampling_interval;
         self.queue_size =
             sanitize_queue_size(info, request.requested_parameters.queue_size as usize);
         self.client_handle = request.requested_parameters.client_handle;
@@ -397,6 +539,14 @@ impl MonitoredItem {
             }
         }
 
+        if let FilterType::AggregateFilter(filter) = &mut self.filter {
+            // Aggregates are calculated from raw values once each interval has passed.
+            if value.status() != StatusCode::BadWaitingForInitialData {
+                filter.add_value(value);
+            }
+            return false;
+        }
+
         let data_change = match (&self.last_data_value, &self.filter) {
             (Some(last_dv), FilterType::DataChangeFilter(filter)) => {
                 filter.is_changed(&value, last_dv)
@@ -414,7 +564,31 @@ impl MonitoredItem {
         }
 
         self.last_data_value = Some(value.clone());
+        self.enqueue_data_value(value);
 
+        true
+    }
+
+    /// Calculate aggregates for any processing intervals that have passed, if this
+    /// item has an aggregate filter. Returns `true` if any values were enqueued.
+    pub(super) fn tick_aggregate(&mut self, now: DateTime) -> bool {
+        if self.monitoring_mode == MonitoringMode::Disabled {
+            return false;
+        }
+        let FilterType::AggregateFilter(filter) = &mut self.filter else {
+            return false;
+        };
+
+        let values = filter.calculate(now);
+        let any_new = !values.is_empty();
+        for value in values {
+            self.last_data_value = Some(value.clone());
+            self.enqueue_data_value(value);
+        }
+        any_new
+    }
+
+    fn enqueue_data_value(&mut self, mut value: DataValue) {
         match self.timestamps_to_return {
             TimestampsToReturn::Neither | TimestampsToReturn::Invalid => {
                 value.source_timestamp = None;
@@ -440,8 +614,6 @@ impl MonitoredItem {
             client_handle,
             value,
         });
-
-        true
     }
 
     pub(super) fn notify_event(&mut self, event: &dyn Event) -> bool {
@@ -608,12 +780,12 @@ pub(super) mod tests {
 
     use crate::{node_manager::ParsedReadValueId, subscriptions::monitored_item::Notification};
     use opcua_types::{
-        AttributeId, DataChangeFilter, DataChangeTrigger, DataValue, DateTime, Deadband,
-        DeadbandType, MonitoringMode, NodeId, ParsedDataChangeFilter, ReadValueId, StatusCode,
-        Variant,
+        AggregateConfiguration, AggregateFilter, AttributeId, DataChangeFilter, DataChangeTrigger,
+        DataValue, DateTime, Deadband, DeadbandType, MonitoringMode, NodeId, ObjectId,
+        ParsedDataChangeFilter, ReadValueId, StatusCode, Variant,
     };
 
-    use super::{FilterType, MonitoredItem};
+    use super::{FilterType, MonitoredItem, ParsedAggregateFilter};
 
     pub fn new_monitored_item(
         id: u32,
@@ -867,4 +1039,73 @@ pub(super) mod tests {
             }
         }
     }
+
+    #[test]
+    fn monitored_item_aggregate_filter() {
+        let start: DateTime = DateTime::ymd_hms(2024, 1, 1, 0, 0, 0);
+        let at = |ms: i64| start + Duration::try_milliseconds(ms).unwrap();
+        let (filter, result) = ParsedAggregateFilter::parse(
+            AggregateFilter {
+                start_time: start,
+                aggregate_type: ObjectId::AggregateFunction_Average.into(),
+                processing_interval: 50.0,
+                aggregate_configuration: AggregateConfiguration {
+                    use_server_capabilities_defaults: true,
+                    ..Default::default()
+                },
+            },
+            100.0,
+            start,
+        )
+        .unwrap();
+        // The processing interval is revised to the minimum.
+        assert_eq!(result.revised_processing_interval, 100.0);
+        assert_eq!(result.revised_start_time, start);
+        assert!(
+            result
+                .revised_aggregate_configuration
+                .treat_uncertain_as_bad
+        );
+
+        let mut item = new_monitored_item(
+            1,
+            ReadValueId {
+                node_id: NodeId::null(),
+                attribute_id: AttributeId::Value as u32,
+                ..Default::default()
+            },
+            MonitoringMode::Reporting,
+            FilterType::AggregateFilter(filter),
+            100.0,
+            true,
+            None,
+        );
+
+        // Raw values are never reported directly.
+        for (ms, v) in [(0, 1.0), (50, 3.0), (100, 10.0), (150, 20.0), (250, 100.0)] {
+            assert!(!item.notify_data_value(DataValue::new_at(v, at(ms))));
+        }
+        assert!(!item.has_notifications());
+
+        // Neither interval has passed yet.
+        assert!(!item.tick_aggregate(at(90)));
+        assert!(item.tick_aggregate(at(210)));
+        let values: Vec<_> = std::iter::from_fn(|| item.pop_notification())
+            .map(|n| match n {
+                Notification::MonitoredItemNotification(n) => n.value,
+                _ => panic!("Expected data change"),
+            })
+            .collect();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].value, Some(Variant::Double(2.0)));
+        assert_eq!(values[0].source_timestamp, Some(at(0)));
+        assert_eq!(values[1].value, Some(Variant::Double(15.0)));
+        assert_eq!(values[1].source_timestamp, Some(at(100)));
+
+        // Values before the current interval are discarded, except the last one.
+        let FilterType::AggregateFilter(filter) = &item.filter else {
+            panic!("Expected aggregate filter");
+        };
+        assert_eq!(filter.values.len(), 2);
+    }
 }
@@ -490,6 +490,14 @@ impl Subscription {
         if matches!(tick_reason, TickReason::TickTimerFired) && !publishing_interval_elapsed {
             return TickResult::None;
         }
+
+        // Calculate aggregates for monitored items with aggregate filters.
+        let now_dt = DateTime::from(*now);
+        for (id, item) in self.monitored_items.iter_mut() {
+            if item.tick_aggregate(now_dt) {
+                self.notified_monitored_items.insert(*id);
+            }
+        }
         // First, get the actual state transition we're in.
         let transition = self.get_state_transition(
             tick_reason,
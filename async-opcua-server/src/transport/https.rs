@@ -0,0 +1,226 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::never::Never;
+use log::{debug, error};
+use opcua_core::{
+    comms::{
+        https::{self, HttpRequest, HttpsEncoding},
+        secure_channel::{Role, SecureChannel},
+    },
+    sync::RwLock,
+    Message, RequestMessage, ResponseMessage,
+};
+use opcua_crypto::CertificateStore;
+use opcua_types::{MessageSecurityMode, ServiceFault, StatusCode};
+use tokio::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    constants::DEFAULT_HTTPS_IDLE_TIMEOUT_MS,
+    info::ServerInfo,
+    node_manager::NodeManagers,
+    session::{
+        manager::SessionManager,
+        processor::{ProcessResult, RequestProcessor},
+    },
+    subscriptions::SubscriptionCache,
+};
+
+/// Create a TLS acceptor for HTTPS connections.
+pub(crate) fn tls_acceptor(info: &ServerInfo) -> Result<Option<TlsAcceptor>, String> {
+    let Some(config) = info.config.https_config.as_ref() else {
+        return Ok(None);
+    };
+    super::tls::tls_acceptor(
+        info,
+        "HTTPS",
+        config.certificate_path.as_ref(),
+        config.private_key_path.as_ref(),
+    )
+    .map(Some)
+}
+
+/// Shared state used to process requests received over HTTPS.
+#[derive(Clone)]
+pub(crate) struct HttpsContext {
+    pub info: Arc<ServerInfo>,
+    pub session_manager: Arc<RwLock<SessionManager>>,
+    pub certificate_store: Arc<RwLock<CertificateStore>>,
+    pub node_managers: NodeManagers,
+    pub subscriptions: Arc<SubscriptionCache>,
+    /// HTTPS has no secure channel, so all requests are treated as if they
+    /// were received on a single secure channel with this ID. Sessions are
+    /// identified by their authentication token alone.
+    pub secure_channel_id: u32,
+}
+
+impl HttpsContext {
+    fn channel(&self) -> SecureChannel {
+        let mut channel = SecureChannel::new(
+            self.certificate_store.clone(),
+            Role::Server,
+            Arc::new(RwLock::new(self.info.initial_encoding_context())),
+        );
+        channel.set_security_mode(MessageSecurityMode::None);
+        channel.set_secure_channel_id(self.secure_channel_id);
+        channel
+    }
+}
+
+/// Accept HTTPS connections on `listener`. Each connection runs in a separate task, which
+/// processes service requests until the connection is closed or `token` is cancelled.
+pub(crate) async fn run_https_listener(
+    context: HttpsContext,
+    listener: TcpListener,
+    tls: TlsAcceptor,
+    token: CancellationToken,
+) -> Never {
+    loop {
+        match listener.accept().await {
+            Ok((socket, addr)) => {
+                debug!("Accept new HTTPS connection from {addr}");
+                tokio::spawn(run_connection(
+                    context.clone(),
+                    socket,
+                    tls.clone(),
+                    token.clone(),
+                ));
+            }
+            Err(e) => {
+                error!("Failed to accept HTTPS connection: {e}");
+            }
+        }
+    }
+}
+
+async fn run_connection(
+    context: HttpsContext,
+    socket: TcpStream,
+    tls: TlsAcceptor,
+    token: CancellationToken,
+) {
+    let timeout = Duration::from_secs(context.info.config.tcp_config.hello_timeout as u64);
+    let stream = match tokio::time::timeout(timeout, tls.accept(socket)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            debug!("TLS handshake failed: {e}");
+            return;
+        }
+        Err(_) => {
+            debug!("Timeout waiting for TLS handshake");
+            return;
+        }
+    };
+    let mut stream = BufReader::new(stream);
+    let mut channel = context.channel();
+    let mut processor = RequestProcessor::new(
+        context.session_manager.clone(),
+        context.certificate_store.clone(),
+        context.info.clone(),
+        context.node_managers.clone(),
+        context.subscriptions.clone(),
+    );
+    let max_message_size = context.info.config.limits.max_message_size;
+    let idle_timeout = Duration::from_millis(
+        context
+            .info
+            .config
+            .https_config
+            .as_ref()
+            .map_or(DEFAULT_HTTPS_IDLE_TIMEOUT_MS, |c| c.idle_timeout_ms),
+    );
+    let mut request_id = 0u32;
+
+    loop {
+        let request = tokio::select! {
+            r = tokio::time::timeout(
+                idle_timeout,
+                https::read_request(&mut stream, max_message_size),
+            ) => r,
+            _ = token.cancelled() => break,
+        };
+        let request = match request {
+            Ok(Ok(Some(r))) => r,
+            Ok(Ok(None)) => break,
+            Err(_) => {
+                debug!("Timeout waiting for HTTPS request");
+                break;
+            }
+            Ok(Err(e)) => {
+                let status = if e == StatusCode::BadRequestTooLarge {
+                    413
+                } else {
+                    400
+                };
+                let _ = https::write_response(&mut stream, status, None, &[]).await;
+                break;
+            }
+        };
+
+        request_id = request_id.wrapping_add(1);
+        let result = tokio::select! {
+            r = process_request(&mut processor, &mut channel, request, request_id) => r,
+            _ = token.cancelled() => break,
+        };
+        let res = match result {
+            Ok((encoding, body)) => {
+                https::write_response(&mut stream, 200, Some(encoding.content_type()), &body).await
+            }
+            Err(status) => https::write_response(&mut stream, status, None, &[]).await,
+        };
+        if let Err(e) = res {
+            debug!("Failed to write HTTPS response: {e}");
+            break;
+        }
+    }
+}
+
+/// Decode and process a single service request, returning the encoded response,
+/// or an HTTP status code if the request is invalid.
+async fn process_request(
+    processor: &mut RequestProcessor,
+    channel: &mut SecureChannel,
+    request: HttpRequest,
+    request_id: u32,
+) -> Result<(HttpsEncoding, Vec<u8>), u16> {
+    if request.method != "POST" {
+        debug!("Unsupported HTTP method {}", request.method);
+        return Err(405);
+    }
+    let Some(encoding) = request
+        .content_type
+        .as_deref()
+        .and_then(HttpsEncoding::from_content_type)
+    else {
+        debug!("Unsupported HTTP content type {:?}", request.content_type);
+        return Err(415);
+    };
+
+    let message: RequestMessage = {
+        let ctx_r = channel.context();
+        https::decode_message(&request.body, encoding, &ctx_r.context()).map_err(|_| 400u16)?
+    };
+    let request_handle = message.request_handle();
+
+    let response = match processor.process(channel, message, request_id).await {
+        ProcessResult::Response(r) => r,
+        ProcessResult::Pending(fut) => match fut.await {
+            Ok(r) => r.message,
+            Err(e) => {
+                error!("Unexpected error in message handler: {e}");
+                ResponseMessage::from(ServiceFault::new(
+                    request_handle,
+                    StatusCode::BadInternalError,
+                ))
+            }
+        },
+    };
+
+    let ctx_r = channel.context();
+    let body = https::encode_message(response, encoding, &ctx_r.context()).map_err(|_| 500u16)?;
+    Ok((encoding, body))
+}
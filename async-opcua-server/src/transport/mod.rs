@@ -1,3 +1,10 @@
 mod connect;
+#[cfg(feature = "https")]
+pub(crate) mod https;
+pub(crate) mod reverse_connect;
 pub mod tcp;
+#[cfg(any(feature = "websocket", feature = "https"))]
+mod tls;
+#[cfg(feature = "websocket")]
+pub(crate) mod websocket;
 pub use connect::Connector;
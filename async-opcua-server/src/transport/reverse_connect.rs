@@ -0,0 +1,116 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::never::Never;
+use log::{debug, info, warn};
+use opcua_core::comms::{tcp_types::ReverseHelloMessage, url::hostname_port_from_url};
+use opcua_types::{SimpleBinaryEncodable, StatusCode};
+use tokio::{
+    io::AsyncWriteExt,
+    net::TcpStream,
+    sync::{mpsc, oneshot},
+};
+
+use crate::{config::ReverseConnectTarget, info::ServerInfo};
+
+/// A connection opened by the server to a reverse connect client,
+/// after the reverse hello message has been sent.
+pub(crate) struct ReverseConnection {
+    pub socket: TcpStream,
+    pub client_url: String,
+    /// Dropped once the connection is closed, which tells the reverse
+    /// connect task to open a new connection.
+    pub closed: oneshot::Sender<()>,
+}
+
+/// Open reverse connections to all configured clients, sending them on `send`.
+/// Each client has at most one open connection at a time, once it closes a new one
+/// is opened after the configured retry interval.
+pub(crate) async fn run_reverse_connect(
+    info: Arc<ServerInfo>,
+    send: mpsc::Sender<ReverseConnection>,
+) -> Never {
+    let targets = info.config.reverse_connect.targets.clone();
+    futures::future::join_all(
+        targets
+            .into_iter()
+            .map(|target| reverse_connect_target(info.clone(), target, send.clone())),
+    )
+    .await;
+    futures::future::pending().await
+}
+
+async fn reverse_connect_target(
+    info: Arc<ServerInfo>,
+    target: ReverseConnectTarget,
+    send: mpsc::Sender<ReverseConnection>,
+) {
+    let retry_interval = Duration::from_millis(info.config.reverse_connect.retry_interval_ms);
+    loop {
+        match open_reverse_connection(&info, &target).await {
+            Ok(socket) => {
+                let (closed, closed_recv) = oneshot::channel();
+                let conn = ReverseConnection {
+                    socket,
+                    client_url: target.client_url.clone(),
+                    closed,
+                };
+                if send.send(conn).await.is_err() {
+                    // The server is shutting down.
+                    return;
+                }
+                // Wait for the connection to close before opening a new one.
+                let _ = closed_recv.await;
+                debug!(
+                    "Reverse connection to {} closed, reconnecting",
+                    target.client_url
+                );
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to open reverse connection to {}: {e}",
+                    target.client_url
+                );
+            }
+        }
+        tokio::time::sleep(retry_interval).await;
+    }
+}
+
+async fn open_reverse_connection(
+    info: &ServerInfo,
+    target: &ReverseConnectTarget,
+) -> Result<TcpStream, StatusCode> {
+    let (host, port) = hostname_port_from_url(
+        &target.client_url,
+        opcua_core::constants::DEFAULT_OPC_UA_SERVER_PORT,
+    )?;
+    let mut socket = TcpStream::connect((host.as_str(), port))
+        .await
+        .map_err(|e| {
+            debug!("Could not connect to {}: {e}", target.client_url);
+            StatusCode::BadCommunicationError
+        })?;
+
+    let endpoint_url = target.endpoint_url.clone().unwrap_or_else(|| {
+        let base_endpoint = info.base_endpoint();
+        info.config
+            .default_endpoint()
+            .or_else(|| info.config.endpoints.values().next())
+            .map(|e| e.endpoint_url(&base_endpoint))
+            .unwrap_or(base_endpoint)
+    });
+    let reverse_hello = ReverseHelloMessage::new(info.application_uri.as_ref(), &endpoint_url);
+    socket
+        .write_all(&reverse_hello.encode_to_vec())
+        .await
+        .map_err(|e| {
+            debug!("Failed to send reverse hello to {}: {e}", target.client_url);
+            StatusCode::BadCommunicationError
+        })?;
+    info!(
+        "Opened reverse connection to {} with endpoint url {endpoint_url}",
+        target.client_url
+    );
+
+    Ok(socket)
+}
@@ -11,6 +11,7 @@ use opcua_core::{
         message_chunk::{MessageChunk, MessageIsFinalType},
         message_chunk_info::ChunkInfo,
         secure_channel::SecureChannel,
+        stream::BoxedStream,
         tcp_codec::{Message, TcpCodec},
         tcp_types::{AcknowledgeMessage, ErrorMessage},
     },
@@ -21,18 +22,16 @@ use crate::info::ServerInfo;
 use opcua_types::{DecodingOptions, Error, ResponseHeader, ServiceFault, StatusCode};
 
 use futures::StreamExt;
-use tokio::{
-    io::{AsyncWriteExt, ReadHalf, WriteHalf},
-    net::TcpStream,
-};
+use tokio::io::{AsyncWriteExt, ReadHalf, WriteHalf};
 use tokio_util::{codec::FramedRead, sync::CancellationToken};
 
 use super::connect::Connector;
 
-/// Transport implementation for opc.tcp.
+/// Transport implementation for the OPC-UA binary protocol, over
+/// `opc.tcp` or WebSockets.
 pub(crate) struct TcpTransport {
-    read: FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-    write: WriteHalf<TcpStream>,
+    read: FramedRead<ReadHalf<BoxedStream>, TcpCodec>,
+    write: WriteHalf<BoxedStream>,
     send_buffer: SendBuffer,
     state: TransportState,
     pending_chunks: Vec<MessageChunk>,
@@ -85,8 +84,8 @@ fn min_zero_infinite(server: u32, client: u32) -> u32 {
 }
 
 pub struct TcpConnector {
-    read: FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-    write: WriteHalf<TcpStream>,
+    read: FramedRead<ReadHalf<BoxedStream>, TcpCodec>,
+    write: WriteHalf<BoxedStream>,
     deadline: Instant,
     config: TransportConfig,
     decoding_options: DecodingOptions,
@@ -94,7 +93,7 @@ pub struct TcpConnector {
 
 impl TcpConnector {
     pub fn new(
-        stream: TcpStream,
+        stream: BoxedStream,
         config: TransportConfig,
         decoding_options: DecodingOptions,
     ) -> Self {
@@ -231,8 +230,8 @@ impl Connector for TcpConnector {
 
 impl TcpTransport {
     pub fn new(
-        read: FramedRead<ReadHalf<TcpStream>, TcpCodec>,
-        write: WriteHalf<TcpStream>,
+        read: FramedRead<ReadHalf<BoxedStream>, TcpCodec>,
+        write: WriteHalf<BoxedStream>,
         send_buffer: SendBuffer,
     ) -> Self {
         Self {
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use opcua_core::comms::tls;
+use opcua_crypto::CertificateStore;
+use tokio_rustls::TlsAcceptor;
+
+use crate::info::ServerInfo;
+
+/// Create a TLS acceptor for the transport named `transport`. Uses the application
+/// instance certificate unless a separate certificate and private key are configured.
+pub(crate) fn tls_acceptor(
+    info: &ServerInfo,
+    transport: &str,
+    certificate_path: Option<&PathBuf>,
+    private_key_path: Option<&PathBuf>,
+) -> Result<TlsAcceptor, String> {
+    let (cert, pkey) = match (certificate_path, private_key_path) {
+        (Some(cert_path), Some(key_path)) => (
+            CertificateStore::read_cert(cert_path)?,
+            CertificateStore::read_pkey(key_path)?,
+        ),
+        _ => match (info.server_certificate(), info.server_pkey()) {
+            (Some(cert), Some(pkey)) => ((*cert).clone(), (*pkey).clone()),
+            _ => {
+                return Err(format!(
+                    "{transport} TLS is enabled, but the server has no certificate"
+                ))
+            }
+        },
+    };
+    let config = tls::server_config(&cert, &pkey)
+        .map_err(|e| format!("Failed to create {transport} TLS configuration: {e}"))?;
+    Ok(TlsAcceptor::from(config))
+}
@@ -0,0 +1,73 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use futures::never::Never;
+use log::{debug, error};
+use opcua_core::comms::{stream::BoxedStream, websocket};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::TlsAcceptor;
+
+use crate::info::ServerInfo;
+
+/// Create a TLS acceptor for WebSocket connections, or `None` if TLS is
+/// disabled.
+pub(crate) fn tls_acceptor(info: &ServerInfo) -> Result<Option<TlsAcceptor>, String> {
+    let Some(config) = info.config.websocket_config.as_ref().filter(|c| c.tls) else {
+        return Ok(None);
+    };
+    super::tls::tls_acceptor(
+        info,
+        "WebSocket",
+        config.certificate_path.as_ref(),
+        config.private_key_path.as_ref(),
+    )
+    .map(Some)
+}
+
+/// Accept WebSocket connections on `listener`, sending the stream and remote address
+/// on `send` once the handshake is complete. Each handshake runs in a separate task, and must
+/// complete within the configured hello timeout.
+pub(crate) async fn run_websocket_listener(
+    info: Arc<ServerInfo>,
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+    send: mpsc::Sender<(BoxedStream, SocketAddr)>,
+) -> Never {
+    let timeout = Duration::from_secs(info.config.tcp_config.hello_timeout as u64);
+    loop {
+        match listener.accept().await {
+            Ok((socket, addr)) => {
+                let tls = tls.clone();
+                let send = send.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(timeout, handshake(socket, tls)).await {
+                        Ok(Some(stream)) => {
+                            let _ = send.send((stream, addr)).await;
+                        }
+                        Ok(None) => (),
+                        Err(_) => debug!("Timeout waiting for WebSocket handshake from {addr}"),
+                    }
+                });
+            }
+            Err(e) => {
+                error!("Failed to accept WebSocket connection: {e}");
+            }
+        }
+    }
+}
+
+async fn handshake(socket: TcpStream, tls: Option<TlsAcceptor>) -> Option<BoxedStream> {
+    let result = match tls {
+        Some(tls) => match tls.accept(socket).await {
+            Ok(stream) => websocket::accept(stream).await,
+            Err(e) => {
+                debug!("TLS handshake failed: {e}");
+                return None;
+            }
+        },
+        None => websocket::accept(socket).await,
+    };
+    result.ok()
+}
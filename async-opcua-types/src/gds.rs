@@ -0,0 +1,114 @@
+// OPCUA for Rust
+// SPDX-License-Identifier: MPL-2.0
+// Copyright (C) 2017-2024 Adam Lock
+
+//! Types from the Global Discovery Server namespace, defined in OPC UA Part 12.
+//!
+//! The GDS namespace is not part of the core nodeset, so these types are identified by
+//! expanded node IDs with the GDS namespace URI.
+
+use std::sync::LazyLock;
+
+use crate::{
+    binary_decode_to_enc, ApplicationType, ExpandedMessageInfo, ExpandedNodeId, LocalizedText,
+    NodeId, StaticTypeLoader, TypeLoaderInstance, UAString,
+};
+
+#[allow(unused)]
+mod opcua {
+    pub use crate as types;
+}
+
+/// The namespace URI of the Global Discovery Server information model.
+pub const GDS_NAMESPACE_URI: &str = "http://opcfoundation.org/UA/GDS/";
+
+/// Identifiers of the `ApplicationRecordDataType` in the GDS namespace.
+mod application_record_ids {
+    pub(super) const DATA_TYPE: u32 = 1;
+    pub(super) const ENCODING_DEFAULT_XML: u32 = 127;
+    pub(super) const ENCODING_DEFAULT_BINARY: u32 = 134;
+    pub(super) const ENCODING_DEFAULT_JSON: u32 = 8001;
+}
+
+/// OPC UA Part 12, 6.5.1. Describes an application registered with a GDS.
+#[crate::ua_encodable]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ApplicationRecordDataType {
+    /// The ID assigned to the application by the GDS. Null when registering a new application.
+    pub application_id: NodeId,
+    /// The application URI, which must match the URI in the application instance certificate.
+    pub application_uri: UAString,
+    /// The type of application.
+    pub application_type: ApplicationType,
+    /// The application names, one per locale.
+    pub application_names: Option<Vec<LocalizedText>>,
+    /// The product URI of the application.
+    pub product_uri: UAString,
+    /// The discovery URLs of the application, if it is a server.
+    pub discovery_urls: Option<Vec<UAString>>,
+    /// The server capability identifiers of the application, if it is a server.
+    pub server_capabilities: Option<Vec<UAString>>,
+}
+
+impl ExpandedMessageInfo for ApplicationRecordDataType {
+    fn full_type_id(&self) -> ExpandedNodeId {
+        ExpandedNodeId::new_with_namespace(
+            GDS_NAMESPACE_URI,
+            application_record_ids::ENCODING_DEFAULT_BINARY,
+        )
+    }
+
+    fn full_json_type_id(&self) -> ExpandedNodeId {
+        ExpandedNodeId::new_with_namespace(
+            GDS_NAMESPACE_URI,
+            application_record_ids::ENCODING_DEFAULT_JSON,
+        )
+    }
+
+    fn full_xml_type_id(&self) -> ExpandedNodeId {
+        ExpandedNodeId::new_with_namespace(
+            GDS_NAMESPACE_URI,
+            application_record_ids::ENCODING_DEFAULT_XML,
+        )
+    }
+
+    fn full_data_type_id(&self) -> ExpandedNodeId {
+        ExpandedNodeId::new_with_namespace(GDS_NAMESPACE_URI, application_record_ids::DATA_TYPE)
+    }
+}
+
+static TYPES: LazyLock<TypeLoaderInstance> = LazyLock::new(|| {
+    let mut inst = TypeLoaderInstance::new();
+    inst.add_binary_type(
+        application_record_ids::DATA_TYPE,
+        application_record_ids::ENCODING_DEFAULT_BINARY,
+        binary_decode_to_enc::<ApplicationRecordDataType>,
+    );
+    #[cfg(feature = "json")]
+    inst.add_json_type(
+        application_record_ids::DATA_TYPE,
+        application_record_ids::ENCODING_DEFAULT_JSON,
+        crate::json_decode_to_enc::<ApplicationRecordDataType>,
+    );
+    #[cfg(feature = "xml")]
+    inst.add_xml_type(
+        application_record_ids::DATA_TYPE,
+        application_record_ids::ENCODING_DEFAULT_XML,
+        crate::xml_decode_to_enc::<ApplicationRecordDataType>,
+    );
+    inst
+});
+
+/// Type loader for the types in the GDS namespace. Add this to the encoding context of a
+/// client or server that needs to decode them.
+pub struct GdsTypeLoader;
+
+impl StaticTypeLoader for GdsTypeLoader {
+    fn instance() -> &'static TypeLoaderInstance {
+        &TYPES
+    }
+
+    fn namespace() -> &'static str {
+        GDS_NAMESPACE_URI
+    }
+}
@@ -19,6 +19,15 @@ pub mod profiles {
     /// Transport profile for OPC UA Binary
     pub const TRANSPORT_PROFILE_URI_BINARY: &str =
         "http://opcfoundation.org/UA-Profile/Transport/uatcp-uasc-uabinary";
+    /// Transport profile for OPC UA Binary over WebSockets
+    pub const TRANSPORT_PROFILE_URI_WSS_BINARY: &str =
+        "http://opcfoundation.org/UA-Profile/Transport/wss-uasc-uabinary";
+    /// Transport profile for OPC UA Binary over HTTPS
+    pub const TRANSPORT_PROFILE_URI_HTTPS_BINARY: &str =
+        "http://opcfoundation.org/UA-Profile/Transport/https-uabinary";
+    /// Transport profile for OPC UA JSON over HTTPS
+    pub const TRANSPORT_PROFILE_URI_HTTPS_JSON: &str =
+        "http://opcfoundation.org/UA-Profile/Transport/https-uajson";
     /// Security policy for anonymous tokens.
     pub const SECURITY_USER_TOKEN_POLICY_ANONYMOUS: &str =
         "http://opcfoundation.org/UA-Profile/Security/UserToken/Anonymous";
@@ -254,6 +263,7 @@ pub mod errors;
 pub mod event_field;
 pub mod expanded_node_id;
 pub mod extension_object;
+pub mod gds;
 pub mod guid;
 mod impls;
 #[cfg(feature = "json")]
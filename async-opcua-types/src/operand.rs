@@ -101,6 +101,12 @@ impl From<&str> for LiteralOperand {
     }
 }
 
+impl From<NodeId> for LiteralOperand {
+    fn from(v: NodeId) -> Self {
+        Self::from(Variant::from(v))
+    }
+}
+
 impl From<()> for LiteralOperand {
     fn from(_v: ()) -> Self {
         Self::from(Variant::from(()))
@@ -409,6 +415,48 @@ impl ContentFilterBuilder {
         self.add_element(FilterOperator::BitwiseOr, vec![o1.into(), o2.into()])
     }
 
+    /// Add an "of type" operand. The operand should be a literal node ID of the type.
+    pub fn of_type<T>(self, o1: T) -> Self
+    where
+        T: Into<Operand>,
+    {
+        self.add_element(FilterOperator::OfType, vec![o1.into()])
+    }
+
+    /// Add a "related to" operand. The operands are the source type, the target type,
+    /// the reference type, the number of hops, and whether to include subtypes of the
+    /// source and target types, and of the reference type.
+    #[allow(clippy::too_many_arguments)]
+    pub fn related_to<T, S, U, V, W, X>(
+        self,
+        source_type: T,
+        target_type: S,
+        reference_type: U,
+        hops: V,
+        include_type_subtypes: W,
+        include_reference_subtypes: X,
+    ) -> Self
+    where
+        T: Into<Operand>,
+        S: Into<Operand>,
+        U: Into<Operand>,
+        V: Into<Operand>,
+        W: Into<Operand>,
+        X: Into<Operand>,
+    {
+        self.add_element(
+            FilterOperator::RelatedTo,
+            vec![
+                source_type.into(),
+                target_type.into(),
+                reference_type.into(),
+                hops.into(),
+                include_type_subtypes.into(),
+                include_reference_subtypes.into(),
+            ],
+        )
+    }
+
     /// Build a content filter.
     pub fn build(self) -> ContentFilter {
         ContentFilter {
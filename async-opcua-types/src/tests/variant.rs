@@ -5,7 +5,8 @@ use crate::{
     status_code::StatusCode,
     variant::{Variant, VariantTypeId},
     ByteString, DataTypeId, DataValue, DateTime, DiagnosticInfo, ExpandedNodeId, Guid,
-    LocalizedText, NodeId, QualifiedName, TryFromVariant, UAString, VariantScalarTypeId,
+    LocalizedText, MethodArg, NodeId, QualifiedName, ReadValueId, TryFromVariant, UAString,
+    VariantScalarTypeId,
 };
 
 #[test]
@@ -194,6 +195,29 @@ fn variant_try_into_u32_array() {
     assert_eq!(result.len(), 3);
 }
 
+#[test]
+fn method_arg_description() {
+    let arg = <Option<u32>>::argument("Count");
+    assert_eq!(arg.name.as_ref(), "Count");
+    assert_eq!(arg.data_type, DataTypeId::UInt32);
+    assert_eq!(arg.value_rank, -1);
+    assert_eq!(arg.array_dimensions, None);
+
+    let arg = <Vec<String>>::argument("Names");
+    assert_eq!(arg.data_type, DataTypeId::String);
+    assert_eq!(arg.value_rank, 1);
+
+    let arg = <[f64; 3]>::argument("Point");
+    assert_eq!(arg.data_type, DataTypeId::Double);
+    assert_eq!(arg.array_dimensions, Some(vec![3]));
+
+    assert_eq!(ReadValueId::data_type(), DataTypeId::Structure);
+    assert_eq!(
+        <Variant as MethodArg>::data_type(),
+        DataTypeId::BaseDataType
+    );
+}
+
 #[test]
 fn variant_i32_array() {
     let vars = [1, 2, 3];
@@ -0,0 +1,138 @@
+use uuid::Uuid;
+
+use crate::{
+    Argument, ByteString, DataTypeId, DataValue, DateTime, DateTimeUtc, DiagnosticInfo,
+    DynEncodable, ExpandedNodeId, ExtensionObject, Guid, LocalizedText, NodeId, QualifiedName,
+    StatusCode, UAString,
+};
+
+use super::{TryFromVariant, Variant, XmlElement};
+
+/// Trait for types that can be used as arguments to methods.
+///
+/// The value is converted using [`TryFromVariant`], this trait describes
+/// the argument in the `InputArguments` and `OutputArguments` properties of
+/// a method.
+pub trait MethodArg: TryFromVariant {
+    /// The data type of the argument.
+    fn data_type() -> NodeId;
+
+    /// The value rank of the argument, `-1` for scalars.
+    fn value_rank() -> i32 {
+        -1
+    }
+
+    /// The array dimensions of the argument, if it has a fixed length.
+    fn array_dimensions() -> Option<Vec<u32>> {
+        None
+    }
+
+    /// Create a description of this argument with the given name.
+    fn argument(name: &str) -> Argument {
+        Argument {
+            name: UAString::from(name),
+            data_type: Self::data_type(),
+            value_rank: Self::value_rank(),
+            array_dimensions: Self::array_dimensions(),
+            description: LocalizedText::null(),
+        }
+    }
+}
+
+macro_rules! impl_method_arg {
+    ($tp:ty, $dt:ident) => {
+        impl MethodArg for $tp {
+            fn data_type() -> NodeId {
+                DataTypeId::$dt.into()
+            }
+        }
+    };
+}
+
+impl_method_arg!(bool, Boolean);
+impl_method_arg!(i8, SByte);
+impl_method_arg!(u8, Byte);
+impl_method_arg!(i16, Int16);
+impl_method_arg!(u16, UInt16);
+impl_method_arg!(i32, Int32);
+impl_method_arg!(u32, UInt32);
+impl_method_arg!(i64, Int64);
+impl_method_arg!(u64, UInt64);
+impl_method_arg!(f32, Float);
+impl_method_arg!(f64, Double);
+impl_method_arg!(UAString, String);
+impl_method_arg!(String, String);
+impl_method_arg!(XmlElement, XmlElement);
+impl_method_arg!(DateTime, DateTime);
+impl_method_arg!(DateTimeUtc, DateTime);
+impl_method_arg!(Guid, Guid);
+impl_method_arg!(Uuid, Guid);
+impl_method_arg!(StatusCode, StatusCode);
+impl_method_arg!(ByteString, ByteString);
+impl_method_arg!(QualifiedName, QualifiedName);
+impl_method_arg!(LocalizedText, LocalizedText);
+impl_method_arg!(NodeId, NodeId);
+impl_method_arg!(ExpandedNodeId, ExpandedNodeId);
+impl_method_arg!(ExtensionObject, Structure);
+impl_method_arg!(DataValue, DataValue);
+impl_method_arg!(DiagnosticInfo, DiagnosticInfo);
+impl_method_arg!(Variant, BaseDataType);
+
+// The data type of a structure is only known from an instance,
+// so structures are described by the abstract `Structure` data type.
+impl<T> MethodArg for T
+where
+    T: DynEncodable,
+{
+    fn data_type() -> NodeId {
+        DataTypeId::Structure.into()
+    }
+}
+
+// Optional arguments accept an empty variant.
+impl<T> MethodArg for Option<T>
+where
+    T: MethodArg,
+{
+    fn data_type() -> NodeId {
+        T::data_type()
+    }
+
+    fn value_rank() -> i32 {
+        T::value_rank()
+    }
+
+    fn array_dimensions() -> Option<Vec<u32>> {
+        T::array_dimensions()
+    }
+}
+
+impl<T> MethodArg for Vec<T>
+where
+    T: MethodArg,
+{
+    fn data_type() -> NodeId {
+        T::data_type()
+    }
+
+    fn value_rank() -> i32 {
+        1
+    }
+}
+
+impl<const N: usize, T> MethodArg for [T; N]
+where
+    T: MethodArg,
+{
+    fn data_type() -> NodeId {
+        T::data_type()
+    }
+
+    fn value_rank() -> i32 {
+        1
+    }
+
+    fn array_dimensions() -> Option<Vec<u32>> {
+        Some(vec![N as u32])
+    }
+}
@@ -9,6 +9,7 @@ mod from;
 mod into;
 #[cfg(feature = "json")]
 mod json;
+mod method_arg;
 mod type_id;
 #[cfg(feature = "xml")]
 mod xml;
@@ -19,6 +20,7 @@ pub use xml_element::XmlElement;
 
 pub use from::TryFromVariant;
 pub use into::IntoVariant;
+pub use method_arg::MethodArg;
 pub use type_id::*;
 
 use std::{
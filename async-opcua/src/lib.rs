@@ -24,7 +24,7 @@ extern crate tempdir;
 pub use opcua_core::sync;
 
 #[cfg(feature = "server")]
-pub use opcua_macros::{Event, EventField};
+pub use opcua_macros::{Event, EventField, UaObject};
 
 #[cfg(feature = "client")]
 pub use opcua_client as client;
@@ -0,0 +1,529 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use opcua::{
+    client::Session,
+    crypto::{
+        CertificateListKind, CertificateStoreBackend, InMemoryCertificateStoreBackend,
+        SecurityPolicy, Thumbprint, X509Data, X509,
+    },
+    server::{
+        authenticator::{AuthManager, DefaultAuthenticator, Password, UserToken},
+        ServerEndpoint,
+    },
+    types::{
+        ApplicationDescription, BinaryDecodable, BinaryEncodable, ByteString, CallMethodRequest,
+        CallMethodResult, ContextOwned, Error, MessageSecurityMode, MethodId, NodeId, ObjectId,
+        ObjectTypeId, StatusCode, TrustListDataType, TrustListMasks, UAString, UserTokenPolicy,
+        Variant,
+    },
+};
+use opcua_core::config::Config;
+
+use crate::utils::{
+    client_user_token, default_server, hostname, test_server, Tester, CLIENT_USERPASS_ID,
+};
+
+/// Authenticator giving the username/password test user the `SecurityAdmin` role.
+struct SecurityAdminAuthenticator(DefaultAuthenticator);
+
+#[async_trait]
+impl AuthManager for SecurityAdminAuthenticator {
+    async fn authenticate_anonymous_token(&self, endpoint: &ServerEndpoint) -> Result<(), Error> {
+        self.0.authenticate_anonymous_token(endpoint).await
+    }
+
+    async fn authenticate_username_identity_token(
+        &self,
+        endpoint: &ServerEndpoint,
+        username: &str,
+        password: &Password,
+    ) -> Result<UserToken, Error> {
+        self.0
+            .authenticate_username_identity_token(endpoint, username, password)
+            .await
+    }
+
+    fn is_security_admin(&self, token: &UserToken) -> bool {
+        token.0 == CLIENT_USERPASS_ID
+    }
+
+    fn user_token_policies(&self, endpoint: &ServerEndpoint) -> Vec<UserTokenPolicy> {
+        self.0.user_token_policies(endpoint)
+    }
+}
+
+async fn setup_certificate_management() -> (Tester, Arc<InMemoryCertificateStoreBackend>) {
+    let backend = Arc::new(InMemoryCertificateStoreBackend::new());
+    let server = test_server();
+    let authenticator = DefaultAuthenticator::new(server.config().user_tokens.clone());
+    let server = server
+        .with_authenticator(Arc::new(SecurityAdminAuthenticator(authenticator)))
+        .with_certificate_store_backend(backend.clone());
+    (Tester::new(server, false).await, backend)
+}
+
+async fn connect_admin(tester: &mut Tester) -> Arc<Session> {
+    tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap()
+}
+
+async fn call(
+    session: &Session,
+    object_id: impl Into<NodeId>,
+    method: MethodId,
+    args: Vec<Variant>,
+) -> CallMethodResult {
+    session
+        .call_one(CallMethodRequest {
+            object_id: object_id.into(),
+            method_id: method.into(),
+            input_arguments: Some(args),
+        })
+        .await
+        .unwrap()
+}
+
+async fn call_trust_list(
+    session: &Session,
+    method: MethodId,
+    args: Vec<Variant>,
+) -> CallMethodResult {
+    call(
+        session,
+        ObjectId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList,
+        method,
+        args,
+    )
+    .await
+}
+
+fn output(result: &CallMethodResult, index: usize) -> Variant {
+    assert_eq!(result.status_code, StatusCode::Good);
+    result.output_arguments.as_ref().unwrap()[index].clone()
+}
+
+fn new_certificate(tester: &Tester) -> (X509, opcua::crypto::PrivateKey) {
+    let description = tester.handle.info().config.application_description();
+    let x509_data = X509Data::from((description, Some(vec![hostname()])));
+    X509::cert_and_pkey(&x509_data).unwrap()
+}
+
+#[tokio::test]
+async fn certificate_management_access_control() {
+    let (mut tester, _) = setup_certificate_management().await;
+
+    // Anonymous users are not security admins.
+    let session = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            opcua::client::IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::BadUserAccessDenied);
+
+    // Security admins must use an encrypted channel.
+    let session = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::Sign,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::BadSecurityModeInsufficient);
+
+    // The default authenticator has no security admins.
+    let mut tester = Tester::new(default_server(), false).await;
+    let session = connect_admin(&mut tester).await;
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::BadUserAccessDenied);
+}
+
+#[tokio::test]
+async fn certificate_management_trust_list() {
+    let (mut tester, backend) = setup_certificate_management().await;
+    let session = connect_admin(&mut tester).await;
+
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert!(matches!(output(&r, 0), Variant::Array(_)));
+
+    // Add a certificate to the trusted list.
+    let (cert, _) = new_certificate(&tester);
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_AddCertificate,
+        vec![cert.as_byte_string().into(), true.into()],
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert!(backend.contains_certificate(CertificateListKind::Trusted, &cert));
+
+    // Only CA certificates can be added to the issuer list.
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_AddCertificate,
+        vec![cert.as_byte_string().into(), false.into()],
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::BadCertificateInvalid);
+
+    // Read the trusted certificates.
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_OpenWithMasks,
+        vec![(TrustListMasks::TrustedCertificates as u32).into()],
+    )
+    .await;
+    let Variant::UInt32(handle) = output(&r, 0) else {
+        panic!("Expected file handle");
+    };
+    let mut data = Vec::new();
+    loop {
+        let r = call_trust_list(
+            &session,
+            MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Read,
+            vec![handle.into(), 1000i32.into()],
+        )
+        .await;
+        let Variant::ByteString(chunk) = output(&r, 0) else {
+            panic!("Expected byte string");
+        };
+        if chunk.is_null_or_empty() {
+            break;
+        }
+        data.extend_from_slice(chunk.as_ref());
+    }
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Close,
+        vec![handle.into()],
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+
+    let ctx = ContextOwned::default();
+    let trust_list = TrustListDataType::decode(&mut data.as_slice(), &ctx.context()).unwrap();
+    assert_eq!(
+        trust_list.specified_lists,
+        TrustListMasks::TrustedCertificates as u32
+    );
+    assert!(trust_list
+        .trusted_certificates
+        .unwrap()
+        .contains(&cert.as_byte_string()));
+    assert!(trust_list.issuer_certificates.is_none());
+
+    // Replace the trusted certificates with just the new certificate.
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Open,
+        vec![6u8.into()],
+    )
+    .await;
+    let Variant::UInt32(handle) = output(&r, 0) else {
+        panic!("Expected file handle");
+    };
+    // The trust list can't be changed while it is open for writing.
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_AddCertificate,
+        vec![cert.as_byte_string().into(), true.into()],
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::BadInvalidState);
+
+    let (other_cert, _) = new_certificate(&tester);
+    let update = TrustListDataType {
+        specified_lists: TrustListMasks::TrustedCertificates as u32,
+        trusted_certificates: Some(vec![other_cert.as_byte_string()]),
+        trusted_crls: None,
+        issuer_certificates: None,
+        issuer_crls: None,
+    };
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_Write,
+        vec![
+            handle.into(),
+            ByteString::from(update.encode_to_vec(&ctx.context())).into(),
+        ],
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_CloseAndUpdate,
+        vec![handle.into()],
+    )
+    .await;
+    assert_eq!(output(&r, 0), Variant::Boolean(false));
+    let trusted = backend.certificates(CertificateListKind::Trusted);
+    assert_eq!(trusted.len(), 1);
+    assert_eq!(trusted[0].thumbprint(), other_cert.thumbprint());
+
+    // Remove it again using its thumbprint.
+    let thumbprint = UAString::from(other_cert.thumbprint().as_hex_string());
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_RemoveCertificate,
+        vec![thumbprint.clone().into(), true.into()],
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert!(backend
+        .certificates(CertificateListKind::Trusted)
+        .is_empty());
+    let r = call_trust_list(
+        &session,
+        MethodId::ServerConfiguration_CertificateGroups_DefaultApplicationGroup_TrustList_RemoveCertificate,
+        vec![thumbprint.into(), true.into()],
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::BadInvalidArgument);
+}
+
+#[tokio::test]
+async fn certificate_management_update_certificate() {
+    let (mut tester, backend) = setup_certificate_management().await;
+    let session = connect_admin(&mut tester).await;
+    let old_thumbprint = backend.read_own_cert().unwrap().thumbprint();
+
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_CreateSigningRequest,
+        vec![
+            NodeId::null().into(),
+            NodeId::null().into(),
+            UAString::null().into(),
+            false.into(),
+            ByteString::null().into(),
+        ],
+    )
+    .await;
+    let Variant::ByteString(request) = output(&r, 0) else {
+        panic!("Expected signing request");
+    };
+    assert!(!request.is_null_or_empty());
+
+    // A certificate with a private key that doesn't belong to it is rejected.
+    let (cert, pkey) = new_certificate(&tester);
+    let (_, other_pkey) = new_certificate(&tester);
+    let update_args = |pkey: &opcua::crypto::PrivateKey| -> Vec<Variant> {
+        vec![
+            NodeId::null().into(),
+            NodeId::from(ObjectTypeId::RsaSha256ApplicationCertificateType).into(),
+            cert.as_byte_string().into(),
+            Vec::<ByteString>::new().into(),
+            UAString::from("PEM").into(),
+            ByteString::from(pkey.to_pem().unwrap().into_bytes()).into(),
+        ]
+    };
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_UpdateCertificate,
+        update_args(&other_pkey),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::BadSecurityChecksFailed);
+
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_UpdateCertificate,
+        update_args(&pkey),
+    )
+    .await;
+    assert_eq!(output(&r, 0), Variant::Boolean(true));
+    // Nothing changes until ApplyChanges is called.
+    assert_eq!(
+        backend.read_own_cert().unwrap().thumbprint(),
+        old_thumbprint
+    );
+
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_ApplyChanges,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+    let new_thumbprint: Thumbprint = cert.thumbprint();
+    assert_eq!(
+        backend.read_own_cert().unwrap().thumbprint(),
+        new_thumbprint
+    );
+    assert_eq!(
+        tester
+            .handle
+            .info()
+            .server_certificate()
+            .unwrap()
+            .thumbprint(),
+        new_thumbprint
+    );
+
+    // New connections use the new certificate and key.
+    let session = connect_admin(&mut tester).await;
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+}
+
+#[tokio::test]
+async fn server_certificate_hot_reload() {
+    let (mut tester, backend) = setup_certificate_management().await;
+    let session = connect_admin(&mut tester).await;
+
+    let (cert, pkey) = new_certificate(&tester);
+    let (_, other_pkey) = new_certificate(&tester);
+    assert_eq!(
+        tester
+            .handle
+            .set_server_certificate(cert.clone(), other_pkey),
+        Err(StatusCode::BadSecurityChecksFailed)
+    );
+    tester
+        .handle
+        .set_server_certificate(cert.clone(), pkey)
+        .unwrap();
+    assert_eq!(
+        backend.read_own_cert().unwrap().thumbprint(),
+        cert.thumbprint()
+    );
+
+    // The open secure channel keeps working with the old certificate.
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+
+    // Endpoints are returned with the new certificate.
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    assert!(endpoints
+        .iter()
+        .all(|e| e.server_certificate == cert.as_byte_string()));
+
+    // New connections use the new certificate, including for encrypting the password.
+    let session = connect_admin(&mut tester).await;
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+
+    // Reloading picks up a certificate written directly to the store.
+    let (cert, pkey) = new_certificate(&tester);
+    backend.store_own_cert_and_pkey(&cert, &pkey, true).unwrap();
+    tester.handle.reload_server_certificate().unwrap();
+    assert_eq!(
+        tester
+            .handle
+            .info()
+            .server_certificate()
+            .unwrap()
+            .thumbprint(),
+        cert.thumbprint()
+    );
+}
+
+#[tokio::test]
+async fn client_certificate_hot_reload() {
+    let (mut tester, backend) = setup_certificate_management().await;
+    let session = connect_admin(&mut tester).await;
+
+    let description = ApplicationDescription {
+        application_uri: "x".into(),
+        application_name: "integration_client".into(),
+        ..Default::default()
+    };
+    let (cert, pkey) =
+        X509::cert_and_pkey(&X509Data::from((description, Some(vec![hostname()])))).unwrap();
+    let (_, other_pkey) = new_certificate(&tester);
+    assert_eq!(
+        tester
+            .client
+            .set_client_certificate(cert.clone(), other_pkey),
+        Err(StatusCode::BadSecurityChecksFailed)
+    );
+    tester
+        .client
+        .set_client_certificate(cert.clone(), pkey)
+        .unwrap();
+
+    // The open session keeps working.
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+
+    // A new session is created with the new certificate.
+    let session = connect_admin(&mut tester).await;
+    let r = call(
+        &session,
+        ObjectId::ServerConfiguration,
+        MethodId::ServerConfiguration_GetRejectedList,
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+    // The server trusts unknown client certificates, which adds them to its trusted list.
+    assert!(backend.contains_certificate(CertificateListKind::Trusted, &cert));
+}
@@ -0,0 +1,537 @@
+use std::time::Duration;
+
+use chrono::TimeDelta;
+
+use crate::utils::{setup, ChannelNotifications};
+use opcua::{
+    client::Session,
+    server::conditions::{Condition, ShelvingState},
+    types::{
+        AttributeId, ByteString, CallMethodRequest, ContentFilter, DateTime, EventFilter,
+        ExtensionObject, LocalizedText, MethodId, MonitoredItemCreateRequest, NodeId, NumericRange,
+        ObjectId, ObjectTypeId, ReadValueId, SimpleAttributeOperand, StatusCode,
+        TimestampsToReturn, Variant,
+    },
+};
+use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
+
+// Indexes of the selected fields in each event.
+const EVENT_ID: usize = 0;
+const EVENT_TYPE: usize = 1;
+const CONDITION_ID: usize = 2;
+const BRANCH_ID: usize = 3;
+const RETAIN: usize = 4;
+const ENABLED: usize = 5;
+const ACTIVE: usize = 6;
+const ACKED: usize = 7;
+const CONFIRMED: usize = 8;
+
+fn select(type_definition_id: ObjectTypeId, path: &str) -> SimpleAttributeOperand {
+    SimpleAttributeOperand::new(
+        type_definition_id,
+        path,
+        AttributeId::Value,
+        NumericRange::None,
+    )
+}
+
+async fn subscribe_to_events(
+    session: &Session,
+) -> (
+    u32,
+    u32,
+    UnboundedReceiver<(ReadValueId, Option<Vec<Variant>>)>,
+) {
+    let (notifs, _, events) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+
+    let filter = EventFilter {
+        select_clauses: Some(vec![
+            select(ObjectTypeId::BaseEventType, "EventId"),
+            select(ObjectTypeId::BaseEventType, "EventType"),
+            SimpleAttributeOperand {
+                type_definition_id: ObjectTypeId::ConditionType.into(),
+                browse_path: Some(Vec::new()),
+                attribute_id: AttributeId::NodeId as u32,
+                index_range: NumericRange::None,
+            },
+            select(ObjectTypeId::ConditionType, "BranchId"),
+            select(ObjectTypeId::ConditionType, "Retain"),
+            select(ObjectTypeId::ConditionType, "EnabledState/Id"),
+            select(ObjectTypeId::AlarmConditionType, "ActiveState/Id"),
+            select(ObjectTypeId::AcknowledgeableConditionType, "AckedState/Id"),
+            select(
+                ObjectTypeId::AcknowledgeableConditionType,
+                "ConfirmedState/Id",
+            ),
+        ]),
+        where_clause: ContentFilter { elements: None },
+    };
+    let mut item: MonitoredItemCreateRequest = NodeId::from(ObjectId::Server).into();
+    item.item_to_monitor.attribute_id = AttributeId::EventNotifier as u32;
+    item.requested_parameters.queue_size = 100;
+    item.requested_parameters.filter = ExtensionObject::from_message(filter);
+
+    let res = session
+        .create_monitored_items(sub_id, TimestampsToReturn::Neither, vec![item])
+        .await
+        .unwrap();
+    assert_eq!(res[0].status_code, StatusCode::Good);
+    (sub_id, res[0].monitored_item_id, events)
+}
+
+async fn next_event(
+    events: &mut UnboundedReceiver<(ReadValueId, Option<Vec<Variant>>)>,
+) -> Vec<Variant> {
+    let (_, fields) = timeout(Duration::from_secs(2), events.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    fields.unwrap()
+}
+
+fn event_id(fields: &[Variant]) -> ByteString {
+    let Variant::ByteString(id) = &fields[EVENT_ID] else {
+        panic!("Expected event ID, got {:?}", fields[EVENT_ID]);
+    };
+    id.clone()
+}
+
+async fn call_condition_method(
+    session: &Session,
+    object_id: &NodeId,
+    method: MethodId,
+    args: Vec<Variant>,
+) -> StatusCode {
+    session
+        .call_one(CallMethodRequest {
+            object_id: object_id.clone(),
+            method_id: method.into(),
+            input_arguments: Some(args),
+        })
+        .await
+        .unwrap()
+        .status_code
+}
+
+fn comment_args(event_id: ByteString, comment: &str) -> Vec<Variant> {
+    vec![event_id.into(), LocalizedText::from(comment).into()]
+}
+
+#[tokio::test]
+async fn alarm_acknowledge_and_confirm() {
+    let (tester, _nm, session) = setup().await;
+    let conditions = tester.handle.conditions();
+    let (_, _, mut events) = subscribe_to_events(&session).await;
+
+    let alarm_id = NodeId::new(2, "alarm");
+    conditions.add_condition(
+        Condition::new_alarm(&alarm_id, ObjectTypeId::AlarmConditionType, "Alarm", true)
+            .set_source_node(ObjectId::Server)
+            .set_source_name("Server")
+            .set_severity(500),
+    );
+
+    conditions
+        .set_active(&alarm_id, true, "Alarm active")
+        .unwrap();
+    let evt = next_event(&mut events).await;
+    assert_eq!(
+        evt[EVENT_TYPE],
+        Variant::from(NodeId::from(ObjectTypeId::AlarmConditionType))
+    );
+    assert_eq!(evt[CONDITION_ID], Variant::from(alarm_id.clone()));
+    assert_eq!(evt[BRANCH_ID], Variant::from(NodeId::null()));
+    assert_eq!(evt[RETAIN], Variant::Boolean(true));
+    assert_eq!(evt[ENABLED], Variant::Boolean(true));
+    assert_eq!(evt[ACTIVE], Variant::Boolean(true));
+    assert_eq!(evt[ACKED], Variant::Boolean(false));
+    assert_eq!(evt[CONFIRMED], Variant::Boolean(false));
+
+    // Acknowledge with an unknown event ID.
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &alarm_id,
+            MethodId::AcknowledgeableConditionType_Acknowledge,
+            comment_args(ByteString::from(vec![1u8, 2, 3]), "Ack"),
+        )
+        .await,
+        StatusCode::BadEventIdUnknown
+    );
+
+    // Acknowledge the alarm.
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &alarm_id,
+            MethodId::AcknowledgeableConditionType_Acknowledge,
+            comment_args(event_id(&evt), "Ack"),
+        )
+        .await,
+        StatusCode::Good
+    );
+    let evt = next_event(&mut events).await;
+    assert_eq!(evt[RETAIN], Variant::Boolean(true));
+    assert_eq!(evt[ACKED], Variant::Boolean(true));
+    assert_eq!(evt[CONFIRMED], Variant::Boolean(false));
+    let condition = conditions.get_condition(&alarm_id).unwrap();
+    assert_eq!(condition.comment(), &LocalizedText::from("Ack"));
+
+    // Acknowledging again fails.
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &alarm_id,
+            MethodId::AcknowledgeableConditionType_Acknowledge,
+            comment_args(event_id(&evt), "Ack"),
+        )
+        .await,
+        StatusCode::BadConditionBranchAlreadyAcked
+    );
+
+    // Confirm the alarm.
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &alarm_id,
+            MethodId::AcknowledgeableConditionType_Confirm,
+            comment_args(event_id(&evt), "Confirm"),
+        )
+        .await,
+        StatusCode::Good
+    );
+    let evt = next_event(&mut events).await;
+    assert_eq!(evt[CONFIRMED], Variant::Boolean(true));
+    // Still active, so still retained.
+    assert_eq!(evt[RETAIN], Variant::Boolean(true));
+
+    conditions
+        .set_active(&alarm_id, false, "Alarm inactive")
+        .unwrap();
+    let evt = next_event(&mut events).await;
+    assert_eq!(evt[ACTIVE], Variant::Boolean(false));
+    assert_eq!(evt[RETAIN], Variant::Boolean(false));
+}
+
+#[tokio::test]
+async fn alarm_branches_and_refresh() {
+    let (tester, _nm, session) = setup().await;
+    let conditions = tester.handle.conditions();
+    let (sub_id, item_id, mut events) = subscribe_to_events(&session).await;
+
+    let alarm_id = NodeId::new(2, "alarm");
+    conditions.add_condition(
+        Condition::new_alarm(&alarm_id, ObjectTypeId::AlarmConditionType, "Alarm", false)
+            .set_source_node(ObjectId::Server),
+    );
+
+    conditions.set_active(&alarm_id, true, "Active").unwrap();
+    let first = next_event(&mut events).await;
+    conditions.set_active(&alarm_id, false, "Inactive").unwrap();
+    let evt = next_event(&mut events).await;
+    // Not yet acknowledged, so still retained.
+    assert_eq!(evt[RETAIN], Variant::Boolean(true));
+    assert_ne!(event_id(&first), event_id(&evt));
+
+    // Activating again moves the unacknowledged state to a branch.
+    conditions.set_active(&alarm_id, true, "Active").unwrap();
+    let branch = next_event(&mut events).await;
+    let Variant::NodeId(branch_id) = &branch[BRANCH_ID] else {
+        panic!("Expected branch ID");
+    };
+    assert!(!branch_id.is_null());
+    assert_eq!(branch[ACTIVE], Variant::Boolean(false));
+    assert_eq!(branch[ACKED], Variant::Boolean(false));
+    assert_eq!(branch[RETAIN], Variant::Boolean(true));
+    let main = next_event(&mut events).await;
+    assert_eq!(main[BRANCH_ID], Variant::from(NodeId::null()));
+    assert_eq!(main[ACTIVE], Variant::Boolean(true));
+    assert_eq!(main[ACKED], Variant::Boolean(false));
+    assert_eq!(
+        conditions
+            .get_condition(&alarm_id)
+            .unwrap()
+            .branch_ids()
+            .count(),
+        1
+    );
+
+    // Refresh reports both retained branches.
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &ObjectTypeId::ConditionType.into(),
+            MethodId::ConditionType_ConditionRefresh,
+            vec![sub_id.into()],
+        )
+        .await,
+        StatusCode::Good
+    );
+    let start = next_event(&mut events).await;
+    assert_eq!(
+        start[EVENT_TYPE],
+        Variant::from(NodeId::from(ObjectTypeId::RefreshStartEventType))
+    );
+    let mut refreshed = [next_event(&mut events).await, next_event(&mut events).await];
+    refreshed.sort_by_key(|e| e[BRANCH_ID] == Variant::from(NodeId::null()));
+    assert_eq!(event_id(&refreshed[0]), event_id(&branch));
+    assert_eq!(event_id(&refreshed[1]), event_id(&main));
+    let end = next_event(&mut events).await;
+    assert_eq!(
+        end[EVENT_TYPE],
+        Variant::from(NodeId::from(ObjectTypeId::RefreshEndEventType))
+    );
+
+    // Acknowledging the branch removes it.
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &alarm_id,
+            MethodId::AcknowledgeableConditionType_Acknowledge,
+            comment_args(event_id(&branch), ""),
+        )
+        .await,
+        StatusCode::Good
+    );
+    let evt = next_event(&mut events).await;
+    assert_eq!(evt[BRANCH_ID], branch[BRANCH_ID]);
+    assert_eq!(evt[RETAIN], Variant::Boolean(false));
+    assert_eq!(
+        conditions
+            .get_condition(&alarm_id)
+            .unwrap()
+            .branch_ids()
+            .count(),
+        0
+    );
+
+    // Refresh for a single monitored item.
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &ObjectTypeId::ConditionType.into(),
+            MethodId::ConditionType_ConditionRefresh2,
+            vec![sub_id.into(), item_id.into()],
+        )
+        .await,
+        StatusCode::Good
+    );
+    next_event(&mut events).await;
+    let evt = next_event(&mut events).await;
+    assert_eq!(evt[BRANCH_ID], Variant::from(NodeId::null()));
+    next_event(&mut events).await;
+
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &ObjectTypeId::ConditionType.into(),
+            MethodId::ConditionType_ConditionRefresh2,
+            vec![sub_id.into(), (item_id + 100).into()],
+        )
+        .await,
+        StatusCode::BadMonitoredItemIdInvalid
+    );
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &ObjectTypeId::ConditionType.into(),
+            MethodId::ConditionType_ConditionRefresh,
+            vec![(sub_id + 100).into()],
+        )
+        .await,
+        StatusCode::BadSubscriptionIdInvalid
+    );
+}
+
+#[tokio::test]
+async fn condition_enable_disable() {
+    let (tester, _nm, session) = setup().await;
+    let conditions = tester.handle.conditions();
+    let (_, _, mut events) = subscribe_to_events(&session).await;
+
+    let condition_id = NodeId::new(2, "condition");
+    conditions.add_condition(
+        Condition::new_acknowledgeable(
+            &condition_id,
+            ObjectTypeId::AcknowledgeableConditionType,
+            "Condition",
+            false,
+        )
+        .set_source_node(ObjectId::Server),
+    );
+    conditions
+        .set_retain(&condition_id, true, "Retained")
+        .unwrap();
+    let evt = next_event(&mut events).await;
+    assert_eq!(evt[RETAIN], Variant::Boolean(true));
+    assert_eq!(evt[ACKED], Variant::Boolean(true));
+    // Not an alarm, so there is no active state.
+    assert_eq!(evt[ACTIVE], Variant::Empty);
+
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &condition_id,
+            MethodId::ConditionType_Disable,
+            Vec::new()
+        )
+        .await,
+        StatusCode::Good
+    );
+    let evt = next_event(&mut events).await;
+    assert_eq!(evt[ENABLED], Variant::Boolean(false));
+    assert_eq!(evt[RETAIN], Variant::Boolean(false));
+
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &condition_id,
+            MethodId::ConditionType_Disable,
+            Vec::new()
+        )
+        .await,
+        StatusCode::BadConditionAlreadyDisabled
+    );
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &condition_id,
+            MethodId::ConditionType_AddComment,
+            comment_args(event_id(&evt), "Comment"),
+        )
+        .await,
+        StatusCode::BadConditionDisabled
+    );
+
+    // Changes while disabled are not reported.
+    conditions
+        .set_severity(&condition_id, 800, "Severity changed")
+        .unwrap();
+
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &condition_id,
+            MethodId::ConditionType_Enable,
+            Vec::new()
+        )
+        .await,
+        StatusCode::Good
+    );
+    let evt = next_event(&mut events).await;
+    assert_eq!(evt[ENABLED], Variant::Boolean(true));
+    assert_eq!(evt[RETAIN], Variant::Boolean(true));
+    assert_eq!(
+        conditions.get_condition(&condition_id).unwrap().severity(),
+        800
+    );
+
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &condition_id,
+            MethodId::ConditionType_AddComment,
+            comment_args(event_id(&evt), "Comment"),
+        )
+        .await,
+        StatusCode::Good
+    );
+    next_event(&mut events).await;
+    assert_eq!(
+        conditions.get_condition(&condition_id).unwrap().comment(),
+        &LocalizedText::from("Comment")
+    );
+
+    // Methods on unknown conditions are not handled.
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &NodeId::new(2, "unknown"),
+            MethodId::ConditionType_Enable,
+            Vec::new()
+        )
+        .await,
+        StatusCode::BadMethodInvalid
+    );
+
+    // Methods must belong to the type of the condition, a plain condition
+    // cannot be acknowledged.
+    let plain_id = NodeId::new(2, "plain");
+    conditions.add_condition(Condition::new(
+        &plain_id,
+        ObjectTypeId::ConditionType,
+        "Plain",
+    ));
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &plain_id,
+            MethodId::AcknowledgeableConditionType_Acknowledge,
+            comment_args(event_id(&evt), "Comment"),
+        )
+        .await,
+        StatusCode::BadMethodInvalid
+    );
+    assert_eq!(
+        call_condition_method(
+            &session,
+            &plain_id,
+            MethodId::ConditionType_Disable,
+            Vec::new()
+        )
+        .await,
+        StatusCode::Good
+    );
+}
+
+#[tokio::test]
+async fn alarm_timed_shelving() {
+    let (tester, _nm, _session) = setup().await;
+    let conditions = tester.handle.conditions();
+
+    let alarm_id = NodeId::new(2, "alarm");
+    conditions.add_condition(
+        Condition::new_alarm(&alarm_id, ObjectTypeId::AlarmConditionType, "Alarm", true)
+            .set_source_node(ObjectId::Server),
+    );
+
+    let unshelve_time = DateTime::now() + TimeDelta::try_milliseconds(300).unwrap();
+    conditions
+        .set_shelving_state(&alarm_id, ShelvingState::TimedShelved { unshelve_time })
+        .unwrap();
+    assert_eq!(
+        conditions
+            .get_condition(&alarm_id)
+            .unwrap()
+            .shelving_state(),
+        Some(ShelvingState::TimedShelved { unshelve_time })
+    );
+
+    // The alarm is unshelved once the unshelve time is reached.
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(
+        conditions
+            .get_condition(&alarm_id)
+            .unwrap()
+            .shelving_state(),
+        Some(ShelvingState::Unshelved)
+    );
+
+    // Timed shelving is scheduled on the current runtime, so it fails outside of one.
+    let conditions = conditions.clone();
+    let res = std::thread::spawn(move || {
+        conditions.set_shelving_state(
+            &alarm_id,
+            ShelvingState::TimedShelved {
+                unshelve_time: DateTime::now() + TimeDelta::try_seconds(10).unwrap(),
+            },
+        )
+    })
+    .join()
+    .unwrap();
+    assert_eq!(res, Err(StatusCode::BadInvalidState));
+}
@@ -1,18 +1,27 @@
-use std::{sync::atomic::Ordering, time::Duration};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 
 use super::utils::hostname;
 use bytes::BytesMut;
 use log::debug;
 use opcua::{
-    client::IdentityToken,
+    client::{IdentityToken, ReverseConnector},
     core::comms::tcp_codec::{Message, TcpCodec},
     core::config::Config,
-    crypto::SecurityPolicy,
+    crypto::{
+        password, pkey::KeyType, x509::X509Data, CertificateListKind, CertificateStore,
+        CertificateStoreBackend, InMemoryCertificateStoreBackend, SecurityPolicy, X509,
+    },
+    server::{ServerUserToken, ANONYMOUS_USER_TOKEN_ID},
     types::{
-        ApplicationType, DecodingOptions, MessageSecurityMode, NodeId, ReadValueId, StatusCode,
-        TimestampsToReturn, VariableId, Variant,
+        profiles, ApplicationDescription, ApplicationType, DecodingOptions, LocalizedText,
+        MessageSecurityMode, NodeId, ReadValueId, RegisteredServer, StatusCode, TimestampsToReturn,
+        UAString, VariableId, Variant,
     },
 };
+use tempdir::TempDir;
 use tokio::{
     io::AsyncReadExt,
     net::{TcpListener, TcpStream},
@@ -20,8 +29,8 @@ use tokio::{
 use tokio_util::codec::Decoder;
 
 use crate::utils::{
-    client_user_token, client_x509_token, copy_shared_certs, default_server, test_server, Tester,
-    CLIENT_USERPASS_ID, TEST_COUNTER,
+    client_user_token, client_x509_token, copy_shared_certs, default_client, default_server,
+    test_server, Tester, CLIENT_USERPASS_ID, TEST_COUNTER,
 };
 
 #[tokio::test]
@@ -219,6 +228,25 @@ async fn connect_basic128rsa15_with_username_password() {
     .await;
 }
 
+#[tokio::test]
+async fn connect_aes256sha256rsapss_with_rsa_encrypted_secret() {
+    let client = default_client(0, false).rsa_encrypted_secret(true);
+    let mut tester = Tester::new_custom_client(default_server(), client).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Aes256Sha256RsaPss,
+            MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+}
+
 #[tokio::test]
 async fn connect_basic128rsa15_with_x509_token() {
     conn_test(
@@ -244,6 +272,40 @@ async fn connect_basic128rsa_15_with_invalid_token() {
     assert_eq!(res, StatusCode::BadIdentityTokenRejected);
 }
 
+#[tokio::test]
+async fn connect_with_hashed_password() {
+    let pass_hash = password::hash_password(&format!("{CLIENT_USERPASS_ID}_password")).unwrap();
+    let server = default_server().add_user_token(
+        CLIENT_USERPASS_ID,
+        ServerUserToken::user_pass_hash(CLIENT_USERPASS_ID, &pass_hash),
+    );
+    let mut tester = Tester::new(server, true).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            client_user_token(),
+        )
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let (_, handle) = tester
+        .connect(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::UserName(CLIENT_USERPASS_ID.to_owned(), "invalid".to_owned()),
+        )
+        .await
+        .unwrap();
+    let res = handle.spawn().await.unwrap();
+    assert_eq!(res, StatusCode::BadIdentityTokenRejected);
+}
+
 #[tokio::test]
 async fn find_servers() {
     let tester = Tester::new_default_server(true).await;
@@ -397,3 +459,593 @@ async fn recoverable_error_test_server() {
         .await
         .unwrap();
 }
+
+fn registered_server(server_uri: &str, is_online: bool) -> RegisteredServer {
+    RegisteredServer {
+        server_uri: server_uri.into(),
+        product_uri: "urn:registered_server Testkit".into(),
+        server_names: Some(vec![LocalizedText::new("en", "registered_server")]),
+        server_type: ApplicationType::Server,
+        gateway_server_uri: UAString::null(),
+        discovery_urls: Some(vec!["opc.tcp://localhost:4855/".into()]),
+        semaphore_file_path: UAString::null(),
+        is_online,
+    }
+}
+
+#[tokio::test]
+async fn register_server_unsupported() {
+    let mut tester = Tester::new_default_server(true).await;
+    let endpoint = tester.endpoint();
+    let res = tester
+        .client
+        .register_server(endpoint, registered_server("urn:integration_server", true))
+        .await;
+    assert_eq!(res.unwrap_err(), StatusCode::BadServiceUnsupported);
+}
+
+#[tokio::test]
+async fn local_discovery_server() {
+    let tester = Tester::new(default_server().local_discovery_server(true), true).await;
+
+    // Register using a client with its own certificate, since the server checks that
+    // the server URI matches the certificate.
+    let mut client = default_client(tester.test_id, true)
+        .application_uri("urn:registered_server")
+        .pki_dir(format!("./pki-client/{}-registered", tester.test_id))
+        .client()
+        .unwrap();
+
+    client
+        .register_server(
+            tester.endpoint(),
+            registered_server("urn:registered_server", true),
+        )
+        .await
+        .unwrap();
+
+    // The server URI must match the certificate of the registering server.
+    let res = client
+        .register_server(tester.endpoint(), registered_server("urn:other", true))
+        .await;
+    assert_eq!(res.unwrap_err(), StatusCode::BadCertificateUriInvalid);
+
+    let servers = client
+        .find_servers(tester.endpoint(), None, None)
+        .await
+        .unwrap();
+    assert_eq!(servers.len(), 2);
+    assert_eq!(
+        servers[0].application_uri.as_ref(),
+        "urn:integration_server"
+    );
+    assert_eq!(
+        servers[0].application_type,
+        ApplicationType::DiscoveryServer
+    );
+    assert_eq!(servers[1].application_uri.as_ref(), "urn:registered_server");
+    assert_eq!(servers[1].application_type, ApplicationType::Server);
+    assert_eq!(
+        servers[1].application_name.text.as_ref(),
+        "registered_server"
+    );
+
+    let servers = client
+        .find_servers(
+            tester.endpoint(),
+            None,
+            Some(vec!["urn:registered_server".into()]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(servers.len(), 1);
+
+    let res = client
+        .find_servers_on_network(tester.endpoint(), 0, 0, None)
+        .await
+        .unwrap();
+    let servers = res.servers.unwrap_or_default();
+    assert_eq!(servers.len(), 1);
+    assert_eq!(servers[0].record_id, 1);
+    assert_eq!(servers[0].server_name.as_ref(), "registered_server");
+    assert_eq!(
+        servers[0].discovery_url.as_ref(),
+        "opc.tcp://localhost:4855/"
+    );
+
+    // Registering as offline removes the registration.
+    client
+        .register_server(
+            tester.endpoint(),
+            registered_server("urn:registered_server", false),
+        )
+        .await
+        .unwrap();
+    let servers = client
+        .find_servers(tester.endpoint(), None, None)
+        .await
+        .unwrap();
+    assert_eq!(servers.len(), 1);
+    assert!(tester
+        .handle
+        .local_discovery()
+        .unwrap()
+        .registered_servers()
+        .is_empty());
+}
+
+async fn reverse_connect_tester(connector: &ReverseConnector) -> Tester {
+    let client_url = format!(
+        "opc.tcp://{}:{}",
+        hostname(),
+        connector.local_addr().unwrap().port()
+    );
+    Tester::new(
+        default_server()
+            .reverse_connect(client_url, None)
+            .reverse_connect_retry_interval_ms(100),
+        true,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn reverse_connect() {
+    let connector = ReverseConnector::bind(format!("{}:0", hostname()))
+        .await
+        .unwrap()
+        .server_uri("urn:integration_server");
+    let tester = reverse_connect_tester(&connector).await;
+
+    // Fetch the endpoints normally, to get the server certificate.
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    let (session, event_loop) = tester
+        .client
+        .session_builder()
+        .with_endpoints(endpoints)
+        .connect_to_matching_endpoint((
+            &tester.endpoint() as &str,
+            SecurityPolicy::Basic256Sha256.to_str(),
+            MessageSecurityMode::SignAndEncrypt,
+        ))
+        .unwrap()
+        .reverse_connect(connector)
+        .build(tester.client.certificate_store().clone());
+    let _h = event_loop.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn reverse_connect_wrong_server_uri() {
+    let connector = ReverseConnector::bind(format!("{}:0", hostname()))
+        .await
+        .unwrap()
+        .server_uri("urn:other_server");
+    let tester = reverse_connect_tester(&connector).await;
+
+    let (session, event_loop) = tester
+        .client
+        .session_builder()
+        .connect_to_endpoint_directly((
+            &tester.endpoint() as &str,
+            SecurityPolicy::None.to_str(),
+            MessageSecurityMode::None,
+        ))
+        .unwrap()
+        .reverse_connect(connector)
+        .build(tester.client.certificate_store().clone());
+    let _h = event_loop.spawn();
+
+    // Connections from the server are rejected, since the server URI does not match.
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap_err();
+}
+
+/// Connect to a server with the WebSocket transport enabled, using the WebSocket
+/// endpoint with the given security policy and mode.
+async fn websocket_connect(tls: bool, security_policy: SecurityPolicy, mode: MessageSecurityMode) {
+    let tester = Tester::new(default_server().websocket(0, tls), true).await;
+    let scheme = if tls { "opc.wss://" } else { "opc.ws://" };
+
+    // Endpoints are exposed on both transports.
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    let endpoint = endpoints
+        .iter()
+        .find(|e| {
+            e.endpoint_url.as_ref().starts_with(scheme)
+                && e.security_policy_uri.as_ref() == security_policy.to_uri()
+                && e.security_mode == mode
+        })
+        .unwrap()
+        .clone();
+    assert_eq!(
+        endpoint.transport_profile_uri.as_ref(),
+        profiles::TRANSPORT_PROFILE_URI_WSS_BINARY
+    );
+    assert!(endpoints
+        .iter()
+        .any(|e| e.endpoint_url.as_ref() == tester.endpoint()));
+
+    let (session, event_loop) = tester
+        .client
+        .session_builder()
+        .with_endpoints(endpoints)
+        .connect_to_matching_endpoint(endpoint)
+        .unwrap()
+        .build(tester.client.certificate_store().clone());
+    let _h = event_loop.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn websocket() {
+    websocket_connect(false, SecurityPolicy::None, MessageSecurityMode::None).await;
+}
+
+#[tokio::test]
+async fn websocket_tls() {
+    websocket_connect(
+        true,
+        SecurityPolicy::Basic256Sha256,
+        MessageSecurityMode::SignAndEncrypt,
+    )
+    .await;
+}
+
+/// Connect to a server over HTTPS, using the HTTPS endpoint with the transport profile
+/// `profile_uri`.
+async fn https_connect(profile_uri: &str) {
+    let tester = Tester::new(default_server().https(0), true).await;
+
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    // Only endpoints without message security are available over HTTPS.
+    assert!(endpoints
+        .iter()
+        .filter(|e| e.endpoint_url.as_ref().starts_with("https://"))
+        .all(|e| e.security_mode == MessageSecurityMode::None));
+    let endpoint = endpoints
+        .iter()
+        .find(|e| e.transport_profile_uri.as_ref() == profile_uri)
+        .unwrap()
+        .clone();
+    assert!(endpoint.endpoint_url.as_ref().starts_with("https://"));
+
+    let (session, event_loop) = tester
+        .client
+        .session_builder()
+        .connect_to_endpoint_directly(endpoint)
+        .unwrap()
+        .build(tester.client.certificate_store().clone());
+    let _h = event_loop.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    let values = session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert!(values[0].value.is_some());
+
+    session.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn https_binary() {
+    https_connect(profiles::TRANSPORT_PROFILE_URI_HTTPS_BINARY).await;
+}
+
+#[tokio::test]
+async fn https_json() {
+    https_connect(profiles::TRANSPORT_PROFILE_URI_HTTPS_JSON).await;
+}
+
+/// Connect using an ECC security policy. Both the server and client need an ECC
+/// application instance certificate for this.
+async fn ecc_connect(
+    security_policy: SecurityPolicy,
+    mode: MessageSecurityMode,
+    token: IdentityToken,
+) {
+    let key_type = security_policy.key_type().unwrap();
+    let tmp_dir = TempDir::new("ecc-certs").unwrap();
+    let create_cert = |name: &str, desc: ApplicationDescription| {
+        let mut x509_data: X509Data = desc.into();
+        x509_data.key_type = key_type;
+        let cert_path = tmp_dir.path().join(format!("{name}.der"));
+        let pkey_path = tmp_dir.path().join(format!("{name}.pem"));
+        CertificateStore::create_certificate_and_key(&x509_data, true, &cert_path, &pkey_path)
+            .unwrap();
+        (cert_path, pkey_path)
+    };
+
+    let server = default_server().add_endpoint(
+        "ecc",
+        (
+            "/",
+            security_policy,
+            mode,
+            &[ANONYMOUS_USER_TOKEN_ID, CLIENT_USERPASS_ID] as &[&str],
+        ),
+    );
+    let (cert_path, pkey_path) = create_cert("server", server.config().application_description());
+    let server = server
+        .certificate_path(cert_path)
+        .private_key_path(pkey_path);
+
+    let (cert_path, pkey_path) = create_cert(
+        "client",
+        default_client(0, true).config().application_description(),
+    );
+    let client = default_client(0, true)
+        .certificate_path(cert_path)
+        .private_key_path(pkey_path);
+
+    let mut tester = Tester::new_custom_client(server, client).await;
+    let (session, handle) = tester.connect(security_policy, mode, token).await.unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    session
+        .read(
+            &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                VariableId::Server_ServiceLevel,
+            ))],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn connect_ecc_nist_p256_sign() {
+    ecc_connect(
+        SecurityPolicy::EccNistP256,
+        MessageSecurityMode::Sign,
+        IdentityToken::Anonymous,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn connect_ecc_nist_p256_sign_and_encrypt() {
+    ecc_connect(
+        SecurityPolicy::EccNistP256,
+        MessageSecurityMode::SignAndEncrypt,
+        IdentityToken::Anonymous,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn connect_ecc_nist_p256_with_username_password() {
+    ecc_connect(
+        SecurityPolicy::EccNistP256,
+        MessageSecurityMode::SignAndEncrypt,
+        client_user_token(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn connect_ecc_nist_p384_sign_and_encrypt() {
+    ecc_connect(
+        SecurityPolicy::EccNistP384,
+        MessageSecurityMode::SignAndEncrypt,
+        IdentityToken::Anonymous,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn connect_ecc_nist_p384_with_username_password() {
+    ecc_connect(
+        SecurityPolicy::EccNistP384,
+        MessageSecurityMode::Sign,
+        client_user_token(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn connect_ecc_curve25519_sign() {
+    ecc_connect(
+        SecurityPolicy::EccCurve25519,
+        MessageSecurityMode::Sign,
+        IdentityToken::Anonymous,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn connect_ecc_curve25519_with_username_password() {
+    ecc_connect(
+        SecurityPolicy::EccCurve25519,
+        MessageSecurityMode::SignAndEncrypt,
+        client_user_token(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn connect_ecc_brainpool_p256r1_sign_and_encrypt() {
+    ecc_connect(
+        SecurityPolicy::EccBrainpoolP256r1,
+        MessageSecurityMode::SignAndEncrypt,
+        IdentityToken::Anonymous,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn connect_ecc_brainpool_p384r1_with_username_password() {
+    ecc_connect(
+        SecurityPolicy::EccBrainpoolP384r1,
+        MessageSecurityMode::Sign,
+        client_user_token(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn rsa_and_ecc_endpoints() {
+    // A server with RSA and ECC endpoints uses one certificate per key type, the
+    // ECC certificate is created next to the default RSA certificate.
+    let server = default_server().add_endpoint(
+        "ecc",
+        (
+            "/",
+            SecurityPolicy::EccNistP256,
+            MessageSecurityMode::SignAndEncrypt,
+            &[ANONYMOUS_USER_TOKEN_ID] as &[&str],
+        ),
+    );
+    let mut tester = Tester::new_custom_client(server, default_client(0, true)).await;
+
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    let server_certificate = |security_policy: SecurityPolicy| {
+        let endpoint = endpoints
+            .iter()
+            .find(|e| e.security_policy_uri.as_ref() == security_policy.to_uri())
+            .unwrap();
+        X509::from_byte_string(&endpoint.server_certificate).unwrap()
+    };
+    assert_eq!(
+        server_certificate(SecurityPolicy::Basic256Sha256)
+            .public_key()
+            .unwrap()
+            .key_type(),
+        KeyType::Rsa
+    );
+    assert_eq!(
+        server_certificate(SecurityPolicy::EccNistP256)
+            .public_key()
+            .unwrap()
+            .key_type(),
+        KeyType::NistP256
+    );
+
+    let mut x509_data: X509Data = default_client(0, true)
+        .config()
+        .application_description()
+        .into();
+    x509_data.key_type = KeyType::NistP256;
+    tester
+        .client
+        .certificate_store()
+        .read()
+        .create_and_store_application_instance_cert(&x509_data, false)
+        .unwrap();
+
+    for security_policy in [SecurityPolicy::Basic256Sha256, SecurityPolicy::EccNistP256] {
+        let (session, handle) = tester
+            .connect(
+                security_policy,
+                MessageSecurityMode::SignAndEncrypt,
+                IdentityToken::Anonymous,
+            )
+            .await
+            .unwrap();
+        let _h = handle.spawn();
+
+        tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+            .await
+            .unwrap();
+        session
+            .read(
+                &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                    VariableId::Server_ServiceLevel,
+                ))],
+                TimestampsToReturn::Both,
+                0.0,
+            )
+            .await
+            .unwrap();
+        session.disconnect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn connect_in_memory_certificate_stores() {
+    let server_backend = Arc::new(InMemoryCertificateStoreBackend::new());
+    let client_backend = Arc::new(InMemoryCertificateStoreBackend::new());
+    let server = default_server().with_certificate_store_backend(server_backend.clone());
+    let client = default_client(0, true).certificate_store_backend(client_backend.clone());
+
+    let mut tester = Tester::new_custom_client(server, client).await;
+    let (session, handle) = tester
+        .connect(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            IdentityToken::Anonymous,
+        )
+        .await
+        .unwrap();
+    let _h = handle.spawn();
+
+    tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    // Both sides created their key pair in memory, and trusted the other side.
+    let client_cert = client_backend.read_own_cert().unwrap();
+    let server_cert = server_backend.read_own_cert().unwrap();
+    assert!(server_backend.contains_certificate(CertificateListKind::Trusted, &client_cert));
+    assert!(client_backend.contains_certificate(CertificateListKind::Trusted, &server_cert));
+}
@@ -0,0 +1,345 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use opcua::{
+    client::{gds::GdsClient, Session},
+    crypto::{CertificateListKind, X509Data, X509},
+    server::{
+        address_space::{MethodBuilder, ObjectBuilder},
+        node_manager::memory::{simple_node_manager, NamespaceMetadata, SimpleNodeManager},
+    },
+    types::{
+        gds::{ApplicationRecordDataType, GdsTypeLoader, GDS_NAMESPACE_URI},
+        ApplicationType, Argument, BinaryEncodable, ByteString, ContextOwned, DataTypeId,
+        LocalizedText, NodeId, ObjectId, QualifiedName, StatusCode, TrustListDataType,
+        TrustListMasks, Variant,
+    },
+};
+
+use crate::utils::{test_server, Tester};
+
+/// A mock GDS, with a `Directory` object exposing the pull management methods.
+struct MockGds {
+    tester: Tester,
+    session: Arc<Session>,
+    /// The certificate returned by `FinishRequest`.
+    issued: X509,
+    /// The issuer certificate returned by `FinishRequest`.
+    issuer: X509,
+    /// The certificate in the trust list returned by `GetTrustList`.
+    trusted: X509,
+}
+
+fn add_method(
+    nm: &SimpleNodeManager,
+    parent_id: &NodeId,
+    id: NodeId,
+    browse_name: QualifiedName,
+    inputs: &[(&str, DataTypeId)],
+    cb: impl Fn(&[Variant]) -> Result<Vec<Variant>, StatusCode> + Send + Sync + 'static,
+) {
+    {
+        let mut address_space = nm.address_space().write();
+        let name = browse_name.name.to_string();
+        let inputs_id = NodeId::new(id.namespace, format!("{id}_InputArguments"));
+        let inputs: Vec<Argument> = inputs.iter().map(|&i| i.into()).collect();
+        MethodBuilder::new(&id, browse_name, name)
+            .executable(true)
+            .user_executable(true)
+            .component_of(parent_id.clone())
+            .input_args(&mut *address_space, &inputs_id, &inputs)
+            .insert(&mut *address_space);
+    }
+    nm.inner().add_method_callback(id, cb);
+}
+
+fn generate_cert(name: &str) -> X509 {
+    let mut data = X509Data::sample_cert();
+    data.common_name = name.to_owned();
+    X509::cert_and_pkey(&data).unwrap().0
+}
+
+async fn setup_gds() -> MockGds {
+    let server = test_server()
+        .with_node_manager(simple_node_manager(
+            NamespaceMetadata {
+                namespace_uri: GDS_NAMESPACE_URI.to_owned(),
+                ..Default::default()
+            },
+            "gds",
+        ))
+        .with_type_loader(Arc::new(GdsTypeLoader));
+    let mut tester = Tester::new(server, false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<SimpleNodeManager>()
+        .unwrap();
+    let ns = tester
+        .handle
+        .get_namespace_index(GDS_NAMESPACE_URI)
+        .unwrap();
+
+    // The GDS issues a certificate for the key pair of the client.
+    let client_pkey = tester
+        .client
+        .certificate_store()
+        .read()
+        .read_own_pkey()
+        .unwrap();
+    let mut data = X509Data::sample_cert();
+    data.common_name = "issued".to_owned();
+    let issued = X509::from_pkey(&client_pkey, &data).unwrap();
+    let issuer = generate_cert("issuer");
+    let trusted = generate_cert("trusted");
+
+    let directory_id = NodeId::new(ns, "Directory");
+    {
+        let mut address_space = nm.address_space().write();
+        ObjectBuilder::new(
+            &directory_id,
+            QualifiedName::new(ns, "Directory"),
+            "Directory",
+        )
+        .organized_by(ObjectId::ObjectsFolder)
+        .insert(&mut *address_space);
+    }
+
+    let application_id = NodeId::new(ns, "App");
+    let request_id = NodeId::new(ns, "Request");
+    let trust_list_id = NodeId::new(ns, "TrustList");
+
+    let app_id = application_id.clone();
+    add_method(
+        &nm,
+        &directory_id,
+        NodeId::new(ns, "RegisterApplication"),
+        QualifiedName::new(ns, "RegisterApplication"),
+        &[("Application", DataTypeId::Structure)],
+        move |args| {
+            let Some(Variant::ExtensionObject(obj)) = args.first() else {
+                return Err(StatusCode::BadInvalidArgument);
+            };
+            let Some(record) = obj.inner_as::<ApplicationRecordDataType>() else {
+                return Err(StatusCode::BadInvalidArgument);
+            };
+            if record.application_uri.as_ref() != "urn:gdsclient" {
+                return Err(StatusCode::BadInvalidArgument);
+            }
+            Ok(vec![app_id.clone().into()])
+        },
+    );
+
+    let (app_id, req_id) = (application_id.clone(), request_id.clone());
+    add_method(
+        &nm,
+        &directory_id,
+        NodeId::new(ns, "StartSigningRequest"),
+        QualifiedName::new(ns, "StartSigningRequest"),
+        &[
+            ("ApplicationId", DataTypeId::NodeId),
+            ("CertificateGroupId", DataTypeId::NodeId),
+            ("CertificateTypeId", DataTypeId::NodeId),
+            ("CertificateRequest", DataTypeId::ByteString),
+        ],
+        move |args| {
+            let [Variant::NodeId(application_id), _, _, Variant::ByteString(csr)] = args else {
+                return Err(StatusCode::BadInvalidArgument);
+            };
+            // The signing request is a DER encoded sequence.
+            if **application_id != app_id || csr.as_ref().first() != Some(&0x30) {
+                return Err(StatusCode::BadInvalidArgument);
+            }
+            Ok(vec![req_id.clone().into()])
+        },
+    );
+
+    // The request is approved after the first call to `FinishRequest`.
+    let approved = AtomicBool::new(false);
+    let (cert, issuer_cert) = (issued.clone(), issuer.clone());
+    add_method(
+        &nm,
+        &directory_id,
+        NodeId::new(ns, "FinishRequest"),
+        QualifiedName::new(ns, "FinishRequest"),
+        &[
+            ("ApplicationId", DataTypeId::NodeId),
+            ("RequestId", DataTypeId::NodeId),
+        ],
+        move |args| {
+            if args.get(1) != Some(&Variant::from(request_id.clone())) {
+                return Err(StatusCode::BadInvalidArgument);
+            }
+            if !approved.swap(true, Ordering::Relaxed) {
+                return Err(StatusCode::BadNothingToDo);
+            }
+            Ok(vec![
+                cert.as_byte_string().into(),
+                ByteString::null().into(),
+                vec![issuer_cert.as_byte_string()].into(),
+            ])
+        },
+    );
+
+    let trust_list_node = trust_list_id.clone();
+    add_method(
+        &nm,
+        &directory_id,
+        NodeId::new(ns, "GetTrustList"),
+        QualifiedName::new(ns, "GetTrustList"),
+        &[
+            ("ApplicationId", DataTypeId::NodeId),
+            ("CertificateGroupId", DataTypeId::NodeId),
+        ],
+        move |_| Ok(vec![trust_list_node.clone().into()]),
+    );
+    add_method(
+        &nm,
+        &directory_id,
+        NodeId::new(ns, "GetCertificateStatus"),
+        QualifiedName::new(ns, "GetCertificateStatus"),
+        &[
+            ("ApplicationId", DataTypeId::NodeId),
+            ("CertificateGroupId", DataTypeId::NodeId),
+            ("CertificateTypeId", DataTypeId::NodeId),
+        ],
+        move |_| Ok(vec![true.into()]),
+    );
+
+    // The trust list file object, read in small chunks.
+    {
+        let mut address_space = nm.address_space().write();
+        ObjectBuilder::new(&trust_list_id, "TrustList", "TrustList")
+            .component_of(directory_id.clone())
+            .insert(&mut *address_space);
+    }
+    let trust_list = TrustListDataType {
+        specified_lists: TrustListMasks::TrustedCertificates as u32,
+        trusted_certificates: Some(vec![trusted.as_byte_string()]),
+        trusted_crls: None,
+        issuer_certificates: None,
+        issuer_crls: None,
+    };
+    let data = trust_list.encode_to_vec(&ContextOwned::default().context());
+    let position = AtomicUsize::new(0);
+    add_method(
+        &nm,
+        &trust_list_id,
+        NodeId::new(ns, "TrustListOpen"),
+        "Open".into(),
+        &[("Mode", DataTypeId::Byte)],
+        |_| Ok(vec![1u32.into()]),
+    );
+    add_method(
+        &nm,
+        &trust_list_id,
+        NodeId::new(ns, "TrustListRead"),
+        "Read".into(),
+        &[
+            ("FileHandle", DataTypeId::UInt32),
+            ("Length", DataTypeId::Int32),
+        ],
+        move |_| {
+            let start = position.load(Ordering::Relaxed);
+            let end = (start + 100).min(data.len());
+            position.store(end, Ordering::Relaxed);
+            Ok(vec![ByteString::from(&data[start..end]).into()])
+        },
+    );
+    add_method(
+        &nm,
+        &trust_list_id,
+        NodeId::new(ns, "TrustListClose"),
+        "Close".into(),
+        &[("FileHandle", DataTypeId::UInt32)],
+        |_| Ok(vec![]),
+    );
+
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    session.wait_for_connection().await;
+
+    MockGds {
+        tester,
+        session,
+        issued,
+        issuer,
+        trusted,
+    }
+}
+
+#[tokio::test]
+async fn gds_pull_enrolment() {
+    let gds = setup_gds().await;
+    let client = GdsClient::new(&gds.session).await.unwrap();
+    let store = gds.tester.client.certificate_store();
+
+    let application_id = client
+        .register_application(ApplicationRecordDataType {
+            application_uri: "urn:gdsclient".into(),
+            application_type: ApplicationType::Client,
+            application_names: Some(vec![LocalizedText::from("GDS client")]),
+            product_uri: "urn:gdsclient:product".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(client
+        .get_certificate_status(&application_id, &NodeId::null(), &NodeId::null())
+        .await
+        .unwrap());
+
+    let request_id = client
+        .request_certificate(store, &application_id, &NodeId::null(), &NodeId::null())
+        .await
+        .unwrap();
+    // The first attempt finds the request still pending.
+    assert!(!client
+        .finish_certificate_request(store, &application_id, &request_id)
+        .await
+        .unwrap());
+    assert_ne!(
+        store.read().read_own_cert().unwrap().thumbprint(),
+        gds.issued.thumbprint()
+    );
+    assert!(client
+        .finish_certificate_request(store, &application_id, &request_id)
+        .await
+        .unwrap());
+    {
+        let store = store.read();
+        assert_eq!(
+            store.read_own_cert().unwrap().thumbprint(),
+            gds.issued.thumbprint()
+        );
+        assert!(store
+            .backend()
+            .contains_certificate(CertificateListKind::Issuer, &gds.issuer));
+    }
+
+    client
+        .update_trust_list(store, &application_id, &NodeId::null())
+        .await
+        .unwrap();
+    let trusted = store
+        .read()
+        .backend()
+        .certificates(CertificateListKind::Trusted);
+    assert_eq!(trusted.len(), 1);
+    assert_eq!(trusted[0].thumbprint(), gds.trusted.thumbprint());
+}
+
+#[tokio::test]
+async fn gds_client_requires_directory() {
+    let mut tester = Tester::new(test_server(), false).await;
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    session.wait_for_connection().await;
+
+    let Err(e) = GdsClient::new(&session).await else {
+        panic!("Expected creating a GDS client to fail");
+    };
+    assert_eq!(e, StatusCode::BadNotSupported);
+}
@@ -0,0 +1,403 @@
+use std::sync::Arc;
+
+use chrono::TimeDelta;
+use opcua::{
+    client::{HistoryReadAction, HistoryUpdateAction, Session},
+    server::{
+        address_space::{AccessLevel, VariableBuilder},
+        node_manager::memory::{
+            InMemoryHistoryStorage, InMemoryNodeManagerBuilder, NamespaceMetadata,
+            SimpleNodeManager, SimpleNodeManagerBuilder,
+        },
+    },
+    types::{
+        AggregateConfiguration, DataTypeId, DataValue, DateTime, HistoryData, HistoryModifiedData,
+        HistoryReadResult, HistoryReadValueId, HistoryUpdateType, NodeId, ObjectId,
+        PerformUpdateType, ReadAtTimeDetails, ReadProcessedDetails, ReadRawModifiedDetails,
+        StatusCode, TimestampsToReturn, UpdateDataDetails, VariableTypeId, Variant,
+    },
+};
+
+use super::utils::{default_server, Tester};
+
+async fn setup_history() -> (Tester, Arc<SimpleNodeManager>, Arc<Session>) {
+    let server = default_server().with_node_manager(
+        InMemoryNodeManagerBuilder::new(SimpleNodeManagerBuilder::new(
+            NamespaceMetadata {
+                namespace_uri: "urn:historytest".to_owned(),
+                ..Default::default()
+            },
+            "history",
+        ))
+        .with_history_storage(InMemoryHistoryStorage::new(100)),
+    );
+    let mut tester = Tester::new(server, false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<SimpleNodeManager>()
+        .unwrap();
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    session.wait_for_connection().await;
+
+    (tester, nm, session)
+}
+
+fn add_variable(tester: &Tester, nm: &SimpleNodeManager, name: &str, historizing: bool) -> NodeId {
+    let ns = tester
+        .handle
+        .get_namespace_index("urn:historytest")
+        .unwrap();
+    let id = NodeId::new(ns, name.to_owned());
+    let access = AccessLevel::CURRENT_READ | AccessLevel::HISTORY_READ | AccessLevel::HISTORY_WRITE;
+    let mut address_space = nm.address_space().write();
+    VariableBuilder::new(&id, name, name)
+        .historizing(historizing)
+        .value(0.0)
+        .data_type(DataTypeId::Double)
+        .access_level(access)
+        .user_access_level(access)
+        .organized_by(ObjectId::ObjectsFolder)
+        .has_type_definition(VariableTypeId::BaseDataVariableType)
+        .insert(&mut *address_space);
+    id
+}
+
+fn set_history(tester: &Tester, nm: &SimpleNodeManager, id: &NodeId, start: DateTime, count: i64) {
+    for i in 0..count {
+        let ts = start + TimeDelta::try_seconds(i).unwrap();
+        nm.set_value(
+            tester.handle.subscriptions(),
+            id,
+            None,
+            DataValue {
+                value: Some(Variant::Double(i as f64)),
+                status: Some(StatusCode::Good),
+                source_timestamp: Some(ts),
+                server_timestamp: Some(ts),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    }
+}
+
+async fn read_history(
+    session: &Session,
+    action: HistoryReadAction,
+    id: &NodeId,
+    continuation_point: opcua::types::ByteString,
+) -> HistoryReadResult {
+    let mut r = session
+        .history_read(
+            action,
+            TimestampsToReturn::Both,
+            false,
+            &[HistoryReadValueId {
+                node_id: id.clone(),
+                index_range: Default::default(),
+                data_encoding: Default::default(),
+                continuation_point,
+            }],
+        )
+        .await
+        .unwrap();
+    assert_eq!(r.len(), 1);
+    r.remove(0)
+}
+
+fn values(r: &HistoryReadResult) -> Vec<f64> {
+    r.history_data
+        .inner_as::<HistoryData>()
+        .unwrap()
+        .data_values
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|v| v.value.as_ref().and_then(|v| v.as_f64()).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn history_storage_read_raw() {
+    let (tester, nm, session) = setup_history().await;
+    let id = add_variable(&tester, &nm, "Historized", true);
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    // Store more values than the capacity, only the last 100 are kept.
+    set_history(&tester, &nm, &id, start, 120);
+
+    let action = HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+        is_read_modified: false,
+        start_time: start,
+        end_time: start + TimeDelta::try_seconds(2000).unwrap(),
+        num_values_per_node: 30,
+        return_bounds: false,
+    });
+
+    let mut data = Vec::new();
+    let mut cp = Default::default();
+    for i in 0..4 {
+        let r = read_history(&session, action.clone(), &id, cp).await;
+        assert_eq!(r.status_code, StatusCode::Good);
+        data.extend(values(&r));
+        cp = r.continuation_point;
+        assert_eq!(cp.is_null(), i == 3);
+    }
+    assert_eq!(data, (20..120).map(|v| v as f64).collect::<Vec<_>>());
+
+    // Read backwards with bounds.
+    let r = read_history(
+        &session,
+        HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+            is_read_modified: false,
+            start_time: start + TimeDelta::try_seconds(50).unwrap(),
+            end_time: start + TimeDelta::try_seconds(45).unwrap(),
+            num_values_per_node: 0,
+            return_bounds: true,
+        }),
+        &id,
+        Default::default(),
+    )
+    .await;
+    assert!(r.continuation_point.is_null());
+    assert_eq!(values(&r), vec![50.0, 49.0, 48.0, 47.0, 46.0, 45.0]);
+
+    // Without a start time, the newest values before the end time are read backwards.
+    let r = read_history(
+        &session,
+        HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+            is_read_modified: false,
+            start_time: DateTime::null(),
+            end_time: start + TimeDelta::try_seconds(50).unwrap(),
+            num_values_per_node: 5,
+            return_bounds: false,
+        }),
+        &id,
+        Default::default(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert!(!r.continuation_point.is_null());
+    assert_eq!(values(&r), vec![49.0, 48.0, 47.0, 46.0, 45.0]);
+}
+
+#[tokio::test]
+async fn history_storage_not_historizing() {
+    let (tester, nm, session) = setup_history().await;
+    let id = add_variable(&tester, &nm, "NotHistorized", false);
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    set_history(&tester, &nm, &id, start, 10);
+
+    let r = read_history(
+        &session,
+        HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+            is_read_modified: false,
+            start_time: start,
+            end_time: DateTime::now(),
+            num_values_per_node: 100,
+            return_bounds: false,
+        }),
+        &id,
+        Default::default(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::GoodNoData);
+    assert!(values(&r).is_empty());
+}
+
+#[tokio::test]
+async fn history_storage_read_at_time() {
+    let (tester, nm, session) = setup_history().await;
+    let id = add_variable(&tester, &nm, "Historized", true);
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    set_history(&tester, &nm, &id, start, 10);
+
+    let r = read_history(
+        &session,
+        HistoryReadAction::ReadAtTimeDetails(ReadAtTimeDetails {
+            req_times: Some(vec![
+                start + TimeDelta::try_seconds(3).unwrap(),
+                start + TimeDelta::try_milliseconds(4500).unwrap(),
+                start - TimeDelta::try_seconds(1).unwrap(),
+            ]),
+            use_simple_bounds: true,
+        }),
+        &id,
+        Default::default(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+    let data = r
+        .history_data
+        .inner_as::<HistoryData>()
+        .unwrap()
+        .data_values
+        .clone()
+        .unwrap();
+    assert_eq!(data.len(), 3);
+    assert_eq!(data[0].value, Some(Variant::Double(3.0)));
+    assert_eq!(data[1].value, Some(Variant::Double(4.5)));
+    assert!(data[1].status().is_good());
+    assert_eq!(
+        data[1].status().value_type(),
+        opcua::types::StatusCodeValueType::Interpolated
+    );
+    assert_eq!(data[2].status(), StatusCode::BadNoData);
+}
+
+#[tokio::test]
+async fn history_storage_update() {
+    let (tester, nm, session) = setup_history().await;
+    let id = add_variable(&tester, &nm, "Historized", true);
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    set_history(&tester, &nm, &id, start, 10);
+
+    let value = |secs: i64, v: f64| DataValue {
+        value: Some(Variant::Double(v)),
+        status: Some(StatusCode::Good),
+        source_timestamp: Some(start + TimeDelta::try_seconds(secs).unwrap()),
+        ..Default::default()
+    };
+
+    let results = session
+        .history_update(&[
+            HistoryUpdateAction::UpdateDataDetails(UpdateDataDetails {
+                node_id: id.clone(),
+                perform_insert_replace: PerformUpdateType::Replace,
+                update_values: Some(vec![value(2, 20.0), value(20, 200.0)]),
+            }),
+            HistoryUpdateAction::UpdateDataDetails(UpdateDataDetails {
+                node_id: id.clone(),
+                perform_insert_replace: PerformUpdateType::Insert,
+                update_values: Some(vec![value(3, 30.0), value(15, 150.0)]),
+            }),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(
+        results[0].operation_results,
+        Some(vec![
+            StatusCode::GoodEntryReplaced,
+            StatusCode::BadNoEntryExists
+        ])
+    );
+    assert_eq!(
+        results[1].operation_results,
+        Some(vec![
+            StatusCode::BadEntryExists,
+            StatusCode::GoodEntryInserted
+        ])
+    );
+
+    let r = read_history(
+        &session,
+        HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+            is_read_modified: false,
+            start_time: start,
+            end_time: DateTime::now(),
+            num_values_per_node: 100,
+            return_bounds: false,
+        }),
+        &id,
+        Default::default(),
+    )
+    .await;
+    assert_eq!(
+        values(&r),
+        vec![0.0, 1.0, 20.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 150.0]
+    );
+
+    // The replaced value is available as a modified value.
+    let r = read_history(
+        &session,
+        HistoryReadAction::ReadRawModifiedDetails(ReadRawModifiedDetails {
+            is_read_modified: true,
+            start_time: start,
+            end_time: DateTime::now(),
+            num_values_per_node: 100,
+            return_bounds: false,
+        }),
+        &id,
+        Default::default(),
+    )
+    .await;
+    let modified = r.history_data.inner_as::<HistoryModifiedData>().unwrap();
+    let data = modified.data_values.as_ref().unwrap();
+    let infos = modified.modification_infos.as_ref().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0].value, Some(Variant::Double(2.0)));
+    assert_eq!(infos[0].update_type, HistoryUpdateType::Replace);
+}
+
+#[tokio::test]
+async fn history_storage_read_processed() {
+    let (tester, nm, session) = setup_history().await;
+    let id = add_variable(&tester, &nm, "Historized", true);
+    let start = DateTime::now() - TimeDelta::try_seconds(1000).unwrap();
+    set_history(&tester, &nm, &id, start, 10);
+
+    let processed = |aggregate: ObjectId| {
+        HistoryReadAction::ReadProcessedDetails(ReadProcessedDetails {
+            start_time: start,
+            end_time: start + TimeDelta::try_seconds(10).unwrap(),
+            processing_interval: 5000.0,
+            aggregate_type: Some(vec![aggregate.into()]),
+            aggregate_configuration: AggregateConfiguration {
+                use_server_capabilities_defaults: true,
+                ..Default::default()
+            },
+        })
+    };
+
+    let r = read_history(
+        &session,
+        processed(ObjectId::AggregateFunction_Average),
+        &id,
+        Default::default(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert_eq!(values(&r), vec![2.0, 7.0]);
+
+    let r = read_history(
+        &session,
+        processed(ObjectId::AggregateFunction_Maximum),
+        &id,
+        Default::default(),
+    )
+    .await;
+    assert_eq!(values(&r), vec![4.0, 9.0]);
+
+    let r = read_history(
+        &session,
+        processed(ObjectId::AggregateFunction_StandardDeviationSample),
+        &id,
+        Default::default(),
+    )
+    .await;
+    assert_eq!(r.status_code, StatusCode::BadAggregateNotSupported);
+
+    // The number of aggregates must match the number of nodes.
+    let err = session
+        .history_read(
+            HistoryReadAction::ReadProcessedDetails(ReadProcessedDetails {
+                start_time: start,
+                end_time: DateTime::now(),
+                processing_interval: 5000.0,
+                aggregate_type: None,
+                aggregate_configuration: Default::default(),
+            }),
+            TimestampsToReturn::Both,
+            false,
+            &[HistoryReadValueId {
+                node_id: id.clone(),
+                index_range: Default::default(),
+                data_encoding: Default::default(),
+                continuation_point: Default::default(),
+            }],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err, StatusCode::BadAggregateListMismatch);
+}
@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use opcua::{
+    client::IdentityToken,
+    crypto::{KeySize, PrivateKey, SecurityPolicy, X509Data, X509},
+    server::{
+        jwt::{JwtConfig, JWT_ISSUED_TOKEN_TYPE},
+        ServerUserToken,
+    },
+    types::{
+        CallMethodRequest, ExtensionObject, IdentityCriteriaType, IdentityMappingRuleType,
+        MessageSecurityMode, MethodId, NodeId, ObjectId, ReadValueId, StatusCode,
+        TimestampsToReturn, UserTokenType, VariableId, Variant,
+    },
+};
+use serde_json::json;
+use tempdir::TempDir;
+
+use crate::utils::{default_client, default_server, Tester};
+
+const JWT_USER_TOKEN_ID: &str = "jwt";
+
+/// A mock authorization service, signing tokens with an RSA key.
+struct Issuer {
+    _dir: TempDir,
+    jwks_path: String,
+    key: PrivateKey,
+}
+
+impl Issuer {
+    fn new() -> Self {
+        let (cert, key) = X509::cert_and_pkey(&X509Data::sample_cert()).unwrap();
+        let dir = TempDir::new("jwks").unwrap();
+        let jwks_path = dir.path().join("jwks.json");
+        let jwks = json!({
+            "keys": [{
+                "kty": "RSA",
+                "kid": "issuer-key",
+                "x5c": [STANDARD.encode(cert.as_byte_string().as_ref())],
+            }]
+        });
+        std::fs::write(&jwks_path, jwks.to_string()).unwrap();
+        Self {
+            _dir: dir,
+            jwks_path: jwks_path.to_string_lossy().to_string(),
+            key,
+        }
+    }
+
+    fn token(&self, audience: &str, expires_in: i64) -> IdentityToken {
+        self.token_with_scope(audience, expires_in, "opcua.admin")
+    }
+
+    fn token_with_scope(&self, audience: &str, expires_in: i64, scope: &str) -> IdentityToken {
+        let header = json!({ "alg": "RS256", "kid": "issuer-key", "typ": "JWT" });
+        let claims = json!({
+            "iss": "urn:issuer",
+            "sub": "jwt_user",
+            "aud": audience,
+            "exp": chrono::Utc::now().timestamp() + expires_in,
+            "scope": scope,
+        });
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let mut signature = vec![0u8; self.key.signature_size()];
+        let len = self
+            .key
+            .sign_sha256(signed.as_bytes(), &mut signature)
+            .unwrap();
+        let token = format!("{signed}.{}", URL_SAFE_NO_PAD.encode(&signature[..len]));
+        IdentityToken::IssuedToken(token.as_bytes().into())
+    }
+}
+
+async fn issued_token_tester(issuer: &Issuer) -> Tester {
+    let mut server = default_server().add_user_token(
+        JWT_USER_TOKEN_ID,
+        ServerUserToken::jwt(
+            "oauth",
+            JwtConfig {
+                jwks_files: vec![issuer.jwks_path.clone()],
+                issuer: Some("urn:issuer".to_owned()),
+                audience: "urn:integration_server".to_owned(),
+                scope_roles: BTreeMap::from([(
+                    "opcua.admin".to_owned(),
+                    vec!["SecurityAdmin".to_owned()],
+                )]),
+                ..Default::default()
+            },
+        ),
+    );
+    for endpoint in server.config_mut().endpoints.values_mut() {
+        endpoint.user_token_ids.insert(JWT_USER_TOKEN_ID.to_owned());
+    }
+    Tester::new_custom_client(server, default_client(0, true)).await
+}
+
+#[tokio::test]
+async fn connect_with_issued_token() {
+    let issuer = Issuer::new();
+    let mut tester = issued_token_tester(&issuer).await;
+
+    let endpoints = tester
+        .client
+        .get_server_endpoints_from_url(tester.endpoint())
+        .await
+        .unwrap();
+    let policy = endpoints[0]
+        .find_policy(UserTokenType::IssuedToken)
+        .unwrap();
+    assert_eq!(policy.issued_token_type.as_ref(), JWT_ISSUED_TOKEN_TYPE);
+
+    for (security_policy, mode) in [
+        (SecurityPolicy::None, MessageSecurityMode::None),
+        (
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+        ),
+    ] {
+        let (session, handle) = tester
+            .connect(
+                security_policy,
+                mode,
+                issuer.token("urn:integration_server", 300),
+            )
+            .await
+            .unwrap();
+        let _h = handle.spawn();
+
+        tokio::time::timeout(Duration::from_secs(20), session.wait_for_connection())
+            .await
+            .unwrap();
+
+        session
+            .read(
+                &[ReadValueId::from(<VariableId as Into<NodeId>>::into(
+                    VariableId::Server_ServiceLevel,
+                ))],
+                TimestampsToReturn::Both,
+                0.0,
+            )
+            .await
+            .unwrap();
+        session.disconnect().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn connect_with_invalid_issued_token() {
+    let issuer = Issuer::new();
+    let mut tester = issued_token_tester(&issuer).await;
+
+    for token in [
+        issuer.token("urn:integration_server", -300),
+        issuer.token("urn:other_server", 300),
+    ] {
+        let (_, handle) = tester
+            .connect(
+                SecurityPolicy::Basic256Sha256,
+                MessageSecurityMode::SignAndEncrypt,
+                token,
+            )
+            .await
+            .unwrap();
+        let res = handle.spawn().await.unwrap();
+        assert_eq!(res, StatusCode::BadIdentityTokenRejected);
+    }
+}
+
+#[tokio::test]
+async fn issued_token_roles_are_per_session() {
+    let issuer = Issuer::new();
+    let mut tester = issued_token_tester(&issuer).await;
+
+    // Two sessions of the same subject, only the first is granted `SecurityAdmin`.
+    let admin = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            issuer.token("urn:integration_server", 300),
+        )
+        .await
+        .unwrap();
+    let reader = tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            issuer.token_with_scope("urn:integration_server", 300, "opcua.read"),
+        )
+        .await
+        .unwrap();
+
+    let rule = Variant::from(ExtensionObject::from_message(IdentityMappingRuleType {
+        criteria_type: IdentityCriteriaType::Anonymous,
+        criteria: Default::default(),
+    }));
+    for (session, method, expected) in [
+        (
+            &reader,
+            MethodId::WellKnownRole_Operator_AddIdentity,
+            StatusCode::BadUserAccessDenied,
+        ),
+        (
+            &admin,
+            MethodId::WellKnownRole_Operator_AddIdentity,
+            StatusCode::Good,
+        ),
+        (
+            &admin,
+            MethodId::WellKnownRole_Operator_RemoveIdentity,
+            StatusCode::Good,
+        ),
+    ] {
+        let res = session
+            .call_one(CallMethodRequest {
+                object_id: ObjectId::WellKnownRole_Operator.into(),
+                method_id: method.into(),
+                input_arguments: Some(vec![rule.clone()]),
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.status_code, expected);
+    }
+}
@@ -3,14 +3,14 @@ use std::{
     time::Duration,
 };
 
-use crate::utils::ChannelNotifications;
+use crate::utils::{read_value_id, setup_simple, ChannelNotifications, SIMPLE_NAMESPACE_URI};
 
 use super::utils::setup;
 use opcua::{
-    server::address_space::MethodBuilder,
+    server::{address_space::MethodBuilder, node_manager::TypedMethod},
     types::{
-        AttributeId, CallMethodRequest, DataTypeId, NodeId, ObjectId, StatusCode, Variant,
-        VariantTypeId,
+        Argument, AttributeId, CallMethodRequest, DataTypeId, NodeId, ObjectId, StatusCode,
+        Variant, VariantTypeId,
     },
 };
 use opcua_types::{
@@ -304,3 +304,136 @@ async fn call_get_monitored_items() {
     assert_eq!(handles.len(), 1);
     assert_eq!(15, handles[0]);
 }
+
+#[tokio::test]
+async fn call_typed() {
+    let (tester, nm, session) = setup_simple().await;
+    let ns = tester
+        .handle
+        .get_namespace_index(SIMPLE_NAMESPACE_URI)
+        .unwrap();
+
+    let add = TypedMethod::new(|lhs: i64, rhs: Option<i64>| Ok((lhs + rhs.unwrap_or(1),)))
+        .input_names(["Lhs", "Rhs"])
+        .output_names(["Result"]);
+    let hello = TypedMethod::new_async(|names: Vec<String>| async move {
+        if names.is_empty() {
+            return Err(StatusCode::BadInvalidArgument);
+        }
+        Ok((format!("Hello {}!", names.join(", ")), names.len() as u32))
+    });
+
+    let add_id = NodeId::new(ns, "Add");
+    let add_input_id = NodeId::new(ns, "AddInput");
+    let hello_id = NodeId::new(ns, "Hello");
+    {
+        let mut sp = nm.address_space().write();
+        MethodBuilder::new(&add_id, "Add", "Add")
+            .executable(true)
+            .user_executable(true)
+            .component_of(ObjectId::ObjectsFolder)
+            .input_args(&mut *sp, &add_input_id, add.input_arguments())
+            .output_args(
+                &mut *sp,
+                &NodeId::new(ns, "AddOutput"),
+                add.output_arguments(),
+            )
+            .insert(&mut *sp);
+        MethodBuilder::new(&hello_id, "Hello", "Hello")
+            .executable(true)
+            .user_executable(true)
+            .component_of(ObjectId::ObjectsFolder)
+            .input_args(
+                &mut *sp,
+                &NodeId::new(ns, "HelloInput"),
+                hello.input_arguments(),
+            )
+            .output_args(
+                &mut *sp,
+                &NodeId::new(ns, "HelloOutput"),
+                hello.output_arguments(),
+            )
+            .insert(&mut *sp);
+    }
+    nm.inner().add_typed_method(add_id.clone(), add);
+    nm.inner().add_typed_method(hello_id.clone(), hello);
+
+    // The input arguments are described by the rust types.
+    let r = session
+        .read(
+            &[read_value_id(AttributeId::Value, &add_input_id)],
+            TimestampsToReturn::Neither,
+            0.0,
+        )
+        .await
+        .unwrap();
+    let Some(Variant::Array(arr)) = &r[0].value else {
+        panic!("Expected an array, got {:?}", r[0]);
+    };
+    let args: Vec<_> = arr
+        .values
+        .iter()
+        .map(|v| match v {
+            Variant::ExtensionObject(o) => {
+                let arg = o.inner_as::<Argument>().unwrap();
+                (arg.name.to_string(), arg.data_type.clone(), arg.value_rank)
+            }
+            v => panic!("Expected an extension object, got {v:?}"),
+        })
+        .collect();
+    assert_eq!(
+        args,
+        vec![
+            ("Lhs".to_owned(), DataTypeId::Int64.into(), -1),
+            ("Rhs".to_owned(), DataTypeId::Int64.into(), -1)
+        ]
+    );
+
+    let call = |method_id: &NodeId, args: Vec<Variant>| {
+        session.call_one(CallMethodRequest {
+            object_id: ObjectId::ObjectsFolder.into(),
+            method_id: method_id.clone(),
+            input_arguments: Some(args),
+        })
+    };
+
+    let r = call(&add_id, vec![Variant::Int64(3), Variant::Int64(2)])
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert_eq!(r.output_arguments, Some(vec![Variant::Int64(5)]));
+
+    // Arguments are cast where possible, and optional arguments may be empty.
+    let r = call(&add_id, vec![Variant::Int32(3), Variant::Empty])
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert_eq!(r.output_arguments, Some(vec![Variant::Int64(4)]));
+
+    // Each argument that cannot be converted gets a result.
+    let r = call(&add_id, vec![Variant::from("foo"), Variant::Int64(2)])
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::BadInvalidArgument);
+    assert_eq!(
+        r.input_argument_results,
+        Some(vec![StatusCode::BadTypeMismatch, StatusCode::Good])
+    );
+
+    let r = call(&add_id, vec![Variant::Int64(3)]).await.unwrap();
+    assert_eq!(r.status_code, StatusCode::BadArgumentsMissing);
+
+    let r = call(&hello_id, vec![Variant::from(vec!["Foo", "Bar"])])
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::Good);
+    assert_eq!(
+        r.output_arguments,
+        Some(vec![Variant::from("Hello Foo, Bar!"), Variant::UInt32(2)])
+    );
+
+    let r = call(&hello_id, vec![Variant::from(Vec::<String>::new())])
+        .await
+        .unwrap();
+    assert_eq!(r.status_code, StatusCode::BadInvalidArgument);
+}
@@ -1,9 +1,17 @@
 mod browse;
+mod certificate_management;
+mod conditions;
 mod core_tests;
 mod custom_types;
+mod gds;
+mod history;
+mod issued_tokens;
 mod methods;
 mod node_management;
+mod query;
 mod read;
+mod roles;
+mod simple;
 mod subscriptions;
 mod write;
 
@@ -1,10 +1,22 @@
-use super::utils::setup;
+use std::path::Path;
+
+use super::utils::{
+    default_server, make_test_node_manager_impl, read_value_id, setup, setup_with_server,
+};
 use opcua::{
-    server::address_space::{EventNotifier, NodeBase, NodeType, ObjectBuilder},
+    nodes::TypeTree,
+    server::{
+        address_space::{AccessLevel, EventNotifier, NodeBase, NodeType, ObjectBuilder},
+        node_manager::memory::{
+            FileNodeStorage, InMemoryNodeManagerBuilder, NodeChange, NodeStorage,
+        },
+        ServerBuilder,
+    },
     types::{
-        AddNodeAttributes, AddNodesItem, AddReferencesItem, DeleteNodesItem, DeleteReferencesItem,
-        ExpandedNodeId, NodeClass, NodeId, ObjectAttributes, ObjectId, ObjectTypeId,
-        ReferenceTypeId, StatusCode,
+        AddNodeAttributes, AddNodesItem, AddReferencesItem, AttributeId, AttributesMask,
+        DataTypeId, DataValue, DeleteNodesItem, DeleteReferencesItem, ExpandedNodeId, NodeClass,
+        NodeId, NumericRange, ObjectAttributes, ObjectId, ObjectTypeId, ReferenceTypeId,
+        StatusCode, TimestampsToReturn, VariableAttributes, VariableTypeId, Variant, WriteValue,
     },
 };
 
@@ -212,3 +224,177 @@ async fn add_delete_reference_limits() {
         .unwrap_err();
     assert_eq!(e, StatusCode::BadTooManyOperations);
 }
+
+fn persistent_server(path: &Path) -> ServerBuilder {
+    default_server().with_node_manager(
+        InMemoryNodeManagerBuilder::new(make_test_node_manager_impl)
+            .with_node_storage(FileNodeStorage::new(path)),
+    )
+}
+
+fn add_object_item(id: &NodeId, name: &str) -> AddNodesItem {
+    AddNodesItem {
+        parent_node_id: ObjectId::ObjectsFolder.into(),
+        reference_type_id: ReferenceTypeId::Organizes.into(),
+        requested_new_node_id: id.clone().into(),
+        browse_name: name.into(),
+        node_class: NodeClass::Object,
+        node_attributes: AddNodeAttributes::Object(ObjectAttributes {
+            specified_attributes: AttributesMask::DISPLAY_NAME.bits(),
+            display_name: name.into(),
+            ..Default::default()
+        })
+        .as_extension_object(),
+        type_definition: ExpandedNodeId::new(ObjectTypeId::FolderType),
+    }
+}
+
+#[tokio::test]
+async fn persistent_node_storage() {
+    let path = std::env::temp_dir().join(format!("opcua-node-storage-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (obj_id, var_id, removed_id) = {
+        let (tester, nm, session) = setup_with_server(persistent_server(&path)).await;
+        let ns = tester
+            .handle
+            .get_namespace_index("urn:rustopcuatestserver")
+            .unwrap();
+        let obj_id = NodeId::new(ns, "Persisted");
+        let var_id = NodeId::new(ns, "PersistedVar");
+        let removed_id = NodeId::new(ns, "Removed");
+        let access = (AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE).bits();
+
+        // The parent of the variable must exist before the variable is added.
+        let r = session
+            .add_nodes(&[
+                add_object_item(&obj_id, "Persisted"),
+                add_object_item(&removed_id, "Removed"),
+            ])
+            .await
+            .unwrap();
+        assert!(r.iter().all(|r| r.status_code == StatusCode::Good));
+
+        let r = session
+            .add_nodes(&[AddNodesItem {
+                parent_node_id: obj_id.clone().into(),
+                reference_type_id: ReferenceTypeId::HasComponent.into(),
+                requested_new_node_id: var_id.clone().into(),
+                browse_name: "PersistedVar".into(),
+                node_class: NodeClass::Variable,
+                node_attributes: AddNodeAttributes::Variable(VariableAttributes {
+                    specified_attributes: (AttributesMask::DISPLAY_NAME
+                        | AttributesMask::DATA_TYPE
+                        | AttributesMask::VALUE
+                        | AttributesMask::ACCESS_LEVEL
+                        | AttributesMask::USER_ACCESS_LEVEL)
+                        .bits(),
+                    display_name: "PersistedVar".into(),
+                    data_type: DataTypeId::Int32.into(),
+                    value: Variant::Int32(1),
+                    value_rank: -1,
+                    access_level: access,
+                    user_access_level: access,
+                    ..Default::default()
+                })
+                .as_extension_object(),
+                type_definition: ExpandedNodeId::new(VariableTypeId::BaseDataVariableType),
+            }])
+            .await
+            .unwrap();
+        assert_eq!(r[0].status_code, StatusCode::Good);
+
+        let r = session
+            .write(&[WriteValue {
+                node_id: var_id.clone(),
+                attribute_id: AttributeId::Value as u32,
+                index_range: NumericRange::None,
+                value: DataValue::new_now(42),
+            }])
+            .await
+            .unwrap();
+        assert_eq!(r, vec![StatusCode::Good]);
+
+        let r = session
+            .delete_nodes(&[DeleteNodesItem {
+                node_id: removed_id.clone(),
+                delete_target_references: true,
+            }])
+            .await
+            .unwrap();
+        assert_eq!(r, vec![StatusCode::Good]);
+
+        // Values set by the server are only stored once enabled for the variable.
+        nm.set_value(
+            tester.handle.subscriptions(),
+            &var_id,
+            None,
+            DataValue::new_now(43),
+        )
+        .unwrap();
+        nm.flush_node_storage();
+        let stored = FileNodeStorage::new(&path)
+            .load(tester.handle.type_tree().read().namespaces())
+            .unwrap();
+        let Some(NodeChange::Stored(node)) = stored.iter().find(|c| c.node_id() == &var_id) else {
+            panic!("Missing stored variable");
+        };
+        let (_, value) = node
+            .attributes
+            .iter()
+            .find(|(id, _)| *id == AttributeId::Value)
+            .unwrap();
+        assert_eq!(value.value, Some(Variant::Int32(42)));
+
+        nm.set_value_persistence(&var_id, true);
+        nm.set_value(
+            tester.handle.subscriptions(),
+            &var_id,
+            None,
+            DataValue::new_now(44),
+        )
+        .unwrap();
+        nm.flush_node_storage();
+
+        (obj_id, var_id, removed_id)
+    };
+
+    // Start a new server using the same storage.
+    let (_tester, nm, session) = setup_with_server(persistent_server(&path)).await;
+    std::fs::remove_file(&path).unwrap();
+
+    {
+        let sp = nm.address_space().read();
+        let Some(NodeType::Object(o)) = sp.find(&obj_id) else {
+            panic!("Missing restored object");
+        };
+        assert_eq!(o.display_name(), &"Persisted".into());
+        assert!(sp.has_reference(
+            &ObjectId::ObjectsFolder.into(),
+            &obj_id,
+            ReferenceTypeId::Organizes
+        ));
+        assert!(sp.has_reference(
+            &obj_id,
+            &ObjectTypeId::FolderType.into(),
+            ReferenceTypeId::HasTypeDefinition
+        ));
+        assert!(sp.has_reference(&obj_id, &var_id, ReferenceTypeId::HasComponent));
+        assert!(sp.find(&removed_id).is_none());
+        assert!(!sp.has_reference(
+            &ObjectId::ObjectsFolder.into(),
+            &removed_id,
+            ReferenceTypeId::Organizes
+        ));
+    }
+
+    let r = session
+        .read(
+            &[read_value_id(AttributeId::Value, &var_id)],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Int32(44)));
+}
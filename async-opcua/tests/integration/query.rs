@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+use super::utils::{setup, TestNodeManager, Tester};
+use futures::TryStreamExt;
+use opcua::{
+    client::services::QueryFirst,
+    server::address_space::{ObjectBuilder, ObjectTypeBuilder, VariableBuilder},
+    types::{
+        AttributeId, ContentFilter, ContentFilterBuilder, DataTypeId, ExpandedNodeId, NodeId,
+        NodeTypeDescription, NumericRange, ObjectId, ObjectTypeId, Operand, QualifiedName,
+        QueryDataDescription, ReferenceTypeId, RelativePath, RelativePathElement, StatusCode,
+        VariableTypeId, Variant,
+    },
+};
+use tokio_util::sync::CancellationToken;
+
+/// Add an object type with a `Value` property, and one instance of it
+/// for each value in `values`. Returns the ID of the type.
+fn add_test_objects(tester: &Tester, nm: &Arc<TestNodeManager>, values: &[i32]) -> NodeId {
+    let type_id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        ObjectTypeBuilder::new(&type_id, "QueryTestType", "QueryTestType")
+            .build()
+            .into(),
+        &ObjectTypeId::BaseObjectType.into(),
+        &ReferenceTypeId::HasSubtype.into(),
+        None,
+        Vec::new(),
+    );
+    let prop_id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&prop_id, "Value", "Value")
+            .data_type(DataTypeId::Int32)
+            .value(0)
+            .build()
+            .into(),
+        &type_id,
+        &ReferenceTypeId::HasProperty.into(),
+        Some(&VariableTypeId::PropertyType.into()),
+        Vec::new(),
+    );
+
+    for (idx, value) in values.iter().enumerate() {
+        let id = nm.inner().next_node_id();
+        let name = format!("QueryTestObj{idx}");
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            ObjectBuilder::new(&id, name.as_str(), name.as_str())
+                .build()
+                .into(),
+            &ObjectId::ObjectsFolder.into(),
+            &ReferenceTypeId::Organizes.into(),
+            Some(&type_id),
+            Vec::new(),
+        );
+        let prop_id = nm.inner().next_node_id();
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            VariableBuilder::new(&prop_id, "Value", "Value")
+                .data_type(DataTypeId::Int32)
+                .value(*value)
+                .build()
+                .into(),
+            &id,
+            &ReferenceTypeId::HasProperty.into(),
+            Some(&VariableTypeId::PropertyType.into()),
+            Vec::new(),
+        );
+    }
+
+    type_id
+}
+
+fn value_path() -> RelativePath {
+    RelativePath {
+        elements: Some(vec![RelativePathElement {
+            reference_type_id: ReferenceTypeId::HasProperty.into(),
+            is_inverse: false,
+            include_subtypes: true,
+            target_name: "Value".into(),
+        }]),
+    }
+}
+
+fn node_type(type_id: &NodeId) -> NodeTypeDescription {
+    NodeTypeDescription {
+        type_definition_node: type_id.into(),
+        include_sub_types: true,
+        data_to_return: Some(vec![
+            QueryDataDescription {
+                relative_path: RelativePath::default(),
+                attribute_id: AttributeId::BrowseName as u32,
+                index_range: NumericRange::None,
+            },
+            QueryDataDescription {
+                relative_path: value_path(),
+                attribute_id: AttributeId::Value as u32,
+                index_range: NumericRange::None,
+            },
+        ]),
+    }
+}
+
+#[tokio::test]
+async fn query_first() {
+    let (tester, nm, session) = setup().await;
+    let type_id = add_test_objects(&tester, &nm, &[1, 2, 3]);
+
+    let filter = ContentFilterBuilder::new()
+        .gt(
+            Operand::simple_attribute(
+                type_id.clone(),
+                "Value",
+                AttributeId::Value,
+                NumericRange::None,
+            ),
+            Operand::literal(1),
+        )
+        .build();
+
+    let r = session
+        .query_first(&[node_type(&type_id)], filter, 0, 0, None)
+        .await
+        .unwrap();
+    assert!(r.continuation_point.is_null_or_empty());
+    let mut data_sets = r.query_data_sets.unwrap_or_default();
+    assert_eq!(data_sets.len(), 2);
+    data_sets.sort_by_key(|d| d.node_id.node_id.to_string());
+
+    for (data_set, (name, value)) in data_sets
+        .iter()
+        .zip([("QueryTestObj1", 2), ("QueryTestObj2", 3)])
+    {
+        assert_eq!(data_set.type_definition_node.node_id, type_id);
+        let values = data_set.values.clone().unwrap_or_default();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0], Variant::from(QualifiedName::from(name)));
+        assert_eq!(values[1], Variant::Int32(value));
+    }
+}
+
+#[tokio::test]
+async fn query_next() {
+    let (tester, nm, session) = setup().await;
+    let type_id = add_test_objects(&tester, &nm, &[1, 2, 3, 4, 5]);
+
+    let r = session
+        .query_first(&[node_type(&type_id)], ContentFilter::default(), 2, 0, None)
+        .await
+        .unwrap();
+    let mut count = r.query_data_sets.unwrap_or_default().len();
+    assert_eq!(count, 2);
+    let mut continuation_point = r.continuation_point;
+
+    while !continuation_point.is_null_or_empty() {
+        let r = session
+            .query_next(false, continuation_point.clone())
+            .await
+            .unwrap();
+        let num = r.query_data_sets.unwrap_or_default().len();
+        assert!(num <= 2);
+        count += num;
+        continuation_point = r.revised_continuation_point;
+    }
+    assert_eq!(count, 5);
+
+    // Releasing a continuation point means it can no longer be used.
+    let r = session
+        .query_first(&[node_type(&type_id)], ContentFilter::default(), 2, 0, None)
+        .await
+        .unwrap();
+    assert!(!r.continuation_point.is_null_or_empty());
+    let released = session
+        .query_next(true, r.continuation_point.clone())
+        .await
+        .unwrap();
+    assert!(released.query_data_sets.unwrap_or_default().is_empty());
+    let err = session
+        .query_next(false, r.continuation_point)
+        .await
+        .unwrap_err();
+    assert_eq!(err, StatusCode::BadContinuationPointInvalid);
+}
+
+#[tokio::test]
+async fn query_stream() {
+    let (tester, nm, session) = setup().await;
+    let type_id = add_test_objects(&tester, &nm, &[1, 2, 3, 4, 5, 6, 7]);
+
+    let data_sets: Vec<_> = session
+        .query(
+            QueryFirst::new(&session)
+                .node_type(node_type(&type_id))
+                .max_data_sets_to_return(3),
+            CancellationToken::new(),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(data_sets.len(), 7);
+    let mut values: Vec<_> = data_sets
+        .into_iter()
+        .map(|d| d.values.unwrap_or_default()[1].clone())
+        .collect();
+    values.sort_by_key(|v| match v {
+        Variant::Int32(v) => *v,
+        _ => panic!("Unexpected value {v:?}"),
+    });
+    assert_eq!(values, (1..=7).map(Variant::Int32).collect::<Vec<_>>());
+
+    // A cancelled query stops immediately.
+    let token = CancellationToken::new();
+    token.cancel();
+    let data_sets: Vec<_> = session
+        .query(
+            QueryFirst::new(&session).node_type(node_type(&type_id)),
+            token,
+        )
+        .try_collect()
+        .await
+        .unwrap();
+    assert!(data_sets.is_empty());
+}
+
+#[tokio::test]
+async fn query_cross_node_manager() {
+    let (tester, nm, session) = setup().await;
+    let type_id = add_test_objects(&tester, &nm, &[1]);
+
+    // Select nodes from both the core node manager and the test node manager,
+    // paging through results one at a time.
+    let filter = ContentFilterBuilder::new()
+        .or(Operand::element(1), Operand::element(2))
+        .of_type(Operand::literal(NodeId::from(ObjectTypeId::ServerType)))
+        .of_type(Operand::literal(type_id.clone()))
+        .build();
+
+    let data_sets: Vec<_> = session
+        .query(
+            QueryFirst::new(&session)
+                .node_type(NodeTypeDescription {
+                    type_definition_node: ObjectTypeId::BaseObjectType.into(),
+                    include_sub_types: true,
+                    data_to_return: None,
+                })
+                .filter(filter)
+                .max_data_sets_to_return(1),
+            CancellationToken::new(),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(data_sets.len(), 2);
+    let mut types: Vec<ExpandedNodeId> = data_sets
+        .into_iter()
+        .map(|d| d.type_definition_node)
+        .collect();
+    types.sort_by_key(|t| t.to_string());
+    let mut expected: Vec<ExpandedNodeId> = vec![type_id.into(), ObjectTypeId::ServerType.into()];
+    expected.sort_by_key(|t| t.to_string());
+    assert_eq!(types, expected);
+}
+
+#[tokio::test]
+async fn query_of_type() {
+    let (tester, nm, session) = setup().await;
+    let type_id = add_test_objects(&tester, &nm, &[1, 2]);
+
+    let filter = ContentFilterBuilder::new()
+        .of_type(Operand::literal(type_id.clone()))
+        .build();
+    let data_sets: Vec<_> = session
+        .query(
+            QueryFirst::new(&session)
+                .node_type(NodeTypeDescription {
+                    type_definition_node: ObjectTypeId::BaseObjectType.into(),
+                    include_sub_types: true,
+                    data_to_return: None,
+                })
+                .filter(filter),
+            CancellationToken::new(),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(data_sets.len(), 2);
+    for data_set in data_sets {
+        assert_eq!(data_set.type_definition_node.node_id, type_id);
+    }
+}
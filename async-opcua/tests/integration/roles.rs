@@ -0,0 +1,393 @@
+use std::sync::Arc;
+
+use opcua::{
+    client::{IdentityToken, Session},
+    crypto::SecurityPolicy,
+    server::{
+        address_space::{AccessLevel, MethodBuilder, VariableBuilder},
+        ServerUserToken,
+    },
+    types::{
+        AttributeId, BrowseDescription, BrowseDirection, BrowseResultMask, CallMethodRequest,
+        DataTypeId, DataValue, ExtensionObject, IdentityCriteriaType, IdentityMappingRuleType,
+        MessageSecurityMode, MethodId, NodeClassMask, NodeId, NumericRange, ObjectId,
+        PermissionType, ReferenceTypeId, RolePermissionType, StatusCode, TimestampsToReturn,
+        VariableId, VariableTypeId, Variant, WriteValue,
+    },
+};
+
+use crate::utils::{
+    client_user_token, read_value_id, test_server, TestNodeManager, Tester, CLIENT_USERPASS_ID,
+};
+
+struct RoleNodes {
+    variable: NodeId,
+    hidden: NodeId,
+    method: NodeId,
+}
+
+fn role_permission(role: ObjectId, permissions: PermissionType) -> RolePermissionType {
+    RolePermissionType {
+        role_id: role.into(),
+        permissions,
+    }
+}
+
+async fn setup_roles(roles: &[&str]) -> (Tester, RoleNodes) {
+    let server = test_server().add_user_token(
+        CLIENT_USERPASS_ID,
+        ServerUserToken {
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+            ..ServerUserToken::user_pass(
+                CLIENT_USERPASS_ID,
+                &format!("{CLIENT_USERPASS_ID}_password"),
+            )
+        },
+    );
+    let tester = Tester::new(server, true).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<TestNodeManager>()
+        .unwrap();
+
+    let nodes = RoleNodes {
+        variable: nm.inner().next_node_id(),
+        hidden: nm.inner().next_node_id(),
+        method: nm.inner().next_node_id(),
+    };
+    let access_level = AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE;
+    for (id, name, role_permissions) in [
+        (
+            &nodes.variable,
+            "RoleVar",
+            vec![
+                role_permission(
+                    ObjectId::WellKnownRole_Operator,
+                    PermissionType::Browse | PermissionType::Read | PermissionType::Write,
+                ),
+                role_permission(ObjectId::WellKnownRole_Anonymous, PermissionType::Browse),
+            ],
+        ),
+        (
+            &nodes.hidden,
+            "HiddenVar",
+            vec![role_permission(
+                ObjectId::WellKnownRole_Operator,
+                PermissionType::Browse | PermissionType::Read,
+            )],
+        ),
+    ] {
+        nm.inner().add_node(
+            nm.address_space(),
+            tester.handle.type_tree(),
+            VariableBuilder::new(id, name, name)
+                .data_type(DataTypeId::Int32)
+                .value(1)
+                .access_level(access_level)
+                .user_access_level(access_level)
+                .role_permissions(role_permissions)
+                .build()
+                .into(),
+            &ObjectId::ObjectsFolder.into(),
+            &ReferenceTypeId::Organizes.into(),
+            Some(&VariableTypeId::BaseDataVariableType.into()),
+            Vec::new(),
+        );
+    }
+    {
+        let mut sp = nm.address_space().write();
+        MethodBuilder::new(&nodes.method, "RoleMethod", "RoleMethod")
+            .executable(true)
+            .user_executable(true)
+            .component_of(ObjectId::ObjectsFolder)
+            .role_permissions(vec![role_permission(
+                ObjectId::WellKnownRole_Operator,
+                PermissionType::Browse | PermissionType::Call,
+            )])
+            .insert(&mut *sp);
+    }
+    nm.inner()
+        .add_method_cb(nodes.method.clone(), |_| Ok(Vec::new()));
+
+    (tester, nodes)
+}
+
+async fn connect(tester: &mut Tester, identity: IdentityToken) -> Arc<Session> {
+    tester
+        .connect_and_wait(
+            SecurityPolicy::Basic256Sha256,
+            MessageSecurityMode::SignAndEncrypt,
+            identity,
+        )
+        .await
+        .unwrap()
+}
+
+async fn read(session: &Session, attribute: AttributeId, id: impl Into<NodeId>) -> DataValue {
+    session
+        .read(
+            &[read_value_id(attribute, id)],
+            TimestampsToReturn::Neither,
+            0.0,
+        )
+        .await
+        .unwrap()
+        .remove(0)
+}
+
+async fn write(session: &Session, id: &NodeId, value: i32) -> StatusCode {
+    session
+        .write(&[WriteValue {
+            node_id: id.clone(),
+            attribute_id: AttributeId::Value as u32,
+            index_range: NumericRange::None,
+            value: DataValue::new_now(value),
+        }])
+        .await
+        .unwrap()[0]
+}
+
+async fn browse_objects(session: &Session) -> Vec<NodeId> {
+    session
+        .browse(
+            &[BrowseDescription {
+                node_id: ObjectId::ObjectsFolder.into(),
+                browse_direction: BrowseDirection::Forward,
+                reference_type_id: ReferenceTypeId::HierarchicalReferences.into(),
+                include_subtypes: true,
+                node_class_mask: NodeClassMask::all().bits(),
+                result_mask: BrowseResultMask::All as u32,
+            }],
+            1000,
+            None,
+        )
+        .await
+        .unwrap()
+        .remove(0)
+        .references
+        .unwrap_or_default()
+        .into_iter()
+        .map(|r| r.node_id.node_id)
+        .collect()
+}
+
+async fn call(
+    session: &Session,
+    object_id: ObjectId,
+    method_id: NodeId,
+    args: Vec<Variant>,
+) -> StatusCode {
+    session
+        .call_one(CallMethodRequest {
+            object_id: object_id.into(),
+            method_id,
+            input_arguments: Some(args),
+        })
+        .await
+        .unwrap()
+        .status_code
+}
+
+fn user_role_permissions(value: &DataValue) -> Vec<RolePermissionType> {
+    let Some(Variant::Array(arr)) = &value.value else {
+        panic!("Expected an array, got {value:?}");
+    };
+    arr.values
+        .iter()
+        .map(|v| match v {
+            Variant::ExtensionObject(o) => o.inner_as::<RolePermissionType>().unwrap().clone(),
+            v => panic!("Expected an extension object, got {v:?}"),
+        })
+        .collect()
+}
+
+fn anonymous_rule() -> IdentityMappingRuleType {
+    IdentityMappingRuleType {
+        criteria_type: IdentityCriteriaType::Anonymous,
+        criteria: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn role_permissions() {
+    let (mut tester, nodes) = setup_roles(&["Operator"]).await;
+
+    // Anonymous users may only browse the first variable.
+    let session = connect(&mut tester, IdentityToken::Anonymous).await;
+    let r = read(&session, AttributeId::Value, nodes.variable.clone()).await;
+    assert_eq!(r.status, Some(StatusCode::BadUserAccessDenied));
+    let r = read(&session, AttributeId::DisplayName, nodes.hidden.clone()).await;
+    assert_eq!(r.status, Some(StatusCode::BadNodeIdUnknown));
+    let r = read(
+        &session,
+        AttributeId::UserAccessLevel,
+        nodes.variable.clone(),
+    )
+    .await;
+    assert_eq!(r.value, Some(Variant::Byte(0)));
+    let r = read(
+        &session,
+        AttributeId::UserRolePermissions,
+        nodes.variable.clone(),
+    )
+    .await;
+    assert_eq!(
+        user_role_permissions(&r),
+        vec![role_permission(
+            ObjectId::WellKnownRole_Anonymous,
+            PermissionType::Browse
+        )]
+    );
+    assert_eq!(
+        write(&session, &nodes.variable, 2).await,
+        StatusCode::BadUserAccessDenied
+    );
+    let browsed = browse_objects(&session).await;
+    assert!(browsed.contains(&nodes.variable));
+    assert!(!browsed.contains(&nodes.hidden));
+    assert!(!browsed.contains(&nodes.method));
+    assert_eq!(
+        call(
+            &session,
+            ObjectId::ObjectsFolder,
+            nodes.method.clone(),
+            Vec::new()
+        )
+        .await,
+        StatusCode::BadUserAccessDenied
+    );
+
+    // Operators may do everything.
+    let session = connect(&mut tester, client_user_token()).await;
+    let r = read(&session, AttributeId::Value, nodes.variable.clone()).await;
+    assert_eq!(r.value, Some(Variant::Int32(1)));
+    let r = read(&session, AttributeId::Value, nodes.hidden.clone()).await;
+    assert_eq!(r.value, Some(Variant::Int32(1)));
+    let r = read(
+        &session,
+        AttributeId::UserRolePermissions,
+        nodes.variable.clone(),
+    )
+    .await;
+    assert_eq!(
+        user_role_permissions(&r),
+        vec![role_permission(
+            ObjectId::WellKnownRole_Operator,
+            PermissionType::Browse | PermissionType::Read | PermissionType::Write
+        )]
+    );
+    // Reading the role permissions requires the `ReadRolePermissions` permission.
+    let r = read(
+        &session,
+        AttributeId::RolePermissions,
+        nodes.variable.clone(),
+    )
+    .await;
+    assert_eq!(r.status, Some(StatusCode::BadUserAccessDenied));
+    assert_eq!(write(&session, &nodes.variable, 2).await, StatusCode::Good);
+    assert_eq!(
+        write(&session, &nodes.hidden, 2).await,
+        StatusCode::BadUserAccessDenied
+    );
+    let browsed = browse_objects(&session).await;
+    assert!(browsed.contains(&nodes.variable));
+    assert!(browsed.contains(&nodes.hidden));
+    assert!(browsed.contains(&nodes.method));
+    assert_eq!(
+        call(
+            &session,
+            ObjectId::ObjectsFolder,
+            nodes.method.clone(),
+            Vec::new()
+        )
+        .await,
+        StatusCode::Good
+    );
+}
+
+#[tokio::test]
+async fn role_identity_management() {
+    let (mut tester, nodes) = setup_roles(&["SecurityAdmin"]).await;
+
+    let anonymous = connect(&mut tester, IdentityToken::Anonymous).await;
+    let r = read(
+        &anonymous,
+        AttributeId::Value,
+        VariableId::WellKnownRole_Operator_Identities,
+    )
+    .await;
+    let Some(Variant::Array(arr)) = r.value else {
+        panic!("Expected an array, got {r:?}");
+    };
+    assert_eq!(arr.values.len(), 1);
+
+    // Only security admins may manage identities.
+    let rule = Variant::from(ExtensionObject::from_message(anonymous_rule()));
+    assert_eq!(
+        call(
+            &anonymous,
+            ObjectId::WellKnownRole_Operator,
+            MethodId::WellKnownRole_Operator_AddIdentity.into(),
+            vec![rule.clone()]
+        )
+        .await,
+        StatusCode::BadUserAccessDenied
+    );
+    let r = read(&anonymous, AttributeId::Value, nodes.hidden.clone()).await;
+    assert_eq!(r.status, Some(StatusCode::BadNodeIdUnknown));
+
+    // Let anonymous users act as operators.
+    let admin = connect(&mut tester, client_user_token()).await;
+    assert_eq!(
+        call(
+            &admin,
+            ObjectId::WellKnownRole_Operator,
+            MethodId::WellKnownRole_Operator_AddIdentity.into(),
+            vec![rule.clone()]
+        )
+        .await,
+        StatusCode::Good
+    );
+    let r = read(&anonymous, AttributeId::Value, nodes.hidden.clone()).await;
+    assert_eq!(r.value, Some(Variant::Int32(1)));
+
+    // Rules must have criteria matching their type.
+    let invalid = Variant::from(ExtensionObject::from_message(IdentityMappingRuleType {
+        criteria_type: IdentityCriteriaType::UserName,
+        criteria: Default::default(),
+    }));
+    assert_eq!(
+        call(
+            &admin,
+            ObjectId::WellKnownRole_Operator,
+            MethodId::WellKnownRole_Operator_AddIdentity.into(),
+            vec![invalid]
+        )
+        .await,
+        StatusCode::BadInvalidArgument
+    );
+
+    assert_eq!(
+        call(
+            &admin,
+            ObjectId::WellKnownRole_Operator,
+            MethodId::WellKnownRole_Operator_RemoveIdentity.into(),
+            vec![rule.clone()]
+        )
+        .await,
+        StatusCode::Good
+    );
+    assert_eq!(
+        call(
+            &admin,
+            ObjectId::WellKnownRole_Operator,
+            MethodId::WellKnownRole_Operator_RemoveIdentity.into(),
+            vec![rule]
+        )
+        .await,
+        StatusCode::BadNotFound
+    );
+    let r = read(&anonymous, AttributeId::Value, nodes.hidden.clone()).await;
+    assert_eq!(r.status, Some(StatusCode::BadNodeIdUnknown));
+}
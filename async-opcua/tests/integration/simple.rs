@@ -0,0 +1,302 @@
+use std::{
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use opcua::{
+    nodes::{ObjectBuilder, UaObject},
+    server::{
+        address_space::{AccessLevel, MethodBuilder, VariableBuilder},
+        node_manager::memory::SimpleNodeManager,
+        ANONYMOUS_USER_TOKEN_ID,
+    },
+    types::{
+        AttributeId, CallMethodRequest, DataTypeId, DataValue, LocalizedText,
+        MonitoredItemCreateRequest, MonitoringMode, MonitoringParameters, NodeId, NumericRange,
+        ObjectId, ReadValueId, StatusCode, TimestampsToReturn, Variant, WriteValue,
+    },
+};
+use tokio::{sync::Barrier, time::timeout};
+
+use crate::utils::{read_value_id, setup_simple, ChannelNotifications, SIMPLE_NAMESPACE_URI};
+
+fn add_variable(nm: &SimpleNodeManager, id: &NodeId) {
+    let mut sp = nm.address_space().write();
+    let access_level = AccessLevel::CURRENT_READ | AccessLevel::CURRENT_WRITE;
+    VariableBuilder::new(id, "AsyncVar", "AsyncVar")
+        .data_type(DataTypeId::Int32)
+        .value(0)
+        .access_level(access_level)
+        .user_access_level(access_level)
+        .organized_by(ObjectId::ObjectsFolder)
+        .insert(&mut *sp);
+}
+
+#[tokio::test]
+async fn async_read_callback() {
+    let (tester, nm, session) = setup_simple().await;
+    let ns = tester
+        .handle
+        .get_namespace_index(SIMPLE_NAMESPACE_URI)
+        .unwrap();
+    let id = NodeId::new(ns, "AsyncRead");
+    add_variable(&nm, &id);
+
+    let counter = Arc::new(AtomicI32::new(0));
+    let address_space = nm.address_space().clone();
+    nm.inner()
+        .add_async_read_callback(id.clone(), move |ctx, _, _, _| {
+            let counter = counter.clone();
+            let address_space = address_space.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                // The callback is not called while the address space is locked.
+                if address_space.try_write().is_none() {
+                    return Err(StatusCode::BadInternalError);
+                }
+                if !ctx.token.is_anonymous() {
+                    return Err(StatusCode::BadUserAccessDenied);
+                }
+                Ok(DataValue::new_now(
+                    counter.fetch_add(1, Ordering::Relaxed) + 1,
+                ))
+            }
+        });
+
+    let r = session
+        .read(
+            &[
+                read_value_id(AttributeId::Value, &id),
+                read_value_id(AttributeId::DisplayName, &id),
+            ],
+            TimestampsToReturn::Both,
+            0.0,
+        )
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Int32(1)));
+    // Other attributes are read from the node.
+    assert_eq!(
+        r[1].value,
+        Some(Variant::from(LocalizedText::from("AsyncVar")))
+    );
+
+    // Monitored items on the node are sampled using the callback.
+    let (notifs, mut data, _) = ChannelNotifications::new();
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+    let res = session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![MonitoredItemCreateRequest {
+                item_to_monitor: ReadValueId {
+                    node_id: id.clone(),
+                    attribute_id: AttributeId::Value as u32,
+                    ..Default::default()
+                },
+                monitoring_mode: MonitoringMode::Reporting,
+                requested_parameters: MonitoringParameters {
+                    sampling_interval: 100.0,
+                    queue_size: 10,
+                    discard_oldest: true,
+                    ..Default::default()
+                },
+            }],
+        )
+        .await
+        .unwrap();
+    assert_eq!(res[0].status_code, StatusCode::Good);
+
+    let mut last = 0;
+    for _ in 0..3 {
+        let (_, v) = timeout(Duration::from_millis(1000), data.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let Some(Variant::Int32(v)) = v.value else {
+            panic!("Expected integer value, got {v:?}");
+        };
+        assert!(v > last);
+        last = v;
+    }
+}
+
+#[tokio::test]
+async fn async_write_callback() {
+    let (tester, nm, session) = setup_simple().await;
+    let ns = tester
+        .handle
+        .get_namespace_index(SIMPLE_NAMESPACE_URI)
+        .unwrap();
+    let id = NodeId::new(ns, "AsyncWrite");
+    add_variable(&nm, &id);
+
+    let written = Arc::new(AtomicI32::new(0));
+    let written_ref = written.clone();
+    let address_space = nm.address_space().clone();
+    nm.inner()
+        .add_async_write_callback(id.clone(), move |_, value, _| {
+            let written = written_ref.clone();
+            let address_space = address_space.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                if address_space.try_write().is_none() {
+                    return StatusCode::BadInternalError;
+                }
+                match value.value {
+                    Some(Variant::Int32(v)) if v >= 0 => {
+                        written.store(v, Ordering::Relaxed);
+                        StatusCode::Good
+                    }
+                    Some(Variant::Int32(_)) => StatusCode::BadOutOfRange,
+                    _ => StatusCode::BadTypeMismatch,
+                }
+            }
+        });
+
+    let write = |value: i32| WriteValue {
+        node_id: id.clone(),
+        attribute_id: AttributeId::Value as u32,
+        index_range: NumericRange::None,
+        value: DataValue::new_now(value),
+    };
+    let r = session.write(&[write(5), write(-1)]).await.unwrap();
+    assert_eq!(r, vec![StatusCode::Good, StatusCode::BadOutOfRange]);
+    assert_eq!(written.load(Ordering::Relaxed), 5);
+}
+
+#[tokio::test]
+async fn async_method_callback() {
+    let (tester, nm, session) = setup_simple().await;
+    let ns = tester
+        .handle
+        .get_namespace_index(SIMPLE_NAMESPACE_URI)
+        .unwrap();
+    let id = NodeId::new(ns, "AsyncMethod");
+    {
+        let mut sp = nm.address_space().write();
+        MethodBuilder::new(&id, "AsyncMethod", "AsyncMethod")
+            .executable(true)
+            .user_executable(true)
+            .component_of(ObjectId::ObjectsFolder)
+            .output_args(
+                &mut *sp,
+                &NodeId::new(ns, "AsyncMethodOutput"),
+                &[("User", DataTypeId::String).into()],
+            )
+            .insert(&mut *sp);
+    }
+
+    let address_space = nm.address_space().clone();
+    let barrier = Arc::new(Barrier::new(2));
+    nm.inner()
+        .add_async_method_callback(id.clone(), move |ctx, args| {
+            let address_space = address_space.clone();
+            let barrier = barrier.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                if address_space.try_write().is_none() {
+                    return Err(StatusCode::BadInternalError);
+                }
+                // Both calls in the request must be running at the same time.
+                if timeout(Duration::from_millis(1000), barrier.wait())
+                    .await
+                    .is_err()
+                {
+                    return Err(StatusCode::BadTimeout);
+                }
+                if !args.is_empty() {
+                    return Err(StatusCode::BadTooManyArguments);
+                }
+                Ok(vec![Variant::from(ctx.token.0.clone())])
+            }
+        });
+
+    let call = CallMethodRequest {
+        object_id: ObjectId::ObjectsFolder.into(),
+        method_id: id.clone(),
+        input_arguments: None,
+    };
+    let r = session.call(vec![call.clone(), call]).await.unwrap();
+    for r in r {
+        assert_eq!(r.status_code, StatusCode::Good);
+        assert_eq!(
+            r.output_arguments,
+            Some(vec![Variant::from(ANONYMOUS_USER_TOKEN_ID.to_owned())])
+        );
+    }
+}
+
+#[derive(UaObject)]
+struct Motor {
+    speed: f64,
+    #[opcua(property)]
+    serial_number: String,
+}
+
+#[derive(UaObject)]
+struct Pump {
+    flow: f64,
+    #[opcua(object)]
+    motor: Motor,
+}
+
+#[tokio::test]
+async fn derived_object() {
+    let (tester, nm, session) = setup_simple().await;
+    let ns = tester
+        .handle
+        .get_namespace_index(SIMPLE_NAMESPACE_URI)
+        .unwrap();
+    let id = NodeId::new(ns, "Pump");
+    let mut pump = Pump {
+        flow: 1.0,
+        motor: Motor {
+            speed: 100.0,
+            serial_number: "ABC123".to_owned(),
+        },
+    };
+    let binding = {
+        let mut sp = nm.address_space().write();
+        pump.insert(
+            &mut *sp,
+            ObjectBuilder::new(&id, "Pump", "Pump").organized_by(ObjectId::ObjectsFolder),
+        )
+    };
+
+    let read_ids = [
+        read_value_id(AttributeId::Value, binding.get("Flow").unwrap()),
+        read_value_id(AttributeId::Value, binding.get("Motor/Speed").unwrap()),
+        read_value_id(
+            AttributeId::Value,
+            binding.get("Motor/SerialNumber").unwrap(),
+        ),
+    ];
+    let r = session
+        .read(&read_ids, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Double(1.0)));
+    assert_eq!(r[1].value, Some(Variant::Double(100.0)));
+    assert_eq!(r[2].value, Some(Variant::from("ABC123")));
+
+    // Update the struct, then write it to the address space.
+    pump.flow = 2.5;
+    pump.motor.speed = 150.0;
+    nm.set_values(tester.handle.subscriptions(), binding.values(&pump))
+        .unwrap();
+
+    let r = session
+        .read(&read_ids, TimestampsToReturn::Both, 0.0)
+        .await
+        .unwrap();
+    assert_eq!(r[0].value, Some(Variant::Double(2.5)));
+    assert_eq!(r[1].value, Some(Variant::Double(150.0)));
+    assert_eq!(r[2].value, Some(Variant::from("ABC123")));
+}
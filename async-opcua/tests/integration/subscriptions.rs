@@ -14,7 +14,8 @@ use opcua::{
 use opcua_client::{services::TransferSubscriptions, IdentityToken, Subscription, UARequest};
 use opcua_crypto::SecurityPolicy;
 use opcua_types::{
-    DataChangeFilter, DataChangeTrigger, DeadbandType, ExtensionObject, MessageSecurityMode, Range,
+    AggregateConfiguration, AggregateFilter, AggregateFilterResult, DataChangeFilter,
+    DataChangeTrigger, DateTime, DeadbandType, ExtensionObject, MessageSecurityMode, Range,
 };
 use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
 
@@ -792,3 +793,99 @@ async fn test_data_change_filters() {
 }
 
 // TODO: Add more detailed high level tests on subscriptions.
+
+#[tokio::test]
+async fn test_aggregate_filter() {
+    let (tester, nm, session) = setup().await;
+
+    let id = nm.inner().next_node_id();
+    nm.inner().add_node(
+        nm.address_space(),
+        tester.handle.type_tree(),
+        VariableBuilder::new(&id, "TestVar1", "TestVar1")
+            .value(0.0f64)
+            .data_type(DataTypeId::Double)
+            .access_level(AccessLevel::CURRENT_READ)
+            .user_access_level(AccessLevel::CURRENT_READ)
+            .build()
+            .into(),
+        &ObjectId::ObjectsFolder.into(),
+        &ReferenceTypeId::Organizes.into(),
+        Some(&VariableTypeId::BaseDataVariableType.into()),
+        Vec::new(),
+    );
+
+    let (notifs, mut data, _) = ChannelNotifications::new();
+
+    let sub_id = session
+        .create_subscription(Duration::from_millis(100), 100, 20, 1000, 0, true, notifs)
+        .await
+        .unwrap();
+
+    let item = |aggregate: ObjectId| MonitoredItemCreateRequest {
+        item_to_monitor: ReadValueId {
+            node_id: id.clone(),
+            attribute_id: AttributeId::Value as u32,
+            ..Default::default()
+        },
+        monitoring_mode: opcua::types::MonitoringMode::Reporting,
+        requested_parameters: MonitoringParameters {
+            sampling_interval: 0.0,
+            queue_size: 10,
+            discard_oldest: true,
+            filter: ExtensionObject::from_message(AggregateFilter {
+                start_time: DateTime::null(),
+                aggregate_type: aggregate.into(),
+                processing_interval: 500.0,
+                aggregate_configuration: AggregateConfiguration {
+                    use_server_capabilities_defaults: true,
+                    ..Default::default()
+                },
+            }),
+            ..Default::default()
+        },
+    };
+
+    let res = session
+        .create_monitored_items(
+            sub_id,
+            TimestampsToReturn::Both,
+            vec![
+                item(ObjectId::AggregateFunction_Count),
+                item(ObjectId::AggregateFunction_StandardDeviationSample),
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 2);
+    assert_eq!(res[0].status_code, StatusCode::Good);
+    let filter_res = res[0]
+        .filter_result
+        .inner_as::<AggregateFilterResult>()
+        .unwrap();
+    assert_eq!(filter_res.revised_processing_interval, 500.0);
+    assert!(!filter_res.revised_start_time.is_null());
+    assert_eq!(res[1].status_code, StatusCode::BadAggregateNotSupported);
+
+    for i in 1..4 {
+        nm.set_value(
+            tester.handle.subscriptions(),
+            &id,
+            None,
+            DataValue::new_now(i as f64),
+        )
+        .unwrap();
+    }
+
+    // Raw values are not reported, we only get the count once the first interval has passed.
+    let (r, v) = timeout(Duration::from_millis(1500), data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r.node_id, id);
+    assert_eq!(v.value, Some(Variant::Int32(3)));
+    assert_eq!(
+        v.status().value_type(),
+        opcua::types::StatusCodeValueType::Calculated
+    );
+}
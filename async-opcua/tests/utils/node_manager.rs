@@ -91,7 +91,8 @@ pub fn test_node_manager() -> impl NodeManagerBuilder {
     InMemoryNodeManagerBuilder::new(make_test_node_manager_impl)
 }
 
-fn make_test_node_manager_impl(
+#[allow(unused)]
+pub fn make_test_node_manager_impl(
     context: ServerContext,
     address_space: &mut AddressSpace,
 ) -> TestNodeManagerImpl {
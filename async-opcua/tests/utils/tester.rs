@@ -12,7 +12,10 @@ use std::{
 use opcua::{
     client::{Client, ClientBuilder, IdentityToken, Session, SessionEventLoop},
     crypto::SecurityPolicy,
-    server::{ServerBuilder, ServerHandle, ServerUserToken, ANONYMOUS_USER_TOKEN_ID},
+    server::{
+        node_manager::memory::{simple_node_manager, NamespaceMetadata, SimpleNodeManager},
+        ServerBuilder, ServerHandle, ServerUserToken, ANONYMOUS_USER_TOKEN_ID,
+    },
     types::{MessageSecurityMode, StatusCode},
 };
 use opcua_core::config::Config;
@@ -50,7 +53,14 @@ pub fn hostname() -> String {
 
 #[allow(unused)]
 pub async fn setup() -> (Tester, Arc<TestNodeManager>, Arc<Session>) {
-    let server = test_server();
+    setup_with_server(test_server()).await
+}
+
+/// Set up a server from `server`, which must contain a test node manager.
+#[allow(unused)]
+pub async fn setup_with_server(
+    server: ServerBuilder,
+) -> (Tester, Arc<TestNodeManager>, Arc<Session>) {
     let mut tester = Tester::new(server, false).await;
     let nm = tester
         .handle
@@ -66,6 +76,34 @@ pub async fn setup() -> (Tester, Arc<TestNodeManager>, Arc<Session>) {
     (tester, nm, session)
 }
 
+#[allow(unused)]
+pub const SIMPLE_NAMESPACE_URI: &str = "urn:SimpleTestServer";
+
+/// Set up a server with a simple node manager in the namespace `SIMPLE_NAMESPACE_URI`.
+#[allow(unused)]
+pub async fn setup_simple() -> (Tester, Arc<SimpleNodeManager>, Arc<Session>) {
+    let server = test_server().with_node_manager(simple_node_manager(
+        NamespaceMetadata {
+            namespace_uri: SIMPLE_NAMESPACE_URI.to_owned(),
+            ..Default::default()
+        },
+        "simple",
+    ));
+    let mut tester = Tester::new(server, false).await;
+    let nm = tester
+        .handle
+        .node_managers()
+        .get_of_type::<SimpleNodeManager>()
+        .unwrap();
+    let (session, lp) = tester.connect_default().await.unwrap();
+    lp.spawn();
+    tokio::time::timeout(Duration::from_secs(2), session.wait_for_connection())
+        .await
+        .unwrap();
+
+    (tester, nm, session)
+}
+
 #[allow(unused)]
 pub fn client_user_token() -> IdentityToken {
     IdentityToken::UserName(
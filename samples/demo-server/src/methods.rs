@@ -9,7 +9,7 @@ use std::sync::Arc;
 use opcua::{
     server::{
         address_space::{EventNotifier, MethodBuilder, ObjectBuilder},
-        node_manager::memory::SimpleNodeManager,
+        node_manager::{memory::SimpleNodeManager, TypedMethod},
     },
     types::{DataTypeId, NodeId, ObjectId, StatusCode, Variant},
 };
@@ -55,6 +55,12 @@ pub fn add_methods(manager: Arc<SimpleNodeManager>, ns: u16) {
 
     // HelloX has 1 one input and 1 output - "Hello Foo" in a result parameter
     let fn_node_id = NodeId::new(ns, "HelloX");
+    let method = TypedMethod::new(|name: String| {
+        debug!("HelloX method called");
+        Ok((format!("Hello {name}!"),))
+    })
+    .input_names(["YourName"])
+    .output_names(["Result"]);
     MethodBuilder::new(&fn_node_id, "HelloX", "HelloX")
         .component_of(object_id.clone())
         .executable(true)
@@ -62,25 +68,15 @@ pub fn add_methods(manager: Arc<SimpleNodeManager>, ns: u16) {
         .input_args(
             &mut *address_space,
             &NodeId::new(ns, "HelloXInput"),
-            &[("YourName", DataTypeId::String).into()],
+            method.input_arguments(),
         )
         .output_args(
             &mut *address_space,
             &NodeId::new(ns, "HelloXOutput"),
-            &[("Result", DataTypeId::String).into()],
+            method.output_arguments(),
         )
         .insert(&mut *address_space);
-    manager.inner().add_method_callback(fn_node_id, |args| {
-        // We don't actually need to do much validation here, since it should all have happened elsewhere,
-        // but we don't want to panic if something goes wrong.
-        let Some(Variant::String(s)) = args.first() else {
-            return Err(StatusCode::BadTypeMismatch);
-        };
-
-        Ok(vec![Variant::String(
-            format!("Hello {}!", s.as_ref()).into(),
-        )])
-    });
+    manager.inner().add_typed_method(fn_node_id, method);
 
     // Boop has 1 one input and 0 output
     let fn_node_id = NodeId::new(ns, "Boop");
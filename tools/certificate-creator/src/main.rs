@@ -1,11 +1,20 @@
-use std::path::PathBuf;
+use std::{io::BufRead, path::PathBuf};
 
 use opcua::crypto::*;
 
 fn main() {
-    if let Ok((x509_data, overwrite, pki_path, cert_path, pkey_path)) = parse_x509_args() {
+    let Ok(args) = Args::parse_args().map_err(|_| Args::usage()) else {
+        return;
+    };
+    if args.hash_password && !args.help {
+        hash_password();
+    } else if let Ok((x509_data, overwrite, pki_path, cert_path, pkey_path)) = parse_x509_args(args)
+    {
         println!("Creating certificate...");
-        println!("  Key size = {}", x509_data.key_size);
+        println!("  Key type = {:?}", x509_data.key_type);
+        if x509_data.key_type == KeyType::Rsa {
+            println!("  Key size = {}", x509_data.key_size);
+        }
         println!("  CN (common name) = \"{}\"", x509_data.common_name);
         println!("  O (organization) = \"{}\"", x509_data.organization);
         println!(
@@ -59,9 +68,26 @@ fn main() {
     }
 }
 
+/// Read a password from stdin and print its hash, to use as the `pass_hash` of a server user token.
+fn hash_password() {
+    eprintln!("Enter the password to hash:");
+    let mut pass = String::new();
+    if let Err(err) = std::io::stdin().lock().read_line(&mut pass) {
+        eprintln!("Failed to read password: {}", err);
+        return;
+    }
+    let pass = pass.trim_end_matches(['\r', '\n']);
+    match password::hash_password(pass) {
+        Ok(hash) => println!("{}", hash),
+        Err(err) => eprintln!("Password hashing failed: {}", err),
+    }
+}
+
 struct Args {
     help: bool,
+    hash_password: bool,
     overwrite: bool,
+    key_type: String,
     key_size: u16,
     pki_path: String,
     cert_path: String,
@@ -84,7 +110,11 @@ impl Args {
         let mut args = pico_args::Arguments::from_env();
         Ok(Args {
             help: args.contains(["-h", "--help"]),
+            hash_password: args.contains("--hash-password"),
             overwrite: args.contains(["-o", "--overwrite"]),
+            key_type: args
+                .opt_value_from_str("--key-type")?
+                .unwrap_or_else(|| String::from(DEFAULT_KEY_TYPE)),
             key_size: args
                 .opt_value_from_str("--key-size")?
                 .unwrap_or(DEFAULT_KEY_SIZE),
@@ -135,10 +165,16 @@ This creates a self-signed key and X509 certificate for use with OPC UA clients
 Use the flags to control what the certificate contains. For convenience some values will be
 prefilled from defaults, but for production purposes all defaults should be overridden.
 
+With --hash-password, it instead reads a password from stdin and prints a salted hash of it,
+to use as the pass_hash of a user token in the server configuration.
+
 Usage:
   -h, --help            Show help.
+  --hash-password       Hash a password read from stdin, instead of creating a certificate.
   -o, --overwrite       Overwrites existing files.
-  --key-size size       Sets the key size in bits - [2048, 4096] (default: {})
+  --key-type type       Sets the key type - [rsa, nistp256, nistp384, curve25519,
+                        brainpoolp256r1, brainpoolp384r1] (default: {})
+  --key-size size       Sets the RSA key size in bits - [2048, 4096] (default: {})
   --pki-path path       Path to write the certificate and key. (default: {})
   --cert-name           Name of certificate file relative to pki-path. (default: {})
   --pkey-name           Name of private key file relative to pki-path. (default: {})
@@ -153,6 +189,7 @@ Usage:
   --OU name             Specifies the Organization Unit for the cert (default: {}).
   --C name              Specifies the Country for the cert (default: {}).
   --ST name             "Specifies the State for the cert. (default: {})"#,
+            DEFAULT_KEY_TYPE,
             DEFAULT_KEY_SIZE,
             DEFAULT_PKI_PATH,
             DEFAULT_CERT_PATH,
@@ -168,6 +205,7 @@ Usage:
     }
 }
 
+const DEFAULT_KEY_TYPE: &str = "rsa";
 const DEFAULT_KEY_SIZE: u16 = 2048;
 const DEFAULT_PKI_PATH: &str = ".";
 const DEFAULT_DURATION: u32 = 365;
@@ -180,61 +218,76 @@ const DEFAULT_ST: &str = "Dublin";
 const DEFAULT_CERT_PATH: &str = "cert.der";
 const DEFAULT_PKEY_PATH: &str = "private.pem";
 
-fn parse_x509_args() -> Result<(X509Data, bool, PathBuf, PathBuf, PathBuf), ()> {
-    // Read command line arguments
-    let args = Args::parse_args().map_err(|_| Args::usage())?;
-    if args.help || ![2048u16, 4096u16].contains(&args.key_size) || args.duration == 0 {
-        Args::usage();
-        Err(())
-    } else {
-        let pki_path = args.pki_path;
-        let cert_path = args.cert_path;
-        let pkey_path = args.pkey_path;
-        let key_size = args.key_size as u32;
-        let overwrite = args.overwrite;
-        let certificate_duration_days = args.duration;
-
-        let common_name = args.common_name;
-        let organization = args.organization;
-        let organizational_unit = args.organizational_unit;
-        let country = args.country;
-        let state = args.state;
-        let application_uri = args.application_uri;
-        let add_localhost = args.add_localhost_name;
-        let add_computer_name = args.add_computer_name;
-        let add_ip_addresses = args.add_ip_addresses;
-
-        // Create alt host names for application uri, localhost and computer name if required
-        let hostnames: Vec<String> = args.hostnames.split(',').map(|s| s.to_string()).collect();
-        let alt_host_names = X509Data::alt_host_names(
-            &application_uri,
-            Some(hostnames),
-            add_localhost,
-            add_computer_name,
-            add_ip_addresses,
-        );
-
-        // Add the host names that were supplied by argument
-        if alt_host_names.len() == 1 {
-            eprintln!("No alt host names were supplied or could be inferred. Certificate is useless without at least one DNS entry.");
+fn parse_x509_args(args: Args) -> Result<(X509Data, bool, PathBuf, PathBuf, PathBuf), ()> {
+    let key_type = match args.key_type.as_str() {
+        "rsa" => Some(KeyType::Rsa),
+        "nistp256" => Some(KeyType::NistP256),
+        "nistp384" => Some(KeyType::NistP384),
+        "curve25519" => Some(KeyType::Curve25519),
+        "brainpoolp256r1" => Some(KeyType::BrainpoolP256r1),
+        "brainpoolp384r1" => Some(KeyType::BrainpoolP384r1),
+        _ => None,
+    };
+    let key_type = match key_type {
+        Some(key_type)
+            if !args.help && [2048u16, 4096u16].contains(&args.key_size) && args.duration != 0 =>
+        {
+            key_type
+        }
+        _ => {
+            Args::usage();
             return Err(());
         }
+    };
+
+    let pki_path = args.pki_path;
+    let cert_path = args.cert_path;
+    let pkey_path = args.pkey_path;
+    let key_size = args.key_size as u32;
+    let overwrite = args.overwrite;
+    let certificate_duration_days = args.duration;
 
-        Ok((
-            X509Data {
-                key_size,
-                common_name,
-                organization,
-                organizational_unit,
-                country,
-                state,
-                alt_host_names,
-                certificate_duration_days,
-            },
-            overwrite,
-            PathBuf::from(&pki_path),
-            PathBuf::from(&cert_path),
-            PathBuf::from(&pkey_path),
-        ))
+    let common_name = args.common_name;
+    let organization = args.organization;
+    let organizational_unit = args.organizational_unit;
+    let country = args.country;
+    let state = args.state;
+    let application_uri = args.application_uri;
+    let add_localhost = args.add_localhost_name;
+    let add_computer_name = args.add_computer_name;
+    let add_ip_addresses = args.add_ip_addresses;
+
+    // Create alt host names for application uri, localhost and computer name if required
+    let hostnames: Vec<String> = args.hostnames.split(',').map(|s| s.to_string()).collect();
+    let alt_host_names = X509Data::alt_host_names(
+        &application_uri,
+        Some(hostnames),
+        add_localhost,
+        add_computer_name,
+        add_ip_addresses,
+    );
+
+    // Add the host names that were supplied by argument
+    if alt_host_names.len() == 1 {
+        eprintln!("No alt host names were supplied or could be inferred. Certificate is useless without at least one DNS entry.");
+        return Err(());
     }
+
+    Ok((
+        X509Data {
+            key_type,
+            key_size,
+            common_name,
+            organization,
+            organizational_unit,
+            country,
+            state,
+            alt_host_names,
+            certificate_duration_days,
+        },
+        overwrite,
+        PathBuf::from(&pki_path),
+        PathBuf::from(&cert_path),
+        PathBuf::from(&pkey_path),
+    ))
 }